@@ -1,13 +1,14 @@
 //! Application server and client for managing MMR accumulator operations via async message passing.
 
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use bitcoin::block::Header as BlockHeader;
+use serde::{Deserialize, Serialize};
 use tokio::sync::{broadcast, mpsc, oneshot};
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 use raito_spv_core::{
-    block_mmr::{BlockInclusionProof, BlockMMR},
+    block_mmr::{BlockConsistencyProof, BlockInclusionProof, BlockMMR},
     sparse_roots::SparseRoots,
 };
 
@@ -28,10 +29,18 @@ pub enum ApiRequestBody {
     /// Get MMR sparse roots for a given chain height (optional)
     /// The chain height is the number of blocks in the MMR minus one
     GetSparseRoots(Option<u32>),
-    /// Add a new block header to the MMR
-    AddBlock(BlockHeader),
+    /// Get the MMR root hash for a given chain height (optional)
+    GetRootHash(Option<u32>),
+    /// Get the MMR peak hashes for a given chain height (optional)
+    GetPeaks(Option<u32>),
+    /// Add new leaves (block header digests) to the MMR
+    AddLeaves(Vec<String>),
+    /// Get the leaves of a number of consecutive blocks starting at a block height
+    GetLeaves((u32, u32)),
     /// Generate an inclusion proof for a block at the given height and chain height (optional)
     GenerateBlockProof((u32, Option<u32>)),
+    /// Generate a consistency proof between an earlier chain height and a chain height (optional)
+    GenerateConsistencyProof((u32, Option<u32>)),
 }
 
 /// Response body for API requests containing the result data
@@ -40,10 +49,48 @@ pub enum ApiResponseBody {
     GetBlockCount(u32),
     /// Response containing the sparse roots for a given block count
     GetSparseRoots(SparseRoots),
-    /// Response containing sparse roots after adding a block
-    AddBlock(SparseRoots),
+    /// Response containing the root hash for a given chain height
+    GetRootHash(String),
+    /// Response containing the peak hashes for a given chain height
+    GetPeaks(Vec<String>),
+    /// Response containing the sparse roots after adding each leaf
+    AddLeaves(Vec<SparseRoots>),
+    /// Response containing the leaves of consecutive blocks
+    GetLeaves(Vec<String>),
     /// Response containing the inclusion proof for a block
     GenerateBlockProof(BlockInclusionProof),
+    /// Response containing the consistency proof between two MMR states
+    GenerateConsistencyProof(BlockConsistencyProof),
+}
+
+/// Block inclusion proof with the MMR state it was generated against, so that clients can
+/// detect proofs from nodes with inconsistent states
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchoredBlockProof {
+    #[serde(flatten)]
+    pub proof: BlockInclusionProof,
+    /// Root hash of the MMR state the proof was generated against
+    pub mmr_root: String,
+    /// Time the proof was generated at (UNIX seconds)
+    pub generated_at: u64,
+    /// Version of the bridge node that generated the proof
+    pub bridge_version: String,
+}
+
+impl AnchoredBlockProof {
+    /// Anchor a proof just generated against the MMR state with root `mmr_root`
+    pub fn new(proof: BlockInclusionProof, mmr_root: String) -> Self {
+        let generated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        Self {
+            proof,
+            mmr_root,
+            generated_at,
+            bridge_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -98,16 +145,35 @@ impl AppServer {
                             let res = mmr.get_sparse_roots(chain_height).await.map(|sparse_roots| ApiResponseBody::GetSparseRoots(sparse_roots));
                             req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to GetSparseRoots request"))?;
                         }
+                        ApiRequestBody::GetRootHash(chain_height) => {
+                            let res = mmr.get_root_hash(chain_height).await.map(ApiResponseBody::GetRootHash);
+                            req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to GetRootHash request"))?;
+                        }
+                        ApiRequestBody::GetPeaks(chain_height) => {
+                            let res = mmr.get_peaks(chain_height).await.map(ApiResponseBody::GetPeaks);
+                            req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to GetPeaks request"))?;
+                        }
                         ApiRequestBody::GenerateBlockProof((block_height, chain_height)) => {
                             let res = mmr.generate_proof(block_height, chain_height).await.map(|proof| ApiResponseBody::GenerateBlockProof(proof));
                             req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to GenerateBlockProof request"))?;
                         }
-                        ApiRequestBody::AddBlock(block_header) => {
+                        ApiRequestBody::GenerateConsistencyProof((prev_chain_height, chain_height)) => {
+                            let res = mmr.generate_consistency_proof(prev_chain_height, chain_height).await.map(ApiResponseBody::GenerateConsistencyProof);
+                            req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to GenerateConsistencyProof request"))?;
+                        }
+                        ApiRequestBody::AddLeaves(leaves) => {
                             // This is a local-only method, so we treat errors differently here
-                            mmr.add_block_header(&block_header).await?;
-                            let sparse_roots = mmr.get_sparse_roots(None).await?;
-                            let res = Ok(ApiResponseBody::AddBlock(sparse_roots));
-                            req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to AddBlock request"))?;
+                            let sparse_roots = mmr.add_leaves(&leaves).await?;
+                            let res = Ok(ApiResponseBody::AddLeaves(sparse_roots));
+                            // The indexer pipeline may be stopping, the leaves are appended
+                            // anyway and their sparse roots written on restart
+                            if req.tx_response.send(res).is_err() {
+                                debug!("AddLeaves response dropped, the requester is gone");
+                            }
+                        }
+                        ApiRequestBody::GetLeaves((from_height, count)) => {
+                            let res = mmr.get_leaves(from_height, count).await.map(ApiResponseBody::GetLeaves);
+                            req.tx_response.send(res).map_err(|_| anyhow::anyhow!("Failed to send response to GetLeaves request"))?;
                         }
                     }
                 },
@@ -181,11 +247,48 @@ impl AppClient {
         .await
     }
 
-    pub async fn add_block(&self, block_header: BlockHeader) -> Result<SparseRoots, anyhow::Error> {
+    pub async fn get_root_hash(&self, chain_height: Option<u32>) -> Result<String, anyhow::Error> {
         self.send_request(
-            ApiRequestBody::AddBlock(block_header),
+            ApiRequestBody::GetRootHash(chain_height),
             |response| match response {
-                ApiResponseBody::AddBlock(sparse_roots) => Some(sparse_roots),
+                ApiResponseBody::GetRootHash(root_hash) => Some(root_hash),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    pub async fn get_peaks(&self, chain_height: Option<u32>) -> Result<Vec<String>, anyhow::Error> {
+        self.send_request(
+            ApiRequestBody::GetPeaks(chain_height),
+            |response| match response {
+                ApiResponseBody::GetPeaks(peaks) => Some(peaks),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    pub async fn add_leaves(&self, leaves: Vec<String>) -> Result<Vec<SparseRoots>, anyhow::Error> {
+        self.send_request(
+            ApiRequestBody::AddLeaves(leaves),
+            |response| match response {
+                ApiResponseBody::AddLeaves(sparse_roots) => Some(sparse_roots),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    pub async fn get_leaves(
+        &self,
+        from_height: u32,
+        count: u32,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        self.send_request(
+            ApiRequestBody::GetLeaves((from_height, count)),
+            |response| match response {
+                ApiResponseBody::GetLeaves(leaves) => Some(leaves),
                 _ => None,
             },
         )
@@ -206,6 +309,21 @@ impl AppClient {
         )
         .await
     }
+
+    pub async fn generate_consistency_proof(
+        &self,
+        prev_chain_height: u32,
+        chain_height: Option<u32>,
+    ) -> Result<BlockConsistencyProof, anyhow::Error> {
+        self.send_request(
+            ApiRequestBody::GenerateConsistencyProof((prev_chain_height, chain_height)),
+            |response| match response {
+                ApiResponseBody::GenerateConsistencyProof(proof) => Some(proof),
+                _ => None,
+            },
+        )
+        .await
+    }
 }
 
 /// Create app server and client
@@ -0,0 +1,153 @@
+//! Recovery of the sparse roots files of a range of heights after a partial disk failure: the
+//! files missing or not matching the digest recorded in their shard manifest are regenerated
+//! from the MMR, which is only read.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use raito_spv_core::block_mmr::{block_header_leaf, BlockMMR};
+use raito_spv_core::sparse_roots::SparseRoots;
+use tracing::{info, warn};
+
+use crate::file_sink::{file_digest, SparseRootsSink, SparseRootsSinkConfig};
+
+/// Number of heights between two progress logs
+const PROGRESS_INTERVAL: u32 = 10000;
+
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    /// Path to the database storing the MMR accumulator state
+    pub mmr_db_path: PathBuf,
+    /// Location of the sparse roots JSON files
+    pub sink_config: SparseRootsSinkConfig,
+    /// First height to backfill
+    pub from: u32,
+    /// Last height to backfill (latest indexed block if not provided)
+    pub to: Option<u32>,
+}
+
+/// Outcome of a backfill
+#[derive(Debug, Clone, Default)]
+pub struct BackfillReport {
+    /// Number of heights checked
+    pub checked: u32,
+    /// Heights whose file was missing
+    pub missing: Vec<u32>,
+    /// Heights whose file didn't match the digest recorded in the manifest, or wasn't recorded
+    /// and didn't match the MMR
+    pub corrupted: Vec<u32>,
+    /// Number of heights whose file was intact but not recorded in the manifest, e.g. written
+    /// before the manifests were introduced, and is now recorded
+    pub recorded: u32,
+}
+
+/// State of a sparse roots file
+#[derive(Debug, PartialEq, Eq)]
+enum FileState {
+    /// The file matches the digest recorded in the manifest
+    Intact,
+    /// The file doesn't exist
+    Missing,
+    /// The file doesn't match the digest recorded in the manifest
+    Corrupted,
+    /// The file is not recorded in the manifest, with its contents
+    Unrecorded(Vec<u8>),
+}
+
+/// Regenerate the missing or corrupted sparse roots files of the heights `from..=to`
+///
+/// A file is intact if its digest is the one recorded in its shard manifest. A file that is
+/// not recorded is compared to the roots of the MMR, and to its leaf if the file includes the
+/// block, and recorded if it matches. The regenerated files don't include the block.
+pub async fn run(config: BackfillConfig) -> Result<BackfillReport, anyhow::Error> {
+    let mmr = BlockMMR::from_file(&config.mmr_db_path, "blocks").await?;
+    let block_count = mmr.get_block_count().await?;
+    if block_count == 0 {
+        anyhow::bail!("MMR at {} is empty", config.mmr_db_path.display());
+    }
+    let to = config.to.unwrap_or(block_count - 1);
+    if to >= block_count {
+        anyhow::bail!(
+            "Height {} is not indexed yet, the MMR has {} blocks",
+            to,
+            block_count
+        );
+    }
+    if config.from > to {
+        anyhow::bail!("Empty range of heights {}..={}", config.from, to);
+    }
+
+    let mut sink = SparseRootsSink::new(config.sink_config.clone()).await?;
+    let mut report = BackfillReport::default();
+    // Manifest of the shard of the current height
+    let mut manifest: Option<(PathBuf, HashMap<u32, String>)> = None;
+
+    info!("Backfilling heights {}..={} ...", config.from, to);
+    for height in config.from..=to {
+        if height > config.from && (height - config.from).is_multiple_of(PROGRESS_INTERVAL) {
+            info!("Backfilled heights {}..{}", config.from, height);
+        }
+        let manifest_path = config.sink_config.manifest_path(height);
+        let digests = match &manifest {
+            Some((path, digests)) if *path == manifest_path => digests,
+            _ => {
+                let digests = config.sink_config.read_manifest(height).await?;
+                &manifest.insert((manifest_path, digests)).1
+            }
+        };
+
+        let path = config.sink_config.file_path(height);
+        let state = match (tokio::fs::read(&path).await, digests.get(&height)) {
+            (Ok(contents), Some(digest)) if *digest == file_digest(&contents) => FileState::Intact,
+            (Ok(_), Some(_)) => FileState::Corrupted,
+            (Ok(contents), None) => FileState::Unrecorded(contents),
+            (Err(err), _) if err.kind() == std::io::ErrorKind::NotFound => FileState::Missing,
+            (Err(err), _) => return Err(err.into()),
+        };
+        report.checked += 1;
+        if state == FileState::Intact {
+            continue;
+        }
+
+        let roots = mmr.get_sparse_roots(Some(height)).await?;
+        match state {
+            FileState::Unrecorded(contents)
+                if matches_mmr(&mmr, height, &roots, &contents).await? =>
+            {
+                sink.record(height, &file_digest(&contents)).await?;
+                report.recorded += 1;
+                continue;
+            }
+            FileState::Missing => {
+                warn!("Sparse roots file {} is missing", path.display());
+                report.missing.push(height);
+            }
+            _ => {
+                warn!("Sparse roots file {} is corrupted", path.display());
+                report.corrupted.push(height);
+            }
+        }
+        sink.write_sparse_roots(&roots).await?;
+    }
+    Ok(report)
+}
+
+/// Whether the contents of the sparse roots file of a height match the MMR: its roots, and the
+/// leaf of its block if included
+async fn matches_mmr(
+    mmr: &BlockMMR,
+    height: u32,
+    roots: &SparseRoots,
+    contents: &[u8],
+) -> Result<bool, anyhow::Error> {
+    let Ok(stored) = serde_json::from_slice::<SparseRoots>(contents) else {
+        return Ok(false);
+    };
+    if stored.roots != roots.roots {
+        return Ok(false);
+    }
+    match stored.block {
+        Some(block) => Ok(mmr.get_leaves(height, 1).await? == [block_header_leaf(&block.header)]),
+        None => Ok(true),
+    }
+}
@@ -0,0 +1,123 @@
+//! Reconstruction of the chain state as of an indexed height from the headers of the Bitcoin
+//! node, for provers resuming from a checkpoint and auditors validating chain state proofs, and
+//! of the difficulty history, to validate claimed total work without a full node.
+
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::BlockHash;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+use raito_spv_core::bitcoin::{BitcoinClient, BitcoinClientError};
+
+/// Number of blocks per difficulty adjustment epoch
+pub const BLOCKS_PER_EPOCH: u32 = 2016;
+
+/// Number of previous timestamps the median time past is computed over
+const MEDIAN_TIME_PAST_BLOCKS: u32 = 11;
+
+/// Chain state after a block, same format as the chain state of the chain state proofs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainState {
+    /// The height of the best block in the chain
+    pub block_height: u32,
+    /// The total accumulated work of the chain as a decimal string
+    pub total_work: String,
+    /// The hash of the best block in the chain
+    pub best_block_hash: BlockHash,
+    /// The current target as a decimal string
+    pub current_target: String,
+    /// The start time (UNIX seconds) of the current difficulty epoch
+    pub epoch_start_time: u32,
+    /// The timestamps (UNIX seconds) of the previous 11 blocks, the oldest first
+    pub prev_timestamps: Vec<u32>,
+}
+
+/// Target and work of a difficulty epoch, within a range of heights
+#[derive(Debug, Clone, Serialize)]
+pub struct EpochWork {
+    /// Index of the difficulty epoch (height / 2016)
+    pub epoch: u32,
+    /// Height of the first block of the epoch in the range
+    pub start_height: u32,
+    /// Height of the last block of the epoch in the range
+    pub end_height: u32,
+    /// Target of the epoch, set by its first block, as a decimal string
+    pub target: String,
+    /// Work of a block at the target of the epoch, as a decimal string
+    pub block_work: String,
+    /// Total accumulated work of the chain up to `end_height` (the node's chain work), as a
+    /// decimal string
+    pub cumulative_work: String,
+}
+
+/// Reconstruct the chain state after the block at `height`
+///
+/// The total work is the chain work reported by the node, the target is the one of the block
+/// (set by the first block of its epoch), and the timestamps are read from the header chain.
+pub async fn reconstruct_chain_state(
+    bitcoin_client: &BitcoinClient,
+    height: u32,
+) -> Result<ChainState, BitcoinClientError> {
+    let best_block_hash = bitcoin_client.get_block_hash(height).await?;
+    let header_ex = bitcoin_client.get_block_header_ex(&best_block_hash).await?;
+    let header = bitcoin_client.get_block_header(&best_block_hash).await?;
+
+    // Walk the header chain back for the median time past window
+    let mut prev_timestamps = vec![header.time];
+    let mut prev_blockhash = header.prev_blockhash;
+    for _ in 1..MEDIAN_TIME_PAST_BLOCKS.min(height + 1) {
+        let prev_header: BlockHeader = bitcoin_client.get_block_header(&prev_blockhash).await?;
+        prev_timestamps.push(prev_header.time);
+        prev_blockhash = prev_header.prev_blockhash;
+    }
+    prev_timestamps.reverse();
+
+    let epoch_start_height = height - height % BLOCKS_PER_EPOCH;
+    let epoch_start_time = if epoch_start_height == height {
+        header.time
+    } else {
+        let (epoch_start_header, _) = bitcoin_client
+            .get_block_header_by_height(epoch_start_height)
+            .await?;
+        epoch_start_header.time
+    };
+
+    Ok(ChainState {
+        block_height: height,
+        total_work: BigUint::from_bytes_be(&header_ex.chainwork).to_string(),
+        best_block_hash,
+        current_target: BigUint::from_bytes_be(&header.target().to_be_bytes()).to_string(),
+        epoch_start_time,
+        prev_timestamps,
+    })
+}
+
+/// Compute the target and cumulative work of the difficulty epochs between the blocks at
+/// `from` and `to` (included)
+pub async fn epoch_work_table(
+    bitcoin_client: &BitcoinClient,
+    from: u32,
+    to: u32,
+) -> Result<Vec<EpochWork>, BitcoinClientError> {
+    let mut table = Vec::new();
+    for epoch in from / BLOCKS_PER_EPOCH..=to / BLOCKS_PER_EPOCH {
+        let start_height = from.max(epoch * BLOCKS_PER_EPOCH);
+        let end_height = to.min(epoch * BLOCKS_PER_EPOCH + BLOCKS_PER_EPOCH - 1);
+        let (epoch_start_header, _) = bitcoin_client
+            .get_block_header_by_height(epoch * BLOCKS_PER_EPOCH)
+            .await?;
+        let end_hash = bitcoin_client.get_block_hash(end_height).await?;
+        let end_header_ex = bitcoin_client.get_block_header_ex(&end_hash).await?;
+
+        let target = epoch_start_header.target();
+        table.push(EpochWork {
+            epoch,
+            start_height,
+            end_height,
+            target: BigUint::from_bytes_be(&target.to_be_bytes()).to_string(),
+            block_work: BigUint::from_bytes_be(&target.to_work().to_be_bytes()).to_string(),
+            cumulative_work: BigUint::from_bytes_be(&end_header_ex.chainwork).to_string(),
+        });
+    }
+    Ok(table)
+}
@@ -0,0 +1,131 @@
+//! Audit of the bridge MMR against a Bitcoin node: the headers of a range of heights are
+//! fetched again, and their digests checked against the MMR leaves, the MMR roots and the
+//! sparse roots files written by the indexer.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use raito_spv_core::{
+    bitcoin::BitcoinClient,
+    block_mmr::{BlockMMR, BlockPeaks},
+    sparse_roots::SparseRoots,
+};
+use tracing::info;
+
+use crate::file_sink::SparseRootsSinkConfig;
+
+/// Number of heights between two progress logs
+const PROGRESS_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct CrossCheckConfig {
+    /// Path to the database storing the MMR accumulator state
+    pub mmr_db_path: PathBuf,
+    /// Location of the sparse roots JSON files
+    pub sink_config: SparseRootsSinkConfig,
+    /// Bitcoin RPC URL
+    pub rpc_url: String,
+    /// Bitcoin RPC user:password (optional)
+    pub rpc_userpwd: Option<String>,
+    /// First height to check
+    pub from: u32,
+    /// Last height to check (latest indexed block if not provided)
+    pub to: Option<u32>,
+}
+
+/// What differs at the first divergent height
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The MMR leaf is not the digest of the header reported by the Bitcoin node
+    Leaf,
+    /// The MMR roots differ from the roots recomputed from the Bitcoin node headers
+    Roots,
+    /// The sparse roots file is missing
+    MissingRootsFile(PathBuf),
+    /// The sparse roots file differs from the roots recomputed from the Bitcoin node headers
+    RootsFile(PathBuf),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Leaf => write!(f, "MMR leaf is not the digest of the block header"),
+            Self::Roots => write!(f, "MMR roots differ from the recomputed roots"),
+            Self::MissingRootsFile(path) => {
+                write!(f, "sparse roots file {} is missing", path.display())
+            }
+            Self::RootsFile(path) => write!(
+                f,
+                "sparse roots file {} differs from the recomputed roots",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Check the heights `from..=to` of the MMR against the Bitcoin node, and return the first
+/// divergent height if any.
+///
+/// The MMR state before `from` is trusted: the roots are recomputed by appending the digests
+/// of the fetched headers to the MMR peaks at `from - 1`.
+pub async fn run(config: CrossCheckConfig) -> Result<Option<(u32, Divergence)>, anyhow::Error> {
+    let mmr = BlockMMR::from_file(&config.mmr_db_path, "blocks").await?;
+    let block_count = mmr.get_block_count().await?;
+    if block_count == 0 {
+        anyhow::bail!("MMR at {} is empty", config.mmr_db_path.display());
+    }
+    let to = config.to.unwrap_or(block_count - 1);
+    if to >= block_count {
+        anyhow::bail!(
+            "Height {} is not indexed yet, the MMR has {} blocks",
+            to,
+            block_count
+        );
+    }
+    if config.from > to {
+        anyhow::bail!("Empty range of heights {}..={}", config.from, to);
+    }
+
+    let client = BitcoinClient::new(config.rpc_url, config.rpc_userpwd)?;
+    let mut replay = match config.from {
+        0 => BlockPeaks::default(),
+        from => BlockPeaks::new(&mmr.get_peaks(Some(from - 1)).await?, from as usize)?,
+    };
+
+    info!("Cross-checking heights {}..={} ...", config.from, to);
+    for height in config.from..=to {
+        let (header, hash) = client.get_block_header_by_height(height).await?;
+
+        let proof = mmr.generate_proof(height, None).await?;
+        if !BlockPeaks::from_proof(&proof)?.verify_block_inclusion(&header, &proof)? {
+            return Ok(Some((height, Divergence::Leaf)));
+        }
+
+        replay.add_block_header(&header);
+        let expected = replay.sparse_roots()?;
+        if mmr.get_sparse_roots(Some(height)).await?.roots != expected.roots {
+            return Ok(Some((height, Divergence::Roots)));
+        }
+
+        let path = config.sink_config.file_path(height);
+        let stored = match tokio::fs::read(&path).await {
+            Ok(contents) => serde_json::from_slice::<SparseRoots>(&contents).ok(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Some((height, Divergence::MissingRootsFile(path))));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        // The block is optional in the file, but must be the one of the node if included
+        let matches = stored.is_some_and(|stored| {
+            stored.roots == expected.roots && stored.block.is_none_or(|block| block.hash == hash)
+        });
+        if !matches {
+            return Ok(Some((height, Divergence::RootsFile(path))));
+        }
+
+        if (height - config.from + 1).is_multiple_of(PROGRESS_INTERVAL) {
+            info!("Checked heights {}..={}", config.from, height);
+        }
+    }
+    Ok(None)
+}
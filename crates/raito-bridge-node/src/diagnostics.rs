@@ -0,0 +1,99 @@
+//! Opt-in runtime diagnostics: CPU profiling endpoints, to diagnose slow proof generation in
+//! production. The tokio-console layer is installed along with the logging.
+
+use std::time::Duration;
+
+use axum::{
+    extract::Query,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use pprof::protos::Message;
+use serde::Deserialize;
+use tracing::{error, info};
+
+/// Default duration of a CPU profile in seconds
+const DEFAULT_PROFILE_SECONDS: u64 = 30;
+
+/// Maximum duration of a CPU profile in seconds
+const MAX_PROFILE_SECONDS: u64 = 300;
+
+/// Sampling frequency of the CPU profiler in Hz
+const PROFILE_FREQUENCY: i32 = 99;
+
+/// Query parameters for CPU profiling
+#[derive(Debug, Deserialize)]
+pub struct ProfileQuery {
+    pub seconds: Option<u64>,
+    pub format: Option<ProfileFormat>,
+}
+
+/// Output format of a CPU profile
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    /// SVG flame graph
+    #[default]
+    Flamegraph,
+    /// Protobuf profile, for `go tool pprof`
+    Pprof,
+}
+
+/// Routes of the CPU profiling endpoints
+pub fn pprof_router() -> Router {
+    Router::new().route("/debug/pprof/profile", get(get_cpu_profile))
+}
+
+/// Record a CPU profile of the node
+///
+/// # Arguments
+/// * `seconds` - The duration of the profile, up to 300 (optional, defaults to 30)
+/// * `format` - `flamegraph` (SVG) or `pprof` (protobuf) (optional, defaults to `flamegraph`)
+///
+/// # Returns
+/// * The profile in the requested format
+/// * `StatusCode::BAD_REQUEST` - If the duration is zero or above the limit
+/// * `StatusCode::CONFLICT` - If another profile is being recorded
+/// * `StatusCode::INTERNAL_SERVER_ERROR` - If profiling fails
+pub async fn get_cpu_profile(Query(query): Query<ProfileQuery>) -> Result<Response, StatusCode> {
+    let seconds = query.seconds.unwrap_or(DEFAULT_PROFILE_SECONDS);
+    if seconds == 0 || seconds > MAX_PROFILE_SECONDS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(PROFILE_FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|err| match err {
+            pprof::Error::Running => StatusCode::CONFLICT,
+            err => {
+                error!("Failed to start the CPU profiler: {}", err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    info!("Recording a CPU profile for {}s", seconds);
+    tokio::time::sleep(Duration::from_secs(seconds)).await;
+
+    let internal_error = |err: &dyn std::fmt::Display| {
+        error!("Failed to build the CPU profile: {}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let report = guard.report().build().map_err(|e| internal_error(&e))?;
+    match query.format.unwrap_or_default() {
+        ProfileFormat::Flamegraph => {
+            let mut svg = Vec::new();
+            report
+                .flamegraph(&mut svg)
+                .map_err(|e| internal_error(&e))?;
+            Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response())
+        }
+        ProfileFormat::Pprof => {
+            let profile = report.pprof().map_err(|e| internal_error(&e))?;
+            let mut body = Vec::new();
+            profile.encode(&mut body).map_err(|e| internal_error(&e))?;
+            Ok(([(header::CONTENT_TYPE, "application/octet-stream")], body).into_response())
+        }
+    }
+}
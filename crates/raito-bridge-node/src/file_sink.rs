@@ -1,11 +1,21 @@
 //! File sink for sparse roots MMR peaks compatible with Cairo implementation.
+//!
+//! Each shard directory has a manifest, `manifest.txt`, to which a `<height> <sha256>` line is
+//! appended for every written file, so that missing or corrupted files can be detected.
 
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use bitcoin::hashes::{sha256, Hash};
 use raito_spv_core::sparse_roots::SparseRoots;
 use serde_json;
-use std::path::PathBuf;
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use tracing::{debug, info};
 
+/// File name of the shard manifests
+const MANIFEST_FILE_NAME: &str = "manifest.txt";
+
 /// Configuration for the sparse roots sink
 #[derive(Debug, Clone)]
 pub struct SparseRootsSinkConfig {
@@ -15,6 +25,59 @@ pub struct SparseRootsSinkConfig {
     pub shard_size: u32,
 }
 
+impl SparseRootsSinkConfig {
+    /// Path of the sparse roots JSON file of a given block height:
+    /// `<output_dir>/<shard end>/block_<height>.json`
+    pub fn file_path(&self, block_height: u32) -> PathBuf {
+        self.shard_dir(block_height)
+            .join(format!("block_{block_height}.json"))
+    }
+
+    /// Path of the manifest of the shard of a given block height:
+    /// `<output_dir>/<shard end>/manifest.txt`
+    pub fn manifest_path(&self, block_height: u32) -> PathBuf {
+        self.shard_dir(block_height).join(MANIFEST_FILE_NAME)
+    }
+
+    /// Directory of the shard of a given block height
+    fn shard_dir(&self, block_height: u32) -> PathBuf {
+        let shard_id = block_height / self.shard_size;
+        let shard_end = (shard_id + 1) * self.shard_size;
+        self.output_dir.join(format!("{shard_end}"))
+    }
+
+    /// Read the digests recorded in the manifest of the shard of a given block height, by
+    /// height; the last line of a height wins. Empty if the manifest doesn't exist.
+    pub async fn read_manifest(
+        &self,
+        block_height: u32,
+    ) -> Result<HashMap<u32, String>, anyhow::Error> {
+        let contents = match fs::read_to_string(self.manifest_path(block_height)).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(err.into()),
+        };
+        // A line truncated by a crash is skipped, its file is then considered unrecorded
+        Ok(contents
+            .lines()
+            .filter_map(|line| {
+                let (height, digest) = line.split_once(' ')?;
+                Some((height.parse().ok()?, digest.to_string()))
+            })
+            .collect())
+    }
+}
+
+/// Hex SHA-256 digest of the contents of a sparse roots file, as recorded in the manifests
+pub fn file_digest(contents: &[u8]) -> String {
+    hex::encode(sha256::Hash::hash(contents).to_byte_array())
+}
+
+/// Contents of the sparse roots file of `sparse_roots`
+fn file_contents(sparse_roots: &SparseRoots) -> Result<String, anyhow::Error> {
+    Ok(serde_json::to_string_pretty(sparse_roots)?)
+}
+
 /// Sink for writing sparse roots to a JSON file
 pub struct SparseRootsSink {
     config: SparseRootsSinkConfig,
@@ -34,28 +97,12 @@ impl SparseRootsSink {
         Ok(Self { config })
     }
 
-    /// Calculate the shard directory path for a given block height
-    fn get_shard_dir(&self, block_height: u32) -> PathBuf {
-        let shard_id = block_height / self.config.shard_size;
-        let shard_start = shard_id * self.config.shard_size;
-        let shard_end = shard_start + self.config.shard_size;
-        let shard_dir_name = format!("{shard_end}");
-        self.config.output_dir.join(shard_dir_name)
-    }
-
-    /// Get the file path for a specific block height
-    fn get_file_path(&self, block_height: u32) -> PathBuf {
-        let shard_dir = self.get_shard_dir(block_height);
-        let filename = format!("block_{block_height}.json");
-        shard_dir.join(filename)
-    }
-
     /// Write sparse roots to a JSON file
     pub async fn write_sparse_roots(
         &mut self,
         sparse_roots: &SparseRoots,
     ) -> Result<(), anyhow::Error> {
-        let file_path = self.get_file_path(sparse_roots.block_height);
+        let file_path = self.config.file_path(sparse_roots.block_height);
 
         // Create the shard directory if it doesn't exist
         if let Some(parent) = file_path.parent() {
@@ -63,16 +110,34 @@ impl SparseRootsSink {
         }
 
         // Serialize the sparse roots to JSON
-        let json_content = serde_json::to_string_pretty(sparse_roots)?;
+        let json_content = file_contents(sparse_roots)?;
 
         // Write to file
-        fs::write(&file_path, json_content).await?;
+        fs::write(&file_path, &json_content).await?;
 
         debug!(
             "Sparse roots for block {} written to {:?}",
             sparse_roots.block_height, file_path
         );
 
+        // Recorded once written, so that a file interrupted by a crash is not in the manifest
+        self.record(
+            sparse_roots.block_height,
+            &file_digest(json_content.as_bytes()),
+        )
+        .await
+    }
+
+    /// Record the digest of the sparse roots file of a block height in the shard manifest
+    pub async fn record(&mut self, block_height: u32, digest: &str) -> Result<(), anyhow::Error> {
+        let mut manifest = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.config.manifest_path(block_height))
+            .await?;
+        manifest
+            .write_all(format!("{} {}\n", block_height, digest).as_bytes())
+            .await?;
         Ok(())
     }
 }
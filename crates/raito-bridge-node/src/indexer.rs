@@ -1,15 +1,45 @@
 //! Bitcoin blockchain indexer that builds MMR accumulator and generates sparse roots for new blocks.
+//!
+//! Each block goes through a pipeline of stages running in their own tasks: fetch → validate →
+//! digest → append → emit roots → notify, so that a failing stage (e.g. the Bitcoin node or
+//! the disk being unavailable) is restarted on its own. The blocks waiting for the digest and
+//! append stages, e.g. during the initial sync, are processed as batches, their leaves and MMR
+//! nodes being hashed at once.
 
-use tokio::sync::broadcast;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::hashes::Hash;
+use bitcoin::BlockHash;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
 use tracing::{error, info};
 
-use raito_spv_core::bitcoin::BitcoinClient;
+use raito_spv_core::bitcoin::{BitcoinClient, PollSchedule};
+use raito_spv_core::block_mmr::block_header_leaves;
+use raito_spv_core::sparse_roots::SparseRoots;
 
 use crate::{
     app::AppClient,
     file_sink::{SparseRootsSink, SparseRootsSinkConfig},
+    pipeline::{run_batch_stage, run_source, run_stage, PipelineMetrics, Stage, StageError},
 };
 
+/// Capacity of the channels between the pipeline stages
+const STAGE_CHANNEL_CAPACITY: usize = 64;
+
+/// Maximum number of blocks digested or appended to the MMR at once
+const MAX_STAGE_BATCH: usize = STAGE_CHANNEL_CAPACITY;
+
+/// Maximum number of blocks appended to the MMR whose sparse roots files are not written yet:
+/// those waiting for the emit stage, the rest of the batch being sent to it, and a batch
+/// appended before the append stage failed
+const MAX_UNEMITTED_BLOCKS: u32 = (STAGE_CHANNEL_CAPACITY + 2 * MAX_STAGE_BATCH) as u32 + 2;
+
 /// Bitcoin block indexer that builds MMR accumulator and generates sparse roots
 pub struct Indexer {
     /// Indexer configuration
@@ -18,18 +48,406 @@ pub struct Indexer {
     app_client: AppClient,
     /// Shutdown signal receiver
     rx_shutdown: broadcast::Receiver<()>,
+    /// Metrics of the pipeline stages
+    metrics: PipelineMetrics,
 }
 
 #[derive(Debug, Clone)]
 pub struct IndexerConfig {
-    /// Bitcoin RPC URL
-    pub rpc_url: String,
+    /// Where to get the block headers from
+    pub headers_source: HeadersSource,
+    /// Delay before each header replayed from a file
+    pub replay_delay: Duration,
+    /// Bitcoin RPC URL (required for the RPC headers source)
+    pub rpc_url: Option<String>,
     /// Bitcoin RPC user:password (optional)
     pub rpc_userpwd: Option<String>,
     /// Indexing lag in blocks
     pub indexing_lag: u32,
+    /// Schedule of the block count polling at the tip
+    pub poll_schedule: PollSchedule,
     /// Output directory for sparse roots JSON files
     pub sink_config: SparseRootsSinkConfig,
+    /// Include the block hash and header in the sparse roots JSON files
+    pub roots_with_block: bool,
+}
+
+/// Where the indexer gets the block headers from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeadersSource {
+    /// Bitcoin node RPC
+    Rpc,
+    /// File of recorded headers, replayed deterministically: one hex-encoded header (consensus
+    /// encoding) per line starting at the genesis block, empty lines and `#` comments ignored
+    File(PathBuf),
+}
+
+impl FromStr for HeadersSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "rpc" => Ok(Self::Rpc),
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+            _ => Err(format!(
+                "invalid headers source '{}', expected 'rpc' or 'file:<path>'",
+                s
+            )),
+        }
+    }
+}
+
+/// Stream of block headers to index
+enum Headers {
+    /// Headers fetched from a Bitcoin node, waiting for new blocks at the tip
+    Rpc(Box<BitcoinClient>),
+    /// Headers replayed from a file, ending at the end of the file
+    File {
+        lines: Box<Lines<BufReader<File>>>,
+        delay: Duration,
+    },
+}
+
+impl Headers {
+    /// Open the headers source of `config` for indexing from `start_height`
+    async fn open(config: &IndexerConfig, start_height: u32) -> Result<Self, anyhow::Error> {
+        match &config.headers_source {
+            HeadersSource::Rpc => {
+                let rpc_url = config.rpc_url.clone().ok_or_else(|| {
+                    anyhow::anyhow!("Bitcoin RPC URL is required to index headers from RPC")
+                })?;
+                if config.poll_schedule.min_interval > config.poll_schedule.max_interval {
+                    anyhow::bail!("Minimum polling interval is above the maximum interval");
+                }
+                let bitcoin_client = BitcoinClient::new(rpc_url, config.rpc_userpwd.clone())?
+                    .with_poll_schedule(config.poll_schedule);
+                info!("Bitcoin RPC client initialized");
+                Ok(Self::Rpc(Box::new(bitcoin_client)))
+            }
+            HeadersSource::File(path) => {
+                let file = File::open(path).await.map_err(|e| {
+                    anyhow::anyhow!("Failed to open headers file {}: {}", path.display(), e)
+                })?;
+                let mut headers = Self::File {
+                    lines: Box::new(BufReader::new(file).lines()),
+                    delay: config.replay_delay,
+                };
+                // Skip the headers already in the MMR
+                for height in 0..start_height {
+                    if headers.next_recorded_header().await?.is_none() {
+                        anyhow::bail!(
+                            "Headers file {} ends at height {}, the MMR has {} blocks",
+                            path.display(),
+                            height,
+                            start_height
+                        );
+                    }
+                }
+                info!("Replaying headers from {}", path.display());
+                Ok(headers)
+            }
+        }
+    }
+
+    /// Header at `height`, `None` once a recorded stream is exhausted
+    async fn next(
+        &mut self,
+        height: u32,
+        lag: u32,
+    ) -> Result<Option<(BlockHeader, BlockHash)>, anyhow::Error> {
+        match self {
+            Self::Rpc(bitcoin_client) => {
+                Ok(Some(bitcoin_client.wait_block_header(height, lag).await?))
+            }
+            Self::File { delay, .. } => {
+                if !delay.is_zero() {
+                    tokio::time::sleep(*delay).await;
+                }
+                let header = self.next_recorded_header().await?;
+                Ok(header.map(|header| (header, header.block_hash())))
+            }
+        }
+    }
+
+    /// Next header of the file, skipping empty lines and comments
+    async fn next_recorded_header(&mut self) -> Result<Option<BlockHeader>, anyhow::Error> {
+        let Self::File { lines, .. } = self else {
+            unreachable!("only files have recorded headers")
+        };
+        while let Some(line) = lines.next_line().await? {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bytes = hex::decode(line)?;
+            return Ok(Some(bitcoin::consensus::deserialize(&bytes)?));
+        }
+        Ok(None)
+    }
+}
+
+/// Block header fetched from the headers source
+#[derive(Debug, Clone)]
+struct FetchedBlock {
+    height: u32,
+    header: BlockHeader,
+    hash: BlockHash,
+}
+
+/// Block with its MMR leaf
+#[derive(Debug, Clone)]
+struct DigestedBlock {
+    height: u32,
+    header: BlockHeader,
+    hash: BlockHash,
+    leaf: String,
+}
+
+/// Block appended to the MMR, with the resulting sparse roots
+#[derive(Debug, Clone)]
+struct AppendedBlock {
+    height: u32,
+    header: BlockHeader,
+    hash: BlockHash,
+    roots: SparseRoots,
+}
+
+/// Fetch the headers from the headers source, reopened on restart
+struct FetchStage {
+    config: IndexerConfig,
+    headers: Headers,
+    next_height: u32,
+}
+
+impl Stage for FetchStage {
+    type Input = ();
+    type Output = FetchedBlock;
+    const NAME: &'static str = "fetch";
+
+    async fn process(&mut self, _: ()) -> Result<Option<FetchedBlock>, anyhow::Error> {
+        let Some((header, hash)) = self
+            .headers
+            .next(self.next_height, self.config.indexing_lag)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let block = FetchedBlock {
+            height: self.next_height,
+            header,
+            hash,
+        };
+        self.next_height += 1;
+        Ok(Some(block))
+    }
+
+    async fn restart(&mut self) -> Result<(), anyhow::Error> {
+        self.headers = Headers::open(&self.config, self.next_height).await?;
+        Ok(())
+    }
+
+    fn height(&self, _: &()) -> Option<u32> {
+        Some(self.next_height)
+    }
+}
+
+/// Check that the headers form a chain, which a reorg deeper than the indexing lag breaks
+///
+/// The link to the block before the first one indexed since the node started is not checked,
+/// except for the genesis block.
+struct ValidateStage {
+    prev_hash: Option<BlockHash>,
+}
+
+impl Stage for ValidateStage {
+    type Input = FetchedBlock;
+    type Output = FetchedBlock;
+    const NAME: &'static str = "validate";
+
+    async fn process(
+        &mut self,
+        block: FetchedBlock,
+    ) -> Result<Option<FetchedBlock>, anyhow::Error> {
+        if block.header.block_hash() != block.hash {
+            anyhow::bail!(
+                "Header of block #{} doesn't hash to {}",
+                block.height,
+                block.hash
+            );
+        }
+        if let Some(prev_hash) = self.prev_hash {
+            if block.header.prev_blockhash != prev_hash {
+                anyhow::bail!(
+                    "Block #{} {} doesn't extend the indexed block {}, reorg deeper than the \
+                     indexing lag?",
+                    block.height,
+                    block.hash,
+                    prev_hash
+                );
+            }
+        }
+        self.prev_hash = Some(block.hash);
+        Ok(Some(block))
+    }
+
+    fn is_transient(&self, _err: &anyhow::Error) -> bool {
+        false
+    }
+
+    fn height(&self, block: &FetchedBlock) -> Option<u32> {
+        Some(block.height)
+    }
+}
+
+/// Compute the MMR leaves of batches of headers
+struct DigestStage;
+
+impl Stage for DigestStage {
+    type Input = Vec<FetchedBlock>;
+    type Output = Vec<DigestedBlock>;
+    const NAME: &'static str = "digest";
+
+    async fn process(
+        &mut self,
+        blocks: Vec<FetchedBlock>,
+    ) -> Result<Option<Vec<DigestedBlock>>, anyhow::Error> {
+        let headers: Vec<BlockHeader> = blocks.iter().map(|block| block.header).collect();
+        let leaves = block_header_leaves(&headers);
+        Ok(Some(
+            blocks
+                .into_iter()
+                .zip(leaves)
+                .map(|(block, leaf)| DigestedBlock {
+                    height: block.height,
+                    header: block.header,
+                    hash: block.hash,
+                    leaf,
+                })
+                .collect(),
+        ))
+    }
+
+    fn height(&self, blocks: &Vec<FetchedBlock>) -> Option<u32> {
+        blocks.first().map(|block| block.height)
+    }
+}
+
+/// Append batches of leaves to the MMR
+struct AppendStage {
+    app_client: AppClient,
+    /// Number of blocks in the MMR, read again on restart as a failed append may have succeeded
+    block_count: u32,
+}
+
+impl Stage for AppendStage {
+    type Input = Vec<DigestedBlock>;
+    type Output = Vec<AppendedBlock>;
+    const NAME: &'static str = "append";
+
+    async fn process(
+        &mut self,
+        blocks: Vec<DigestedBlock>,
+    ) -> Result<Option<Vec<AppendedBlock>>, anyhow::Error> {
+        // Blocks already appended before a restart
+        let mut roots = Vec::with_capacity(blocks.len());
+        for block in blocks
+            .iter()
+            .take_while(|block| block.height < self.block_count)
+        {
+            roots.push(self.app_client.get_sparse_roots(Some(block.height)).await?);
+        }
+
+        let new_blocks = &blocks[roots.len()..];
+        if let Some(block) = new_blocks.first() {
+            if block.height != self.block_count {
+                anyhow::bail!(
+                    "Block #{} is not next to the {} blocks of the MMR",
+                    block.height,
+                    self.block_count
+                );
+            }
+            let leaves = new_blocks.iter().map(|block| block.leaf.clone()).collect();
+            roots.extend(self.app_client.add_leaves(leaves).await?);
+            self.block_count += new_blocks.len() as u32;
+        }
+
+        Ok(Some(
+            blocks
+                .into_iter()
+                .zip(roots)
+                .map(|(block, roots)| AppendedBlock {
+                    height: block.height,
+                    header: block.header,
+                    hash: block.hash,
+                    roots,
+                })
+                .collect(),
+        ))
+    }
+
+    async fn restart(&mut self) -> Result<(), anyhow::Error> {
+        self.block_count = self.app_client.get_block_count().await?;
+        Ok(())
+    }
+
+    fn height(&self, blocks: &Vec<DigestedBlock>) -> Option<u32> {
+        blocks.first().map(|block| block.height)
+    }
+}
+
+/// Write the sparse roots files, the sink is reopened on restart
+struct EmitStage {
+    sink_config: SparseRootsSinkConfig,
+    sink: SparseRootsSink,
+    /// Include the block in the files
+    with_block: bool,
+}
+
+impl Stage for EmitStage {
+    type Input = AppendedBlock;
+    type Output = AppendedBlock;
+    const NAME: &'static str = "emit";
+
+    async fn process(
+        &mut self,
+        mut block: AppendedBlock,
+    ) -> Result<Option<AppendedBlock>, anyhow::Error> {
+        if self.with_block {
+            block.roots = block.roots.with_block(block.header);
+        }
+        self.sink.write_sparse_roots(&block.roots).await?;
+        Ok(Some(block))
+    }
+
+    async fn restart(&mut self) -> Result<(), anyhow::Error> {
+        self.sink = SparseRootsSink::new(self.sink_config.clone()).await?;
+        Ok(())
+    }
+
+    fn height(&self, block: &AppendedBlock) -> Option<u32> {
+        Some(block.height)
+    }
+}
+
+/// Report the indexed blocks
+struct NotifyStage {
+    metrics: PipelineMetrics,
+}
+
+impl Stage for NotifyStage {
+    type Input = AppendedBlock;
+    type Output = ();
+    const NAME: &'static str = "notify";
+
+    async fn process(&mut self, block: AppendedBlock) -> Result<Option<()>, anyhow::Error> {
+        info!("Block #{} {} processed", block.height, block.hash);
+        self.metrics.set_block_count(block.height + 1);
+        Ok(Some(()))
+    }
+
+    fn height(&self, block: &AppendedBlock) -> Option<u32> {
+        Some(block.height)
+    }
 }
 
 impl Indexer {
@@ -37,54 +455,146 @@ impl Indexer {
         config: IndexerConfig,
         app_client: AppClient,
         rx_shutdown: broadcast::Receiver<()>,
+        metrics: PipelineMetrics,
     ) -> Self {
         Self {
             config,
             app_client,
             rx_shutdown,
+            metrics,
         }
     }
 
     async fn run_inner(&mut self) -> Result<(), anyhow::Error> {
         info!("Block indexer started");
 
-        let mut bitcoin_client =
-            BitcoinClient::new(self.config.rpc_url.clone(), self.config.rpc_userpwd.clone())?;
-        info!("Bitcoin RPC client initialized");
+        let start_height = self.app_client.get_block_count().await?;
+        info!("Current MMR blocks count: {}", start_height);
+        self.metrics.set_block_count(start_height);
 
-        let mut next_block_height = self.app_client.get_block_count().await?;
-        info!("Current MMR blocks count: {}", next_block_height);
+        let headers = Headers::open(&self.config, start_height).await?;
+        let start = Instant::now();
 
         // Initialize the sparse roots sink
         let mut sink = SparseRootsSink::new(self.config.sink_config.clone()).await?;
+        self.emit_missing_roots(start_height, &mut sink).await?;
+
+        let (tx_fetched, rx_fetched) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+        let (tx_validated, rx_validated) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+        let (tx_digested, rx_digested) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+        let (tx_appended, rx_appended) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+        let (tx_emitted, rx_emitted) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+        let metrics = &self.metrics;
 
+        // The stages are aborted when the set is dropped
+        let mut stages = JoinSet::new();
+        let fetch = FetchStage {
+            config: self.config.clone(),
+            headers,
+            next_height: start_height,
+        };
+        stages.spawn(run_source(
+            fetch,
+            tx_fetched,
+            metrics.stage(FetchStage::NAME),
+        ));
+        let validate = ValidateStage {
+            prev_hash: (start_height == 0).then(BlockHash::all_zeros),
+        };
+        stages.spawn(run_stage(
+            validate,
+            rx_fetched,
+            Some(tx_validated),
+            metrics.stage(ValidateStage::NAME),
+        ));
+        stages.spawn(run_batch_stage(
+            DigestStage,
+            rx_validated,
+            Some(tx_digested),
+            MAX_STAGE_BATCH,
+            metrics.stage(DigestStage::NAME),
+        ));
+        let append = AppendStage {
+            app_client: self.app_client.clone(),
+            block_count: start_height,
+        };
+        stages.spawn(run_batch_stage(
+            append,
+            rx_digested,
+            Some(tx_appended),
+            MAX_STAGE_BATCH,
+            metrics.stage(AppendStage::NAME),
+        ));
+        let emit = EmitStage {
+            sink_config: self.config.sink_config.clone(),
+            sink,
+            with_block: self.config.roots_with_block,
+        };
+        stages.spawn(run_stage(
+            emit,
+            rx_appended,
+            Some(tx_emitted),
+            metrics.stage(EmitStage::NAME),
+        ));
+        let notify = NotifyStage {
+            metrics: metrics.clone(),
+        };
+        stages.spawn(run_stage(
+            notify,
+            rx_emitted,
+            None,
+            metrics.stage(NotifyStage::NAME),
+        ));
+
+        // The stages end one after the other once the headers are exhausted
         loop {
             tokio::select! {
-                res = bitcoin_client.wait_block_header(next_block_height, self.config.indexing_lag) => {
-                    match res {
-                        Ok((block_header, block_hash)) => {
-                            // Add new block to the MMR accumulator and get resulting sparse roots
-                            let roots = self.app_client.add_block(block_header).await?;
-                            sink.write_sparse_roots(&roots).await?;
-                            info!("Block #{} {} processed", next_block_height, block_hash);
-                            next_block_height += 1;
-                        },
-                        Err(e) => {
-                            return Err(e.into())
-                        }
-                    }
+                res = stages.join_next() => match res {
+                    Some(res) => res??,
+                    None => break,
                 },
                 _ = self.rx_shutdown.recv() => {
                     return Ok(())
                 }
             }
         }
+
+        let elapsed = start.elapsed().as_secs_f64();
+        let count = self.metrics.block_count() - start_height;
+        info!(
+            "Replay finished: {} blocks in {:.1}s ({:.0} blocks/s)",
+            count,
+            elapsed,
+            count as f64 / elapsed.max(f64::EPSILON)
+        );
+        Ok(())
+    }
+
+    /// Write the sparse roots files of the last blocks of the MMR if missing, the blocks still
+    /// in the pipeline when the node stopped
+    async fn emit_missing_roots(
+        &self,
+        block_count: u32,
+        sink: &mut SparseRootsSink,
+    ) -> Result<(), anyhow::Error> {
+        for height in block_count.saturating_sub(MAX_UNEMITTED_BLOCKS)..block_count {
+            let path = self.config.sink_config.file_path(height);
+            if tokio::fs::try_exists(&path).await? {
+                continue;
+            }
+            let roots = self.app_client.get_sparse_roots(Some(height)).await?;
+            sink.write_sparse_roots(&roots).await?;
+            info!("Missing sparse roots file of block #{} written", height);
+        }
+        Ok(())
     }
 
     pub async fn run(&mut self) -> Result<(), ()> {
         match self.run_inner().await {
             Err(err) => {
-                error!("Block indexer exited: {}", err);
+                // Failures outside the pipeline (startup) are not about a block
+                let height = err.downcast_ref::<StageError>().and_then(|err| err.height);
+                error!(height, "Block indexer exited: {}", err);
                 Err(())
             }
             Ok(()) => {
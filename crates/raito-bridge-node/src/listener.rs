@@ -0,0 +1,222 @@
+//! Listeners of the RPC server: TCP (IPv4 or IPv6) or Unix domain sockets, each with optional
+//! TLS, so that a node can serve dual-stack deployments and local clients at the same time.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::broadcast;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info};
+
+/// Time allowed to complete a TLS handshake
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Address the RPC server listens on, with optional TLS:
+/// `<host:port | unix:<path>>[,tls-cert=<path>,tls-key=<path>]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenAddress {
+    /// Socket to bind
+    pub bind: BindAddress,
+    /// Certificate chain and private key (PEM) to serve HTTPS with, plain HTTP if not set
+    pub tls: Option<TlsFiles>,
+}
+
+/// Socket the RPC server binds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindAddress {
+    /// TCP host and port, e.g. `0.0.0.0:5000` or `[::]:5000`
+    Tcp(String),
+    /// Path of a Unix domain socket
+    Unix(PathBuf),
+}
+
+/// TLS certificate chain and private key files (PEM)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFiles {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// Stream of an accepted connection, plain or TLS
+trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+/// Bound socket accepting connections
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let bind = match parts.next().unwrap_or_default() {
+            "" => return Err("empty listen address".to_string()),
+            address => match address.strip_prefix("unix:") {
+                Some("") => return Err("empty Unix socket path".to_string()),
+                Some(path) => BindAddress::Unix(PathBuf::from(path)),
+                None => BindAddress::Tcp(address.to_string()),
+            },
+        };
+
+        let (mut cert_path, mut key_path) = (None, None);
+        for option in parts {
+            match option.split_once('=') {
+                Some(("tls-cert", path)) if !path.is_empty() => cert_path = Some(path.into()),
+                Some(("tls-key", path)) if !path.is_empty() => key_path = Some(path.into()),
+                _ => return Err(format!(
+                    "invalid listen option '{}', expected 'tls-cert=<path>' or 'tls-key=<path>'",
+                    option
+                )),
+            }
+        }
+        let tls = match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => Some(TlsFiles {
+                cert_path,
+                key_path,
+            }),
+            (None, None) => None,
+            _ => return Err("TLS requires both 'tls-cert' and 'tls-key'".to_string()),
+        };
+        Ok(Self { bind, tls })
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        match &self.bind {
+            BindAddress::Tcp(address) => write!(f, "{}://{}", scheme, address),
+            BindAddress::Unix(path) => write!(f, "{}+unix://{}", scheme, path.display()),
+        }
+    }
+}
+
+impl Listener {
+    /// Bind the socket, replacing a stale Unix socket file
+    async fn bind(address: &BindAddress) -> Result<Self, io::Error> {
+        match address {
+            BindAddress::Tcp(address) => Ok(Self::Tcp(TcpListener::bind(address).await?)),
+            BindAddress::Unix(path) => {
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Self::Unix(UnixListener::bind(path)?, path.clone()))
+            }
+        }
+    }
+
+    async fn accept(&self) -> Result<Box<dyn Connection>, io::Error> {
+        match self {
+            Self::Tcp(listener) => Ok(Box::new(listener.accept().await?.0)),
+            Self::Unix(listener, _) => Ok(Box::new(listener.accept().await?.0)),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Self::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Serve `app` on `address` until the shutdown signal, then wait for the open connections
+/// to complete
+pub async fn serve(
+    address: &ListenAddress,
+    app: Router,
+    mut rx_shutdown: broadcast::Receiver<()>,
+) -> Result<(), io::Error> {
+    let acceptor = address.tls.as_ref().map(tls_acceptor).transpose()?;
+    let listener = Listener::bind(&address.bind).await?;
+    info!("Starting RPC server on {}", address);
+
+    let builder = Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    loop {
+        let stream = tokio::select! {
+            res = listener.accept() => match res {
+                Ok(stream) => stream,
+                Err(err) => {
+                    // E.g. too many open files, retrying at once would spin
+                    debug!("Failed to accept connection on {}: {}", address, err);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = rx_shutdown.recv() => break,
+        };
+        let service = TowerToHyperService::new(app.clone());
+        let builder = builder.clone();
+        let acceptor = acceptor.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream: Box<dyn Connection> = match acceptor {
+                Some(acceptor) => {
+                    match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await
+                    {
+                        Ok(Ok(stream)) => Box::new(stream),
+                        Ok(Err(err)) => {
+                            debug!("TLS handshake failed: {}", err);
+                            return;
+                        }
+                        Err(_) => {
+                            debug!("TLS handshake timed out");
+                            return;
+                        }
+                    }
+                }
+                None => stream,
+            };
+            let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(err) = watcher.watch(connection).await {
+                debug!("Connection closed with error: {}", err);
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// Load the TLS certificate chain and private key of a listener
+fn tls_acceptor(files: &TlsFiles) -> Result<TlsAcceptor, io::Error> {
+    let invalid = |what: &str, path: &PathBuf, err: &dyn fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid TLS {} {}: {}", what, path.display(), err),
+        )
+    };
+    let certs = CertificateDer::pem_file_iter(&files.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid("certificate", &files.cert_path, &e))?;
+    let key = PrivateKeyDer::from_pem_file(&files.key_path)
+        .map_err(|e| invalid("private key", &files.key_path, &e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| invalid("certificate", &files.cert_path, &e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
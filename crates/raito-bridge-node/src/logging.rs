@@ -0,0 +1,222 @@
+//! Logging setup: logs to stderr, and optionally to a rotated log file, so that long-running
+//! nodes keep bounded logs without external log shipping, and error reporting to Sentry, so
+//! that operators hear about failures of unattended nodes.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use sentry::{ClientInitGuard, ClientOptions};
+use tracing::{info, subscriber::set_global_default};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{filter::EnvFilter, layer::SubscriberExt, Layer};
+
+/// Time-based rotation of the log file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// Configuration of the log file
+#[derive(Debug, Clone)]
+pub struct LogFileConfig {
+    /// Path of the log file, rotated files are written next to it
+    pub path: PathBuf,
+    /// Time-based rotation, the date is appended to the file name
+    pub rotation: LogRotation,
+    /// Size in bytes above which the file is rotated, replaces the time-based rotation
+    pub max_size: Option<u64>,
+    /// Maximum number of rotated files kept, all of them if zero
+    pub max_files: usize,
+}
+
+/// Initialize the Sentry client reporting panics and `error!` events to `dsn`
+///
+/// The returned guard flushes the pending reports when dropped.
+pub fn init_sentry(
+    dsn: &str,
+    environment: Option<String>,
+) -> Result<ClientInitGuard, anyhow::Error> {
+    let dsn = dsn
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid Sentry DSN: {}", e))?;
+    Ok(sentry::init(ClientOptions {
+        dsn: Some(dsn),
+        release: sentry::release_name!(),
+        environment: environment.map(Into::into),
+        attach_stacktrace: true,
+        ..Default::default()
+    }))
+}
+
+/// Install the global tracing subscriber: logs to stderr, to the log file if configured,
+/// the tokio-console layer listening on `console_addr` if given, and the Sentry layer if
+/// `sentry` is set
+///
+/// The log level only applies to the logs, the console layer records the runtime events it
+/// needs regardless. Sentry receives the `error!` events as issues, with the fields of their
+/// spans (e.g. the request ID), and the `info!` and `warn!` events before them as breadcrumbs.
+/// The returned guard flushes the log file when dropped.
+pub fn init_tracing(
+    log_level: &str,
+    log_file: Option<&LogFileConfig>,
+    console_addr: Option<SocketAddr>,
+    sentry: bool,
+) -> Result<Option<WorkerGuard>, io::Error> {
+    let env_filter =
+        || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
+    let stderr_layer = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
+        .with_filter(env_filter());
+
+    let (file_layer, guard) = match log_file {
+        Some(config) => {
+            let (writer, guard) = tracing_appender::non_blocking(open_log_file(config)?);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .with_filter(env_filter());
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let console_layer = console_addr.map(|addr| {
+        console_subscriber::ConsoleLayer::builder()
+            .server_addr(addr)
+            .spawn()
+    });
+    let sentry_layer = sentry.then(|| {
+        sentry::integrations::tracing::layer()
+            .enable_span_attributes()
+            .with_filter(env_filter())
+    });
+    let subscriber = tracing_subscriber::registry()
+        .with(console_layer)
+        .with(stderr_layer)
+        .with(file_layer)
+        .with(sentry_layer);
+    set_global_default(subscriber).expect("Failed to set subscriber");
+
+    if let Some(config) = log_file {
+        info!("Logging to {}", config.path.display());
+    }
+    if let Some(addr) = console_addr {
+        info!("Tokio console listening on {}", addr);
+    }
+    if sentry {
+        info!("Reporting errors to Sentry");
+    }
+    Ok(guard)
+}
+
+/// Open the log file with the configured rotation
+fn open_log_file(config: &LogFileConfig) -> Result<Box<dyn Write + Send>, io::Error> {
+    let directory = match config.path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(directory)?;
+    if let Some(max_size) = config.max_size {
+        let file = SizeRotatingFile::open(config.path.clone(), max_size, config.max_files)?;
+        return Ok(Box::new(file));
+    }
+
+    let file_name = config
+        .path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Log file has no file name"))?;
+    let rotation = match config.rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name.to_string_lossy())
+        .max_log_files(config.max_files)
+        .build(directory)
+        .map_err(io::Error::other)?;
+    Ok(Box::new(appender))
+}
+
+/// Log file rotated once it exceeds a maximum size: `node.log` is renamed to `node.log.1`,
+/// `node.log.1` to `node.log.2`, and so on, keeping at most `max_files` rotated files (all of
+/// them if zero)
+pub struct SizeRotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotatingFile {
+    /// Open the log file, appending to it if it exists
+    pub fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<Self, io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    /// Path of the `index`-th rotated file
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    /// Shift the rotated files, dropping the oldest one, and start a new log file
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        self.file.flush()?;
+        let max_files = match self.max_files {
+            0 => {
+                (1..)
+                    .take_while(|&index| self.rotated_path(index).exists())
+                    .count()
+                    + 1
+            }
+            max_files => max_files,
+        };
+        for index in (1..max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
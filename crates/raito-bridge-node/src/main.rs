@@ -1,23 +1,45 @@
 #![doc = include_str!("../README.md")]
 
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use clap::{command, Parser};
+use clap::{command, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use raito_spv_core::bitcoin::{BitcoinClient, PollSchedule};
 use tokio::task::JoinHandle;
-use tracing::{error, info, subscriber::set_global_default};
-use tracing_subscriber::filter::EnvFilter;
+use tracing::{error, info};
 
 use crate::{
     app::{create_app, AppConfig},
+    backfill::BackfillConfig,
+    cross_check::CrossCheckConfig,
     file_sink::SparseRootsSinkConfig,
-    indexer::{Indexer, IndexerConfig},
-    rpc::{RpcConfig, RpcServer},
+    indexer::{HeadersSource, Indexer, IndexerConfig},
+    listener::ListenAddress,
+    logging::{init_sentry, init_tracing, LogFileConfig, LogRotation},
+    pipeline::PipelineMetrics,
+    pregen::{PregenConfig, Pregenerator, RecentProofs},
+    proof_cache::{ProofCache, ProofCacheConfig},
+    replicate::{Replicator, ReplicatorConfig},
+    rpc::{RpcConfig, RpcLimits, RpcServer},
     shutdown::Shutdown,
 };
 
 mod app;
+mod backfill;
+mod chainstate;
+mod cross_check;
+mod diagnostics;
 mod file_sink;
 mod indexer;
+mod listener;
+mod logging;
+mod pipeline;
+mod pregen;
+mod proof_cache;
+mod replicate;
 mod rpc;
 mod shutdown;
 
@@ -25,12 +47,28 @@ mod shutdown;
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
-    /// RPC server host
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Address the RPC server listens on, repeat to listen on several: `<host:port>` (IPv4 or
+    /// `[IPv6]`) or `unix:<path>`, followed by `,tls-cert=<path>,tls-key=<path>` (PEM) to
+    /// serve HTTPS
     #[arg(long, default_value = "127.0.0.1:5000")]
-    rpc_host: String,
-    /// Bitcoin RPC URL
+    rpc_host: Vec<ListenAddress>,
+    /// Maximum size of an RPC request body in bytes
+    #[arg(long, default_value = "16384")]
+    rpc_max_body_size: usize,
+    /// Maximum size of an uploaded chain state proof in bytes
+    #[arg(long, default_value = "134217728")]
+    rpc_max_proof_size: usize,
+    /// Maximum time in seconds to respond to an RPC request
+    #[arg(long, default_value = "30")]
+    rpc_timeout_secs: u64,
+    /// Maximum number of requests to each RPC endpoint processed at the same time
+    #[arg(long, default_value = "32", value_parser = clap::value_parser!(u32).range(1..))]
+    rpc_max_concurrent_requests: u32,
+    /// Bitcoin RPC URL (required unless headers are replayed from a file)
     #[arg(long, env = "BITCOIN_RPC")]
-    bitcoin_rpc_url: String,
+    bitcoin_rpc_url: Option<String>,
     /// Bitcoin RPC user:password (optional)
     #[arg(long, env = "USERPWD")]
     bitcoin_rpc_userpwd: Option<String>,
@@ -43,33 +81,293 @@ struct Cli {
     /// Number of blocks per sparse roots shard directory
     #[arg(long, default_value = "10000")]
     mmr_shard_size: u32,
+    /// Include the block hash and header in the sparse roots JSON files written by the indexer
+    #[arg(long, default_value = "false")]
+    mmr_roots_with_block: bool,
     /// Indexing lag in blocks, to address potential reorgs
     #[arg(long, default_value = "1")]
     mmr_block_lag: u32,
+    /// Where to get the block headers from: `rpc`, or `file:<path>` to replay recorded headers
+    /// (one hex-encoded header per line, starting at the genesis block) without a Bitcoin node
+    #[arg(long, default_value = "rpc")]
+    headers_source: HeadersSource,
+    /// Replicate the MMR from the RPC server of another bridge node instead of indexing
+    /// headers, e.g. `http://10.0.0.1:5000`: the new leaves are pulled and the resulting roots
+    /// checked against the leader's
+    #[arg(long, conflicts_with = "headers_source")]
+    replicate_from: Option<String>,
+    /// Interval in milliseconds between polls of the leader once the replica is up to date
+    #[arg(long, default_value = "10000", requires = "replicate_from")]
+    replicate_interval_ms: u64,
+    /// Delay in milliseconds before each header replayed from a file
+    #[arg(long, default_value = "0")]
+    replay_delay_ms: u64,
+    /// Interval in milliseconds between block count polls at the tip once the expected block
+    /// time has elapsed since the last block
+    #[arg(long, default_value = "2000")]
+    poll_min_interval_ms: u64,
+    /// Interval in milliseconds between block count polls at the tip right after a new block
+    #[arg(long, default_value = "30000")]
+    poll_max_interval_ms: u64,
+    /// Random variation of each polling interval, as a fraction of it (0 to 1)
+    #[arg(long, default_value = "0.1", value_parser = parse_jitter)]
+    poll_jitter: f64,
+    /// Only serve block inclusion proofs at block counts that are multiples of this interval,
+    /// the MMR states for which chain state proofs are generated
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    checkpoint_interval: Option<u32>,
+    /// Latest chain state proof to serve at `/chainstate-proof/recent_proof` (JSON, or
+    /// gzip-compressed JSON with a `.gz` extension), so that clients can fetch every proof
+    /// component from this node
+    #[arg(long)]
+    chain_state_proof_path: Option<PathBuf>,
+    /// Bearer token authorizing provers to publish new chain state proofs to
+    /// `--chain-state-proof-path` with `POST /chainstate-proof`
+    #[arg(long, env = "RAITO_INGEST_TOKEN", requires = "chain_state_proof_path")]
+    ingest_token: Option<String>,
+    /// Pre-generate the inclusion proofs of this many most recent blocks at the MMR state of
+    /// each new chain state proof, the heights clients request the most
+    #[arg(long, requires = "chain_state_proof_path")]
+    pregenerate_proofs: Option<u32>,
+    /// Redis URL of a block inclusion proof cache shared by the nodes behind a load balancer,
+    /// e.g. `redis://127.0.0.1:6379/0`
+    #[arg(long, env = "REDIS_URL")]
+    redis_url: Option<String>,
+    /// Prefix of the proof cache keys
+    #[arg(long, default_value = "raito", requires = "redis_url")]
+    redis_key_prefix: String,
+    /// Expiration of the cached proofs in seconds, never if zero
+    #[arg(long, default_value = "86400", requires = "redis_url")]
+    proof_cache_ttl_secs: u64,
     /// Logging level (off, error, warn, info, debug, trace)
     #[arg(long, default_value = "info")]
     log_level: String,
+    /// Also write the logs to this file, rotated over time or by size
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    /// Time-based rotation of the log file, the date is appended to the file name
+    #[arg(long, value_enum, default_value = "daily", requires = "log_file")]
+    log_rotation: LogRotation,
+    /// Rotate the log file once it exceeds this size in bytes instead of over time, the
+    /// rotated files are suffixed with `.1`, `.2`, ...
+    #[arg(long, requires = "log_file", conflicts_with = "log_rotation")]
+    log_max_size: Option<u64>,
+    /// Maximum number of rotated log files kept, all of them if zero
+    #[arg(long, default_value = "7", requires = "log_file")]
+    log_max_files: usize,
+    /// Sentry DSN to report panics and errors to, with their context (block height, request
+    /// ID)
+    #[arg(long, env = "SENTRY_DSN")]
+    sentry_dsn: Option<String>,
+    /// Environment of the reported errors, e.g. `production` or `staging`
+    #[arg(long, env = "SENTRY_ENVIRONMENT", requires = "sentry_dsn")]
+    sentry_environment: Option<String>,
+    /// Serve the async runtime state to tokio-console (requires a build with
+    /// `RUSTFLAGS="--cfg tokio_unstable"` to see the tasks)
+    #[arg(long, default_value = "false")]
+    tokio_console: bool,
+    /// Address the tokio-console server listens on
+    #[arg(long, default_value = "127.0.0.1:6669", requires = "tokio_console")]
+    tokio_console_addr: SocketAddr,
+    /// Expose CPU profiling at `/debug/pprof/profile` on a separate server
+    #[arg(long, default_value = "false")]
+    pprof: bool,
+    /// Address the CPU profiling server listens on, same format as `--rpc-host`; keep it
+    /// private, the endpoint is not authenticated
+    #[arg(long, default_value = "127.0.0.1:6060", requires = "pprof")]
+    pprof_addr: ListenAddress,
+    /// Number of worker threads of the async runtime, serving the RPC requests and polling the
+    /// Bitcoin node (defaults to the number of CPU cores)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    worker_threads: Option<u32>,
+    /// Maximum number of threads of the blocking pool, one of which runs the MMR operations
+    /// while the others serve the file I/O
+    #[arg(long, default_value = "512", value_parser = clap::value_parser!(u32).range(2..))]
+    blocking_threads: u32,
 }
 
-fn init_tracing(log_level: &str) {
-    let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
+/// Parse a polling jitter, a fraction between 0 and 1
+fn parse_jitter(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(jitter) if (0.0..=1.0).contains(&jitter) => Ok(jitter),
+        _ => Err(format!(
+            "invalid jitter '{}', expected a number between 0 and 1",
+            s
+        )),
+    }
+}
 
-    let subscriber_builder =
-        tracing_subscriber::fmt::Subscriber::builder().with_env_filter(env_filter);
+/// Subcommands for operating and packaging the node, the node runs if none is given
+#[derive(Subcommand)]
+enum Command {
+    /// Check the MMR leaves, MMR roots and sparse roots files of a range of heights against
+    /// the headers of the Bitcoin node, and report the first divergent height
+    CrossCheck {
+        /// First height to check
+        #[arg(long, default_value = "0")]
+        from: u32,
+        /// Last height to check, the latest indexed block if not provided
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// Regenerate the sparse roots files of a range of heights that are missing or don't match
+    /// their shard manifest, from the MMR which is left untouched
+    Backfill {
+        /// First height to backfill
+        #[arg(long, default_value = "0")]
+        from: u32,
+        /// Last height to backfill, the latest indexed block if not provided
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// Print the shell completion script
+    Completions {
+        /// Shell to generate the completion script for
+        shell: Shell,
+    },
+    /// Write the man pages to a directory
+    Manpages {
+        /// Directory to write the man pages to
+        dir: PathBuf,
+    },
+}
+
+/// Run a subcommand instead of the node
+async fn run_command(command: Command, cli: &Cli) -> Result<(), anyhow::Error> {
+    let mut cmd = Cli::command();
+    match command {
+        Command::CrossCheck { from, to } => {
+            let config = CrossCheckConfig {
+                mmr_db_path: cli.mmr_db_path.clone(),
+                sink_config: SparseRootsSinkConfig {
+                    output_dir: cli.mmr_roots_dir.clone(),
+                    shard_size: cli.mmr_shard_size,
+                },
+                rpc_url: cli
+                    .bitcoin_rpc_url
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("Bitcoin RPC URL is required"))?,
+                rpc_userpwd: cli.bitcoin_rpc_userpwd.clone(),
+                from,
+                to,
+            };
+            match cross_check::run(config).await? {
+                None => println!("No divergence from the Bitcoin node"),
+                Some((height, divergence)) => {
+                    println!("First divergent height: {}", height);
+                    anyhow::bail!("Divergence at height {}: {}", height, divergence);
+                }
+            }
+        }
+        Command::Backfill { from, to } => {
+            let config = BackfillConfig {
+                mmr_db_path: cli.mmr_db_path.clone(),
+                sink_config: SparseRootsSinkConfig {
+                    output_dir: cli.mmr_roots_dir.clone(),
+                    shard_size: cli.mmr_shard_size,
+                },
+                from,
+                to,
+            };
+            let report = backfill::run(config).await?;
+            println!(
+                "Checked {} heights: {} missing and {} corrupted files regenerated, {} intact \
+                 files recorded in the manifests",
+                report.checked,
+                report.missing.len(),
+                report.corrupted.len(),
+                report.recorded
+            );
+            for height in report.missing.iter().chain(&report.corrupted) {
+                println!("Regenerated height {}", height);
+            }
+        }
+        Command::Completions { shell } => {
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+        }
+        Command::Manpages { dir } => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(cmd, &dir)?;
+        }
+    }
+    Ok(())
+}
 
-    let subscriber = subscriber_builder.with_writer(std::io::stderr).finish();
-    set_global_default(subscriber).expect("Failed to set subscriber");
+/// Build the async runtime of the node, sized by the CLI
+fn build_runtime(cli: &Cli) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder
+        .enable_all()
+        .max_blocking_threads(cli.blocking_threads as usize);
+    if let Some(worker_threads) = cli.worker_threads {
+        builder.worker_threads(worker_threads as usize);
+    }
+    builder.build()
 }
 
-#[tokio::main]
-async fn main() {
+fn main() {
     // Load environment variables from .env file if it exists
     dotenv::dotenv().ok();
 
-    let cli = Cli::parse();
-    init_tracing(&cli.log_level);
+    let mut cli = Cli::parse();
+    let log_file = cli.log_file.clone().map(|path| LogFileConfig {
+        path,
+        rotation: cli.log_rotation,
+        max_size: cli.log_max_size,
+        max_files: cli.log_max_files,
+    });
+    // Sentry is initialized first, so that every thread reports to it
+    let sentry_guard = match &cli.sentry_dsn {
+        Some(dsn) => match init_sentry(dsn, cli.sentry_environment.clone()) {
+            Ok(sentry_guard) => Some(sentry_guard),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let log_guard = match init_tracing(
+        &cli.log_level,
+        log_file.as_ref(),
+        cli.tokio_console.then_some(cli.tokio_console_addr),
+        sentry_guard.is_some(),
+    ) {
+        Ok(log_guard) => log_guard,
+        Err(err) => {
+            eprintln!("Failed to open the log file: {}", err);
+            std::process::exit(1);
+        }
+    };
 
+    let runtime = match build_runtime(&cli) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("Failed to start the async runtime: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let code = runtime.block_on(async {
+        match cli.command.take() {
+            Some(command) => match run_command(command, &cli).await {
+                Ok(()) => 0,
+                Err(err) => {
+                    error!("{}", err);
+                    1
+                }
+            },
+            None => run_node(cli).await,
+        }
+    });
+    // Flush the log file and the error reports, which exiting the process would skip
+    drop(log_guard);
+    drop(sentry_guard);
+    std::process::exit(code);
+}
+
+/// Run the node until shutdown, returns the exit code
+async fn run_node(cli: Cli) -> i32 {
     info!("Raito bridge node is launching...");
 
     // Instantiating components and wiring them together
@@ -81,26 +379,137 @@ async fn main() {
     };
     let (mut app_server, app_client) = create_app(app_config, shutdown.subscribe());
 
-    let indexer_config = IndexerConfig {
-        rpc_url: cli.bitcoin_rpc_url,
-        rpc_userpwd: cli.bitcoin_rpc_userpwd,
-        indexing_lag: cli.mmr_block_lag,
-        sink_config: SparseRootsSinkConfig {
-            output_dir: cli.mmr_roots_dir,
-            shard_size: cli.mmr_shard_size,
+    // Block headers and transaction proofs are served from the same node as the indexed headers
+    let bitcoin_client = match &cli.bitcoin_rpc_url {
+        Some(rpc_url) => match BitcoinClient::new(rpc_url.clone(), cli.bitcoin_rpc_userpwd.clone())
+        {
+            Ok(bitcoin_client) => Some(Arc::new(bitcoin_client)),
+            Err(err) => {
+                error!("Failed to create Bitcoin RPC client: {}", err);
+                return 1;
+            }
         },
+        None => None,
+    };
+
+    let proof_cache = match cli.redis_url {
+        Some(redis_url) => {
+            let config = ProofCacheConfig {
+                redis_url,
+                key_prefix: cli.redis_key_prefix,
+                ttl: (cli.proof_cache_ttl_secs > 0)
+                    .then(|| Duration::from_secs(cli.proof_cache_ttl_secs)),
+            };
+            match ProofCache::connect(config).await {
+                Ok(proof_cache) => {
+                    info!("Caching block inclusion proofs in Redis");
+                    Some(proof_cache)
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    return 1;
+                }
+            }
+        }
+        None => None,
+    };
+
+    let sink_config = SparseRootsSinkConfig {
+        output_dir: cli.mmr_roots_dir,
+        shard_size: cli.mmr_shard_size,
+    };
+    let pipeline_metrics = PipelineMetrics::default();
+    // A replica gets the MMR leaves from its leader instead of indexing headers
+    let mut replicator = cli.replicate_from.clone().map(|leader_url| {
+        Replicator::new(
+            ReplicatorConfig {
+                leader_url,
+                poll_interval: Duration::from_millis(cli.replicate_interval_ms),
+                sink_config: sink_config.clone(),
+            },
+            app_client.clone(),
+            shutdown.subscribe(),
+        )
+    });
+    let mut indexer = match replicator {
+        Some(_) => None,
+        None => Some(Indexer::new(
+            IndexerConfig {
+                headers_source: cli.headers_source,
+                replay_delay: Duration::from_millis(cli.replay_delay_ms),
+                rpc_url: cli.bitcoin_rpc_url,
+                rpc_userpwd: cli.bitcoin_rpc_userpwd,
+                indexing_lag: cli.mmr_block_lag,
+                poll_schedule: PollSchedule {
+                    min_interval: Duration::from_millis(cli.poll_min_interval_ms),
+                    max_interval: Duration::from_millis(cli.poll_max_interval_ms),
+                    jitter: cli.poll_jitter,
+                    ..Default::default()
+                },
+                sink_config,
+                roots_with_block: cli.mmr_roots_with_block,
+            },
+            app_client.clone(),
+            shutdown.subscribe(),
+            pipeline_metrics.clone(),
+        )),
+    };
+
+    let recent_proofs = RecentProofs::default();
+    let mut pregenerator = match (cli.pregenerate_proofs, &cli.chain_state_proof_path) {
+        (Some(block_count), Some(chain_state_proof_path)) => Some(Pregenerator::new(
+            PregenConfig {
+                chain_state_proof_path: chain_state_proof_path.clone(),
+                block_count,
+            },
+            app_client.clone(),
+            recent_proofs.clone(),
+            proof_cache.clone(),
+            shutdown.subscribe(),
+        )),
+        _ => None,
     };
-    let mut indexer = Indexer::new(indexer_config, app_client.clone(), shutdown.subscribe());
 
     let rpc_config = RpcConfig {
-        rpc_host: cli.rpc_host,
+        listen_addresses: cli.rpc_host,
+        checkpoint_interval: cli.checkpoint_interval,
+        bitcoin_client,
+        chain_state_proof_path: cli.chain_state_proof_path,
+        ingest_token: cli.ingest_token,
+        proof_cache,
+        recent_proofs,
+        pipeline_metrics,
+        pprof_address: cli.pprof.then_some(cli.pprof_addr),
+        limits: RpcLimits {
+            max_body_size: cli.rpc_max_body_size,
+            max_proof_size: cli.rpc_max_proof_size,
+            timeout: Duration::from_secs(cli.rpc_timeout_secs),
+            max_concurrent_requests: cli.rpc_max_concurrent_requests as usize,
+        },
     };
     let rpc_server = RpcServer::new(rpc_config, app_client.clone(), shutdown.subscribe());
 
     // Launching threads for each component
-    let app_handle = tokio::spawn(async move { app_server.run().await });
-    let indexer_handle = tokio::spawn(async move { indexer.run().await });
+    // The MMR operations hash and query SQLite synchronously within their futures, so the app
+    // server runs on a thread of the blocking pool: heavy proof generation must not hold the
+    // workers serving the RPC requests and polling the Bitcoin node
+    let app_handle = tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(app_server.run())
+    });
+    let indexer_handle = tokio::spawn(async move {
+        match (&mut indexer, &mut replicator) {
+            (Some(indexer), _) => indexer.run().await,
+            (None, Some(replicator)) => replicator.run().await,
+            (None, None) => Ok(()),
+        }
+    });
     let rpc_handle = tokio::spawn(async move { rpc_server.run().await });
+    let pregen_handle = tokio::spawn(async move {
+        match &mut pregenerator {
+            Some(pregenerator) => pregenerator.run().await,
+            None => Ok(()),
+        }
+    });
     let shutdown_handle = tokio::spawn(async move { shutdown.run().await });
 
     // If at least one component exits with an error, the node will exit with an error
@@ -108,15 +517,16 @@ async fn main() {
         flatten(app_handle),
         flatten(indexer_handle),
         flatten(rpc_handle),
+        flatten(pregen_handle),
         flatten(shutdown_handle)
     ) {
         Ok(_) => {
             info!("Raito bridge node has shut down");
-            std::process::exit(0);
+            0
         }
         Err(_) => {
             error!("Raito bridge node has exited with error");
-            std::process::exit(1);
+            1
         }
     }
 }
@@ -0,0 +1,290 @@
+//! Stages of a processing pipeline connected by channels, each running in its own task: a
+//! failed stage is restarted and retries its input, without disturbing the other stages, and
+//! its activity is recorded in metrics served in the Prometheus text format.
+
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Number of consecutive restarts after which a stage fails
+const MAX_RESTARTS: u32 = 5;
+
+/// Delay before the first restart of a stage, doubled at each consecutive restart
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// A stage of a pipeline, turning each input into an output for the next stage
+pub trait Stage: Send + 'static {
+    type Input: Send + 'static;
+    type Output: Send + 'static;
+
+    /// Name of the stage in the logs and metrics
+    const NAME: &'static str;
+
+    /// Process an input, `None` if the stage has nothing more to produce (end of a source)
+    fn process(
+        &mut self,
+        input: Self::Input,
+    ) -> impl Future<Output = Result<Option<Self::Output>, anyhow::Error>> + Send;
+
+    /// Reset the state of the stage after a failure, before the input is processed again
+    fn restart(&mut self) -> impl Future<Output = Result<(), anyhow::Error>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Whether the stage is restarted after this failure, rather than failing the pipeline
+    fn is_transient(&self, _err: &anyhow::Error) -> bool {
+        true
+    }
+
+    /// Block height an input is about, reported if the stage fails on it
+    fn height(&self, _input: &Self::Input) -> Option<u32> {
+        None
+    }
+}
+
+/// Failure of a stage, with the block height of the input it failed on
+#[derive(Debug, thiserror::Error)]
+#[error("{stage} stage failed: {source}")]
+pub struct StageError {
+    /// Name of the stage
+    pub stage: &'static str,
+    /// Block height of the input, if the stage reports it
+    pub height: Option<u32>,
+    /// Last failure of the stage
+    pub source: anyhow::Error,
+}
+
+/// Run a source stage, processing `()` until it has nothing more to produce
+pub async fn run_source<S: Stage<Input = ()>>(
+    mut stage: S,
+    tx: mpsc::Sender<S::Output>,
+    metrics: Arc<StageMetrics>,
+) -> Result<(), anyhow::Error> {
+    loop {
+        let Some(output) = process(&mut stage, (), 1, &metrics).await? else {
+            return Ok(());
+        };
+        if tx.send(output).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Run a stage until its input channel is closed, sending its outputs to the next stage if any
+pub async fn run_stage<S: Stage>(
+    mut stage: S,
+    mut rx: mpsc::Receiver<S::Input>,
+    tx: Option<mpsc::Sender<S::Output>>,
+    metrics: Arc<StageMetrics>,
+) -> Result<(), anyhow::Error>
+where
+    S::Input: Clone,
+{
+    while let Some(input) = rx.recv().await {
+        let Some(output) = process(&mut stage, input, 1, &metrics).await? else {
+            return Ok(());
+        };
+        if let Some(tx) = &tx {
+            if tx.send(output).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run a stage processing its inputs as batches until its input channel is closed: the inputs
+/// waiting in the channel are processed at once, up to `max_batch`, and the outputs sent one by
+/// one to the next stage if any
+///
+/// A stage keeping up with its input processes batches of one input.
+pub async fn run_batch_stage<S, I, O>(
+    mut stage: S,
+    mut rx: mpsc::Receiver<I>,
+    tx: Option<mpsc::Sender<O>>,
+    max_batch: usize,
+    metrics: Arc<StageMetrics>,
+) -> Result<(), anyhow::Error>
+where
+    S: Stage<Input = Vec<I>, Output = Vec<O>>,
+    I: Clone + Send + 'static,
+    O: Send + 'static,
+{
+    while let Some(input) = rx.recv().await {
+        let mut batch = vec![input];
+        while batch.len() < max_batch {
+            match rx.try_recv() {
+                Ok(input) => batch.push(input),
+                Err(_) => break,
+            }
+        }
+        let count = batch.len() as u64;
+        let Some(outputs) = process(&mut stage, batch, count, &metrics).await? else {
+            return Ok(());
+        };
+        if let Some(tx) = &tx {
+            for output in outputs {
+                if tx.send(output).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Process an input, made of `count` inputs of the previous stage, restarting the stage on
+/// transient failures
+async fn process<S: Stage>(
+    stage: &mut S,
+    input: S::Input,
+    count: u64,
+    metrics: &StageMetrics,
+) -> Result<Option<S::Output>, anyhow::Error>
+where
+    S::Input: Clone,
+{
+    let mut restarts = 0;
+    loop {
+        let start = Instant::now();
+        let res = stage.process(input.clone()).await;
+        metrics.record_busy(start.elapsed());
+        let err = match res {
+            Ok(output) => {
+                metrics.processed.fetch_add(count, Ordering::Relaxed);
+                return Ok(output);
+            }
+            Err(err) if restarts < MAX_RESTARTS && stage.is_transient(&err) => err,
+            Err(err) => {
+                return Err(StageError {
+                    stage: S::NAME,
+                    height: stage.height(&input),
+                    source: err,
+                }
+                .into())
+            }
+        };
+
+        let delay = RESTART_DELAY * 2u32.pow(restarts);
+        restarts += 1;
+        metrics.restarts.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "{} stage failed, restarting in {:?} ({}/{}): {}",
+            S::NAME,
+            delay,
+            restarts,
+            MAX_RESTARTS,
+            err
+        );
+        tokio::time::sleep(delay).await;
+        // A stage that can't be restarted is retried at the next attempt
+        if let Err(err) = stage.restart().await {
+            warn!("Failed to restart the {} stage: {}", S::NAME, err);
+        }
+    }
+}
+
+/// Metrics of a pipeline stage
+#[derive(Debug, Default)]
+pub struct StageMetrics {
+    /// Number of inputs processed
+    processed: AtomicU64,
+    /// Number of restarts after a failure
+    restarts: AtomicU64,
+    /// Time spent processing inputs, failed attempts included, in microseconds
+    busy_micros: AtomicU64,
+}
+
+impl StageMetrics {
+    fn record_busy(&self, elapsed: Duration) {
+        self.busy_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Metrics of the stages of a pipeline, shared with the RPC server
+#[derive(Debug, Clone, Default)]
+pub struct PipelineMetrics(Arc<PipelineMetricsInner>);
+
+#[derive(Debug, Default)]
+struct PipelineMetricsInner {
+    /// Metrics of each stage, in pipeline order
+    stages: Mutex<Vec<(&'static str, Arc<StageMetrics>)>>,
+    /// Number of blocks through the pipeline
+    block_count: AtomicU64,
+}
+
+impl PipelineMetrics {
+    /// Metrics of a stage, registered on first use
+    pub fn stage(&self, name: &'static str) -> Arc<StageMetrics> {
+        let mut stages = self.0.stages.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, metrics)) = stages.iter().find(|(stage, _)| *stage == name) {
+            return metrics.clone();
+        }
+        let metrics = Arc::new(StageMetrics::default());
+        stages.push((name, metrics.clone()));
+        metrics
+    }
+
+    /// Record the number of blocks through the pipeline
+    pub fn set_block_count(&self, block_count: u32) {
+        self.0
+            .block_count
+            .store(block_count as u64, Ordering::Relaxed);
+    }
+
+    /// Number of blocks through the pipeline
+    pub fn block_count(&self) -> u32 {
+        self.0.block_count.load(Ordering::Relaxed) as u32
+    }
+
+    /// Render the metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let stages = self.0.stages.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        let mut family =
+            |name: &str, kind: &str, help: &str, value: &dyn Fn(&StageMetrics) -> String| {
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+                let _ = writeln!(out, "# TYPE {} {}", name, kind);
+                for (stage, metrics) in stages.iter() {
+                    let _ = writeln!(out, "{}{{stage=\"{}\"}} {}", name, stage, value(metrics));
+                }
+            };
+        family(
+            "raito_pipeline_processed_total",
+            "counter",
+            "Inputs processed by each indexer pipeline stage",
+            &|metrics| metrics.processed.load(Ordering::Relaxed).to_string(),
+        );
+        family(
+            "raito_pipeline_restarts_total",
+            "counter",
+            "Restarts of each indexer pipeline stage after a failure",
+            &|metrics| metrics.restarts.load(Ordering::Relaxed).to_string(),
+        );
+        family(
+            "raito_pipeline_busy_seconds_total",
+            "counter",
+            "Time spent processing by each indexer pipeline stage",
+            &|metrics| {
+                format!(
+                    "{:.6}",
+                    metrics.busy_micros.load(Ordering::Relaxed) as f64 / 1e6
+                )
+            },
+        );
+        let _ = writeln!(
+            out,
+            "# HELP raito_indexed_block_count Blocks through the indexer pipeline\n\
+             # TYPE raito_indexed_block_count gauge\n\
+             raito_indexed_block_count {}",
+            self.0.block_count.load(Ordering::Relaxed)
+        );
+        out
+    }
+}
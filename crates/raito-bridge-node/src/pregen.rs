@@ -0,0 +1,224 @@
+//! Pre-generation of the inclusion proofs of the most recent blocks at the MMR state anchored by
+//! the latest chain state proof, the heights SPV clients overwhelmingly request, whenever a new
+//! chain state proof lands.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
+
+use crate::app::{AnchoredBlockProof, AppClient};
+use crate::proof_cache::ProofCache;
+
+/// Interval between checks of the chain state proof file
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Configuration of the proof pre-generation
+#[derive(Debug, Clone)]
+pub struct PregenConfig {
+    /// Chain state proof served to clients, watched for new proofs
+    pub chain_state_proof_path: PathBuf,
+    /// Number of blocks below the anchored block count to pre-generate proofs for
+    pub block_count: u32,
+}
+
+/// Inclusion proofs pre-generated at the anchored MMR state, shared with the RPC handlers
+#[derive(Clone, Default)]
+pub struct RecentProofs(Arc<RwLock<Option<RecentProofSet>>>);
+
+/// Inclusion proofs of the most recent blocks at one MMR state
+struct RecentProofSet {
+    /// Number of blocks of the MMR state the proofs were generated at
+    block_count: u32,
+    /// Proofs by block height
+    proofs: HashMap<u32, AnchoredBlockProof>,
+}
+
+impl RecentProofs {
+    /// Get the pre-generated inclusion proof of the block at `block_height` in the MMR state
+    /// with `block_count` blocks, if any
+    pub fn get(&self, block_height: u32, block_count: u32) -> Option<AnchoredBlockProof> {
+        let set = self.0.read().unwrap_or_else(|e| e.into_inner());
+        set.as_ref()
+            .filter(|set| set.block_count == block_count)
+            .and_then(|set| set.proofs.get(&block_height).cloned())
+    }
+
+    /// Block count of the MMR state of the pre-generated proofs, if any
+    fn block_count(&self) -> Option<u32> {
+        let set = self.0.read().unwrap_or_else(|e| e.into_inner());
+        set.as_ref().map(|set| set.block_count)
+    }
+
+    /// Replace the pre-generated proofs
+    fn replace(&self, block_count: u32, proofs: HashMap<u32, AnchoredBlockProof>) {
+        let mut set = self.0.write().unwrap_or_else(|e| e.into_inner());
+        *set = Some(RecentProofSet {
+            block_count,
+            proofs,
+        });
+    }
+}
+
+/// Background task pre-generating the inclusion proofs of the most recent blocks whenever a new
+/// chain state proof lands
+pub struct Pregenerator {
+    config: PregenConfig,
+    app_client: AppClient,
+    recent_proofs: RecentProofs,
+    proof_cache: Option<ProofCache>,
+    rx_shutdown: broadcast::Receiver<()>,
+}
+
+/// Chain state proof fields needed to find the anchored MMR state
+#[derive(Deserialize)]
+struct ChainStateAnchor {
+    chainstate: AnchoredChainState,
+}
+
+#[derive(Deserialize)]
+struct AnchoredChainState {
+    block_height: u32,
+}
+
+impl Pregenerator {
+    pub fn new(
+        config: PregenConfig,
+        app_client: AppClient,
+        recent_proofs: RecentProofs,
+        proof_cache: Option<ProofCache>,
+        rx_shutdown: broadcast::Receiver<()>,
+    ) -> Self {
+        Self {
+            config,
+            app_client,
+            recent_proofs,
+            proof_cache,
+            rx_shutdown,
+        }
+    }
+
+    async fn run_inner(&mut self) -> Result<(), anyhow::Error> {
+        info!(
+            "Pre-generating the inclusion proofs of the {} most recent blocks of each chain state proof",
+            self.config.block_count
+        );
+        let mut modified: Option<SystemTime> = None;
+        // Block count anchored by the latest chain state proof, until its proofs are generated
+        let mut pending: Option<u32> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = self.rx_shutdown.recv() => return Ok(()),
+            }
+
+            let path = &self.config.chain_state_proof_path;
+            match std::fs::metadata(path).and_then(|metadata| metadata.modified()) {
+                Ok(time) if modified != Some(time) => {
+                    let anchor_path = path.clone();
+                    match tokio::task::spawn_blocking(move || read_anchor(&anchor_path)).await? {
+                        Ok(block_count) => {
+                            modified = Some(time);
+                            if self.recent_proofs.block_count() != Some(block_count) {
+                                info!("New chain state proof at block count {}", block_count);
+                                pending = Some(block_count);
+                            }
+                        }
+                        // Read again at the next check, the file may be being replaced
+                        Err(err) => warn!(
+                            "Failed to read chain state proof {}: {}",
+                            path.display(),
+                            err
+                        ),
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => debug!(
+                    "Chain state proof {} not available: {}",
+                    path.display(),
+                    err
+                ),
+            }
+
+            let Some(block_count) = pending else {
+                continue;
+            };
+            if self.app_client.get_block_count().await? < block_count {
+                debug!("Waiting for block count {} to be indexed", block_count);
+                continue;
+            }
+            match self.pregenerate(block_count).await {
+                Ok(()) => pending = None,
+                Err(err) => error!(
+                    "Failed to pre-generate proofs at block count {}: {}",
+                    block_count, err
+                ),
+            }
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), ()> {
+        match self.run_inner().await {
+            Err(err) => {
+                error!("Proof pre-generator exited: {}", err);
+                Err(())
+            }
+            Ok(()) => {
+                info!("Proof pre-generator terminated");
+                Ok(())
+            }
+        }
+    }
+
+    /// Generate the inclusion proofs of the most recent blocks at the MMR state with
+    /// `block_count` blocks, and add them to the shared cache if any
+    async fn pregenerate(&self, block_count: u32) -> Result<(), anyhow::Error> {
+        let start_time = Instant::now();
+        let chain_height = block_count - 1;
+        let mmr_root = self.app_client.get_root_hash(Some(chain_height)).await?;
+
+        let mut proofs = HashMap::new();
+        for block_height in block_count.saturating_sub(self.config.block_count)..block_count {
+            let proof = self
+                .app_client
+                .generate_block_proof(block_height, Some(chain_height))
+                .await?;
+            let proof = AnchoredBlockProof::new(proof, mmr_root.clone());
+            if let Some(proof_cache) = &self.proof_cache {
+                proof_cache.set(&proof).await;
+            }
+            proofs.insert(block_height, proof);
+        }
+
+        info!(
+            "Pre-generated {} proofs at block count {} in {:.1}s",
+            proofs.len(),
+            block_count,
+            start_time.elapsed().as_secs_f64()
+        );
+        self.recent_proofs.replace(block_count, proofs);
+        Ok(())
+    }
+}
+
+/// Read the block count of the MMR state anchored by a chain state proof (JSON, gzip-compressed
+/// if the extension is `.gz`)
+pub fn read_anchor(path: &Path) -> Result<u32, anyhow::Error> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|extension| extension == "gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let anchor: ChainStateAnchor = serde_json::from_reader(BufReader::new(reader))?;
+    Ok(anchor.chainstate.block_height + 1)
+}
@@ -0,0 +1,129 @@
+//! Redis cache of the generated block inclusion proofs, shared by the bridge nodes behind a
+//! load balancer so that the fleet doesn't regenerate the same proofs on every node.
+//!
+//! A proof is keyed by the block height, the block count and the root of the MMR state it was
+//! generated at, so that nodes with diverging MMR states never serve each other's proofs.
+
+use std::time::Duration;
+
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::AsyncCommands;
+use tracing::{debug, warn};
+
+use crate::app::AnchoredBlockProof;
+
+/// Time allowed to connect to Redis
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time allowed for Redis to respond, a slow cache must not hold up the proofs
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of attempts to reconnect to Redis before failing a command
+const CONNECTION_RETRIES: usize = 2;
+
+/// Maximum delay between the attempts to reconnect to Redis in milliseconds
+const CONNECTION_RETRY_MAX_DELAY_MS: u64 = 500;
+
+/// Configuration of the shared proof cache
+#[derive(Debug, Clone)]
+pub struct ProofCacheConfig {
+    /// Redis URL, e.g. `redis://127.0.0.1:6379/0` or `rediss://...` for TLS
+    pub redis_url: String,
+    /// Prefix of the cache keys, to share a Redis instance between networks
+    pub key_prefix: String,
+    /// Expiration of the cached proofs, never if not set
+    pub ttl: Option<Duration>,
+}
+
+/// Shared cache of block inclusion proofs
+///
+/// The cache is an optimization: Redis failures are logged and the proofs are generated as if
+/// they were missing.
+#[derive(Clone)]
+pub struct ProofCache {
+    connection: ConnectionManager,
+    key_prefix: String,
+    ttl: Option<Duration>,
+}
+
+impl ProofCache {
+    /// Connect to Redis, the connection is re-established automatically if it drops
+    pub async fn connect(config: ProofCacheConfig) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(config.redis_url.as_str())
+            .map_err(|e| anyhow::anyhow!("Invalid Redis URL: {}", e))?;
+        let connection_config = ConnectionManagerConfig::new()
+            .set_connection_timeout(CONNECTION_TIMEOUT)
+            .set_response_timeout(RESPONSE_TIMEOUT)
+            .set_number_of_retries(CONNECTION_RETRIES)
+            .set_max_delay(CONNECTION_RETRY_MAX_DELAY_MS);
+        let connection = ConnectionManager::new_with_config(client, connection_config)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to connect to Redis: {}", e))?;
+        Ok(Self {
+            connection,
+            key_prefix: config.key_prefix,
+            ttl: config.ttl,
+        })
+    }
+
+    /// Key of the inclusion proof of the block at `block_height` in the MMR state with
+    /// `block_count` blocks and root `mmr_root`
+    fn key(&self, block_height: u32, block_count: u32, mmr_root: &str) -> String {
+        format!(
+            "{}:block-inclusion-proof:{}:{}:{}",
+            self.key_prefix, block_height, block_count, mmr_root
+        )
+    }
+
+    /// Get a cached block inclusion proof, `None` if it is missing or Redis is unavailable
+    pub async fn get(
+        &self,
+        block_height: u32,
+        block_count: u32,
+        mmr_root: &str,
+    ) -> Option<AnchoredBlockProof> {
+        let key = self.key(block_height, block_count, mmr_root);
+        let mut connection = self.connection.clone();
+        let value: Option<String> = match connection.get(&key).await {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("Failed to get {} from the proof cache: {}", key, err);
+                return None;
+            }
+        };
+        match serde_json::from_str(&value?) {
+            Ok(proof) => {
+                debug!("Proof cache hit for {}", key);
+                Some(proof)
+            }
+            Err(err) => {
+                warn!("Invalid proof cached at {}: {}", key, err);
+                None
+            }
+        }
+    }
+
+    /// Cache a block inclusion proof, failures are only logged
+    pub async fn set(&self, proof: &AnchoredBlockProof) {
+        let key = self.key(
+            proof.proof.leaf_index as u32,
+            proof.proof.leaf_count as u32,
+            &proof.mmr_root,
+        );
+        let value = match serde_json::to_string(proof) {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("Failed to serialize the proof cached at {}: {}", key, err);
+                return;
+            }
+        };
+        let mut connection = self.connection.clone();
+        let res: Result<(), redis::RedisError> = match self.ttl {
+            Some(ttl) => connection.set_ex(&key, value, ttl.as_secs()).await,
+            None => connection.set(&key, value).await,
+        };
+        if let Err(err) = res {
+            warn!("Failed to add {} to the proof cache: {}", key, err);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! MMR replication from another bridge node, the leader: the leaves indexed by the leader since
+//! the local block count are pulled over HTTP, appended to a copy of the local peaks and only
+//! persisted if the resulting root matches the leader's, so that proof-serving replicas don't
+//! need a Bitcoin node.
+
+use std::time::Duration;
+
+use raito_spv_core::{
+    block_mmr::BlockPeaks,
+    primitives::{hash_to_hex, hashes_from_hex},
+};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    app::AppClient,
+    file_sink::{SparseRootsSink, SparseRootsSinkConfig},
+    rpc::{MmrLeaves, MAX_LEAVES_PER_REQUEST},
+};
+
+/// Time allowed for the leader to respond
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct ReplicatorConfig {
+    /// URL of the RPC server of the leader, e.g. `http://10.0.0.1:5000`
+    pub leader_url: String,
+    /// Interval between polls of the leader once up to date
+    pub poll_interval: Duration,
+    /// Output directory for sparse roots JSON files
+    pub sink_config: SparseRootsSinkConfig,
+}
+
+/// MMR replicator, appending the leaves indexed by the leader
+pub struct Replicator {
+    /// Replicator configuration
+    config: ReplicatorConfig,
+    /// App client
+    app_client: AppClient,
+    /// Shutdown signal receiver
+    rx_shutdown: broadcast::Receiver<()>,
+    /// Block count of the local MMR, reported with the errors
+    block_count: Option<u32>,
+}
+
+impl Replicator {
+    pub fn new(
+        config: ReplicatorConfig,
+        app_client: AppClient,
+        rx_shutdown: broadcast::Receiver<()>,
+    ) -> Self {
+        Self {
+            config,
+            app_client,
+            rx_shutdown,
+            block_count: None,
+        }
+    }
+
+    async fn run_inner(&mut self) -> Result<(), anyhow::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let leader_url = self.config.leader_url.trim_end_matches('/').to_string();
+        info!("Replicating the MMR from {}", leader_url);
+
+        let mut sink = SparseRootsSink::new(self.config.sink_config.clone()).await?;
+
+        loop {
+            let block_count = self.app_client.get_block_count().await?;
+            self.block_count = Some(block_count);
+            let url = format!(
+                "{}/mmr-leaves/{}?count={}",
+                leader_url, block_count, MAX_LEAVES_PER_REQUEST
+            );
+            let res = tokio::select! {
+                res = fetch_leaves(&client, &url) => res,
+                _ = self.rx_shutdown.recv() => return Ok(()),
+            };
+            let up_to_date = match res {
+                Ok(mmr_leaves) => {
+                    let leaf_count = mmr_leaves.leaves.len() as u32;
+                    self.apply(block_count, mmr_leaves, &mut sink).await?;
+                    leaf_count < MAX_LEAVES_PER_REQUEST
+                }
+                // The leader may be restarting, retry at the next poll
+                Err(err) => {
+                    warn!("Failed to fetch the MMR leaves from {}: {}", url, err);
+                    true
+                }
+            };
+
+            if up_to_date {
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.poll_interval) => {},
+                    _ = self.rx_shutdown.recv() => return Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Append the leaves fetched from the leader to the local MMR of `block_count` blocks,
+    /// checking that both MMRs have the same roots before and after
+    ///
+    /// The leaves are first appended to the peaks of the local MMR in memory, and only
+    /// persisted if the resulting root matches the leader's: a faulty leader cannot corrupt the
+    /// local MMR.
+    async fn apply(
+        &mut self,
+        block_count: u32,
+        mmr_leaves: MmrLeaves,
+        sink: &mut SparseRootsSink,
+    ) -> Result<(), anyhow::Error> {
+        if mmr_leaves.block_count != block_count + mmr_leaves.leaves.len() as u32 {
+            anyhow::bail!(
+                "Leader returned {} leaves from block count {} up to {}",
+                mmr_leaves.leaves.len(),
+                block_count,
+                mmr_leaves.block_count
+            );
+        }
+        if mmr_leaves.leaves.is_empty() {
+            debug!("Up to date with the leader at block count {}", block_count);
+            return Ok(());
+        }
+        let mut peaks = self.peaks(block_count).await?;
+        let root_hash = peaks_root_hash(&peaks)?;
+        if root_hash != mmr_leaves.prev_root_hash {
+            anyhow::bail!(
+                "MMR diverged from the leader at block count {}: root {:?}, leader has {:?}",
+                block_count,
+                root_hash,
+                mmr_leaves.prev_root_hash
+            );
+        }
+
+        let leaves = hashes_from_hex(&mmr_leaves.leaves).map_err(|err| {
+            anyhow::anyhow!(
+                "Leader returned an invalid leaf after block count {}: {}",
+                block_count,
+                err
+            )
+        })?;
+        peaks.extend(&leaves);
+        let root_hash = peaks_root_hash(&peaks)?;
+        if root_hash != mmr_leaves.root_hash {
+            anyhow::bail!(
+                "MMR diverged from the leader at block count {}: root {:?} once the leaves \
+                 are appended, leader has {:?}",
+                mmr_leaves.block_count,
+                root_hash,
+                mmr_leaves.root_hash
+            );
+        }
+
+        let leaves = leaves.iter().map(hash_to_hex).collect();
+        for roots in self.app_client.add_leaves(leaves).await? {
+            sink.write_sparse_roots(&roots).await?;
+        }
+        self.block_count = Some(mmr_leaves.block_count);
+        info!(
+            "Replicated blocks {}..{}, root {}",
+            block_count,
+            mmr_leaves.block_count,
+            root_hash.unwrap_or_default()
+        );
+        Ok(())
+    }
+
+    /// Peaks of the local MMR state with `block_count` blocks
+    async fn peaks(&self, block_count: u32) -> Result<BlockPeaks, anyhow::Error> {
+        match block_count {
+            0 => Ok(BlockPeaks::default()),
+            _ => BlockPeaks::new(
+                &self.app_client.get_peaks(Some(block_count - 1)).await?,
+                block_count as usize,
+            ),
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), ()> {
+        match self.run_inner().await {
+            Err(err) => {
+                error!(
+                    block_count = self.block_count,
+                    "MMR replicator exited: {}", err
+                );
+                Err(())
+            }
+            Ok(()) => {
+                info!("MMR replicator terminated");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Root hash of an MMR state, `None` if it is empty
+fn peaks_root_hash(peaks: &BlockPeaks) -> Result<Option<String>, anyhow::Error> {
+    match peaks.leaf_count() {
+        0 => Ok(None),
+        _ => Ok(Some(peaks.root_hash()?)),
+    }
+}
+
+/// Fetch the MMR leaves from the leader
+async fn fetch_leaves(client: &reqwest::Client, url: &str) -> Result<MmrLeaves, reqwest::Error> {
+    client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
@@ -1,21 +1,51 @@
-//! HTTP RPC server providing REST endpoints for MMR proof generation and block count queries.
+//! HTTP RPC server providing REST endpoints for MMR proof generation and block count queries,
+//! and for the Bitcoin data and chain state proof needed to assemble compressed SPV proofs.
 
-use tokio::net::TcpListener;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path as FilePath, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{consensus, MerkleBlock, Txid};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::future::try_join_all;
 use tokio::sync::broadcast;
-use tracing::{error, info};
+use tracing::{debug, error, info, info_span, warn, Span};
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    routing::get,
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, MethodRouter},
     Json, Router,
 };
-use serde::Deserialize;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
 
-use raito_spv_core::{block_mmr::BlockInclusionProof, sparse_roots::SparseRoots};
+use raito_spv_core::{
+    bitcoin::{BitcoinClient, BitcoinClientError},
+    block_mmr::BlockConsistencyProof,
+    sparse_roots::SparseRoots,
+};
 
-use crate::app::AppClient;
+use crate::app::{AnchoredBlockProof, AppClient};
+use crate::chainstate::{
+    epoch_work_table, reconstruct_chain_state, ChainState, EpochWork, BLOCKS_PER_EPOCH,
+};
+use crate::diagnostics::pprof_router;
+use crate::listener::{self, ListenAddress};
+use crate::pipeline::PipelineMetrics;
+use crate::pregen::{read_anchor, RecentProofs};
+use crate::proof_cache::ProofCache;
 
 /// Query parameters for block inclusion proof generation and roots retrieval
 #[derive(Debug, Deserialize)]
@@ -23,10 +53,176 @@ pub struct ChainHeightQuery {
     pub chain_height: Option<u32>,
 }
 
+/// Query parameters for block inclusion proof generation, the MMR state is given either by
+/// its chain height or by its number of blocks
+#[derive(Debug, Deserialize)]
+pub struct BlockProofQuery {
+    pub chain_height: Option<u32>,
+    pub block_count: Option<u32>,
+}
+
+/// Maximum number of block headers returned by a single request, each header is a Bitcoin
+/// node request
+const MAX_HEADERS_PER_REQUEST: u32 = 144;
+
+/// Maximum number of MMR leaves returned by a single replication request
+pub const MAX_LEAVES_PER_REQUEST: u32 = 2016;
+
+/// Maximum number of difficulty epochs returned by a single work table request
+const MAX_EPOCHS_PER_REQUEST: u32 = 100;
+
+/// Header carrying the hex SHA-256 digest of the chain state proof, same as the Raito API
+const CONTENT_SHA256_HEADER: &str = "x-goog-meta-sha256";
+
+/// Maximum ratio between the decompressed and the uploaded size of a chain state proof
+const MAX_PROOF_COMPRESSION_RATIO: u64 = 32;
+
+/// Query parameters for block headers retrieval
+#[derive(Debug, Deserialize)]
+pub struct HeadersQuery {
+    pub count: Option<u32>,
+}
+
+/// Query parameters for the work table, a range of block heights
+#[derive(Debug, Deserialize)]
+pub struct WorkQuery {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+}
+
+/// Transaction inclusion data, as consensus-encoded hex strings
+#[derive(Debug, Serialize)]
+pub struct TransactionProof {
+    /// The transaction
+    pub transaction: String,
+    /// Partial Merkle tree with the branch of the transaction
+    pub transaction_proof: String,
+    /// Header of the block containing the transaction
+    pub block_header: String,
+    /// Height of the block containing the transaction
+    pub block_height: u32,
+}
+
+/// Chain state proof uploaded by a prover, in the format served to clients
+#[derive(Deserialize)]
+struct UploadedChainStateProof {
+    chainstate: ChainState,
+    /// The proof itself, only required to be present
+    #[serde(rename = "proof")]
+    _proof: IgnoredAny,
+}
+
+/// MMR leaves appended after a given block count, for replicas to catch up with this node
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MmrLeaves {
+    /// Root hash of the MMR state the leaves are appended to, `None` if it is empty
+    pub prev_root_hash: Option<String>,
+    /// The leaves (block header digests)
+    pub leaves: Vec<String>,
+    /// Number of blocks of the MMR state once the leaves are appended
+    pub block_count: u32,
+    /// Root hash of the MMR state once the leaves are appended, `None` if it is empty
+    pub root_hash: Option<String>,
+}
+
 /// Configuration for the RPC server
 pub struct RpcConfig {
-    /// Host and port binding for the RPC server (e.g., "127.0.0.1:5000")
-    pub rpc_host: String,
+    /// Addresses the RPC server listens on, e.g. "127.0.0.1:5000", "[::]:5000" or
+    /// "unix:/run/raito.sock", each with optional TLS
+    pub listen_addresses: Vec<ListenAddress>,
+    /// If set, block inclusion proofs are only generated at block counts that are multiples
+    /// of this interval, the MMR states for which chain state proofs are generated
+    pub checkpoint_interval: Option<u32>,
+    /// Bitcoin node the block headers and transaction proofs are served from, if any
+    pub bitcoin_client: Option<Arc<BitcoinClient>>,
+    /// Latest chain state proof served to clients (JSON, gzip-compressed if the extension is
+    /// `.gz`), if any
+    pub chain_state_proof_path: Option<PathBuf>,
+    /// Bearer token authorizing provers to upload new chain state proofs, uploads are
+    /// disabled if not set
+    pub ingest_token: Option<String>,
+    /// Redis cache of the block inclusion proofs shared with the other nodes, if any
+    pub proof_cache: Option<ProofCache>,
+    /// Inclusion proofs of the most recent blocks pre-generated at the MMR state of the latest
+    /// chain state proof
+    pub recent_proofs: RecentProofs,
+    /// Metrics of the indexer pipeline served at `/metrics`
+    pub pipeline_metrics: PipelineMetrics,
+    /// Address of the server exposing the CPU profiling endpoints, separate from the RPC
+    /// listeners since they are not authenticated; disabled if not set
+    pub pprof_address: Option<ListenAddress>,
+    /// Limits protecting the server against resource exhaustion
+    pub limits: RpcLimits,
+}
+
+/// Limits protecting the RPC server against resource exhaustion
+#[derive(Debug, Clone, Copy)]
+pub struct RpcLimits {
+    /// Maximum size of a request body in bytes, larger requests get a 413 Payload Too Large
+    pub max_body_size: usize,
+    /// Maximum size of an uploaded chain state proof in bytes, as sent
+    pub max_proof_size: usize,
+    /// Maximum time to respond to a request, including the wait for a slot, slower requests
+    /// get a 408 Request Timeout (the proof upload endpoint is exempt)
+    pub timeout: Duration,
+    /// Maximum number of requests to each endpoint processed at the same time, further
+    /// requests wait for a slot
+    pub max_concurrent_requests: usize,
+}
+
+/// Shared state of the RPC handlers
+#[derive(Clone)]
+pub struct RpcState {
+    app_client: AppClient,
+    checkpoint_interval: Option<u32>,
+    bitcoin_client: Option<Arc<BitcoinClient>>,
+    chain_state_proof_path: Option<Arc<PathBuf>>,
+    ingest_token: Option<Arc<String>>,
+    proof_cache: Option<ProofCache>,
+    recent_proofs: RecentProofs,
+    pipeline_metrics: PipelineMetrics,
+    served_proof: ServedProof,
+}
+
+/// Chain state proof file as last served, with its digest, so that the file is only read and
+/// hashed again once it changes
+#[derive(Clone, Default)]
+struct ServedProof(Arc<RwLock<Option<ServedProofFile>>>);
+
+/// Contents of the chain state proof file and the metadata they were read with
+struct ServedProofFile {
+    modified: SystemTime,
+    len: u64,
+    bytes: Bytes,
+    digest: String,
+}
+
+impl ServedProof {
+    /// Contents and digest of the proof file, read again if its metadata changed
+    async fn get(&self, path: &FilePath) -> Result<(Bytes, String), std::io::Error> {
+        let metadata = tokio::fs::metadata(path).await?;
+        let modified = metadata.modified()?;
+        if let Some(file) = self.0.read().unwrap().as_ref() {
+            if file.modified == modified && file.len == metadata.len() {
+                return Ok((file.bytes.clone(), file.digest.clone()));
+            }
+        }
+
+        let bytes = Bytes::from(tokio::fs::read(path).await?);
+        let digest = sha256::Hash::hash(&bytes).to_string();
+        *self.0.write().unwrap() = Some(ServedProofFile {
+            modified,
+            len: metadata.len(),
+            bytes: bytes.clone(),
+            digest: digest.clone(),
+        });
+        Ok((bytes, digest))
+    }
+
+    /// Read and hash the proof file again on the next request
+    fn invalidate(&self) {
+        *self.0.write().unwrap() = None;
+    }
 }
 
 /// HTTP RPC server that provides endpoints for MMR operations
@@ -50,21 +246,94 @@ impl RpcServer {
     }
 
     async fn run_inner(&self) -> Result<(), std::io::Error> {
-        info!("Starting RPC server on {}", self.config.rpc_host);
-
+        // Each endpoint has its own pool of slots, so that slow requests to one endpoint
+        // don't starve the others
+        let limits = self.config.limits;
+        let limited = |method_router: MethodRouter<RpcState>| {
+            method_router.layer(GlobalConcurrencyLimitLayer::new(
+                limits.max_concurrent_requests,
+            ))
+        };
+        let state = RpcState {
+            app_client: self.app_client.clone(),
+            checkpoint_interval: self.config.checkpoint_interval,
+            bitcoin_client: self.config.bitcoin_client.clone(),
+            chain_state_proof_path: self.config.chain_state_proof_path.clone().map(Arc::new),
+            ingest_token: self.config.ingest_token.clone().map(Arc::new),
+            proof_cache: self.config.proof_cache.clone(),
+            recent_proofs: self.config.recent_proofs.clone(),
+            pipeline_metrics: self.config.pipeline_metrics.clone(),
+            served_proof: ServedProof::default(),
+        };
         let app = Router::new()
-            .route("/block-inclusion-proof/:block_height", get(generate_proof))
-            .route("/head", get(get_head))
-            .route("/roots", get(get_roots))
-            .with_state(self.app_client.clone())
-            .layer(TraceLayer::new_for_http());
-
-        let listener = TcpListener::bind(&self.config.rpc_host).await?;
-        let mut rx_shutdown = self.rx_shutdown.resubscribe();
+            .route(
+                "/block-inclusion-proof/:block_height",
+                limited(get(generate_proof)),
+            )
+            .route(
+                "/block-consistency-proof/:prev_chain_height",
+                limited(get(generate_consistency_proof)),
+            )
+            .route("/head", limited(get(get_head)))
+            .route("/roots", limited(get(get_roots)))
+            .route(
+                "/block-headers/:start_height",
+                limited(get(get_block_headers)),
+            )
+            .route("/mmr-leaves/:start_height", limited(get(get_mmr_leaves)))
+            .route(
+                "/transaction-proof/:txid",
+                limited(get(get_transaction_proof)),
+            )
+            .route("/chainstate/:height", limited(get(get_chain_state)))
+            .route("/work", limited(get(get_work)))
+            .route("/metrics", limited(get(get_metrics)))
+            .route(
+                "/chainstate-proof/recent_proof",
+                limited(get(get_chain_state_proof)),
+            )
+            .with_state(state.clone())
+            .layer(TimeoutLayer::new(limits.timeout))
+            .layer(RequestBodyLimitLayer::new(limits.max_body_size));
+        // Chain state proofs are far larger than the other requests, and published one at a time
+        let app = app.merge(
+            Router::new()
+                .route(
+                    "/chainstate-proof",
+                    post(post_chain_state_proof).layer(GlobalConcurrencyLimitLayer::new(1)),
+                )
+                .with_state(state)
+                .layer(DefaultBodyLimit::disable())
+                .layer(RequestBodyLimitLayer::new(limits.max_proof_size)),
+        );
+        // Requests are tagged with an ID, returned in the `x-request-id` header, so that the
+        // reported errors can be traced back to them
+        let app = app
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(request_span)
+                    .on_response(log_response)
+                    .on_failure(()),
+            )
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
-        axum::serve(listener, app)
-            .with_graceful_shutdown(async move { rx_shutdown.recv().await.unwrap_or_default() })
-            .await
+        // The server exits if any listener fails
+        let rpc =
+            try_join_all(self.config.listen_addresses.iter().map(|address| {
+                listener::serve(address, app.clone(), self.rx_shutdown.resubscribe())
+            }));
+        let pprof = async {
+            match &self.config.pprof_address {
+                Some(address) => {
+                    info!("CPU profiling enabled at {}/debug/pprof/profile", address);
+                    listener::serve(address, pprof_router(), self.rx_shutdown.resubscribe()).await
+                }
+                None => Ok(()),
+            }
+        };
+        tokio::try_join!(rpc, pprof)?;
+        Ok(())
     }
 
     pub async fn run(&self) -> Result<(), ()> {
@@ -81,22 +350,202 @@ impl RpcServer {
     }
 }
 
+/// Span of an RPC request, with its ID
+fn request_span(request: &Request<Body>) -> Span {
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = request_id(request.headers()),
+    )
+}
+
+/// Log the response to an RPC request, server errors are logged as errors with the request ID
+fn log_response(response: &Response, latency: Duration, _span: &Span) {
+    let status = response.status();
+    let latency_ms = latency.as_millis() as u64;
+    if status.is_server_error() {
+        error!(
+            request_id = request_id(response.headers()),
+            status = status.as_u16(),
+            latency_ms,
+            "RPC request failed with {}",
+            status
+        );
+    } else {
+        debug!(status = status.as_u16(), latency_ms, "RPC request finished");
+    }
+}
+
+/// ID of a request, as set in the `x-request-id` header
+fn request_id(headers: &HeaderMap) -> &str {
+    headers
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default()
+}
+
 /// Generate an inclusion proof for a block at the specified height
 ///
 /// # Arguments
 /// * `block_height` - The block height to generate a proof for
 /// * `chain_height` - The chain (MMR) height to generate a proof for (optional)
+/// * `block_count` - The number of blocks of the MMR state, same as `chain_height + 1` (optional)
+///
+/// With a checkpoint interval, the MMR state must be a checkpoint, and defaults to the latest
+/// checkpoint instead of the latest state. The proofs pre-generated at the MMR state of the
+/// latest chain state proof are served as is, others are looked up in the proof cache, if
+/// any, before being generated.
 ///
 /// # Returns
-/// * `Json<InclusionProof>` - The inclusion proof in JSON format
+/// * `Json<AnchoredBlockProof>` - The inclusion proof in JSON format, with the root of the MMR
+///   state it was generated against, its generation time and the version of the node
+/// * `StatusCode::BAD_REQUEST` - If the MMR state is not a checkpoint or is ambiguous
 /// * `StatusCode::INTERNAL_SERVER_ERROR` - If proof generation fails
 pub async fn generate_proof(
-    State(app_client): State<AppClient>,
+    State(state): State<RpcState>,
     Path(block_height): Path<u32>,
+    Query(query): Query<BlockProofQuery>,
+) -> Result<Json<AnchoredBlockProof>, StatusCode> {
+    let block_count = match state.checkpoint_interval {
+        Some(_) => Some(
+            state
+                .app_client
+                .get_block_count()
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        ),
+        None => None,
+    };
+    let chain_height = anchored_chain_height(&query, state.checkpoint_interval, block_count)
+        .map_err(|err| {
+            warn!("Rejected block inclusion proof request: {}", err);
+            StatusCode::BAD_REQUEST
+        })?;
+    let proof = anchored_block_proof(
+        &state.app_client,
+        state.proof_cache.as_ref(),
+        &state.recent_proofs,
+        block_height,
+        chain_height,
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(proof))
+}
+
+/// Get a block inclusion proof with the root of its MMR state: pre-generated, from the shared
+/// cache, or generated (and cached)
+///
+/// The latest MMR state is pinned before the root is computed, so that the root and the cache
+/// key match the generated proof even if a block is indexed meanwhile.
+async fn anchored_block_proof(
+    app_client: &AppClient,
+    proof_cache: Option<&ProofCache>,
+    recent_proofs: &RecentProofs,
+    block_height: u32,
+    chain_height: Option<u32>,
+) -> Result<AnchoredBlockProof, anyhow::Error> {
+    let latest_block_count = app_client.get_block_count().await?;
+    let block_count = match chain_height {
+        Some(chain_height) if chain_height >= latest_block_count => anyhow::bail!(
+            "Chain height {} is not indexed yet, the latest is {}",
+            chain_height,
+            latest_block_count as i64 - 1
+        ),
+        Some(chain_height) => chain_height + 1,
+        None if latest_block_count == 0 => anyhow::bail!("No block is indexed yet"),
+        None => latest_block_count,
+    };
+    if let Some(proof) = recent_proofs.get(block_height, block_count) {
+        return Ok(proof);
+    }
+    let mmr_root = app_client.get_root_hash(Some(block_count - 1)).await?;
+    if let Some(proof_cache) = proof_cache {
+        if let Some(proof) = proof_cache.get(block_height, block_count, &mmr_root).await {
+            return Ok(proof);
+        }
+    }
+
+    let proof = app_client
+        .generate_block_proof(block_height, Some(block_count - 1))
+        .await?;
+    let proof = AnchoredBlockProof::new(proof, mmr_root);
+    if let Some(proof_cache) = proof_cache {
+        proof_cache.set(&proof).await;
+    }
+    Ok(proof)
+}
+
+/// Chain height of the MMR state to generate a block inclusion proof at, `None` for the
+/// latest state
+///
+/// - `checkpoint_interval`: Interval of the block counts with chain state proofs, if pinned
+/// - `block_count`: Current number of blocks in the MMR, required if pinned
+fn anchored_chain_height(
+    query: &BlockProofQuery,
+    checkpoint_interval: Option<u32>,
+    block_count: Option<u32>,
+) -> Result<Option<u32>, anyhow::Error> {
+    // Block count of the requested chain height, the MMR state the proof is anchored to
+    let chain_block_count = query
+        .chain_height
+        .map(|chain_height| {
+            chain_height
+                .checked_add(1)
+                .ok_or_else(|| anyhow::anyhow!("chain height {} is out of range", chain_height))
+        })
+        .transpose()?;
+    // Requested block count, positive
+    let requested = match (chain_block_count, query.block_count) {
+        (Some(chain_block_count), Some(block_count)) if chain_block_count != block_count => {
+            anyhow::bail!(
+                "chain height {} and block count {} don't match",
+                chain_block_count - 1,
+                block_count
+            )
+        }
+        (_, Some(0)) => anyhow::bail!("block count must be positive"),
+        (chain_block_count, block_count) => chain_block_count.or(block_count),
+    };
+    let Some(interval) = checkpoint_interval else {
+        return Ok(requested.map(|count| count - 1));
+    };
+
+    match requested {
+        Some(block_count) if !block_count.is_multiple_of(interval) => anyhow::bail!(
+            "block count {} is not a multiple of the checkpoint interval {}",
+            block_count,
+            interval
+        ),
+        Some(block_count) => Ok(Some(block_count - 1)),
+        None => {
+            // Snap the latest state down to the latest checkpoint
+            let checkpoint = block_count.unwrap_or_default() / interval * interval;
+            if checkpoint == 0 {
+                anyhow::bail!("no checkpoint is indexed yet");
+            }
+            Ok(Some(checkpoint - 1))
+        }
+    }
+}
+
+/// Generate a consistency proof between an earlier MMR state and the current one
+///
+/// # Arguments
+/// * `prev_chain_height` - The chain (MMR) height of the earlier state
+/// * `chain_height` - The chain (MMR) height of the later state (optional)
+///
+/// # Returns
+/// * `Json<BlockConsistencyProof>` - The consistency proof in JSON format
+/// * `StatusCode::INTERNAL_SERVER_ERROR` - If proof generation fails
+pub async fn generate_consistency_proof(
+    State(RpcState { app_client, .. }): State<RpcState>,
+    Path(prev_chain_height): Path<u32>,
     Query(query): Query<ChainHeightQuery>,
-) -> Result<Json<BlockInclusionProof>, StatusCode> {
+) -> Result<Json<BlockConsistencyProof>, StatusCode> {
     let proof = app_client
-        .generate_block_proof(block_height, query.chain_height)
+        .generate_consistency_proof(prev_chain_height, query.chain_height)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(proof))
@@ -111,7 +560,7 @@ pub async fn generate_proof(
 /// * `Json<SparseRoots>` - The sparse roots in JSON format
 /// * `StatusCode::INTERNAL_SERVER_ERROR` - If getting roots fails
 pub async fn get_roots(
-    State(app_client): State<AppClient>,
+    State(RpcState { app_client, .. }): State<RpcState>,
     Query(query): Query<ChainHeightQuery>,
 ) -> Result<Json<SparseRoots>, StatusCode> {
     let sparse_roots = app_client
@@ -121,15 +570,457 @@ pub async fn get_roots(
     Ok(Json(sparse_roots))
 }
 
+/// Get the metrics of the indexer pipeline stages in the Prometheus text format
+pub async fn get_metrics(
+    State(RpcState {
+        pipeline_metrics, ..
+    }): State<RpcState>,
+) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        pipeline_metrics.render(),
+    )
+}
+
 /// Get the current head (latest processed block height) from the MMR
 ///
 /// # Returns
 /// * `Json<u32>` - The current block count in JSON format
 /// * `StatusCode::INTERNAL_SERVER_ERROR` - If getting block count fails
-pub async fn get_head(State(app_client): State<AppClient>) -> Result<Json<u32>, StatusCode> {
+pub async fn get_head(
+    State(RpcState { app_client, .. }): State<RpcState>,
+) -> Result<Json<u32>, StatusCode> {
     let block_count = app_client
         .get_block_count()
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(block_count - 1))
 }
+
+/// Get the headers of consecutive indexed blocks from the Bitcoin node
+///
+/// # Arguments
+/// * `start_height` - The height of the first block
+/// * `count` - The number of headers, up to 144 (optional, defaults to 1)
+///
+/// Headers past the latest indexed block are not returned.
+///
+/// # Returns
+/// * `Json<Vec<String>>` - The consensus-encoded headers in hex
+/// * `StatusCode::NOT_FOUND` - If the first block is not indexed yet
+/// * `StatusCode::BAD_REQUEST` - If the count is zero or above the limit
+/// * `StatusCode::SERVICE_UNAVAILABLE` - If no Bitcoin RPC is configured
+/// * `StatusCode::BAD_GATEWAY` - If the Bitcoin node request fails
+pub async fn get_block_headers(
+    State(RpcState {
+        app_client,
+        bitcoin_client,
+        ..
+    }): State<RpcState>,
+    Path(start_height): Path<u32>,
+    Query(query): Query<HeadersQuery>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let bitcoin_client = bitcoin_client.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let count = query.count.unwrap_or(1);
+    if count == 0 || count > MAX_HEADERS_PER_REQUEST {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let block_count = app_client
+        .get_block_count()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if start_height >= block_count {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut headers = Vec::new();
+    for height in start_height..block_count.min(start_height.saturating_add(count)) {
+        let (header, _) = bitcoin_client
+            .get_block_header_by_height(height)
+            .await
+            .map_err(|err| bitcoin_error_status("block header", &err))?;
+        headers.push(consensus::encode::serialize_hex(&header));
+    }
+    Ok(Json(headers))
+}
+
+/// Get the MMR leaves of consecutive indexed blocks, with the roots of the MMR states before
+/// and after them, so that a replica with the first `start_height` blocks can append them and
+/// verify that it reaches the same state
+///
+/// # Arguments
+/// * `start_height` - The height of the first block, the block count of the replica
+/// * `count` - The maximum number of leaves, up to 2016 (optional, defaults to 2016)
+///
+/// Leaves past the latest indexed block are not returned, none if the replica is up to date.
+///
+/// # Returns
+/// * `Json<MmrLeaves>` - The leaves and the MMR roots
+/// * `StatusCode::NOT_FOUND` - If the replica has more blocks than this node
+/// * `StatusCode::BAD_REQUEST` - If the count is zero or above the limit
+/// * `StatusCode::INTERNAL_SERVER_ERROR` - If reading the MMR fails
+pub async fn get_mmr_leaves(
+    State(RpcState { app_client, .. }): State<RpcState>,
+    Path(start_height): Path<u32>,
+    Query(query): Query<HeadersQuery>,
+) -> Result<Json<MmrLeaves>, StatusCode> {
+    let count = query.count.unwrap_or(MAX_LEAVES_PER_REQUEST);
+    if count == 0 || count > MAX_LEAVES_PER_REQUEST {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // The state is pinned to the current block count, blocks indexed meanwhile are ignored
+    let latest_block_count = app_client
+        .get_block_count()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if start_height > latest_block_count {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let block_count = latest_block_count.min(start_height.saturating_add(count));
+
+    let mmr_leaves = MmrLeaves {
+        prev_root_hash: root_hash_at(&app_client, start_height).await?,
+        leaves: app_client
+            .get_leaves(start_height, block_count - start_height)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        block_count,
+        root_hash: root_hash_at(&app_client, block_count).await?,
+    };
+    Ok(Json(mmr_leaves))
+}
+
+/// Root hash of the MMR state with `block_count` blocks, `None` if it is empty
+async fn root_hash_at(
+    app_client: &AppClient,
+    block_count: u32,
+) -> Result<Option<String>, StatusCode> {
+    match block_count {
+        0 => Ok(None),
+        _ => app_client
+            .get_root_hash(Some(block_count - 1))
+            .await
+            .map(Some)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Get the inclusion data of a confirmed transaction from the Bitcoin node
+///
+/// # Arguments
+/// * `txid` - The ID of the transaction
+///
+/// # Returns
+/// * `Json<TransactionProof>` - The transaction, its Merkle branch and its block
+/// * `StatusCode::NOT_FOUND` - If the transaction is not confirmed
+/// * `StatusCode::SERVICE_UNAVAILABLE` - If no Bitcoin RPC is configured
+/// * `StatusCode::BAD_GATEWAY` - If the Bitcoin node request fails
+pub async fn get_transaction_proof(
+    State(RpcState { bitcoin_client, .. }): State<RpcState>,
+    Path(txid): Path<Txid>,
+) -> Result<Json<TransactionProof>, StatusCode> {
+    let bitcoin_client = bitcoin_client.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let MerkleBlock { header, txn } = bitcoin_client
+        .get_transaction_inclusion_proof(&txid)
+        .await
+        .map_err(|err| bitcoin_error_status("transaction proof", &err))?;
+    let block_hash = header.block_hash();
+    let transaction = bitcoin_client
+        .get_transaction(&txid, &block_hash)
+        .await
+        .map_err(|err| bitcoin_error_status("transaction", &err))?;
+    let block_header_ex = bitcoin_client
+        .get_block_header_ex(&block_hash)
+        .await
+        .map_err(|err| bitcoin_error_status("block header", &err))?;
+    Ok(Json(TransactionProof {
+        transaction: consensus::encode::serialize_hex(&transaction),
+        transaction_proof: consensus::encode::serialize_hex(&txn),
+        block_header: consensus::encode::serialize_hex(&header),
+        block_height: block_header_ex.height as u32,
+    }))
+}
+
+/// Reconstruct the chain state as of an indexed block from the Bitcoin node
+///
+/// # Arguments
+/// * `height` - The height of the block
+///
+/// # Returns
+/// * `Json<ChainState>` - The chain state after the block, same format as in chain state proofs
+/// * `StatusCode::NOT_FOUND` - If the block is not indexed yet
+/// * `StatusCode::SERVICE_UNAVAILABLE` - If no Bitcoin RPC is configured
+/// * `StatusCode::BAD_GATEWAY` - If the Bitcoin node request fails
+pub async fn get_chain_state(
+    State(RpcState {
+        app_client,
+        bitcoin_client,
+        ..
+    }): State<RpcState>,
+    Path(height): Path<u32>,
+) -> Result<Json<ChainState>, StatusCode> {
+    let bitcoin_client = bitcoin_client.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let block_count = app_client
+        .get_block_count()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if height >= block_count {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let chain_state = reconstruct_chain_state(&bitcoin_client, height)
+        .await
+        .map_err(|err| bitcoin_error_status("chain state", &err))?;
+    Ok(Json(chain_state))
+}
+
+/// Get the target and cumulative work of each difficulty epoch in a range of indexed blocks,
+/// from the Bitcoin node
+///
+/// # Arguments
+/// * `from` - The height of the first block (optional, defaults to 0)
+/// * `to` - The height of the last block (optional, defaults to the latest indexed block)
+///
+/// Heights past the latest indexed block are not included.
+///
+/// # Returns
+/// * `Json<Vec<EpochWork>>` - The work of each epoch in the range, the earliest first
+/// * `StatusCode::NOT_FOUND` - If the first block is not indexed yet
+/// * `StatusCode::BAD_REQUEST` - If the range is empty or spans more than 100 epochs
+/// * `StatusCode::SERVICE_UNAVAILABLE` - If no Bitcoin RPC is configured
+/// * `StatusCode::BAD_GATEWAY` - If the Bitcoin node request fails
+pub async fn get_work(
+    State(RpcState {
+        app_client,
+        bitcoin_client,
+        ..
+    }): State<RpcState>,
+    Query(query): Query<WorkQuery>,
+) -> Result<Json<Vec<EpochWork>>, StatusCode> {
+    let bitcoin_client = bitcoin_client.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let block_count = app_client
+        .get_block_count()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let from = query.from.unwrap_or(0);
+    if from >= block_count {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let to = query.to.unwrap_or(u32::MAX).min(block_count - 1);
+    if from > to || to / BLOCKS_PER_EPOCH - from / BLOCKS_PER_EPOCH >= MAX_EPOCHS_PER_REQUEST {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let table = epoch_work_table(&bitcoin_client, from, to)
+        .await
+        .map_err(|err| bitcoin_error_status("work table", &err))?;
+    Ok(Json(table))
+}
+
+/// Get the latest chain state proof, as provided by the operator
+///
+/// The proof is kept in memory along with its digest until the file changes.
+///
+/// # Returns
+/// * The chain state proof in JSON, gzip-encoded if the file is compressed, with its SHA-256
+///   digest in the `x-goog-meta-sha256` header
+/// * `StatusCode::NOT_FOUND` - If no chain state proof is configured
+/// * `StatusCode::INTERNAL_SERVER_ERROR` - If the chain state proof cannot be read
+pub async fn get_chain_state_proof(
+    State(RpcState {
+        chain_state_proof_path,
+        served_proof,
+        ..
+    }): State<RpcState>,
+) -> Result<Response, StatusCode> {
+    let path = chain_state_proof_path.ok_or(StatusCode::NOT_FOUND)?;
+    let (bytes, digest) = served_proof.get(&path).await.map_err(|err| {
+        error!(
+            "Failed to read chain state proof {}: {}",
+            path.display(),
+            err
+        );
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::HeaderName::from_static(CONTENT_SHA256_HEADER),
+                digest,
+            ),
+        ],
+        bytes,
+    )
+        .into_response();
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        response.headers_mut().insert(
+            header::CONTENT_ENCODING,
+            header::HeaderValue::from_static("gzip"),
+        );
+    }
+    Ok(response)
+}
+
+/// Publish a new chain state proof uploaded by a prover, replacing the file served at
+/// `/chainstate-proof/recent_proof`
+///
+/// The body is the chain state proof in the served JSON format, gzip-compressed if sent with
+/// `Content-Encoding: gzip`, and the request is authorized by the ingestion token as a bearer
+/// token. The proof must be more recent than the published one, and its chain state must be
+/// the one of the Bitcoin node at its height, if a node is configured.
+///
+/// # Returns
+/// * `StatusCode::NO_CONTENT` - If the proof is published
+/// * `StatusCode::NOT_FOUND` - If no ingestion token or chain state proof is configured
+/// * `StatusCode::UNAUTHORIZED` - If the ingestion token is missing or wrong
+/// * `StatusCode::BAD_REQUEST` - If the body is not a chain state proof
+/// * `StatusCode::CONFLICT` - If the proof is not more recent than the published one
+/// * `StatusCode::UNPROCESSABLE_ENTITY` - If the chain state doesn't match the Bitcoin node
+/// * `StatusCode::INTERNAL_SERVER_ERROR` - If the proof cannot be written
+pub async fn post_chain_state_proof(
+    State(RpcState {
+        bitcoin_client,
+        chain_state_proof_path,
+        ingest_token,
+        served_proof,
+        ..
+    }): State<RpcState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+    let (Some(path), Some(ingest_token)) = (chain_state_proof_path, ingest_token) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    if !is_authorized(&headers, &ingest_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let gzip = headers
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding == "gzip");
+
+    let published_path = path.clone();
+    let (json, chain_state, published) = tokio::task::spawn_blocking(move || {
+        let (json, chain_state) = decode_chain_state_proof(&body, gzip)?;
+        // A missing or unreadable published proof is replaced by any proof
+        let published = read_anchor(&published_path).ok();
+        Ok::<_, anyhow::Error>((json, chain_state, published))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|err| {
+        warn!("Rejected chain state proof upload: {}", err);
+        StatusCode::BAD_REQUEST
+    })?;
+    let height = chain_state.block_height;
+    if let Some(block_count) = published.filter(|block_count| height < *block_count) {
+        warn!(
+            "Rejected chain state proof at height {}, the published one is at height {}",
+            height,
+            block_count - 1
+        );
+        return Err(StatusCode::CONFLICT);
+    }
+    if let Some(bitcoin_client) = bitcoin_client {
+        let expected = reconstruct_chain_state(&bitcoin_client, height)
+            .await
+            .map_err(|err| {
+                if err.is_rejected() {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                } else {
+                    bitcoin_error_status("chain state", &err)
+                }
+            })?;
+        if expected != chain_state {
+            warn!(
+                "Rejected chain state proof at height {}, its chain state doesn't match the \
+                 Bitcoin node: {:?}, expected {:?}",
+                height, chain_state, expected
+            );
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+
+    let written_path = path.clone();
+    tokio::task::spawn_blocking(move || write_chain_state_proof(&written_path, &json))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|err| {
+            error!(
+                "Failed to write chain state proof {}: {}",
+                path.display(),
+                err
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    served_proof.invalidate();
+    info!("Published chain state proof at height {}", height);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Whether a request carries the ingestion token as a bearer token, compared in constant time
+fn is_authorized(headers: &HeaderMap, ingest_token: &str) -> bool {
+    let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    token.len() == ingest_token.len()
+        && token
+            .bytes()
+            .zip(ingest_token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Decode an uploaded chain state proof into its JSON and its chain state, decompressing it if
+/// `gzip` up to [MAX_PROOF_COMPRESSION_RATIO] times its size
+fn decode_chain_state_proof(
+    body: &[u8],
+    gzip: bool,
+) -> Result<(Vec<u8>, ChainState), anyhow::Error> {
+    let json = if gzip {
+        let max_size = body.len() as u64 * MAX_PROOF_COMPRESSION_RATIO;
+        let mut json = Vec::new();
+        GzDecoder::new(body)
+            .take(max_size + 1)
+            .read_to_end(&mut json)?;
+        if json.len() as u64 > max_size {
+            anyhow::bail!("Decompressed proof larger than {} bytes", max_size);
+        }
+        json
+    } else {
+        body.to_vec()
+    };
+    let proof: UploadedChainStateProof = serde_json::from_slice(&json)?;
+    Ok((json, proof.chainstate))
+}
+
+/// Write a chain state proof to the served file, gzip-compressed if the extension is `.gz`,
+/// through a temporary file so that readers never see a partial proof
+fn write_chain_state_proof(path: &FilePath, json: &[u8]) -> Result<(), std::io::Error> {
+    let tmp_path = path.with_extension("tmp");
+    let file = File::create(&tmp_path)?;
+    let file = if path.extension().is_some_and(|extension| extension == "gz") {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(json)?;
+        encoder.finish()?
+    } else {
+        let mut file = file;
+        file.write_all(json)?;
+        file
+    };
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Status of a failed Bitcoin node request: not found if the node rejected it, e.g. for an
+/// unknown transaction, bad gateway if the node is unreachable
+fn bitcoin_error_status(what: &str, err: &BitcoinClientError) -> StatusCode {
+    if err.is_rejected() {
+        StatusCode::NOT_FOUND
+    } else {
+        warn!("Failed to get {} from the Bitcoin node: {}", what, err);
+        StatusCode::BAD_GATEWAY
+    }
+}
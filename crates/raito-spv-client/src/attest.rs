@@ -0,0 +1,231 @@
+//! Signed attestations of a successful verification, so that downstream systems can rely on
+//! the output of a verifier service without re-verifying the heavy proof themselves.
+
+use std::path::{Path, PathBuf};
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{schnorr::Signature, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::{BlockHash, Txid};
+use chrono::Utc;
+use raito_spv_verifier::verify::{CheckStatus, VerificationReport};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Version of the attestation statement format
+const STATEMENT_VERSION: u32 = 1;
+
+/// CLI arguments for signing an attestation of the verification result
+#[derive(Clone, Debug, clap::Args)]
+pub struct AttestArgs {
+    /// Path to a file containing the secp256k1 secret key (hex string) used to sign
+    /// the attestation
+    #[arg(
+        long,
+        requires = "attestation_out",
+        conflicts_with_all = ["bundle", "proof_dir"]
+    )]
+    attest_key: Option<PathBuf>,
+    /// Path to write the signed attestation (JSON) to
+    #[arg(long, requires = "attest_key")]
+    attestation_out: Option<PathBuf>,
+}
+
+/// CLI arguments for the `verify-attestation` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct VerifyAttestationArgs {
+    /// Path to the attestation (JSON)
+    #[arg(long)]
+    attestation_path: PathBuf,
+    /// Expected x-only public key of the verifier (hex string)
+    #[arg(long)]
+    attester: Option<XOnlyPublicKey>,
+}
+
+/// Statement that a transaction was verified against a proven chain state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationStatement {
+    /// Version of the statement format
+    pub version: u32,
+    /// ID of the verified transaction
+    pub txid: Txid,
+    /// Hash of the block containing the transaction
+    pub block_hash: BlockHash,
+    /// Height of the block containing the transaction
+    pub block_height: u32,
+    /// Height of the best block in the proven chain state
+    pub chain_height: u32,
+    /// Hash of the best block in the proven chain state
+    pub best_block_hash: BlockHash,
+    /// Block MMR root committed by the chain state proof
+    pub block_mmr_root: String,
+    /// Unix timestamp of the verification
+    pub verified_at: i64,
+}
+
+/// Attestation statement signed with a BIP340 Schnorr signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attestation {
+    /// The attested statement
+    pub statement: AttestationStatement,
+    /// X-only public key of the verifier
+    pub public_key: XOnlyPublicKey,
+    /// Signature of the SHA-256 of the JSON-serialized statement
+    pub signature: Signature,
+}
+
+impl AttestArgs {
+    /// Sign an attestation of the verification report and write it to the output file,
+    /// if requested
+    pub fn run(&self, report: &VerificationReport) -> Result<(), anyhow::Error> {
+        let (Some(key_path), Some(out_path)) = (&self.attest_key, &self.attestation_out) else {
+            return Ok(());
+        };
+        let keypair = load_keypair(key_path)?;
+        let statement = AttestationStatement::new(report, Utc::now().timestamp())?;
+        let attestation = Attestation::sign(statement, &keypair)?;
+        std::fs::write(out_path, serde_json::to_string_pretty(&attestation)?)?;
+        info!(
+            "Attestation signed by {} written to {}",
+            attestation.public_key,
+            out_path.display()
+        );
+        Ok(())
+    }
+}
+
+impl AttestationStatement {
+    /// Statement of a verification report, refused if any check was skipped (e.g. with
+    /// `--skip-stark` or a development proof) since the statement doesn't record the policy
+    ///
+    /// - `verified_at`: Unix timestamp of the verification
+    pub fn new(report: &VerificationReport, verified_at: i64) -> Result<Self, anyhow::Error> {
+        let skipped: Vec<_> = report
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Skipped)
+            .map(|check| check.name)
+            .collect();
+        if !skipped.is_empty() {
+            anyhow::bail!(
+                "Refusing to attest a verification with skipped checks: {}",
+                skipped.join(", ")
+            );
+        }
+        Ok(Self {
+            version: STATEMENT_VERSION,
+            txid: report.txid,
+            block_hash: report.block_hash,
+            block_height: report.block_height,
+            chain_height: report.chain_height,
+            best_block_hash: report.best_block_hash,
+            block_mmr_root: report.block_mmr_root.clone(),
+            verified_at,
+        })
+    }
+
+    /// Message signed by the verifier
+    fn message(&self) -> Result<Message, anyhow::Error> {
+        let digest = sha256::Hash::hash(&serde_json::to_vec(self)?);
+        Ok(Message::from_digest(digest.to_byte_array()))
+    }
+}
+
+impl Attestation {
+    /// Sign the statement with the verifier key
+    pub fn sign(statement: AttestationStatement, keypair: &Keypair) -> Result<Self, anyhow::Error> {
+        let secp = Secp256k1::signing_only();
+        let signature = secp.sign_schnorr_no_aux_rand(&statement.message()?, keypair);
+        Ok(Self {
+            statement,
+            public_key: keypair.x_only_public_key().0,
+            signature,
+        })
+    }
+
+    /// Check the signature of the statement
+    pub fn verify(&self) -> Result<(), anyhow::Error> {
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &self.signature,
+                &self.statement.message()?,
+                &self.public_key,
+            )
+            .map_err(|e| anyhow::anyhow!("Invalid attestation signature: {}", e))
+    }
+}
+
+/// Run the `verify-attestation` subcommand: check the signature of an attestation and
+/// print the attested statement
+pub async fn run(args: VerifyAttestationArgs) -> Result<(), anyhow::Error> {
+    let attestation: Attestation = serde_json::from_slice(&std::fs::read(&args.attestation_path)?)?;
+    if let Some(attester) = args.attester {
+        if attestation.public_key != attester {
+            anyhow::bail!(
+                "Attestation is signed by {}, expected {}",
+                attestation.public_key,
+                attester
+            );
+        }
+    }
+    attestation.verify()?;
+
+    let AttestationStatement {
+        txid,
+        block_height,
+        chain_height,
+        best_block_hash,
+        verified_at,
+        ..
+    } = &attestation.statement;
+    println!(
+        "Verifier {} attests that transaction {} at height {} was verified against the chain state at height {} (best block {}) at {}",
+        attestation.public_key,
+        txid,
+        block_height,
+        chain_height,
+        best_block_hash,
+        chrono::DateTime::from_timestamp(*verified_at, 0)
+            .map_or_else(|| verified_at.to_string(), |time| time.to_rfc3339())
+    );
+    Ok(())
+}
+
+/// Load a secp256k1 key pair from a file containing the secret key as a hex string
+pub fn load_keypair(path: &Path) -> Result<Keypair, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    Keypair::from_seckey_str(&Secp256k1::signing_only(), contents.trim())
+        .map_err(|e| anyhow::anyhow!("Invalid attestation key in {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attestation() {
+        let secp = Secp256k1::signing_only();
+        let keypair = Keypair::from_seckey_str(
+            &secp,
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let statement = AttestationStatement {
+            version: STATEMENT_VERSION,
+            txid: Txid::all_zeros(),
+            block_hash: BlockHash::all_zeros(),
+            block_height: 100,
+            chain_height: 110,
+            best_block_hash: BlockHash::all_zeros(),
+            block_mmr_root: "0x01".to_string(),
+            verified_at: 1_700_000_000,
+        };
+
+        let attestation = Attestation::sign(statement, &keypair).unwrap();
+        let json = serde_json::to_string(&attestation).unwrap();
+        let mut attestation: Attestation = serde_json::from_str(&json).unwrap();
+        assert!(attestation.verify().is_ok());
+
+        attestation.statement.block_height += 1;
+        assert!(attestation.verify().is_err());
+    }
+}
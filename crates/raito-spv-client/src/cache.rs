@@ -0,0 +1,189 @@
+//! On-disk cache of the most recent chain state proof.
+//!
+//! The chain state proof only changes once per proving interval and is by far the largest
+//! component of a compressed proof, so consecutive `fetch` invocations can reuse it.
+
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use raito_spv_verifier::fetch::{ApiEndpoint, ChainStateProof};
+use tracing::{info, warn};
+
+use crate::fetch::fetch_chain_state_proof;
+
+/// File name prefix of the cached chain state proofs, followed by the chain height
+const CACHE_FILE_PREFIX: &str = "chainstate-proof-";
+/// File name extension of the cached chain state proofs (bincode binary codec)
+const CACHE_FILE_EXTENSION: &str = "bin";
+
+/// CLI arguments controlling the chain state proof cache
+#[derive(Clone, Debug, clap::Args)]
+pub struct CacheArgs {
+    /// Directory to cache the chain state proof in (defaults to the user cache directory)
+    #[arg(long, env = "RAITO_SPV_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Maximum age in seconds of a cached chain state proof to be reused
+    #[arg(long, default_value = "600")]
+    cache_max_age: u64,
+    /// Always fetch a fresh chain state proof and do not update the cache
+    #[arg(long, default_value = "false")]
+    no_cache: bool,
+}
+
+impl CacheArgs {
+    /// Build the cache from the CLI arguments, `None` if caching is disabled or no cache
+    /// directory is available on this platform
+    pub fn cache(&self) -> Option<ChainStateCache> {
+        if self.no_cache {
+            return None;
+        }
+        let dir = self
+            .cache_dir
+            .clone()
+            .or_else(|| dirs::cache_dir().map(|dir| dir.join("raito-spv-client")))?;
+        Some(ChainStateCache::new(
+            dir,
+            Duration::from_secs(self.cache_max_age),
+        ))
+    }
+}
+
+/// Cache holding the most recent chain state proof, keyed by its chain height
+#[derive(Clone, Debug)]
+pub struct ChainStateCache {
+    /// Directory the cached proof is stored in
+    dir: PathBuf,
+    /// Maximum age of a cached proof to be reused
+    max_age: Duration,
+}
+
+impl ChainStateCache {
+    /// Create a new cache stored in `dir`, reusing proofs not older than `max_age`
+    pub fn new(dir: PathBuf, max_age: Duration) -> Self {
+        Self { dir, max_age }
+    }
+
+    /// Load the most recent cached chain state proof if it is fresh enough
+    ///
+    /// Returns `None` if there is no cached proof, or it is stale or unreadable.
+    pub fn load(&self) -> Option<ChainStateProof> {
+        let (height, path) = self.cached_files().into_iter().max_by_key(|(h, _)| *h)?;
+
+        let age = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        if age > self.max_age {
+            info!(
+                "Cached chain state proof at height {} is stale ({}s old)",
+                height,
+                age.as_secs()
+            );
+            return None;
+        }
+
+        let proof = std::fs::File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(bincode::deserialize_from(BufReader::new(file))?));
+        match proof {
+            Ok(proof) => {
+                info!(
+                    "Using cached chain state proof at height {} ({}s old)",
+                    height,
+                    age.as_secs()
+                );
+                Some(proof)
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to read cached chain state proof {}: {}",
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Store a chain state proof, replacing the previously cached ones
+    pub fn store(&self, proof: &ChainStateProof) -> Result<(), anyhow::Error> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let height = proof.chain_state.block_height;
+        let path = self.file_path(height);
+
+        // Write to a temporary file first so that concurrent readers never see a partial proof
+        let tmp_path = path.with_extension("tmp");
+        let file = std::fs::File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        bincode::serialize_into(&mut writer, proof)?;
+        writer.flush()?;
+        std::fs::rename(&tmp_path, &path)?;
+
+        for (cached_height, cached_path) in self.cached_files() {
+            if cached_height != height {
+                std::fs::remove_file(cached_path).ok();
+            }
+        }
+
+        info!(
+            "Cached chain state proof at height {} in {}",
+            height,
+            self.dir.display()
+        );
+        Ok(())
+    }
+
+    /// Path of the cached proof for the given chain height
+    fn file_path(&self, height: u32) -> PathBuf {
+        self.dir.join(format!(
+            "{}{}.{}",
+            CACHE_FILE_PREFIX, height, CACHE_FILE_EXTENSION
+        ))
+    }
+
+    /// List all cached proofs along with their chain heights
+    fn cached_files(&self) -> Vec<(u32, PathBuf)> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name();
+                let height = file_name
+                    .to_str()?
+                    .strip_prefix(CACHE_FILE_PREFIX)?
+                    .strip_suffix(CACHE_FILE_EXTENSION)?
+                    .strip_suffix('.')?
+                    .parse()
+                    .ok()?;
+                Some((height, entry.path()))
+            })
+            .collect()
+    }
+}
+
+/// Fetch the latest chain state proof, reusing the cached one if it is fresh enough
+///
+/// - `raito_rpc`: Raito bridge RPC endpoint
+/// - `cache`: Chain state proof cache, `None` to always fetch
+pub async fn fetch_chain_state_proof_cached(
+    raito_rpc: &ApiEndpoint,
+    cache: Option<&ChainStateCache>,
+) -> Result<ChainStateProof, anyhow::Error> {
+    let Some(cache) = cache else {
+        return fetch_chain_state_proof(raito_rpc).await;
+    };
+
+    if let Some(proof) = cache.load() {
+        return Ok(proof);
+    }
+
+    let proof = fetch_chain_state_proof(raito_rpc).await?;
+    if let Err(err) = cache.store(&proof) {
+        warn!("Failed to cache chain state proof: {}", err);
+    }
+    Ok(proof)
+}
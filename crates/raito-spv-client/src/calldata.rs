@@ -0,0 +1,286 @@
+//! Export of a compressed SPV proof as Starknet calldata, so that the proof a user verified
+//! locally can be submitted to an on-chain verifier contract.
+//!
+//! The felt layout follows the Cairo `Serde` conventions used for Raito program arguments
+//! (see `scripts/data/format_args.py`):
+//! - `u32`, `usize`: a single felt;
+//! - `Digest` (SHA-256): 8 `u32` words of the hash in internal byte order;
+//! - `Blake2sDigest` (MMR node): 2 felts, the high and low 128-bit parts;
+//! - `ByteArray`: number of 31-byte words, the words, the pending word and its length;
+//! - `Span<T>`: length followed by the elements.
+
+use std::path::PathBuf;
+
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::merkle_tree::PartialMerkleTree;
+use bitcoin::{Transaction, TxMerkleNode, Txid};
+use raito_spv_verifier::proof::CompressedSpvProof;
+use tracing::info;
+
+use crate::convert::{load_proof, ProofFormat};
+
+/// CLI arguments for the `export-calldata` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ExportCalldataArgs {
+    /// Path to read the proof from (binary or JSON)
+    #[arg(long)]
+    proof_path: PathBuf,
+    /// Path to write the calldata (JSON array of hex felts) to, stdout if not set
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// Run the `export-calldata` subcommand: serialize a proof into a felt array
+pub async fn run(args: ExportCalldataArgs) -> Result<(), anyhow::Error> {
+    let proof = load_proof(&args.proof_path, ProofFormat::detect(&args.proof_path)?)?;
+    let calldata = serde_json::to_string(&proof_to_calldata(&proof)?)?;
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, calldata)?;
+            info!("Calldata written to {}", path.display());
+        }
+        None => println!("{}", calldata),
+    }
+    Ok(())
+}
+
+/// Serialize the transaction, its Merkle branch, the block header and the block MMR
+/// inclusion proof into a felt array, in this order:
+///
+/// ```text
+/// transaction: ByteArray          // consensus encoding without witness
+/// merkle_branch: Span<Digest>     // siblings from the leaf up to the Merkle root
+/// tx_index: u32                   // position of the transaction in the block
+/// header: (version: u32, prev_block_hash: Digest, merkle_root: Digest,
+///          time: u32, bits: u32, nonce: u32)
+/// block_height: u32
+/// leaf_count: usize               // number of blocks in the MMR
+/// mmr_siblings: Span<Blake2sDigest>
+/// mmr_peaks: Span<Blake2sDigest>
+/// ```
+pub fn proof_to_calldata(proof: &CompressedSpvProof) -> Result<Vec<String>, anyhow::Error> {
+    let mut felts = Vec::new();
+
+    // Transaction and its Merkle branch
+    let mut transaction = proof.transaction.clone();
+    strip_witness(&mut transaction);
+    push_byte_array(&mut felts, &serialize(&transaction));
+
+    let pmt: PartialMerkleTree = bitcoin::consensus::deserialize(&proof.transaction_proof)
+        .map_err(|e| anyhow::anyhow!("Failed to decode transaction proof: {}", e))?;
+    let (tx_index, merkle_branch) = merkle_branch(&pmt, proof.transaction.compute_txid())?;
+    felts.push(felt_from_u64(merkle_branch.len() as u64));
+    for node in &merkle_branch {
+        push_digest(&mut felts, &node.to_byte_array());
+    }
+    felts.push(felt_from_u64(tx_index as u64));
+
+    // Block header
+    let header = &proof.block_header;
+    felts.push(felt_from_u64(header.version.to_consensus() as u32 as u64));
+    push_digest(&mut felts, &header.prev_blockhash.to_byte_array());
+    push_digest(&mut felts, &header.merkle_root.to_byte_array());
+    felts.push(felt_from_u64(header.time as u64));
+    felts.push(felt_from_u64(header.bits.to_consensus() as u64));
+    felts.push(felt_from_u64(header.nonce as u64));
+    felts.push(felt_from_u64(proof.block_height as u64));
+
+    // Block MMR inclusion proof
+    let mmr_proof = &proof.block_header_proof;
+    felts.push(felt_from_u64(mmr_proof.leaf_count as u64));
+    for nodes in [&mmr_proof.siblings_hashes, &mmr_proof.peaks_hashes] {
+        felts.push(felt_from_u64(nodes.len() as u64));
+        for node in nodes {
+            push_blake2s_digest(&mut felts, node)?;
+        }
+    }
+
+    Ok(felts)
+}
+
+/// Remove the witness data, so that the transaction serializes to its txid preimage
+pub fn strip_witness(transaction: &mut Transaction) {
+    for input in &mut transaction.input {
+        input.witness.clear();
+    }
+}
+
+/// Extract the position of the single matched transaction and its Merkle branch
+/// (siblings from the leaf up to the root) from a partial Merkle tree
+pub fn merkle_branch(
+    pmt: &PartialMerkleTree,
+    txid: Txid,
+) -> Result<(u32, Vec<TxMerkleNode>), anyhow::Error> {
+    let mut matches = Vec::new();
+    let mut indexes = Vec::new();
+    pmt.extract_matches(&mut matches, &mut indexes)
+        .map_err(|e| anyhow::anyhow!("Invalid transaction proof: {}", e))?;
+    if matches != [txid] {
+        anyhow::bail!("Transaction proof doesn't match transaction {} only", txid);
+    }
+
+    let mut height = 0;
+    while tree_width(pmt.num_transactions(), height) > 1 {
+        height += 1;
+    }
+    let mut traversal = Traversal {
+        pmt,
+        bit_idx: 0,
+        hash_idx: 0,
+        branch: Vec::new(),
+    };
+    traversal.visit(height, 0)?;
+    Ok((indexes[0], traversal.branch))
+}
+
+/// Compute the Merkle root by folding the branch of the transaction at `tx_index`, as
+/// extracted by [merkle_branch]
+pub fn merkle_root_from_branch(txid: Txid, tx_index: u32, branch: &[TxMerkleNode]) -> TxMerkleNode {
+    let mut hash = TxMerkleNode::from_raw_hash(txid.to_raw_hash());
+    for (level, sibling) in branch.iter().enumerate() {
+        let (left, right) = if (tx_index >> level) & 1 == 0 {
+            (hash, *sibling)
+        } else {
+            (*sibling, hash)
+        };
+        let mut data = left.to_byte_array().to_vec();
+        data.extend(right.to_byte_array());
+        hash = TxMerkleNode::from_raw_hash(sha256d::Hash::hash(&data));
+    }
+    hash
+}
+
+/// Depth-first traversal of a partial Merkle tree collecting the siblings of the matched leaf
+struct Traversal<'a> {
+    pmt: &'a PartialMerkleTree,
+    bit_idx: usize,
+    hash_idx: usize,
+    branch: Vec<TxMerkleNode>,
+}
+
+impl Traversal<'_> {
+    /// Visit a node, returns its hash and whether the matched leaf is below it
+    fn visit(&mut self, height: u32, pos: u32) -> Result<(TxMerkleNode, bool), anyhow::Error> {
+        let flag = *self
+            .pmt
+            .bits()
+            .get(self.bit_idx)
+            .ok_or_else(|| anyhow::anyhow!("Transaction proof is missing flag bits"))?;
+        self.bit_idx += 1;
+
+        if height == 0 || !flag {
+            let hash = *self
+                .pmt
+                .hashes()
+                .get(self.hash_idx)
+                .ok_or_else(|| anyhow::anyhow!("Transaction proof is missing hashes"))?;
+            self.hash_idx += 1;
+            return Ok((hash, height == 0 && flag));
+        }
+
+        let (left, left_matched) = self.visit(height - 1, pos * 2)?;
+        let (right, right_matched) =
+            if pos * 2 + 1 < tree_width(self.pmt.num_transactions(), height - 1) {
+                self.visit(height - 1, pos * 2 + 1)?
+            } else {
+                // Odd number of nodes, the last one is paired with itself
+                (left, false)
+            };
+        if left_matched {
+            self.branch.push(right);
+        } else if right_matched {
+            self.branch.push(left);
+        }
+
+        let mut engine = sha256d::Hash::engine();
+        bitcoin::hashes::HashEngine::input(&mut engine, left.as_byte_array());
+        bitcoin::hashes::HashEngine::input(&mut engine, right.as_byte_array());
+        let hash = TxMerkleNode::from_raw_hash(sha256d::Hash::from_engine(engine));
+        Ok((hash, left_matched || right_matched))
+    }
+}
+
+/// Number of nodes at the given height of a Merkle tree with `num_transactions` leaves
+fn tree_width(num_transactions: u32, height: u32) -> u32 {
+    (num_transactions + (1 << height) - 1) >> height
+}
+
+/// Hex felt of an integer
+fn felt_from_u64(value: u64) -> String {
+    format!("{:#x}", value)
+}
+
+/// Hex felt of big-endian bytes (at most 31)
+fn felt_from_bytes(bytes: &[u8]) -> String {
+    let hex = hex::encode(bytes);
+    let trimmed = hex.trim_start_matches('0');
+    format!("0x{}", if trimmed.is_empty() { "0" } else { trimmed })
+}
+
+/// Push a SHA-256 digest as 8 big-endian `u32` words
+fn push_digest(felts: &mut Vec<String>, bytes: &[u8; 32]) {
+    for word in bytes.chunks(4) {
+        felts.push(felt_from_bytes(word));
+    }
+}
+
+/// Push a Blake2s digest (hex string of a `u256`) as its high and low parts
+fn push_blake2s_digest(felts: &mut Vec<String>, digest: &str) -> Result<(), anyhow::Error> {
+    let bytes = hex::decode(digest.strip_prefix("0x").unwrap_or(digest))
+        .map_err(|e| anyhow::anyhow!("Invalid MMR node {}: {}", digest, e))?;
+    if bytes.len() != 32 {
+        anyhow::bail!("Invalid MMR node {}: expected 32 bytes", digest);
+    }
+    felts.push(felt_from_bytes(&bytes[..16]));
+    felts.push(felt_from_bytes(&bytes[16..]));
+    Ok(())
+}
+
+/// Push bytes as a Cairo `ByteArray`
+fn push_byte_array(felts: &mut Vec<String>, bytes: &[u8]) {
+    let chunks = bytes.chunks_exact(31);
+    let pending = chunks.remainder();
+    felts.push(felt_from_u64(chunks.len() as u64));
+    for chunk in chunks {
+        felts.push(felt_from_bytes(chunk));
+    }
+    felts.push(felt_from_bytes(pending));
+    felts.push(felt_from_u64(pending.len() as u64));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_branch() {
+        let txids: Vec<Txid> = (0..7u8).map(|i| Txid::from_byte_array([i; 32])).collect();
+        for index in [0, 3, 6] {
+            let matches: Vec<bool> = (0..txids.len()).map(|i| i == index).collect();
+            let pmt = PartialMerkleTree::from_txids(&txids, &matches);
+            let (tx_index, branch) = merkle_branch(&pmt, txids[index]).unwrap();
+            assert_eq!(tx_index as usize, index);
+            assert_eq!(branch.len(), 3);
+
+            let mut matched = Vec::new();
+            let mut indexes = Vec::new();
+            // Folding the branch gives back the Merkle root
+            assert_eq!(
+                pmt.extract_matches(&mut matched, &mut indexes).unwrap(),
+                merkle_root_from_branch(txids[index], tx_index, &branch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_byte_array() {
+        let mut felts = Vec::new();
+        push_byte_array(&mut felts, &[0xab; 33]);
+        assert_eq!(felts.len(), 4);
+        assert_eq!(felts[0], "0x1");
+        assert_eq!(felts[1], format!("0x{}", "ab".repeat(31)));
+        assert_eq!(felts[2], "0xabab");
+        assert_eq!(felts[3], "0x2");
+    }
+}
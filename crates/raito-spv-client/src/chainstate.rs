@@ -0,0 +1,75 @@
+//! Fetch and verify the latest chain state proven by Raito, without a transaction.
+
+use chrono::Utc;
+use raito_spv_verifier::fetch::{ApiEndpoint, ChainStateProof, RetryPolicy};
+use raito_spv_verifier::verify::{
+    format_duration, verify_chain_state, verify_chain_state_age, VerifiedChainState,
+};
+use raito_spv_verifier::work::WorkSummary;
+use tracing::info;
+
+use crate::fetch::fetch_chain_state_proof;
+use crate::format::format_block_time;
+use crate::network::{MAINNET_RAITO_RPC_URL, RAITO_RPC_URLS, RAITO_RPC_URL_REQUIRED};
+use crate::verify::VerifierConfigArgs;
+
+/// CLI arguments for the `chainstate` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ChainStateArgs {
+    /// Raito node RPC URL, defaults to the Raito bridge of the `--network`; required on
+    /// `testnet4` and `signet`, which have no public bridge
+    #[arg(
+        long,
+        env = "RAITO_BRIDGE_RPC",
+        default_value = MAINNET_RAITO_RPC_URL,
+        default_value_ifs = RAITO_RPC_URLS,
+        required_if_eq_any = RAITO_RPC_URL_REQUIRED
+    )]
+    raito_rpc_url: String,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+}
+
+/// Run the `chainstate` subcommand: fetch the recent chain state proof, verify it and print
+/// what Raito currently proves
+pub async fn run(args: ChainStateArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let config = args.verifier_config.resolve()?;
+    let ChainStateProof {
+        chain_state,
+        chain_state_proof,
+    } = fetch_chain_state_proof(&raito_rpc)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch chain state proof: {:?}", e))?;
+
+    info!("Verifying chain state proof ...");
+    let VerifiedChainState {
+        block_mmr_root,
+        program_release,
+        ..
+    } = verify_chain_state(&chain_state, chain_state_proof.into(), &config)?;
+    info!("Chain state proof is valid");
+    verify_chain_state_age(&chain_state, &config)?;
+
+    let best_block_time = chain_state.best_block_time();
+    let age = Utc::now().timestamp() - best_block_time as i64;
+
+    println!("Proven height: {}", chain_state.block_height);
+    println!("Best block hash: {}", chain_state.best_block_hash);
+    println!(
+        "Best block timestamp: {}",
+        format_block_time(best_block_time, Utc::now())
+    );
+    println!(
+        "Total work: {} ({})",
+        chain_state.total_work,
+        WorkSummary::new(&chain_state.total_work()?, &chain_state)
+    );
+    println!("Block MMR root: {}", block_mmr_root);
+    if let Some(program_release) = program_release {
+        println!("Program release: {}", program_release);
+    }
+    println!("Proof age: {}", format_duration(age));
+
+    Ok(())
+}
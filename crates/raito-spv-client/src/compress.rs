@@ -0,0 +1,300 @@
+//! The `compress` subcommand, re-compressing a proof bundle into a single recursive proof: the
+//! bundle is verified, then the bundle program (`packages/bundle`) verifies the chain state
+//! proof and the inclusion of every transaction in Cairo, and is proven with the local proving
+//! toolchain. The resulting proof attests that all the transactions are included in the chain
+//! with the proven chain state, and is much cheaper to relay than the bundle.
+
+use std::path::PathBuf;
+
+use bitcoin::hashes::Hash;
+use bitcoin::merkle_tree::PartialMerkleTree;
+use bitcoin::Txid;
+use chrono::Utc;
+use raito_spv_core::block_mmr::BlockPeaks;
+use raito_spv_core::primitives::{hash_pair, hash_to_hex, hashes_from_hex};
+use raito_spv_verifier::proof::{BundledTransaction, ProofBundle};
+use raito_spv_verifier::verify::{load_proof_bundle_from_bzip2, verify_bundle};
+use tracing::info;
+
+use crate::calldata::merkle_branch;
+use crate::progress;
+use crate::prove::{
+    convert_proof, encode_block_mmr, encode_chain_state, encode_digest, find_proof, run_program,
+    to_hex_felts, ProgramFiles,
+};
+use crate::verify::VerifierConfigArgs;
+
+/// CLI arguments for the `compress` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct CompressArgs {
+    /// Path to read the proof bundle from, as written by `fetch --txids-file --bundle`
+    #[arg(long)]
+    bundle: PathBuf,
+    /// Path to write the recursive proof to; defaults to the bundle path with a
+    /// `.recursive.json` extension
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Proof of the chain state of the bundle in the Cairo serde format, as written by
+    /// `prove`; defaults to the one proven in the work directory
+    #[arg(long)]
+    chain_state_proof: Option<PathBuf>,
+    /// Work directory of `prove`, holding the chain state proofs and the runs of the bundle
+    /// program
+    #[arg(long, default_value = "./proving")]
+    work_dir: PathBuf,
+    /// Compiled bundle program (`make bundle-build`)
+    #[arg(long, default_value = "target/proving/bundle.executable.json")]
+    executable: PathBuf,
+    /// Compiled simple bootloader running the program
+    #[arg(long, default_value = "bootloaders/simple_bootloader_compiled.json")]
+    bootloader: PathBuf,
+    /// Parameters of the Stwo prover
+    #[arg(long, default_value = "packages/bundle/prover_params.json")]
+    prover_params: PathBuf,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+}
+
+/// Run the `compress` subcommand: verify a proof bundle and prove it with the bundle program
+pub async fn run(args: CompressArgs) -> Result<(), anyhow::Error> {
+    let config = args.verifier_config.resolve()?;
+    let files = ProgramFiles::resolve(
+        &args.executable,
+        "build it with `make bundle-build`",
+        &args.bootloader,
+        &args.prover_params,
+    )?;
+
+    let bundle = load_proof_bundle_from_bzip2(&args.bundle)?;
+    let chain_state = bundle.chain_state.clone();
+    let chain_height = chain_state.block_height;
+    let chain_state_proof_path = match &args.chain_state_proof {
+        Some(path) => path.clone(),
+        None => find_proof(&args.work_dir, chain_height)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No proof of the chain state at height {} in {}, prove it first",
+                chain_height,
+                args.work_dir.display()
+            )
+        })?,
+    };
+    let chain_state_proof: Vec<String> =
+        serde_json::from_slice(&std::fs::read(&chain_state_proof_path)?)?;
+    let txids: Vec<Txid> = bundle
+        .transactions
+        .iter()
+        .map(|bundled| bundled.transaction.compute_txid())
+        .collect();
+    let mut arguments = encode_arguments(&bundle)?;
+    arguments.extend(chain_state_proof);
+
+    // The bundle program fails on an invalid bundle, after a costly run
+    let progress = progress::stage("Verifying proof bundle");
+    let results = verify_bundle(bundle, &config).await?;
+    for (txid, res) in txids.iter().zip(results) {
+        if let Err(err) = res {
+            anyhow::bail!("Invalid proof of transaction {}: {}", txid, err);
+        }
+    }
+    progress.finish_with_message("Verified proof bundle");
+
+    let txids_hash = txids_hash(&txids);
+    let dir = args
+        .work_dir
+        .join(format!("bundle_{}_{}", chain_height, &txids_hash[2..10]));
+    std::fs::create_dir_all(&dir)?;
+    info!(
+        "Proving the inclusion of {} transactions at chain height {}",
+        txids.len(),
+        chain_height
+    );
+    run_program(&dir, &files, &arguments).await?;
+    let proof = convert_proof(&dir).await?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.bundle.with_extension("recursive.json"));
+    let recursive_proof = serde_json::json!({
+        "timestamp": Utc::now().to_rfc3339(),
+        "chainstate": chain_state,
+        "txids": txids,
+        "txids_hash": txids_hash,
+        "proof": proof,
+    });
+    std::fs::write(&output, serde_json::to_vec(&recursive_proof)?)?;
+    info!(
+        "Recursive proof of {} transactions written to {}",
+        txids.len(),
+        output.display()
+    );
+    println!("{}", output.display());
+    Ok(())
+}
+
+/// Arguments of the bundle program in the Cairo serde format, as hex felts: the chain state,
+/// the block MMR and the inclusion of each transaction, the chain state proof is appended
+fn encode_arguments(bundle: &ProofBundle) -> Result<Vec<String>, anyhow::Error> {
+    let first = bundle
+        .transactions
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Empty proof bundle"))?;
+    // The transactions of a bundle share the block MMR of the chain state
+    let peaks = BlockPeaks::from_proof(&first.block_header_proof)?;
+    if peaks.leaf_count() != bundle.chain_state.block_height as usize + 1 {
+        anyhow::bail!(
+            "Block MMR of {} blocks doesn't match the chain state at height {}",
+            peaks.leaf_count(),
+            bundle.chain_state.block_height
+        );
+    }
+
+    let mut felts = encode_chain_state(&bundle.chain_state)?;
+    felts.extend(encode_block_mmr(&peaks.sparse_roots()?)?);
+    felts.push(bundle.transactions.len() as u128);
+    for bundled in &bundle.transactions {
+        felts.extend(encode_transaction(bundled)?);
+    }
+    Ok(to_hex_felts(&felts))
+}
+
+/// Inclusion of a bundled transaction in the Cairo serde format: its txid and Merkle branch,
+/// the header and height of its block, and the siblings of the block in the block MMR
+fn encode_transaction(bundled: &BundledTransaction) -> Result<Vec<u128>, anyhow::Error> {
+    let txid = bundled.transaction.compute_txid();
+    let pmt: PartialMerkleTree = bitcoin::consensus::deserialize(&bundled.transaction_proof)
+        .map_err(|e| anyhow::anyhow!("Failed to decode transaction proof: {}", e))?;
+    let (tx_index, branch) = merkle_branch(&pmt, txid)?;
+
+    let mut felts = encode_digest(txid.to_byte_array()).to_vec();
+    felts.push(branch.len() as u128);
+    for node in &branch {
+        felts.extend(encode_digest(node.to_byte_array()));
+    }
+    felts.push(tx_index.into());
+
+    let header = &bundled.block_header;
+    felts.push(u128::from(header.version.to_consensus() as u32));
+    felts.push(header.time.into());
+    felts.push(header.bits.to_consensus().into());
+    felts.push(header.nonce.into());
+    felts.extend(encode_digest(header.prev_blockhash.to_byte_array()));
+    felts.extend(encode_digest(header.merkle_root.to_byte_array()));
+    felts.push(bundled.block_height.into());
+
+    // MMR siblings as u256, whose low part comes first
+    let siblings = hashes_from_hex(&bundled.block_header_proof.siblings_hashes)?;
+    felts.push(siblings.len() as u128);
+    for sibling in siblings {
+        let (high, low) = sibling.split_at(16);
+        felts.push(u128::from_be_bytes(low.try_into()?));
+        felts.push(u128::from_be_bytes(high.try_into()?));
+    }
+    Ok(felts)
+}
+
+/// Commitment of the bundle program to the verified transaction IDs, in order: starting from
+/// zero, the commitment is hashed with each txid (internal byte order) as an MMR node pair
+///
+/// The words of the txid passed to the program ([encode_digest]) are the big-endian words of
+/// its bytes, the way MMR nodes are hashed, so both commitments are the same.
+fn txids_hash(txids: &[Txid]) -> String {
+    let hash = txids.iter().fold([0; 32], |hash, txid| {
+        hash_pair(&hash, &txid.to_byte_array())
+    });
+    hash_to_hex(&hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::block::{Header as BlockHeader, Version};
+    use bitcoin::hashes::sha256d;
+    use bitcoin::transaction::Version as TxVersion;
+    use bitcoin::{BlockHash, CompactTarget, Transaction, TxMerkleNode};
+    use raito_spv_core::block_mmr::BlockInclusionProof;
+
+    #[test]
+    fn test_encode_transaction() {
+        let transaction = Transaction {
+            version: TxVersion::ONE,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let txid = transaction.compute_txid();
+        let sibling = Txid::from_byte_array([7; 32]);
+        let pmt = PartialMerkleTree::from_txids(&[sibling, txid], &[false, true]);
+        let mut data = sibling.to_byte_array().to_vec();
+        data.extend(txid.to_byte_array());
+        let bundled = BundledTransaction {
+            block_header: BlockHeader {
+                version: Version::ONE,
+                prev_blockhash: BlockHash::from_byte_array([1; 32]),
+                merkle_root: TxMerkleNode::from_raw_hash(sha256d::Hash::hash(&data)),
+                time: 2,
+                bits: CompactTarget::from_consensus(3),
+                nonce: 4,
+            },
+            block_height: 5,
+            block_header_proof: BlockInclusionProof {
+                peaks_hashes: vec![],
+                siblings_hashes: vec![format!("0x{}{}", "0a".repeat(16), "0b".repeat(16))],
+                leaf_index: 5,
+                leaf_count: 6,
+            },
+            transaction,
+            transaction_proof: bitcoin::consensus::serialize(&pmt),
+        };
+
+        let felts = encode_transaction(&bundled).unwrap();
+        // txid, branch of one node, index, header, height, one MMR sibling
+        assert_eq!(felts.len(), 8 + 1 + 8 + 1 + 4 + 8 + 8 + 1 + 1 + 2);
+        assert_eq!(felts[..8], encode_digest(txid.to_byte_array()));
+        assert_eq!(felts[8], 1);
+        assert_eq!(felts[9..17], encode_digest(sibling.to_byte_array()));
+        assert_eq!(felts[17], 1);
+        assert_eq!(felts[18..22], [1, 2, 3, 4]);
+        assert_eq!(felts[38], 5);
+        assert_eq!(felts[39], 1);
+        assert_eq!(felts[40], u128::from_be_bytes([0x0b; 16]));
+        assert_eq!(felts[41], u128::from_be_bytes([0x0a; 16]));
+    }
+
+    #[test]
+    fn test_txids_hash() {
+        let (a, b) = (
+            Txid::from_byte_array([1; 32]),
+            Txid::from_byte_array([2; 32]),
+        );
+        assert_eq!(txids_hash(&[]), hash_to_hex(&[0; 32]));
+        assert_eq!(
+            txids_hash(&[a, b]),
+            hash_to_hex(&hash_pair(
+                &hash_pair(&[0; 32], &a.to_byte_array()),
+                &b.to_byte_array()
+            ))
+        );
+        assert_ne!(txids_hash(&[a, b]), txids_hash(&[b, a]));
+    }
+
+    #[test]
+    fn test_txids_hash_cairo() {
+        // Txids whose words are 1..=8 and 9..=16, as in the `test_commit_txid` test of the
+        // bundle program, which computes the same commitment
+        let txid = |first: u32| {
+            let mut bytes = [0; 32];
+            for (i, chunk) in bytes.chunks_exact_mut(4).enumerate() {
+                chunk.copy_from_slice(&(first + i as u32).to_be_bytes());
+            }
+            Txid::from_byte_array(bytes)
+        };
+        let (a, b) = (txid(1), txid(9));
+        assert_eq!(encode_digest(a.to_byte_array()), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            txids_hash(&[a, b]),
+            "0x8e6aa2a22f27840dbe007a623961db5089f7b1bfa0c5086f91011868f3869717"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Conversion of compressed SPV proofs between the compact binary and the JSON representations.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use raito_spv_verifier::proof::CompressedSpvProof;
+use raito_spv_verifier::verify::BZIP2_MAGIC;
+use tracing::info;
+
+use crate::fetch::save_compressed_proof_with_bzip2;
+use crate::verify::load_compressed_proof_from_bzip2;
+
+/// CLI arguments for the `convert` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ConvertArgs {
+    /// Path to read the proof from (format is detected automatically)
+    #[arg(long = "in")]
+    input: PathBuf,
+    /// Path to write the converted proof to
+    #[arg(long = "out")]
+    output: PathBuf,
+    /// Output format; inferred from the output file extension if omitted
+    #[arg(long)]
+    to: Option<ProofFormat>,
+}
+
+/// On-disk representation of a compressed SPV proof
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProofFormat {
+    /// Bincode binary codec with bzip2 compression
+    Binary,
+    /// Pretty-printed JSON
+    Json,
+}
+
+impl ProofFormat {
+    /// Infer the format from the file extension: `.json` is JSON, anything else is binary
+    pub fn from_extension(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::Json,
+            _ => Self::Binary,
+        }
+    }
+
+    /// Detect the format of an existing proof file by looking at its leading bytes
+    pub fn detect(path: &Path) -> Result<Self, anyhow::Error> {
+        let mut magic = [0u8; 3];
+        let file = std::fs::File::open(path)?;
+        let read = file.take(magic.len() as u64).read(&mut magic)?;
+        if read == magic.len() && &magic == BZIP2_MAGIC {
+            Ok(Self::Binary)
+        } else {
+            Ok(Self::Json)
+        }
+    }
+}
+
+/// Run the `convert` subcommand: read a proof in one format and write it in another
+pub async fn run(args: ConvertArgs) -> Result<(), anyhow::Error> {
+    let input_format = ProofFormat::detect(&args.input)?;
+    let output_format = args
+        .to
+        .unwrap_or_else(|| ProofFormat::from_extension(&args.output));
+
+    let proof = load_proof(&args.input, input_format)?;
+    save_proof(&proof, &args.output, output_format)?;
+
+    info!(
+        "Converted {} ({:?}) to {} ({:?})",
+        args.input.display(),
+        input_format,
+        args.output.display(),
+        output_format
+    );
+    Ok(())
+}
+
+/// Load a compressed proof from disk in the given format
+pub fn load_proof(
+    proof_path: &PathBuf,
+    format: ProofFormat,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    match format {
+        ProofFormat::Binary => Ok(load_compressed_proof_from_bzip2(proof_path)?),
+        ProofFormat::Json => load_compressed_proof_from_json(proof_path),
+    }
+}
+
+/// Save a compressed proof to disk in the given format
+pub fn save_proof(
+    proof: &CompressedSpvProof,
+    proof_path: &PathBuf,
+    format: ProofFormat,
+) -> Result<(), anyhow::Error> {
+    match format {
+        ProofFormat::Binary => save_compressed_proof_with_bzip2(proof, proof_path),
+        ProofFormat::Json => save_compressed_proof_to_json(proof, proof_path),
+    }
+}
+
+/// Load a compressed proof from a JSON file
+pub fn load_compressed_proof_from_json(
+    proof_path: &PathBuf,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    info!("Loading JSON proof from {}", proof_path.display());
+    let file = std::fs::File::open(proof_path)?;
+    let proof = serde_json::from_reader(BufReader::new(file))?;
+    Ok(proof)
+}
+
+/// Save a compressed proof to disk as pretty-printed JSON
+///
+/// The JSON form is meant for inspection (e.g. with `jq`), diffing, and text-based storage;
+/// it is significantly larger than the binary form used for transport.
+pub fn save_compressed_proof_to_json(
+    proof: &CompressedSpvProof,
+    proof_path: &PathBuf,
+) -> Result<(), anyhow::Error> {
+    // Create parent directories if they don't exist
+    if let Some(proof_dir) = proof_path.parent() {
+        std::fs::create_dir_all(proof_dir)?;
+    }
+
+    let file = std::fs::File::create(proof_path)?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, proof)?;
+    writer.flush()?;
+
+    info!("JSON proof written to {}", proof_path.display());
+    Ok(())
+}
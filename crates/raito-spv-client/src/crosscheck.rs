@@ -0,0 +1,149 @@
+//! Optional live cross-check of a proven chain state against a Bitcoin node or an Esplora
+//! instance, catching chain state proofs that are stale or committed to a fork.
+
+use bitcoin::BlockHash;
+use raito_spv_core::bitcoin::BitcoinClient;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// CLI arguments for the live chain state cross-check
+#[derive(Clone, Debug, clap::Args)]
+pub struct CrossCheckArgs {
+    /// Check that the proven best block is on the current best chain of a Bitcoin node
+    /// or an Esplora instance
+    #[arg(long, default_value = "false", conflicts_with = "proof_dir")]
+    cross_check: bool,
+    /// Bitcoin RPC URL used for the cross-check
+    #[arg(long, env = "BITCOIN_RPC")]
+    bitcoin_rpc_url: Option<String>,
+    /// Bitcoin RPC user:password (optional)
+    #[arg(long, env = "USERPWD")]
+    bitcoin_rpc_userpwd: Option<String>,
+    /// Esplora API URL used for the cross-check instead of Bitcoin RPC,
+    /// e.g. https://blockstream.info/api
+    #[arg(long)]
+    esplora_url: Option<String>,
+}
+
+/// Position of the proven best block relative to the live best chain
+#[derive(Debug, Clone, Serialize)]
+pub struct CrossCheckReport {
+    /// Height of the live best chain tip
+    pub tip_height: u32,
+    /// Number of blocks the live best chain is ahead of the proven chain state
+    pub blocks_behind: u32,
+}
+
+/// Block status as returned by the Esplora `/block/:hash/status` endpoint
+#[derive(Debug, Deserialize)]
+struct EsploraBlockStatus {
+    in_best_chain: bool,
+    height: Option<u32>,
+}
+
+impl CrossCheckArgs {
+    /// Whether the cross-check was requested
+    pub fn enabled(&self) -> bool {
+        self.cross_check
+    }
+
+    /// Check that the proven best block is on the live best chain
+    ///
+    /// - `best_block_hash`: Hash of the best block in the proven chain state
+    /// - `block_height`: Height of the best block in the proven chain state
+    pub async fn run(
+        &self,
+        best_block_hash: &BlockHash,
+        block_height: u32,
+    ) -> Result<CrossCheckReport, anyhow::Error> {
+        let (in_best_chain, height, tip_height) = match (&self.bitcoin_rpc_url, &self.esplora_url) {
+            (_, Some(url)) => {
+                info!("Cross-checking proven chain state against Esplora {}", url);
+                cross_check_esplora(url, best_block_hash).await?
+            }
+            (Some(url), None) => {
+                info!(
+                    "Cross-checking proven chain state against Bitcoin node {}",
+                    url
+                );
+                cross_check_bitcoin_rpc(
+                    url.clone(),
+                    self.bitcoin_rpc_userpwd.clone(),
+                    best_block_hash,
+                )
+                .await?
+            }
+            (None, None) => {
+                anyhow::bail!("Cross-check requires either a Bitcoin RPC URL or an Esplora URL")
+            }
+        };
+
+        if !in_best_chain {
+            anyhow::bail!(
+                "Proven best block {} is not on the current best chain (forked chain state proof)",
+                best_block_hash
+            );
+        }
+        if height != block_height {
+            anyhow::bail!(
+                "Proven best block {} is at height {}, but the chain state claims height {}",
+                best_block_hash,
+                height,
+                block_height
+            );
+        }
+
+        Ok(CrossCheckReport {
+            tip_height,
+            blocks_behind: tip_height.saturating_sub(block_height),
+        })
+    }
+}
+
+/// Look up the best block via Bitcoin RPC, returns whether it is on the best chain, its
+/// height, and the height of the chain tip
+async fn cross_check_bitcoin_rpc(
+    url: String,
+    userpwd: Option<String>,
+    best_block_hash: &BlockHash,
+) -> Result<(bool, u32, u32), anyhow::Error> {
+    let client = BitcoinClient::new(url, userpwd)?;
+    let header = client.get_block_header_ex(best_block_hash).await?;
+    let tip_height = client.get_block_count().await?;
+    // Bitcoin Core reports -1 confirmations for blocks that are not on the best chain
+    Ok((header.confirmations > 0, header.height as u32, tip_height))
+}
+
+/// Look up the best block via the Esplora API, returns whether it is on the best chain, its
+/// height, and the height of the chain tip
+async fn cross_check_esplora(
+    url: &str,
+    best_block_hash: &BlockHash,
+) -> Result<(bool, u32, u32), anyhow::Error> {
+    let url = url.trim_end_matches('/');
+    let client = reqwest::Client::new();
+
+    let status: EsploraBlockStatus = client
+        .get(format!("{}/block/{}/status", url, best_block_hash))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let tip_height: u32 = client
+        .get(format!("{}/blocks/tip/height", url))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid tip height returned by Esplora: {}", e))?;
+
+    match (status.in_best_chain, status.height) {
+        (false, _) => Ok((false, 0, tip_height)),
+        (true, Some(height)) => Ok((true, height, tip_height)),
+        (true, None) => anyhow::bail!("Esplora returned no height for block {}", best_block_hash),
+    }
+}
@@ -0,0 +1,235 @@
+//! Output descriptors of single-key wallets with an extended public key, e.g.
+//! `wpkh([d34db33f/84'/0'/0']xpub…/0/*)`, so that the receive addresses of a wallet can be
+//! watched without enumerating them.
+//!
+//! Supported descriptors are `pkh`, `wpkh`, `sh(wpkh)` and `tr` (key path only), with a single
+//! unhardened wildcard at the end of the key path. The checksum is optional, and verified if
+//! present.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpub};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Address, Network, NetworkKind};
+
+/// Characters allowed in a descriptor, in checksum order
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+/// Characters of the checksum
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Script type of the derived addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptKind {
+    /// Legacy P2PKH
+    Pkh,
+    /// Native segwit P2WPKH
+    Wpkh,
+    /// P2WPKH nested in P2SH
+    ShWpkh,
+    /// Taproot key path spend
+    Tr,
+}
+
+/// Ranged single-key output descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptor {
+    /// Script type of the derived addresses
+    kind: ScriptKind,
+    /// Extended public key
+    xpub: Xpub,
+    /// Derivation path from the extended public key to the parent of the wildcard
+    path: DerivationPath,
+    /// Descriptor without checksum, as given
+    source: String,
+}
+
+impl FromStr for Descriptor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = match s.split_once('#') {
+            Some((source, expected)) => {
+                let actual = checksum(source)?;
+                if actual != expected {
+                    anyhow::bail!(
+                        "Invalid descriptor checksum {}, expected {}",
+                        expected,
+                        actual
+                    );
+                }
+                source
+            }
+            None => s,
+        };
+
+        let (kind, key) = [
+            ("sh(wpkh(", "))", ScriptKind::ShWpkh),
+            ("wpkh(", ")", ScriptKind::Wpkh),
+            ("pkh(", ")", ScriptKind::Pkh),
+            ("tr(", ")", ScriptKind::Tr),
+        ]
+        .into_iter()
+        .find_map(|(prefix, suffix, kind)| {
+            let key = source.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some((kind, key))
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!("Unsupported descriptor, expected pkh, wpkh, sh(wpkh) or tr")
+        })?;
+
+        // The key origin only documents the derivation from the master key
+        let key = match key.strip_prefix('[') {
+            Some(key) => {
+                key.split_once(']')
+                    .ok_or_else(|| anyhow::anyhow!("Unterminated key origin"))?
+                    .1
+            }
+            None => key,
+        };
+        let mut parts = key.split('/');
+        let xpub = Xpub::from_str(parts.next().unwrap_or_default())
+            .map_err(|e| anyhow::anyhow!("Invalid extended public key: {}", e))?;
+        let mut parts: Vec<&str> = parts.collect();
+        if parts.pop() != Some("*") {
+            anyhow::bail!("Descriptor key must end with an unhardened wildcard `/*`");
+        }
+        let path = parts
+            .into_iter()
+            .map(|part| match ChildNumber::from_str(part) {
+                Ok(child @ ChildNumber::Normal { .. }) => Ok(child),
+                Ok(_) => anyhow::bail!("Hardened derivation {} requires a private key", part),
+                Err(e) => anyhow::bail!("Invalid derivation step {}: {}", part, e),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+
+        Ok(Self {
+            kind,
+            xpub,
+            path,
+            source: source.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Descriptor {
+    /// Address at `index` of the range, for the given network
+    pub fn address(&self, index: u32, network: Network) -> Result<Address, anyhow::Error> {
+        if self.xpub.network != NetworkKind::from(network) {
+            anyhow::bail!("Descriptor {} is not for network {}", self, network);
+        }
+        let secp = Secp256k1::verification_only();
+        let path = self.path.child(ChildNumber::from_normal_idx(index)?);
+        let public_key = self.xpub.derive_pub(&secp, &path)?.to_pub();
+        Ok(match self.kind {
+            ScriptKind::Pkh => Address::p2pkh(public_key, network),
+            ScriptKind::Wpkh => Address::p2wpkh(&public_key, network),
+            ScriptKind::ShWpkh => Address::p2shwpkh(&public_key, network),
+            ScriptKind::Tr => Address::p2tr(&secp, public_key.0.into(), None, network),
+        })
+    }
+}
+
+/// Checksum of a descriptor as defined by BIP380
+pub fn checksum(descriptor: &str) -> Result<String, anyhow::Error> {
+    fn polymod(c: u64, value: u64) -> u64 {
+        const GENERATOR: [u64; 5] = [
+            0xf5dee51989,
+            0xa9fdca3312,
+            0x1bab10e32d,
+            0x3706b1677a,
+            0x644d626ffd,
+        ];
+        let top = c >> 35;
+        let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                c ^= generator;
+            }
+        }
+        c
+    }
+
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET
+            .find(ch)
+            .ok_or_else(|| anyhow::anyhow!("Invalid character {:?} in descriptor", ch))?
+            as u64;
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Ok((0..8)
+        .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::base58;
+
+    /// Account key of the BIP84 test vector, converted from zpub to xpub
+    fn bip84_xpub() -> String {
+        let mut data = base58::decode_check("zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs").unwrap();
+        data[..4].copy_from_slice(&[0x04, 0x88, 0xb2, 0x1e]);
+        base58::encode_check(&data)
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert!(checksum("raw(déadbeef)").is_err());
+    }
+
+    #[test]
+    fn test_derive_addresses() {
+        let source = format!("wpkh([73c5da0a/84'/0'/0']{}/0/*)", bip84_xpub());
+        let descriptor: Descriptor = format!("{}#{}", source, checksum(&source).unwrap())
+            .parse()
+            .unwrap();
+        assert_eq!(descriptor.to_string(), source);
+        assert_eq!(
+            descriptor.address(0, Network::Bitcoin).unwrap().to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            descriptor.address(1, Network::Bitcoin).unwrap().to_string(),
+            "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"
+        );
+        assert!(descriptor.address(0, Network::Testnet).is_err());
+
+        assert!(format!("{}#qqqqqqqq", source)
+            .parse::<Descriptor>()
+            .is_err());
+        assert!(format!("wpkh({}/0'/*)", bip84_xpub())
+            .parse::<Descriptor>()
+            .is_err());
+        assert!(format!("wpkh({}/0)", bip84_xpub())
+            .parse::<Descriptor>()
+            .is_err());
+    }
+}
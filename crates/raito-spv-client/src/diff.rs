@@ -0,0 +1,174 @@
+//! Component-wise comparison of two compressed SPV proofs.
+
+use std::path::PathBuf;
+
+use raito_spv_core::block_mmr::root_hash_from_peaks;
+use raito_spv_verifier::proof::{BootloaderOutput, CompressedSpvProof};
+use serde::Serialize;
+use stwo_prover::core::vcs::blake2_hash::Blake2sHasher;
+
+use crate::convert::{load_proof, ProofFormat};
+
+/// CLI arguments for the `diff` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct DiffArgs {
+    /// Path to the first proof (binary or JSON)
+    left: PathBuf,
+    /// Path to the second proof (binary or JSON)
+    right: PathBuf,
+    /// Print all compared fields, not only the differing ones
+    #[arg(long, default_value = "false")]
+    all: bool,
+}
+
+/// Run the `diff` subcommand: load two proofs and report which components differ
+///
+/// Neither proof is verified, the comparison is done on the claimed values only.
+pub async fn run(args: DiffArgs) -> Result<(), anyhow::Error> {
+    let left = load_proof(&args.left, ProofFormat::detect(&args.left)?)?;
+    let right = load_proof(&args.right, ProofFormat::detect(&args.right)?)?;
+
+    let left_fields = proof_fields(&left).await?;
+    let right_fields = proof_fields(&right).await?;
+
+    let mut num_differences = 0;
+    for ((name, left_value), (_, right_value)) in left_fields.iter().zip(right_fields.iter()) {
+        if left_value != right_value {
+            num_differences += 1;
+            if console::colors_enabled() {
+                println!("\x1b[31m{}\x1b[0m", name);
+            } else {
+                println!("{}", name);
+            }
+            println!("  - {}", left_value);
+            println!("  + {}", right_value);
+        } else if args.all {
+            println!("{}", name);
+            println!("    {}", left_value);
+        }
+    }
+
+    if num_differences == 0 {
+        println!("Proofs are identical");
+    } else {
+        println!(
+            "{} of {} compared fields differ",
+            num_differences,
+            left_fields.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Extract the list of comparable fields from a proof
+///
+/// Every proof yields the same fields in the same order, so two lists can be zipped.
+pub async fn proof_fields(
+    proof: &CompressedSpvProof,
+) -> Result<Vec<(&'static str, String)>, anyhow::Error> {
+    let chain_state = &proof.chain_state;
+    let block_header_proof = &proof.block_header_proof;
+
+    let mut fields = vec![
+        (
+            "chain_state.block_height",
+            chain_state.block_height.to_string(),
+        ),
+        (
+            "chain_state.best_block_hash",
+            chain_state.best_block_hash.to_string(),
+        ),
+        ("chain_state.total_work", chain_state.total_work.clone()),
+        (
+            "chain_state.current_target",
+            chain_state.current_target.clone(),
+        ),
+        (
+            "chain_state.epoch_start_time",
+            chain_state.epoch_start_time.to_string(),
+        ),
+        (
+            "chain_state.prev_timestamps",
+            format!("{:?}", chain_state.prev_timestamps),
+        ),
+        ("chain_state.digest", chain_state.blake2s_digest()?),
+        (
+            "chain_state_proof",
+            component_digest(&proof.chain_state_proof)?,
+        ),
+    ];
+
+    // Claimed public output of the recursive proof
+    let program_fields = [
+        "program.bootloader_hash",
+        "program.task_program_hash",
+        "program.prev_bootloader_hash",
+        "program.prev_program_hash",
+        "program.chain_state_hash",
+        "program.block_mmr_hash",
+    ];
+    let program_values = match BootloaderOutput::from_proof(&proof.chain_state_proof) {
+        Ok((bootloader_hash, output)) => [
+            bootloader_hash,
+            output.task_program_hash,
+            output.task_result.bootloader_hash,
+            output.task_result.program_hash,
+            output.task_result.chain_state_hash,
+            output.task_result.block_mmr_hash,
+        ],
+        Err(err) => std::array::from_fn(|_| format!("<undecodable: {}>", err)),
+    };
+    fields.extend(program_fields.into_iter().zip(program_values));
+
+    // Block MMR root implied by the inclusion proof peaks
+    let mmr_root = root_hash_from_peaks(
+        block_header_proof.peaks_hashes.clone(),
+        block_header_proof.leaf_count,
+    )
+    .unwrap_or_else(|err| format!("<invalid peaks: {}>", err));
+
+    fields.extend([
+        ("block.height", proof.block_height.to_string()),
+        (
+            "block.mmr_leaf_index",
+            block_header_proof.leaf_index.to_string(),
+        ),
+        ("block.hash", proof.block_header.block_hash().to_string()),
+        (
+            "block.mmr_leaf_count",
+            block_header_proof.leaf_count.to_string(),
+        ),
+        ("block.mmr_root", mmr_root),
+        (
+            "block.mmr_siblings",
+            format!("{:?}", block_header_proof.siblings_hashes),
+        ),
+        (
+            "transaction.txid",
+            proof.transaction.compute_txid().to_string(),
+        ),
+        (
+            "transaction.wtxid",
+            proof.transaction.compute_wtxid().to_string(),
+        ),
+        (
+            "transaction_proof",
+            component_digest(&proof.transaction_proof)?,
+        ),
+    ]);
+
+    Ok(fields)
+}
+
+/// Compute a short fingerprint of a proof component: serialized size and Blake2s digest
+fn component_digest<T: Serialize + ?Sized>(component: &T) -> Result<String, anyhow::Error> {
+    let bytes = bincode::serialize(component)?;
+    let mut hasher = Blake2sHasher::new();
+    hasher.update(&bytes);
+    Ok(format!(
+        "{} bytes, blake2s 0x{}",
+        bytes.len(),
+        hex::encode(hasher.finalize().0)
+    ))
+}
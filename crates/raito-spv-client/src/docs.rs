@@ -0,0 +1,35 @@
+//! Shell completions and man pages generated from the CLI definition, for packagers.
+
+use std::path::PathBuf;
+
+use clap_complete::Shell;
+use tracing::info;
+
+/// CLI arguments for the `completions` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    shell: Shell,
+}
+
+/// CLI arguments for the `manpages` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ManpagesArgs {
+    /// Directory to write the man pages to, one per subcommand
+    dir: PathBuf,
+}
+
+/// Run the `completions` subcommand: print the completion script of `cmd` to stdout
+pub fn completions(args: CompletionsArgs, mut cmd: clap::Command) -> Result<(), anyhow::Error> {
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(args.shell, &mut cmd, name, &mut std::io::stdout());
+    Ok(())
+}
+
+/// Run the `manpages` subcommand: write the man pages of `cmd` and its subcommands
+pub fn manpages(args: ManpagesArgs, cmd: clap::Command) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(&args.dir)?;
+    clap_mangen::generate_to(cmd, &args.dir)?;
+    info!("Man pages written to {}", args.dir.display());
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! Download of published proofs over HTTP(S), with size limits, checksum verification,
+//! and resumption of interrupted transfers.
+
+use bitcoin::hashes::{sha256, Hash};
+use raito_spv_verifier::fetch::{download_resumable, RetryPolicy};
+use tracing::info;
+
+/// Download a proof from `url` into memory
+///
+/// - `url`: URL of the published proof (binary or JSON)
+/// - `max_size`: Maximum size of the proof in bytes, the download is aborted if exceeded
+/// - `sha256`: Optional expected SHA-256 checksum of the proof file (hex string)
+/// - `retry_policy`: Timeout and retries of the requests
+///
+/// Failed attempts are retried as per `retry_policy`; an interrupted download is resumed with
+/// an HTTP range request when the server supports it and the proof is unchanged, otherwise it
+/// is restarted from scratch.
+pub async fn download_proof(
+    url: &str,
+    max_size: u64,
+    sha256: Option<&str>,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<u8>, anyhow::Error> {
+    info!("Downloading proof from {} ...", url);
+    let bytes = download_resumable(url, retry_policy, Some(max_size), |_, _| {}).await?;
+    if let Some(expected) = sha256 {
+        verify_checksum(&bytes, expected)?;
+    }
+    Ok(bytes)
+}
+
+/// Check that the SHA-256 digest of `bytes` matches the expected hex string
+fn verify_checksum(bytes: &[u8], expected: &str) -> Result<(), anyhow::Error> {
+    let actual = sha256::Hash::hash(bytes).to_string();
+    if !actual.eq_ignore_ascii_case(expected.trim_start_matches("0x")) {
+        anyhow::bail!(
+            "Proof checksum doesn't match the expected one: {} != {}",
+            actual,
+            expected
+        );
+    }
+    info!("Proof checksum matches: {}", actual);
+    Ok(())
+}
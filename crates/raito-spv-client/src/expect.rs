@@ -0,0 +1,250 @@
+//! Assertions on the content of a verified transaction, so that a valid proof of the wrong
+//! transaction is not mistaken for the expected payment.
+
+use std::str::FromStr;
+
+use bitcoin::script::Instruction;
+use bitcoin::{Address, Amount, Network, ScriptBuf, Transaction, TxOut};
+
+/// CLI arguments asserting the expected outcome of the proven transaction
+#[derive(Clone, Debug, clap::Args)]
+pub struct ExpectArgs {
+    /// Expect an output paying to this address
+    #[arg(long, conflicts_with_all = ["bundle", "proof_dir"])]
+    expect_address: Option<String>,
+    /// Expect an output of this amount, with denomination (e.g. `0.01 BTC` or `1000 sat`);
+    /// combined with the address or script, the same output must match both
+    #[arg(long, conflicts_with_all = ["bundle", "proof_dir"])]
+    expect_amount: Option<String>,
+    /// Expect an OP_RETURN output carrying this data (hex string)
+    #[arg(long, conflicts_with_all = ["bundle", "proof_dir"])]
+    expect_op_return: Option<String>,
+    /// Expect an output locked by this script (hex string)
+    #[arg(long, conflicts_with_all = ["bundle", "proof_dir"])]
+    expect_output_script: Option<String>,
+}
+
+/// Parsed expectations on the outputs of a transaction
+#[derive(Debug, Clone)]
+pub struct Expectations {
+    /// Expected script of a payment output
+    pub script_pubkey: Option<ScriptBuf>,
+    /// Expected value of a payment output
+    pub amount: Option<Amount>,
+    /// Expected data of an OP_RETURN output
+    pub op_return: Option<Vec<u8>>,
+    /// Network of the expected address
+    pub network: Network,
+}
+
+impl ExpectArgs {
+    /// Parse the expectations for the given network, failing on malformed values or an
+    /// address of another network
+    pub fn parse(&self, network: Network) -> Result<Expectations, anyhow::Error> {
+        let address_script = match &self.expect_address {
+            Some(address) => Some(
+                Address::from_str(address)
+                    .map_err(|e| anyhow::anyhow!("Invalid expected address {}: {}", address, e))?
+                    .require_network(network)
+                    .map_err(|e| anyhow::anyhow!("Invalid expected address {}: {}", address, e))?
+                    .script_pubkey(),
+            ),
+            None => None,
+        };
+        let output_script = match &self.expect_output_script {
+            Some(script) => Some(
+                ScriptBuf::from_hex(script)
+                    .map_err(|e| anyhow::anyhow!("Invalid expected output script: {}", e))?,
+            ),
+            None => None,
+        };
+        let script_pubkey = match (address_script, output_script) {
+            (Some(a), Some(b)) if a != b => {
+                anyhow::bail!("Expected address and expected output script don't match")
+            }
+            (a, b) => a.or(b),
+        };
+
+        let amount = match &self.expect_amount {
+            Some(amount) => Some(Amount::from_str(amount).map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid expected amount {} (use e.g. `0.01 BTC` or `1000 sat`): {}",
+                    amount,
+                    e
+                )
+            })?),
+            None => None,
+        };
+
+        let op_return = match &self.expect_op_return {
+            Some(data) => Some(
+                hex::decode(data.strip_prefix("0x").unwrap_or(data))
+                    .map_err(|e| anyhow::anyhow!("Invalid expected OP_RETURN data: {}", e))?,
+            ),
+            None => None,
+        };
+
+        Ok(Expectations {
+            script_pubkey,
+            amount,
+            op_return,
+            network,
+        })
+    }
+}
+
+impl Expectations {
+    /// Whether no expectation is set
+    pub fn is_empty(&self) -> bool {
+        self.script_pubkey.is_none() && self.amount.is_none() && self.op_return.is_none()
+    }
+
+    /// Check that the transaction satisfies every expectation
+    pub fn check(&self, transaction: &Transaction) -> Result<(), anyhow::Error> {
+        if (self.script_pubkey.is_some() || self.amount.is_some())
+            && !transaction
+                .output
+                .iter()
+                .any(|output| self.matches_payment(output))
+        {
+            anyhow::bail!(
+                "Transaction {} has no output {}",
+                transaction.compute_txid(),
+                self.describe_payment()
+            );
+        }
+
+        if let Some(data) = &self.op_return {
+            if !transaction
+                .output
+                .iter()
+                .any(|output| op_return_data(output).as_ref() == Some(data))
+            {
+                anyhow::bail!(
+                    "Transaction {} has no OP_RETURN output with data {}",
+                    transaction.compute_txid(),
+                    hex::encode(data)
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// First output of the transaction matching the expected payment, if a payment is expected
+    pub fn payment_output<'a>(&self, transaction: &'a Transaction) -> Option<&'a TxOut> {
+        if self.script_pubkey.is_none() && self.amount.is_none() {
+            return None;
+        }
+        transaction
+            .output
+            .iter()
+            .find(|output| self.matches_payment(output))
+    }
+
+    fn matches_payment(&self, output: &TxOut) -> bool {
+        self.script_pubkey
+            .as_ref()
+            .is_none_or(|script| &output.script_pubkey == script)
+            && self.amount.is_none_or(|amount| output.value == amount)
+    }
+
+    fn describe_payment(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(script) = &self.script_pubkey {
+            match Address::from_script(script, self.network) {
+                Ok(address) => parts.push(format!("paying to {}", address)),
+                Err(_) => parts.push(format!("locked by script {}", script.to_hex_string())),
+            }
+        }
+        if let Some(amount) = &self.amount {
+            parts.push(format!("of {}", amount));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Concatenated data pushes of an OP_RETURN output, `None` for other outputs
+fn op_return_data(output: &TxOut) -> Option<Vec<u8>> {
+    if !output.script_pubkey.is_op_return() {
+        return None;
+    }
+    let mut data = Vec::new();
+    for instruction in output.script_pubkey.instructions().skip(1) {
+        match instruction {
+            Ok(Instruction::PushBytes(bytes)) => data.extend_from_slice(bytes.as_bytes()),
+            _ => return None,
+        }
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+
+    use super::*;
+
+    fn transaction() -> Transaction {
+        let address = Address::from_str("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+            .unwrap()
+            .assume_checked();
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(1000),
+                    script_pubkey: address.script_pubkey(),
+                },
+                TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: ScriptBuf::new_op_return([0xde, 0xad, 0xbe, 0xef]),
+                },
+            ],
+        }
+    }
+
+    fn expect(address: Option<&str>, amount: Option<&str>, op_return: Option<&str>) -> ExpectArgs {
+        ExpectArgs {
+            expect_address: address.map(String::from),
+            expect_amount: amount.map(String::from),
+            expect_op_return: op_return.map(String::from),
+            expect_output_script: None,
+        }
+    }
+
+    #[test]
+    fn test_expectations() {
+        let tx = transaction();
+        let address = Some("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq");
+
+        let ok = expect(address, Some("1000 sat"), Some("deadbeef"));
+        assert!(ok.parse(Network::Bitcoin).unwrap().check(&tx).is_ok());
+
+        // Amount of a different output
+        let wrong_amount = expect(address, Some("0 sat"), None);
+        assert!(wrong_amount
+            .parse(Network::Bitcoin)
+            .unwrap()
+            .check(&tx)
+            .is_err());
+
+        let wrong_op_return = expect(None, None, Some("deadbe"));
+        assert!(wrong_op_return
+            .parse(Network::Bitcoin)
+            .unwrap()
+            .check(&tx)
+            .is_err());
+
+        // Amount without denomination
+        assert!(expect(None, Some("1000"), None)
+            .parse(Network::Bitcoin)
+            .is_err());
+
+        // Address of another network
+        assert!(expect(address, None, None).parse(Network::Testnet).is_err());
+    }
+}
@@ -1,293 +1,785 @@
-//! Functions to fetch all components required to construct a compressed SPV proof
-//! from the Raito bridge RPC and a Bitcoin node.
+//! The `fetch` subcommand, assembling compressed SPV proofs from the Raito bridge RPC and a
+//! Bitcoin node, or from a bridge node alone, with progress bars on top of the library
+//! fetching functions.
 
-use std::{io::Write, path::PathBuf};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use bitcoin::{block::Header as BlockHeader, consensus, MerkleBlock, Transaction, Txid};
+use bitcoin::{block::Header as BlockHeader, consensus, Transaction, Txid};
 use bzip2::write::BzEncoder;
 use bzip2::Compression;
-use cairo_air::CairoProof;
-use raito_spv_core::{bitcoin::BitcoinClient, block_mmr::BlockInclusionProof};
-use serde::{Deserialize, Serialize};
-use stwo_prover::core::vcs::blake2_merkle::Blake2sMerkleHasher;
-use tracing::info;
+use raito_spv_core::block_mmr::BlockInclusionProof;
+use raito_spv_verifier::fetch::{
+    self, check_block_header, fetch_prevouts, fetch_witness_proof, ApiEndpoint, BitcoinDataSource,
+    ChainStateProof, RetryPolicy, TransactionInclusionProof, TransactionSource,
+};
+use raito_spv_verifier::proof::{
+    BundledTransaction, ChainStateCairoProof, CompressedSpvProof, ProofBundle,
+};
+use raito_spv_verifier::verify::{verify_proof, VerifierConfig};
+use raito_spv_verifier::work::check_block_depth;
+use tracing::{info, warn};
 
+use crate::network::{MAINNET_RAITO_RPC_URL, RAITO_RPC_URLS, RAITO_RPC_URL_REQUIRED};
 use crate::{
-    proof::{ChainState, CompressedSpvProof},
-    verify::{verify_proof, VerifierConfig},
+    cache::{fetch_chain_state_proof_cached, CacheArgs, ChainStateCache},
+    inspect::format_component_sizes,
+    pin::PinArgs,
+    progress,
+    verify::{print_verified_transaction, VerifierConfigArgs},
 };
 
 /// CLI arguments for the `fetch` subcommand
 #[derive(Clone, Debug, clap::Args)]
 pub struct FetchArgs {
     /// Transaction ID
+    #[arg(long, required_unless_present_any = ["txids_file", "tx_hex", "tx_file"])]
+    txid: Option<Txid>,
+    /// Raw transaction to prove (hex), embedded as is: only its Merkle path and block header
+    /// are fetched
+    #[arg(long, conflicts_with_all = ["txid", "txids_file", "tx_file"])]
+    tx_hex: Option<String>,
+    /// File with the raw transaction to prove, hex-encoded or binary
+    #[arg(long, conflicts_with_all = ["txid", "txids_file"])]
+    tx_file: Option<PathBuf>,
+    /// File with one transaction ID per line to fetch proofs for in a single batch
+    #[arg(
+        long,
+        conflicts_with_all = ["txid", "proof_path", "verify", "wait", "min_confirmations"]
+    )]
+    txids_file: Option<PathBuf>,
+    /// Write all batch proofs into a single bundle file instead of one file per transaction
+    #[arg(long, requires = "txids_file")]
+    bundle: Option<PathBuf>,
+    /// Path to save the proof (`-` for stdout); defaults to `<proof-dir>/<txid>-<height>.raito`
     #[arg(long)]
-    txid: Txid,
-    /// Path to save the proof
-    #[arg(long)]
-    proof_path: PathBuf,
-    /// Raito node RPC URL
+    proof_path: Option<PathBuf>,
+    /// Directory to save the proofs to when no proof path is given
+    #[arg(long, default_value = "./proofs")]
+    proof_dir: PathBuf,
+    /// Raito node RPC URL, defaults to the Raito bridge of the `--network`; required on
+    /// `testnet4` and `signet`, which have no public bridge
     #[arg(
         long,
         env = "RAITO_BRIDGE_RPC",
-        default_value = "https://api.raito.wtf"
+        default_value = MAINNET_RAITO_RPC_URL,
+        default_value_ifs = RAITO_RPC_URLS,
+        required_if_eq_any = RAITO_RPC_URL_REQUIRED
     )]
     raito_rpc_url: String,
     /// Bitcoin RPC URL
-    #[arg(long, env = "BITCOIN_RPC")]
-    bitcoin_rpc_url: String,
+    #[arg(long, env = "BITCOIN_RPC", required_unless_present = "bridge_only")]
+    bitcoin_rpc_url: Option<String>,
     /// Bitcoin RPC user:password (optional)
     #[arg(long, env = "USERPWD")]
     bitcoin_rpc_userpwd: Option<String>,
+    /// Fetch the transaction proofs and headers from the Raito bridge node as well, which
+    /// serves them from the Bitcoin node it indexes, instead of from a Bitcoin RPC
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["prevouts", "embed_witness"]
+    )]
+    bridge_only: bool,
+    /// Esplora API URL to fetch the transaction proofs of the blocks pruned by the Bitcoin
+    /// node from, and to check the blocks against with `--verify-sources`, e.g.
+    /// https://blockstream.info/api; the Raito bridge is used if not set
+    #[arg(long)]
+    esplora_url: Option<String>,
+    /// Check the block of the transaction against a second, independent source (Esplora if
+    /// `--esplora-url` is set, the Raito bridge otherwise) and fail if they disagree
+    #[arg(long, default_value = "false")]
+    verify_sources: bool,
     /// Verify the proof after fetching it
     #[arg(long, default_value = "false")]
     verify: bool,
+    /// Wait until the transaction is confirmed and buried under enough work in the proven
+    /// chain to pass verification (see `--min-confirmations` and `--min-work`)
+    #[arg(long, default_value = "false")]
+    wait: bool,
+    /// Embed the headers between the transaction block and the proven best block, so that
+    /// verification sums their actual work instead of a lower bound
+    #[arg(long, default_value = "false", conflicts_with = "txids_file")]
+    embed_headers: bool,
+    /// Maximum number of headers embedded with `--embed-headers`, the fetch fails if the
+    /// transaction block is buried under more blocks in the proven chain
+    #[arg(long, default_value = "2016")]
+    max_embedded_headers: u32,
+    /// Embed the outputs spent by the transaction inputs, so that the transaction display
+    /// shows input addresses and values and the fee (requires Bitcoin Core 25+, not proven)
+    #[arg(long, default_value = "false", conflicts_with = "txids_file")]
+    prevouts: bool,
+    /// Embed the coinbase of the block and the Merkle branches to its witness commitment, so
+    /// that verification proves the transaction witnesses along with its ID (downloads the
+    /// full block)
+    #[arg(long, default_value = "false", conflicts_with = "txids_file")]
+    embed_witness: bool,
+    /// Polling interval in seconds when waiting
+    #[arg(long, default_value = "60")]
+    poll_interval: u64,
+    #[command(flatten)]
+    cache: CacheArgs,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+    #[command(flatten)]
+    pin: PinArgs,
+    /// Do not draw progress bars
+    #[arg(long, default_value = "false")]
+    no_progress: bool,
     /// Development mode
     #[arg(long, default_value = "false")]
     dev: bool,
 }
 
-/// Chain state and its recursive proof produced by the Raito node
-#[derive(Serialize, Deserialize)]
-pub struct ChainStateProof {
-    /// Canonical chain state snapshot
-    #[serde(rename = "chainstate")]
-    pub chain_state: ChainState,
-    /// Recursive STARK proof attesting `chain_state` and block MMR root validity
-    #[serde(rename = "proof")]
-    pub chain_state_proof: CairoProof<Blake2sMerkleHasher>,
-}
+impl FetchArgs {
+    /// Where the transaction proofs and headers are fetched from
+    fn bitcoin_source(&self, raito_rpc: &ApiEndpoint) -> Result<BitcoinDataSource, anyhow::Error> {
+        if self.bridge_only {
+            return Ok(BitcoinDataSource::Bridge {
+                endpoint: raito_rpc.clone(),
+            });
+        }
+        Ok(BitcoinDataSource::Node {
+            url: self.bitcoin_rpc_url()?,
+            userpwd: self.bitcoin_rpc_userpwd.clone(),
+            pruned_fallback: Some(pruned_fallback(self.esplora_url.as_deref(), raito_rpc)),
+        })
+    }
 
-/// Bitcoin transaction inclusion data in a specific block
-#[derive(Serialize, Deserialize)]
-pub struct TransactionInclusionProof {
-    /// The full Bitcoin transaction being proven
-    pub transaction: Transaction,
-    /// Encoded PartialMerkleTree containing the Merkle path for the transaction
-    pub transaction_proof: Vec<u8>,
-    /// Header of the block that includes the transaction
-    pub block_header: BlockHeader,
-    /// Height of the block that includes the transaction
-    pub block_height: u32,
+    /// Source to check the blocks of the transactions against with `--verify-sources`,
+    /// independent from [FetchArgs::bitcoin_source]
+    fn verification_source(
+        &self,
+        raito_rpc: &ApiEndpoint,
+    ) -> Result<Option<BitcoinDataSource>, anyhow::Error> {
+        if !self.verify_sources {
+            return Ok(None);
+        }
+        match (&self.esplora_url, self.bridge_only) {
+            (Some(url), _) => Ok(Some(BitcoinDataSource::Esplora {
+                endpoint: esplora_endpoint(url, raito_rpc),
+            })),
+            (None, false) => Ok(Some(BitcoinDataSource::Bridge {
+                endpoint: raito_rpc.clone(),
+            })),
+            (None, true) => {
+                anyhow::bail!("--verify-sources with --bridge-only requires an --esplora-url")
+            }
+        }
+    }
+
+    /// Bitcoin RPC URL, for the data only a Bitcoin node serves
+    fn bitcoin_rpc_url(&self) -> Result<String, anyhow::Error> {
+        self.bitcoin_rpc_url
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Bitcoin RPC URL is required unless --bridge-only"))
+    }
 }
 
 /// Run the `fetch` subcommand: build a compressed proof and write it to disk
 ///
 /// Returns an error if any network request fails or the proof cannot be written
 /// to the specified path.
-pub async fn run(args: FetchArgs) -> Result<(), anyhow::Error> {
-    // Construct compressed proof from different components
-    let compressed_proof = fetch_compressed_proof(
-        args.txid,
-        args.bitcoin_rpc_url,
-        args.bitcoin_rpc_userpwd,
-        args.raito_rpc_url,
-        args.dev,
-    )
-    .await?;
+pub async fn run(args: FetchArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let (mut config, denomination) = args.verifier_config.resolve_with_denomination()?;
+    if args.dev {
+        config.enable_dev_mode();
+    }
+    let cache = args.cache.cache();
+    if !args.no_progress {
+        progress::enable();
+    }
+
+    if let Some(txids_file) = &args.txids_file {
+        return run_batch(&args, &raito_rpc, txids_file, cache.as_ref()).await;
+    }
+    let transaction = if let Some(tx_hex) = &args.tx_hex {
+        TransactionSource::Raw(parse_raw_transaction(tx_hex.as_bytes())?)
+    } else if let Some(tx_file) = &args.tx_file {
+        TransactionSource::Raw(parse_raw_transaction(&std::fs::read(tx_file)?)?)
+    } else if let Some(txid) = args.txid {
+        TransactionSource::Txid(txid)
+    } else {
+        anyhow::bail!("Either a transaction ID, a raw transaction or a txids file is required");
+    };
+    let txid = transaction.txid();
+
+    let mut compressed_proof = if args.wait || config.min_confirmations.is_some() {
+        // Wait for the transaction to be confirmed deep enough before assembling the proof
+        let (chain_state_proof, transaction_proof) =
+            wait_for_confirmations(&transaction, &args, &raito_rpc, &config, cache.as_ref())
+                .await?;
+        build_compressed_proof(chain_state_proof, transaction_proof, &raito_rpc, args.dev).await?
+    } else {
+        // Construct compressed proof from different components
+        fetch_compressed_proof(
+            transaction,
+            args.bitcoin_source(&raito_rpc)?,
+            &raito_rpc,
+            cache.as_ref(),
+            args.dev,
+        )
+        .await?
+    };
+
+    if let Some(source) = args.verification_source(&raito_rpc)? {
+        check_block_header(
+            &compressed_proof.block_header,
+            compressed_proof.block_height,
+            &source,
+        )
+        .await?;
+    }
+
+    if args.embed_headers {
+        compressed_proof.subchain_headers = Some(
+            fetch_subchain_headers(
+                compressed_proof.block_height,
+                compressed_proof.chain_state.block_height,
+                args.max_embedded_headers,
+                &args.bitcoin_source(&raito_rpc)?,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch subchain headers: {:?}", e))?,
+        );
+    }
+
+    if args.prevouts {
+        compressed_proof.prevouts = Some(
+            fetch_prevouts(
+                txid,
+                compressed_proof.block_header.block_hash(),
+                args.bitcoin_rpc_url()?,
+                args.bitcoin_rpc_userpwd.clone(),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch previous outputs: {:?}", e))?,
+        );
+    }
+
+    if args.embed_witness {
+        compressed_proof.witness_proof = Some(
+            fetch_witness_proof(
+                txid,
+                compressed_proof.block_header.block_hash(),
+                args.bitcoin_rpc_url()?,
+                args.bitcoin_rpc_userpwd.clone(),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch witness proof: {:?}", e))?,
+        );
+    }
+
+    let proof_path = args.proof_path.clone().unwrap_or_else(|| {
+        args.proof_dir
+            .join(default_proof_file_name(&compressed_proof))
+    });
 
     // Save proof to the file using bincode binary codec with bzip2 compression
-    save_compressed_proof_with_bzip2(&compressed_proof, &args.proof_path)?;
+    save_compressed_proof_with_bzip2(&compressed_proof, &proof_path)?;
+
+    // Summary of the proof size per component, on stderr to keep stdout for the proof stream
+    eprint!("{}", format_component_sizes(&compressed_proof)?);
+    if !is_stdio_path(&proof_path) {
+        eprintln!(
+            "  {:<20} {} bytes",
+            "compressed file",
+            std::fs::metadata(&proof_path)?.len()
+        );
+    }
+
+    // Print the resulting path so that scripts can pick the proof up
+    if args.proof_path.is_none() {
+        println!("{}", proof_path.display());
+    }
 
     if args.verify {
-        verify_proof(compressed_proof, &VerifierConfig::default(), args.dev).await?;
+        let mut report = verify_proof(compressed_proof, &config).await?;
+        args.pin.check(&mut report)?;
+        print_verified_transaction(&report, denomination);
     }
 
     Ok(())
 }
 
+/// Poll the Raito bridge RPC and the Bitcoin node until the transaction is confirmed and
+/// buried deep enough in the proven chain
+///
+/// Without `--wait`, checks the work policy once and fails if it is not satisfied.
+/// Returns the chain state proof and transaction inclusion data to assemble the proof from.
+async fn wait_for_confirmations(
+    transaction: &TransactionSource,
+    args: &FetchArgs,
+    raito_rpc: &ApiEndpoint,
+    config: &VerifierConfig,
+    cache: Option<&ChainStateCache>,
+) -> Result<(ChainStateProof, TransactionInclusionProof), anyhow::Error> {
+    let poll_interval = Duration::from_secs(args.poll_interval);
+    let txid = transaction.txid();
+    let source = args.bitcoin_source(raito_rpc)?;
+
+    loop {
+        let transaction_proof = match fetch_transaction_proof(transaction.clone(), &source).await {
+            Ok(proof) => proof,
+            Err(err) if args.wait => {
+                info!("Transaction {} is not confirmed yet: {}", txid, err);
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+            Err(err) => anyhow::bail!("Failed to fetch transaction proof: {:?}", err),
+        };
+
+        let chain_state_proof = match fetch_chain_state_proof_cached(raito_rpc, cache).await {
+            Ok(proof) => proof,
+            Err(err) if args.wait => {
+                warn!("Failed to fetch chain state proof: {}", err);
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+            Err(err) => anyhow::bail!("Failed to fetch chain state proof: {:?}", err),
+        };
+
+        // Make sure the proof will pass the verifier's work policy
+        let block_height = transaction_proof.block_height;
+        match check_block_depth(block_height, &chain_state_proof.chain_state, config) {
+            Ok(()) => return Ok((chain_state_proof, transaction_proof)),
+            Err(err) if args.wait => {
+                info!("Transaction {}: {}, waiting ...", txid, err);
+                tokio::time::sleep(poll_interval).await;
+            }
+            Err(err) => anyhow::bail!("Transaction {}: {}", txid, err),
+        }
+    }
+}
+
+/// Fetch proofs for all transactions listed in `txids_file`, sharing one chain state proof
+///
+/// Proofs are written either to one file per transaction in the proof directory or to a
+/// single bundle. A summary of successes and failures is printed to stdout; returns an
+/// error if any of the proofs could not be fetched.
+async fn run_batch(
+    args: &FetchArgs,
+    raito_rpc: &ApiEndpoint,
+    txids_file: &Path,
+    cache: Option<&ChainStateCache>,
+) -> Result<(), anyhow::Error> {
+    let txids = read_txids_file(txids_file)?;
+    info!("Fetching proofs for {} transaction(s) ...", txids.len());
+
+    // The chain state proof is shared by all transactions, fetch it only once
+    let ChainStateProof {
+        mut chain_state,
+        chain_state_proof,
+    } = fetch_chain_state_proof_cached(raito_rpc, cache)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch chain state proof: {:?}", e))?;
+    let mut chain_state_proof: ChainStateCairoProof = chain_state_proof.into();
+
+    let mut bundled = Vec::new();
+    let mut failures = Vec::new();
+    for txid in &txids {
+        let transaction =
+            match fetch_bundled_transaction(*txid, chain_state.block_height, args, raito_rpc).await
+            {
+                Ok(transaction) => transaction,
+                Err(err) => {
+                    warn!("Failed to fetch proof for {}: {}", txid, err);
+                    failures.push((*txid, err.to_string()));
+                    continue;
+                }
+            };
+        if args.bundle.is_some() {
+            println!("OK      {}", txid);
+            bundled.push(transaction);
+            continue;
+        }
+
+        // Assemble a standalone proof, then take the shared chain state proof back
+        let BundledTransaction {
+            block_header,
+            block_height,
+            block_header_proof,
+            transaction,
+            transaction_proof,
+        } = transaction;
+        let proof = CompressedSpvProof {
+            chain_state,
+            chain_state_proof,
+            block_header,
+            block_height,
+            block_header_proof,
+            transaction,
+            transaction_proof,
+            subchain_headers: None,
+            prevouts: None,
+            witness_proof: None,
+        };
+        let proof_path = args.proof_dir.join(default_proof_file_name(&proof));
+        let res = save_compressed_proof_with_bzip2(&proof, &proof_path);
+        match res {
+            Ok(()) => println!("OK      {} {}", txid, proof_path.display()),
+            Err(err) => {
+                warn!("Failed to save proof for {}: {}", txid, err);
+                failures.push((*txid, err.to_string()));
+            }
+        }
+        chain_state = proof.chain_state;
+        chain_state_proof = proof.chain_state_proof;
+    }
+
+    if let Some(bundle_path) = &args.bundle {
+        let bundle = ProofBundle {
+            chain_state,
+            chain_state_proof,
+            transactions: bundled,
+        };
+        save_proof_bundle_with_bzip2(&bundle, bundle_path)?;
+        println!(
+            "Bundle of {} proof(s) written to {}",
+            bundle.transactions.len(),
+            bundle_path.display()
+        );
+    }
+
+    for (txid, error) in &failures {
+        println!("FAILED  {} {}", txid, error);
+    }
+    println!(
+        "Fetched {} of {} proof(s), {} failed",
+        txids.len() - failures.len(),
+        txids.len(),
+        failures.len()
+    );
+
+    if !failures.is_empty() {
+        anyhow::bail!("Failed to fetch {} proof(s)", failures.len());
+    }
+    Ok(())
+}
+
+/// Fetch the inclusion data of a single transaction of a batch
+async fn fetch_bundled_transaction(
+    txid: Txid,
+    chain_height: u32,
+    args: &FetchArgs,
+    raito_rpc: &ApiEndpoint,
+) -> Result<BundledTransaction, anyhow::Error> {
+    let TransactionInclusionProof {
+        transaction,
+        transaction_proof,
+        block_header,
+        block_height,
+    } = fetch_transaction_proof(txid, &args.bitcoin_source(raito_rpc)?).await?;
+    if let Some(source) = args.verification_source(raito_rpc)? {
+        check_block_header(&block_header, block_height, &source).await?;
+    }
+    let block_header_proof =
+        fetch_block_proof(block_height, chain_height, raito_rpc, args.dev).await?;
+    Ok(BundledTransaction {
+        block_header,
+        block_height,
+        block_header_proof,
+        transaction,
+        transaction_proof,
+    })
+}
+
+/// Parse a raw transaction, hex-encoded (surrounding whitespace is ignored) or binary
+pub fn parse_raw_transaction(bytes: &[u8]) -> Result<Transaction, anyhow::Error> {
+    let hex_transaction = std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| hex::decode(text.trim()).ok());
+    let transaction_bytes = hex_transaction.as_deref().unwrap_or(bytes);
+    consensus::deserialize(transaction_bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid raw transaction: {}", e))
+}
+
+/// Read transaction IDs from a file, one per line; blank lines and `#` comments are skipped
+pub fn read_txids_file(txids_file: &Path) -> Result<Vec<Txid>, anyhow::Error> {
+    let contents = std::fs::read_to_string(txids_file)?;
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .map_err(|e| anyhow::anyhow!("Invalid transaction ID {}: {}", line, e))
+        })
+        .collect()
+}
+
+/// Save a proof bundle to disk using bincode binary codec with bzip2 compression
+pub fn save_proof_bundle_with_bzip2(
+    bundle: &ProofBundle,
+    bundle_path: &PathBuf,
+) -> Result<(), anyhow::Error> {
+    let serialized_bytes = bundle.to_bytes()?;
+
+    // Create parent directories if they don't exist
+    if let Some(bundle_dir) = bundle_path.parent() {
+        std::fs::create_dir_all(bundle_dir)?;
+    }
+
+    let file = std::fs::File::create(bundle_path)?;
+    write_bzip2(&serialized_bytes, file)?;
+
+    info!("Proof bundle written to {}", bundle_path.display());
+    Ok(())
+}
+
 /// Save a compressed proof to disk using bincode binary codec with bzip2 compression
 ///
 /// - `proof`: The compressed SPV proof to save
-/// - `proof_path`: Path where the proof should be saved
+/// - `proof_path`: Path where the proof should be saved, `-` for stdout
 ///
-/// This function first serializes the proof to bytes using bincode binary codec,
-/// then applies bzip2 compression with maximum compression ratio for optimal file size.
+/// This function first serializes the proof to bytes using bincode binary codec, prefixed
+/// with the format version (see [CompressedSpvProof::to_bytes]), then applies bzip2
+/// compression with maximum compression ratio for optimal file size.
 pub fn save_compressed_proof_with_bzip2(
     proof: &CompressedSpvProof,
     proof_path: &PathBuf,
 ) -> Result<(), anyhow::Error> {
     info!("Serializing proof to binary format...");
 
-    // Step 1: Serialize the proof to bytes using bincode
-    let serialized_bytes = bincode::serialize(proof)?;
+    // Step 1: Serialize the proof to versioned bincode bytes
+    let serialized_bytes = proof.to_bytes()?;
     info!(
         "Serialized {} bytes, now compressing...",
         serialized_bytes.len()
     );
 
+    // Step 2: Compress the serialized bytes and write to stdout or file
+    save_serialized_proof_with_bzip2(&serialized_bytes, proof_path)
+}
+
+/// Save a compressed proof already serialized with [CompressedSpvProof::to_bytes] to disk with
+/// bzip2 compression, see [save_compressed_proof_with_bzip2]
+pub fn save_serialized_proof_with_bzip2(
+    serialized_bytes: &[u8],
+    proof_path: &PathBuf,
+) -> Result<(), anyhow::Error> {
+    if is_stdio_path(proof_path) {
+        let mut stdout = std::io::stdout().lock();
+        write_bzip2(serialized_bytes, &mut stdout)?;
+        stdout.flush()?;
+        info!("Compressed proof written to stdout");
+        return Ok(());
+    }
+
     // Create parent directories if they don't exist
     if let Some(proof_dir) = proof_path.parent() {
         std::fs::create_dir_all(proof_dir)?;
     }
 
-    // Step 2: Compress the serialized bytes and write to file
     let file = std::fs::File::create(proof_path)?;
-    let mut bz_encoder = BzEncoder::new(file, Compression::best());
+    write_bzip2(serialized_bytes, file)?;
+
+    info!("Compressed proof written to {}", proof_path.display());
+    Ok(())
+}
+
+/// Compress bytes with bzip2 and write them to `writer`
+pub fn write_bzip2<W: Write>(bytes: &[u8], writer: W) -> Result<(), anyhow::Error> {
+    let mut bz_encoder = BzEncoder::new(writer, Compression::best());
 
     // Write the serialized bytes to the bzip2 encoder
-    bz_encoder.write_all(&serialized_bytes)?;
+    bz_encoder.write_all(bytes)?;
 
     // Finish the bzip2 stream to ensure all data is written
     bz_encoder.finish()?;
-
-    info!("Compressed proof written to {}", proof_path.display());
     Ok(())
 }
 
+/// Default file name of a proof: `<txid>-<height>.raito`
+pub fn default_proof_file_name(proof: &CompressedSpvProof) -> String {
+    format!(
+        "{}-{}.raito",
+        proof.transaction.compute_txid(),
+        proof.block_height
+    )
+}
+
+/// Whether the proof path is `-`, i.e. the proof is read from stdin or written to stdout
+pub fn is_stdio_path(proof_path: &Path) -> bool {
+    proof_path.as_os_str() == "-"
+}
+
 /// Fetch all components required to construct a `CompressedSpvProof`
 ///
-/// - `txid`: Transaction id to prove
-/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
-/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
-/// - `raito_rpc_url`: URL of the Raito bridge RPC
+/// - `transaction`: Transaction id to prove, or raw transaction to embed
+/// - `source`: Where the transaction proof is fetched from
+/// - `raito_rpc`: Raito bridge RPC endpoint
+/// - `cache`: Chain state proof cache, `None` to always fetch a fresh chain state proof
 pub async fn fetch_compressed_proof(
-    txid: Txid,
-    bitcoin_rpc_url: String,
-    bitcoin_rpc_userpwd: Option<String>,
-    raito_rpc_url: String,
+    transaction: impl Into<TransactionSource>,
+    source: BitcoinDataSource,
+    raito_rpc: &ApiEndpoint,
+    cache: Option<&ChainStateCache>,
     dev: bool,
 ) -> Result<CompressedSpvProof, anyhow::Error> {
-    let ChainStateProof {
-        chain_state,
-        chain_state_proof,
-    } = fetch_chain_state_proof(&raito_rpc_url)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to fetch chain state proof: {:?}", e))?;
-
-    let TransactionInclusionProof {
-        transaction,
-        transaction_proof,
-        block_header,
-        block_height,
-    } = fetch_transaction_proof(txid, bitcoin_rpc_url, bitcoin_rpc_userpwd)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to fetch transaction proof: {:?}", e))?;
+    // The chain state proof download dominates wall time, so fetch the transaction proof
+    // concurrently; the block proof depends on both and is fetched as soon as they are ready
+    let (chain_state_proof, transaction_proof) = tokio::try_join!(
+        async {
+            fetch_chain_state_proof_cached(raito_rpc, cache)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch chain state proof: {:?}", e))
+        },
+        async {
+            fetch_transaction_proof(transaction, &source)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch transaction proof: {:?}", e))
+        },
+    )?;
 
-    let block_header_proof = fetch_block_proof(
-        block_height,
-        chain_state.block_height as u32,
-        &raito_rpc_url,
-        dev,
-    )
-    .await
-    .map_err(|e| anyhow::anyhow!("Failed to fetch block proof: {:?}", e))?;
+    build_compressed_proof(chain_state_proof, transaction_proof, raito_rpc, dev).await
+}
 
-    Ok(CompressedSpvProof {
-        chain_state,
-        chain_state_proof,
-        block_header,
-        block_header_proof,
-        transaction,
-        transaction_proof,
-    })
+/// Construct a `CompressedSpvProof` from an already fetched chain state proof and
+/// transaction inclusion proof, see [fetch::build_compressed_proof]
+pub async fn build_compressed_proof(
+    chain_state_proof: ChainStateProof,
+    transaction_proof: TransactionInclusionProof,
+    raito_rpc: &ApiEndpoint,
+    dev: bool,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    let progress = progress::stage("Fetching block proof");
+    let proof =
+        fetch::build_compressed_proof(chain_state_proof, transaction_proof, raito_rpc, dev).await?;
+    progress.finish_with_message("Fetched block proof");
+    Ok(proof)
 }
 
-/// Fetch the latest chain state proof from the Raito bridge RPC
-///
-/// - `raito_rpc_url`: URL of the Raito bridge RPC endpoint
+/// Fetch the latest chain state proof from the Raito bridge RPC, see
+/// [fetch::fetch_chain_state_proof]
 pub async fn fetch_chain_state_proof(
-    raito_rpc_url: &str,
+    raito_rpc: &ApiEndpoint,
 ) -> Result<ChainStateProof, anyhow::Error> {
-    info!("Fetching latest chain state proof ...");
-    let url = format!("{}/chainstate-proof/recent_proof", raito_rpc_url);
-    let client = reqwest::Client::new();
-    let response = client
-        .get(url)
-        .header("Accept-Encoding", "gzip")
-        .send()
-        .await?;
-    match response.error_for_status() {
-        Ok(res) => Ok(res.json().await?),
-        Err(e) => Err(e.into()),
+    // The Cairo proof can be tens of megabytes, report the download progress
+    let mut progress = None;
+    let proof = fetch::fetch_chain_state_proof_with_progress(raito_rpc, |position, len| {
+        progress
+            .get_or_insert_with(|| progress::download("Downloading chain state proof", len))
+            .set_position(position)
+    })
+    .await?;
+    if let Some(progress) = progress {
+        progress.finish_with_message("Downloaded chain state proof");
     }
+    Ok(proof)
 }
 
-/// Fetch the transaction inclusion data from a Bitcoin RPC
-///
-/// - `txid`: Transaction id to fetch
-/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
-/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
-pub async fn fetch_transaction_proof(
-    txid: Txid,
-    bitcoin_rpc_url: String,
-    bitcoin_rpc_userpwd: Option<String>,
-) -> Result<TransactionInclusionProof, anyhow::Error> {
-    info!("Fetching transaction proof for {} ...", txid);
-    let bitcoin_client = BitcoinClient::new(bitcoin_rpc_url, bitcoin_rpc_userpwd)?;
-    let MerkleBlock { header, txn } = bitcoin_client
-        .get_transaction_inclusion_proof(&txid)
-        .await?;
-
-    let block_hash = header.block_hash();
-    let transaction = bitcoin_client.get_transaction(&txid, &block_hash).await?;
+/// Source of the transaction proofs of the blocks pruned by the Bitcoin node: the Esplora API
+/// if set, the Raito bridge RPC otherwise
+pub fn pruned_fallback(
+    esplora_url: Option<&str>,
+    raito_rpc: &ApiEndpoint,
+) -> Box<BitcoinDataSource> {
+    Box::new(match esplora_url {
+        Some(url) => BitcoinDataSource::Esplora {
+            endpoint: esplora_endpoint(url, raito_rpc),
+        },
+        None => BitcoinDataSource::Bridge {
+            endpoint: raito_rpc.clone(),
+        },
+    })
+}
 
-    let block_header_ex = bitcoin_client.get_block_header_ex(&block_hash).await?;
-    let block_height = block_header_ex.height;
+/// Esplora API endpoint at `url`, with the retry policy of the Raito bridge RPC endpoint
+pub fn esplora_endpoint(url: &str, raito_rpc: &ApiEndpoint) -> ApiEndpoint {
+    ApiEndpoint::new(url).with_retry_policy(raito_rpc.retry_policy.clone())
+}
 
-    Ok(TransactionInclusionProof {
-        transaction,
-        transaction_proof: consensus::encode::serialize(&txn),
-        block_header: header,
-        block_height: block_height as u32,
-    })
+/// Fetch the transaction inclusion data from a Bitcoin RPC or the Raito bridge RPC, see
+/// [BitcoinDataSource::fetch_transaction_proof]
+pub async fn fetch_transaction_proof(
+    transaction: impl Into<TransactionSource>,
+    source: &BitcoinDataSource,
+) -> Result<TransactionInclusionProof, anyhow::Error> {
+    let progress = progress::stage("Fetching transaction proof");
+    let proof = source.fetch_transaction_proof(transaction).await?;
+    progress.finish_with_message("Fetched transaction proof");
+    Ok(proof)
 }
 
-/// Fetch the block MMR inclusion proof from the Raito bridge RPC
+/// Fetch the headers of the blocks on top of the target block, up to the chain height, see
+/// [BitcoinDataSource::fetch_subchain_headers]
 ///
-/// - `block_height`: Height of the block to prove
-/// - `chain_height`: Current best height (chain head)
-/// - `raito_rpc_url`: URL of the Raito bridge RPC endpoint
+/// Fails without fetching anything if there are more than `max_headers` headers.
+pub async fn fetch_subchain_headers(
+    block_height: u32,
+    chain_height: u32,
+    max_headers: u32,
+    source: &BitcoinDataSource,
+) -> Result<Vec<BlockHeader>, anyhow::Error> {
+    let count = chain_height.saturating_sub(block_height);
+    if count > max_headers {
+        anyhow::bail!(
+            "Block {} is buried under {} blocks, more headers than --max-embedded-headers ({})",
+            block_height,
+            count,
+            max_headers
+        );
+    }
+    let progress = progress::stage("Fetching subchain headers");
+    let headers = source
+        .fetch_subchain_headers(block_height, chain_height)
+        .await?;
+    progress.finish_with_message("Fetched subchain headers");
+    Ok(headers)
+}
+
+/// Fetch the block MMR inclusion proof from the Raito bridge RPC, see
+/// [fetch::fetch_block_proof]
 pub async fn fetch_block_proof(
     block_height: u32,
     chain_height: u32,
-    raito_rpc_url: &str,
+    raito_rpc: &ApiEndpoint,
     dev: bool,
 ) -> Result<BlockInclusionProof, anyhow::Error> {
-    let url = if dev {
-        info!("DEV MODE: using local bridge node and default chain height");
-        format!(
-            "http://127.0.0.1:5000/block-inclusion-proof/{}",
-            block_height
-        )
-    } else {
-        let mmr_height = get_mmr_height(&raito_rpc_url).await?;
-        if mmr_height < chain_height {
-            return Err(anyhow::anyhow!(
-                "MMR height {} is less than chain height {}",
-                mmr_height,
-                chain_height
-            ));
-        }
-        format!(
-            "{}/block-inclusion-proof/{}?chain_height={}",
-            raito_rpc_url, block_height, chain_height
-        )
-    };
+    let progress = progress::stage("Fetching block proof");
+    let proof = fetch::fetch_block_proof(block_height, chain_height, raito_rpc, dev).await?;
+    progress.finish_with_message("Fetched block proof");
+    Ok(proof)
+}
 
-    if block_height > chain_height {
-        return Err(anyhow::anyhow!(
-            "Block height {} cannot be greater than chain height {}",
-            block_height,
-            chain_height
-        ));
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::Network;
 
-    info!("Fetching block proof for block height {} ...", block_height);
-    let response = reqwest::get(url).await?;
-    match response.error_for_status() {
-        Ok(res) => Ok(res.json().await?),
-        Err(e) => Err(e.into()),
+    #[test]
+    fn test_parse_raw_transaction() {
+        let transaction = genesis_block(Network::Bitcoin).txdata[0].clone();
+        let bytes = consensus::serialize(&transaction);
+
+        let hex_transaction = format!("  {}\n", hex::encode(&bytes));
+        assert_eq!(
+            parse_raw_transaction(hex_transaction.as_bytes()).unwrap(),
+            transaction
+        );
+        assert_eq!(parse_raw_transaction(&bytes).unwrap(), transaction);
+        assert!(parse_raw_transaction(b"00").is_err());
     }
-}
 
-/// Get the current MMR height from the Raito bridge RPC
-pub async fn get_mmr_height(raito_rpc_url: &str) -> Result<u32, anyhow::Error> {
-    let url = format!("{}/head", raito_rpc_url);
-    let client = reqwest::Client::new();
-    let response = client.get(url).send().await?;
-    match response.error_for_status() {
-        Ok(res) => Ok(res.json().await?),
-        Err(e) => Err(e.into()),
+    #[tokio::test]
+    async fn test_fetch_subchain_headers_limit() {
+        // Nothing listens there, the limit is checked before any request
+        let source = BitcoinDataSource::Esplora {
+            endpoint: ApiEndpoint::new("http://127.0.0.1:9"),
+        };
+        let err = fetch_subchain_headers(100, 2200, 2016, &source)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--max-embedded-headers"));
+        assert!(fetch_subchain_headers(100, 100, 0, &source)
+            .await
+            .unwrap()
+            .is_empty());
     }
 }
@@ -0,0 +1,194 @@
+//! Generation of proof files with controlled defects, to test the error handling of
+//! applications integrating the verifier.
+
+use std::path::PathBuf;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::Transaction;
+use raito_spv_core::block_mmr::BlockInclusionProof;
+use raito_spv_core::primitives::{find_mountain, hash_from_hex, hash_to_hex};
+use raito_spv_verifier::proof::ChainState;
+use tracing::info;
+
+use crate::convert::{load_proof, save_proof, ProofFormat};
+
+/// CLI arguments for the `gen-fixture` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct GenFixtureArgs {
+    /// Path to a valid proof to derive the fixture from (binary or JSON)
+    #[arg(long)]
+    template: PathBuf,
+    /// Path to write the fixture to
+    #[arg(long = "out")]
+    output: PathBuf,
+    /// Defect to introduce, can be repeated; the fixture is a copy of the template if omitted
+    #[arg(long = "defect", value_enum)]
+    defects: Vec<Defect>,
+    /// Age in seconds of the chain state with the `stale-chain-state` defect
+    #[arg(long, default_value_t = 30 * 24 * 3600)]
+    stale_age: u32,
+    /// Output format; inferred from the output file extension if omitted
+    #[arg(long)]
+    to: Option<ProofFormat>,
+}
+
+/// Defect of a generated proof, each one failing a different stage of the verification
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Defect {
+    /// The transaction is not the one committed to by the Merkle proof
+    /// (`transaction_inclusion` failure)
+    WrongTxid,
+    /// A hash of the block MMR inclusion proof is altered (`mmr` failure)
+    BrokenMmrPath,
+    /// The chain state is older than `--stale-age` (`policy` failure with a shorter
+    /// `--max-proof-age`, `chain_state` failure otherwise since the recursive proof doesn't
+    /// attest it)
+    StaleChainState,
+}
+
+/// Run the `gen-fixture` subcommand: load the template, introduce the defects and save the
+/// fixture
+pub async fn run(args: GenFixtureArgs) -> Result<(), anyhow::Error> {
+    let mut proof = load_proof(&args.template, ProofFormat::detect(&args.template)?)?;
+
+    for defect in &args.defects {
+        match defect {
+            Defect::WrongTxid => {
+                let txid = proof.transaction.compute_txid();
+                change_txid(&mut proof.transaction);
+                info!(
+                    "Replaced transaction {} with {}",
+                    txid,
+                    proof.transaction.compute_txid()
+                );
+            }
+            Defect::BrokenMmrPath => {
+                let hash = break_mmr_path(&mut proof.block_header_proof)?;
+                info!("Altered block MMR proof hash {}", hash);
+            }
+            Defect::StaleChainState => {
+                let now = chrono::Utc::now().timestamp();
+                let best_block_time = (now - args.stale_age as i64).max(0) as u32;
+                make_stale(&mut proof.chain_state, best_block_time);
+                info!(
+                    "Moved the chain state back to best block time {}",
+                    best_block_time
+                );
+            }
+        }
+    }
+
+    let format = args
+        .to
+        .unwrap_or_else(|| ProofFormat::from_extension(&args.output));
+    save_proof(&proof, &args.output, format)?;
+    info!(
+        "Fixture with defects {:?} written to {}",
+        args.defects,
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Change the transaction, and therefore its txid, by bumping its lock time
+pub fn change_txid(transaction: &mut Transaction) {
+    let lock_time = transaction.lock_time.to_consensus_u32();
+    transaction.lock_time = LockTime::from_consensus(lock_time.wrapping_add(1));
+}
+
+/// Flip a bit of the first sibling hash of the MMR path, or of the peak of the leaf if it has
+/// no siblings. Returns the original hash.
+pub fn break_mmr_path(proof: &mut BlockInclusionProof) -> Result<String, anyhow::Error> {
+    let hash = match proof.siblings_hashes.first_mut() {
+        Some(sibling) => sibling,
+        None => {
+            let (peak_index, _) = find_mountain(proof.leaf_index, proof.leaf_count)
+                .ok_or_else(|| anyhow::anyhow!("Leaf index is out of range"))?;
+            proof
+                .peaks_hashes
+                .get_mut(peak_index)
+                .ok_or_else(|| anyhow::anyhow!("Missing peak hash {}", peak_index))?
+        }
+    };
+    let mut altered = hash_from_hex(hash)?;
+    altered[31] ^= 1;
+    Ok(std::mem::replace(hash, hash_to_hex(&altered)))
+}
+
+/// Shift the timestamps of the chain state so that its best block is mined at
+/// `best_block_time`
+pub fn make_stale(chain_state: &mut ChainState, best_block_time: u32) {
+    let shift = chain_state
+        .best_block_time()
+        .saturating_sub(best_block_time);
+    chain_state.epoch_start_time = chain_state.epoch_start_time.saturating_sub(shift);
+    for timestamp in &mut chain_state.prev_timestamps {
+        *timestamp = timestamp.saturating_sub(shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{BlockHash, MerkleBlock, Network};
+    use raito_spv_core::block_mmr::{verify_block_inclusion, BlockMMR};
+    use raito_spv_verifier::error::FailureClass;
+    use raito_spv_verifier::verify::{verify_chain_state_age, verify_transaction, VerifierConfig};
+
+    #[test]
+    fn test_change_txid() {
+        let block = bitcoin::constants::genesis_block(Network::Bitcoin);
+        let mut transaction = block.txdata[0].clone();
+        let txid = transaction.compute_txid();
+        let proof = MerkleBlock::from_block_with_predicate(&block, |id| *id == txid).txn;
+        let transaction_proof = consensus::serialize(&proof);
+        assert!(verify_transaction(&transaction, &block.header, transaction_proof.clone()).is_ok());
+
+        change_txid(&mut transaction);
+        let err = verify_transaction(&transaction, &block.header, transaction_proof).unwrap_err();
+        assert_eq!(err.class(), FailureClass::TransactionInclusion);
+    }
+
+    #[tokio::test]
+    async fn test_break_mmr_path() {
+        let header = bitcoin::constants::genesis_block(Network::Bitcoin).header;
+        let mut mmr = BlockMMR::default();
+        for _ in 0..5 {
+            mmr.add_block_header(&header).await.unwrap();
+        }
+
+        // Leaf in the mountain of 4 leaves, then the leaf alone in its mountain
+        for block_height in [1, 4] {
+            let mut proof = mmr.generate_proof(block_height, None).await.unwrap();
+            assert!(verify_block_inclusion(&header, &proof).unwrap());
+            break_mmr_path(&mut proof).unwrap();
+            assert!(!verify_block_inclusion(&header, &proof).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_make_stale() {
+        let mut chain_state = ChainState {
+            block_height: 1,
+            total_work: "8590065666".to_string(),
+            best_block_hash: BlockHash::all_zeros(),
+            current_target: "26959535291011309493156476344723991336010898738574164086137773096960"
+                .to_string(),
+            epoch_start_time: 1_000,
+            prev_timestamps: vec![1_000, 1_600],
+        };
+        make_stale(&mut chain_state, 600);
+        assert_eq!(chain_state.best_block_time(), 600);
+        assert_eq!(chain_state.epoch_start_time, 0);
+        assert_eq!(chain_state.prev_timestamps, vec![0, 600]);
+
+        let config = VerifierConfig {
+            max_proof_age: Some(3600),
+            ..Default::default()
+        };
+        let err = verify_chain_state_age(&chain_state, &config).unwrap_err();
+        assert_eq!(err.class(), FailureClass::Policy);
+    }
+}
@@ -1,75 +1,278 @@
 //! Transaction formatting utilities for terminal display.
 //!
-//! Provides ASCII art visualization of Bitcoin transactions similar to block explorers.
+//! Provides ASCII art visualization of Bitcoin transactions and of the block containing them,
+//! similar to block explorers.
+//! The box drawing scales to the terminal width, falling back to a single-column layout on
+//! narrow terminals, and colors are only emitted when enabled (see [`Layout::detect`]).
 
 use bitcoin::absolute::LockTime;
 use bitcoin::block::Header as BlockHeader;
-use bitcoin::{Address, Amount, Network, Transaction, TxIn, TxOut};
-use chrono::DateTime;
+use bitcoin::opcodes::all::OP_PUSHNUM_13;
+use bitcoin::script::Instruction;
+use bitcoin::{
+    Address, Amount, BlockHash, Network, OutPoint, Script, Transaction, TxIn, TxOut, Txid, Wtxid,
+};
+use chrono::{DateTime, Utc};
+use raito_spv_core::block_mmr::BlockInclusionPath;
+use raito_spv_verifier::verify::format_duration;
+use serde::{Deserialize, Serialize};
+
+/// Width of the two-column layout, which fits a full TXID and address in each column
+const WIDE_WIDTH: usize = 135;
+/// Narrowest box drawn, narrower terminals wrap the lines
+const MIN_WIDTH: usize = 40;
+/// Width used when it cannot be detected, e.g. when writing to a log file
+const DEFAULT_WIDTH: usize = 80;
+
+/// ANSI color of the section titles
+const TITLE_COLOR: &str = "33";
+/// ANSI color of the output scripts
+const SCRIPT_COLOR: &str = "90";
+
+/// Format of dates and times, always in UTC
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+/// Maximum number of bytes of an OP_RETURN push displayed
+const MAX_OP_RETURN_DISPLAY: usize = 160;
+
+/// Protocols tagging their OP_RETURN payloads with a well-known prefix
+const OP_RETURN_PROTOCOLS: &[(&[u8], &str)] =
+    &[(b"omni", "Omni Layer"), (b"OA\x01\x00", "Open Assets")];
+
+/// Format of the transaction display
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DisplayFormat {
+    /// Box drawing with colors, scaled to the terminal
+    #[default]
+    Pretty,
+    /// Plain text lines without box drawing nor colors, for logs
+    Plain,
+    /// Decoded transaction structure as JSON
+    Json,
+    /// Self-contained HTML report
+    Html,
+}
+
+/// Denomination of the displayed amounts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Denomination {
+    /// Bitcoins with eight decimals, e.g. `0.00100000 BTC`
+    #[default]
+    Btc,
+    /// Satoshis with thousands separators, e.g. `100,000 sat`
+    Sats,
+}
+
+/// Format an amount in the given denomination
+pub fn format_amount(amount: Amount, denomination: Denomination) -> String {
+    match denomination {
+        Denomination::Btc => format!("{:.8} BTC", amount.to_btc()),
+        Denomination::Sats => {
+            let digits = amount.to_sat().to_string();
+            let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+            for (i, digit) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                    grouped.push(',');
+                }
+                grouped.push(digit);
+            }
+            format!("{} sat", grouped)
+        }
+    }
+}
+
+/// Terminal layout of the transaction card
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    /// Whether to emit ANSI colors
+    pub color: bool,
+    /// Total width of the box, in columns
+    pub width: usize,
+    /// Denomination of the amounts
+    pub denomination: Denomination,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            color: true,
+            width: WIDE_WIDTH,
+            denomination: Denomination::default(),
+        }
+    }
+}
+
+impl Layout {
+    /// Detect the layout of stderr, where the transaction card is printed.
+    ///
+    /// Colors are disabled by `NO_COLOR`, `--no-color` or when stderr is not a terminal.
+    /// The width is the terminal width, or the `COLUMNS` variable when stderr is redirected.
+    pub fn detect() -> Self {
+        let width = console::Term::stderr()
+            .size_checked()
+            .map(|(_, columns)| columns as usize)
+            .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        Self {
+            color: console::colors_enabled_stderr(),
+            width: width.clamp(MIN_WIDTH, WIDE_WIDTH),
+            denomination: Denomination::default(),
+        }
+    }
+
+    /// Layout without colors for a box of the given width, e.g. inside another terminal UI
+    pub fn plain(width: usize, denomination: Denomination) -> Self {
+        Self {
+            color: false,
+            width: width.clamp(MIN_WIDTH, WIDE_WIDTH),
+            denomination,
+        }
+    }
+
+    /// Whether inputs and outputs fit side by side
+    fn is_wide(&self) -> bool {
+        self.width >= WIDE_WIDTH
+    }
+
+    /// Width of the content of a single-column row
+    fn content_width(&self) -> usize {
+        self.width - 4
+    }
+
+    /// Width of the content of the inputs and outputs columns
+    fn column_width(&self) -> usize {
+        if self.is_wide() {
+            (self.width - 7) / 2
+        } else {
+            self.content_width()
+        }
+    }
+
+    /// Wrap `text` in the given ANSI color if colors are enabled
+    fn paint(&self, text: &str, color: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", color, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Horizontal rule of the box between the given corners, with an optional title
+    fn rule(&self, left: &str, right: &str, title: &str) -> String {
+        let title = if title.is_empty() {
+            String::new()
+        } else {
+            format!("─ {} ", title)
+        };
+        let fill = self
+            .width
+            .saturating_sub(2 + console::measure_text_width(&title));
+        format!("{}{}{}{}\n", left, title, "─".repeat(fill), right)
+    }
+
+    /// Single-column row of the box
+    fn row(&self, content: &str) -> String {
+        format!(
+            "│ {} │\n",
+            format_column_content(content, self.content_width())
+        )
+    }
+}
 
 /// Format a Bitcoin transaction for terminal display
+///
+/// When the outputs spent by the inputs (`prevouts`) are known, inputs are shown with their
+/// address and value, and the fee is shown in the details.
 pub fn format_transaction(
     tx: &Transaction,
+    prevouts: Option<&[TxOut]>,
     network: Network,
-    block_header: &BlockHeader,
     block_height: u32,
     chain_height: u32,
+    layout: &Layout,
 ) -> String {
     let mut output = String::new();
 
     output.push_str("\n");
 
-    // Header - make even wider to accommodate full TXID and longer addresses
-    output.push_str("┌─ Bitcoin Transaction ───────────────────────────────────────────────────────────────────────────────────────────────────────────────┐\n");
-    output.push_str(&format!(
-        "│ \x1b[33mTXID:\x1b[0m {:<125} │\n",
+    output.push_str(&layout.rule("┌", "┐", "Bitcoin Transaction"));
+    output.push_str(&layout.row(&format!(
+        "{} {}",
+        layout.paint("TXID:", TITLE_COLOR),
         tx.compute_txid()
-    ));
-    output.push_str("├─────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────┤\n");
+    )));
+    output.push_str(&layout.rule("├", "┤", ""));
 
-    // Two-column layout: inputs on left, outputs on right
-    let inputs_section = format_inputs(&tx.input);
-    let outputs_section = format_outputs(&tx.output, network);
+    let inputs_section = format_inputs(&tx.input, prevouts, network, layout);
+    let outputs_section = format_outputs(&tx.output, network, layout);
 
-    // Split sections into lines for side-by-side display
-    let input_lines: Vec<&str> = inputs_section.lines().collect();
-    let output_lines: Vec<&str> = outputs_section.lines().collect();
-    let max_lines = input_lines.len().max(output_lines.len());
+    if layout.is_wide() {
+        // Two-column layout: inputs on left, outputs on right
+        let input_lines: Vec<&str> = inputs_section.lines().collect();
+        let output_lines: Vec<&str> = outputs_section.lines().collect();
+        let max_lines = input_lines.len().max(output_lines.len());
+        let column_width = layout.column_width();
 
-    for i in 0..max_lines {
-        let left = input_lines.get(i).unwrap_or(&"");
-        let right = output_lines.get(i).unwrap_or(&"");
+        for i in 0..max_lines {
+            let left = input_lines.get(i).unwrap_or(&"");
+            let right = output_lines.get(i).unwrap_or(&"");
 
-        // Handle line formatting with proper truncation and padding - make left column wider for full TXID
-        let left_formatted = format_column_content(left, 64);
-        let right_formatted = format_column_content(right, 64);
+            let left_formatted = format_column_content(left, column_width);
+            let right_formatted = format_column_content(right, column_width);
 
-        output.push_str(&format!("│ {} │ {} │\n", left_formatted, right_formatted));
+            output.push_str(&format!("│ {} │ {} │\n", left_formatted, right_formatted));
+        }
+    } else {
+        // Narrow layout: outputs below inputs
+        for line in inputs_section.lines() {
+            output.push_str(&layout.row(line));
+        }
+        output.push_str(&layout.rule("├", "┤", ""));
+        for line in outputs_section.lines() {
+            output.push_str(&layout.row(line));
+        }
     }
 
-    output.push_str("├─────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────┤\n");
+    output.push_str(&layout.rule("├", "┤", ""));
 
     // Details section - one column
-    let details = format_transaction_details(tx, block_header, block_height, chain_height);
+    let details = format_transaction_details(tx, prevouts, block_height, chain_height, layout);
 
     for line in details.lines() {
-        let line_formatted = format_column_content(line, 131);
-        output.push_str(&format!("│ {} │\n", line_formatted));
+        output.push_str(&layout.row(line));
     }
 
-    output.push_str("└─────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────┘\n");
+    output.push_str(&layout.rule("└", "┘", ""));
 
     output
 }
 
-/// Format transaction inputs
-fn format_inputs(inputs: &[TxIn]) -> String {
+/// Format transaction inputs, with the address and value of the spent outputs if known
+fn format_inputs(
+    inputs: &[TxIn],
+    prevouts: Option<&[TxOut]>,
+    network: Network,
+    layout: &Layout,
+) -> String {
     let mut output = String::new();
-    output.push_str("\x1b[33mINPUTS:\x1b[0m\n");
+    output.push_str(&format!("{}\n", layout.paint("INPUTS:", TITLE_COLOR)));
 
-    for input in inputs.iter() {
-        let address = format_input_address(input);
-        output.push_str(&format!("{}\n\n", address));
+    for (i, input) in inputs.iter().enumerate() {
+        match prevouts.and_then(|prevouts| prevouts.get(i)) {
+            Some(prevout) if !input.previous_output.is_null() => {
+                let address = format_output_address(prevout, network);
+                output.push_str(&format_amount_line(&address, prevout, layout));
+                for line in format_input_address(input).lines() {
+                    output.push_str(&format!("{}\n", layout.paint(line, SCRIPT_COLOR)));
+                }
+                output.push('\n');
+            }
+            _ => {
+                let address = format_input_address(input);
+                output.push_str(&format!("{}\n\n", address));
+            }
+        }
     }
 
     if inputs.is_empty() {
@@ -80,22 +283,29 @@ fn format_inputs(inputs: &[TxIn]) -> String {
 }
 
 /// Format transaction outputs
-fn format_outputs(outputs: &[TxOut], network: Network) -> String {
+fn format_outputs(outputs: &[TxOut], network: Network, layout: &Layout) -> String {
     let mut output = String::new();
-    output.push_str("\x1b[33mOUTPUTS:\x1b[0m\n");
+    output.push_str(&format!("{}\n", layout.paint("OUTPUTS:", TITLE_COLOR)));
 
     for txout in outputs.iter() {
         let address = format_output_address(txout, network);
-        let amount_btc = Amount::from_sat(txout.value.to_sat()).to_btc();
+        output.push_str(&format_amount_line(&address, txout, layout));
 
-        output.push_str(&format!("{}        {:.8} BTC\n", address, amount_btc));
+        if txout.script_pubkey.is_op_return() {
+            output.push_str(&format_op_return(&txout.script_pubkey, layout));
+            output.push('\n');
+            continue;
+        }
 
         // Add script with each opcode on separate line
         let script_asm = txout.script_pubkey.to_asm_string();
         if !script_asm.is_empty() {
             let opcodes: Vec<&str> = script_asm.split_whitespace().collect();
             for opcode in opcodes {
-                output.push_str(&format!("\x1b[90m  {}\x1b[0m\n", opcode));
+                output.push_str(&format!(
+                    "{}\n",
+                    layout.paint(&format!("  {}", opcode), SCRIPT_COLOR)
+                ));
             }
             // Add padding between outputs
             output.push_str("\n");
@@ -109,27 +319,140 @@ fn format_outputs(outputs: &[TxOut], network: Network) -> String {
     output
 }
 
+/// Format the data of an OP_RETURN output: the protocol if recognized, then each push as hex
+/// and, when it is printable UTF-8, as text
+fn format_op_return(script: &Script, layout: &Layout) -> String {
+    let mut output = String::new();
+    let pushes = op_return_pushes(script);
+
+    if let Some(protocol) = op_return_protocol(script, &pushes) {
+        output.push_str(&format!("  Protocol: {}\n", protocol));
+    }
+
+    // Indent of two columns, hex lines of a whole number of bytes
+    let hex_width = (layout.column_width() - 2) / 2 * 2;
+    for push in pushes.iter().filter(|push| !push.is_empty()) {
+        let shown = &push[..push.len().min(MAX_OP_RETURN_DISPLAY)];
+        let hex = hex::encode(shown);
+        for line in hex.as_bytes().chunks(hex_width) {
+            let line = std::str::from_utf8(line).expect("hex is ASCII");
+            output.push_str(&format!(
+                "{}\n",
+                layout.paint(&format!("  {}", line), SCRIPT_COLOR)
+            ));
+        }
+        if shown.len() < push.len() {
+            output.push_str(&format!("  … ({} bytes)\n", push.len()));
+        }
+
+        if let Some(text) = printable_text(shown) {
+            for line in wrap_to_width(&format!("\"{}\"", text), layout.column_width() - 2) {
+                output.push_str(&format!("  {}\n", line));
+            }
+        }
+    }
+    output
+}
+
+/// Data pushed by an OP_RETURN script
+fn op_return_pushes(script: &Script) -> Vec<&[u8]> {
+    script
+        .instructions()
+        .skip(1)
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Protocol of an OP_RETURN payload, recognized by its marker or prefix
+fn op_return_protocol(script: &Script, pushes: &[&[u8]]) -> Option<&'static str> {
+    // Runestones are marked by OP_13 right after OP_RETURN
+    if script.as_bytes().get(1) == Some(&OP_PUSHNUM_13.to_u8()) {
+        return Some("Runestone");
+    }
+    let payload = pushes.first()?;
+    OP_RETURN_PROTOCOLS
+        .iter()
+        .find(|(prefix, _)| payload.starts_with(prefix))
+        .map(|(_, protocol)| *protocol)
+}
+
+/// Data as text if it is valid UTF-8 without control characters
+fn printable_text(data: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(data).ok()?;
+    if text.chars().any(char::is_control) {
+        return None;
+    }
+    Some(text)
+}
+
+/// Split text into lines of at most `width` display columns
+fn wrap_to_width(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    let mut line_width = 0;
+    for c in text.chars() {
+        let char_width = console::measure_text_width(c.encode_utf8(&mut [0; 4]));
+        if line_width + char_width > width && line_width > 0 {
+            lines.push(String::new());
+            line_width = 0;
+        }
+        lines.last_mut().expect("lines are not empty").push(c);
+        line_width += char_width;
+    }
+    lines
+}
+
+/// Format the address and value of an output on one line, or on two lines if the column is
+/// too narrow, to keep the amount visible rather than truncating it with the address
+fn format_amount_line(address: &str, txout: &TxOut, layout: &Layout) -> String {
+    let amount = format_amount(txout.value, layout.denomination);
+    let line = format!("{}        {}", address, amount);
+    if console::measure_text_width(&line) <= layout.column_width() {
+        format!("{}\n", line)
+    } else {
+        format!("{}\n  {}\n", address, amount)
+    }
+}
+
 /// Format transaction details card
 fn format_transaction_details(
     tx: &Transaction,
-    block_header: &BlockHeader,
+    prevouts: Option<&[TxOut]>,
     block_height: u32,
     chain_height: u32,
+    layout: &Layout,
 ) -> String {
     let mut output = String::new();
-    output.push_str("\x1b[33mDETAILS:\x1b[0m\n");
+    output.push_str(&format!("{}\n", layout.paint("DETAILS:", TITLE_COLOR)));
 
-    output.push_str(&format!("Transaction size: {} bytes\n", tx.total_size()));
+    output.push_str(&format!(
+        "Transaction size: {} bytes, {} vB\n",
+        tx.total_size(),
+        tx.vsize()
+    ));
 
-    output.push_str(&format!("Block hash: {}\n", block_header.block_hash()));
-    output.push_str(&format!("Block height: {}\n", block_height));
+    let total_output = tx.output.iter().map(|output| output.value).sum::<Amount>();
+    output.push_str(&format!(
+        "Total output: {}\n",
+        format_amount(total_output, layout.denomination)
+    ));
 
-    let timestamp = format_unix_timestamp(block_header.time);
-    output.push_str(&format!("Block timestamp: {}\n", timestamp));
+    if let Some(fee) = prevouts.and_then(|prevouts| transaction_fee(tx, prevouts)) {
+        output.push_str(&format!(
+            "Fee: {}, {:.2} sat/vB (from unproven previous outputs)\n",
+            format_amount(fee, layout.denomination),
+            fee_rate(tx, fee)
+        ));
+    }
 
-    // Calculate confirmations if both block_height and chain_height are available
-    let confirmations = chain_height.saturating_sub(block_height);
-    output.push_str(&format!("Confirmations: {}\n", confirmations));
+    // The block containing the transaction counts as the first confirmation
+    let confirmations = (chain_height + 1).saturating_sub(block_height);
+    output.push_str(&format!(
+        "Confirmations: {} (proven tip at height {})\n",
+        confirmations, chain_height
+    ));
 
     // Format locktime if set
     if tx.lock_time != LockTime::ZERO {
@@ -139,7 +462,7 @@ fn format_transaction_details(
                 // Convert Unix timestamp to readable format
                 format!(
                     "timestamp {}",
-                    format_unix_timestamp(timestamp.to_consensus_u32())
+                    block_time(timestamp.to_consensus_u32()).format(TIME_FORMAT)
                 )
             }
         };
@@ -149,6 +472,22 @@ fn format_transaction_details(
     output
 }
 
+/// Fee paid by a transaction, i.e. the value of the spent outputs in excess of its outputs,
+/// `None` for a coinbase or if the spent outputs don't cover the outputs
+pub fn transaction_fee(tx: &Transaction, prevouts: &[TxOut]) -> Option<Amount> {
+    if tx.is_coinbase() {
+        return None;
+    }
+    let spent = prevouts.iter().map(|prevout| prevout.value).sum::<Amount>();
+    let created = tx.output.iter().map(|output| output.value).sum::<Amount>();
+    spent.checked_sub(created)
+}
+
+/// Fee rate of a transaction in satoshis per virtual byte
+pub fn fee_rate(tx: &Transaction, fee: Amount) -> f64 {
+    fee.to_sat() as f64 / tx.vsize() as f64
+}
+
 /// Get address string for a transaction input
 fn format_input_address(input: &TxIn) -> String {
     // For inputs, we can try to extract address from script_sig, but it's not always possible
@@ -192,45 +531,799 @@ fn format_output_address(output: &TxOut, network: Network) -> String {
     }
 }
 
-/// Format content for a column with proper padding and truncation
+/// Pad or truncate content to exactly `width` terminal columns.
+///
+/// Widths are measured in display columns rather than bytes, ignoring ANSI color codes and
+/// accounting for wide and zero-width characters, so multi-byte content never splits a
+/// character or misaligns the box. Truncated content ends with an ellipsis.
 fn format_column_content(content: &str, width: usize) -> String {
-    // Remove ANSI color codes for length calculation
-    let visible_content = strip_ansi_codes(content);
-    let visible_len = visible_content.len();
-
-    if visible_len <= width {
-        // Content fits, pad with spaces
-        let padding = width - visible_len;
-        format!("{}{}", content, " ".repeat(padding))
+    let visible_width = console::measure_text_width(content);
+    if visible_width <= width {
+        format!("{}{}", content, " ".repeat(width - visible_width))
     } else {
-        // Return content as-is without truncation
-        content.to_string()
+        let truncated = console::truncate_str(content, width, "…");
+        // Truncating before a wide character can leave a column free
+        let truncated_width = console::measure_text_width(&truncated);
+        format!("{}{}", truncated, " ".repeat(width - truncated_width))
     }
 }
 
-/// Remove ANSI color codes from a string for length calculation
-fn strip_ansi_codes(s: &str) -> String {
-    let mut result = String::new();
-    let mut chars = s.chars();
+/// Format the block containing the transaction for terminal display: its height, hash, time,
+/// difficulty and position relative to the proven chain tip
+pub fn format_block(
+    block_header: &BlockHeader,
+    block_height: u32,
+    chain_height: u32,
+    now: DateTime<Utc>,
+    layout: &Layout,
+) -> String {
+    let mut output = String::new();
+    output.push_str(&layout.rule("┌", "┐", "Proven Block"));
+
+    let field = |name: &str, value: String| {
+        layout.row(&format!(
+            "{} {}",
+            layout.paint(&format!("{}:", name), TITLE_COLOR),
+            value
+        ))
+    };
+    output.push_str(&field("Height", block_height.to_string()));
+    output.push_str(&field("Hash", block_header.block_hash().to_string()));
+    output.push_str(&field("Time", format_block_time(block_header.time, now)));
+    output.push_str(&field(
+        "Difficulty",
+        format!(
+            "{} (bits {:#010x})",
+            format_difficulty(block_header.difficulty_float()),
+            block_header.bits.to_consensus()
+        ),
+    ));
+    let blocks_below = chain_height.saturating_sub(block_height);
+    let position = if blocks_below == 0 {
+        "proven chain tip".to_string()
+    } else {
+        format!(
+            "{} block(s) below the proven chain tip at height {}",
+            blocks_below, chain_height
+        )
+    };
+    output.push_str(&field("Position", position));
 
-    while let Some(c) = chars.next() {
-        if c == '\x1b' {
-            // Skip the ANSI escape sequence
-            while let Some(next_c) = chars.next() {
-                if next_c == 'm' {
-                    break;
+    output.push_str(&layout.rule("└", "┘", ""));
+    output
+}
+
+/// Format the path of a block in the block MMR as a tree, from the peak reached down to the
+/// leaf of the block, with the sibling hashes at each level
+pub fn format_inclusion_path(path: &BlockInclusionPath, layout: &Layout) -> String {
+    let mut output = String::new();
+    output.push_str(&format!(
+        "\n{} leaf {} of {} (block height {})\n",
+        layout.paint("BLOCK MMR PATH:", TITLE_COLOR),
+        path.leaf_index,
+        path.leaf_count,
+        path.leaf_index
+    ));
+
+    let top_hash = path
+        .steps
+        .last()
+        .map_or(&path.leaf_hash, |step| &step.parent_hash);
+    output.push_str(&format!(
+        "peak {} of {}  {}  {}\n",
+        path.peak_index + 1,
+        path.leaf_count.count_ones(),
+        short_hash(top_hash),
+        if path.peak_matches {
+            "matches the proof peak"
+        } else {
+            "DOESN'T MATCH the proof peak"
+        }
+    ));
+
+    // Walk down from the peak: at each level the sibling is drawn first, then the path node
+    // whose children are drawn below it
+    let mut prefix = String::new();
+    for (level, step) in path.steps.iter().enumerate().rev() {
+        let (sibling_side, node_side) = if step.sibling_is_left {
+            ("L", "R")
+        } else {
+            ("R", "L")
+        };
+        output.push_str(&format!(
+            "{}├── {}\n",
+            prefix,
+            layout.paint(
+                &format!(
+                    "{} {}  sibling",
+                    sibling_side,
+                    short_hash(&step.sibling_hash)
+                ),
+                SCRIPT_COLOR
+            )
+        ));
+        let node = if level == 0 {
+            format!(
+                "{} {}  leaf {}",
+                node_side,
+                short_hash(&path.leaf_hash),
+                path.leaf_index
+            )
+        } else {
+            format!(
+                "{} {}",
+                node_side,
+                short_hash(&path.steps[level - 1].parent_hash)
+            )
+        };
+        output.push_str(&format!("{}└── {}\n", prefix, node));
+        prefix.push_str("    ");
+    }
+    if path.steps.is_empty() {
+        output.push_str(&format!("└── leaf {} is the peak\n", path.leaf_index));
+    }
+    output
+}
+
+/// Shorten a hex hash to its first and last digits, e.g. `0x1a2b3c4d…7d8e9f`
+fn short_hash(hash: &str) -> String {
+    let digits = hash.strip_prefix("0x").unwrap_or(hash);
+    match (
+        digits.get(..8),
+        digits.get(digits.len().saturating_sub(6)..),
+    ) {
+        (Some(head), Some(tail)) if digits.len() > 14 => format!("0x{}…{}", head, tail),
+        _ => format!("0x{}", digits),
+    }
+}
+
+/// Date and time of a block timestamp
+pub fn block_time(timestamp: u32) -> DateTime<Utc> {
+    // Any 32-bit timestamp is within the supported range
+    DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default()
+}
+
+/// Format a block timestamp as a UTC date and time with its age relative to `now`,
+/// e.g. `2024-04-20 00:09:27 UTC (3d 4h ago)`
+pub fn format_block_time(timestamp: u32, now: DateTime<Utc>) -> String {
+    let time = block_time(timestamp);
+    let age = now.signed_duration_since(time).num_seconds();
+    if age < 0 {
+        format!(
+            "{} (in {})",
+            time.format(TIME_FORMAT),
+            format_duration(-age)
+        )
+    } else {
+        format!(
+            "{} ({} ago)",
+            time.format(TIME_FORMAT),
+            format_duration(age)
+        )
+    }
+}
+
+/// Format a mining difficulty with an SI suffix, e.g. `126.98 T`
+fn format_difficulty(difficulty: f64) -> String {
+    const SUFFIXES: [&str; 6] = ["", " K", " M", " G", " T", " P"];
+    let mut value = difficulty;
+    let mut suffix = 0;
+    while value >= 1000.0 && suffix + 1 < SUFFIXES.len() {
+        value /= 1000.0;
+        suffix += 1;
+    }
+    format!("{:.2}{}", value, SUFFIXES[suffix])
+}
+
+/// Decoded transaction and the block containing it, rendered by the plain, JSON and HTML
+/// display formats
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionView {
+    /// Transaction ID
+    pub txid: Txid,
+    /// Witness transaction ID
+    pub wtxid: Wtxid,
+    /// Transaction version
+    pub version: i32,
+    /// Raw lock time
+    pub lock_time: u32,
+    /// Serialized size in bytes
+    pub size: usize,
+    /// Virtual size in vbytes
+    pub vsize: usize,
+    /// Transaction inputs
+    pub inputs: Vec<InputView>,
+    /// Transaction outputs
+    pub outputs: Vec<OutputView>,
+    /// Total value of the outputs in satoshis
+    pub total_output: Amount,
+    /// Fee in satoshis, if the spent outputs are known (not proven)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Amount>,
+    /// Fee rate in sat/vB, if the spent outputs are known (not proven)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<f64>,
+    /// Block containing the transaction
+    pub block: BlockView,
+}
+
+/// Decoded transaction input
+#[derive(Debug, Clone, Serialize)]
+pub struct InputView {
+    /// Output spent by the input, null for a coinbase
+    pub previous_output: OutPoint,
+    /// Whether the input is the coinbase input
+    pub coinbase: bool,
+    /// Address of the spent output, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Value of the spent output in satoshis, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Amount>,
+}
+
+/// Decoded transaction output
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputView {
+    /// Address, or script type if it has no address
+    pub address: String,
+    /// Value in satoshis
+    pub value: Amount,
+    /// Script in assembly notation
+    pub script: String,
+    /// Recognized protocol of an OP_RETURN payload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<&'static str>,
+    /// Data pushed by an OP_RETURN script, as hex strings
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<String>,
+}
+
+/// Block containing the transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockView {
+    /// Block height
+    pub height: u32,
+    /// Block hash
+    pub hash: BlockHash,
+    /// Block timestamp in UTC
+    pub time: String,
+    /// Mining difficulty
+    pub difficulty: f64,
+    /// Number of proven blocks on top of the block, including itself
+    pub confirmations: u32,
+    /// Height of the proven chain tip
+    pub chain_height: u32,
+}
+
+impl TransactionView {
+    /// Decode a transaction, with the outputs spent by its inputs if known
+    pub fn new(
+        tx: &Transaction,
+        prevouts: Option<&[TxOut]>,
+        network: Network,
+        block_header: &BlockHeader,
+        block_height: u32,
+        chain_height: u32,
+    ) -> Self {
+        let inputs = tx
+            .input
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let coinbase = input.previous_output.is_null();
+                let prevout = prevouts
+                    .and_then(|prevouts| prevouts.get(i))
+                    .filter(|_| !coinbase);
+                InputView {
+                    previous_output: input.previous_output,
+                    coinbase,
+                    address: prevout.map(|prevout| format_output_address(prevout, network)),
+                    value: prevout.map(|prevout| prevout.value),
                 }
-            }
+            })
+            .collect();
+        let outputs = tx
+            .output
+            .iter()
+            .map(|output| {
+                let script = &output.script_pubkey;
+                let pushes = if script.is_op_return() {
+                    op_return_pushes(script)
+                } else {
+                    Vec::new()
+                };
+                OutputView {
+                    address: format_output_address(output, network),
+                    value: output.value,
+                    script: script.to_asm_string(),
+                    protocol: op_return_protocol(script, &pushes).filter(|_| script.is_op_return()),
+                    data: pushes.into_iter().map(hex::encode).collect(),
+                }
+            })
+            .collect();
+        let fee = prevouts.and_then(|prevouts| transaction_fee(tx, prevouts));
+
+        Self {
+            txid: tx.compute_txid(),
+            wtxid: tx.compute_wtxid(),
+            version: tx.version.0,
+            lock_time: tx.lock_time.to_consensus_u32(),
+            size: tx.total_size(),
+            vsize: tx.vsize(),
+            inputs,
+            outputs,
+            total_output: tx.output.iter().map(|output| output.value).sum(),
+            fee,
+            fee_rate: fee.map(|fee| fee_rate(tx, fee)),
+            block: BlockView {
+                height: block_height,
+                hash: block_header.block_hash(),
+                time: block_time(block_header.time)
+                    .format(TIME_FORMAT)
+                    .to_string(),
+                difficulty: block_header.difficulty_float(),
+                confirmations: (chain_height + 1).saturating_sub(block_height),
+                chain_height,
+            },
+        }
+    }
+}
+
+/// Format a decoded transaction as plain text lines, followed by `facts` about its
+/// verification as `name: value` lines
+pub fn format_plain(
+    view: &TransactionView,
+    facts: &[(&str, String)],
+    denomination: Denomination,
+) -> String {
+    let mut output = String::new();
+    output.push_str(&format!("Transaction: {}\n", view.txid));
+    output.push_str(&format!("Size: {} bytes, {} vB\n", view.size, view.vsize));
+    for (i, input) in view.inputs.iter().enumerate() {
+        if input.coinbase {
+            output.push_str(&format!("Input {}: coinbase\n", i));
+            continue;
+        }
+        output.push_str(&format!("Input {}: {}", i, input.previous_output));
+        if let (Some(address), Some(value)) = (&input.address, input.value) {
+            output.push_str(&format!(
+                " {} {}",
+                address,
+                format_amount(value, denomination)
+            ));
+        }
+        output.push('\n');
+    }
+    for (i, txout) in view.outputs.iter().enumerate() {
+        output.push_str(&format!(
+            "Output {}: {} {}\n",
+            i,
+            txout.address,
+            format_amount(txout.value, denomination)
+        ));
+        if let Some(protocol) = txout.protocol {
+            output.push_str(&format!("  protocol: {}\n", protocol));
+        }
+        for data in &txout.data {
+            output.push_str(&format!("  data: {}\n", data));
+        }
+    }
+    output.push_str(&format!(
+        "Total output: {}\n",
+        format_amount(view.total_output, denomination)
+    ));
+    if let (Some(fee), Some(fee_rate)) = (view.fee, view.fee_rate) {
+        output.push_str(&format!(
+            "Fee: {}, {:.2} sat/vB (from unproven previous outputs)\n",
+            format_amount(fee, denomination),
+            fee_rate
+        ));
+    }
+    output.push_str(&format!(
+        "Block: {} at height {}, {}, difficulty {}\n",
+        view.block.hash,
+        view.block.height,
+        view.block.time,
+        format_difficulty(view.block.difficulty)
+    ));
+    output.push_str(&format!(
+        "Confirmations: {} (proven tip at height {})\n",
+        view.block.confirmations, view.block.chain_height
+    ));
+    for (name, value) in facts {
+        output.push_str(&format!("{}: {}\n", name, value));
+    }
+    output
+}
+
+/// Format a decoded transaction as a self-contained HTML report, followed by a table of
+/// `facts` about its verification
+pub fn format_html(
+    view: &TransactionView,
+    facts: &[(&str, String)],
+    denomination: Denomination,
+) -> String {
+    let mut rows = String::new();
+    let mut push_row = |name: &str, value: &str| {
+        rows.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape_html(name),
+            escape_html(value)
+        ));
+    };
+    push_row("Transaction", &view.txid.to_string());
+    push_row("Size", &format!("{} bytes, {} vB", view.size, view.vsize));
+    push_row(
+        "Total output",
+        &format_amount(view.total_output, denomination),
+    );
+    if let (Some(fee), Some(fee_rate)) = (view.fee, view.fee_rate) {
+        push_row(
+            "Fee",
+            &format!(
+                "{}, {:.2} sat/vB (from unproven previous outputs)",
+                format_amount(fee, denomination),
+                fee_rate
+            ),
+        );
+    }
+    push_row("Block height", &view.block.height.to_string());
+    push_row("Block hash", &view.block.hash.to_string());
+    push_row("Block time", &view.block.time);
+    push_row("Difficulty", &format_difficulty(view.block.difficulty));
+    push_row(
+        "Confirmations",
+        &format!(
+            "{} (proven tip at height {})",
+            view.block.confirmations, view.block.chain_height
+        ),
+    );
+    for (name, value) in facts {
+        push_row(name, value);
+    }
+
+    let mut inputs = String::new();
+    for input in &view.inputs {
+        let (outpoint, address, value) = if input.coinbase {
+            ("coinbase".to_string(), String::new(), String::new())
         } else {
-            result.push(c);
+            (
+                input.previous_output.to_string(),
+                input.address.clone().unwrap_or_default(),
+                input
+                    .value
+                    .map(|value| format_amount(value, denomination))
+                    .unwrap_or_default(),
+            )
+        };
+        inputs.push_str(&format!(
+            "<tr><td class=\"mono\">{}</td><td class=\"mono\">{}</td><td>{}</td></tr>\n",
+            escape_html(&outpoint),
+            escape_html(&address),
+            value
+        ));
+    }
+    let mut outputs = String::new();
+    for txout in &view.outputs {
+        let mut script = escape_html(&txout.script);
+        if let Some(protocol) = txout.protocol {
+            script.push_str(&format!("<br>protocol: {}", escape_html(protocol)));
         }
+        outputs.push_str(&format!(
+            "<tr><td class=\"mono\">{}</td><td>{}</td><td class=\"mono\">{}</td></tr>\n",
+            escape_html(&txout.address),
+            format_amount(txout.value, denomination),
+            script
+        ));
     }
 
-    result
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Raito SPV proof of transaction {txid}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; color: #222; }}
+table {{ border-collapse: collapse; margin-bottom: 2em; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }}
+th {{ background: #f4f4f4; }}
+.mono {{ font-family: monospace; word-break: break-all; }}
+</style>
+</head>
+<body>
+<h1>Raito SPV proof of transaction</h1>
+<table>
+{rows}</table>
+<h2>Inputs</h2>
+<table>
+<tr><th>Spent output</th><th>Address</th><th>Value</th></tr>
+{inputs}</table>
+<h2>Outputs</h2>
+<table>
+<tr><th>Address</th><th>Value</th><th>Script</th></tr>
+{outputs}</table>
+</body>
+</html>
+"#,
+        txid = view.txid,
+    )
+}
+
+/// Escape text for inclusion in HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
-/// Format Unix timestamp to human-readable string
-fn format_unix_timestamp(timestamp: u32) -> String {
-    let dt = DateTime::from_timestamp(timestamp as i64, 0).expect("Invalid timestamp");
-    dt.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::hashes::Hash;
+    use bitcoin::opcodes::all::OP_RETURN;
+    use bitcoin::script::Builder;
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, ScriptBuf, Txid, WPubkeyHash};
+    use raito_spv_core::block_mmr::PathStep;
+
+    #[test]
+    fn test_format_transaction_layout() {
+        let block = genesis_block(Network::Bitcoin);
+        let tx = &block.txdata[0];
+
+        for width in [MIN_WIDTH, DEFAULT_WIDTH, WIDE_WIDTH] {
+            let layout = Layout {
+                color: false,
+                width,
+                ..Default::default()
+            };
+            let output = format_transaction(tx, None, Network::Bitcoin, 0, 0, &layout);
+            assert!(!output.contains('\x1b'));
+            for line in output
+                .lines()
+                .filter(|line| line.starts_with(['┌', '├', '└']))
+            {
+                assert_eq!(line.chars().count(), width);
+            }
+            // Outputs get their own rows below the inputs on narrow terminals
+            assert_eq!(
+                output.lines().any(|line| line.starts_with("│ OUTPUTS:")),
+                width < WIDE_WIDTH
+            );
+        }
+
+        let output = format_transaction(tx, None, Network::Bitcoin, 0, 0, &Layout::default());
+        assert!(output.contains("\x1b[33mTXID:\x1b[0m"));
+    }
+
+    #[test]
+    fn test_format_transaction_prevouts() {
+        let script_pubkey = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 1),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: script_pubkey.clone(),
+            }],
+        };
+        let prevouts = [TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey,
+        }];
+        assert_eq!(
+            transaction_fee(&tx, &prevouts),
+            Some(Amount::from_sat(10_000))
+        );
+
+        let layout = Layout {
+            color: false,
+            width: WIDE_WIDTH,
+            ..Default::default()
+        };
+        let output = format_transaction(&tx, Some(&prevouts), Network::Bitcoin, 0, 0, &layout);
+        let address = format_output_address(&prevouts[0], Network::Bitcoin);
+        assert!(output.contains(&format!("│ {}        0.00100000 BTC", address)));
+        assert!(output.contains(&format!(
+            "Fee: 0.00010000 BTC, {:.2} sat/vB",
+            10_000.0 / tx.vsize() as f64
+        )));
+        assert!(output.contains("Confirmations: 1 (proven tip at height 0)"));
+
+        let output = format_transaction(&tx, None, Network::Bitcoin, 0, 0, &layout);
+        assert!(output.contains(&format!("│ {}", Txid::all_zeros())));
+        assert!(!output.contains("Fee:"));
+    }
+
+    #[test]
+    fn test_format_block() {
+        let block = genesis_block(Network::Bitcoin);
+        let layout = Layout {
+            color: false,
+            width: DEFAULT_WIDTH,
+            ..Default::default()
+        };
+        let now = block_time(block.header.time) + chrono::Duration::days(2);
+        let output = format_block(&block.header, 0, 10, now, &layout);
+        for line in output.lines() {
+            assert_eq!(console::measure_text_width(line), DEFAULT_WIDTH);
+        }
+        assert!(output.contains(&format!("│ Hash: {} ", block.header.block_hash())));
+        assert!(output.contains("│ Time: 2009-01-03 18:15:05 UTC (2d 0h ago) "));
+        assert!(output.contains("│ Difficulty: 1.00 (bits 0x1d00ffff) "));
+        assert!(output.contains("│ Position: 10 block(s) below the proven chain tip at height 10 "));
+
+        assert_eq!(format_difficulty(126_982_285_146_989.0), "126.98 T");
+        assert_eq!(format_difficulty(4.66e-10), "0.00");
+    }
+
+    #[test]
+    fn test_format_amount() {
+        let amount = |sat| format_amount(Amount::from_sat(sat), Denomination::Sats);
+        assert_eq!(amount(0), "0 sat");
+        assert_eq!(amount(999), "999 sat");
+        assert_eq!(amount(100_000), "100,000 sat");
+        assert_eq!(amount(1_234_567), "1,234,567 sat");
+        assert_eq!(
+            format_amount(Amount::from_sat(100_000), Denomination::Btc),
+            "0.00100000 BTC"
+        );
+    }
+
+    #[test]
+    fn test_transaction_view_formats() {
+        let block = genesis_block(Network::Bitcoin);
+        let tx = &block.txdata[0];
+        let view = TransactionView::new(tx, None, Network::Bitcoin, &block.header, 0, 10);
+        assert!(view.inputs[0].coinbase);
+        assert_eq!(view.block.confirmations, 11);
+        let facts = [("Checks", "<transaction> & block".to_string())];
+
+        let plain = format_plain(&view, &facts, Denomination::Btc);
+        assert!(!plain.contains(['\x1b', '│', '─']));
+        assert!(plain.contains(&format!("Transaction: {}\n", view.txid)));
+        assert!(plain.contains("Input 0: coinbase\n"));
+        assert!(plain.contains("Total output: 50.00000000 BTC\n"));
+        assert!(plain.contains("Confirmations: 11 (proven tip at height 10)\n"));
+        assert!(plain.ends_with("Checks: <transaction> & block\n"));
+
+        let json = serde_json::to_value(&view).unwrap();
+        assert_eq!(json["txid"], view.txid.to_string());
+        assert_eq!(json["outputs"][0]["value"], 5_000_000_000u64);
+        assert_eq!(json["block"]["time"], "2009-01-03 18:15:05 UTC");
+        assert!(json.get("fee").is_none());
+
+        let html = format_html(&view, &facts, Denomination::Sats);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td>&lt;transaction&gt; &amp; block</td>"));
+        assert!(html.contains("<td>5,000,000,000 sat</td>"));
+        assert!(!html.contains("<transaction>"));
+    }
+
+    #[test]
+    fn test_format_inclusion_path() {
+        let hash = |byte: u8| format!("0x{}", hex::encode([byte; 32]));
+        let path = BlockInclusionPath {
+            leaf_index: 5,
+            leaf_count: 10,
+            leaf_hash: hash(0x11),
+            peak_index: 0,
+            steps: vec![
+                PathStep {
+                    sibling_hash: hash(0xaa),
+                    sibling_is_left: true,
+                    parent_hash: hash(0x22),
+                },
+                PathStep {
+                    sibling_hash: hash(0xbb),
+                    sibling_is_left: false,
+                    parent_hash: hash(0x33),
+                },
+            ],
+            peak_matches: true,
+        };
+        let layout = Layout {
+            color: false,
+            width: DEFAULT_WIDTH,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_inclusion_path(&path, &layout),
+            "\nBLOCK MMR PATH: leaf 5 of 10 (block height 5)\n\
+             peak 1 of 2  0x33333333…333333  matches the proof peak\n\
+             ├── R 0xbbbbbbbb…bbbbbb  sibling\n\
+             └── L 0x22222222…222222\n    \
+             ├── L 0xaaaaaaaa…aaaaaa  sibling\n    \
+             └── R 0x11111111…111111  leaf 5\n"
+        );
+    }
+
+    #[test]
+    fn test_format_op_return() {
+        let layout = Layout {
+            color: false,
+            width: MIN_WIDTH,
+            ..Default::default()
+        };
+
+        let script = ScriptBuf::new_op_return(b"hello raito");
+        let output = format_op_return(&script, &layout);
+        assert_eq!(
+            output,
+            format!("  {}\n  \"hello raito\"\n", hex::encode("hello raito"))
+        );
+
+        // Binary data is shown as hex only, wrapped to the column width
+        let script = ScriptBuf::new_op_return([0xffu8; 20]);
+        let output = format_op_return(&script, &layout);
+        assert_eq!(
+            output,
+            format!("  {}\n  {}\n", "ff".repeat(17), "ff".repeat(3))
+        );
+
+        let script = ScriptBuf::new_op_return(b"omni\x00\x00\x00\x00");
+        assert!(format_op_return(&script, &layout).starts_with("  Protocol: Omni Layer\n"));
+
+        let script = Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_opcode(OP_PUSHNUM_13)
+            .push_slice([0x14, 0x01])
+            .into_script();
+        assert!(format_op_return(&script, &layout).starts_with("  Protocol: Runestone\n"));
+    }
+
+    #[test]
+    fn test_format_column_content() {
+        assert_eq!(format_column_content("abc", 5), "abc  ");
+        assert_eq!(format_column_content("abcde", 5), "abcde");
+        assert_eq!(format_column_content("abcdef", 5), "abcd…");
+        // Multi-byte and wide characters are measured in display columns
+        assert_eq!(format_column_content("héllo", 6), "héllo ");
+        assert_eq!(format_column_content("日本語", 7), "日本語 ");
+        assert_eq!(format_column_content("日本語", 5), "日本…");
+        // A wide character that doesn't fit before the ellipsis leaves a padded column
+        assert_eq!(format_column_content("日本語", 4), "日… ");
+        // Color codes don't count towards the width and are kept when truncating
+        assert_eq!(
+            format_column_content("\x1b[33mabcdef\x1b[0m", 4),
+            "\x1b[33mabc…\x1b[0m"
+        );
+        assert_eq!(
+            format_column_content("\x1b[33mab\x1b[0m", 4),
+            "\x1b[33mab\x1b[0m  "
+        );
+    }
+
+    #[test]
+    fn test_layout_wide_characters() {
+        let layout = Layout {
+            color: false,
+            width: MIN_WIDTH,
+            ..Default::default()
+        };
+        let lines = [
+            layout.rule("┌", "┐", "交易"),
+            layout.row("備考：比特幣交易の証明"),
+            layout.row(&"比特幣".repeat(MIN_WIDTH)),
+        ];
+        for line in lines {
+            assert_eq!(console::measure_text_width(line.trim_end()), MIN_WIDTH);
+        }
+    }
 }
@@ -0,0 +1,179 @@
+//! Inspection of compressed SPV proof files without running the (expensive) verification.
+
+use std::path::PathBuf;
+
+use raito_spv_verifier::proof::{BootloaderOutput, CompressedSpvProof, TaskResult};
+use serde::Serialize;
+
+use crate::verify::load_compressed_proof_from_bzip2;
+
+/// CLI arguments for the `inspect` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct InspectArgs {
+    /// Path to read the proof from
+    #[arg(long)]
+    proof_path: PathBuf,
+}
+
+/// Run the `inspect` subcommand: read a proof from disk and print its contents
+///
+/// No verification is performed, the output only reflects what the proof claims.
+pub async fn run(args: InspectArgs) -> Result<(), anyhow::Error> {
+    let file_size = std::fs::metadata(&args.proof_path)?.len();
+    let proof = load_compressed_proof_from_bzip2(&args.proof_path)?;
+
+    println!("{}", format_proof_summary(&proof, file_size)?);
+
+    Ok(())
+}
+
+/// Format a human-readable summary of the proof contents
+///
+/// - `proof`: The compressed SPV proof to summarize
+/// - `file_size`: Size of the (bzip2 compressed) proof file in bytes
+pub fn format_proof_summary(
+    proof: &CompressedSpvProof,
+    file_size: u64,
+) -> Result<String, anyhow::Error> {
+    let mut output = String::new();
+
+    output.push_str("PROOF FILE:\n");
+    output.push_str(&format!("  Compressed size: {} bytes\n", file_size));
+    output.push_str(&format!(
+        "  Serialized size: {} bytes\n",
+        bincode::serialized_size(proof)?
+    ));
+
+    let chain_state = &proof.chain_state;
+    output.push_str("\nCHAIN STATE:\n");
+    output.push_str(&format!("  Block height: {}\n", chain_state.block_height));
+    output.push_str(&format!(
+        "  Best block hash: {}\n",
+        chain_state.best_block_hash
+    ));
+    output.push_str(&format!("  Total work: {}\n", chain_state.total_work));
+    output.push_str(&format!(
+        "  Current target: {}\n",
+        chain_state.current_target
+    ));
+    output.push_str(&format!(
+        "  Epoch start time: {}\n",
+        chain_state.epoch_start_time
+    ));
+    output.push_str(&format!(
+        "  Previous timestamps: {:?}\n",
+        chain_state.prev_timestamps
+    ));
+    output.push_str(&format!("  Digest: {}\n", chain_state.blake2s_digest()?));
+
+    output.push_str("\nBLOCK:\n");
+    output.push_str(&format!("  Block height: {}\n", proof.block_height));
+    output.push_str(&format!(
+        "  Block hash: {}\n",
+        proof.block_header.block_hash()
+    ));
+    output.push_str(&format!(
+        "  MMR leaf count: {}\n",
+        proof.block_header_proof.leaf_count
+    ));
+    if let Some(headers) = &proof.subchain_headers {
+        output.push_str(&format!("  Subchain headers: {}\n", headers.len()));
+    }
+
+    output.push_str("\nTRANSACTION:\n");
+    output.push_str(&format!("  Txid: {}\n", proof.transaction.compute_txid()));
+    output.push_str(&format!(
+        "  Inputs: {}, outputs: {}\n",
+        proof.transaction.input.len(),
+        proof.transaction.output.len()
+    ));
+    if let Some(prevouts) = &proof.prevouts {
+        output.push_str(&format!(
+            "  Previous outputs: {} (not proven)\n",
+            prevouts.len()
+        ));
+    }
+    if let Some(witness_proof) = &proof.witness_proof {
+        output.push_str(&format!(
+            "  Wtxid: {} (witness proof embedded)\n",
+            proof.transaction.compute_wtxid()
+        ));
+        output.push_str(&format!(
+            "  Coinbase: {}\n",
+            witness_proof.coinbase.compute_txid()
+        ));
+    }
+
+    output.push('\n');
+    output.push_str(&format_component_sizes(proof)?);
+
+    output.push_str("\nPROGRAM HASHES (claimed, not verified):\n");
+    match BootloaderOutput::from_proof(&proof.chain_state_proof) {
+        Ok((bootloader_hash, bootloader_output)) => {
+            let BootloaderOutput {
+                n_tasks,
+                task_output_size,
+                task_program_hash,
+                task_result:
+                    TaskResult {
+                        chain_state_hash,
+                        block_mmr_hash,
+                        bootloader_hash: prev_bootloader_hash,
+                        program_hash: prev_program_hash,
+                    },
+            } = bootloader_output;
+            output.push_str(&format!("  Bootloader hash: {}\n", bootloader_hash));
+            output.push_str(&format!("  Number of tasks: {}\n", n_tasks));
+            output.push_str(&format!("  Task output size: {}\n", task_output_size));
+            output.push_str(&format!("  Task program hash: {}\n", task_program_hash));
+            output.push_str(&format!(
+                "  Previous bootloader hash: {}\n",
+                prev_bootloader_hash
+            ));
+            output.push_str(&format!("  Previous program hash: {}\n", prev_program_hash));
+            output.push_str(&format!("  Chain state hash: {}\n", chain_state_hash));
+            output.push_str(&format!("  Block MMR hash: {}\n", block_mmr_hash));
+        }
+        Err(err) => {
+            output.push_str(&format!("  Failed to decode bootloader output: {}\n", err));
+        }
+    }
+
+    Ok(output)
+}
+
+/// Format the serialized size of each proof component
+pub fn format_component_sizes(proof: &CompressedSpvProof) -> Result<String, anyhow::Error> {
+    let mut output = String::new();
+    output.push_str("COMPONENT SIZES:\n");
+    push_component_size(&mut output, "chain_state", &proof.chain_state)?;
+    push_component_size(&mut output, "chain_state_proof", &proof.chain_state_proof)?;
+    push_component_size(&mut output, "block_header", &proof.block_header)?;
+    push_component_size(&mut output, "block_header_proof", &proof.block_header_proof)?;
+    push_component_size(&mut output, "transaction", &proof.transaction)?;
+    push_component_size(&mut output, "transaction_proof", &proof.transaction_proof)?;
+    if let Some(headers) = &proof.subchain_headers {
+        push_component_size(&mut output, "subchain_headers", headers)?;
+    }
+    if let Some(prevouts) = &proof.prevouts {
+        push_component_size(&mut output, "prevouts", prevouts)?;
+    }
+    if let Some(witness_proof) = &proof.witness_proof {
+        push_component_size(&mut output, "witness_proof", witness_proof)?;
+    }
+    Ok(output)
+}
+
+/// Append the serialized size of a proof component to the output
+fn push_component_size<T: Serialize + ?Sized>(
+    output: &mut String,
+    name: &str,
+    component: &T,
+) -> Result<(), anyhow::Error> {
+    output.push_str(&format!(
+        "  {:<20} {} bytes\n",
+        name,
+        bincode::serialized_size(component)?
+    ));
+    Ok(())
+}
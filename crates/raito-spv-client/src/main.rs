@@ -1,14 +1,46 @@
 #![doc = include_str!("../README.md")]
 
-use clap::{command, Parser, Subcommand};
+use std::time::Duration;
+
+use clap::{command, CommandFactory, Parser, Subcommand};
+use raito_spv_verifier::fetch::RetryPolicy;
 use tracing::{error, info, subscriber::set_global_default};
 use tracing_subscriber::filter::EnvFilter;
 
+mod attest;
+mod cache;
+mod calldata;
+mod chainstate;
+#[cfg(feature = "prove")]
+mod compress;
+mod convert;
+mod crosscheck;
+mod descriptor;
+mod diff;
+mod docs;
+mod download;
+mod expect;
 mod fetch;
+mod fixture;
 mod format;
-mod proof;
+mod inspect;
+mod monitor;
+mod network;
+mod nostr;
+mod package;
+mod pin;
+mod progress;
+#[cfg(feature = "prove")]
+mod prove;
+mod prove_dev;
+mod psbt;
+mod serve;
+mod store;
+mod timestamp;
+mod trusted;
 mod verify;
-mod work;
+mod view;
+mod watch;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -19,21 +51,79 @@ struct Cli {
     /// Logging level (off, error, warn, info, debug, trace)
     #[arg(long, default_value = "info")]
     log_level: String,
+    /// Disable colored output (also disabled by the `NO_COLOR` environment variable)
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Timeout of a Raito bridge RPC request, in seconds
+    #[arg(long, global = true, default_value_t = 120)]
+    rpc_timeout: u64,
+    /// Number of retries of a Raito bridge RPC request failing with a server or network error
+    #[arg(long, global = true, default_value_t = 3)]
+    rpc_retries: u32,
 }
 
 #[derive(Subcommand, Clone, Debug)]
 enum Commands {
     /// Fetch a compressed proof
     Fetch(fetch::FetchArgs),
-    Verify(verify::VerifyArgs),
+    Verify(Box<verify::VerifyArgs>),
+    /// Print the contents of a proof file without verifying it
+    Inspect(inspect::InspectArgs),
+    /// Convert a proof between the binary and JSON representations
+    Convert(convert::ConvertArgs),
+    /// Compare two proofs and report which components differ
+    Diff(diff::DiffArgs),
+    /// Fetch and verify the latest proven chain state
+    Chainstate(chainstate::ChainStateArgs),
+    /// Run a local HTTP server exposing proof verification
+    Serve(serve::ServeArgs),
+    /// Watch transactions until they are buried deep enough, then fetch and verify their proofs
+    Watch(watch::WatchArgs),
+    /// Watch addresses for deposits, then verify them and emit signed events
+    Monitor(monitor::MonitorArgs),
+    /// Verify a proof and check that it covers a transaction signed by a wallet (finalized
+    /// PSBT or extracted transaction)
+    VerifyPsbt(psbt::VerifyPsbtArgs),
+    /// Check the signature of a verification attestation
+    VerifyAttestation(attest::VerifyAttestationArgs),
+    /// Export a proof as Starknet calldata for an on-chain verifier
+    ExportCalldata(calldata::ExportCalldataArgs),
+    /// Export a compact timestamp attestation for data committed via OP_RETURN
+    ExportTimestamp(timestamp::ExportTimestampArgs),
+    /// Bundle a verified proof with its report and renderings into an archive for auditors
+    Package(package::PackageArgs),
+    /// Browse a proof interactively in the terminal
+    View(view::ViewArgs),
+    /// List the verified proofs recorded in the local database
+    List(store::ListArgs),
+    /// Show the recorded verifications of a proof, by record or transaction ID
+    Show(store::ShowArgs),
+    /// Generate a proof file with controlled defects, for testing verifier integrations
+    GenFixture(fixture::GenFixtureArgs),
+    /// Assemble a development proof from a local bridge node, with a stub in place of the
+    /// chain state proof, for testing verifier integrations
+    ProveDev(prove_dev::ProveDevArgs),
+    /// Prove the chain state from a local bridge node with the Cairo and Stwo toolchain, and
+    /// publish the proof to the bridge node
+    #[cfg(feature = "prove")]
+    Prove(prove::ProveArgs),
+    /// Re-compress a proof bundle into a single recursive proof of the inclusion of all its
+    /// transactions, with the Cairo and Stwo toolchain
+    #[cfg(feature = "prove")]
+    Compress(compress::CompressArgs),
+    /// Print the shell completion script
+    Completions(docs::CompletionsArgs),
+    /// Write the man pages to a directory
+    Manpages(docs::ManpagesArgs),
 }
 
 fn init_tracing(log_level: &str) {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
 
-    let subscriber_builder =
-        tracing_subscriber::fmt::Subscriber::builder().with_env_filter(env_filter);
+    let subscriber_builder = tracing_subscriber::fmt::Subscriber::builder()
+        .with_env_filter(env_filter)
+        .with_ansi(console::colors_enabled_stderr());
 
     let subscriber = subscriber_builder.with_writer(std::io::stderr).finish();
     set_global_default(subscriber).expect("Failed to set subscriber");
@@ -45,11 +135,43 @@ async fn main() {
     dotenv::dotenv().ok();
 
     let cli = Cli::parse();
+    if cli.no_color {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
     init_tracing(&cli.log_level);
+    let retry_policy = RetryPolicy {
+        timeout: Duration::from_secs(cli.rpc_timeout),
+        max_retries: cli.rpc_retries,
+        ..Default::default()
+    };
 
     let res = match cli.command {
-        Commands::Fetch(args) => fetch::run(args).await,
-        Commands::Verify(args) => verify::run(args).await,
+        Commands::Fetch(args) => fetch::run(args, retry_policy).await,
+        Commands::Verify(args) => verify::run(*args, retry_policy).await,
+        Commands::Inspect(args) => inspect::run(args).await,
+        Commands::Convert(args) => convert::run(args).await,
+        Commands::Diff(args) => diff::run(args).await,
+        Commands::Chainstate(args) => chainstate::run(args, retry_policy).await,
+        Commands::Serve(args) => serve::run(args, retry_policy).await,
+        Commands::Watch(args) => watch::run(args, retry_policy).await,
+        Commands::Monitor(args) => monitor::run(args, retry_policy).await,
+        Commands::VerifyPsbt(args) => psbt::run(args).await,
+        Commands::VerifyAttestation(args) => attest::run(args).await,
+        Commands::ExportCalldata(args) => calldata::run(args).await,
+        Commands::ExportTimestamp(args) => timestamp::run(args).await,
+        Commands::Package(args) => package::run(args).await,
+        Commands::View(args) => view::run(args).await,
+        Commands::List(args) => store::list(args).await,
+        Commands::Show(args) => store::show(args).await,
+        Commands::GenFixture(args) => fixture::run(args).await,
+        Commands::ProveDev(args) => prove_dev::run(args, retry_policy).await,
+        #[cfg(feature = "prove")]
+        Commands::Prove(args) => prove::run(args, retry_policy).await,
+        #[cfg(feature = "prove")]
+        Commands::Compress(args) => compress::run(args).await,
+        Commands::Completions(args) => docs::completions(args, Cli::command()),
+        Commands::Manpages(args) => docs::manpages(args, Cli::command()),
     };
 
     match res {
@@ -59,7 +181,9 @@ async fn main() {
         }
         Err(err) => {
             error!("Raito client has exited with error: {}", err);
-            std::process::exit(1);
+            let code =
+                raito_spv_verifier::error::failure_class(&err).map_or(1, |class| class.exit_code());
+            std::process::exit(code);
         }
     }
 }
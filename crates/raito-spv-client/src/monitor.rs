@@ -0,0 +1,772 @@
+//! Deposit verification daemon: watch a list of addresses, given explicitly or derived from
+//! output descriptors up to a gap limit, and once a deposit is buried deep
+//! enough in the proven chain, fetch and verify its compressed proof.
+//!
+//! Deposits are discovered through a Bitcoin node (`scantxoutset`) or an Esplora instance, and
+//! are not trusted: the amount and address of a verified deposit are read from the proven
+//! transaction. Events are optionally signed with the attestation key format, and delivered at
+//! least once to stdout, a webhook or a file queue.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{schnorr::Signature, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::{Address, Network, OutPoint, ScriptBuf, Txid};
+use chrono::Utc;
+use raito_spv_core::bitcoin::BitcoinClient;
+use raito_spv_verifier::error::FailureClass;
+use raito_spv_verifier::fetch::{ApiEndpoint, BitcoinDataSource, ChainStateProof, RetryPolicy};
+use raito_spv_verifier::verify::{verify_proof, VerificationReport, VerifierConfig};
+use raito_spv_verifier::work::check_block_depth;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::attest::load_keypair;
+use crate::descriptor::Descriptor;
+use crate::fetch::{
+    build_compressed_proof, default_proof_file_name, fetch_chain_state_proof,
+    fetch_transaction_proof, pruned_fallback, save_serialized_proof_with_bzip2,
+};
+use crate::network::{MAINNET_RAITO_RPC_URL, RAITO_RPC_URLS, RAITO_RPC_URL_REQUIRED};
+use crate::store::{record, ProofStore, RecordArgs};
+use crate::verify::VerifierConfigArgs;
+
+/// Timeout of a `scantxoutset` call, which scans the whole UTXO set
+const SCAN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// CLI arguments for the `monitor` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct MonitorArgs {
+    /// Address to watch for deposits (can be repeated)
+    #[arg(long = "address", required_unless_present = "descriptors")]
+    addresses: Vec<Address<NetworkUnchecked>>,
+    /// Output descriptor of a wallet whose addresses to watch, e.g.
+    /// `wpkh([fingerprint/84'/0'/0']xpub…/0/*)` (can be repeated)
+    #[arg(long = "descriptor")]
+    descriptors: Vec<Descriptor>,
+    /// Number of unused addresses derived past the last used address of each descriptor
+    #[arg(long, default_value = "20")]
+    gap_limit: u32,
+    /// Polling interval in seconds
+    #[arg(long, default_value = "60")]
+    poll_interval: u64,
+    /// Esplora API URL used to discover deposits instead of scanning the UTXO set of the
+    /// Bitcoin node, e.g. https://blockstream.info/api
+    #[arg(long)]
+    esplora_url: Option<String>,
+    /// URL to POST the events to (JSON)
+    #[arg(long, conflicts_with = "queue_dir")]
+    webhook: Option<String>,
+    /// Directory to write the events to, one JSON file per event
+    #[arg(long)]
+    queue_dir: Option<PathBuf>,
+    /// Path to a file containing the secp256k1 secret key (hex string) used to sign the events
+    #[arg(long)]
+    sign_key: Option<PathBuf>,
+    /// Path to the file recording the processed deposits, so that they are not verified again
+    /// after a restart
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+    /// Directory to save verified proofs to
+    #[arg(long)]
+    proof_dir: Option<PathBuf>,
+    /// Raito node RPC URL, defaults to the Raito bridge of the `--network`; required on
+    /// `testnet4` and `signet`, which have no public bridge
+    #[arg(
+        long,
+        env = "RAITO_BRIDGE_RPC",
+        default_value = MAINNET_RAITO_RPC_URL,
+        default_value_ifs = RAITO_RPC_URLS,
+        required_if_eq_any = RAITO_RPC_URL_REQUIRED
+    )]
+    raito_rpc_url: String,
+    /// Bitcoin RPC URL
+    #[arg(long, env = "BITCOIN_RPC")]
+    bitcoin_rpc_url: String,
+    /// Bitcoin RPC user:password (optional)
+    #[arg(long, env = "USERPWD")]
+    bitcoin_rpc_userpwd: Option<String>,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+    #[command(flatten)]
+    record: RecordArgs,
+    /// Development mode
+    #[arg(long, default_value = "false")]
+    dev: bool,
+}
+
+/// Output paying a watched address, as reported by the deposit discovery backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Deposit {
+    /// Transaction ID
+    pub txid: Txid,
+    /// Index of the output paying the address
+    pub vout: u32,
+    /// Watched address
+    pub address: String,
+    /// Amount in satoshis
+    pub amount: u64,
+    /// Height of the block containing the transaction, `None` while unconfirmed
+    pub block_height: Option<u32>,
+}
+
+/// Event emitted when a deposit changes state
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MonitorEvent {
+    /// A new deposit to a watched address was discovered
+    Detected {
+        #[serde(flatten)]
+        deposit: Deposit,
+    },
+    /// The deposit proof was fetched and successfully verified, amount and address are
+    /// proven
+    Verified {
+        #[serde(flatten)]
+        deposit: Deposit,
+        /// Path the proof was saved to, if a proof directory is configured
+        proof_path: Option<PathBuf>,
+        /// Summary of the verified proof
+        report: Box<VerificationReport>,
+    },
+    /// The deposit proof is invalid, or the proven transaction doesn't pay the deposit
+    Failed {
+        #[serde(flatten)]
+        deposit: Deposit,
+        /// Reason of the failure
+        error: String,
+    },
+}
+
+/// Event signed with a BIP340 Schnorr signature
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedEvent {
+    /// The signed event
+    pub payload: serde_json::Value,
+    /// X-only public key of the monitor
+    pub public_key: XOnlyPublicKey,
+    /// Signature of the SHA-256 of the compact JSON serialization of the payload, as emitted
+    pub signature: Signature,
+}
+
+impl SignedEvent {
+    /// Sign the event with the monitor key
+    pub fn sign(event: &MonitorEvent, keypair: &Keypair) -> Result<Self, anyhow::Error> {
+        // The payload is signed and emitted from the same JSON value, so that the signed bytes
+        // are exactly the emitted ones
+        let payload = serde_json::to_value(event)?;
+        let signature =
+            Secp256k1::signing_only().sign_schnorr_no_aux_rand(&message(&payload)?, keypair);
+        Ok(Self {
+            payload,
+            public_key: keypair.x_only_public_key().0,
+            signature,
+        })
+    }
+}
+
+/// Message signed by the monitor
+fn message(payload: &serde_json::Value) -> Result<Message, anyhow::Error> {
+    let digest = sha256::Hash::hash(&serde_json::to_vec(payload)?);
+    Ok(Message::from_digest(digest.to_byte_array()))
+}
+
+/// Destination of the events
+enum EventSink {
+    /// One JSON line per event on stdout
+    Stdout,
+    /// POST request per event
+    Webhook(reqwest::Client, String),
+    /// One JSON file per event in a directory
+    Queue(PathBuf),
+}
+
+/// Delivery of the events
+struct Emitter {
+    /// Destination of the events
+    sink: EventSink,
+    /// Key signing the events, if any
+    keypair: Option<Keypair>,
+}
+
+/// Run the `monitor` subcommand: poll the watched addresses until interrupted
+pub async fn run(args: MonitorArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let mut config = args.verifier_config.resolve()?;
+    if args.dev {
+        config.enable_dev_mode();
+    }
+    let mut watch_list = WatchList::new(&args, config.network)?;
+    let keypair = args.sign_key.as_deref().map(load_keypair).transpose()?;
+    let store = args.record.open().await?;
+    let sink = match (&args.webhook, &args.queue_dir) {
+        (Some(url), _) => EventSink::Webhook(reqwest::Client::new(), url.clone()),
+        (None, Some(dir)) => {
+            std::fs::create_dir_all(dir)?;
+            EventSink::Queue(dir.clone())
+        }
+        (None, None) => EventSink::Stdout,
+    };
+    let emitter = Emitter { sink, keypair };
+    let mut processed = match &args.state_file {
+        Some(path) => load_state(path)?,
+        None => BTreeSet::new(),
+    };
+    let poll_interval = Duration::from_secs(args.poll_interval);
+    info!(
+        "Monitoring {} address(es), {} deposit(s) already processed",
+        watch_list.addresses.len(),
+        processed.len()
+    );
+
+    let mut pending: BTreeMap<OutPoint, Deposit> = BTreeMap::new();
+    loop {
+        match watch_list.discover(&args).await {
+            Ok(deposits) => {
+                for deposit in deposits {
+                    let outpoint = OutPoint::new(deposit.txid, deposit.vout);
+                    if processed.contains(&outpoint) {
+                        continue;
+                    }
+                    if let Some(known) = pending.get_mut(&outpoint) {
+                        known.block_height = deposit.block_height;
+                        continue;
+                    }
+                    // Undelivered events are retried on the next poll
+                    let event = MonitorEvent::Detected {
+                        deposit: deposit.clone(),
+                    };
+                    match emit(&emitter, &event).await {
+                        Ok(()) => {
+                            pending.insert(outpoint, deposit);
+                        }
+                        Err(err) => warn!("Failed to deliver event: {}", err),
+                    }
+                }
+            }
+            Err(err) => warn!("Failed to discover deposits: {}", err),
+        }
+
+        if pending
+            .values()
+            .any(|deposit| deposit.block_height.is_some())
+        {
+            match fetch_chain_state_proof(&raito_rpc).await {
+                Ok(proof) => {
+                    process_pending(
+                        &args,
+                        &raito_rpc,
+                        &config,
+                        &emitter,
+                        store.as_ref(),
+                        proof,
+                        &mut pending,
+                        &mut processed,
+                    )
+                    .await?
+                }
+                Err(err) => warn!("Failed to fetch chain state proof: {}", err),
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Monitor interrupted, {} deposit(s) pending", pending.len());
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Addresses watched for deposits, given explicitly or derived from descriptors
+struct WatchList {
+    /// Watched addresses and their script
+    addresses: Vec<(Address, ScriptBuf)>,
+    /// Descriptors and the number of addresses derived from each
+    descriptors: Vec<(Descriptor, u32)>,
+    /// Descriptor and index of each derived address
+    derived: HashMap<String, (usize, u32)>,
+    /// Number of unused addresses to keep past the last used address of each descriptor
+    gap_limit: u32,
+    /// Network of the addresses
+    network: Network,
+}
+
+impl WatchList {
+    /// Watch the given addresses, and the first `gap_limit` addresses of each descriptor
+    fn new(args: &MonitorArgs, network: Network) -> Result<Self, anyhow::Error> {
+        let mut watch_list = Self {
+            addresses: Vec::new(),
+            descriptors: args.descriptors.iter().map(|d| (d.clone(), 0)).collect(),
+            derived: HashMap::new(),
+            gap_limit: args.gap_limit,
+            network,
+        };
+        for address in &args.addresses {
+            watch_list.push(address.clone().require_network(network)?);
+        }
+        for index in 0..watch_list.descriptors.len() {
+            watch_list.derive(index, args.gap_limit)?;
+        }
+        Ok(watch_list)
+    }
+
+    fn push(&mut self, address: Address) {
+        let script = address.script_pubkey();
+        self.addresses.push((address, script));
+    }
+
+    /// Derive the addresses of a descriptor up to index `count` (excluded)
+    fn derive(&mut self, index: usize, count: u32) -> Result<(), anyhow::Error> {
+        let (descriptor, derived) = &self.descriptors[index];
+        let (descriptor, start) = (descriptor.clone(), *derived);
+        for child in start..count {
+            let address = descriptor.address(child, self.network)?;
+            self.derived.insert(address.to_string(), (index, child));
+            self.push(address);
+        }
+        self.descriptors[index].1 = start.max(count);
+        Ok(())
+    }
+
+    /// Discover the deposits to the watched addresses, deriving new addresses from the
+    /// descriptors until each has `gap_limit` unused addresses past its last used one
+    async fn discover(&mut self, args: &MonitorArgs) -> Result<Vec<Deposit>, anyhow::Error> {
+        let mut deposits = Vec::new();
+        let mut start = 0;
+        while start < self.addresses.len() {
+            deposits.extend(discover_deposits(args, &self.addresses[start..]).await?);
+            start = self.addresses.len();
+
+            let mut used: HashMap<usize, u32> = HashMap::new();
+            for deposit in &deposits {
+                if let Some(&(index, child)) = self.derived.get(&deposit.address) {
+                    let max = used.entry(index).or_default();
+                    *max = (*max).max(child);
+                }
+            }
+            for (index, child) in used {
+                self.derive(index, child + 1 + self.gap_limit)?;
+            }
+            if self.addresses.len() > start {
+                info!(
+                    "Derived {} more address(es) to keep the gap limit",
+                    self.addresses.len() - start
+                );
+            }
+        }
+        Ok(deposits)
+    }
+}
+
+/// Verify the confirmed pending deposits that are buried deep enough in the proven chain
+#[allow(clippy::too_many_arguments)]
+async fn process_pending(
+    args: &MonitorArgs,
+    raito_rpc: &ApiEndpoint,
+    config: &VerifierConfig,
+    emitter: &Emitter,
+    store: Option<&ProofStore>,
+    chain_state_proof: ChainStateProof,
+    pending: &mut BTreeMap<OutPoint, Deposit>,
+    processed: &mut BTreeSet<OutPoint>,
+) -> Result<(), anyhow::Error> {
+    let chain_height = chain_state_proof.chain_state.block_height;
+    info!(
+        "Proven chain height: {}, {} deposit(s) pending",
+        chain_height,
+        pending.len()
+    );
+    let mut chain_state_proof = Some(chain_state_proof);
+
+    let outpoints: Vec<OutPoint> = pending.keys().copied().collect();
+    for outpoint in outpoints {
+        let deposit = &pending[&outpoint];
+        let Some(block_height) = deposit.block_height else {
+            continue;
+        };
+
+        // The chain state proof is consumed by the compressed proof, fetch a new one if
+        // several deposits become ready during the same poll
+        let proof = match chain_state_proof.take() {
+            Some(proof) => proof,
+            None => match fetch_chain_state_proof(raito_rpc).await {
+                Ok(proof) => proof,
+                Err(err) => {
+                    warn!("Failed to fetch chain state proof: {}", err);
+                    return Ok(());
+                }
+            },
+        };
+        if let Err(err) = check_block_depth(block_height, &proof.chain_state, config) {
+            info!("Deposit {}: {}", outpoint, err);
+            chain_state_proof = Some(proof);
+            continue;
+        }
+
+        let event = match prove_and_verify(args, raito_rpc, config, proof, deposit).await {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!(
+                    "Failed to prove deposit {}, retrying on the next poll: {}",
+                    outpoint, err
+                );
+                continue;
+            }
+        };
+        if let Err(err) = emit(emitter, &event).await {
+            warn!("Failed to deliver event: {}", err);
+            continue;
+        }
+        if let MonitorEvent::Verified {
+            deposit,
+            proof_path,
+            report,
+        } = &event
+        {
+            let payment = report.transaction.output.get(deposit.vout as usize);
+            let source = proof_path.as_ref().map(|path| path.display().to_string());
+            record(store, report, config, payment, source).await?;
+        }
+        pending.remove(&outpoint);
+        processed.insert(outpoint);
+        if let Some(path) = &args.state_file {
+            save_state(path, processed)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fetch and verify the proof of a deposit, checking that the proven transaction pays the
+/// deposit. Returns an error if the proof could not be fetched or failed a check that may pass
+/// later (see [is_terminal]), to retry on the next poll, and `None` if the deposit is no longer
+/// in the block reported by the backend (reorg).
+async fn prove_and_verify(
+    args: &MonitorArgs,
+    raito_rpc: &ApiEndpoint,
+    config: &VerifierConfig,
+    chain_state_proof: ChainStateProof,
+    deposit: &Deposit,
+) -> Result<Option<MonitorEvent>, anyhow::Error> {
+    let transaction_proof = fetch_transaction_proof(
+        deposit.txid,
+        &BitcoinDataSource::Node {
+            url: args.bitcoin_rpc_url.clone(),
+            userpwd: args.bitcoin_rpc_userpwd.clone(),
+            pruned_fallback: Some(pruned_fallback(args.esplora_url.as_deref(), raito_rpc)),
+        },
+    )
+    .await?;
+    if Some(transaction_proof.block_height) != deposit.block_height {
+        info!(
+            "Deposit {}:{} moved to height {}, waiting for the next poll",
+            deposit.txid, deposit.vout, transaction_proof.block_height
+        );
+        return Ok(None);
+    }
+    let proof =
+        build_compressed_proof(chain_state_proof, transaction_proof, raito_rpc, args.dev).await?;
+
+    // The backend is not trusted, the event reports the proven output
+    let Some(output) = proof.transaction.output.get(deposit.vout as usize) else {
+        return Ok(Some(MonitorEvent::Failed {
+            deposit: deposit.clone(),
+            error: format!("Transaction has no output {}", deposit.vout),
+        }));
+    };
+    let address = Address::from_script(&output.script_pubkey, config.network)
+        .map(|address| address.to_string());
+    let mut proven = deposit.clone();
+    proven.amount = output.value.to_sat();
+    if address.as_ref().ok() != Some(&deposit.address) {
+        return Ok(Some(MonitorEvent::Failed {
+            deposit: proven,
+            error: format!("Output {} doesn't pay {}", deposit.vout, deposit.address),
+        }));
+    }
+
+    // Only valid proofs are saved, the proof is serialized beforehand since verification
+    // consumes it
+    let saved_proof = match &args.proof_dir {
+        Some(proof_dir) => Some((
+            proof_dir.join(default_proof_file_name(&proof)),
+            proof.to_bytes()?,
+        )),
+        None => None,
+    };
+    let report = match verify_proof(proof, config).await {
+        Ok(report) => report,
+        Err(err) if is_terminal(err.class()) => {
+            return Ok(Some(MonitorEvent::Failed {
+                deposit: proven,
+                error: err.to_string(),
+            }))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let proof_path = match saved_proof {
+        Some((proof_path, proof_bytes)) => {
+            save_serialized_proof_with_bzip2(&proof_bytes, &proof_path)?;
+            Some(proof_path)
+        }
+        None => None,
+    };
+    Ok(Some(MonitorEvent::Verified {
+        deposit: proven,
+        proof_path,
+        report: Box::new(report),
+    }))
+}
+
+/// Whether a verification failure is final for the deposit: the proven transaction itself is
+/// rejected. Other failures (e.g. a chain state not yet deep enough, or a bridge node out of
+/// sync) may pass with the proof fetched on a later poll
+fn is_terminal(class: FailureClass) -> bool {
+    matches!(
+        class,
+        FailureClass::TransactionInclusion | FailureClass::Assertion
+    )
+}
+
+/// Deposits to the watched addresses reported by the discovery backend
+async fn discover_deposits(
+    args: &MonitorArgs,
+    watched: &[(Address, ScriptBuf)],
+) -> Result<Vec<Deposit>, anyhow::Error> {
+    match &args.esplora_url {
+        Some(url) => {
+            let url = url.trim_end_matches('/');
+            let client = reqwest::Client::new();
+            let mut deposits = Vec::new();
+            for (address, script) in watched {
+                let txs: Vec<EsploraTx> = client
+                    .get(format!("{}/address/{}/txs", url, address))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                deposits.extend(deposits_from_esplora(address, script, &txs));
+            }
+            Ok(deposits)
+        }
+        None => {
+            let client = BitcoinClient::with_timeout(
+                args.bitcoin_rpc_url.clone(),
+                args.bitcoin_rpc_userpwd.clone(),
+                SCAN_TIMEOUT,
+            )?;
+            let descriptors: Vec<String> = watched
+                .iter()
+                .map(|(address, _)| format!("addr({})", address))
+                .collect();
+            let result = client.scan_tx_out_set(&descriptors).await?;
+            Ok(result
+                .unspents
+                .iter()
+                .filter_map(|utxo| {
+                    let (address, _) = watched
+                        .iter()
+                        .find(|(_, script)| *script == utxo.script_pub_key)?;
+                    Some(Deposit {
+                        txid: utxo.txid,
+                        vout: utxo.vout,
+                        address: address.to_string(),
+                        amount: utxo.amount.to_sat(),
+                        block_height: Some(utxo.height as u32),
+                    })
+                })
+                .collect())
+        }
+    }
+}
+
+/// Transaction as returned by the Esplora `/address/:address/txs` endpoint
+#[derive(Debug, Deserialize)]
+struct EsploraTx {
+    txid: Txid,
+    vout: Vec<EsploraTxOut>,
+    status: EsploraTxStatus,
+}
+
+/// Transaction output as returned by Esplora
+#[derive(Debug, Deserialize)]
+struct EsploraTxOut {
+    scriptpubkey: ScriptBuf,
+    value: u64,
+}
+
+/// Transaction confirmation status as returned by Esplora
+#[derive(Debug, Deserialize)]
+struct EsploraTxStatus {
+    confirmed: bool,
+    block_height: Option<u32>,
+}
+
+/// Outputs of the Esplora transactions paying the address
+fn deposits_from_esplora(address: &Address, script: &ScriptBuf, txs: &[EsploraTx]) -> Vec<Deposit> {
+    txs.iter()
+        .flat_map(|tx| {
+            tx.vout
+                .iter()
+                .enumerate()
+                .filter(|(_, output)| output.scriptpubkey == *script)
+                .map(|(vout, output)| Deposit {
+                    txid: tx.txid,
+                    vout: vout as u32,
+                    address: address.to_string(),
+                    amount: output.value,
+                    block_height: tx.status.block_height.filter(|_| tx.status.confirmed),
+                })
+        })
+        .collect()
+}
+
+/// Deliver an event, signed if a key is configured
+async fn emit(emitter: &Emitter, event: &MonitorEvent) -> Result<(), anyhow::Error> {
+    let json = match &emitter.keypair {
+        Some(keypair) => serde_json::to_string(&SignedEvent::sign(event, keypair)?)?,
+        None => serde_json::to_string(event)?,
+    };
+    match &emitter.sink {
+        EventSink::Stdout => println!("{}", json),
+        EventSink::Webhook(client, url) => {
+            client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(json)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        EventSink::Queue(dir) => write_queue_file(dir, event, &json)?,
+    }
+    Ok(())
+}
+
+/// Write an event to the queue directory as `<unix millis>-<txid>-<vout>-<event>.json`, through
+/// a temporary file so that consumers never read a partial event
+fn write_queue_file(dir: &Path, event: &MonitorEvent, json: &str) -> Result<(), anyhow::Error> {
+    let (kind, deposit) = match event {
+        MonitorEvent::Detected { deposit } => ("detected", deposit),
+        MonitorEvent::Verified { deposit, .. } => ("verified", deposit),
+        MonitorEvent::Failed { deposit, .. } => ("failed", deposit),
+    };
+    let name = format!(
+        "{}-{}-{}-{}.json",
+        Utc::now().timestamp_millis(),
+        deposit.txid,
+        deposit.vout,
+        kind
+    );
+    let tmp_path = dir.join(format!(".{}.tmp", name));
+    std::fs::write(&tmp_path, json)?;
+    std::fs::rename(&tmp_path, dir.join(name))?;
+    Ok(())
+}
+
+/// Load the processed deposits, none if the state file doesn't exist yet
+fn load_state(path: &Path) -> Result<BTreeSet<OutPoint>, anyhow::Error> {
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|e| anyhow::anyhow!("Invalid state file {}: {}", path.display(), e))
+}
+
+/// Save the processed deposits
+fn save_state(path: &Path, processed: &BTreeSet<OutPoint>) -> Result<(), anyhow::Error> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(processed)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Network;
+
+    fn deposit() -> Deposit {
+        Deposit {
+            txid: Txid::all_zeros(),
+            vout: 1,
+            address: "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+            amount: 100_000,
+            block_height: Some(101),
+        }
+    }
+
+    #[test]
+    fn test_deposits_from_esplora() {
+        let address: Address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"
+            .parse::<Address<NetworkUnchecked>>()
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap();
+        let script = address.script_pubkey();
+        let txs: Vec<EsploraTx> = serde_json::from_value(serde_json::json!([
+            {
+                "txid": Txid::all_zeros(),
+                "vout": [
+                    { "scriptpubkey": "51", "value": 1 },
+                    { "scriptpubkey": script.to_hex_string(), "value": 100_000 }
+                ],
+                "status": { "confirmed": true, "block_height": 101 }
+            },
+            {
+                "txid": Txid::from_byte_array([1; 32]),
+                "vout": [{ "scriptpubkey": script.to_hex_string(), "value": 5 }],
+                "status": { "confirmed": false }
+            }
+        ]))
+        .unwrap();
+
+        let deposits = deposits_from_esplora(&address, &script, &txs);
+        assert_eq!(deposits.len(), 2);
+        assert_eq!(deposits[0], deposit());
+        assert_eq!(deposits[1].block_height, None);
+    }
+
+    #[test]
+    fn test_signed_event() {
+        let keypair = Keypair::from_seckey_str(
+            &Secp256k1::signing_only(),
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let event = MonitorEvent::Detected { deposit: deposit() };
+        let json = serde_json::to_string(&SignedEvent::sign(&event, &keypair).unwrap()).unwrap();
+
+        let mut signed: SignedEvent = serde_json::from_str(&json).unwrap();
+        assert_eq!(signed.payload["event"], "detected");
+        assert_eq!(signed.payload["amount"], 100_000);
+        let verify = |signed: &SignedEvent| {
+            Secp256k1::verification_only().verify_schnorr(
+                &signed.signature,
+                &message(&signed.payload).unwrap(),
+                &signed.public_key,
+            )
+        };
+        assert!(verify(&signed).is_ok());
+
+        signed.payload["amount"] = 200_000.into();
+        assert!(verify(&signed).is_err());
+    }
+
+    #[test]
+    fn test_state_file() {
+        let path = std::env::temp_dir().join(format!("raito-monitor-{}.json", std::process::id()));
+        assert!(load_state(&path).unwrap().is_empty());
+
+        let processed = BTreeSet::from([OutPoint::new(Txid::all_zeros(), 1)]);
+        save_state(&path, &processed).unwrap();
+        assert_eq!(load_state(&path).unwrap(), processed);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
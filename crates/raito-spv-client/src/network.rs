@@ -0,0 +1,83 @@
+//! Defaults that follow the network selected with `--network`: the Raito bridge endpoint, the
+//! work policy, and (through the program registry) the accepted program hashes.
+
+use std::str::FromStr;
+
+use bitcoin::Network;
+
+/// Raito bridge RPC on mainnet
+pub const MAINNET_RAITO_RPC_URL: &str = "https://api.raito.wtf";
+
+/// Default Raito bridge RPC of the networks other than mainnet, keyed by the `--network` value,
+/// for `default_value_ifs`
+pub const RAITO_RPC_URLS: [(&str, &str, &str); 1] = [
+    // Bridge node running locally with its default settings
+    ("network", "regtest", "http://127.0.0.1:5000"),
+];
+
+/// Networks without a public Raito bridge, for which `--raito-rpc-url` must be given, keyed by
+/// the `--network` value for `required_if_eq_any`
+pub const RAITO_RPC_URL_REQUIRED: [(&str, &str); 3] = [
+    ("network", "testnet4"),
+    ("network", "signet"),
+    ("network", "testnet"),
+];
+
+/// Confirmations required by default on networks whose difficulty says nothing about the work
+/// of past blocks, where the absolute minimum work of mainnet is meaningless
+const TEST_NETWORK_MIN_CONFIRMATIONS: u32 = 6;
+
+/// Parse a network name, accepting `mainnet` as an alias of `bitcoin`
+pub fn parse_network(s: &str) -> Result<Network, anyhow::Error> {
+    match s {
+        "mainnet" => Ok(Network::Bitcoin),
+        _ => Network::from_str(s).map_err(|e| anyhow::anyhow!("{}", e)),
+    }
+}
+
+/// Default work policy of the network in confirmations, `None` for the absolute minimum work
+pub fn default_min_confirmations(network: Network) -> Option<u32> {
+    match network {
+        Network::Bitcoin => None,
+        _ => Some(TEST_NETWORK_MIN_CONFIRMATIONS),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[arg(long, value_parser = parse_network)]
+        network: Option<Network>,
+        #[arg(
+            long,
+            default_value = MAINNET_RAITO_RPC_URL,
+            default_value_ifs = RAITO_RPC_URLS,
+            required_if_eq_any = RAITO_RPC_URL_REQUIRED
+        )]
+        raito_rpc_url: String,
+    }
+
+    #[test]
+    fn test_network_defaults() {
+        let cli = Cli::parse_from(["raito", "--network", "mainnet"]);
+        assert_eq!(cli.network, Some(Network::Bitcoin));
+        assert_eq!(cli.raito_rpc_url, MAINNET_RAITO_RPC_URL);
+
+        let cli = Cli::parse_from(["raito", "--network", "regtest"]);
+        assert_eq!(cli.network, Some(Network::Regtest));
+        assert_eq!(cli.raito_rpc_url, "http://127.0.0.1:5000");
+
+        // No public bridge on test networks
+        assert!(Cli::try_parse_from(["raito", "--network", "signet"]).is_err());
+        let cli = Cli::parse_from(["raito", "--network", "testnet4", "--raito-rpc-url", "x"]);
+        assert_eq!(cli.raito_rpc_url, "x");
+
+        assert!(Cli::try_parse_from(["raito", "--network", "litecoin"]).is_err());
+        assert_eq!(default_min_confirmations(Network::Bitcoin), None);
+        assert_eq!(default_min_confirmations(Network::Testnet4), Some(6));
+    }
+}
@@ -0,0 +1,276 @@
+//! Publication of verified proofs to Nostr relays, so that Raito attestations can be
+//! distributed publicly without a central verifier service.
+//!
+//! The event (NIP-01) is a regular event of a kind dedicated to Raito, signed with the
+//! publisher key. Its content is the verified transaction, block height, chain height, best
+//! block hash and block MMR root in JSON, and it carries single-letter tags, which relays
+//! index, for the transaction (NIP-73) and the SHA-256 of the proof file (and its URL when it
+//! was downloaded), along with a human-readable summary (NIP-31):
+//!
+//! ```text
+//! ["t", "raito"]
+//! ["i", "bitcoin:tx:<txid>"]
+//! ["k", "bitcoin:tx"]
+//! ["x", "<SHA-256 of the proof file>"]
+//! ["r", "<proof URL>"]
+//! ["alt", "<summary>"]
+//! ```
+//!
+//! Only proofs whose chain state STARK proof was verified are published, not those verified
+//! with `--skip-stark` or in development mode.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{Keypair, Message, Secp256k1};
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use raito_spv_verifier::verify::VerificationReport;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tracing::{info, warn};
+
+use crate::attest::load_keypair;
+
+/// Kind of the published events, a regular event kind (1000-9999) dedicated to Raito
+/// verifications
+const EVENT_KIND: u32 = 8333;
+
+/// Time to wait for the relay to acknowledge the event
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// CLI arguments for publishing the verified proof to a Nostr relay
+#[derive(Clone, Debug, clap::Args)]
+pub struct NostrArgs {
+    /// URL of a Nostr relay to publish an event referencing the verified proof to
+    /// (`wss://...`)
+    #[arg(
+        long,
+        requires = "nostr_key",
+        conflicts_with_all = ["bundle", "proof_dir"]
+    )]
+    publish_nostr: Option<String>,
+    /// Path to a file containing the secp256k1 secret key (hex string) used to sign the
+    /// Nostr event
+    #[arg(long, env = "RAITO_NOSTR_KEY", requires = "publish_nostr")]
+    nostr_key: Option<PathBuf>,
+}
+
+/// Reference to the proof file in the published event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofReference {
+    /// SHA-256 of the proof file (hex string)
+    pub sha256: Option<String>,
+    /// URL the proof was downloaded from
+    pub url: Option<String>,
+}
+
+/// Signed Nostr event (NIP-01)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NostrEvent {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: i64,
+    pub kind: u32,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+impl NostrArgs {
+    /// Whether publication to a relay is requested
+    pub fn enabled(&self) -> bool {
+        self.publish_nostr.is_some()
+    }
+
+    /// Sign an event referencing the verified proof and publish it to the relay, if requested
+    ///
+    /// The verification result stands regardless of the relay, so a failed publication is
+    /// only reported as a warning. Nothing is published if the STARK proof of the chain state
+    /// was not verified.
+    pub async fn run(&self, report: &VerificationReport, proof: &ProofReference) {
+        let (Some(relay), Some(key_path)) = (&self.publish_nostr, &self.nostr_key) else {
+            return;
+        };
+        if !report.stark_verified() {
+            warn!(
+                "Not publishing the Nostr event: the chain state proof was not verified \
+                 (--skip-stark or development mode)"
+            );
+            return;
+        }
+        let res = async {
+            let keypair = load_keypair(key_path)?;
+            let event = NostrEvent::sign(
+                verification_tags(report, proof),
+                verification_content(report)?,
+                Utc::now().timestamp(),
+                &keypair,
+            )?;
+            publish(relay, &event).await?;
+            Ok::<_, anyhow::Error>(event)
+        }
+        .await;
+        match res {
+            Ok(event) => info!("Nostr event {} published to {}", event.id, relay),
+            Err(err) => warn!("Failed to publish the Nostr event to {}: {:#}", relay, err),
+        }
+    }
+}
+
+impl NostrEvent {
+    /// Sign an event with the publisher key
+    pub fn sign(
+        tags: Vec<Vec<String>>,
+        content: String,
+        created_at: i64,
+        keypair: &Keypair,
+    ) -> Result<Self, anyhow::Error> {
+        let pubkey = keypair.x_only_public_key().0.to_string();
+        let id = event_id(&pubkey, created_at, EVENT_KIND, &tags, &content)?;
+        let signature = Secp256k1::signing_only()
+            .sign_schnorr_no_aux_rand(&Message::from_digest(id.to_byte_array()), keypair);
+        Ok(Self {
+            id: id.to_string(),
+            pubkey,
+            created_at,
+            kind: EVENT_KIND,
+            tags,
+            content,
+            sig: hex::encode(signature.as_ref()),
+        })
+    }
+}
+
+/// Event ID: SHA-256 of the serialized `[0, pubkey, created_at, kind, tags, content]`
+fn event_id(
+    pubkey: &str,
+    created_at: i64,
+    kind: u32,
+    tags: &[Vec<String>],
+    content: &str,
+) -> Result<sha256::Hash, anyhow::Error> {
+    let serialized = serde_json::to_vec(&json!([0, pubkey, created_at, kind, tags, content]))?;
+    Ok(sha256::Hash::hash(&serialized))
+}
+
+/// Tags referencing the verified transaction and the proof file, with a summary of the
+/// verification for clients that don't know the event kind
+fn verification_tags(report: &VerificationReport, proof: &ProofReference) -> Vec<Vec<String>> {
+    let tag = |name: &str, value: String| vec![name.to_string(), value];
+    let mut tags = vec![
+        tag("t", "raito".to_string()),
+        tag("i", format!("bitcoin:tx:{}", report.txid)),
+        tag("k", "bitcoin:tx".to_string()),
+    ];
+    if let Some(sha256) = &proof.sha256 {
+        tags.push(tag("x", sha256.clone()));
+    }
+    if let Some(url) = &proof.url {
+        tags.push(tag("r", url.clone()));
+    }
+    tags.push(tag(
+        "alt",
+        format!(
+            "Raito: transaction {} at height {} verified against the chain state at height {} \
+             (best block {})",
+            report.txid, report.block_height, report.chain_height, report.best_block_hash
+        ),
+    ));
+    tags
+}
+
+/// Content of the event: the verified transaction and chain state in JSON
+fn verification_content(report: &VerificationReport) -> Result<String, anyhow::Error> {
+    Ok(serde_json::to_string(&json!({
+        "txid": report.txid,
+        "block_height": report.block_height,
+        "chain_height": report.chain_height,
+        "best_block_hash": report.best_block_hash,
+        "block_mmr_root": report.block_mmr_root,
+    }))?)
+}
+
+/// Send the event to the relay and wait for its acknowledgement
+async fn publish(relay: &str, event: &NostrEvent) -> Result<(), anyhow::Error> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(relay).await?;
+    let request = serde_json::to_string(&json!(["EVENT", event]))?;
+    socket.send(WsMessage::Text(request)).await?;
+
+    let ack = tokio::time::timeout(RELAY_TIMEOUT, async {
+        while let Some(message) = socket.next().await {
+            let WsMessage::Text(text) = message? else {
+                continue;
+            };
+            // ["OK", <event id>, <accepted>, <message>]
+            if let Ok((kind, id, accepted, reason)) =
+                serde_json::from_str::<(String, String, bool, String)>(&text)
+            {
+                if kind == "OK" && id == event.id {
+                    return Ok((accepted, reason));
+                }
+            }
+        }
+        anyhow::bail!("Relay closed the connection before acknowledging the event")
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Relay didn't acknowledge the event in time"))??;
+    let _ = socket.close(None).await;
+
+    match ack {
+        (true, _) => Ok(()),
+        (false, reason) => anyhow::bail!("Relay rejected the event: {}", reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{schnorr::Signature, XOnlyPublicKey};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_sign_event() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_str(
+            &secp,
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let tags = vec![vec!["txid".to_string(), "00".repeat(32)]];
+        let event = NostrEvent::sign(
+            tags,
+            "Raito \"test\"\n".to_string(),
+            1_700_000_000,
+            &keypair,
+        )
+        .unwrap();
+
+        assert_eq!(
+            event.pubkey,
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        let serialized = format!(
+            r#"[0,"{}",1700000000,8333,[["txid","{}"]],"Raito \"test\"\n"]"#,
+            event.pubkey,
+            "00".repeat(32)
+        );
+        assert_eq!(
+            event.id,
+            sha256::Hash::hash(serialized.as_bytes()).to_string()
+        );
+
+        let id = sha256::Hash::from_str(&event.id).unwrap();
+        let signature = Signature::from_str(&event.sig).unwrap();
+        let pubkey = XOnlyPublicKey::from_str(&event.pubkey).unwrap();
+        assert!(secp
+            .verify_schnorr(
+                &signature,
+                &Message::from_digest(id.to_byte_array()),
+                &pubkey
+            )
+            .is_ok());
+    }
+}
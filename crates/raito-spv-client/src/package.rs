@@ -0,0 +1,164 @@
+//! Proof archives for auditors and counterparties: a single zip file with the binary proof, the
+//! JSON verification report and human-readable renderings of the transaction.
+
+use std::io::{Seek, Write};
+use std::path::PathBuf;
+
+use bitcoin::hashes::{sha256, Hash};
+use raito_spv_verifier::verify::verify_proof;
+use tracing::info;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::convert::{load_proof, ProofFormat};
+use crate::fetch::write_bzip2;
+use crate::format::{DisplayFormat, Layout};
+use crate::verify::{format_verified_display, JsonVerificationResult, VerifierConfigArgs};
+
+/// Name of the proof in the archive
+const PROOF_FILE_NAME: &str = "proof.raito";
+/// Width of the transaction card in the text rendering
+const CARD_WIDTH: usize = 100;
+
+/// CLI arguments for the `package` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct PackageArgs {
+    /// Path to the proof to package (binary or JSON)
+    #[arg(long)]
+    proof_path: PathBuf,
+    /// Path to write the archive to (zip)
+    #[arg(long = "out")]
+    output: PathBuf,
+    #[command(flatten)]
+    config: VerifierConfigArgs,
+}
+
+/// Run the `package` subcommand: verify the proof, then write it to the archive along with
+/// the verification report and the renderings of the transaction
+pub async fn run(args: PackageArgs) -> Result<(), anyhow::Error> {
+    let (config, denomination) = args.config.resolve_with_denomination()?;
+    let format = ProofFormat::detect(&args.proof_path)?;
+    let proof = load_proof(&args.proof_path, format)?;
+
+    // The archive always holds the binary proof, as read by `verify --proof-path`
+    let proof_bytes = match format {
+        ProofFormat::Binary => std::fs::read(&args.proof_path)?,
+        ProofFormat::Json => {
+            let mut bytes = Vec::new();
+            write_bzip2(&proof.to_bytes()?, &mut bytes)?;
+            bytes
+        }
+    };
+
+    // Only valid proofs are packaged
+    let report = verify_proof(proof, &config).await?;
+    let layout = Layout::plain(CARD_WIDTH, denomination);
+    let files = [
+        (PROOF_FILE_NAME, proof_bytes),
+        (
+            "report.json",
+            serde_json::to_vec_pretty(&JsonVerificationResult::valid(&report, None))?,
+        ),
+        (
+            "transaction.html",
+            format_verified_display(&report, DisplayFormat::Html, &layout)?.into_bytes(),
+        ),
+        (
+            "transaction.txt",
+            format_verified_display(&report, DisplayFormat::Pretty, &layout)?.into_bytes(),
+        ),
+    ];
+    let readme = format!(
+        "Compressed SPV proof of transaction {} at height {}, verified against the chain state at height {}.\n\n\
+         - proof.raito: the proof, to check with `raito-spv-client verify --proof-path proof.raito`\n\
+         - report.json: the verification report\n\
+         - transaction.html, transaction.txt: renderings of the verified transaction\n\
+         - SHA256SUMS: checksums of the files above\n",
+        report.txid, report.block_height, report.chain_height
+    );
+
+    let file = std::fs::File::create(&args.output)?;
+    let prefix = format!("{}-{}", report.txid, report.block_height);
+    write_archive(file, &prefix, &files, &readme)?;
+
+    info!(
+        "Proof archive of transaction {} written to {}",
+        report.txid,
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Write the files to a zip archive under the `prefix` directory, along with the readme and
+/// their SHA-256 checksums
+fn write_archive<W: Write + Seek>(
+    writer: W,
+    prefix: &str,
+    files: &[(&str, Vec<u8>)],
+    readme: &str,
+) -> Result<(), anyhow::Error> {
+    let checksums: String = files
+        .iter()
+        .map(|(name, bytes)| format!("{}  {}\n", sha256::Hash::hash(bytes), name))
+        .collect();
+
+    let mut archive = ZipWriter::new(writer);
+    let entries = files
+        .iter()
+        .map(|(name, bytes)| (*name, bytes.as_slice()))
+        .chain([
+            ("README.txt", readme.as_bytes()),
+            ("SHA256SUMS", checksums.as_bytes()),
+        ]);
+    for (name, bytes) in entries {
+        // The proof is already compressed with bzip2
+        let method = if name == PROOF_FILE_NAME {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        archive.start_file(
+            format!("{}/{}", prefix, name),
+            SimpleFileOptions::default().compression_method(method),
+        )?;
+        archive.write_all(bytes)?;
+    }
+    archive.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_write_archive() {
+        let files = [
+            (PROOF_FILE_NAME, vec![0x42; 16]),
+            ("report.json", b"{\"valid\":true}".to_vec()),
+        ];
+        let mut buffer = Cursor::new(Vec::new());
+        write_archive(&mut buffer, "tx-100", &files, "readme").unwrap();
+
+        let mut archive = ZipArchive::new(buffer).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names.len(), 4);
+
+        let mut checksums = String::new();
+        archive
+            .by_name("tx-100/SHA256SUMS")
+            .unwrap()
+            .read_to_string(&mut checksums)
+            .unwrap();
+        assert_eq!(
+            checksums.lines().nth(1).unwrap(),
+            format!("{}  report.json", sha256::Hash::hash(b"{\"valid\":true}"))
+        );
+
+        let proof = archive.by_name("tx-100/proof.raito").unwrap();
+        assert_eq!(proof.compression(), CompressionMethod::Stored);
+        assert_eq!(proof.size(), 16);
+    }
+}
@@ -0,0 +1,344 @@
+//! Pinning of the program the verified chain state proofs were generated with, trusted on
+//! first use like an SSH host key: once a proof is verified, proofs generated by another
+//! program are rejected until the new program is explicitly accepted, unless it is a release
+//! listed in the program registry for the network.
+//!
+//! The block MMR roots of the verified chain states are recorded along with the program, and
+//! a root conflicting with the one recorded at the same chain height is rejected. Only chain
+//! states whose STARK proof was verified are pinned.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use bitcoin::Network;
+use chrono::Utc;
+use raito_spv_verifier::error::VerifyError;
+use raito_spv_verifier::verify::{CheckResult, VerificationReport};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Number of block MMR roots recorded per network, the most recent ones are kept
+const MAX_PINNED_ROOTS: usize = 1024;
+
+/// CLI arguments controlling the pinning of the chain state proof program
+#[derive(Clone, Debug, clap::Args)]
+pub struct PinArgs {
+    /// File pinning the program and recording the block MMR roots of the verified chain state
+    /// proofs (defaults to the user data directory)
+    #[arg(long, env = "RAITO_SPV_PIN_FILE")]
+    pin_file: Option<PathBuf>,
+    /// Accept a chain state proof generated by another program than the pinned one, and pin
+    /// its program instead
+    #[arg(long, default_value = "false", conflicts_with = "no_pin")]
+    accept_new_program: bool,
+    /// Neither check nor pin the program of the chain state proofs
+    #[arg(long, default_value = "false")]
+    no_pin: bool,
+}
+
+/// Pinned programs and recorded block MMR roots, by network
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pins {
+    networks: BTreeMap<String, NetworkPins>,
+}
+
+/// Pinned program and recorded block MMR roots of a network
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct NetworkPins {
+    /// Program of the verified chain state proofs, `None` until the first one
+    program: Option<PinnedProgram>,
+    /// Block MMR roots of the verified chain states by chain height
+    mmr_roots: BTreeMap<u32, String>,
+}
+
+/// Program a chain state proof was generated with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PinnedProgram {
+    bootloader_hash: String,
+    task_program_hash: String,
+    /// Name of the program release, if known
+    release: Option<String>,
+    /// When the program was pinned (UNIX seconds)
+    pinned_at: i64,
+}
+
+impl PinArgs {
+    /// Whether the program of the chain state proofs is pinned
+    pub fn enabled(&self) -> bool {
+        !self.no_pin
+    }
+
+    /// Path of the pin file, `None` if pinning is disabled or no data directory is available
+    /// on this platform
+    fn path(&self) -> Option<PathBuf> {
+        if self.no_pin {
+            return None;
+        }
+        self.pin_file
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("raito-spv-client").join("pins.json")))
+    }
+
+    /// Check the program of a verified proof against the pinned one, pinning it on first use
+    /// or if accepted, and record its block MMR root, adding the `pinned_program` check to the
+    /// report if pinning is enabled
+    ///
+    /// Chain states whose STARK proof was not verified (`--skip-stark`, development proofs)
+    /// are neither checked nor recorded, the check is reported as skipped.
+    pub fn check(&self, report: &mut VerificationReport) -> Result<(), anyhow::Error> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let pinned = self.pin(report)?;
+        report
+            .checks
+            .push(CheckResult::new("pinned_program", !pinned));
+        Ok(())
+    }
+
+    /// Same as [PinArgs::check] for every verified proof of a bundle or a directory, a proof
+    /// rejected by the pins becomes a failure
+    pub fn check_all(
+        &self,
+        results: &mut [Result<VerificationReport, VerifyError>],
+    ) -> Result<(), anyhow::Error> {
+        for res in results.iter_mut() {
+            let Ok(report) = res else {
+                continue;
+            };
+            if let Err(err) = self.check(report) {
+                // Failures to read or write the pin file fail the whole command
+                *res = Err(err.downcast::<VerifyError>()?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check and record the program and block MMR root of a verified proof, returns whether
+    /// they were pinned
+    fn pin(&self, report: &VerificationReport) -> Result<bool, anyhow::Error> {
+        if !report.stark_verified() {
+            warn!("The chain state proof was not verified, its program is not pinned");
+            return Ok(false);
+        }
+        let Some(path) = self.path() else {
+            warn!("No data directory available, the chain state proof program is not pinned");
+            return Ok(false);
+        };
+        let mut pins = Pins::load(&path)?;
+        let program = PinnedProgram {
+            bootloader_hash: report.bootloader_hash.clone(),
+            task_program_hash: report.task_program_hash.clone(),
+            release: report.program_release.clone(),
+            pinned_at: Utc::now().timestamp(),
+        };
+        pins.check_program(report.network, program, self.accept_new_program)?;
+        pins.record_root(report.network, report.chain_height, &report.block_mmr_root)?;
+        pins.save(&path)?;
+        Ok(true)
+    }
+}
+
+impl Pins {
+    /// Load the pins, none if the pin file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| anyhow::anyhow!("Invalid pin file {}: {}", path.display(), e))
+    }
+
+    /// Save the pins
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Check the program of a verified proof against the pinned one, pinning it if none is
+    /// pinned yet or if it is a release of the program registry (the verifier only reports a
+    /// release listed for the network)
+    ///
+    /// - `accept_new_program`: Pin the program of the proof if it differs from the pinned one
+    fn check_program(
+        &mut self,
+        network: Network,
+        program: PinnedProgram,
+        accept_new_program: bool,
+    ) -> Result<(), VerifyError> {
+        let pins = self.networks.entry(network.to_string()).or_default();
+        match &pins.program {
+            None => {
+                info!(
+                    "Pinning chain state proof program on first use: bootloader {}, task program {}",
+                    program.bootloader_hash, program.task_program_hash
+                );
+                pins.program = Some(program);
+            }
+            Some(pinned)
+                if pinned.bootloader_hash == program.bootloader_hash
+                    && pinned.task_program_hash == program.task_program_hash => {}
+            Some(pinned) if program.release.is_some() => {
+                info!(
+                    "Chain state proof program changed from bootloader {} and task program {} \
+                     to release {}, pinning the new release",
+                    pinned.bootloader_hash,
+                    pinned.task_program_hash,
+                    program.release.as_deref().unwrap_or_default()
+                );
+                pins.program = Some(program);
+            }
+            Some(pinned) if accept_new_program => {
+                warn!(
+                    "Chain state proof program changed from bootloader {} and task program {} \
+                     to bootloader {} and task program {}, pinning the new program",
+                    pinned.bootloader_hash,
+                    pinned.task_program_hash,
+                    program.bootloader_hash,
+                    program.task_program_hash
+                );
+                pins.program = Some(program);
+            }
+            Some(pinned) => {
+                warn!(
+                    "THE CHAIN STATE PROOF PROGRAM HAS CHANGED since {}! If the Raito program \
+                     was upgraded, check the release and verify again with \
+                     --accept-new-program",
+                    chrono::DateTime::from_timestamp(pinned.pinned_at, 0)
+                        .map_or_else(|| pinned.pinned_at.to_string(), |time| time.to_rfc3339())
+                );
+                return Err(VerifyError::UnpinnedProgram {
+                    bootloader_hash: program.bootloader_hash,
+                    task_program_hash: program.task_program_hash,
+                    pinned_bootloader_hash: pinned.bootloader_hash.clone(),
+                    pinned_task_program_hash: pinned.task_program_hash.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the block MMR root of a verified chain state, failing if it conflicts with the
+    /// root recorded at the same chain height
+    fn record_root(
+        &mut self,
+        network: Network,
+        chain_height: u32,
+        root: &str,
+    ) -> Result<(), VerifyError> {
+        let pins = self.networks.entry(network.to_string()).or_default();
+        let root = root.to_lowercase();
+        if let Some(pinned_root) = pins.mmr_roots.get(&chain_height) {
+            if *pinned_root != root {
+                warn!(
+                    "BLOCK MMR ROOT CONFLICT at chain height {}: {} was verified before, the \
+                     proof commits to {}",
+                    chain_height, pinned_root, root
+                );
+                return Err(VerifyError::PinnedRootConflict {
+                    chain_height,
+                    root,
+                    pinned_root: pinned_root.clone(),
+                });
+            }
+        }
+        pins.mmr_roots.insert(chain_height, root);
+        while pins.mmr_roots.len() > MAX_PINNED_ROOTS {
+            pins.mmr_roots.pop_first();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(bootloader_hash: &str) -> PinnedProgram {
+        PinnedProgram {
+            bootloader_hash: bootloader_hash.to_string(),
+            task_program_hash: "0x02".to_string(),
+            release: None,
+            pinned_at: 0,
+        }
+    }
+
+    fn release(bootloader_hash: &str, name: &str) -> PinnedProgram {
+        PinnedProgram {
+            release: Some(name.to_string()),
+            ..program(bootloader_hash)
+        }
+    }
+
+    #[test]
+    fn test_check_program() {
+        let mut pins = Pins::default();
+        // Trusted on first use, per network
+        pins.check_program(Network::Bitcoin, program("0x01"), false)
+            .unwrap();
+        pins.check_program(Network::Bitcoin, program("0x01"), false)
+            .unwrap();
+        pins.check_program(Network::Signet, program("0x03"), false)
+            .unwrap();
+
+        let err = pins
+            .check_program(Network::Bitcoin, program("0x03"), false)
+            .unwrap_err();
+        assert!(matches!(err, VerifyError::UnpinnedProgram { .. }));
+        pins.check_program(Network::Bitcoin, program("0x03"), true)
+            .unwrap();
+        // The previous program is no longer accepted
+        assert!(pins
+            .check_program(Network::Bitcoin, program("0x01"), false)
+            .is_err());
+
+        // Registry releases are accepted and pinned
+        pins.check_program(Network::Bitcoin, release("0x04", "v2"), false)
+            .unwrap();
+        assert_eq!(
+            pins.networks["bitcoin"].program,
+            Some(release("0x04", "v2"))
+        );
+        assert!(pins
+            .check_program(Network::Bitcoin, program("0x03"), false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_record_root() {
+        let mut pins = Pins::default();
+        pins.record_root(Network::Bitcoin, 100, "0xAB").unwrap();
+        pins.record_root(Network::Bitcoin, 100, "0xab").unwrap();
+        let err = pins.record_root(Network::Bitcoin, 100, "0xcd").unwrap_err();
+        assert!(matches!(err, VerifyError::PinnedRootConflict { .. }));
+        assert_eq!(pins.networks["bitcoin"].mmr_roots[&100], "0xab");
+        pins.record_root(Network::Signet, 100, "0xcd").unwrap();
+
+        for height in 0..MAX_PINNED_ROOTS as u32 {
+            pins.record_root(Network::Bitcoin, 1000 + height, "0xef")
+                .unwrap();
+        }
+        let roots = &pins.networks["bitcoin"].mmr_roots;
+        assert_eq!(roots.len(), MAX_PINNED_ROOTS);
+        assert!(!roots.contains_key(&100));
+    }
+
+    #[test]
+    fn test_pin_file() {
+        let path = std::env::temp_dir().join(format!("raito-pins-{}.json", std::process::id()));
+        assert_eq!(Pins::load(&path).unwrap(), Pins::default());
+
+        let mut pins = Pins::default();
+        pins.check_program(Network::Bitcoin, program("0x01"), false)
+            .unwrap();
+        pins.record_root(Network::Bitcoin, 100, "0xab").unwrap();
+        pins.save(&path).unwrap();
+        assert_eq!(Pins::load(&path).unwrap(), pins);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+//! Progress reporting for long-running downloads and proof assembly stages.
+//!
+//! Progress bars are drawn on stderr and are hidden unless explicitly enabled, so that
+//! library-like callers (the verification server, the watcher) stay quiet.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Whether progress bars are drawn
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Container of all concurrently drawn progress bars
+static MULTI_PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Enable drawing of progress bars on stderr
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn multi_progress() -> &'static MultiProgress {
+    MULTI_PROGRESS.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::stderr()))
+}
+
+fn add(bar: ProgressBar) -> ProgressBar {
+    if ENABLED.load(Ordering::Relaxed) {
+        let bar = multi_progress().add(bar);
+        bar.enable_steady_tick(Duration::from_millis(100));
+        bar
+    } else {
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+        bar
+    }
+}
+
+/// Create a progress bar for a download of `len` bytes (unknown if the response is compressed)
+pub fn download(message: &'static str, len: Option<u64>) -> ProgressBar {
+    let bar = match len {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template(
+                "{spinner} {msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta})",
+            )
+            .expect("valid progress template")
+            .progress_chars("=> "),
+        ),
+        None => ProgressBar::no_length().with_style(
+            ProgressStyle::with_template("{spinner} {msg} {bytes} ({bytes_per_sec})")
+                .expect("valid progress template"),
+        ),
+    };
+    add(bar.with_message(message))
+}
+
+/// Create a spinner for a proof assembly stage of unknown duration
+pub fn stage(message: &'static str) -> ProgressBar {
+    let bar = ProgressBar::new_spinner().with_style(
+        ProgressStyle::with_template("{spinner} {msg} ({elapsed})")
+            .expect("valid progress template"),
+    );
+    add(bar.with_message(message))
+}
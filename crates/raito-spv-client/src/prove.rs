@@ -0,0 +1,648 @@
+//! The `prove` subcommand, driving the Cairo and Stwo proving pipeline locally: the inputs of
+//! the assumevalid program are built from a local bridge node, the program is run by the
+//! bootloader and proven by the prover binaries installed with `make install`, and the
+//! resulting chain state proof is published to the bridge node.
+//!
+//! The chain state is proven in batches of blocks, each proof verifying the proof of the
+//! previous batch. The batches are kept in the work directory with the same layout as
+//! `scripts/data/prove_pow.py` (`light_<start>_to_<end>/proof.json`), so that proving resumes
+//! from the latest proven chain state.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::hashes::Hash;
+use chrono::Utc;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use num_bigint::BigUint;
+use raito_spv_core::primitives::hashes_from_hex;
+use raito_spv_core::sparse_roots::SparseRoots;
+use raito_spv_verifier::fetch::{
+    fetch_chain_state, fetch_sparse_roots, fetch_subchain_headers_from_bridge, get_mmr_height,
+    ApiEndpoint, ChainStateProof, RetryPolicy,
+};
+use raito_spv_verifier::proof::ChainState;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::StatusCode;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::progress;
+
+/// CLI arguments for the `prove` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ProveArgs {
+    /// Local Raito bridge node RPC URL, providing the chain states, block headers and block MMR
+    /// roots the program is run on, and publishing the proof
+    #[arg(long, default_value = "http://127.0.0.1:5000")]
+    raito_rpc_url: String,
+    /// Height of the chain state to prove from, defaults to the latest chain state proven in
+    /// the work directory (genesis if none)
+    #[arg(long)]
+    start: Option<u32>,
+    /// Height of the chain state to prove, defaults to the latest block indexed by the bridge
+    /// node
+    #[arg(long)]
+    end: Option<u32>,
+    /// Maximum number of blocks proven in a batch
+    #[arg(long, default_value = "1000", value_parser = clap::value_parser!(u32).range(1..))]
+    step: u32,
+    /// Directory of the batches: their inputs, proofs and prover logs
+    #[arg(long, default_value = "./proving")]
+    work_dir: PathBuf,
+    /// Compiled assumevalid program (`make assumevalid-build`)
+    #[arg(long, default_value = "target/proving/assumevalid.executable.json")]
+    executable: PathBuf,
+    /// Compiled simple bootloader running the program
+    #[arg(long, default_value = "bootloaders/simple_bootloader_compiled.json")]
+    bootloader: PathBuf,
+    /// Parameters of the Stwo prover
+    #[arg(long, default_value = "packages/assumevalid/prover_params.json")]
+    prover_params: PathBuf,
+    /// Token of the chain state proof ingestion endpoint of the bridge node (see its
+    /// `--ingest-token`); the proof is only written to the work directory if not set
+    #[arg(long, env = "RAITO_INGEST_TOKEN")]
+    ingest_token: Option<String>,
+}
+
+/// Run the `prove` subcommand: prove the chain state up to the end height and publish the
+/// proof to the bridge node
+pub async fn run(args: ProveArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let end = match args.end {
+        Some(end) => end,
+        None => get_mmr_height(&raito_rpc).await?,
+    };
+    let start = match args.start {
+        Some(start) => start,
+        None => latest_proven_height(&args.work_dir)?.unwrap_or(0),
+    };
+    if start >= end {
+        info!("Chain state at height {} is already proven", end);
+        return Ok(());
+    }
+    let files = ProgramFiles::resolve(
+        &args.executable,
+        "build it with `make assumevalid-build`",
+        &args.bootloader,
+        &args.prover_params,
+    )?;
+
+    info!(
+        "Proving the chain state from height {} to {}, {} blocks per batch",
+        start, end, args.step
+    );
+    let mut height = start;
+    let mut batch_dir = PathBuf::new();
+    while height < end {
+        let step = args.step.min(end - height);
+        batch_dir = prove_batch(&args, &raito_rpc, &files, height, height + step).await?;
+        height += step;
+    }
+
+    let proof = assemble_recent_proof(&raito_rpc, &batch_dir, end).await?;
+    let proof_path = batch_dir.join("recent_proof.json.gz");
+    std::fs::write(&proof_path, &proof)?;
+    info!("Chain state proof written to {}", proof_path.display());
+
+    match &args.ingest_token {
+        Some(ingest_token) => {
+            let progress = progress::stage("Publishing chain state proof");
+            publish(&args.raito_rpc_url, ingest_token, proof).await?;
+            progress.finish_with_message("Published chain state proof");
+            info!(
+                "Published the chain state proof at height {} to {}",
+                end, args.raito_rpc_url
+            );
+        }
+        None => info!("No ingestion token, the chain state proof is not published"),
+    }
+    Ok(())
+}
+
+/// Absolute paths of the programs and parameters, as the prover binaries run in the batch
+/// directories
+pub struct ProgramFiles {
+    executable: PathBuf,
+    bootloader: PathBuf,
+    prover_params: PathBuf,
+}
+
+impl ProgramFiles {
+    /// Resolve the paths of the programs and parameters
+    ///
+    /// - `build_hint`: How to build the executable, reported if it is missing
+    pub fn resolve(
+        executable: &Path,
+        build_hint: &str,
+        bootloader: &Path,
+        prover_params: &Path,
+    ) -> Result<Self, anyhow::Error> {
+        let resolve = |path: &Path, hint: &str| {
+            std::fs::canonicalize(path)
+                .map_err(|err| anyhow::anyhow!("{}: {} ({})", path.display(), err, hint))
+        };
+        Ok(Self {
+            executable: resolve(executable, build_hint)?,
+            bootloader: resolve(bootloader, "run from the repository root")?,
+            prover_params: resolve(prover_params, "run from the repository root")?,
+        })
+    }
+}
+
+/// Prove the blocks on top of the chain state at height `start` up to height `end`, on top of
+/// the proof of the previous batch, and return the directory of the batch
+async fn prove_batch(
+    args: &ProveArgs,
+    raito_rpc: &ApiEndpoint,
+    files: &ProgramFiles,
+    start: u32,
+    end: u32,
+) -> Result<PathBuf, anyhow::Error> {
+    let batch_dir = args.work_dir.join(batch_name(start, end));
+    std::fs::create_dir_all(&batch_dir)?;
+    info!("Proving blocks {}..={}", start + 1, end);
+
+    // The genesis chain state is proven by the program itself
+    let prev_proof = match start {
+        0 => None,
+        _ => {
+            let path = find_proof(&args.work_dir, start)?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No proof of the chain state at height {} in {}, prove from an earlier height",
+                    start,
+                    args.work_dir.display()
+                )
+            })?;
+            let proof: Vec<String> = serde_json::from_slice(&std::fs::read(&path)?)?;
+            Some(proof)
+        }
+    };
+
+    let progress = progress::stage("Fetching prover inputs");
+    let chain_state = fetch_chain_state(raito_rpc, start).await?;
+    let headers = fetch_subchain_headers_from_bridge(start, end, raito_rpc).await?;
+    let roots = fetch_sparse_roots(raito_rpc, start).await?;
+    progress.finish_with_message("Fetched prover inputs");
+
+    let arguments = encode_arguments(&chain_state, &headers, &roots, prev_proof.as_deref())?;
+    run_program(&batch_dir, files, &arguments).await?;
+    Ok(batch_dir)
+}
+
+/// Run the program on its arguments with the bootloader and prove its execution, the proof
+/// being written to `proof.json` in the Cairo serde format, along with the logs of each step
+pub async fn run_program(
+    dir: &Path,
+    files: &ProgramFiles,
+    arguments: &[String],
+) -> Result<(), anyhow::Error> {
+    let arguments_path = dir.join("arguments.json");
+    std::fs::write(&arguments_path, serde_json::to_vec(arguments)?)?;
+    let program_input_path = dir.join("program-input.json");
+    let program_input = serde_json::json!({
+        "single_page": true,
+        "tasks": [{
+            "type": "Cairo1Executable",
+            "path": files.executable,
+            "program_hash_function": "blake",
+            "user_args_file": std::fs::canonicalize(&arguments_path)?,
+        }],
+    });
+    std::fs::write(
+        &program_input_path,
+        serde_json::to_vec_pretty(&program_input)?,
+    )?;
+
+    let (pub_json, priv_json) = (dir.join("pub.json"), dir.join("priv.json"));
+    let (trace_file, memory_file) = (dir.join("trace.json"), dir.join("memory.json"));
+    run_step(
+        dir,
+        "CAIRO_RUNNER",
+        "Running the program",
+        Command::new("cairo_program_runner")
+            .arg("--program")
+            .arg(&files.bootloader)
+            .arg("--program_input")
+            .arg(&program_input_path)
+            .arg("--air_public_input")
+            .arg(&pub_json)
+            .arg("--air_private_input")
+            .arg(&priv_json)
+            .arg("--trace_file")
+            .arg(std::path::absolute(&trace_file)?)
+            .arg("--memory_file")
+            .arg(std::path::absolute(&memory_file)?)
+            .args(["--layout", "all_cairo_stwo", "--proof_mode"])
+            .arg("--execution_resources_file")
+            .arg(dir.join("resources.json"))
+            .args(["--disable_trace_padding", "--merge_extra_segments"]),
+    )
+    .await?;
+    run_step(
+        dir,
+        "PROVE",
+        "Proving",
+        Command::new("adapted_stwo")
+            .arg("--priv_json")
+            .arg(&priv_json)
+            .arg("--pub_json")
+            .arg(&pub_json)
+            .arg("--params_json")
+            .arg(&files.prover_params)
+            .arg("--proof_path")
+            .arg(dir.join("proof.json"))
+            .args(["--proof-format", "cairo-serde", "--verify"]),
+    )
+    .await?;
+
+    // The execution trace is by far the largest file, and only needed to prove
+    for path in [program_input_path, priv_json, trace_file, memory_file] {
+        if let Err(err) = std::fs::remove_file(&path) {
+            debug!("Failed to clean up {}: {}", path.display(), err);
+        }
+    }
+    Ok(())
+}
+
+/// Convert the proof of the last batch to the JSON format served to clients, along with the
+/// chain state it proves, and return it gzip-compressed
+async fn assemble_recent_proof(
+    raito_rpc: &ApiEndpoint,
+    batch_dir: &Path,
+    chain_height: u32,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let proof = convert_proof(batch_dir).await?;
+    let chain_state = fetch_chain_state(raito_rpc, chain_height).await?;
+    let recent_proof = serde_json::to_vec(&serde_json::json!({
+        "timestamp": Utc::now().to_rfc3339(),
+        "chainstate": chain_state,
+        "proof": proof,
+    }))?;
+    // Checked the way clients decode it, before it is published
+    serde_json::from_slice::<ChainStateProof>(&recent_proof)
+        .map_err(|err| anyhow::anyhow!("Converted proof is not a chain state proof: {}", err))?;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&recent_proof)?;
+    Ok(encoder.finish()?)
+}
+
+/// Convert the proof written by [run_program] to the JSON format verified by the Rust verifier
+pub async fn convert_proof(dir: &Path) -> Result<serde_json::Value, anyhow::Error> {
+    let json_proof_path = dir.join("proof_json.json");
+    run_step(
+        dir,
+        "CONVERT",
+        "Converting the proof",
+        Command::new("convert_proof_format")
+            .arg("--input")
+            .arg(dir.join("proof.json"))
+            .arg("--output")
+            .arg(&json_proof_path)
+            .args(["--hash", "blake2s"]),
+    )
+    .await?;
+    Ok(serde_json::from_slice(&std::fs::read(&json_proof_path)?)?)
+}
+
+/// Publish a gzip-compressed chain state proof to the ingestion endpoint of the bridge node
+async fn publish(
+    raito_rpc_url: &str,
+    ingest_token: &str,
+    proof: Vec<u8>,
+) -> Result<(), anyhow::Error> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/chainstate-proof", raito_rpc_url))
+        .bearer_auth(ingest_token)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_ENCODING, "gzip")
+        .body(proof)
+        .send()
+        .await?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        StatusCode::CONFLICT => {
+            anyhow::bail!("Bridge node already publishes a chain state proof at least as recent")
+        }
+        StatusCode::UNPROCESSABLE_ENTITY => {
+            anyhow::bail!("Bridge node rejected the chain state, its Bitcoin node disagrees")
+        }
+        status => anyhow::bail!("Bridge node rejected the chain state proof: {}", status),
+    }
+}
+
+/// Run a step of the proving pipeline, writing its output to `<step>.log` in the directory
+async fn run_step(
+    dir: &Path,
+    step: &str,
+    message: &'static str,
+    command: &mut Command,
+) -> Result<(), anyhow::Error> {
+    debug!("[{}] command: {:?}", step, command.as_std());
+    let program = command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+    let progress = progress::stage(message);
+    let start = Instant::now();
+    let output = command.output().await.map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => anyhow::anyhow!(
+            "{} not found, install the proving toolchain with `make install`",
+            program
+        ),
+        _ => err.into(),
+    })?;
+    let elapsed = start.elapsed().as_secs_f64();
+    progress.finish_and_clear();
+
+    let log_path = dir.join(format!("{}.log", step.to_lowercase()));
+    let (stdout, stderr) = (
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    std::fs::write(
+        &log_path,
+        format!(
+            "=== {} STEP LOG ===\nTimestamp: {}\nStatus: {}\nExecution Time: {:.2} seconds\n\
+             === STDOUT ===\n{}\n=== STDERR ===\n{}\n",
+            step,
+            Utc::now().to_rfc3339(),
+            output.status,
+            elapsed,
+            stdout,
+            stderr
+        ),
+    )?;
+    if !output.status.success() {
+        let reason = stderr
+            .lines()
+            .chain(stdout.lines())
+            .last()
+            .unwrap_or_default();
+        anyhow::bail!(
+            "[{}] failed with {}, see {}: {}",
+            step,
+            output.status,
+            log_path.display(),
+            reason
+        );
+    }
+    info!("[{}] done in {:.2}s", step, elapsed);
+    Ok(())
+}
+
+/// Name of the directory of the batch proving the chain state at `end` from the one at `start`
+fn batch_name(start: u32, end: u32) -> String {
+    format!("light_{}_to_{}", start, end)
+}
+
+/// Proven batches of the work directory, as their start and end heights and directory
+fn proven_batches(work_dir: &Path) -> Result<Vec<(u32, u32, PathBuf)>, anyhow::Error> {
+    let entries = match std::fs::read_dir(work_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut batches = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some((start, end)) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("light_"))
+            .and_then(|name| name.split_once("_to_"))
+            .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+        else {
+            continue;
+        };
+        if path.join("proof.json").is_file() {
+            batches.push((start, end, path));
+        }
+    }
+    Ok(batches)
+}
+
+/// Height of the latest chain state proven in the work directory, if any
+fn latest_proven_height(work_dir: &Path) -> Result<Option<u32>, anyhow::Error> {
+    Ok(proven_batches(work_dir)?
+        .into_iter()
+        .map(|(_, end, _)| end)
+        .max())
+}
+
+/// Proof of the chain state at `height` in the work directory, if any
+pub fn find_proof(work_dir: &Path, height: u32) -> Result<Option<PathBuf>, anyhow::Error> {
+    Ok(proven_batches(work_dir)?
+        .into_iter()
+        .find(|(_, end, _)| *end == height)
+        .map(|(_, _, dir)| dir.join("proof.json")))
+}
+
+/// Arguments of the assumevalid program in the Cairo serde format, as hex felts: the chain
+/// state, the headers of the blocks on top of it, the block MMR, and the proof of the chain
+/// state unless it is the genesis one (see `scripts/data/format_assumevalid_args.py`)
+fn encode_arguments(
+    chain_state: &ChainState,
+    headers: &[BlockHeader],
+    roots: &SparseRoots,
+    prev_proof: Option<&[String]>,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut felts = encode_chain_state(chain_state)?;
+
+    felts.push(headers.len() as u128);
+    for header in headers {
+        felts.push(u128::from(header.version.to_consensus() as u32));
+        felts.push(header.time.into());
+        felts.push(header.bits.to_consensus().into());
+        felts.push(header.nonce.into());
+        // Transaction data variant with the Merkle root only
+        felts.push(0);
+        felts.extend(encode_digest(header.merkle_root.to_byte_array()));
+    }
+
+    felts.extend(encode_block_mmr(roots)?);
+
+    let mut arguments = to_hex_felts(&felts);
+    match prev_proof {
+        Some(proof) => {
+            arguments.push("0x0".to_string());
+            arguments.extend_from_slice(proof);
+        }
+        None => arguments.push("0x1".to_string()),
+    }
+    Ok(arguments)
+}
+
+/// Chain state in the Cairo serde format
+pub fn encode_chain_state(chain_state: &ChainState) -> Result<Vec<u128>, anyhow::Error> {
+    let mut felts: Vec<u128> = vec![chain_state.block_height.into()];
+    felts.extend(encode_u256(&chain_state.total_work)?);
+    felts.extend(encode_digest(chain_state.best_block_hash.to_byte_array()));
+    felts.extend(encode_u256(&chain_state.current_target)?);
+    felts.push(chain_state.epoch_start_time.into());
+    felts.push(chain_state.prev_timestamps.len() as u128);
+    felts.extend(
+        chain_state
+            .prev_timestamps
+            .iter()
+            .map(|time| u128::from(*time)),
+    );
+    Ok(felts)
+}
+
+/// Block MMR in the Cairo serde format: its roots as the high and low parts of a u256, zero
+/// for the missing ones
+pub fn encode_block_mmr(roots: &SparseRoots) -> Result<Vec<u128>, anyhow::Error> {
+    let mut felts = vec![roots.roots.len() as u128];
+    for root in hashes_from_hex(&roots.roots)? {
+        let (high, low) = root.split_at(16);
+        felts.push(u128::from_be_bytes(high.try_into()?));
+        felts.push(u128::from_be_bytes(low.try_into()?));
+    }
+    Ok(felts)
+}
+
+/// Hex strings of felts, the format of the program arguments
+pub fn to_hex_felts(felts: &[u128]) -> Vec<String> {
+    felts.iter().map(|felt| format!("{:#x}", felt)).collect()
+}
+
+/// Low and high parts of a u256 given as a decimal string
+fn encode_u256(decimal: &str) -> Result<[u128; 2], anyhow::Error> {
+    let value: BigUint = decimal
+        .parse()
+        .map_err(|err| anyhow::anyhow!("Invalid u256 {}: {}", decimal, err))?;
+    let digits = value.to_u64_digits();
+    if digits.len() > 4 {
+        anyhow::bail!("Invalid u256 {}: more than 256 bits", decimal);
+    }
+    let digit = |i: usize| u128::from(digits.get(i).copied().unwrap_or_default());
+    Ok([digit(0) | digit(1) << 64, digit(2) | digit(3) << 64])
+}
+
+/// Big-endian u32 words of a hash in its internal byte order, the digests of the Cairo program
+pub fn encode_digest(hash: [u8; 32]) -> [u128; 8] {
+    let mut words = [0; 8];
+    for (word, chunk) in words.iter_mut().zip(hash.chunks_exact(4)) {
+        *word = u32::from_be_bytes(chunk.try_into().expect("4-byte chunk")).into();
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus;
+
+    #[test]
+    fn test_encode_arguments() {
+        // Block 1 on top of the genesis chain state, encoded by `format_args.py` the same way
+        let chain_state = ChainState {
+            block_height: 0,
+            total_work: "4295032833".to_string(),
+            best_block_hash: "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+                .parse()
+                .unwrap(),
+            current_target: "26959535291011309493156476344723991336010898738574164086137773096960"
+                .to_string(),
+            epoch_start_time: 1231006505,
+            prev_timestamps: vec![1231006505],
+        };
+        let header: BlockHeader = consensus::encode::deserialize_hex(
+            "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4b\
+             a744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299",
+        )
+        .unwrap();
+        let roots = SparseRoots {
+            block_height: 0,
+            roots: vec![
+                format!("0x{}{}", "a1".repeat(16), "b2".repeat(16)),
+                format!("0x{}", "00".repeat(32)),
+            ],
+            block: None,
+        };
+        let expected = [
+            "0x0",
+            "0x100010001",
+            "0x0",
+            "0x6fe28c0a",
+            "0xb6f1b372",
+            "0xc1a6a246",
+            "0xae63f74f",
+            "0x931e8365",
+            "0xe15a089c",
+            "0x68d61900",
+            "0x0",
+            "0x0",
+            "0xffff00000000000000000000",
+            "0x495fab29",
+            "0x1",
+            "0x495fab29",
+            "0x1",
+            "0x1",
+            "0x4966bc61",
+            "0x1d00ffff",
+            "0x9962e301",
+            "0x0",
+            "0x982051fd",
+            "0x1e4ba744",
+            "0xbbbe680e",
+            "0x1fee1467",
+            "0x7ba1a3c3",
+            "0x540bf7b1",
+            "0xcdb606e8",
+            "0x57233e0e",
+            "0x2",
+            "0xa1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1",
+            "0xb2b2b2b2b2b2b2b2b2b2b2b2b2b2b2b2",
+            "0x0",
+            "0x0",
+        ];
+
+        let arguments = encode_arguments(&chain_state, &[header], &roots, None).unwrap();
+        assert_eq!(arguments[..expected.len()], expected);
+        assert_eq!(arguments[expected.len()..], ["0x1"]);
+
+        let proof = ["0x5".to_string(), "0x6".to_string()];
+        let arguments = encode_arguments(&chain_state, &[header], &roots, Some(&proof)).unwrap();
+        assert_eq!(arguments[expected.len()..], ["0x0", "0x5", "0x6"]);
+    }
+
+    #[test]
+    fn test_encode_u256() {
+        assert_eq!(encode_u256("0").unwrap(), [0, 0]);
+        assert_eq!(
+            encode_u256(&(BigUint::from(3u8) << 128u32 | BigUint::from(7u8)).to_string()).unwrap(),
+            [7, 3]
+        );
+        assert!(encode_u256(&(BigUint::from(1u8) << 256u32).to_string()).is_err());
+        assert!(encode_u256("0x10").is_err());
+    }
+
+    #[test]
+    fn test_proven_batches() {
+        let work_dir = std::env::temp_dir().join(format!("raito-prove-{}", std::process::id()));
+        assert_eq!(latest_proven_height(&work_dir).unwrap(), None);
+        for (start, end, proven) in [(0, 10, true), (10, 20, true), (20, 30, false)] {
+            let dir = work_dir.join(batch_name(start, end));
+            std::fs::create_dir_all(&dir).unwrap();
+            if proven {
+                std::fs::write(dir.join("proof.json"), "[]").unwrap();
+            }
+        }
+        std::fs::create_dir_all(work_dir.join("other")).unwrap();
+
+        assert_eq!(latest_proven_height(&work_dir).unwrap(), Some(20));
+        assert_eq!(
+            find_proof(&work_dir, 10).unwrap(),
+            Some(work_dir.join("light_0_to_10").join("proof.json"))
+        );
+        assert_eq!(find_proof(&work_dir, 30).unwrap(), None);
+        std::fs::remove_dir_all(&work_dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! The `prove-dev` subcommand, assembling compressed SPV proofs from a local bridge node with a
+//! development stub in place of the chain state proof, so that integrators can test their
+//! verification pipelines before a prover is available.
+
+use std::path::PathBuf;
+
+use bitcoin::Txid;
+use raito_spv_verifier::fetch::{
+    fetch_dev_compressed_proof, get_mmr_height, ApiEndpoint, RetryPolicy,
+};
+use raito_spv_verifier::proof::CompressedSpvProof;
+use raito_spv_verifier::verify::{verify_proof, VerifierConfig};
+use tracing::{info, warn};
+
+use crate::{
+    fetch::save_compressed_proof_with_bzip2,
+    progress,
+    verify::{print_verified_transaction, VerifierConfigArgs},
+};
+
+/// CLI arguments for the `prove-dev` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ProveDevArgs {
+    /// Transaction ID
+    #[arg(long)]
+    txid: Txid,
+    /// Local Raito bridge node RPC URL, serving the transaction proofs and the chain states
+    /// of the Bitcoin node it indexes
+    #[arg(long, default_value = "http://127.0.0.1:5000")]
+    raito_rpc_url: String,
+    /// Height of the chain state claimed by the development proof, defaults to the latest
+    /// block indexed by the bridge node
+    #[arg(long)]
+    chain_height: Option<u32>,
+    /// Path to save the proof (`-` for stdout); defaults to
+    /// `<proof-dir>/<txid>-<height>.dev.raito`
+    #[arg(long)]
+    proof_path: Option<PathBuf>,
+    /// Directory to save the proofs to when no proof path is given
+    #[arg(long, default_value = "./proofs")]
+    proof_dir: PathBuf,
+    /// Verify the proof after assembling it, accepting the development stub
+    #[arg(long, default_value = "false")]
+    verify: bool,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+}
+
+/// Run the `prove-dev` subcommand: assemble a development proof and write it to disk
+pub async fn run(args: ProveDevArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let (mut config, denomination) = args.verifier_config.resolve_with_denomination()?;
+    config.allow_dev_proofs = true;
+    warn!("DEV PROOF: the chain state proof is a development stub, the proof proves nothing");
+
+    let chain_height = match args.chain_height {
+        Some(chain_height) => chain_height,
+        None => get_mmr_height(&raito_rpc).await?,
+    };
+    let (bootloader_hash, task_program_hash) = claimed_program(&config, chain_height)?;
+
+    let progress = progress::stage("Assembling development proof");
+    let proof = fetch_dev_compressed_proof(
+        args.txid,
+        &raito_rpc,
+        Some(chain_height),
+        &bootloader_hash,
+        &task_program_hash,
+    )
+    .await?;
+    progress.finish_with_message("Assembled development proof");
+
+    let proof_path = args
+        .proof_path
+        .clone()
+        .unwrap_or_else(|| args.proof_dir.join(dev_proof_file_name(&proof)));
+    save_compressed_proof_with_bzip2(&proof, &proof_path)?;
+    info!("Development proof written to {}", proof_path.display());
+    if args.proof_path.is_none() {
+        println!("{}", proof_path.display());
+    }
+
+    if args.verify {
+        let report = verify_proof(proof, &config).await?;
+        print_verified_transaction(&report, denomination);
+    }
+    Ok(())
+}
+
+/// Bootloader and task program hashes claimed by the development proof: those of the release
+/// active at the chain height if the verifier accepts a registry of releases, the pinned ones
+/// otherwise, so that the proof passes the program checks
+fn claimed_program(
+    config: &VerifierConfig,
+    chain_height: u32,
+) -> Result<(String, String), anyhow::Error> {
+    let Some(registry) = &config.programs else {
+        return Ok((
+            config.bootloader_hash.clone(),
+            config.task_program_hash.clone(),
+        ));
+    };
+    let release = registry
+        .releases
+        .iter()
+        .filter(|release| release.activation_height <= chain_height)
+        .max_by_key(|release| release.activation_height)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No program release is active at chain height {}",
+                chain_height
+            )
+        })?;
+    Ok((
+        release.bootloader_hash.clone(),
+        release.task_program_hash.clone(),
+    ))
+}
+
+/// Default file name of a development proof, marked as such
+fn dev_proof_file_name(proof: &CompressedSpvProof) -> String {
+    format!(
+        "{}-{}.dev.raito",
+        proof.transaction.compute_txid(),
+        proof.block_height
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raito_spv_verifier::registry::ProgramRegistry;
+
+    #[test]
+    fn test_claimed_program() {
+        let registry = ProgramRegistry::parse(
+            r#"
+            [[release]]
+            name = "v1"
+            bootloader_hash = "0x01"
+            task_program_hash = "0x02"
+            task_output_size = 8
+            activation_height = 100
+
+            [[release]]
+            name = "v2"
+            bootloader_hash = "0x03"
+            task_program_hash = "0x04"
+            task_output_size = 8
+            activation_height = 200
+            "#,
+        )
+        .unwrap();
+        let mut config = VerifierConfig {
+            programs: Some(registry),
+            ..Default::default()
+        };
+        assert!(claimed_program(&config, 99).is_err());
+        assert_eq!(
+            claimed_program(&config, 199).unwrap(),
+            ("0x01".to_string(), "0x02".to_string())
+        );
+        assert_eq!(claimed_program(&config, 200).unwrap().0, "0x03");
+
+        config.programs = None;
+        assert_eq!(
+            claimed_program(&config, 0).unwrap().0,
+            config.bootloader_hash
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Check that a proof covers a transaction signed by a wallet, given as a finalized PSBT or as
+//! the extracted transaction, bridging wallet signing flows with SPV proof verification.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::{Address, Amount, Network, Psbt, Transaction, TxOut, Txid, Wtxid};
+use raito_spv_verifier::error::failure_class;
+use raito_spv_verifier::verify::verify_proof;
+use raito_spv_verifier::{VerificationReport, VerifyError};
+use serde::Serialize;
+use tracing::info;
+
+use crate::convert::{load_proof, ProofFormat};
+use crate::fetch::parse_raw_transaction;
+use crate::format::{format_amount, Denomination};
+use crate::verify::{JsonVerificationResult, OutputFormat, VerifierConfigArgs};
+
+/// Magic bytes of a binary PSBT
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// CLI arguments for the `verify-psbt` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct VerifyPsbtArgs {
+    /// Path to the proof to check (binary or JSON)
+    #[arg(long)]
+    proof_path: PathBuf,
+    /// Finalized PSBT (base64) or extracted transaction (hex)
+    #[arg(
+        long,
+        required_unless_present = "psbt_file",
+        conflicts_with = "psbt_file"
+    )]
+    psbt: Option<String>,
+    /// File with the finalized PSBT (binary or base64) or the extracted transaction (binary
+    /// or hex)
+    #[arg(long)]
+    psbt_file: Option<PathBuf>,
+    /// Format of the result
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+    #[command(flatten)]
+    config: VerifierConfigArgs,
+}
+
+/// Whether an output of the signed transaction is in the proven transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputCoverage {
+    /// Index of the output in the signed transaction
+    pub index: usize,
+    /// Value of the output in satoshis
+    pub value: u64,
+    /// Address paid by the output, or its script in hex if it has no address
+    pub destination: String,
+    /// Whether the proven transaction has the same output at the same index
+    pub covered: bool,
+}
+
+/// Comparison of the signed transaction with the proven transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct PsbtCoverage {
+    /// ID of the signed transaction
+    pub txid: Txid,
+    /// Witness ID of the signed transaction
+    pub wtxid: Wtxid,
+    /// Whether the proven transaction has the same ID
+    pub txid_matches: bool,
+    /// Whether the proven transaction has the same witness ID, i.e. the same witnesses
+    pub wtxid_matches: bool,
+    /// Coverage of every output of the signed transaction
+    pub outputs: Vec<OutputCoverage>,
+}
+
+impl PsbtCoverage {
+    /// Compare the signed transaction with the proven transaction
+    pub fn new(signed: &Transaction, proven: &Transaction, network: Network) -> Self {
+        let outputs = signed
+            .output
+            .iter()
+            .enumerate()
+            .map(|(index, output)| OutputCoverage {
+                index,
+                value: output.value.to_sat(),
+                destination: output_destination(output, network),
+                covered: proven.output.get(index) == Some(output),
+            })
+            .collect();
+        Self {
+            txid: signed.compute_txid(),
+            wtxid: signed.compute_wtxid(),
+            txid_matches: signed.compute_txid() == proven.compute_txid(),
+            wtxid_matches: signed.compute_wtxid() == proven.compute_wtxid(),
+            outputs,
+        }
+    }
+
+    /// Fail unless the proof covers this exact transaction, witnesses included
+    pub fn check(&self, proven_txid: &Txid) -> Result<(), anyhow::Error> {
+        if !self.txid_matches {
+            let covered = self.outputs.iter().filter(|output| output.covered).count();
+            anyhow::bail!(
+                "Proof covers transaction {}, not the signed transaction {} ({} of {} outputs in common)",
+                proven_txid,
+                self.txid,
+                covered,
+                self.outputs.len()
+            );
+        }
+        if !self.wtxid_matches {
+            anyhow::bail!(
+                "Proof covers transaction {} with different witnesses than the signed transaction (wtxid {})",
+                proven_txid,
+                self.wtxid
+            );
+        }
+        Ok(())
+    }
+}
+
+/// JSON result of the `verify-psbt` subcommand
+#[derive(Debug, Serialize)]
+struct JsonPsbtResult<'a> {
+    #[serde(flatten)]
+    verification: JsonVerificationResult<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coverage: Option<&'a PsbtCoverage>,
+}
+
+/// Run the `verify-psbt` subcommand: verify the proof, then check that it covers the signed
+/// transaction
+pub async fn run(args: VerifyPsbtArgs) -> Result<(), anyhow::Error> {
+    let (config, denomination) = args.config.resolve_with_denomination()?;
+    let signed = match (&args.psbt, &args.psbt_file) {
+        (Some(psbt), _) => parse_signed_transaction(psbt.as_bytes())?,
+        (None, Some(psbt_file)) => parse_signed_transaction(&std::fs::read(psbt_file)?)?,
+        (None, None) => anyhow::bail!("Either a PSBT or a PSBT file is required"),
+    };
+    let proof = load_proof(&args.proof_path, ProofFormat::detect(&args.proof_path)?)?;
+
+    let (res, coverage) = match verify_proof(proof, &config).await {
+        Ok(report) => {
+            let coverage = PsbtCoverage::new(&signed, &report.transaction, config.network);
+            let res = coverage
+                .check(&report.txid)
+                .map(|()| report)
+                .map_err(|e| VerifyError::Assertion(e).into());
+            (res, Some(coverage))
+        }
+        Err(err) => (Err(anyhow::Error::from(err)), None),
+    };
+
+    match args.output {
+        OutputFormat::Text => {
+            if let Some(coverage) = &coverage {
+                print!("{}", format_coverage(coverage, denomination));
+            }
+            let report = res?;
+            info!(
+                "Proof covers the signed transaction {} at height {}",
+                report.txid, report.block_height
+            );
+            println!("{}", format_proven_line(&report));
+        }
+        OutputFormat::Json => {
+            let verification = match &res {
+                Ok(report) => JsonVerificationResult::valid(report, None),
+                Err(err) => {
+                    JsonVerificationResult::invalid(format!("{:#}", err), failure_class(err))
+                }
+            };
+            let json = JsonPsbtResult {
+                verification,
+                coverage: coverage.as_ref(),
+            };
+            println!("{}", serde_json::to_string_pretty(&json)?);
+            res?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse the signed transaction from a finalized PSBT (binary or base64) or an extracted
+/// transaction (binary or hex)
+pub fn parse_signed_transaction(bytes: &[u8]) -> Result<Transaction, anyhow::Error> {
+    let psbt = if bytes.starts_with(PSBT_MAGIC) {
+        Some(Psbt::deserialize(bytes).map_err(|e| anyhow::anyhow!("Invalid PSBT: {}", e))?)
+    } else {
+        match std::str::from_utf8(bytes).map(str::trim) {
+            // Base64 encoding of the magic bytes
+            Ok(text) if text.starts_with("cHNidP8") => {
+                Some(Psbt::from_str(text).map_err(|e| anyhow::anyhow!("Invalid PSBT: {}", e))?)
+            }
+            _ => None,
+        }
+    };
+    let Some(psbt) = psbt else {
+        return parse_raw_transaction(bytes);
+    };
+
+    // Without the final scripts, the extracted transaction is not the broadcast one
+    if let Some(index) = psbt
+        .inputs
+        .iter()
+        .position(|input| input.final_script_sig.is_none() && input.final_script_witness.is_none())
+    {
+        anyhow::bail!("PSBT is not finalized, input {} has no final script", index);
+    }
+    Ok(psbt.extract_tx_unchecked_fee_rate())
+}
+
+/// Address paid by an output, or its script in hex if it has no address
+fn output_destination(output: &TxOut, network: Network) -> String {
+    match Address::from_script(&output.script_pubkey, network) {
+        Ok(address) => address.to_string(),
+        Err(_) => format!("script {}", output.script_pubkey.to_hex_string()),
+    }
+}
+
+/// Format the coverage of the signed transaction outputs
+fn format_coverage(coverage: &PsbtCoverage, denomination: Denomination) -> String {
+    let mut text = format!(
+        "Signed transaction {}\n  txid   {}\n  wtxid  {}\n",
+        coverage.txid,
+        if coverage.txid_matches {
+            "matches"
+        } else {
+            "differs"
+        },
+        if coverage.wtxid_matches {
+            "matches"
+        } else {
+            "differs"
+        },
+    );
+    for output in &coverage.outputs {
+        text.push_str(&format!(
+            "  #{:<4} {:>20}  {}  {}\n",
+            output.index,
+            format_amount(Amount::from_sat(output.value), denomination),
+            if output.covered {
+                "covered"
+            } else {
+                "NOT COVERED"
+            },
+            output.destination,
+        ));
+    }
+    text
+}
+
+/// One-line summary of where the proven transaction is
+fn format_proven_line(report: &VerificationReport) -> String {
+    format!(
+        "Proven in block {} at height {} ({} confirmations)",
+        report.block_hash, report.block_height, report.confirmations
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::consensus;
+    use bitcoin::psbt::Input;
+    use bitcoin::{ScriptBuf, Witness};
+
+    /// Transaction with a single segwit input, and its witness
+    fn signed_transaction() -> Transaction {
+        let mut transaction = genesis_block(Network::Bitcoin).txdata[0].clone();
+        transaction.input[0].script_sig = ScriptBuf::new();
+        transaction.input[0].witness = Witness::from_slice(&[[1u8; 64]]);
+        transaction
+    }
+
+    #[test]
+    fn test_parse_signed_transaction() {
+        let transaction = signed_transaction();
+        let hex_transaction = consensus::encode::serialize_hex(&transaction);
+        assert_eq!(
+            parse_signed_transaction(hex_transaction.as_bytes()).unwrap(),
+            transaction
+        );
+
+        let mut unsigned = transaction.clone();
+        unsigned.input[0].witness = Witness::new();
+        let mut psbt = Psbt::from_unsigned_tx(unsigned).unwrap();
+        assert!(parse_signed_transaction(&psbt.serialize()).is_err());
+
+        psbt.inputs[0] = Input {
+            final_script_witness: Some(transaction.input[0].witness.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_signed_transaction(&psbt.serialize()).unwrap(),
+            transaction
+        );
+        assert_eq!(
+            parse_signed_transaction(format!(" {}\n", psbt).as_bytes()).unwrap(),
+            transaction
+        );
+    }
+
+    #[test]
+    fn test_psbt_coverage() {
+        let signed = signed_transaction();
+        let coverage = PsbtCoverage::new(&signed, &signed, Network::Bitcoin);
+        assert!(coverage.check(&signed.compute_txid()).is_ok());
+        assert!(coverage.outputs.iter().all(|output| output.covered));
+
+        let mut malleated = signed.clone();
+        malleated.input[0].witness = Witness::from_slice(&[[2u8; 64]]);
+        let coverage = PsbtCoverage::new(&signed, &malleated, Network::Bitcoin);
+        assert!(coverage.txid_matches && !coverage.wtxid_matches);
+        assert!(coverage.check(&malleated.compute_txid()).is_err());
+
+        let mut other = signed.clone();
+        other.output[0].value = Amount::from_sat(1);
+        let coverage = PsbtCoverage::new(&signed, &other, Network::Bitcoin);
+        assert!(!coverage.txid_matches && !coverage.outputs[0].covered);
+        assert!(coverage.check(&other.compute_txid()).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! Local HTTP API exposing proof verification, so that backends written in other languages
+//! can verify SPV proofs by calling a sidecar instead of shelling out to the CLI.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use bitcoin::Txid;
+use raito_spv_verifier::fetch::{ApiEndpoint, BitcoinDataSource, RetryPolicy};
+use raito_spv_verifier::proof::CompressedSpvProof;
+use raito_spv_verifier::verify::{decode_proof, verify_proof, VerificationReport, VerifierConfig};
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+use crate::fetch::{fetch_compressed_proof, pruned_fallback};
+use crate::network::{MAINNET_RAITO_RPC_URL, RAITO_RPC_URLS, RAITO_RPC_URL_REQUIRED};
+use crate::verify::VerifierConfigArgs;
+
+/// CLI arguments for the `serve` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ServeArgs {
+    /// Host and port to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+    /// Raito node RPC URL (used when verifying by txid), defaults to the Raito bridge of the
+    /// `--network`; required on `testnet4` and `signet`, which have no public bridge
+    #[arg(
+        long,
+        env = "RAITO_BRIDGE_RPC",
+        default_value = MAINNET_RAITO_RPC_URL,
+        default_value_ifs = RAITO_RPC_URLS,
+        required_if_eq_any = RAITO_RPC_URL_REQUIRED
+    )]
+    raito_rpc_url: String,
+    /// Bitcoin RPC URL (required for verifying by txid)
+    #[arg(long, env = "BITCOIN_RPC")]
+    bitcoin_rpc_url: Option<String>,
+    /// Bitcoin RPC user:password (optional)
+    #[arg(long, env = "USERPWD")]
+    bitcoin_rpc_userpwd: Option<String>,
+    /// Maximum size of an uploaded proof in bytes
+    #[arg(long, default_value = "134217728")]
+    max_proof_size: usize,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+    /// Development mode
+    #[arg(long, default_value = "false")]
+    dev: bool,
+}
+
+/// Shared state of the verification server
+#[derive(Clone)]
+struct ServeState {
+    args: Arc<ServeArgs>,
+    raito_rpc: Arc<ApiEndpoint>,
+    config: Arc<VerifierConfig>,
+}
+
+/// Response body of the verification endpoints
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum VerifyResponse {
+    /// The proof is valid
+    Valid {
+        /// Summary of the verified proof
+        report: Box<VerificationReport>,
+    },
+    /// The proof could not be obtained, decoded, or verified
+    Invalid {
+        /// Reason of the failure
+        error: String,
+    },
+}
+
+type VerifyResult = (StatusCode, Json<VerifyResponse>);
+
+/// Run the `serve` subcommand: start the verification HTTP server and run until Ctrl-C
+pub async fn run(args: ServeArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let listen = args.listen.clone();
+    let max_proof_size = args.max_proof_size;
+    let mut config = args.verifier_config.resolve()?;
+    if args.dev {
+        config.enable_dev_mode();
+    }
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let state = ServeState {
+        args: Arc::new(args),
+        raito_rpc: Arc::new(raito_rpc),
+        config: Arc::new(config),
+    };
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/verify", post(verify_upload))
+        .route("/verify/:txid", post(verify_txid))
+        .layer(DefaultBodyLimit::max(max_proof_size))
+        .with_state(state);
+
+    info!("Starting verification server on {}", listen);
+    let listener = TcpListener::bind(&listen).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    info!("Verification server terminated");
+    Ok(())
+}
+
+/// Liveness check
+async fn health() -> &'static str {
+    "ok"
+}
+
+/// Verify an uploaded proof, either in binary (bzip2) or JSON representation
+///
+/// # Returns
+/// * `200 OK` - The proof is valid, the body contains the verification report
+/// * `400 Bad Request` - The proof could not be decoded
+/// * `422 Unprocessable Entity` - The proof is invalid
+async fn verify_upload(State(state): State<ServeState>, body: Bytes) -> VerifyResult {
+    let proof = match decode_proof(&body) {
+        Ok(proof) => proof,
+        Err(err) => return invalid(StatusCode::BAD_REQUEST, err.into()),
+    };
+    verify(state, proof).await
+}
+
+/// Fetch a proof for the given transaction and verify it
+///
+/// # Returns
+/// * `200 OK` - The proof is valid, the body contains the verification report
+/// * `502 Bad Gateway` - The proof could not be fetched
+/// * `422 Unprocessable Entity` - The proof is invalid
+/// * `503 Service Unavailable` - No Bitcoin RPC is configured
+async fn verify_txid(State(state): State<ServeState>, Path(txid): Path<Txid>) -> VerifyResult {
+    let Some(bitcoin_rpc_url) = state.args.bitcoin_rpc_url.clone() else {
+        return invalid(
+            StatusCode::SERVICE_UNAVAILABLE,
+            anyhow::anyhow!("Bitcoin RPC is not configured"),
+        );
+    };
+    let proof = match fetch_compressed_proof(
+        txid,
+        BitcoinDataSource::Node {
+            url: bitcoin_rpc_url,
+            userpwd: state.args.bitcoin_rpc_userpwd.clone(),
+            pruned_fallback: Some(pruned_fallback(None, &state.raito_rpc)),
+        },
+        &state.raito_rpc,
+        None,
+        state.args.dev,
+    )
+    .await
+    {
+        Ok(proof) => proof,
+        Err(err) => return invalid(StatusCode::BAD_GATEWAY, err),
+    };
+    verify(state, proof).await
+}
+
+/// Verify a proof on the blocking thread pool, since STARK verification is CPU-bound
+async fn verify(state: ServeState, proof: CompressedSpvProof) -> VerifyResult {
+    let handle = tokio::runtime::Handle::current();
+    let res =
+        tokio::task::spawn_blocking(move || handle.block_on(verify_proof(proof, &state.config)))
+            .await;
+
+    match res {
+        Ok(Ok(report)) => {
+            info!("Proof for {} is valid", report.txid);
+            (
+                StatusCode::OK,
+                Json(VerifyResponse::Valid {
+                    report: Box::new(report),
+                }),
+            )
+        }
+        Ok(Err(err)) => invalid(StatusCode::UNPROCESSABLE_ENTITY, err.into()),
+        Err(err) => {
+            error!("Verification task failed: {}", err);
+            invalid(StatusCode::INTERNAL_SERVER_ERROR, err.into())
+        }
+    }
+}
+
+fn invalid(status: StatusCode, err: anyhow::Error) -> VerifyResult {
+    info!("Verification request failed: {}", err);
+    (
+        status,
+        Json(VerifyResponse::Invalid {
+            error: err.to_string(),
+        }),
+    )
+}
@@ -0,0 +1,528 @@
+//! Local database of verified proofs, so that operators have an auditable history of what was
+//! verified, when and under which policy, instead of loose proof files.
+//!
+//! Proofs verified by `verify`, `watch` and `monitor` are recorded with `--record`; the history
+//! is queried with the `list` and `show` subcommands.
+
+use std::path::{Path, PathBuf};
+
+use bitcoin::{Address, TxOut, Txid};
+use chrono::Utc;
+use raito_spv_verifier::verify::{VerificationReport, VerifierConfig};
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use tracing::info;
+
+use crate::verify::OutputFormat;
+
+/// Schema of the database, created on first use
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS verified_proofs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    txid TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    block_hash TEXT NOT NULL,
+    chain_height INTEGER NOT NULL,
+    amount INTEGER,
+    address TEXT,
+    verified_at INTEGER NOT NULL,
+    policy TEXT NOT NULL,
+    source TEXT,
+    report TEXT NOT NULL
+)";
+/// Index for the lookups by transaction
+const TXID_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS verified_proofs_txid ON verified_proofs (txid)";
+
+/// CLI arguments locating the database
+#[derive(Clone, Debug, clap::Args)]
+pub struct DbArgs {
+    /// Path to the database of verified proofs (defaults to the user data directory)
+    #[arg(long, env = "RAITO_SPV_DB")]
+    db: Option<PathBuf>,
+}
+
+/// CLI arguments controlling the recording of verified proofs
+#[derive(Clone, Debug, clap::Args)]
+pub struct RecordArgs {
+    #[command(flatten)]
+    db: DbArgs,
+    /// Record the verified proofs in the database
+    #[arg(long, default_value = "false")]
+    record: bool,
+}
+
+/// CLI arguments for the `list` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ListArgs {
+    /// Only list the proofs of this transaction
+    #[arg(long)]
+    txid: Option<Txid>,
+    /// Only list the proofs of payments to this address
+    #[arg(long)]
+    address: Option<String>,
+    /// Only list the proofs verified since this date (RFC 3339) or Unix timestamp
+    #[arg(long)]
+    since: Option<String>,
+    /// Maximum number of proofs listed, most recent first
+    #[arg(long, default_value = "50")]
+    limit: u32,
+    /// Format of the list
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+    #[command(flatten)]
+    db: DbArgs,
+}
+
+/// CLI arguments for the `show` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ShowArgs {
+    /// Record ID, or transaction ID to show every record of the transaction
+    id: String,
+    /// Format of the records
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+    #[command(flatten)]
+    db: DbArgs,
+}
+
+/// Record of a verified proof
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct ProofRecord {
+    /// Record ID, assigned by the database
+    pub id: i64,
+    /// ID of the verified transaction
+    pub txid: String,
+    /// Height of the block containing the transaction
+    pub block_height: i64,
+    /// Hash of the block containing the transaction
+    pub block_hash: String,
+    /// Height of the best block in the proven chain state
+    pub chain_height: i64,
+    /// Value in satoshis of the output of the expected payment or deposit, if any
+    pub amount: Option<i64>,
+    /// Address of the expected payment or deposit, if any
+    pub address: Option<String>,
+    /// Unix timestamp of the verification
+    pub verified_at: i64,
+    /// Verification policies and checks applied (JSON)
+    pub policy: String,
+    /// Path or URL the proof was read from, or saved to
+    pub source: Option<String>,
+    /// Full verification report (JSON)
+    pub report: String,
+}
+
+impl ProofRecord {
+    /// Record of a verification report
+    ///
+    /// - `payment`: Output of the expected payment or deposit, if any
+    /// - `source`: Path or URL the proof was read from, or saved to
+    pub fn new(
+        report: &VerificationReport,
+        config: &VerifierConfig,
+        payment: Option<&TxOut>,
+        source: Option<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let amount = payment.map(|output| output.value.to_sat() as i64);
+        let address = payment
+            .and_then(|output| Address::from_script(&output.script_pubkey, config.network).ok())
+            .map(|address| address.to_string());
+        let policy = serde_json::json!({
+            "network": config.network,
+            "min_work": config.min_work,
+            "min_confirmations": config.min_confirmations,
+            "max_proof_age": config.max_proof_age,
+            "max_block_age": config.max_block_age,
+            "program_release": report.program_release,
+            "checks": report.checks,
+        });
+        Ok(Self {
+            id: 0,
+            txid: report.txid.to_string(),
+            block_height: report.block_height.into(),
+            block_hash: report.block_hash.to_string(),
+            chain_height: report.chain_height.into(),
+            amount,
+            address,
+            verified_at: Utc::now().timestamp(),
+            policy: policy.to_string(),
+            source,
+            report: serde_json::to_string(report)?,
+        })
+    }
+}
+
+/// Database of verified proofs
+#[derive(Clone, Debug)]
+pub struct ProofStore {
+    pool: SqlitePool,
+}
+
+impl DbArgs {
+    /// Path of the database, `None` if no data directory is available on this platform
+    pub fn path(&self) -> Option<PathBuf> {
+        self.db
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("raito-spv-client").join("proofs.db")))
+    }
+
+    /// Open the database for querying
+    pub async fn open(&self) -> Result<ProofStore, anyhow::Error> {
+        let path = self
+            .path()
+            .ok_or_else(|| anyhow::anyhow!("No data directory available, set --db"))?;
+        ProofStore::open(&path).await
+    }
+}
+
+impl RecordArgs {
+    /// Open the database for recording, `None` if recording is disabled
+    pub async fn open(&self) -> Result<Option<ProofStore>, anyhow::Error> {
+        if !self.record {
+            return Ok(None);
+        }
+        Ok(Some(self.db.open().await?))
+    }
+}
+
+impl ProofStore {
+    /// Open the database, creating it if it doesn't exist
+    pub async fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to open database {}: {}", path.display(), e))?;
+        sqlx::query(SCHEMA).execute(&pool).await?;
+        sqlx::query(TXID_INDEX).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// Record a verified proof, returns the ID of the record
+    pub async fn insert(&self, record: &ProofRecord) -> Result<i64, anyhow::Error> {
+        let result = sqlx::query(
+            "INSERT INTO verified_proofs (txid, block_height, block_hash, chain_height, amount, \
+             address, verified_at, policy, source, report) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&record.txid)
+        .bind(record.block_height)
+        .bind(&record.block_hash)
+        .bind(record.chain_height)
+        .bind(record.amount)
+        .bind(&record.address)
+        .bind(record.verified_at)
+        .bind(&record.policy)
+        .bind(&record.source)
+        .bind(&record.report)
+        .execute(&self.pool)
+        .await?;
+        let id = result.last_insert_rowid();
+        info!("Verified proof of {} recorded as #{}", record.txid, id);
+        Ok(id)
+    }
+
+    /// Records matching the filters, most recent first
+    ///
+    /// - `since`: Minimum Unix timestamp of the verification
+    pub async fn list(
+        &self,
+        txid: Option<&str>,
+        address: Option<&str>,
+        since: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<ProofRecord>, anyhow::Error> {
+        Ok(sqlx::query_as(
+            "SELECT * FROM verified_proofs \
+             WHERE (? IS NULL OR txid = ?) AND (? IS NULL OR address = ?) \
+             AND (? IS NULL OR verified_at >= ?) \
+             ORDER BY id DESC LIMIT ?",
+        )
+        .bind(txid)
+        .bind(txid)
+        .bind(address)
+        .bind(address)
+        .bind(since)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Record with the given ID
+    pub async fn get(&self, id: i64) -> Result<Option<ProofRecord>, anyhow::Error> {
+        Ok(sqlx::query_as("SELECT * FROM verified_proofs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+}
+
+/// Record a verified proof if recording is enabled, a failure to record fails the command so
+/// that the requested history has no gaps
+pub async fn record(
+    store: Option<&ProofStore>,
+    report: &VerificationReport,
+    config: &VerifierConfig,
+    payment: Option<&TxOut>,
+    source: Option<String>,
+) -> Result<(), anyhow::Error> {
+    if let Some(store) = store {
+        store
+            .insert(&ProofRecord::new(report, config, payment, source)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to record verified proof: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Record as printed by `list --output json`, without the full report
+#[derive(Debug, Serialize)]
+struct JsonListEntry<'a> {
+    id: i64,
+    txid: &'a str,
+    block_height: i64,
+    chain_height: i64,
+    amount: Option<i64>,
+    address: Option<&'a str>,
+    verified_at: i64,
+    policy: serde_json::Value,
+    source: Option<&'a str>,
+}
+
+/// Record as printed by `show --output json`
+#[derive(Debug, Serialize)]
+struct JsonRecord<'a> {
+    #[serde(flatten)]
+    entry: JsonListEntry<'a>,
+    block_hash: &'a str,
+    report: serde_json::Value,
+}
+
+impl<'a> JsonListEntry<'a> {
+    fn new(record: &'a ProofRecord) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            id: record.id,
+            txid: &record.txid,
+            block_height: record.block_height,
+            chain_height: record.chain_height,
+            amount: record.amount,
+            address: record.address.as_deref(),
+            verified_at: record.verified_at,
+            policy: serde_json::from_str(&record.policy)?,
+            source: record.source.as_deref(),
+        })
+    }
+}
+
+/// Run the `list` subcommand: print the recorded proofs matching the filters
+pub async fn list(args: ListArgs) -> Result<(), anyhow::Error> {
+    let since = args.since.as_deref().map(parse_since).transpose()?;
+    let store = args.db.open().await?;
+    let txid = args.txid.map(|txid| txid.to_string());
+    let records = store
+        .list(txid.as_deref(), args.address.as_deref(), since, args.limit)
+        .await?;
+
+    match args.output {
+        OutputFormat::Text => {
+            if records.is_empty() {
+                eprintln!("No verified proofs recorded");
+            }
+            for record in &records {
+                println!("{}", format_list_line(record));
+            }
+        }
+        OutputFormat::Json => {
+            let json = records
+                .iter()
+                .map(JsonListEntry::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+    }
+    Ok(())
+}
+
+/// Run the `show` subcommand: print the records with the given ID or transaction ID
+pub async fn show(args: ShowArgs) -> Result<(), anyhow::Error> {
+    let store = args.db.open().await?;
+    let records = match args.id.parse::<i64>() {
+        Ok(id) => store.get(id).await?.into_iter().collect(),
+        Err(_) => {
+            let txid: Txid = args
+                .id
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid record or transaction ID: {}", e))?;
+            store
+                .list(Some(&txid.to_string()), None, None, u32::MAX)
+                .await?
+        }
+    };
+    if records.is_empty() {
+        anyhow::bail!("No verified proof recorded for {}", args.id);
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            for record in &records {
+                println!("{}", format_record(record)?);
+            }
+        }
+        OutputFormat::Json => {
+            let json = records
+                .iter()
+                .map(|record| {
+                    Ok(JsonRecord {
+                        entry: JsonListEntry::new(record)?,
+                        block_hash: &record.block_hash,
+                        report: serde_json::from_str(&record.report)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a date as RFC 3339 or a Unix timestamp
+fn parse_since(since: &str) -> Result<i64, anyhow::Error> {
+    if let Ok(timestamp) = since.parse::<i64>() {
+        return Ok(timestamp);
+    }
+    chrono::DateTime::parse_from_rfc3339(since)
+        .map(|date| date.timestamp())
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid date {} (use RFC 3339 or a Unix timestamp): {}",
+                since,
+                e
+            )
+        })
+}
+
+/// Format a Unix timestamp as RFC 3339
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map_or_else(|| timestamp.to_string(), |time| time.to_rfc3339())
+}
+
+/// Amount of a record in satoshis, `-` if the record has no expected payment
+fn format_amount(amount: Option<i64>) -> String {
+    amount.map_or_else(|| "-".to_string(), |amount| format!("{} sat", amount))
+}
+
+/// One line summary of a record
+fn format_list_line(record: &ProofRecord) -> String {
+    format!(
+        "#{:<5} {}  {}  height {:>7}  {:>20}  {}",
+        record.id,
+        format_time(record.verified_at),
+        record.txid,
+        record.block_height,
+        format_amount(record.amount),
+        record.address.as_deref().unwrap_or("-")
+    )
+}
+
+/// Detailed view of a record
+fn format_record(record: &ProofRecord) -> Result<String, anyhow::Error> {
+    let policy: serde_json::Value = serde_json::from_str(&record.policy)?;
+    Ok(format!(
+        "Record #{}\n  Transaction:   {}\n  Block:         {} (height {})\n  Chain height:  {}\n  Amount:        {}\n  Address:       {}\n  Verified at:   {}\n  Source:        {}\n  Policy:        {}\n",
+        record.id,
+        record.txid,
+        record.block_hash,
+        record.block_height,
+        record.chain_height,
+        format_amount(record.amount),
+        record.address.as_deref().unwrap_or("-"),
+        format_time(record.verified_at),
+        record.source.as_deref().unwrap_or("-"),
+        serde_json::to_string_pretty(&policy)?.replace('\n', "\n  ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(txid: &str, address: Option<&str>, verified_at: i64) -> ProofRecord {
+        ProofRecord {
+            id: 0,
+            txid: txid.to_string(),
+            block_height: 100,
+            block_hash: "00".repeat(32),
+            chain_height: 110,
+            amount: Some(5_000),
+            address: address.map(str::to_string),
+            verified_at,
+            policy: r#"{"network":"bitcoin"}"#.to_string(),
+            source: Some("proof.raito".to_string()),
+            report: "{}".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proof_store() {
+        let path = std::env::temp_dir().join(format!("raito-store-{}.db", std::process::id()));
+        let store = ProofStore::open(&path).await.unwrap();
+        let first = store
+            .insert(&record("aa", Some("bc1q"), 1_000))
+            .await
+            .unwrap();
+        // No expected payment: no amount
+        let unpaid = ProofRecord {
+            amount: None,
+            ..record("bb", None, 2_000)
+        };
+        let unpaid_id = store.insert(&unpaid).await.unwrap();
+        store.insert(&record("aa", None, 3_000)).await.unwrap();
+
+        let all = store.list(None, None, None, 10).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].verified_at, 3_000);
+        assert_eq!(
+            store.list(Some("aa"), None, None, 10).await.unwrap().len(),
+            2
+        );
+        assert_eq!(
+            store
+                .list(None, Some("bc1q"), None, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            store.list(None, None, Some(2_000), 10).await.unwrap().len(),
+            2
+        );
+        assert_eq!(store.list(None, None, None, 1).await.unwrap().len(), 1);
+
+        let mut expected = record("aa", Some("bc1q"), 1_000);
+        expected.id = first;
+        assert_eq!(store.get(first).await.unwrap(), Some(expected));
+        assert_eq!(store.get(first + 10).await.unwrap(), None);
+        let unpaid = store.get(unpaid_id).await.unwrap().unwrap();
+        assert_eq!(unpaid.amount, None);
+        assert!(format_record(&unpaid)
+            .unwrap()
+            .contains("Amount:        -\n"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("1700000000").unwrap(), 1_700_000_000);
+        assert_eq!(parse_since("2023-11-14T22:13:20Z").unwrap(), 1_700_000_000);
+        assert!(parse_since("yesterday").is_err());
+    }
+}
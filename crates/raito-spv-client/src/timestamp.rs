@@ -0,0 +1,370 @@
+//! Export of a compact timestamp attestation, so that data committed to Bitcoin via an
+//! `OP_RETURN` output can be timestamped with a Raito proof and embedded by timestamping tools.
+//!
+//! The attestation links the transaction to the chain state proven by Raito: transaction
+//! Merkle path, block header, block MMR path and the chain state commitment claimed by the
+//! recursive proof. It uses the Bitcoin consensus encoding (little-endian integers,
+//! `CompactSize` lengths):
+//!
+//! ```text
+//! magic: [u8; 7]                  // "RAITOTS"
+//! version: u8                     // 1
+//! transaction: Transaction        // consensus encoding without witness
+//! tx_index: u32
+//! merkle_branch: Vec<[u8; 32]>    // siblings from the leaf up to the Merkle root
+//! block_header: [u8; 80]
+//! block_height: u32
+//! mmr_leaf_count: u64
+//! mmr_siblings: Vec<[u8; 32]>     // big-endian Blake2s digests
+//! mmr_peaks: Vec<[u8; 32]>
+//! chain_height: u32
+//! best_block_hash: [u8; 32]
+//! chain_state_hash: [u8; 32]
+//! block_mmr_hash: [u8; 32]
+//! ```
+
+use std::path::PathBuf;
+
+use bitcoin::consensus::encode::{self, VarInt};
+use bitcoin::consensus::{Decodable, Encodable};
+use bitcoin::io::{Read, Write};
+use bitcoin::merkle_tree::PartialMerkleTree;
+use bitcoin::{block::Header as BlockHeader, BlockHash, Transaction, TxMerkleNode};
+use raito_spv_core::block_mmr::{BlockInclusionProof, BlockPeaks};
+use raito_spv_verifier::proof::{BootloaderOutput, CompressedSpvProof};
+use tracing::{info, warn};
+
+use crate::calldata::{merkle_branch, merkle_root_from_branch, strip_witness};
+use crate::convert::{load_proof, ProofFormat};
+
+/// Magic bytes of the timestamp attestation format
+const MAGIC: &[u8; 7] = b"RAITOTS";
+/// Version of the timestamp attestation format
+const FORMAT_VERSION: u8 = 1;
+
+/// CLI arguments for the `export-timestamp` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ExportTimestampArgs {
+    /// Path to read the proof from (binary or JSON)
+    #[arg(long)]
+    proof_path: PathBuf,
+    /// Path to write the timestamp attestation (binary) to
+    #[arg(long)]
+    output: PathBuf,
+}
+
+/// Compact attestation that a transaction is included in the chain state proven by Raito
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampAttestation {
+    /// Transaction committing to the timestamped data, without witness
+    pub transaction: Transaction,
+    /// Position of the transaction in the block
+    pub tx_index: u32,
+    /// Merkle branch of the transaction, from the leaf up to the root
+    pub merkle_branch: Vec<TxMerkleNode>,
+    /// Header of the block containing the transaction
+    pub block_header: BlockHeader,
+    /// Height of the block containing the transaction
+    pub block_height: u32,
+    /// Number of blocks in the block MMR
+    pub mmr_leaf_count: u64,
+    /// Siblings of the block in the block MMR
+    pub mmr_siblings: Vec<[u8; 32]>,
+    /// Peaks of the block MMR
+    pub mmr_peaks: Vec<[u8; 32]>,
+    /// Height of the best block in the proven chain state
+    pub chain_height: u32,
+    /// Hash of the best block in the proven chain state
+    pub best_block_hash: BlockHash,
+    /// Chain state hash committed by the recursive proof
+    pub chain_state_hash: [u8; 32],
+    /// Block MMR root committed by the recursive proof
+    pub block_mmr_hash: [u8; 32],
+}
+
+/// Run the `export-timestamp` subcommand: extract a timestamp attestation from a proof
+pub async fn run(args: ExportTimestampArgs) -> Result<(), anyhow::Error> {
+    let proof = load_proof(&args.proof_path, ProofFormat::detect(&args.proof_path)?)?;
+    let attestation = TimestampAttestation::from_proof(&proof)?;
+    attestation.check()?;
+
+    let payloads = attestation.op_return_payloads();
+    if payloads.is_empty() {
+        warn!(
+            "Transaction {} has no OP_RETURN output, no data is timestamped",
+            attestation.transaction.compute_txid()
+        );
+    }
+    for payload in payloads {
+        info!("Timestamped data: {}", hex::encode(payload));
+    }
+
+    std::fs::write(&args.output, encode::serialize(&attestation))?;
+    info!(
+        "Timestamp attestation for block {} written to {}",
+        attestation.block_height,
+        args.output.display()
+    );
+    Ok(())
+}
+
+impl TimestampAttestation {
+    /// Build the attestation from a compressed SPV proof, taking the chain state commitment
+    /// from the public output of the recursive proof.
+    ///
+    /// This does not verify the recursive proof, run `verify` on the proof beforehand.
+    pub fn from_proof(proof: &CompressedSpvProof) -> Result<Self, anyhow::Error> {
+        let mut transaction = proof.transaction.clone();
+        strip_witness(&mut transaction);
+
+        let pmt: PartialMerkleTree = encode::deserialize(&proof.transaction_proof)
+            .map_err(|e| anyhow::anyhow!("Failed to decode transaction proof: {}", e))?;
+        let (tx_index, merkle_branch) = merkle_branch(&pmt, transaction.compute_txid())?;
+
+        let (_, bootloader_output) = BootloaderOutput::from_proof(&proof.chain_state_proof)?;
+        let task_result = bootloader_output.task_result;
+
+        let mmr_proof = &proof.block_header_proof;
+        Ok(Self {
+            transaction,
+            tx_index,
+            merkle_branch,
+            block_header: proof.block_header,
+            block_height: proof.block_height,
+            mmr_leaf_count: mmr_proof.leaf_count as u64,
+            mmr_siblings: decode_digests(&mmr_proof.siblings_hashes)?,
+            mmr_peaks: decode_digests(&mmr_proof.peaks_hashes)?,
+            chain_height: proof.chain_state.block_height,
+            best_block_hash: proof.chain_state.best_block_hash,
+            chain_state_hash: decode_digest(&task_result.chain_state_hash)?,
+            block_mmr_hash: decode_digest(&task_result.block_mmr_hash)?,
+        })
+    }
+
+    /// Check that the transaction is linked to the block MMR root committed by the chain
+    /// state: Merkle path, proof of work, MMR inclusion and MMR root.
+    pub fn check(&self) -> Result<(), anyhow::Error> {
+        let merkle_root = merkle_root_from_branch(
+            self.transaction.compute_txid(),
+            self.tx_index,
+            &self.merkle_branch,
+        );
+        if merkle_root != self.block_header.merkle_root {
+            anyhow::bail!("Transaction Merkle path doesn't match the block header");
+        }
+        self.block_header
+            .validate_pow(self.block_header.target())
+            .map_err(|e| anyhow::anyhow!("Invalid block proof of work: {}", e))?;
+        if self.block_height as u64 >= self.mmr_leaf_count {
+            anyhow::bail!(
+                "Block height {} is beyond the MMR leaf count {}",
+                self.block_height,
+                self.mmr_leaf_count
+            );
+        }
+
+        let mmr_proof = BlockInclusionProof {
+            peaks_hashes: self.mmr_peaks.iter().map(encode_digest).collect(),
+            siblings_hashes: self.mmr_siblings.iter().map(encode_digest).collect(),
+            leaf_index: self.block_height as usize,
+            leaf_count: self.mmr_leaf_count as usize,
+        };
+        let peaks = BlockPeaks::from_proof(&mmr_proof)?;
+        if !peaks.verify_block_inclusion(&self.block_header, &mmr_proof)? {
+            anyhow::bail!("Block header is not included in the MMR");
+        }
+        let mmr_root = peaks.root_hash()?;
+        if mmr_root != encode_digest(&self.block_mmr_hash) {
+            anyhow::bail!("Block MMR root doesn't match the chain state commitment");
+        }
+        Ok(())
+    }
+
+    /// Payloads of the `OP_RETURN` outputs of the transaction
+    pub fn op_return_payloads(&self) -> Vec<Vec<u8>> {
+        self.transaction
+            .output
+            .iter()
+            .filter(|output| output.script_pubkey.is_op_return())
+            .map(|output| {
+                output
+                    .script_pubkey
+                    .instructions()
+                    .filter_map(|instruction| {
+                        instruction
+                            .ok()?
+                            .push_bytes()
+                            .map(|b| b.as_bytes().to_vec())
+                    })
+                    .flatten()
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Encodable for TimestampAttestation {
+    fn consensus_encode<W: Write + ?Sized>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, bitcoin::io::Error> {
+        let mut len = 0;
+        writer.write_all(MAGIC)?;
+        len += MAGIC.len();
+        len += FORMAT_VERSION.consensus_encode(writer)?;
+        len += self.transaction.consensus_encode(writer)?;
+        len += self.tx_index.consensus_encode(writer)?;
+        len += self.merkle_branch.consensus_encode(writer)?;
+        len += self.block_header.consensus_encode(writer)?;
+        len += self.block_height.consensus_encode(writer)?;
+        len += self.mmr_leaf_count.consensus_encode(writer)?;
+        len += encode_digests(&self.mmr_siblings, writer)?;
+        len += encode_digests(&self.mmr_peaks, writer)?;
+        len += self.chain_height.consensus_encode(writer)?;
+        len += self.best_block_hash.consensus_encode(writer)?;
+        len += self.chain_state_hash.consensus_encode(writer)?;
+        len += self.block_mmr_hash.consensus_encode(writer)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for TimestampAttestation {
+    fn consensus_decode_from_finite_reader<R: Read + ?Sized>(
+        reader: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let mut magic = [0u8; 7];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(encode::Error::ParseFailed(
+                "not a Raito timestamp attestation",
+            ));
+        }
+        if u8::consensus_decode_from_finite_reader(reader)? != FORMAT_VERSION {
+            return Err(encode::Error::ParseFailed(
+                "unsupported timestamp attestation version",
+            ));
+        }
+        Ok(Self {
+            transaction: Decodable::consensus_decode_from_finite_reader(reader)?,
+            tx_index: Decodable::consensus_decode_from_finite_reader(reader)?,
+            merkle_branch: Decodable::consensus_decode_from_finite_reader(reader)?,
+            block_header: Decodable::consensus_decode_from_finite_reader(reader)?,
+            block_height: Decodable::consensus_decode_from_finite_reader(reader)?,
+            mmr_leaf_count: Decodable::consensus_decode_from_finite_reader(reader)?,
+            mmr_siblings: decode_digest_vec(reader)?,
+            mmr_peaks: decode_digest_vec(reader)?,
+            chain_height: Decodable::consensus_decode_from_finite_reader(reader)?,
+            best_block_hash: Decodable::consensus_decode_from_finite_reader(reader)?,
+            chain_state_hash: Decodable::consensus_decode_from_finite_reader(reader)?,
+            block_mmr_hash: Decodable::consensus_decode_from_finite_reader(reader)?,
+        })
+    }
+}
+
+/// Decode a hex digest (`0x`-prefixed `u256`) into big-endian bytes
+fn decode_digest(digest: &str) -> Result<[u8; 32], anyhow::Error> {
+    let bytes = hex::decode(digest.strip_prefix("0x").unwrap_or(digest))
+        .map_err(|e| anyhow::anyhow!("Invalid digest {}: {}", digest, e))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid digest {}: expected 32 bytes", digest))
+}
+
+fn decode_digests(digests: &[String]) -> Result<Vec<[u8; 32]>, anyhow::Error> {
+    digests.iter().map(|digest| decode_digest(digest)).collect()
+}
+
+/// Encode big-endian digest bytes as a hex digest
+fn encode_digest(digest: &[u8; 32]) -> String {
+    format!("0x{}", hex::encode(digest))
+}
+
+fn encode_digests<W: Write + ?Sized>(
+    digests: &[[u8; 32]],
+    writer: &mut W,
+) -> Result<usize, bitcoin::io::Error> {
+    let mut len = VarInt(digests.len() as u64).consensus_encode(writer)?;
+    for digest in digests {
+        len += digest.consensus_encode(writer)?;
+    }
+    Ok(len)
+}
+
+fn decode_digest_vec<R: Read + ?Sized>(reader: &mut R) -> Result<Vec<[u8; 32]>, encode::Error> {
+    let count = VarInt::consensus_decode_from_finite_reader(reader)?.0;
+    // MMR paths are logarithmic in the number of blocks, reject absurd lengths early
+    if count > 64 {
+        return Err(encode::Error::ParseFailed("too many MMR digests"));
+    }
+    (0..count)
+        .map(|_| Decodable::consensus_decode_from_finite_reader(reader))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, TxOut};
+
+    #[test]
+    fn test_timestamp_attestation_roundtrip() {
+        let data = PushBytesBuf::try_from(b"hello raito".to_vec()).unwrap();
+        let transaction = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: Builder::new()
+                    .push_opcode(bitcoin::opcodes::all::OP_RETURN)
+                    .push_slice(data)
+                    .into_script(),
+            }],
+        };
+        let txids = vec![
+            Txid::from_byte_array([1; 32]),
+            transaction.compute_txid(),
+            Txid::from_byte_array([3; 32]),
+        ];
+        let pmt = PartialMerkleTree::from_txids(&txids, &[false, true, false]);
+        let (tx_index, branch) = merkle_branch(&pmt, txids[1]).unwrap();
+        let mut matches = Vec::new();
+        let mut indexes = Vec::new();
+        let root = pmt.extract_matches(&mut matches, &mut indexes).unwrap();
+        assert_eq!(merkle_root_from_branch(txids[1], tx_index, &branch), root);
+
+        let attestation = TimestampAttestation {
+            transaction,
+            tx_index,
+            merkle_branch: branch,
+            block_header: bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header,
+            block_height: 0,
+            mmr_leaf_count: 1,
+            mmr_siblings: vec![],
+            mmr_peaks: vec![[7; 32]],
+            chain_height: 0,
+            best_block_hash: BlockHash::all_zeros(),
+            chain_state_hash: [8; 32],
+            block_mmr_hash: [9; 32],
+        };
+        assert_eq!(
+            attestation.op_return_payloads(),
+            vec![b"hello raito".to_vec()]
+        );
+
+        let bytes = encode::serialize(&attestation);
+        assert_eq!(&bytes[..7], MAGIC);
+        let decoded: TimestampAttestation = encode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, attestation);
+
+        let mut corrupted = bytes.clone();
+        corrupted[7] = FORMAT_VERSION + 1;
+        assert!(encode::deserialize::<TimestampAttestation>(&corrupted).is_err());
+    }
+}
@@ -0,0 +1,42 @@
+//! CLI arguments pinning a trusted block MMR root, checked by the verifier on top of the
+//! bundled chain state proof.
+
+use std::path::PathBuf;
+
+use raito_spv_verifier::error::VerifyError;
+use raito_spv_verifier::trusted::TrustedRoot;
+
+/// CLI arguments pinning a trusted block MMR root
+#[derive(Clone, Debug, clap::Args)]
+pub struct TrustedRootArgs {
+    /// Trusted block MMR root (hex string), e.g. noted from a previously verified proof
+    #[arg(long, requires_all = ["trusted_height", "consistency_proof"])]
+    trusted_root: Option<String>,
+    /// Chain height the trusted block MMR root was taken at
+    #[arg(long, requires = "trusted_root")]
+    trusted_height: Option<u32>,
+    /// Path to the consistency proof (JSON) between the trusted MMR state and the proof one,
+    /// as returned by the bridge node `/block-consistency-proof/:trusted_height` endpoint
+    #[arg(long, requires = "trusted_root")]
+    consistency_proof: Option<PathBuf>,
+}
+
+impl TrustedRootArgs {
+    /// Load the consistency proof if a trusted root is pinned
+    pub fn load(&self) -> Result<Option<TrustedRoot>, VerifyError> {
+        let (Some(root), Some(height), Some(path)) = (
+            &self.trusted_root,
+            self.trusted_height,
+            &self.consistency_proof,
+        ) else {
+            return Ok(None);
+        };
+        let bytes = std::fs::read(path)?;
+        let consistency_proof = serde_json::from_slice(&bytes).map_err(VerifyError::decode)?;
+        Ok(Some(TrustedRoot {
+            root: root.clone(),
+            height,
+            consistency_proof,
+        }))
+    }
+}
@@ -1,299 +1,915 @@
-//! Verification routines for compressed SPV proofs, including transaction, block MMR,
-//! Cairo recursive proof, and subchain work checks.
-
-use bitcoin::Network;
-use bitcoin::{block::Header as BlockHeader, consensus, MerkleBlock, Transaction};
-use bzip2::read::BzDecoder;
-use cairo_air::utils::{get_verification_output, VerificationOutput};
-use cairo_air::{CairoProof, PreProcessedTraceVariant};
-use raito_spv_core::block_mmr::{BlockInclusionProof, BlockMMR};
-use std::{io::Read, path::PathBuf};
-use stwo_prover::core::vcs::blake2_merkle::{Blake2sMerkleChannel, Blake2sMerkleHasher};
+//! The `verify` subcommand and the resolution of the verification policies from the config
+//! file and the flags, on top of the verification routines of `raito-spv-verifier`.
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{BlockHash, Network};
+use chrono::Utc;
+use raito_spv_core::block_mmr::block_inclusion_path;
+use raito_spv_verifier::error::{failure_class, FailureClass, VerifyError};
+use raito_spv_verifier::fetch::RetryPolicy;
+use raito_spv_verifier::proof::CompressedSpvProof;
+use raito_spv_verifier::registry::ProgramRegistry;
+use raito_spv_verifier::verify::{
+    decode_compressed_proof_from_bzip2, decode_proof, elapsed_ms, load_proof_bundle_from_bzip2,
+    verify_bundle, verify_proof, verify_proof_with_cache, ChainStateVerificationCache, CheckResult,
+    CheckStatus, VerificationReport, VerificationTimings, VerifierConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
 use tracing::info;
 
-use crate::format::format_transaction;
-use crate::proof::{BootloaderOutput, ChainState, CompressedSpvProof, TaskResult};
-use crate::work::verify_subchain_work;
+use crate::attest::AttestArgs;
+use crate::convert::{load_compressed_proof_from_json, ProofFormat};
+use crate::crosscheck::{CrossCheckArgs, CrossCheckReport};
+use crate::download::download_proof;
+use crate::expect::{ExpectArgs, Expectations};
+use crate::fetch::is_stdio_path;
+use crate::format::{
+    format_amount, format_block, format_html, format_inclusion_path, format_plain,
+    format_transaction, Denomination, DisplayFormat, Layout, TransactionView, TIME_FORMAT,
+};
+use crate::network::{default_min_confirmations, parse_network};
+use crate::nostr::{NostrArgs, ProofReference};
+use crate::pin::PinArgs;
+use crate::store::{record, RecordArgs};
+use crate::trusted::TrustedRootArgs;
 
 /// CLI arguments for the `verify` subcommand
 #[derive(Clone, Debug, clap::Args)]
 pub struct VerifyArgs {
-    /// Path to read the proof from
+    /// Path to read the proof from (`-` for stdin)
+    #[arg(
+        long,
+        required_unless_present_any = ["proof_url", "bundle", "proof_dir"],
+        conflicts_with_all = ["proof_url", "bundle", "proof_dir"]
+    )]
+    proof_path: Option<PathBuf>,
+    /// URL to download the proof from (binary or JSON)
+    #[arg(long, conflicts_with_all = ["bundle", "proof_dir"])]
+    proof_url: Option<String>,
+    /// Path to read a proof bundle from, as written by `fetch --txids-file --bundle`
+    #[arg(long, conflicts_with = "proof_dir")]
+    bundle: Option<PathBuf>,
+    /// Directory to verify every proof file from (binary or JSON)
     #[arg(long)]
-    proof_path: PathBuf,
-    /// Development mode
+    proof_dir: Option<PathBuf>,
+    /// Number of proofs verified in parallel with `--proof-dir`
+    #[arg(long, default_value = "1", requires = "proof_dir")]
+    jobs: usize,
+    /// Expected SHA-256 checksum of the downloaded proof file (hex string)
+    #[arg(long, requires = "proof_url")]
+    sha256: Option<String>,
+    /// Maximum size of the downloaded proof in bytes
+    #[arg(long, default_value = "134217728")]
+    max_proof_size: u64,
+    /// Format of the verification result printed to stdout
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+    /// Format of the transaction display printed to stderr with `--output text`
+    #[arg(long, value_enum, default_value = "pretty")]
+    format: DisplayFormat,
+    /// Path to write the transaction display to instead of stderr, e.g. an HTML report
+    #[arg(long, conflicts_with_all = ["bundle", "proof_dir", "quiet"])]
+    display_path: Option<PathBuf>,
+    /// Print the time spent in each verification stage after the result
     #[arg(long, default_value = "false")]
-    dev: bool,
+    verbose: bool,
+    /// Print a single summary line per proof to stdout instead of the transaction card
+    #[arg(long, default_value = "false", conflicts_with = "verbose")]
+    quiet: bool,
+    /// Require the transaction to be a mature coinbase committing to its block height, and
+    /// report the block subsidy and the claimed fees
+    #[arg(long, default_value = "false")]
+    coinbase: bool,
+    /// Draw the path of the block in the block MMR, from its leaf up to its peak, after the
+    /// transaction card
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["bundle", "proof_dir", "quiet"]
+    )]
+    show_path: bool,
+    #[command(flatten)]
+    config: VerifierConfigArgs,
+    #[command(flatten)]
+    cross_check: CrossCheckArgs,
+    #[command(flatten)]
+    expect: ExpectArgs,
+    #[command(flatten)]
+    trusted_root: TrustedRootArgs,
+    #[command(flatten)]
+    pin: PinArgs,
+    #[command(flatten)]
+    attest: AttestArgs,
+    #[command(flatten)]
+    nostr: NostrArgs,
+    #[command(flatten)]
+    record: RecordArgs,
+}
+
+/// Format of the verification result
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable transaction card on stderr, or a summary line on stdout with `--quiet`
+    Text,
+    /// Structured JSON report
+    Json,
 }
 
-/// Configuration parameters controlling verification policies
-#[derive(Debug, Clone)]
-pub struct VerifierConfig {
+/// CLI arguments overriding the verification policies
+///
+/// Values are resolved in order: built-in defaults, then the config file, then the flags.
+#[derive(Clone, Debug, clap::Args)]
+pub struct VerifierConfigArgs {
+    /// Path to a TOML file with verification policies (any subset of the keys
+    /// `min_work`, `min_confirmations`, `bootloader_hash`, `task_program_hash`,
+    /// `task_output_size`, `registry`, `max_proof_age`, `max_block_age`, `network`,
+    /// `denomination`, `require_witness_proof`)
+    #[arg(long, env = "RAITO_SPV_CONFIG")]
+    config: Option<PathBuf>,
     /// Minimum cumulative work required on top of the target block (decimal string)
-    pub min_work: String,
-    /// Expected bootloader program hash used to generate the recursive proof (hex string)
-    pub bootloader_hash: String,
-    /// Expected payload program hash verified by the bootloader (hex string)
-    pub task_program_hash: String,
+    #[arg(long)]
+    min_work: Option<String>,
+    /// Minimum work on top of the target block expressed in blocks at the current target,
+    /// overrides the absolute minimum work
+    #[arg(long, conflicts_with = "min_work")]
+    min_confirmations: Option<u32>,
+    /// Expected bootloader program hash (hex string), disables the program registry
+    #[arg(long)]
+    bootloader_hash: Option<String>,
+    /// Expected payload program hash (hex string), disables the program registry
+    #[arg(long)]
+    task_program_hash: Option<String>,
     /// Expected size of the payload program output in felts
-    pub task_output_size: u32,
+    #[arg(long)]
+    task_output_size: Option<u32>,
+    /// Path to a program registry overriding the embedded one
+    #[arg(long, env = "RAITO_SPV_REGISTRY")]
+    registry: Option<PathBuf>,
+    /// Maximum age in seconds of the proven chain state, judged by its most recent block
+    /// timestamp
+    #[arg(long)]
+    max_proof_age: Option<u64>,
+    /// Maximum age in seconds of the block containing the transaction
+    #[arg(long)]
+    max_block_age: Option<u64>,
+    /// Network the proofs are generated for (mainnet, testnet, testnet4, signet, regtest),
+    /// determines how the subchain work is estimated, the default work policy and Raito
+    /// endpoint, the accepted program releases, and how addresses are rendered
+    #[arg(long, value_parser = parse_network)]
+    network: Option<Network>,
+    /// Denomination of the amounts in the transaction display
+    #[arg(long, value_enum)]
+    denomination: Option<Denomination>,
+    /// Require a witness proof (see `fetch --embed-witness`), so that a witness-malleated
+    /// copy of the transaction cannot pass for the proven one
+    #[arg(long, default_value = "false")]
+    require_witness_proof: bool,
+    /// Skip the subchain work check (development only)
+    #[arg(long, default_value = "false")]
+    skip_work: bool,
+    /// Skip the consistency checks between the block MMR proof and the chain state
+    /// (development only)
+    #[arg(long, default_value = "false")]
+    skip_mmr_consistency: bool,
+    /// Skip the STARK verification of the chain state proof (development only)
+    #[arg(long, default_value = "false")]
+    skip_stark: bool,
+    /// Accept the development stubs of chain state proofs written by `prove-dev`
+    /// (development only)
+    #[arg(long, default_value = "false")]
+    allow_dev_proofs: bool,
 }
 
-impl Default for VerifierConfig {
-    fn default() -> Self {
-        Self {
-            min_work: "1813388729421943762059264".to_string(), // 6 * 2^78, i.e. six block confirmations given the latest difficulty
-            bootloader_hash: "0x0001837d8b77b6368e0129ce3f65b5d63863cfab93c47865ee5cbe62922ab8f3"
-                .to_string(),
-            task_program_hash: "0x00f0876bb47895e8c4a6e7043829d7886e3b135e3ef30544fb688ef4e25663ca"
-                .to_string(),
-            task_output_size: 8,
-        }
+/// Verification policies as read from a config file, all keys are optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VerifierConfigFile {
+    min_work: Option<String>,
+    min_confirmations: Option<u32>,
+    bootloader_hash: Option<String>,
+    task_program_hash: Option<String>,
+    task_output_size: Option<u32>,
+    registry: Option<PathBuf>,
+    max_proof_age: Option<u64>,
+    max_block_age: Option<u64>,
+    network: Option<Network>,
+    denomination: Option<Denomination>,
+    require_witness_proof: Option<bool>,
+}
+
+impl VerifierConfigFile {
+    /// Load the config file from disk
+    fn load(path: &PathBuf) -> Result<Self, anyhow::Error> {
+        info!("Loading verifier configuration from {}", path.display());
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))
     }
 }
 
-/// Load a compressed proof from disk that was saved using bincode binary codec with bzip2 compression
-///
-/// - `proof_path`: Path to the bzip2 compressed proof file
-///
-/// This function first decompresses the bzip2 file, then deserializes the bytes
-/// using bincode binary codec, providing the symmetric operation to
-/// `save_compressed_proof_with_bzip2`.
-pub fn load_compressed_proof_from_bzip2(
-    proof_path: &PathBuf,
-) -> Result<CompressedSpvProof, anyhow::Error> {
-    info!(
-        "Loading and decompressing proof from {}",
-        proof_path.display()
-    );
+impl VerifierConfigArgs {
+    /// Resolve the verifier configuration from the defaults, the config file, and the flags
+    pub fn resolve(&self) -> Result<VerifierConfig, anyhow::Error> {
+        Ok(self.resolve_with_denomination()?.0)
+    }
 
-    // Step 1: Read and decompress the file
-    let file = std::fs::File::open(proof_path)?;
-    let mut bz_decoder = BzDecoder::new(file);
-    let mut decompressed_bytes = Vec::new();
-    bz_decoder.read_to_end(&mut decompressed_bytes)?;
+    /// Resolve the verifier configuration along with the denomination of the transaction
+    /// display, which is read from the same config file
+    pub fn resolve_with_denomination(
+        &self,
+    ) -> Result<(VerifierConfig, Denomination), anyhow::Error> {
+        let file = match &self.config {
+            Some(path) => VerifierConfigFile::load(path)?,
+            None => VerifierConfigFile::default(),
+        };
+
+        let mut config = VerifierConfig::default();
+        if let Some(network) = self.network.or(file.network) {
+            config.network = network;
+        }
 
-    info!(
-        "Decompressed {} bytes, now deserializing...",
-        decompressed_bytes.len()
-    );
+        let min_work = self.min_work.clone().or(file.min_work);
+        config.min_confirmations = self.min_confirmations.or(file.min_confirmations);
+        match min_work {
+            Some(min_work) => config.min_work = min_work,
+            None if config.min_confirmations.is_none() => {
+                config.min_confirmations = default_min_confirmations(config.network);
+            }
+            None => {}
+        }
+        if let Some(task_output_size) = self.task_output_size.or(file.task_output_size) {
+            config.task_output_size = task_output_size;
+        }
 
-    // Step 2: Deserialize the decompressed bytes using bincode
-    let proof: CompressedSpvProof = bincode::deserialize(&decompressed_bytes)?;
+        let bootloader_hash = self.bootloader_hash.clone().or(file.bootloader_hash);
+        let task_program_hash = self.task_program_hash.clone().or(file.task_program_hash);
+        if bootloader_hash.is_some() || task_program_hash.is_some() {
+            // Pinning the program hashes only accepts proofs generated by that exact program
+            config.programs = None;
+            if let Some(bootloader_hash) = bootloader_hash {
+                config.bootloader_hash = bootloader_hash;
+            }
+            if let Some(task_program_hash) = task_program_hash {
+                config.task_program_hash = task_program_hash;
+            }
+        } else {
+            let registry = match self.registry.clone().or(file.registry) {
+                Some(registry) => ProgramRegistry::load(&registry)?,
+                None => ProgramRegistry::embedded(),
+            };
+            config.programs = Some(registry.for_network(config.network)?);
+        }
 
-    info!("Successfully loaded compressed proof");
-    Ok(proof)
+        config.max_proof_age = self.max_proof_age.or(file.max_proof_age);
+        config.max_block_age = self.max_block_age.or(file.max_block_age);
+        config.require_witness_proof =
+            self.require_witness_proof || file.require_witness_proof.unwrap_or(false);
+
+        config.skip_work = self.skip_work;
+        config.skip_mmr_consistency = self.skip_mmr_consistency;
+        config.skip_stark = self.skip_stark;
+        config.allow_dev_proofs = self.allow_dev_proofs;
+
+        config.validate()?;
+        let denomination = self.denomination.or(file.denomination).unwrap_or_default();
+        Ok((config, denomination))
+    }
 }
 
-/// Run the `verify` subcommand: read a proof from disk and verify it
-pub async fn run(args: VerifyArgs) -> Result<(), anyhow::Error> {
-    // Load the compressed proof from the bzip2 compressed file
-    let proof = load_compressed_proof_from_bzip2(&args.proof_path)?;
+/// JSON representation of a verification result, valid or not
+#[derive(Debug, Serialize)]
+pub struct JsonVerificationResult<'a> {
+    valid: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    report: Option<&'a VerificationReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cross_check: Option<&'a CrossCheckReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<FailureClass>,
+}
 
-    let config = VerifierConfig::default();
+impl<'a> JsonVerificationResult<'a> {
+    pub fn valid(
+        report: &'a VerificationReport,
+        cross_check: Option<&'a CrossCheckReport>,
+    ) -> Self {
+        Self {
+            valid: true,
+            report: Some(report),
+            cross_check,
+            error: None,
+            failure: None,
+        }
+    }
 
-    // Verify the proof
-    verify_proof(proof, &config, args.dev).await?;
+    pub fn invalid(error: String, failure: Option<FailureClass>) -> Self {
+        Self {
+            valid: false,
+            report: None,
+            cross_check: None,
+            error: Some(error),
+            failure,
+        }
+    }
+}
 
-    Ok(())
+/// JSON representation of the verification result of a file in a directory
+#[derive(Debug, Serialize)]
+struct JsonFileResult<'a> {
+    file: String,
+    #[serde(flatten)]
+    result: JsonVerificationResult<'a>,
 }
 
-/// Verify a compressed SPV proof end-to-end.
-///
-/// This checks transaction inclusion, block header inclusion in the block MMR,
-/// Cairo recursive proof validity, and sufficient subchain work.
-pub async fn verify_proof(
-    proof: CompressedSpvProof,
-    config: &VerifierConfig,
-    dev: bool,
-) -> Result<(), anyhow::Error> {
-    let CompressedSpvProof {
-        chain_state,
-        chain_state_proof,
-        block_header,
-        block_header_proof,
-        transaction,
-        transaction_proof,
-    } = proof;
-
-    // Sanity checks
-    if !dev && block_header_proof.leaf_count as u32 != chain_state.block_height + 1 {
-        anyhow::bail!("Mismatched chain height and MMR size");
+/// Load a bzip2 compressed proof from disk, or from stdin if `proof_path` is `-`
+pub fn load_compressed_proof_from_bzip2(
+    proof_path: &Path,
+) -> Result<CompressedSpvProof, VerifyError> {
+    if is_stdio_path(proof_path) {
+        info!("Loading and decompressing proof from stdin");
+        return decode_compressed_proof_from_bzip2(std::io::stdin().lock());
     }
+    raito_spv_verifier::verify::load_compressed_proof_from_bzip2(proof_path)
+}
 
-    let block_height = block_header_proof.leaf_index as u32;
+/// Run the `verify` subcommand: read a proof from disk or a URL and verify it
+pub async fn run(args: VerifyArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let (mut config, denomination) = args.config.resolve_with_denomination()?;
+    config.require_coinbase = args.coinbase;
+    config.trusted_root = args.trusted_root.load()?;
+    let expectations = args.expect.parse(config.network)?;
 
-    info!("Verifying transaction inclusion proof ...");
-    verify_transaction(&transaction, &block_header, transaction_proof)?;
+    if let Some(bundle_path) = &args.bundle {
+        return run_bundle(bundle_path, &args, &config, denomination).await;
+    }
+    if let Some(proof_dir) = &args.proof_dir {
+        return run_dir(proof_dir, &args, &config).await;
+    }
 
-    info!("Verifying block inclusion proof ...");
-    let block_mmr_root_0 = verify_block_header(&block_header, block_header_proof).await?;
+    let (res, cross_check) = match verify_single(&args, &config, &expectations, &retry_policy).await
+    {
+        Ok((report, cross_check)) => (Ok(report), cross_check),
+        Err(err) => (Err(err), None),
+    };
+    match args.output {
+        OutputFormat::Text => {
+            let report = res?;
+            if args.quiet {
+                println!("{}", format_summary_line(&report));
+                return Ok(());
+            }
+            let display =
+                format_verified_display(&report, args.format, &display_layout(denomination))?;
+            match &args.display_path {
+                Some(display_path) => {
+                    std::fs::write(display_path, display)?;
+                    info!("Transaction display written to {}", display_path.display());
+                }
+                None => eprintln!("{}", display),
+            }
+            if let Some(inclusion_path) = &report.inclusion_path {
+                eprint!(
+                    "{}",
+                    format_inclusion_path(inclusion_path, &display_layout(denomination))
+                );
+            }
+            if args.verbose {
+                eprint!("{}", format_timings(&report.timings));
+            }
+            if let Some(cross_check) = &cross_check {
+                print_cross_check(&report.best_block_hash, cross_check);
+            }
+        }
+        OutputFormat::Json => {
+            let json = match &res {
+                Ok(report) => JsonVerificationResult::valid(report, cross_check.as_ref()),
+                Err(err) => {
+                    JsonVerificationResult::invalid(format!("{:#}", err), failure_class(err))
+                }
+            };
+            println!("{}", serde_json::to_string_pretty(&json)?);
+            res?;
+        }
+    }
 
-    info!("Verifying chain state proof ...");
-    let block_mmr_hash_1 = verify_chain_state(&chain_state, chain_state_proof, &config)?;
+    Ok(())
+}
+
+/// Load and verify a single proof, then check it against the expectations and the live chain
+async fn verify_single(
+    args: &VerifyArgs,
+    config: &VerifierConfig,
+    expectations: &Expectations,
+    retry_policy: &RetryPolicy,
+) -> Result<(VerificationReport, Option<CrossCheckReport>), anyhow::Error> {
+    let stage = Instant::now();
+    let mut proof_reference = ProofReference::default();
+    let proof = match (&args.proof_path, &args.proof_url) {
+        // Load the compressed proof from the bzip2 compressed file
+        (Some(proof_path), _) => {
+            if args.nostr.enabled() && !is_stdio_path(proof_path) {
+                proof_reference.sha256 =
+                    Some(sha256::Hash::hash(&std::fs::read(proof_path)?).to_string());
+            }
+            load_compressed_proof_from_bzip2(proof_path)?
+        }
+        // Download the proof and decode it in memory
+        (None, Some(proof_url)) => {
+            let bytes = download_proof(
+                proof_url,
+                args.max_proof_size,
+                args.sha256.as_deref(),
+                retry_policy,
+            )
+            .await?;
+            proof_reference = ProofReference {
+                sha256: Some(sha256::Hash::hash(&bytes).to_string()),
+                url: Some(proof_url.clone()),
+            };
+            decode_proof(&bytes)?
+        }
+        (None, None) => anyhow::bail!("Either a proof path or a proof URL is required"),
+    };
+    let load_ms = elapsed_ms(stage);
 
-    if !dev && block_mmr_root_0 != block_mmr_hash_1 {
-        anyhow::bail!("Mismatched block MMR roots");
+    // Verify the proof
+    let inclusion_path = args
+        .show_path
+        .then(|| block_inclusion_path(&proof.block_header, &proof.block_header_proof))
+        .transpose()
+        .map_err(|e| VerifyError::BlockInclusion(e.to_string()))?;
+    let mut report = verify_proof(proof, config).await?;
+    report.timings.load_ms = load_ms;
+    report.inclusion_path = inclusion_path;
+    args.pin.check(&mut report)?;
+    if !expectations.is_empty() {
+        expectations
+            .check(&report.transaction)
+            .map_err(VerifyError::Assertion)?;
+        info!("Transaction matches the expected outputs");
+        report.checks.push(CheckResult::new("expectations", false));
     }
 
-    info!("Verifying subchain work ...");
-    verify_subchain_work(block_height, &chain_state, &config)?;
+    let cross_check = if args.cross_check.enabled() {
+        let cross_check = args
+            .cross_check
+            .run(&report.best_block_hash, report.chain_height)
+            .await
+            .map_err(VerifyError::CrossCheck)?;
+        report.checks.push(CheckResult::new("cross_check", false));
+        Some(cross_check)
+    } else {
+        None
+    };
 
-    info!("Verification successful!");
+    // Only attest, publish and record once every requested check has passed
+    args.attest.run(&report)?;
+    args.nostr.run(&report, &proof_reference).await;
+    let source = match (&args.proof_path, &args.proof_url) {
+        (Some(proof_path), _) => Some(proof_path.display().to_string()),
+        (None, proof_url) => proof_url.clone(),
+    };
+    record(
+        args.record.open().await?.as_ref(),
+        &report,
+        config,
+        expectations.payment_output(&report.transaction),
+        source,
+    )
+    .await?;
+
+    Ok((report, cross_check))
+}
 
-    // Format and display the transaction with ASCII graphics
-    let formatted_tx = format_transaction(
-        &transaction,
-        Network::Bitcoin,
-        &block_header,
-        block_height,
-        chain_state.block_height,
-    );
-    println!("{}", formatted_tx);
+/// Verify a proof bundle and display the result of every bundled transaction
+async fn run_bundle(
+    bundle_path: &Path,
+    args: &VerifyArgs,
+    config: &VerifierConfig,
+    denomination: Denomination,
+) -> Result<(), anyhow::Error> {
+    let stage = Instant::now();
+    let bundle = load_proof_bundle_from_bzip2(bundle_path)?;
+    let load_ms = elapsed_ms(stage);
+    let best_block_hash = bundle.chain_state.best_block_hash;
+    let chain_height = bundle.chain_state.block_height;
+    let mut results = verify_bundle(bundle, config).await?;
+    for report in results.iter_mut().flatten() {
+        report.timings.load_ms = load_ms;
+    }
+    args.pin.check_all(&mut results)?;
+    let failures = results.iter().filter(|res| res.is_err()).count();
+    let store = args.record.open().await?;
+    for report in results.iter().flatten() {
+        let source = Some(bundle_path.display().to_string());
+        record(store.as_ref(), report, config, None, source).await?;
+    }
+
+    let cross_check = if failures == 0 && args.cross_check.enabled() {
+        Some(
+            args.cross_check
+                .run(&best_block_hash, chain_height)
+                .await
+                .map_err(VerifyError::CrossCheck)?,
+        )
+    } else {
+        None
+    };
+
+    match args.output {
+        OutputFormat::Text => {
+            for res in &results {
+                match res {
+                    Ok(report) if args.quiet => println!("{}", format_summary_line(report)),
+                    Ok(report) => {
+                        eprintln!(
+                            "{}",
+                            format_verified_display(
+                                report,
+                                args.format,
+                                &display_layout(denomination)
+                            )?
+                        );
+                        if args.verbose {
+                            eprint!("{}", format_timings(&report.timings));
+                        }
+                    }
+                    Err(err) if args.quiet => println!("INVALID {}", err),
+                    Err(err) => eprintln!("Invalid proof: {}", err),
+                }
+            }
+            match &cross_check {
+                Some(cross_check) if !args.quiet => {
+                    print_cross_check(&best_block_hash, cross_check)
+                }
+                _ => {}
+            }
+        }
+        OutputFormat::Json => {
+            let json: Vec<_> = results
+                .iter()
+                .map(|res| match res {
+                    Ok(report) => JsonVerificationResult::valid(report, cross_check.as_ref()),
+                    Err(err) => JsonVerificationResult::invalid(err.to_string(), Some(err.class())),
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+    }
 
+    // Report the class of the first invalid proof
+    if let Some(err) = results.iter().find_map(|res| res.as_ref().err()) {
+        return Err(VerifyError::InvalidBundle {
+            failures,
+            total: results.len(),
+            class: err.class(),
+        }
+        .into());
+    }
     Ok(())
 }
 
-/// Verify that `transaction` is included in `block_header` using the provided Merkle proof.
-pub fn verify_transaction(
-    transaction: &Transaction,
-    block_header: &BlockHeader,
-    transaction_proof: Vec<u8>,
-) -> anyhow::Result<()> {
-    let merkle_block = MerkleBlock {
-        header: block_header.clone(),
-        txn: consensus::deserialize(&transaction_proof)?,
-    };
-
-    let mut matches = Vec::new();
-    let mut indexes = Vec::new();
-    merkle_block.extract_matches(&mut matches, &mut indexes)?;
+/// Verify every proof file of a directory and display a summary table
+async fn run_dir(
+    proof_dir: &Path,
+    args: &VerifyArgs,
+    config: &VerifierConfig,
+) -> Result<(), anyhow::Error> {
+    let paths = list_proof_files(proof_dir)?;
+    if paths.is_empty() {
+        anyhow::bail!("No proof files found in {}", proof_dir.display());
+    }
+    info!(
+        "Verifying {} proof(s) from {} with {} job(s)",
+        paths.len(),
+        proof_dir.display(),
+        args.jobs
+    );
 
-    if matches.len() != 1 {
-        anyhow::bail!("Expected 1 transaction match");
+    // STARK verification is CPU-bound, so every proof is verified on the blocking thread pool
+    // Proofs fetched from the same chain state share the chain state proof, which is only
+    // verified once
+    let cache = Arc::new(ChainStateVerificationCache::default());
+    let semaphore = Arc::new(Semaphore::new(args.jobs.max(1)));
+    let mut tasks = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let permit = semaphore.clone().acquire_owned().await?;
+        let path = path.clone();
+        let config = config.clone();
+        let cache = cache.clone();
+        let handle = tokio::runtime::Handle::current();
+        tasks.push(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            handle.block_on(verify_proof_file(&path, &config, &cache))
+        }));
+    }
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(task.await?);
+    }
+    args.pin.check_all(&mut results)?;
+    let store = args.record.open().await?;
+    for (path, report) in paths.iter().zip(&results) {
+        if let Ok(report) = report {
+            let source = Some(path.display().to_string());
+            record(store.as_ref(), report, config, None, source).await?;
+        }
     }
 
-    let txid = transaction.compute_txid();
-    if txid != matches[0] {
-        anyhow::bail!("Transaction ID mismatch");
+    match args.output {
+        OutputFormat::Text if args.quiet => {
+            for (path, res) in paths.iter().zip(&results) {
+                match res {
+                    Ok(report) => println!("{} {}", path.display(), format_summary_line(report)),
+                    Err(err) => println!("{} INVALID {}", path.display(), err),
+                }
+            }
+        }
+        OutputFormat::Text => eprint!("{}", format_dir_summary(&paths, &results, args.verbose)),
+        OutputFormat::Json => {
+            let json: Vec<_> = paths
+                .iter()
+                .zip(&results)
+                .map(|(path, res)| JsonFileResult {
+                    file: path.display().to_string(),
+                    result: match res {
+                        Ok(report) => JsonVerificationResult::valid(report, None),
+                        Err(err) => {
+                            JsonVerificationResult::invalid(err.to_string(), Some(err.class()))
+                        }
+                    },
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
     }
 
+    // Report the class of the first invalid proof
+    let failures = results.iter().filter(|res| res.is_err()).count();
+    if let Some(err) = results.iter().find_map(|res| res.as_ref().err()) {
+        return Err(VerifyError::InvalidBundle {
+            failures,
+            total: results.len(),
+            class: err.class(),
+        }
+        .into());
+    }
     Ok(())
 }
 
-/// Verify that `block_header` is included in the block MMR using the supplied inclusion proof.
-///
-/// Returns the computed block MMR root on success.
-pub async fn verify_block_header(
-    block_header: &BlockHeader,
-    block_header_proof: BlockInclusionProof,
-) -> anyhow::Result<String> {
-    let BlockInclusionProof {
-        peaks_hashes,
-        siblings_hashes: _,
-        leaf_index: _,
-        leaf_count,
-    } = block_header_proof.clone();
-    let mmr = BlockMMR::from_peaks(peaks_hashes, leaf_count).await?;
-    mmr.verify_proof(block_header, block_header_proof).await?;
-    mmr.get_root_hash(None).await
+/// List the proof files of a directory in name order, skipping hidden files
+fn list_proof_files(proof_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(proof_dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        if path.is_file() && !hidden {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
 }
 
-/// Verify the Cairo recursive proof and consistency of the bootloader output with `chain_state`.
-///
-/// Returns the block MMR root extracted from the proof on success.
-pub fn verify_chain_state(
-    chain_state: &ChainState,
-    chain_state_proof: CairoProof<Blake2sMerkleHasher>,
+/// Load a proof file in either format and verify it
+async fn verify_proof_file(
+    path: &PathBuf,
     config: &VerifierConfig,
-) -> anyhow::Result<String> {
-    info!("Extracting verification output...");
-
-    // Extract verification output from the public memory
-    let VerificationOutput {
-        program_hash: bootloader_hash,
-        output,
-    } = get_verification_output(&chain_state_proof.claim.public_data.public_memory);
-
-    // Decode the bootloader hash
-    let bootloader_hash = format!("0x{}", hex::encode(&bootloader_hash.to_bytes_be()));
-
-    // Decode bootloader output from the raw output felts
-    let BootloaderOutput {
-        n_tasks,
-        task_output_size,
-        task_program_hash,
-        task_result,
-    } = BootloaderOutput::decode(output)?;
-
-    if n_tasks != 1 {
-        anyhow::bail!(
-            "Bootloader output: number of tasks must be 1, got {}",
-            n_tasks
-        );
+    cache: &ChainStateVerificationCache,
+) -> Result<VerificationReport, VerifyError> {
+    let stage = Instant::now();
+    let proof = match ProofFormat::detect(path).map_err(VerifyError::decode)? {
+        ProofFormat::Binary => load_compressed_proof_from_bzip2(path)?,
+        ProofFormat::Json => load_compressed_proof_from_json(path).map_err(VerifyError::decode)?,
+    };
+    let load_ms = elapsed_ms(stage);
+    let mut report = verify_proof_with_cache(proof, config, Some(cache)).await?;
+    report.timings.load_ms = load_ms;
+    Ok(report)
+}
+
+/// Format the time spent in each verification stage
+pub fn format_timings(timings: &VerificationTimings) -> String {
+    let mut output = String::from("\nVerification stages:\n");
+    for (stage, ms) in [
+        ("load proof", timings.load_ms),
+        ("transaction inclusion", timings.transaction_ms),
+        ("block MMR inclusion", timings.block_header_ms),
+        ("chain state proof", timings.chain_state_ms),
+        ("subchain work", timings.work_ms),
+        ("total (excluding load)", timings.total_ms),
+    ] {
+        output.push_str(&format!("  {:<24} {:>8} ms\n", stage, ms));
     }
-    if task_output_size != config.task_output_size {
-        anyhow::bail!(
-            "Bootloader output: task output size must be {}, got {}",
-            config.task_output_size,
-            task_output_size
-        );
+    output
+}
+
+/// Format the per-file results of a directory verification as a table
+///
+/// - `verbose`: Append the load and chain state proof timings of valid proofs
+fn format_dir_summary(
+    paths: &[PathBuf],
+    results: &[Result<VerificationReport, VerifyError>],
+    verbose: bool,
+) -> String {
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string())
+        })
+        .collect();
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    let mut output = String::new();
+    output.push_str(&format!(
+        "{:<width$}  {:<6}  {}\n",
+        "File", "Result", "Details"
+    ));
+    for (name, res) in names.iter().zip(results) {
+        match res {
+            Ok(report) => {
+                output.push_str(&format!(
+                    "{:<width$}  {:<6}  {} at height {} ({} confirmations)",
+                    name, "OK", report.txid, report.block_height, report.confirmations
+                ));
+                if verbose {
+                    output.push_str(&format!(
+                        ", load {} ms, chain state proof {} ms, total {} ms",
+                        report.timings.load_ms,
+                        report.timings.chain_state_ms,
+                        report.timings.total_ms
+                    ));
+                }
+                output.push('\n');
+            }
+            Err(err) => output.push_str(&format!("{:<width$}  {:<6}  {}\n", name, "FAILED", err)),
+        }
     }
+    let valid = results.iter().filter(|res| res.is_ok()).count();
+    output.push_str(&format!(
+        "\n{} of {} proof(s) are valid\n",
+        valid,
+        results.len()
+    ));
+    output
+}
 
-    let TaskResult {
-        chain_state_hash,
-        block_mmr_hash,
-        program_hash: prev_program_hash,
-        bootloader_hash: prev_bootloader_hash,
-    } = task_result.clone();
-
-    // Check that chain state hashes match
-    let expected_chain_state_hash = chain_state.blake2s_digest()?;
-    if chain_state_hash != expected_chain_state_hash {
-        anyhow::bail!(
-            "Chain state hash doesn't match the expected hash: {} != {}",
-            chain_state_hash,
-            expected_chain_state_hash
-        );
+/// Display the result of the live chain state cross-check on stderr
+fn print_cross_check(best_block_hash: &BlockHash, report: &CrossCheckReport) {
+    eprintln!(
+        "Proven best block {} is on the best chain, {} block(s) behind the tip at height {}",
+        best_block_hash, report.blocks_behind, report.tip_height
+    );
+}
+
+/// Format and display the verified transaction with ASCII graphics on stderr, keeping
+/// stdout for machine-readable output
+pub fn print_verified_transaction(report: &VerificationReport, denomination: Denomination) {
+    eprintln!(
+        "{}",
+        format_verified_transaction(report, &display_layout(denomination))
+    );
+}
+
+/// Layout of stderr for the transaction display, in the configured denomination
+fn display_layout(denomination: Denomination) -> Layout {
+    Layout {
+        denomination,
+        ..Layout::detect()
     }
+}
+
+/// Format a single line summarizing a verified proof, for scripts and logs
+pub fn format_summary_line(report: &VerificationReport) -> String {
+    format!(
+        "VALID {} height={} confirmations={} chain_height={}",
+        report.txid, report.block_height, report.confirmations, report.chain_height
+    )
+}
+
+/// Format the verified transaction in the given display format
+pub fn format_verified_display(
+    report: &VerificationReport,
+    format: DisplayFormat,
+    layout: &Layout,
+) -> Result<String, serde_json::Error> {
+    let view = || {
+        TransactionView::new(
+            &report.transaction,
+            report.prevouts.as_deref(),
+            report.network,
+            &report.block_header,
+            report.block_height,
+            report.chain_height,
+        )
+    };
+    Ok(match format {
+        DisplayFormat::Pretty => format_verified_transaction(report, layout),
+        DisplayFormat::Plain => format_plain(
+            &view(),
+            &verification_facts(report, layout.denomination),
+            layout.denomination,
+        ),
+        DisplayFormat::Json => serde_json::to_string_pretty(&view())?,
+        DisplayFormat::Html => format_html(
+            &view(),
+            &verification_facts(report, layout.denomination),
+            layout.denomination,
+        ),
+    })
+}
 
-    // Check that the program hash is the same as in the bootloader output and as expected
-    if task_program_hash != config.task_program_hash {
-        anyhow::bail!(
-            "Bootloader output: task program hash doesn't match the expected hash: {} != {}",
-            task_program_hash,
-            config.task_program_hash
-        );
+/// Facts about the verification of a proof, as `(name, value)` pairs for the plain and HTML
+/// displays
+pub fn verification_facts(
+    report: &VerificationReport,
+    denomination: Denomination,
+) -> Vec<(&'static str, String)> {
+    let mut facts = vec![
+        ("Proven best block", report.best_block_hash.to_string()),
+        ("Block MMR root", report.block_mmr_root.clone()),
+        ("Cumulative work", report.cumulative_work.to_string()),
+    ];
+    if let Some(program_release) = &report.program_release {
+        facts.push(("Program release", program_release.clone()));
     }
-    if task_program_hash != prev_program_hash {
-        anyhow::bail!(
-            "Previous program hash doesn't match the task result: {} != {}",
-            prev_program_hash,
-            task_program_hash
-        );
+    if let Some(subchain_work) = &report.subchain_work {
+        facts.push(("Proven work on top", subchain_work.to_string()));
     }
-
-    // Check that the previous bootloader hash is the same as in the Cairo claim and as expected
-    if bootloader_hash != config.bootloader_hash {
-        anyhow::bail!(
-            "Bootloader hash doesn't match the expected hash: {} != {}",
-            bootloader_hash,
-            config.bootloader_hash
-        );
+    if let Some(wtxid) = &report.wtxid {
+        facts.push(("Proven wtxid", wtxid.to_string()));
     }
-    if bootloader_hash != prev_bootloader_hash {
-        anyhow::bail!(
-            "Previous bootloader hash doesn't match the verification data: {} != {}",
-            bootloader_hash,
-            prev_bootloader_hash
-        );
+    if let Some(coinbase) = &report.coinbase {
+        facts.push((
+            "Mature coinbase",
+            format!(
+                "subsidy {}, claimed fees {}",
+                format_amount(coinbase.subsidy, denomination),
+                format_amount(coinbase.fees, denomination)
+            ),
+        ));
     }
+    let checks: Vec<_> = report
+        .checks
+        .iter()
+        .map(|check| match check.status {
+            CheckStatus::Passed => check.name.to_string(),
+            CheckStatus::Skipped => format!("{} (skipped)", check.name),
+        })
+        .collect();
+    facts.push(("Checks", checks.join(", ")));
+    facts.push(("Verified at", Utc::now().format(TIME_FORMAT).to_string()));
+    facts
+}
 
-    info!("Verifying Cairo proof...");
-    cairo_air::verifier::verify_cairo::<Blake2sMerkleChannel>(
-        chain_state_proof,
-        PreProcessedTraceVariant::CanonicalWithoutPedersenAndPoseidon,
-    )?;
-
-    Ok(block_mmr_hash)
+/// Format the verified transaction with ASCII graphics
+pub fn format_verified_transaction(report: &VerificationReport, layout: &Layout) -> String {
+    let mut output = format_transaction(
+        &report.transaction,
+        report.prevouts.as_deref(),
+        report.network,
+        report.block_height,
+        report.chain_height,
+        layout,
+    );
+    output.push_str(&format_block(
+        &report.block_header,
+        report.block_height,
+        report.chain_height,
+        Utc::now(),
+        layout,
+    ));
+    if let Some(program_release) = &report.program_release {
+        output.push_str(&format!(
+            "\nProven with program release {}\n",
+            program_release
+        ));
+    }
+    if let Some(subchain_work) = &report.subchain_work {
+        output.push_str(&format!("\nProven work on top: {}\n", subchain_work));
+    }
+    if let Some(coinbase) = &report.coinbase {
+        output.push_str(&format!(
+            "\nMature coinbase: subsidy {}, claimed fees {}\n",
+            format_amount(coinbase.subsidy, layout.denomination),
+            format_amount(coinbase.fees, layout.denomination)
+        ));
+    }
+    output
 }
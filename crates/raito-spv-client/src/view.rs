@@ -0,0 +1,331 @@
+//! Interactive terminal viewer of proof files, for analysts reviewing proofs by hand: the
+//! transaction, the block, the block MMR path, the verification result and the proof contents
+//! are shown in tabs that can be scrolled through.
+
+use std::path::PathBuf;
+
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::{Network, Transaction, TxOut};
+use chrono::Utc;
+use raito_spv_core::block_mmr::{block_inclusion_path, BlockInclusionPath};
+use raito_spv_verifier::verify::{verify_proof, VerificationReport};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::Constraint;
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Paragraph, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use tracing::info;
+
+use crate::format::{
+    format_block, format_inclusion_path, format_transaction, Denomination, Layout,
+};
+use crate::inspect::format_proof_summary;
+use crate::verify::{
+    format_summary_line, format_timings, load_compressed_proof_from_bzip2, verification_facts,
+    VerifierConfigArgs,
+};
+
+/// CLI arguments for the `view` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct ViewArgs {
+    /// Path to read the proof from
+    #[arg(long)]
+    proof_path: PathBuf,
+    /// Open the proof without verifying it first
+    #[arg(long, default_value = "false")]
+    no_verify: bool,
+    #[command(flatten)]
+    config: VerifierConfigArgs,
+}
+
+/// Sections of the viewer, one per tab
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tab {
+    Transaction,
+    Block,
+    Path,
+    Verification,
+    Proof,
+}
+
+/// Tabs in display order
+const TABS: [Tab; 5] = [
+    Tab::Transaction,
+    Tab::Block,
+    Tab::Path,
+    Tab::Verification,
+    Tab::Proof,
+];
+
+impl Tab {
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Transaction => "Transaction",
+            Tab::Block => "Block",
+            Tab::Path => "MMR path",
+            Tab::Verification => "Verification",
+            Tab::Proof => "Proof",
+        }
+    }
+}
+
+/// Contents of a proof, kept for display after the proof is consumed by the verification
+struct ProofView {
+    transaction: Transaction,
+    prevouts: Option<Vec<TxOut>>,
+    network: Network,
+    denomination: Denomination,
+    block_header: BlockHeader,
+    block_height: u32,
+    chain_height: u32,
+    inclusion_path: Result<BlockInclusionPath, String>,
+    summary: String,
+    /// Verification result, unless the proof was opened without verifying it
+    verification: Option<Result<VerificationReport, String>>,
+}
+
+/// State of the viewer: the selected tab and the scroll offset of every tab
+struct Viewer {
+    proof: ProofView,
+    selected: usize,
+    scroll: [u16; TABS.len()],
+    /// Number of lines of the selected tab and of its visible area, as of the last draw
+    content_height: u16,
+    page_height: u16,
+    quit: bool,
+}
+
+/// Run the `view` subcommand: load and verify a proof, then browse it in the terminal
+pub async fn run(args: ViewArgs) -> Result<(), anyhow::Error> {
+    let (config, denomination) = args.config.resolve_with_denomination()?;
+    let file_size = std::fs::metadata(&args.proof_path)?.len();
+    let proof = load_compressed_proof_from_bzip2(&args.proof_path)?;
+
+    let mut view = ProofView {
+        transaction: proof.transaction.clone(),
+        prevouts: proof.prevouts.clone(),
+        network: config.network,
+        denomination,
+        block_header: proof.block_header,
+        block_height: proof.block_height,
+        chain_height: proof.chain_state.block_height,
+        inclusion_path: block_inclusion_path(&proof.block_header, &proof.block_header_proof)
+            .map_err(|e| e.to_string()),
+        summary: format_proof_summary(&proof, file_size)?,
+        verification: None,
+    };
+    if !args.no_verify {
+        info!("Verifying proof before opening the viewer ...");
+        view.verification = Some(
+            verify_proof(proof, &config)
+                .await
+                .map_err(|e| format!("{:#}", e)),
+        );
+    }
+
+    let mut viewer = Viewer::new(view);
+    let mut terminal = ratatui::init();
+    let res = viewer.run(&mut terminal);
+    ratatui::restore();
+    res
+}
+
+impl Viewer {
+    fn new(proof: ProofView) -> Self {
+        Self {
+            proof,
+            selected: 0,
+            scroll: [0; TABS.len()],
+            content_height: 0,
+            page_height: 0,
+            quit: false,
+        }
+    }
+
+    /// Draw the viewer and handle key presses until the user quits
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), anyhow::Error> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key.code);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Text of a tab, rendered for the given width
+    fn contents(&self, tab: Tab, width: usize) -> String {
+        let proof = &self.proof;
+        let layout = Layout::plain(width, proof.denomination);
+        match tab {
+            Tab::Transaction => format_transaction(
+                &proof.transaction,
+                proof.prevouts.as_deref(),
+                proof.network,
+                proof.block_height,
+                proof.chain_height,
+                &layout,
+            ),
+            Tab::Block => {
+                let header = &proof.block_header;
+                let mut output = format_block(
+                    header,
+                    proof.block_height,
+                    proof.chain_height,
+                    Utc::now(),
+                    &layout,
+                );
+                output.push_str(&format!(
+                    "\nVersion: {:#x}\n",
+                    header.version.to_consensus()
+                ));
+                output.push_str(&format!("Previous block: {}\n", header.prev_blockhash));
+                output.push_str(&format!("Merkle root: {}\n", header.merkle_root));
+                output.push_str(&format!("Timestamp: {}\n", header.time));
+                output.push_str(&format!("Nonce: {}\n", header.nonce));
+                output
+            }
+            Tab::Path => match &proof.inclusion_path {
+                Ok(path) => format_inclusion_path(path, &layout),
+                Err(err) => format!("Invalid block MMR inclusion proof: {}\n", err),
+            },
+            Tab::Verification => match &proof.verification {
+                Some(Ok(report)) => {
+                    let mut output = format!("{}\n\n", format_summary_line(report));
+                    for (name, value) in verification_facts(report, proof.denomination) {
+                        output.push_str(&format!("{}: {}\n", name, value));
+                    }
+                    output.push('\n');
+                    output.push_str(&format_timings(&report.timings));
+                    output
+                }
+                Some(Err(err)) => format!("INVALID {}\n", err),
+                None => "Not verified (opened with --no-verify)\n".to_string(),
+            },
+            Tab::Proof => proof.summary.clone(),
+        }
+    }
+
+    /// Update the state on a key press
+    fn handle_key(&mut self, code: KeyCode) {
+        let scroll = &mut self.scroll[self.selected];
+        let max_scroll = self.content_height.saturating_sub(self.page_height);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.selected = (self.selected + 1) % TABS.len();
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.selected = (self.selected + TABS.len() - 1) % TABS.len();
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < TABS.len() {
+                    self.selected = index;
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => *scroll = (*scroll + 1).min(max_scroll),
+            KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+            KeyCode::PageDown | KeyCode::Char(' ') => {
+                *scroll = scroll.saturating_add(self.page_height).min(max_scroll)
+            }
+            KeyCode::PageUp => *scroll = scroll.saturating_sub(self.page_height),
+            KeyCode::Home | KeyCode::Char('g') => *scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => *scroll = max_scroll,
+            _ => {}
+        }
+    }
+
+    /// Draw the tabs, the selected tab contents and the key bindings
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs_area, content_area, help_area] = ratatui::layout::Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let titles = TABS
+            .iter()
+            .enumerate()
+            .map(|(i, tab)| format!("{} {}", i + 1, tab.title()));
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(self.selected)
+                .highlight_style(Style::default().fg(Color::Yellow)),
+            tabs_area,
+        );
+
+        let tab = TABS[self.selected];
+        let block = Block::bordered().title(tab.title());
+        let inner = block.inner(content_area);
+        let contents = self.contents(tab, inner.width as usize);
+        self.content_height = contents.lines().count() as u16;
+        self.page_height = inner.height;
+        let scroll = &mut self.scroll[self.selected];
+        *scroll = (*scroll).min(self.content_height.saturating_sub(self.page_height));
+        frame.render_widget(
+            Paragraph::new(contents).block(block).scroll((*scroll, 0)),
+            content_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new("←/→ tab  ↑/↓ PgUp/PgDn Home/End scroll  1-5 jump  q quit")
+                .style(Style::default().fg(Color::DarkGray)),
+            help_area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::constants::genesis_block;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn viewer() -> Viewer {
+        let block = genesis_block(Network::Bitcoin);
+        Viewer::new(ProofView {
+            transaction: block.txdata[0].clone(),
+            prevouts: None,
+            network: Network::Bitcoin,
+            denomination: Denomination::Btc,
+            block_header: block.header,
+            block_height: 0,
+            chain_height: 0,
+            inclusion_path: Err("no proof".to_string()),
+            summary: "PROOF FILE:\n".to_string(),
+            verification: None,
+        })
+    }
+
+    #[test]
+    fn test_viewer_navigation() {
+        let mut viewer = viewer();
+        let mut terminal = Terminal::new(TestBackend::new(80, 12)).unwrap();
+        terminal.draw(|frame| viewer.draw(frame)).unwrap();
+        assert!(viewer.content_height > viewer.page_height);
+
+        viewer.handle_key(KeyCode::End);
+        let max_scroll = viewer.content_height - viewer.page_height;
+        assert_eq!(viewer.scroll[0], max_scroll);
+        viewer.handle_key(KeyCode::Down);
+        assert_eq!(viewer.scroll[0], max_scroll);
+
+        viewer.handle_key(KeyCode::Left);
+        assert_eq!(TABS[viewer.selected], Tab::Proof);
+        viewer.handle_key(KeyCode::Char('4'));
+        assert_eq!(TABS[viewer.selected], Tab::Verification);
+        terminal.draw(|frame| viewer.draw(frame)).unwrap();
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("Not verified"));
+        // The scroll offset of each tab is kept
+        assert_eq!(viewer.scroll[0], max_scroll);
+
+        viewer.handle_key(KeyCode::Char('q'));
+        assert!(viewer.quit);
+    }
+}
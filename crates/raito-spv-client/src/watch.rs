@@ -0,0 +1,250 @@
+//! Continuous monitoring of transactions: wait until each transaction is buried deep enough
+//! in the proven chain, then fetch and verify its compressed proof.
+//!
+//! One JSON event per line is written to stdout for every state change, so the output can be
+//! consumed by a deposit processing pipeline.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bitcoin::Txid;
+use raito_spv_verifier::fetch::{
+    ApiEndpoint, BitcoinDataSource, ChainStateProof, RetryPolicy, TransactionInclusionProof,
+};
+use raito_spv_verifier::verify::{verify_proof, VerificationReport, VerifierConfig};
+use raito_spv_verifier::work::check_block_depth;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::fetch::{
+    build_compressed_proof, default_proof_file_name, fetch_chain_state_proof,
+    fetch_transaction_proof, pruned_fallback, save_compressed_proof_with_bzip2,
+};
+use crate::network::{MAINNET_RAITO_RPC_URL, RAITO_RPC_URLS, RAITO_RPC_URL_REQUIRED};
+use crate::store::{record, RecordArgs};
+use crate::verify::VerifierConfigArgs;
+
+/// CLI arguments for the `watch` subcommand
+#[derive(Clone, Debug, clap::Args)]
+pub struct WatchArgs {
+    /// Transaction ID to watch (can be repeated)
+    #[arg(long = "txid", required = true)]
+    txids: Vec<Txid>,
+    /// Polling interval in seconds
+    #[arg(long, default_value = "60")]
+    poll_interval: u64,
+    /// Directory to save verified proofs to (as `<txid>-<height>.raito`)
+    #[arg(long)]
+    proof_dir: Option<PathBuf>,
+    /// Raito node RPC URL, defaults to the Raito bridge of the `--network`; required on
+    /// `testnet4` and `signet`, which have no public bridge
+    #[arg(
+        long,
+        env = "RAITO_BRIDGE_RPC",
+        default_value = MAINNET_RAITO_RPC_URL,
+        default_value_ifs = RAITO_RPC_URLS,
+        required_if_eq_any = RAITO_RPC_URL_REQUIRED
+    )]
+    raito_rpc_url: String,
+    /// Bitcoin RPC URL
+    #[arg(long, env = "BITCOIN_RPC")]
+    bitcoin_rpc_url: String,
+    /// Bitcoin RPC user:password (optional)
+    #[arg(long, env = "USERPWD")]
+    bitcoin_rpc_userpwd: Option<String>,
+    #[command(flatten)]
+    verifier_config: VerifierConfigArgs,
+    #[command(flatten)]
+    record: RecordArgs,
+    /// Development mode
+    #[arg(long, default_value = "false")]
+    dev: bool,
+}
+
+/// Event emitted (as a JSON line) when a watched transaction changes state
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchEvent {
+    /// The transaction was found in a block
+    Confirmed {
+        /// Transaction ID
+        txid: Txid,
+        /// Height of the block containing the transaction
+        block_height: u32,
+    },
+    /// The transaction proof was fetched and successfully verified
+    Verified {
+        /// Transaction ID
+        txid: Txid,
+        /// Path the proof was saved to, if a proof directory is configured
+        proof_path: Option<PathBuf>,
+        /// Summary of the verified proof
+        report: Box<VerificationReport>,
+    },
+    /// The transaction proof could not be fetched or verified
+    Failed {
+        /// Transaction ID
+        txid: Txid,
+        /// Reason of the failure
+        error: String,
+    },
+}
+
+/// Run the `watch` subcommand: poll until all transactions are verified or failed
+pub async fn run(args: WatchArgs, retry_policy: RetryPolicy) -> Result<(), anyhow::Error> {
+    let raito_rpc = ApiEndpoint::new(&args.raito_rpc_url).with_retry_policy(retry_policy);
+    let mut config = args.verifier_config.resolve()?;
+    if args.dev {
+        config.enable_dev_mode();
+    }
+    let poll_interval = Duration::from_secs(args.poll_interval);
+    let store = args.record.open().await?;
+
+    // Block height of each pending transaction, once it is confirmed
+    let mut pending: BTreeMap<Txid, Option<u32>> =
+        args.txids.iter().map(|txid| (*txid, None)).collect();
+
+    while !pending.is_empty() {
+        let mut chain_state_proof = match fetch_chain_state_proof(&raito_rpc).await {
+            Ok(proof) => Some(proof),
+            Err(err) => {
+                warn!("Failed to fetch chain state proof: {}", err);
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+        };
+        let chain_height = chain_state_proof
+            .as_ref()
+            .map(|proof| proof.chain_state.block_height)
+            .unwrap_or_default();
+        info!(
+            "Proven chain height: {}, {} transaction(s) pending",
+            chain_height,
+            pending.len()
+        );
+
+        let txids: Vec<Txid> = pending.keys().copied().collect();
+        for txid in txids {
+            let transaction_proof = match fetch_transaction_proof(
+                txid,
+                &BitcoinDataSource::Node {
+                    url: args.bitcoin_rpc_url.clone(),
+                    userpwd: args.bitcoin_rpc_userpwd.clone(),
+                    pruned_fallback: Some(pruned_fallback(None, &raito_rpc)),
+                },
+            )
+            .await
+            {
+                Ok(proof) => proof,
+                Err(err) => {
+                    info!("Transaction {} is not confirmed yet: {}", txid, err);
+                    continue;
+                }
+            };
+
+            let block_height = transaction_proof.block_height;
+            if pending.insert(txid, Some(block_height)) == Some(None) {
+                emit(&WatchEvent::Confirmed { txid, block_height })?;
+            }
+
+            // The chain state proof is consumed by the compressed proof, fetch a new one if
+            // several transactions become ready during the same iteration
+            let proof = match chain_state_proof.take() {
+                Some(proof) => proof,
+                None => match fetch_chain_state_proof(&raito_rpc).await {
+                    Ok(proof) => proof,
+                    Err(err) => {
+                        warn!("Failed to fetch chain state proof: {}", err);
+                        continue;
+                    }
+                },
+            };
+
+            // Wait until the transaction is buried under enough work to pass verification
+            if let Err(err) = check_block_depth(block_height, &proof.chain_state, &config) {
+                info!("Transaction {}: {}", txid, err);
+                chain_state_proof = Some(proof);
+                continue;
+            }
+
+            let event =
+                prove_and_verify(&args, &raito_rpc, &config, proof, transaction_proof).await;
+            if let WatchEvent::Verified {
+                proof_path, report, ..
+            } = &event
+            {
+                let source = proof_path.as_ref().map(|path| path.display().to_string());
+                record(store.as_ref(), report, &config, None, source).await?;
+            }
+            emit(&event)?;
+            pending.remove(&txid);
+        }
+
+        if !pending.is_empty() {
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    info!("All watched transactions are processed");
+    Ok(())
+}
+
+/// Assemble the compressed proof for a transaction, verify it and optionally save it to disk
+async fn prove_and_verify(
+    args: &WatchArgs,
+    raito_rpc: &ApiEndpoint,
+    config: &VerifierConfig,
+    chain_state_proof: ChainStateProof,
+    transaction_proof: TransactionInclusionProof,
+) -> WatchEvent {
+    let txid = transaction_proof.transaction.compute_txid();
+    match prove_and_verify_inner(
+        args,
+        raito_rpc,
+        config,
+        chain_state_proof,
+        transaction_proof,
+    )
+    .await
+    {
+        Ok((proof_path, report)) => WatchEvent::Verified {
+            txid,
+            proof_path,
+            report: Box::new(report),
+        },
+        Err(err) => WatchEvent::Failed {
+            txid,
+            error: err.to_string(),
+        },
+    }
+}
+
+async fn prove_and_verify_inner(
+    args: &WatchArgs,
+    raito_rpc: &ApiEndpoint,
+    config: &VerifierConfig,
+    chain_state_proof: ChainStateProof,
+    transaction_proof: TransactionInclusionProof,
+) -> Result<(Option<PathBuf>, VerificationReport), anyhow::Error> {
+    let proof =
+        build_compressed_proof(chain_state_proof, transaction_proof, raito_rpc, args.dev).await?;
+
+    let proof_path = match &args.proof_dir {
+        Some(proof_dir) => {
+            let proof_path = proof_dir.join(default_proof_file_name(&proof));
+            save_compressed_proof_with_bzip2(&proof, &proof_path)?;
+            Some(proof_path)
+        }
+        None => None,
+    };
+
+    let report = verify_proof(proof, config).await?;
+    Ok((proof_path, report))
+}
+
+/// Write an event as a single JSON line to stdout
+fn emit(event: &WatchEvent) -> Result<(), anyhow::Error> {
+    println!("{}", serde_json::to_string(event)?);
+    Ok(())
+}
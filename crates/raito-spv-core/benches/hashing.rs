@@ -0,0 +1,95 @@
+//! Block header digests and MMR node hashing, one at a time and as batches.
+//!
+//! The batches are hashed with the multi-buffer Blake2s with the `simd` feature, and one at a
+//! time without it; compare both with a saved baseline:
+//!
+//! ```sh
+//! cargo bench -p raito-spv-core --bench hashing -- --save-baseline scalar
+//! cargo bench -p raito-spv-core --bench hashing --features simd -- --baseline scalar
+//! ```
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use raito_spv_core::block_mmr::BlockPeaks;
+use raito_spv_core::primitives::{self, Hash};
+
+/// Number of headers and leaves hashed by each iteration, a day of blocks
+const COUNT: usize = 144;
+
+/// Distinct headers, the nonce being the index
+fn headers() -> Vec<[u8; 80]> {
+    (0..COUNT as u32)
+        .map(|i| {
+            let mut header = [0x5a; 80];
+            header[76..].copy_from_slice(&i.to_le_bytes());
+            header
+        })
+        .collect()
+}
+
+fn bench_header_digests(c: &mut Criterion) {
+    let headers = headers();
+    let mut group = c.benchmark_group("block_header_digests");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    group.bench_function(BenchmarkId::new("one_at_a_time", COUNT), |b| {
+        b.iter(|| {
+            headers
+                .iter()
+                .map(primitives::block_header_digest)
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function(BenchmarkId::new("batch", COUNT), |b| {
+        b.iter(|| primitives::block_header_digests(&headers))
+    });
+    group.finish();
+}
+
+fn bench_mmr_append(c: &mut Criterion) {
+    let leaves: Vec<Hash> = primitives::block_header_digests(&headers());
+    // Appended to an MMR of about the size of the Bitcoin one
+    let mut initial = BlockPeaks::default();
+    initial.extend(&vec![[0x11; 32]; 900_000]);
+
+    let mut group = c.benchmark_group("mmr_append");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    group.bench_function(BenchmarkId::new("one_at_a_time", COUNT), |b| {
+        b.iter_batched_ref(
+            || initial.clone(),
+            |peaks| leaves.iter().for_each(|leaf| peaks.append(*leaf)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("batch", COUNT), |b| {
+        b.iter_batched_ref(
+            || initial.clone(),
+            |peaks| peaks.extend(&leaves),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("one_at_a_time_states", COUNT), |b| {
+        b.iter_batched_ref(
+            || initial.clone(),
+            |peaks| {
+                leaves
+                    .iter()
+                    .map(|leaf| {
+                        peaks.append(*leaf);
+                        peaks.clone()
+                    })
+                    .collect::<Vec<_>>()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("batch_states", COUNT), |b| {
+        b.iter_batched_ref(
+            || initial.clone(),
+            |peaks| peaks.extend_states(&leaves),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_header_digests, bench_mmr_append);
+criterion_main!(benches);
@@ -4,14 +4,16 @@ use base64::{engine::general_purpose, Engine as _};
 use bitcoin::block::Header as BlockHeader;
 use bitcoin::consensus::Decodable;
 use bitcoin::MerkleBlock;
-use bitcoin::{BlockHash, Transaction, Txid};
-use bitcoincore_rpc_json::GetBlockHeaderResult;
+use bitcoin::{Amount, Block, BlockHash, ScriptBuf, Transaction, TxOut, Txid};
+use bitcoincore_rpc_json::{GetBlockHeaderResult, ScanTxOutResult};
 use jsonrpsee::core::client::ClientT;
 use jsonrpsee::core::params::ArrayParams;
 use jsonrpsee::http_client::{HeaderMap, HeaderValue, HttpClient};
 use jsonrpsee::rpc_params;
+use rand::Rng;
 use serde::de::DeserializeOwned;
-use std::time::Duration;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tracing::{debug, info};
 
@@ -30,24 +32,102 @@ pub enum BitcoinClientError {
     /// Failed to deserialize Bitcoin consensus data
     #[error("Failed to deserialize Bitcoin data: {0}")]
     BitcoinDeserialization(#[from] bitcoin::consensus::encode::Error),
+    /// The node didn't report the previous outputs of a transaction (requires Bitcoin Core 25+)
+    #[error("Previous outputs of transaction {0} are not available")]
+    MissingPrevouts(Txid),
+}
+
+impl BitcoinClientError {
+    /// Whether the node rejected the request, e.g. for an unknown block or transaction, as
+    /// opposed to being unreachable
+    pub fn is_rejected(&self) -> bool {
+        matches!(
+            self,
+            Self::RpcClient(jsonrpsee::core::client::Error::Call(_))
+        )
+    }
 }
 
 /// Default HTTP request timeout
 pub const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
 
-/// Default block count update interval in seconds
-pub const BLOCK_COUNT_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+/// Expected time between two blocks
+pub const BLOCK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Schedule of the block count polling while waiting for a new block
+///
+/// The block count is polled every `max_interval` after a new block, then every
+/// `min_interval` once the expected block time has elapsed, when the next block is most
+/// likely to come. Each interval is randomized by `jitter` so that several clients don't
+/// poll the node in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollSchedule {
+    /// Polling interval once the expected block time has elapsed since the last block
+    pub min_interval: Duration,
+    /// Polling interval right after a new block
+    pub max_interval: Duration,
+    /// Expected time between two blocks
+    pub block_interval: Duration,
+    /// Random variation of each interval, as a fraction of it (0 to 1)
+    pub jitter: f64,
+}
+
+impl Default for PollSchedule {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(2),
+            max_interval: Duration::from_secs(30),
+            block_interval: BLOCK_INTERVAL,
+            jitter: 0.1,
+        }
+    }
+}
+
+impl PollSchedule {
+    /// Interval before the next poll without jitter, `since_last_block` after the last block
+    ///
+    /// Long intervals are shortened so as not to overshoot the expected block time.
+    pub fn base_interval(&self, since_last_block: Duration) -> Duration {
+        match self.block_interval.checked_sub(since_last_block) {
+            Some(remaining) => remaining.min(self.max_interval).max(self.min_interval),
+            None => self.min_interval,
+        }
+    }
+
+    /// Interval before the next poll, `since_last_block` after the last block
+    pub fn next_interval(&self, since_last_block: Duration) -> Duration {
+        let base = self.base_interval(since_last_block);
+        if self.jitter <= 0.0 {
+            return base;
+        }
+        let factor = 1.0 + self.jitter.min(1.0) * rand::thread_rng().gen_range(-1.0..=1.0);
+        base.mul_f64(factor)
+    }
+}
 
 /// Bitcoin RPC client
 pub struct BitcoinClient {
     client: HttpClient,
     block_count: u32,
     backoff: backoff::ExponentialBackoff,
+    poll_schedule: PollSchedule,
+    /// When the block count last increased, or polling started
+    last_block_at: Option<Instant>,
 }
 
 impl BitcoinClient {
     /// Create a new Bitcoin RPC client with default retry settings (exponential backoff)
     pub fn new(url: String, userpwd: Option<String>) -> Result<Self, BitcoinClientError> {
+        Self::with_timeout(url, userpwd, HTTP_REQUEST_TIMEOUT)
+    }
+
+    /// Create a new Bitcoin RPC client with a custom request timeout, for slow calls such as
+    /// [BitcoinClient::scan_tx_out_set]
+    pub fn with_timeout(
+        url: String,
+        userpwd: Option<String>,
+        timeout: Duration,
+    ) -> Result<Self, BitcoinClientError> {
         let mut headers = HeaderMap::new();
         if let Some(userpwd) = userpwd {
             let creds = general_purpose::STANDARD.encode(userpwd);
@@ -60,16 +140,24 @@ impl BitcoinClient {
 
         let client = HttpClient::builder()
             .set_headers(headers)
-            .request_timeout(HTTP_REQUEST_TIMEOUT)
+            .request_timeout(timeout)
             .build(url)?;
 
         Ok(Self {
             client,
             block_count: 0,
             backoff: backoff::ExponentialBackoff::default(),
+            poll_schedule: PollSchedule::default(),
+            last_block_at: None,
         })
     }
 
+    /// Set the schedule of the block count polling in [BitcoinClient::wait_block_header]
+    pub fn with_poll_schedule(mut self, poll_schedule: PollSchedule) -> Self {
+        self.poll_schedule = poll_schedule;
+        self
+    }
+
     async fn request_decode<T: Decodable>(
         &self,
         method: &str,
@@ -120,6 +208,12 @@ impl BitcoinClient {
             .await
     }
 
+    /// Get the full block by hash, including the transaction witnesses
+    pub async fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinClientError> {
+        self.request_decode("getblock", rpc_params![hash.to_string(), 0])
+            .await
+    }
+
     /// Get block header by height
     pub async fn get_block_header_by_height(
         &self,
@@ -143,6 +237,38 @@ impl BitcoinClient {
         .await
     }
 
+    /// Get the outputs spent by the inputs of a transaction, in input order, from the undo
+    /// data of the block containing the transaction (no transaction index required).
+    /// Returns an empty list for a coinbase transaction.
+    pub async fn get_transaction_prevouts(
+        &self,
+        txid: &Txid,
+        block_hash: &BlockHash,
+    ) -> Result<Vec<TxOut>, BitcoinClientError> {
+        let result: RawTransactionPrevouts = self
+            .request(
+                "getrawtransaction",
+                rpc_params![txid.to_string(), 2, block_hash.to_string()],
+            )
+            .await?;
+        if result.vin.iter().any(|input| input.coinbase.is_some()) {
+            return Ok(Vec::new());
+        }
+        result
+            .vin
+            .into_iter()
+            .map(|input| {
+                let prevout = input
+                    .prevout
+                    .ok_or(BitcoinClientError::MissingPrevouts(*txid))?;
+                Ok(TxOut {
+                    value: prevout.value,
+                    script_pubkey: prevout.script_pub_key.hex,
+                })
+            })
+            .collect()
+    }
+
     /// Get transaction inclusion proof
     pub async fn get_transaction_inclusion_proof(
         &self,
@@ -152,6 +278,23 @@ impl BitcoinClient {
             .await
     }
 
+    /// Scan the UTXO set for the outputs matching the descriptors, e.g. `addr(<address>)`.
+    /// A full scan takes minutes on mainnet, use a client with a long timeout.
+    pub async fn scan_tx_out_set(
+        &self,
+        descriptors: &[String],
+    ) -> Result<ScanTxOutResult, BitcoinClientError> {
+        self.request("scantxoutset", rpc_params!["start", descriptors])
+            .await
+    }
+
+    /// Get the height of the first block the node still stores the data of, `None` if the
+    /// node doesn't prune blocks. Pruned nodes keep all the headers.
+    pub async fn get_prune_height(&self) -> Result<Option<u32>, BitcoinClientError> {
+        let info: BlockchainPruneInfo = self.request("getblockchaininfo", rpc_params![]).await?;
+        Ok(info.pruned.then(|| info.pruneheight.unwrap_or(0)))
+    }
+
     /// Get current chain height
     pub async fn get_block_count(&self) -> Result<u32, BitcoinClientError> {
         let result: u64 = self.request("getblockcount", rpc_params![]).await?;
@@ -160,24 +303,70 @@ impl BitcoinClient {
 
     /// Wait for a block header at the given height.
     /// If the specified lag is non-zero, the function will wait till `lag` blocks are built on top of the expected block.
+    /// The block count is polled following the [PollSchedule] of the client.
     pub async fn wait_block_header(
         &mut self,
         height: u32,
         lag: u32,
     ) -> Result<(BlockHeader, BlockHash), BitcoinClientError> {
         while height >= self.block_count {
-            self.block_count = self.get_block_count().await?.saturating_sub(lag);
+            let block_count = self.get_block_count().await?.saturating_sub(lag);
+            if block_count > self.block_count || self.last_block_at.is_none() {
+                self.last_block_at = Some(Instant::now());
+            }
+            self.block_count = block_count;
             if height < self.block_count {
                 debug!("New block count: {}", self.block_count);
                 break;
             } else {
-                tokio::time::sleep(BLOCK_COUNT_UPDATE_INTERVAL).await;
+                let since_last_block = self.last_block_at.map_or(Duration::ZERO, |at| at.elapsed());
+                let interval = self.poll_schedule.next_interval(since_last_block);
+                debug!("Next block count poll in {:?}", interval);
+                tokio::time::sleep(interval).await;
             }
         }
         self.get_block_header_by_height(height).await
     }
 }
 
+/// Pruning fields of a `getblockchaininfo` result
+#[derive(Deserialize)]
+struct BlockchainPruneInfo {
+    pruned: bool,
+    /// Set if the node prunes blocks
+    pruneheight: Option<u32>,
+}
+
+/// Inputs of a `getrawtransaction` result with verbosity 2
+#[derive(Deserialize)]
+struct RawTransactionPrevouts {
+    vin: Vec<RawInput>,
+}
+
+/// Input of a `getrawtransaction` result with verbosity 2
+#[derive(Deserialize)]
+struct RawInput {
+    /// Set for the input of a coinbase transaction
+    coinbase: Option<String>,
+    /// Output spent by the input, reported since Bitcoin Core 25
+    prevout: Option<RawPrevout>,
+}
+
+/// Output spent by a transaction input
+#[derive(Deserialize)]
+struct RawPrevout {
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    value: Amount,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: RawScriptPubKey,
+}
+
+/// Script of an output spent by a transaction input
+#[derive(Deserialize)]
+struct RawScriptPubKey {
+    hex: ScriptBuf,
+}
+
 /// Execute a request with retry logic using exponential backoff
 /// Only retries on unexpected HTTP errors (not 200 OK or 400 Bad Request)
 async fn request_with_retry<F, Fut, T>(
@@ -232,3 +421,43 @@ fn is_retryable_error(err: &BitcoinClientError) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_prevouts() {
+        let result: RawTransactionPrevouts = serde_json::from_str(
+            r#"{"vin": [{"txid": "00", "vout": 1, "prevout": {"generated": false, "height": 1,
+                "value": 0.00012345, "scriptPubKey": {"asm": "", "hex": "0014751e76e8199196d454941c45d1b3a323f1433bd6", "type": "witness_v0_keyhash"}}}]}"#,
+        )
+        .unwrap();
+        let prevout = result.vin[0].prevout.as_ref().unwrap();
+        assert_eq!(prevout.value, Amount::from_sat(12_345));
+        assert!(prevout.script_pub_key.hex.is_p2wpkh());
+    }
+
+    #[test]
+    fn test_poll_schedule() {
+        let schedule = PollSchedule {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let secs = Duration::from_secs;
+        assert_eq!(schedule.base_interval(secs(0)), secs(30));
+        assert_eq!(schedule.base_interval(secs(580)), secs(20));
+        assert_eq!(schedule.base_interval(secs(599)), secs(2));
+        assert_eq!(schedule.base_interval(secs(900)), secs(2));
+        assert_eq!(schedule.next_interval(secs(0)), secs(30));
+
+        let schedule = PollSchedule {
+            jitter: 0.5,
+            ..schedule
+        };
+        for _ in 0..100 {
+            let interval = schedule.next_interval(secs(0));
+            assert!(interval >= secs(15) && interval <= secs(45));
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Multi-buffer Blake2s-256: several messages of the same length are hashed at once, one per
+//! lane of the SIMD registers, for the batch [primitives](crate::primitives) used by the
+//! `simd` feature.
+//!
+//! The state is stored transposed (word `i` of every lane side by side), so that each step of
+//! the compression function is the same operation on all lanes, left to the compiler to turn
+//! into the vector instructions of the target (SSE2 on x86-64, NEON on aarch64). On x86-64 with
+//! the `std` feature, a copy compiled for AVX2 is used if the CPU supports it.
+
+/// Number of messages hashed at once
+pub const LANES: usize = 8;
+
+/// The same word of every lane
+pub type Lanes = [u32; LANES];
+
+const IV: [u32; 8] = [
+    0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Words of a message block
+const BLOCK_WORDS: usize = 16;
+
+#[inline(always)]
+fn add(a: Lanes, b: Lanes) -> Lanes {
+    core::array::from_fn(|i| a[i].wrapping_add(b[i]))
+}
+
+#[inline(always)]
+fn xor_rotate(a: Lanes, b: Lanes, n: u32) -> Lanes {
+    core::array::from_fn(|i| (a[i] ^ b[i]).rotate_right(n))
+}
+
+#[inline(always)]
+#[allow(clippy::too_many_arguments)]
+fn g(v: &mut [Lanes; 16], a: usize, b: usize, c: usize, d: usize, x: Lanes, y: Lanes) {
+    v[a] = add(add(v[a], v[b]), x);
+    v[d] = xor_rotate(v[d], v[a], 16);
+    v[c] = add(v[c], v[d]);
+    v[b] = xor_rotate(v[b], v[c], 12);
+    v[a] = add(add(v[a], v[b]), y);
+    v[d] = xor_rotate(v[d], v[a], 8);
+    v[c] = add(v[c], v[d]);
+    v[b] = xor_rotate(v[b], v[c], 7);
+}
+
+#[inline(always)]
+fn compress(h: &mut [Lanes; 8], m: &[Lanes; BLOCK_WORDS], byte_count: u32, last: bool) {
+    let mut v = [[0; LANES]; 16];
+    v[..8].copy_from_slice(h);
+    for (i, word) in IV.iter().enumerate() {
+        v[8 + i] = [*word; LANES];
+    }
+    v[12] = v[12].map(|word| word ^ byte_count);
+    if last {
+        v[14] = v[14].map(|word| !word);
+    }
+    for s in SIGMA {
+        g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] = core::array::from_fn(|lane| h[i][lane] ^ v[i][lane] ^ v[i + 8][lane]);
+    }
+}
+
+#[inline(always)]
+fn hash_lanes_inner(words: &[Lanes]) -> [Lanes; 8] {
+    let mut h = IV.map(|word| [word; LANES]);
+    // Digest length of 32 bytes, no key
+    h[0] = h[0].map(|word| word ^ 0x0101_0020);
+
+    let mut blocks = words.chunks(BLOCK_WORDS).peekable();
+    let mut byte_count: u32 = 0;
+    if blocks.peek().is_none() {
+        compress(&mut h, &[[0; LANES]; BLOCK_WORDS], 0, true);
+    }
+    while let Some(block) = blocks.next() {
+        let mut m = [[0; LANES]; BLOCK_WORDS];
+        m[..block.len()].copy_from_slice(block);
+        byte_count += 4 * block.len() as u32;
+        compress(&mut h, &m, byte_count, blocks.peek().is_none());
+    }
+    h
+}
+
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn hash_lanes_avx2(words: &[Lanes]) -> [Lanes; 8] {
+    hash_lanes_inner(words)
+}
+
+/// Blake2s-256 digests of [LANES] messages of `words.len()` little-endian words each, given
+/// word by word; the digests are returned the same way
+pub fn hash_lanes(words: &[Lanes]) -> [Lanes; 8] {
+    #[cfg(all(feature = "std", target_arch = "x86_64"))]
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 is supported by the CPU
+        return unsafe { hash_lanes_avx2(words) };
+    }
+    hash_lanes_inner(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use blake2::{Blake2s256, Digest};
+
+    #[test]
+    fn test_hash_lanes() {
+        // Empty, one block, one full block, several blocks with a partial one
+        for word_count in [0, 1, 16, 20, 33] {
+            let messages: Vec<Vec<u32>> = (0..LANES as u32)
+                .map(|lane| {
+                    (0..word_count as u32)
+                        .map(|i| lane.wrapping_mul(0x9E37_79B9) ^ i)
+                        .collect()
+                })
+                .collect();
+            let words: Vec<Lanes> = (0..word_count)
+                .map(|i| core::array::from_fn(|lane| messages[lane][i]))
+                .collect();
+            let digests = hash_lanes(&words);
+
+            for (lane, message) in messages.iter().enumerate() {
+                let bytes: Vec<u8> = message.iter().flat_map(|w| w.to_le_bytes()).collect();
+                let expected: [u8; 32] = Blake2s256::digest(&bytes).into();
+                let actual: Vec<u8> = digests.iter().flat_map(|w| w[lane].to_le_bytes()).collect();
+                assert_eq!(actual, expected, "{} words, lane {}", word_count, lane);
+            }
+        }
+    }
+}
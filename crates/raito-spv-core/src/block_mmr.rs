@@ -1,25 +1,33 @@
 //! Merkle Mountain Range (MMR) accumulator implementation for Bitcoin block headers with proof generation.
 
+#[cfg(feature = "node")]
 use std::path::Path;
 use std::sync::Arc;
+#[cfg(feature = "node")]
 use tokio::fs;
 
+#[cfg(feature = "node")]
 use accumulators::hasher::stark_blake::StarkBlakeHasher;
 use accumulators::hasher::Hasher;
+#[cfg(feature = "node")]
 use accumulators::mmr::{
     elements_count_to_leaf_count, leaf_count_to_mmr_size, map_leaf_index_to_element_index,
     PeaksOptions, Proof, ProofOptions, MMR,
 };
-use accumulators::store::memory::InMemoryStore;
-use accumulators::store::sqlite::SQLiteStore;
-use accumulators::store::Store;
+#[cfg(feature = "node")]
+use accumulators::store::{memory::InMemoryStore, sqlite::SQLiteStore, Store};
 use bitcoin::block::Header as BlockHeader;
+use bitcoin::consensus;
 use bitcoin::hashes::Hash;
 use serde::{Deserialize, Serialize};
 
-use crate::sparse_roots::SparseRoots;
+use crate::primitives::{
+    self, find_mountain, hash_from_hex, hash_pair, hash_to_hex, hashes_from_hex, mountains,
+};
+use crate::sparse_roots::{deserialize_hash_lists, deserialize_hashes, SparseRoots};
 
 /// MMR accumulator state for Bitcoin block headers
+#[cfg(feature = "node")]
 #[derive(Debug)]
 pub struct BlockMMR {
     hasher: Arc<dyn Hasher>,
@@ -28,12 +36,26 @@ pub struct BlockMMR {
     mmr: MMR,
 }
 
+/// Peaks-only state of the block MMR: enough to append blocks, compute the root and verify
+/// inclusion proofs, without a store nor an async runtime
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockPeaks {
+    /// Peak hashes, from the highest mountain to the lowest
+    peaks: Vec<primitives::Hash>,
+    /// Number of leaves in the MMR
+    leaf_count: usize,
+}
+
 /// Proof data structure for demonstrating inclusion of a block in the MMR
+///
+/// The hashes are normalized when deserialized, and malformed ones are rejected.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockInclusionProof {
     /// MMR peak hashes at the time of proof generation
+    #[serde(deserialize_with = "deserialize_hashes")]
     pub peaks_hashes: Vec<String>,
     /// Sibling hashes needed to reconstruct the path to the root
+    #[serde(deserialize_with = "deserialize_hashes")]
     pub siblings_hashes: Vec<String>,
     /// Leaf index of the block in the MMR (same as block height)
     pub leaf_index: usize,
@@ -41,7 +63,54 @@ pub struct BlockInclusionProof {
     pub leaf_count: usize,
 }
 
+/// Path of a block from its leaf up to its peak in the block MMR, for display
+#[derive(Debug, Clone)]
+pub struct BlockInclusionPath {
+    /// Leaf index of the block in the MMR (same as block height)
+    pub leaf_index: usize,
+    /// Total number of leaves in the MMR
+    pub leaf_count: usize,
+    /// Digest of the block header, see [block_header_digest]
+    pub leaf_hash: String,
+    /// Index of the peak of the mountain containing the leaf, from the highest mountain
+    pub peak_index: usize,
+    /// Steps from the leaf up to the peak
+    pub steps: Vec<PathStep>,
+    /// Whether the node reached at the top of the path matches the peak of the proof
+    pub peak_matches: bool,
+}
+
+/// Step of a path up the MMR: the sibling of the current node and their parent
+#[derive(Debug, Clone)]
+pub struct PathStep {
+    /// Hash of the sibling node
+    pub sibling_hash: String,
+    /// Whether the sibling is the left child of the parent
+    pub sibling_is_left: bool,
+    /// Hash of the parent node
+    pub parent_hash: String,
+}
+
+/// Proof that an earlier state of the block MMR is a prefix of a later one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockConsistencyProof {
+    /// MMR peak hashes of the earlier state
+    #[serde(deserialize_with = "deserialize_hashes")]
+    pub prev_peaks_hashes: Vec<String>,
+    /// Total number of leaves in the earlier state
+    pub prev_leaf_count: usize,
+    /// For each earlier peak, the sibling hashes on its path to a peak of the later state
+    #[serde(deserialize_with = "deserialize_hash_lists")]
+    pub peaks_siblings_hashes: Vec<Vec<String>>,
+    /// MMR peak hashes of the later state
+    #[serde(deserialize_with = "deserialize_hashes")]
+    pub peaks_hashes: Vec<String>,
+    /// Total number of leaves in the later state
+    pub leaf_count: usize,
+}
+
 /// Default accumulator is an in-memory accumulator with StarkBlake hasher
+#[cfg(feature = "node")]
 impl Default for BlockMMR {
     fn default() -> Self {
         let store = Arc::new(InMemoryStore::default());
@@ -50,6 +119,7 @@ impl Default for BlockMMR {
     }
 }
 
+#[cfg(feature = "node")]
 impl BlockMMR {
     /// Create a new default MMR
     pub fn new(store: Arc<dyn Store>, hasher: Arc<dyn Hasher>, mmr_id: Option<String>) -> Self {
@@ -70,6 +140,9 @@ impl BlockMMR {
     }
 
     /// Create in-memory MMR from peaks hashes and elements count
+    ///
+    /// Only needed to generate proofs: [BlockPeaks] appends blocks, computes the root and
+    /// verifies proofs without a store.
     pub async fn from_peaks(
         peaks_hashes: Vec<String>,
         leaf_count: usize,
@@ -93,6 +166,32 @@ impl BlockMMR {
         Ok(())
     }
 
+    /// Add leaves to the MMR, returning the sparse roots of the MMR after each of them
+    ///
+    /// The sparse roots are computed from the peaks in memory, the new nodes being hashed as
+    /// batches (see [BlockPeaks::extend_states]), rather than read back from the store after
+    /// each leaf. The accumulator hashes the nodes it stores itself, its peaks are checked
+    /// against the ones computed in memory once the leaves are added.
+    pub async fn add_leaves(&mut self, leaves: &[String]) -> anyhow::Result<Vec<SparseRoots>> {
+        let hashes = hashes_from_hex(leaves)?;
+        let block_count = self.get_block_count().await? as usize;
+        let mut peaks = match block_count {
+            0 => BlockPeaks::default(),
+            _ => BlockPeaks::new(&self.get_peaks(None).await?, block_count)?,
+        };
+        let states = peaks.extend_states(&hashes);
+        for leaf in &hashes {
+            self.add(hash_to_hex(leaf)).await?;
+        }
+        if !hashes.is_empty() && hashes_from_hex(&self.get_peaks(None).await?)? != peaks.peaks {
+            anyhow::bail!(
+                "MMR peaks at block count {} differ from the ones computed in memory",
+                peaks.leaf_count
+            );
+        }
+        states.iter().map(BlockPeaks::sparse_roots).collect()
+    }
+
     /// Add a block header to the MMR
     pub async fn add_block_header(&mut self, block_header: &BlockHeader) -> anyhow::Result<()> {
         let leaf = block_header_digest(self.hasher.clone(), block_header)?;
@@ -109,19 +208,55 @@ impl BlockMMR {
             .map_err(|e| anyhow::anyhow!("Failed to get block count: {}", e))
     }
 
-    /// Get the roots of the MMR in sparse format (compatible with Cairo implementation)
-    pub async fn get_sparse_roots(&self, chain_height: Option<u32>) -> anyhow::Result<SparseRoots> {
+    /// Get the leaves (block header digests) of the blocks `from_height..from_height + count`,
+    /// from which another MMR with the first `from_height` blocks can be brought to this state
+    pub async fn get_leaves(&self, from_height: u32, count: u32) -> anyhow::Result<Vec<String>> {
+        let block_count = self.get_block_count().await?;
+        if from_height.saturating_add(count) > block_count {
+            anyhow::bail!(
+                "Blocks {}..{} are not all in the MMR of {} blocks",
+                from_height,
+                from_height.saturating_add(count),
+                block_count
+            );
+        }
+        let mut leaves = Vec::with_capacity(count as usize);
+        for height in from_height..from_height + count {
+            let element_index = map_leaf_index_to_element_index(height as usize);
+            let proof = self
+                .mmr
+                .get_proof(element_index, None)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to get leaf {}: {}", height, e))?;
+            leaves.push(proof.element_hash);
+        }
+        Ok(leaves)
+    }
+
+    /// Get the peaks of the MMR at the given chain height (latest state if not provided),
+    /// from the highest mountain to the lowest
+    pub async fn get_peaks(&self, chain_height: Option<u32>) -> anyhow::Result<Vec<String>> {
         let elements_count = match chain_height {
             Some(chain_height) => leaf_count_to_mmr_size(chain_height as usize + 1),
             None => self.mmr.elements_count.get().await?,
         };
-        let roots = self
+        let peaks = self
             .mmr
             .get_peaks(PeaksOptions {
                 elements_count: Some(elements_count),
                 formatting_opts: None,
             })
             .await?;
+        Ok(peaks)
+    }
+
+    /// Get the roots of the MMR in sparse format (compatible with Cairo implementation)
+    pub async fn get_sparse_roots(&self, chain_height: Option<u32>) -> anyhow::Result<SparseRoots> {
+        let elements_count = match chain_height {
+            Some(chain_height) => leaf_count_to_mmr_size(chain_height as usize + 1),
+            None => self.mmr.elements_count.get().await?,
+        };
+        let roots = self.get_peaks(chain_height).await?;
         SparseRoots::try_from_peaks(roots, elements_count)
     }
 
@@ -151,6 +286,59 @@ impl BlockMMR {
         })
     }
 
+    /// Generate a consistency proof between the MMR states at `prev_chain_height` and
+    /// `chain_height` (latest state if not provided).
+    pub async fn generate_consistency_proof(
+        &self,
+        prev_chain_height: u32,
+        chain_height: Option<u32>,
+    ) -> anyhow::Result<BlockConsistencyProof> {
+        let prev_leaf_count = prev_chain_height as usize + 1;
+        let leaf_count = match chain_height {
+            Some(chain_height) => chain_height as usize + 1,
+            None => self.get_block_count().await? as usize,
+        };
+        if prev_leaf_count > leaf_count {
+            anyhow::bail!(
+                "Earlier chain height {} is above the chain height {}",
+                prev_chain_height,
+                leaf_count - 1
+            );
+        }
+        let prev_peaks_hashes = self
+            .mmr
+            .get_peaks(PeaksOptions {
+                elements_count: Some(leaf_count_to_mmr_size(prev_leaf_count)),
+                formatting_opts: None,
+            })
+            .await?;
+        let peaks_hashes = self
+            .mmr
+            .get_peaks(PeaksOptions {
+                elements_count: Some(leaf_count_to_mmr_size(leaf_count)),
+                formatting_opts: None,
+            })
+            .await?;
+
+        // The path from an earlier peak to a later one is the upper part of the path
+        // of any leaf below that earlier peak
+        let mut peaks_siblings_hashes = Vec::with_capacity(prev_peaks_hashes.len());
+        for (offset, height) in mountains(prev_leaf_count) {
+            let proof = self
+                .generate_proof(offset as u32, Some(leaf_count as u32 - 1))
+                .await?;
+            peaks_siblings_hashes.push(proof.siblings_hashes[height..].to_vec());
+        }
+
+        Ok(BlockConsistencyProof {
+            prev_peaks_hashes,
+            prev_leaf_count,
+            peaks_siblings_hashes,
+            peaks_hashes,
+            leaf_count,
+        })
+    }
+
     /// Verify an inclusion proof for a given block height and block header
     /// NOTE that this only guarantees that the block was included in the MMR with the known peaks hashes.
     /// In order to verify the correctness you have to compute the root hash of the MMR and compare it with the commitеed root.
@@ -185,16 +373,361 @@ impl BlockMMR {
 
     /// Get the root hash of the MMR (compatible with Cairo implementation)
     pub async fn get_root_hash(&self, block_count: Option<u32>) -> anyhow::Result<String> {
-        let SparseRoots {
-            block_height: _,
-            roots,
-        } = self.get_sparse_roots(block_count).await?;
+        let SparseRoots { roots, .. } = self.get_sparse_roots(block_count).await?;
         self.hasher
             .hash(roots)
             .map_err(|e| anyhow::anyhow!("Failed to get root hash: {}", e))
     }
 }
 
+impl BlockPeaks {
+    /// Create from the peak hashes of an MMR of `leaf_count` leaves, from the highest
+    /// mountain to the lowest
+    pub fn new(peaks_hashes: &[String], leaf_count: usize) -> anyhow::Result<Self> {
+        let peaks = hashes_from_hex(peaks_hashes)?;
+        if peaks.len() != leaf_count.count_ones() as usize {
+            return Err(primitives::Error::PeaksCountMismatch {
+                leaf_count,
+                expected: leaf_count.count_ones() as usize,
+                actual: peaks.len(),
+            }
+            .into());
+        }
+        Ok(Self { peaks, leaf_count })
+    }
+
+    /// Peaks committed to by an inclusion proof
+    pub fn from_proof(proof: &BlockInclusionProof) -> anyhow::Result<Self> {
+        Self::new(&proof.peaks_hashes, proof.leaf_count)
+    }
+
+    /// Number of leaves (blocks) in the MMR
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Peak hashes, from the highest mountain to the lowest
+    pub fn peaks_hashes(&self) -> Vec<String> {
+        self.peaks.iter().map(hash_to_hex).collect()
+    }
+
+    /// Append a leaf, merging the mountains of equal height
+    pub fn append(&mut self, leaf: primitives::Hash) {
+        let mut node = leaf;
+        let mut height = 0;
+        while self.leaf_count & (1 << height) != 0 {
+            let left = self.peaks.pop().expect("one peak per mountain");
+            node = hash_pair(&left, &node);
+            height += 1;
+        }
+        self.peaks.push(node);
+        self.leaf_count += 1;
+    }
+
+    /// Append leaves, hashing the new nodes of each height as a batch (see
+    /// [primitives::hash_pairs])
+    pub fn extend(&mut self, leaves: &[primitives::Hash]) {
+        // New peaks, from the lowest mountain to the highest
+        let mut new_peaks = Vec::new();
+        // Nodes of the current height left to merge, from left to right
+        let mut nodes = leaves.to_vec();
+        let mut height = 0;
+        while !nodes.is_empty() {
+            if self.leaf_count & (1 << height) != 0 {
+                nodes.insert(0, self.peaks.pop().expect("one peak per mountain"));
+            }
+            if nodes.len() % 2 == 1 {
+                new_peaks.push(nodes.pop().expect("odd number of nodes"));
+            }
+            let pairs: Vec<_> = nodes.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+            nodes = primitives::hash_pairs(&pairs);
+            height += 1;
+        }
+        self.peaks.extend(new_peaks.into_iter().rev());
+        self.leaf_count += leaves.len();
+    }
+
+    /// Append leaves like [BlockPeaks::extend], returning the peaks of the MMR after each of
+    /// them
+    ///
+    /// The new nodes of each height are hashed as a batch: the peaks of every intermediate
+    /// state are among them or the peaks of the initial state.
+    pub fn extend_states(&mut self, leaves: &[primitives::Hash]) -> Vec<BlockPeaks> {
+        if leaves.is_empty() {
+            return Vec::new();
+        }
+        let start = self.leaf_count;
+        let end = start + leaves.len();
+        // Nodes of each height from the peak of the initial state at that height if any, by
+        // index among the nodes of that height
+        let heights = (usize::BITS - end.leading_zeros()) as usize;
+        let mut levels: Vec<(usize, Vec<primitives::Hash>)> = (0..heights)
+            .map(|height| (start >> height, Vec::new()))
+            .collect();
+        for ((offset, height), peak) in mountains(start).zip(&self.peaks) {
+            levels[height] = (offset >> height, vec![*peak]);
+        }
+        levels[0].1.extend_from_slice(leaves);
+
+        // Parents completed by the new leaves, whose left child may be a peak of the initial
+        // state
+        for height in 1..heights {
+            let (first, children) = &levels[height - 1];
+            let pairs: Vec<_> = (start >> height..end >> height)
+                .map(|index| {
+                    let left = 2 * index - first;
+                    (children[left], children[left + 1])
+                })
+                .collect();
+            levels[height].1.extend(primitives::hash_pairs(&pairs));
+        }
+
+        // The peak of the mountain completed by each leaf replaces the ones it merges
+        let mut peaks = self.peaks.clone();
+        let states: Vec<BlockPeaks> = (start..end)
+            .map(|leaf_index| {
+                let height = leaf_index.trailing_ones() as usize;
+                peaks.truncate(peaks.len() - height);
+                let (first, nodes) = &levels[height];
+                peaks.push(nodes[((leaf_index + 1) >> height) - 1 - first]);
+                BlockPeaks {
+                    peaks: peaks.clone(),
+                    leaf_count: leaf_index + 1,
+                }
+            })
+            .collect();
+        *self = states.last().expect("at least one leaf").clone();
+        states
+    }
+
+    /// Append a block header
+    pub fn add_block_header(&mut self, block_header: &BlockHeader) {
+        self.append(header_digest(block_header));
+    }
+
+    /// Append block headers, their digests and the new nodes are hashed as batches
+    pub fn add_block_headers(&mut self, block_headers: &[BlockHeader]) {
+        let encoded: Vec<[u8; 80]> = block_headers.iter().map(encode_header).collect();
+        self.extend(&primitives::block_header_digests(&encoded));
+    }
+
+    /// Roots in sparse format (compatible with Cairo implementation)
+    pub fn sparse_roots(&self) -> anyhow::Result<SparseRoots> {
+        let roots = primitives::sparse_roots(&self.peaks, self.leaf_count)?;
+        Ok(SparseRoots {
+            roots: roots.iter().map(hash_to_hex).collect(),
+            block_height: self.leaf_count as u32 - 1,
+            block: None,
+        })
+    }
+
+    /// Root hash of the MMR (compatible with Cairo implementation)
+    pub fn root_hash(&self) -> anyhow::Result<String> {
+        let root_hash = primitives::root_hash_from_peaks(&self.peaks, self.leaf_count)?;
+        Ok(hash_to_hex(&root_hash))
+    }
+
+    /// Verify that a block header is included in this MMR with the siblings of the proof
+    ///
+    /// The peaks of the proof are not used: a proof for another MMR state doesn't verify.
+    pub fn verify_block_inclusion(
+        &self,
+        block_header: &BlockHeader,
+        proof: &BlockInclusionProof,
+    ) -> anyhow::Result<bool> {
+        if proof.leaf_count != self.leaf_count {
+            return Ok(false);
+        }
+        Ok(primitives::verify_inclusion(
+            &self.peaks,
+            &header_digest(block_header),
+            proof.leaf_index,
+            self.leaf_count,
+            &hashes_from_hex(&proof.siblings_hashes)?,
+        )?)
+    }
+}
+
+/// Verify that a leaf is included in an MMR with the given peaks.
+///
+/// Unlike [BlockMMR::verify_proof], this is a pure function: it does not reconstruct the MMR,
+/// and needs neither a store nor an async runtime.
+///
+/// # Arguments
+/// * `peaks` - The MMR peak hashes, from the highest mountain to the lowest
+/// * `leaf_digest` - The leaf value, see [block_header_digest]
+/// * `proof` - The inclusion proof of the leaf (its peak hashes are not used)
+///
+/// # Returns
+/// * `bool` - Whether the leaf is included in the MMR
+/// * `anyhow::Error` - If the proof does not match the MMR size or hashing fails
+pub fn verify_inclusion(
+    peaks: &[String],
+    leaf_digest: &str,
+    proof: &BlockInclusionProof,
+) -> anyhow::Result<bool> {
+    verify_node_inclusion(
+        peaks,
+        leaf_digest,
+        0,
+        proof.leaf_index,
+        proof.leaf_count,
+        &proof.siblings_hashes,
+    )
+}
+
+/// Verify that a node of the given height, covering the leaf at `leaf_index`, is included in
+/// an MMR of `leaf_count` leaves with the given peaks, see [primitives::verify_node_inclusion].
+fn verify_node_inclusion(
+    peaks: &[String],
+    node_hash: &str,
+    node_height: usize,
+    leaf_index: usize,
+    leaf_count: usize,
+    siblings_hashes: &[String],
+) -> anyhow::Result<bool> {
+    Ok(primitives::verify_node_inclusion(
+        &hashes_from_hex(peaks)?,
+        &hash_from_hex(node_hash)?,
+        node_height,
+        leaf_index,
+        leaf_count,
+        &hashes_from_hex(siblings_hashes)?,
+    )?)
+}
+
+/// Verify that an earlier state of the block MMR is a prefix of a later one, i.e. that every
+/// earlier peak is a node of the later MMR.
+///
+/// This only checks the proof against its own peaks: compare the root hash of the earlier
+/// peaks (see [root_hash_from_peaks]) with a trusted root to make it meaningful.
+///
+/// # Returns
+/// * `bool` - Whether the earlier MMR state is a prefix of the later one
+/// * `anyhow::Error` - If the proof is malformed or hashing fails
+pub fn verify_consistency(proof: &BlockConsistencyProof) -> anyhow::Result<bool> {
+    let BlockConsistencyProof {
+        prev_peaks_hashes,
+        prev_leaf_count,
+        peaks_siblings_hashes,
+        peaks_hashes,
+        leaf_count,
+    } = proof;
+    if prev_leaf_count > leaf_count {
+        anyhow::bail!(
+            "Earlier MMR state has more leaves than the later one: {} > {}",
+            prev_leaf_count,
+            leaf_count
+        );
+    }
+    if prev_peaks_hashes.len() != prev_leaf_count.count_ones() as usize
+        || peaks_siblings_hashes.len() != prev_peaks_hashes.len()
+    {
+        anyhow::bail!(
+            "Expected {} earlier peaks with their siblings for {} leaves, got {} and {}",
+            prev_leaf_count.count_ones(),
+            prev_leaf_count,
+            prev_peaks_hashes.len(),
+            peaks_siblings_hashes.len()
+        );
+    }
+
+    for (((offset, height), prev_peak), siblings) in mountains(*prev_leaf_count)
+        .zip(prev_peaks_hashes)
+        .zip(peaks_siblings_hashes)
+    {
+        if !verify_node_inclusion(
+            peaks_hashes,
+            prev_peak,
+            height,
+            offset,
+            *leaf_count,
+            siblings,
+        )? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Verify that a block header is included in the block MMR committed to by the proof peaks,
+/// see [verify_inclusion].
+pub fn verify_block_inclusion(
+    block_header: &BlockHeader,
+    proof: &BlockInclusionProof,
+) -> anyhow::Result<bool> {
+    Ok(primitives::verify_inclusion(
+        &hashes_from_hex(&proof.peaks_hashes)?,
+        &header_digest(block_header),
+        proof.leaf_index,
+        proof.leaf_count,
+        &hashes_from_hex(&proof.siblings_hashes)?,
+    )?)
+}
+
+/// Compute the path of a block header from its leaf up to its peak, to display what an
+/// inclusion proof proves. Fails if the proof doesn't have one sibling per level of the
+/// mountain containing the leaf.
+pub fn block_inclusion_path(
+    block_header: &BlockHeader,
+    proof: &BlockInclusionProof,
+) -> anyhow::Result<BlockInclusionPath> {
+    if proof.leaf_index >= proof.leaf_count {
+        anyhow::bail!(
+            "Leaf index {} is out of range for {} leaves",
+            proof.leaf_index,
+            proof.leaf_count
+        );
+    }
+    let (peak_index, (offset, height)) =
+        find_mountain(proof.leaf_index, proof.leaf_count).expect("leaf index is in range");
+    if proof.siblings_hashes.len() != height {
+        anyhow::bail!(
+            "Expected {} siblings for leaf {}, got {}",
+            height,
+            proof.leaf_index,
+            proof.siblings_hashes.len()
+        );
+    }
+
+    let leaf_hash = header_digest(block_header);
+    let index = proof.leaf_index - offset;
+    let path =
+        primitives::hash_to_peak(&leaf_hash, index, &hashes_from_hex(&proof.siblings_hashes)?);
+    let top_hash = hash_to_hex(path.last().unwrap_or(&leaf_hash));
+    let peak_matches = proof.peaks_hashes.get(peak_index) == Some(&top_hash);
+    let steps = proof
+        .siblings_hashes
+        .iter()
+        .zip(path)
+        .enumerate()
+        .map(|(level, (sibling_hash, parent_hash))| PathStep {
+            sibling_hash: sibling_hash.clone(),
+            sibling_is_left: (index >> level) % 2 == 1,
+            parent_hash: hash_to_hex(&parent_hash),
+        })
+        .collect();
+    Ok(BlockInclusionPath {
+        leaf_index: proof.leaf_index,
+        leaf_count: proof.leaf_count,
+        leaf_hash: hash_to_hex(&leaf_hash),
+        peak_index,
+        steps,
+        peak_matches,
+    })
+}
+
+/// Compute the root hash of an MMR from its peaks (compatible with Cairo implementation)
+/// without reconstructing the MMR.
+///
+/// # Arguments
+/// * `peaks` - The MMR peak hashes, from the highest mountain to the lowest
+/// * `leaf_count` - The number of leaves in the MMR
+pub fn root_hash_from_peaks(peaks: Vec<String>, leaf_count: usize) -> anyhow::Result<String> {
+    let root_hash = primitives::root_hash_from_peaks(&hashes_from_hex(&peaks)?, leaf_count)?;
+    Ok(hash_to_hex(&root_hash))
+}
+
 /// Compute the digest of a block header using the specified hasher
 ///
 /// # Arguments
@@ -224,10 +757,39 @@ pub fn block_header_digest(
         .map_err(|e| anyhow::anyhow!("Failed to hash block header: {}", e))
 }
 
-#[cfg(test)]
+/// Compute the digest of a block header with the Cairo compatible hasher,
+/// see [primitives::block_header_digest]
+fn header_digest(block_header: &BlockHeader) -> primitives::Hash {
+    primitives::block_header_digest(&encode_header(block_header))
+}
+
+/// MMR leaf of a block header, same as [block_header_digest] without a hasher
+pub fn block_header_leaf(block_header: &BlockHeader) -> String {
+    primitives::hash_to_hex(&header_digest(block_header))
+}
+
+/// MMR leaves of block headers, see [block_header_leaf]; the headers are hashed as batches
+pub fn block_header_leaves(block_headers: &[BlockHeader]) -> Vec<String> {
+    let encoded: Vec<[u8; 80]> = block_headers.iter().map(encode_header).collect();
+    primitives::block_header_digests(&encoded)
+        .iter()
+        .map(primitives::hash_to_hex)
+        .collect()
+}
+
+/// Consensus encoding of a block header
+fn encode_header(block_header: &BlockHeader) -> [u8; 80] {
+    consensus::serialize(block_header)
+        .try_into()
+        .expect("block headers are 80 bytes")
+}
+
+#[cfg(all(test, feature = "node"))]
 mod tests {
     use super::*;
 
+    const NULL_HASH: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
     #[tokio::test]
     async fn test_mmr_add() {
         let mut mmr = BlockMMR::default();
@@ -238,6 +800,7 @@ mod tests {
         let SparseRoots {
             block_height,
             roots,
+            ..
         } = mmr.get_sparse_roots(None).await.unwrap();
         assert_eq!(roots.len(), 2);
         assert_eq!(block_height, 0);
@@ -255,6 +818,7 @@ mod tests {
         let SparseRoots {
             block_height,
             roots,
+            ..
         } = mmr.get_sparse_roots(None).await.unwrap();
         assert_eq!(roots.len(), 3);
         assert_eq!(block_height, 1);
@@ -276,6 +840,7 @@ mod tests {
         let SparseRoots {
             block_height,
             roots,
+            ..
         } = mmr.get_sparse_roots(None).await.unwrap();
         assert_eq!(roots.len(), 3);
         assert_eq!(block_height, 2);
@@ -297,6 +862,7 @@ mod tests {
         let SparseRoots {
             block_height,
             roots,
+            ..
         } = mmr.get_sparse_roots(None).await.unwrap();
         assert_eq!(roots.len(), 4);
         assert_eq!(block_height, 3);
@@ -322,6 +888,7 @@ mod tests {
         let SparseRoots {
             block_height,
             roots,
+            ..
         } = mmr.get_sparse_roots(None).await.unwrap();
         assert_eq!(roots.len(), 4);
         assert_eq!(block_height, 4);
@@ -364,6 +931,7 @@ mod tests {
             digest,
             "0x50b005dd2964720fcd066875bc1cf13a06703a5c8efe8b02a1fd7ea902050f09"
         );
+        assert_eq!(block_header_leaf(&block_header), digest);
     }
 
     #[test]
@@ -428,6 +996,307 @@ mod tests {
         assert!(view_mmr.verify_proof(&block_header, proof).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_block_inclusion_path() {
+        let mut mmr = BlockMMR::default();
+        let block_header = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header;
+        for _ in 0..10 {
+            mmr.add_block_header(&block_header).await.unwrap();
+        }
+
+        // Leaf 5 (0b101) is in the first mountain of 8 leaves
+        let proof = mmr.generate_proof(5, None).await.unwrap();
+        let path = block_inclusion_path(&block_header, &proof).unwrap();
+        assert!(path.peak_matches);
+        assert_eq!(path.peak_index, 0);
+        assert_eq!(
+            path.steps
+                .iter()
+                .map(|step| step.sibling_is_left)
+                .collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+        assert_eq!(path.steps[2].parent_hash, proof.peaks_hashes[0]);
+
+        let mut tampered = proof.clone();
+        tampered.siblings_hashes[1] = tampered.siblings_hashes[0].clone();
+        assert!(
+            !block_inclusion_path(&block_header, &tampered)
+                .unwrap()
+                .peak_matches
+        );
+        tampered.siblings_hashes.pop();
+        assert!(block_inclusion_path(&block_header, &tampered).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_inclusion() {
+        let mut mmr = BlockMMR::default();
+        let leaf = "0xc713e33d89122b85e2f646cc518c2e6ef88b06d3b016104faa95f84f878dab66".to_string();
+        let other =
+            "0x0000000000000000000000000000000000000000000000000000000000000001".to_string();
+        // Distinct leaves, so that a wrong position fails verification
+        for i in 0..11 {
+            mmr.add(if i == 6 { leaf.clone() } else { other.clone() })
+                .await
+                .unwrap();
+        }
+
+        let proof = mmr.generate_proof(6, None).await.unwrap();
+        assert!(verify_inclusion(&proof.peaks_hashes, &leaf, &proof).unwrap());
+        assert!(!verify_inclusion(&proof.peaks_hashes, &other, &proof).unwrap());
+
+        // Same leaf claimed at a different position
+        let moved = BlockInclusionProof {
+            leaf_index: 7,
+            ..proof.clone()
+        };
+        assert!(!verify_inclusion(&proof.peaks_hashes, &leaf, &moved).unwrap());
+
+        // Root hash matches the one of the full MMR
+        assert_eq!(
+            root_hash_from_peaks(proof.peaks_hashes.clone(), proof.leaf_count).unwrap(),
+            mmr.get_root_hash(None).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consistency_proof() {
+        let mut mmr = BlockMMR::default();
+        for i in 0..13u8 {
+            mmr.add(format!("0x{}", hex::encode([i; 32])))
+                .await
+                .unwrap();
+        }
+
+        for prev_chain_height in [0, 5, 7, 12] {
+            let proof = mmr
+                .generate_consistency_proof(prev_chain_height, Some(11))
+                .await;
+            if prev_chain_height > 11 {
+                assert!(proof.is_err());
+                continue;
+            }
+            let proof = proof.unwrap();
+            assert!(verify_consistency(&proof).unwrap());
+            assert_eq!(
+                root_hash_from_peaks(proof.prev_peaks_hashes.clone(), proof.prev_leaf_count)
+                    .unwrap(),
+                mmr.get_root_hash(Some(prev_chain_height)).await.unwrap()
+            );
+        }
+
+        // Earlier peak that is not part of the later MMR
+        let mut proof = mmr.generate_consistency_proof(5, None).await.unwrap();
+        proof.prev_peaks_hashes[1] = proof.prev_peaks_hashes[0].clone();
+        assert!(!verify_consistency(&proof).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_get_leaves() {
+        let mut mmr = BlockMMR::default();
+        let leaves: Vec<String> = (0..13u8)
+            .map(|i| format!("0x{}", hex::encode([i; 32])))
+            .collect();
+        for leaf in &leaves {
+            mmr.add(leaf.clone()).await.unwrap();
+        }
+        assert_eq!(mmr.get_leaves(0, 13).await.unwrap(), leaves);
+        assert!(mmr.get_leaves(5, 9).await.is_err());
+
+        // A replica of the first blocks reaches the same state from the other leaves
+        let mut replica = BlockMMR::default();
+        for leaf in &leaves[..5] {
+            replica.add(leaf.clone()).await.unwrap();
+        }
+        for leaf in mmr.get_leaves(5, 8).await.unwrap() {
+            replica.add(leaf).await.unwrap();
+        }
+        assert_eq!(
+            replica.get_root_hash(None).await.unwrap(),
+            mmr.get_root_hash(None).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_peaks() {
+        let mut mmr = BlockMMR::default();
+        let mut peaks = BlockPeaks::default();
+        let mut block_header = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header;
+        for nonce in 0..13 {
+            // Distinct headers, so that misplaced nodes change the roots
+            block_header.nonce = nonce;
+            mmr.add_block_header(&block_header).await.unwrap();
+            peaks.add_block_header(&block_header);
+
+            assert_eq!(peaks.peaks_hashes(), mmr.get_peaks(None).await.unwrap());
+            assert_eq!(
+                peaks.sparse_roots().unwrap().roots,
+                mmr.get_sparse_roots(None).await.unwrap().roots
+            );
+            assert_eq!(
+                peaks.root_hash().unwrap(),
+                mmr.get_root_hash(None).await.unwrap()
+            );
+        }
+
+        block_header.nonce = 5;
+        let proof = mmr.generate_proof(5, None).await.unwrap();
+        assert_eq!(BlockPeaks::from_proof(&proof).unwrap(), peaks);
+        assert!(peaks.verify_block_inclusion(&block_header, &proof).unwrap());
+        block_header.nonce = 6;
+        assert!(!peaks.verify_block_inclusion(&block_header, &proof).unwrap());
+
+        // Proof for an earlier MMR state
+        block_header.nonce = 5;
+        let proof = mmr.generate_proof(5, Some(7)).await.unwrap();
+        assert!(!peaks.verify_block_inclusion(&block_header, &proof).unwrap());
+        assert!(BlockPeaks::from_proof(&proof)
+            .unwrap()
+            .verify_block_inclusion(&block_header, &proof)
+            .unwrap());
+
+        assert!(BlockPeaks::new(&proof.peaks_hashes, 9).is_err());
+    }
+
+    #[test]
+    fn test_block_peaks_batch() {
+        let mut block_header = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header;
+        let headers: Vec<BlockHeader> = (0..40)
+            .map(|nonce| {
+                block_header.nonce = nonce;
+                block_header
+            })
+            .collect();
+        let mut expected = BlockPeaks::default();
+        for header in &headers {
+            expected.add_block_header(header);
+        }
+
+        // Batches starting at every MMR state
+        for split in 0..headers.len() {
+            let mut peaks = BlockPeaks::default();
+            peaks.add_block_headers(&headers[..split]);
+            peaks.add_block_headers(&headers[split..split]);
+            peaks.add_block_headers(&headers[split..]);
+            assert_eq!(peaks, expected, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn test_block_peaks_states() {
+        let leaves: Vec<primitives::Hash> = (0..40u8).map(|i| [i; 32]).collect();
+        let mut expected = vec![BlockPeaks::default()];
+        for leaf in &leaves {
+            let mut peaks = expected.last().unwrap().clone();
+            peaks.append(*leaf);
+            expected.push(peaks);
+        }
+
+        // Batches starting and ending at every MMR state
+        for start in 0..leaves.len() {
+            for end in start..=leaves.len() {
+                let mut peaks = expected[start].clone();
+                let states = peaks.extend_states(&leaves[start..end]);
+                assert_eq!(states, expected[start + 1..=end], "{}..{}", start, end);
+                assert_eq!(peaks, expected[end], "{}..{}", start, end);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_leaves() {
+        let mut block_header = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header;
+        let headers: Vec<BlockHeader> = (0..13)
+            .map(|nonce| {
+                block_header.nonce = nonce;
+                block_header
+            })
+            .collect();
+        let leaves = block_header_leaves(&headers);
+        assert_eq!(leaves[5], block_header_leaf(&headers[5]));
+
+        let mut expected = BlockMMR::default();
+        let mut mmr = BlockMMR::default();
+        for batch in [&leaves[..1], &leaves[1..1], &leaves[1..6], &leaves[6..]] {
+            let roots = mmr.add_leaves(batch).await.unwrap();
+            assert_eq!(roots.len(), batch.len());
+            for (leaf, roots) in batch.iter().zip(roots) {
+                expected.add(leaf.clone()).await.unwrap();
+                assert_eq!(
+                    roots.roots,
+                    expected.get_sparse_roots(None).await.unwrap().roots
+                );
+            }
+        }
+        assert_eq!(
+            mmr.get_root_hash(None).await.unwrap(),
+            expected.get_root_hash(None).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proof_hashes_deserialization() {
+        let mut mmr = BlockMMR::default();
+        let leaf = "0xc713e33d89122b85e2f646cc518c2e6ef88b06d3b016104faa95f84f878dab66".to_string();
+        for _ in 0..3 {
+            mmr.add(leaf.clone()).await.unwrap();
+        }
+        let proof = mmr.generate_proof(0, None).await.unwrap();
+
+        // Uppercase digits and missing prefixes are normalized
+        let mut json = serde_json::to_value(&proof).unwrap();
+        json["peaks_hashes"][0] = proof.peaks_hashes[0].to_uppercase().into();
+        json["siblings_hashes"][0] = proof.siblings_hashes[0][2..].into();
+        let normalized: BlockInclusionProof = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(normalized.peaks_hashes, proof.peaks_hashes);
+        assert_eq!(normalized.siblings_hashes, proof.siblings_hashes);
+
+        // Truncated and non-hex hashes are rejected
+        json["peaks_hashes"][1] = "0x1234".into();
+        let err = serde_json::from_value::<BlockInclusionProof>(json.clone()).unwrap_err();
+        assert!(err.to_string().contains("expected 64 hex digits, got 4"));
+        json["peaks_hashes"][1] = leaf.replace('c', "z").into();
+        let err = serde_json::from_value::<BlockInclusionProof>(json).unwrap_err();
+        assert!(err.to_string().contains("'z' is not a hex digit"));
+
+        let proof = mmr.generate_consistency_proof(0, None).await.unwrap();
+        let mut json = serde_json::to_value(&proof).unwrap();
+        json["peaks_siblings_hashes"][0][0] = "0xabc".into();
+        assert!(serde_json::from_value::<BlockConsistencyProof>(json).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sparse_roots_deserialization() {
+        let mut mmr = BlockMMR::default();
+        let leaf = "0xc713e33d89122b85e2f646cc518c2e6ef88b06d3b016104faa95f84f878dab66".to_string();
+        for _ in 0..5 {
+            mmr.add(leaf.clone()).await.unwrap();
+        }
+        let sparse_roots = mmr.get_sparse_roots(None).await.unwrap();
+
+        // As serialized, roots are u256
+        let json = serde_json::to_string(&sparse_roots).unwrap();
+        let roundtrip: SparseRoots = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip.roots, sparse_roots.roots);
+        assert_eq!(roundtrip.block_height, 4);
+
+        let json = serde_json::json!({ "roots": [leaf.to_uppercase(), NULL_HASH] });
+        let from_hex: SparseRoots = serde_json::from_value(json).unwrap();
+        assert_eq!(from_hex.roots, vec![leaf.clone(), NULL_HASH.into()]);
+        assert_eq!(from_hex.block_height, 0);
+
+        let json = serde_json::json!({ "roots": [leaf, NULL_HASH, NULL_HASH] });
+        assert!(serde_json::from_value::<SparseRoots>(json).is_err());
+
+        let json = serde_json::json!({ "roots": ["0x1234"] });
+        assert!(serde_json::from_value::<SparseRoots>(json).is_err());
+        let json = serde_json::json!({ "roots": [NULL_HASH] });
+        assert!(serde_json::from_value::<SparseRoots>(json).is_err());
+        let json = r#"{"roots": [{"hi": 1}]}"#;
+        assert!(serde_json::from_str::<SparseRoots>(json).is_err());
+    }
+
     #[tokio::test]
     async fn test_root_hash() {
         let mut mmr = BlockMMR::default();
@@ -3,7 +3,34 @@
 //! This crate provides shared functionality for both the bridge node and client,
 //! including Bitcoin RPC client, MMR (Merkle Mountain Range) accumulator, and
 //! sparse roots representation.
+//!
+//! The Bitcoin RPC client and the persistent MMR accumulator require the `node` feature
+//! (enabled by default); without it only the pure proof verification functions are built,
+//! which compile to WebAssembly.
+//!
+//! The [primitives] module contains the verification math on plain byte arrays. It only needs
+//! `core` and `alloc`: without the `std` feature (enabled by default) the crate is `no_std`
+//! and contains nothing else.
+//!
+//! The `simd` feature hashes the batches of the [primitives] (e.g.
+//! [primitives::block_header_digests]) several messages at once with a multi-buffer Blake2s,
+//! which vectorizes on x86-64 and aarch64.
+//!
+//! The `test-utils` feature provides mock Bitcoin RPC and Raito RPC servers for integration
+//! tests, see [test_utils].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "node")]
 pub mod bitcoin;
+#[cfg(feature = "simd")]
+mod blake2s_lanes;
+#[cfg(feature = "std")]
 pub mod block_mmr;
+pub mod primitives;
+#[cfg(feature = "std")]
 pub mod sparse_roots;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
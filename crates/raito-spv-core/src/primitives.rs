@@ -0,0 +1,719 @@
+//! Pure verification math of Raito proofs: block header digests, MMR path recomputation,
+//! sparse roots bagging and proof-of-work arithmetic.
+//!
+//! This module depends only on `core` and `alloc`, so that it can be reused in constrained
+//! environments such as zkVM guests or embedded signers: with `default-features = false`
+//! the crate is `no_std` and contains nothing else. Hashes are 32-byte arrays in the order
+//! of their `0x`-prefixed hex representation, and every hash is compatible with the Cairo
+//! implementation (and with the `StarkBlakeHasher` of the MMR accumulator).
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use blake2::{Blake2s256, Digest};
+
+/// Hash of an MMR node, in the order of its hex representation
+pub type Hash = [u8; 32];
+
+/// Placeholder of the missing peaks in the sparse roots
+pub const NULL_ROOT: Hash = [0; 32];
+
+/// Failure of a verification function, when its inputs are inconsistent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The leaf index is not lower than the number of leaves
+    LeafIndexOutOfRange {
+        leaf_index: usize,
+        leaf_count: usize,
+    },
+    /// The number of peaks doesn't match the number of leaves
+    PeaksCountMismatch {
+        leaf_count: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The MMR has no leaves
+    EmptyMmr,
+    /// A hash doesn't have 64 hex digits (32 bytes)
+    InvalidHashLength { hash: String, digits: usize },
+    /// A hash contains a character that is not a hex digit
+    InvalidHashDigit { hash: String, digit: char },
+    /// The number of sparse roots doesn't match their non-null roots
+    SparseRootsCountMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LeafIndexOutOfRange {
+                leaf_index,
+                leaf_count,
+            } => write!(
+                f,
+                "Leaf index {} is out of range for {} leaves",
+                leaf_index, leaf_count
+            ),
+            Error::PeaksCountMismatch {
+                leaf_count,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Expected {} peaks for {} leaves, got {}",
+                expected, leaf_count, actual
+            ),
+            Error::EmptyMmr => write!(f, "MMR has no leaves"),
+            Error::InvalidHashLength { hash, digits } => write!(
+                f,
+                "Invalid hash {}: expected 64 hex digits, got {}",
+                hash, digits
+            ),
+            Error::InvalidHashDigit { hash, digit } => {
+                write!(f, "Invalid hash {}: {:?} is not a hex digit", hash, digit)
+            }
+            Error::SparseRootsCountMismatch { expected, actual } => {
+                write!(f, "Expected {} sparse roots, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Parse a hex string of 32 bytes, with an optional `0x` prefix and digits of any case
+pub fn hash_from_hex(s: &str) -> Result<Hash, Error> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    if let Some(digit) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidHashDigit {
+            hash: s.into(),
+            digit,
+        });
+    }
+    if digits.len() != 64 {
+        return Err(Error::InvalidHashLength {
+            hash: s.into(),
+            digits: digits.len(),
+        });
+    }
+    let mut hash = NULL_ROOT;
+    for (byte, i) in hash.iter_mut().zip((0..64).step_by(2)) {
+        *byte = u8::from_str_radix(&digits[i..i + 2], 16).expect("hex digits");
+    }
+    Ok(hash)
+}
+
+/// Parse a list of `0x`-prefixed hex strings of 32 bytes
+pub fn hashes_from_hex<S: AsRef<str>>(hashes: &[S]) -> Result<Vec<Hash>, Error> {
+    hashes
+        .iter()
+        .map(|hash| hash_from_hex(hash.as_ref()))
+        .collect()
+}
+
+/// Normalize a hash to its canonical representation: a `0x`-prefixed lowercase hex string
+/// of 32 bytes
+pub fn normalize_hash(s: &str) -> Result<String, Error> {
+    hash_from_hex(s).map(|hash| hash_to_hex(&hash))
+}
+
+/// Format a hash as a `0x`-prefixed lowercase hex string
+pub fn hash_to_hex(hash: &Hash) -> String {
+    let mut s = String::with_capacity(66);
+    s.push_str("0x");
+    for byte in hash {
+        write!(s, "{:02x}", byte).expect("writing to a string never fails");
+    }
+    s
+}
+
+/// Blake2s digest of the concatenation of `chunks`, whose 4-byte words are hashed as
+/// little-endian u32 words the way the Cairo implementation does
+fn blake2s<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Hash {
+    let mut hasher = Blake2s256::new();
+    for chunk in chunks {
+        for word in chunk.chunks(4) {
+            let mut word: [u8; 4] = word.try_into().expect("chunks are made of 4-byte words");
+            word.reverse();
+            hasher.update(word);
+        }
+    }
+    let mut hash: Hash = hasher.finalize().into();
+    for word in hash.chunks_mut(4) {
+        word.reverse();
+    }
+    hash
+}
+
+/// Hash a sequence of MMR nodes
+pub fn hash_nodes(nodes: &[Hash]) -> Hash {
+    blake2s(nodes.iter().map(|node| node.as_slice()))
+}
+
+/// Hash the children of an MMR node into their parent
+pub fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    blake2s([left.as_slice(), right.as_slice()])
+}
+
+/// Hash the children of many MMR nodes into their parents, see [hash_pair]
+///
+/// With the `simd` feature, several pairs are hashed at once.
+pub fn hash_pairs(pairs: &[(Hash, Hash)]) -> Vec<Hash> {
+    blake2s_batch(pairs, |(left, right)| [*left, *right])
+}
+
+/// Digest of a block header, the leaf of the block in the block MMR
+///
+/// # Arguments
+/// * `header` - The consensus encoding of the block header
+pub fn block_header_digest(header: &[u8; 80]) -> Hash {
+    blake2s([header_words(header).as_slice()])
+}
+
+/// Digests of many block headers, see [block_header_digest]
+///
+/// With the `simd` feature, several headers are hashed at once.
+pub fn block_header_digests(headers: &[[u8; 80]]) -> Vec<Hash> {
+    blake2s_batch(headers, |header| [header_words(header)])
+}
+
+/// Block header in the byte order it is hashed in: integer fields as big-endian words,
+/// hashes in their internal byte order
+fn header_words(header: &[u8; 80]) -> [u8; 80] {
+    let mut words = *header;
+    for range in [0..4, 68..72, 72..76, 76..80] {
+        words[range].reverse();
+    }
+    words
+}
+
+/// Blake2s digests of many messages made of the chunks returned by `chunks`, see [blake2s]
+#[cfg(not(feature = "simd"))]
+fn blake2s_batch<T, C, const N: usize>(items: &[T], chunks: impl Fn(&T) -> [C; N]) -> Vec<Hash>
+where
+    C: AsRef<[u8]>,
+{
+    items
+        .iter()
+        .map(|item| blake2s(chunks(item).iter().map(|chunk| chunk.as_ref())))
+        .collect()
+}
+
+/// Blake2s digests of many messages of the same length, made of the chunks returned by
+/// `chunks`, see [blake2s]; [LANES](crate::blake2s_lanes::LANES) messages are hashed at once
+#[cfg(feature = "simd")]
+fn blake2s_batch<T, C, const N: usize>(items: &[T], chunks: impl Fn(&T) -> [C; N]) -> Vec<Hash>
+where
+    C: AsRef<[u8]>,
+{
+    use crate::blake2s_lanes::{hash_lanes, Lanes, LANES};
+
+    let mut digests = Vec::with_capacity(items.len());
+    for batch in items.chunks(LANES) {
+        // Hashing the empty lanes costs more than hashing a few messages one at a time, e.g.
+        // the nodes of the upper heights of an MMR
+        if batch.len() < LANES / 2 {
+            digests.extend(
+                batch
+                    .iter()
+                    .map(|item| blake2s(chunks(item).iter().map(|chunk| chunk.as_ref()))),
+            );
+            continue;
+        }
+        // Transpose the messages into words of all lanes, missing lanes hash empty words
+        let mut words: Vec<Lanes> = Vec::new();
+        for (lane, item) in batch.iter().enumerate() {
+            let message = chunks(item);
+            let message_words = message.iter().flat_map(|chunk| chunk.as_ref().chunks(4));
+            for (i, word) in message_words.enumerate() {
+                if i == words.len() {
+                    words.push([0; LANES]);
+                }
+                words[i][lane] =
+                    u32::from_be_bytes(word.try_into().expect("chunks are made of 4-byte words"));
+            }
+        }
+        let state = hash_lanes(&words);
+        digests.extend((0..batch.len()).map(|lane| {
+            let mut hash = NULL_ROOT;
+            for (bytes, word) in hash.chunks_mut(4).zip(&state) {
+                bytes.copy_from_slice(&word[lane].to_be_bytes());
+            }
+            hash
+        }));
+    }
+    digests
+}
+
+/// Mountains of an MMR with `leaf_count` leaves, as (first leaf index, height) pairs.
+///
+/// Mountains are perfect binary trees ordered from the highest to the lowest,
+/// one per bit set in the leaf count.
+pub fn mountains(leaf_count: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..usize::BITS as usize)
+        .rev()
+        .filter(move |height| leaf_count & (1 << height) != 0)
+        .scan(0, |offset, height| {
+            let mountain = (*offset, height);
+            *offset += 1 << height;
+            Some(mountain)
+        })
+}
+
+/// Mountain containing the leaf at `leaf_index`, as its index and (first leaf index, height)
+pub fn find_mountain(leaf_index: usize, leaf_count: usize) -> Option<(usize, (usize, usize))> {
+    mountains(leaf_count)
+        .enumerate()
+        .find(|(_, (offset, height))| leaf_index < offset + (1 << height))
+}
+
+/// Hash a node up to its peak with its siblings, `index` being the position of the node
+/// within its level of the mountain, which tells the side at each level.
+///
+/// Returns the hashes of the nodes on the path, from the parent of the node to the peak.
+pub fn hash_to_peak(node: &Hash, mut index: usize, siblings: &[Hash]) -> Vec<Hash> {
+    let mut hash = *node;
+    let mut path = Vec::with_capacity(siblings.len());
+    for sibling in siblings {
+        hash = if index % 2 == 1 {
+            hash_pair(sibling, &hash)
+        } else {
+            hash_pair(&hash, sibling)
+        };
+        path.push(hash);
+        index /= 2;
+    }
+    path
+}
+
+/// Verify that a leaf is included in an MMR of `leaf_count` leaves with the given peaks
+/// (from the highest mountain to the lowest), by hashing it up to its peak
+pub fn verify_inclusion(
+    peaks: &[Hash],
+    leaf: &Hash,
+    leaf_index: usize,
+    leaf_count: usize,
+    siblings: &[Hash],
+) -> Result<bool, Error> {
+    verify_node_inclusion(peaks, leaf, 0, leaf_index, leaf_count, siblings)
+}
+
+/// Verify that a node of the given height, covering the leaf at `leaf_index`, is included in
+/// an MMR of `leaf_count` leaves with the given peaks, by hashing it up to its peak
+pub fn verify_node_inclusion(
+    peaks: &[Hash],
+    node: &Hash,
+    node_height: usize,
+    leaf_index: usize,
+    leaf_count: usize,
+    siblings: &[Hash],
+) -> Result<bool, Error> {
+    let (peak_index, (offset, height)) =
+        find_mountain(leaf_index, leaf_count).ok_or(Error::LeafIndexOutOfRange {
+            leaf_index,
+            leaf_count,
+        })?;
+    check_peaks_count(peaks, leaf_count)?;
+    if height < node_height || siblings.len() != height - node_height {
+        return Ok(false);
+    }
+
+    let path = hash_to_peak(node, (leaf_index - offset) >> node_height, siblings);
+    Ok(&peaks[peak_index] == path.last().unwrap_or(node))
+}
+
+/// Peaks of an MMR of `leaf_count` leaves for all heights, where missing ones are
+/// [NULL_ROOT]s, as committed to by the chain state of the Cairo implementation
+pub fn sparse_roots(peaks: &[Hash], leaf_count: usize) -> Result<Vec<Hash>, Error> {
+    if leaf_count == 0 {
+        return Err(Error::EmptyMmr);
+    }
+    check_peaks_count(peaks, leaf_count)?;
+
+    // One root per height up to the height of the perfect binary tree with as many
+    // elements as the MMR
+    let mut roots = vec![NULL_ROOT; leaf_count_to_mmr_size(leaf_count).ilog2() as usize + 1];
+    let mut peaks = peaks.iter();
+    for (_, height) in mountains(leaf_count) {
+        roots[height] = *peaks.next().expect("one peak per mountain");
+    }
+    if roots.last() != Some(&NULL_ROOT) {
+        roots.push(NULL_ROOT);
+    }
+    Ok(roots)
+}
+
+/// Peaks (from the highest mountain to the lowest) and number of leaves of an MMR from its
+/// [sparse_roots], which it inverts
+pub fn compact_peaks(roots: &[Hash]) -> Result<(Vec<Hash>, usize), Error> {
+    // The root at index `i` is the peak of the mountain of 2^i leaves, if any; larger MMRs
+    // couldn't count their elements
+    if roots.len() >= usize::BITS as usize {
+        return Err(Error::SparseRootsCountMismatch {
+            expected: usize::BITS as usize - 1,
+            actual: roots.len(),
+        });
+    }
+    let mut peaks = Vec::new();
+    let mut leaf_count = 0;
+    for (height, root) in roots.iter().enumerate().rev() {
+        if *root != NULL_ROOT {
+            peaks.push(*root);
+            leaf_count |= 1 << height;
+        }
+    }
+    let expected = sparse_roots(&peaks, leaf_count)?.len();
+    if roots.len() != expected {
+        return Err(Error::SparseRootsCountMismatch {
+            expected,
+            actual: roots.len(),
+        });
+    }
+    Ok((peaks, leaf_count))
+}
+
+/// Root hash of an MMR of `leaf_count` leaves with the given peaks (from the highest mountain
+/// to the lowest): the hash of its [sparse_roots]
+pub fn root_hash_from_peaks(peaks: &[Hash], leaf_count: usize) -> Result<Hash, Error> {
+    Ok(hash_nodes(&sparse_roots(peaks, leaf_count)?))
+}
+
+/// Number of elements (leaves and inner nodes) of an MMR with `leaf_count` leaves
+pub fn leaf_count_to_mmr_size(leaf_count: usize) -> usize {
+    2 * leaf_count - leaf_count.count_ones() as usize
+}
+
+fn check_peaks_count(peaks: &[Hash], leaf_count: usize) -> Result<(), Error> {
+    let expected = leaf_count.count_ones() as usize;
+    if peaks.len() != expected {
+        return Err(Error::PeaksCountMismatch {
+            leaf_count,
+            expected,
+            actual: peaks.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Unsigned 256-bit integer for proof-of-work arithmetic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct U256([u64; 4]); // Most significant limb first, so that the derived order is numeric
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const ONE: U256 = U256([0, 0, 0, 1]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    /// Create from a big-endian byte array
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0; 4];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks(8)) {
+            *limb = u64::from_be_bytes(chunk.try_into().expect("8-byte chunks"));
+        }
+        Self(limbs)
+    }
+
+    /// Convert to a big-endian byte array
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        for (chunk, limb) in bytes.chunks_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Checked addition, `None` on overflow
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let (sum, carry) = self.overflowing_add(rhs);
+        (!carry).then_some(sum)
+    }
+
+    /// Checked multiplication by a small integer, `None` on overflow
+    pub fn checked_mul_u64(self, rhs: u64) -> Option<Self> {
+        let mut limbs = [0; 4];
+        let mut carry = 0;
+        for i in (0..4).rev() {
+            let product = self.0[i] as u128 * rhs as u128 + carry;
+            limbs[i] = product as u64;
+            carry = product >> 64;
+        }
+        (carry == 0).then_some(Self(limbs))
+    }
+
+    /// Checked division, `None` if `rhs` is zero
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs == Self::ZERO {
+            return None;
+        }
+        // Binary long division
+        let mut quotient = Self::ZERO;
+        let mut remainder = Self::ZERO;
+        for bit in (0..256).rev() {
+            // The remainder is lower than `rhs`, doubling it can exceed 256 bits only if it
+            // then exceeds `rhs` as well
+            let overflow = remainder.bit(255);
+            remainder = remainder.shl1();
+            if self.bit(bit) {
+                remainder.0[3] |= 1;
+            }
+            if overflow || remainder >= rhs {
+                remainder = remainder.overflowing_sub(rhs).0;
+                quotient.0[3 - bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        Some(quotient)
+    }
+
+    fn overflowing_add(self, rhs: Self) -> (Self, bool) {
+        let mut limbs = [0; 4];
+        let mut carry = false;
+        for i in (0..4).rev() {
+            let (sum, c1) = self.0[i].overflowing_add(rhs.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            limbs[i] = sum;
+            carry = c1 || c2;
+        }
+        (Self(limbs), carry)
+    }
+
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
+        let mut limbs = [0; 4];
+        let mut borrow = false;
+        for i in (0..4).rev() {
+            let (diff, b1) = self.0[i].overflowing_sub(rhs.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            limbs[i] = diff;
+            borrow = b1 || b2;
+        }
+        (Self(limbs), borrow)
+    }
+
+    fn shl1(self) -> Self {
+        let mut limbs = self.0.map(|limb| limb << 1);
+        for (limb, next) in limbs.iter_mut().zip(&self.0[1..]) {
+            *limb |= next >> 63;
+        }
+        Self(limbs)
+    }
+
+    fn bit(&self, bit: usize) -> bool {
+        self.0[3 - bit / 64] >> (bit % 64) & 1 == 1
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        Self([0, 0, 0, value])
+    }
+}
+
+/// Expected number of hashes to find a block with the given target: `2^256 / (target + 1)`,
+/// saturating at [U256::MAX] for the zero target
+pub fn work_from_target(target: U256) -> U256 {
+    // 2^256 doesn't fit, but 2^256 / (target + 1) = (2^256 - target - 1) / (target + 1) + 1
+    match target.checked_add(U256::ONE) {
+        Some(divisor) => {
+            let complement = U256::MAX.overflowing_sub(target).0;
+            complement
+                .checked_div(divisor)
+                .expect("divisor is not zero")
+                .checked_add(U256::ONE)
+                .unwrap_or(U256::MAX)
+        }
+        None => U256::ONE,
+    }
+}
+
+/// Decode the compact representation of a target (the `bits` field of block headers).
+///
+/// Returns `None` if the target is negative or doesn't fit in 256 bits.
+pub fn target_from_compact(bits: u32) -> Option<U256> {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if bits & 0x0080_0000 != 0 && mantissa != 0 {
+        return None;
+    }
+    let mut bytes = [0; 32];
+    for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+        // Position of the byte from the least significant one
+        let position = (exponent as usize).checked_sub(i + 1);
+        match position {
+            Some(position) if position < 32 => bytes[31 - position] = *byte,
+            Some(_) if *byte != 0 => return None,
+            _ => {}
+        }
+    }
+    Some(U256::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAF: &str = "0xc713e33d89122b85e2f646cc518c2e6ef88b06d3b016104faa95f84f878dab66";
+
+    fn hex_hash(s: &str) -> Hash {
+        hash_from_hex(s).unwrap()
+    }
+
+    #[test]
+    fn test_hash_pair() {
+        let leaf = hex_hash(LEAF);
+        assert_eq!(
+            hash_to_hex(&hash_pair(&leaf, &leaf)),
+            "0x693aa1ab81c6362fe339fc4c7f6d8ddb1e515701e58c5bb2fb54a193c8287fdc"
+        );
+        assert_eq!(hash_to_hex(&leaf), LEAF);
+        assert!(hash_from_hex("0x1234").is_err());
+    }
+
+    #[test]
+    fn test_normalize_hash() {
+        assert_eq!(normalize_hash(LEAF).unwrap(), LEAF);
+        assert_eq!(normalize_hash(&LEAF[2..]).unwrap(), LEAF);
+        assert_eq!(normalize_hash(&LEAF.to_uppercase()).unwrap(), LEAF);
+        assert_eq!(
+            normalize_hash("0x1234"),
+            Err(Error::InvalidHashLength {
+                hash: "0x1234".into(),
+                digits: 4
+            })
+        );
+        let hash = LEAF.replace('c', "g");
+        assert_eq!(
+            normalize_hash(&hash),
+            Err(Error::InvalidHashDigit { hash, digit: 'g' })
+        );
+        // A sign is not a digit, even though `from_str_radix` accepts it
+        assert!(matches!(
+            normalize_hash(&LEAF.replacen('c', "+", 1)),
+            Err(Error::InvalidHashDigit { digit: '+', .. })
+        ));
+    }
+
+    #[test]
+    fn test_block_header_digest() {
+        // Consensus encoding of the genesis block header
+        let mut header = [0; 80];
+        let encoded = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+        for (i, byte) in header.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&encoded[2 * i..2 * i + 2], 16).unwrap();
+        }
+        assert_eq!(
+            hash_to_hex(&block_header_digest(&header)),
+            "0x5fd720d341e64d17d3b8624b17979b0d0dad4fc17d891796a3a51a99d3f41599"
+        );
+    }
+
+    #[test]
+    fn test_batch_hashing() {
+        // Partial batches and more than one batch of lanes
+        for count in [0u8, 1, 7, 8, 9, 17] {
+            let pairs: Vec<(Hash, Hash)> = (0..count).map(|i| ([i; 32], [!i; 32])).collect();
+            let expected: Vec<Hash> = pairs
+                .iter()
+                .map(|(left, right)| hash_pair(left, right))
+                .collect();
+            assert_eq!(hash_pairs(&pairs), expected);
+
+            let headers: Vec<[u8; 80]> = (0..count).map(|i| [i; 80]).collect();
+            let expected: Vec<Hash> = headers.iter().map(block_header_digest).collect();
+            assert_eq!(block_header_digests(&headers), expected);
+        }
+    }
+
+    #[test]
+    fn test_sparse_roots() {
+        let leaf = hex_hash(LEAF);
+        let parent = hash_pair(&leaf, &leaf);
+        assert_eq!(sparse_roots(&[leaf], 1).unwrap(), vec![leaf, NULL_ROOT]);
+        assert_eq!(
+            sparse_roots(&[parent], 2).unwrap(),
+            vec![NULL_ROOT, parent, NULL_ROOT]
+        );
+        assert_eq!(
+            sparse_roots(&[parent, leaf], 3).unwrap(),
+            vec![leaf, parent, NULL_ROOT]
+        );
+        assert_eq!(
+            sparse_roots(&[leaf], 3),
+            Err(Error::PeaksCountMismatch {
+                leaf_count: 3,
+                expected: 2,
+                actual: 1,
+            })
+        );
+        assert_eq!(sparse_roots(&[], 0), Err(Error::EmptyMmr));
+    }
+
+    #[test]
+    fn test_compact_peaks() {
+        // Distinct peaks, so that misplaced ones are caught
+        let peak = |i: u8| [i + 1; 32];
+        for leaf_count in 1..100usize {
+            let peaks: Vec<Hash> = (0..leaf_count.count_ones() as u8).map(peak).collect();
+            let roots = sparse_roots(&peaks, leaf_count).unwrap();
+            assert_eq!(compact_peaks(&roots).unwrap(), (peaks, leaf_count));
+        }
+
+        let leaf = hex_hash(LEAF);
+        assert_eq!(
+            compact_peaks(&[leaf]),
+            Err(Error::SparseRootsCountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            compact_peaks(&[leaf, NULL_ROOT, NULL_ROOT]),
+            Err(Error::SparseRootsCountMismatch {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert_eq!(compact_peaks(&[NULL_ROOT, NULL_ROOT]), Err(Error::EmptyMmr));
+        assert!(compact_peaks(&[leaf; 64]).is_err());
+    }
+
+    #[test]
+    fn test_verify_inclusion() {
+        // MMR of 3 identical leaves: a mountain of 2 leaves and a mountain of 1 leaf
+        let leaf = hex_hash(LEAF);
+        let peaks = [hash_pair(&leaf, &leaf), leaf];
+        assert!(verify_inclusion(&peaks, &leaf, 1, 3, &[leaf]).unwrap());
+        assert!(verify_inclusion(&peaks, &leaf, 2, 3, &[]).unwrap());
+        assert!(!verify_inclusion(&peaks, &leaf, 2, 3, &[leaf]).unwrap());
+        assert!(!verify_inclusion(&peaks, &NULL_ROOT, 0, 3, &[leaf]).unwrap());
+        assert_eq!(
+            verify_inclusion(&peaks, &leaf, 3, 3, &[]),
+            Err(Error::LeafIndexOutOfRange {
+                leaf_index: 3,
+                leaf_count: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_work_from_target() {
+        assert_eq!(work_from_target(U256::MAX), U256::ONE);
+        // 2^256 saturates
+        assert_eq!(work_from_target(U256::ZERO), U256::MAX);
+        // Genesis target: 0x00000000ffff0000...
+        let target = target_from_compact(0x1d00ffff).unwrap();
+        let mut expected = [0; 32];
+        expected[4..6].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(target.to_be_bytes(), expected);
+        assert_eq!(work_from_target(target), U256::from(0x1_0001_0001));
+        assert_eq!(
+            work_from_target(target).checked_mul_u64(6),
+            Some(U256::from(0x6_0006_0006))
+        );
+        assert_eq!(target_from_compact(0x04923456), None);
+        assert_eq!(target_from_compact(0x2200ffff), None);
+        assert_eq!(U256::MAX.checked_add(U256::ONE), None);
+    }
+}
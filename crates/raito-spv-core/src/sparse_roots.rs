@@ -1,64 +1,292 @@
 //! Sparse roots representation for MMR peaks compatible with Cairo implementation.
 
 use accumulators::mmr::elements_count_to_leaf_count;
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::BlockHash;
 use num_bigint::BigInt;
 use num_traits::Num;
-use serde::{Serialize, Serializer};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json;
+use std::fmt;
 use std::str::FromStr;
 
+use crate::primitives::{self, hash_to_hex, hashes_from_hex, normalize_hash};
+
 /// Sparse roots is MMR peaks for all heights, where missing ones are filled with zeros
 /// This representation is different from the "compact" one, which contains only non-zero peaks
-/// but with total number of elements.
-#[derive(Debug, Clone, Serialize)]
+/// but with total number of elements (see [CompactPeaks]).
+///
+/// Deserialization accepts the roots as u256 (as serialized) or as hex strings, normalizes them
+/// and recovers the block height from the non-zero roots.
+///
+/// The block at the height can be included, so that the roots can be matched with the block
+/// without a separate header source: it is then serialized along with the schema version,
+/// [SPARSE_ROOTS_VERSION]. Without it, the JSON only has the `roots` (version 1).
+#[derive(Debug, Clone)]
 pub struct SparseRoots {
     /// Block height
-    #[serde(skip)]
     pub block_height: u32,
     /// MMR peaks for all heights, where missing ones are filled with zeros
-    #[serde(serialize_with = "serialize_u256_array")]
     pub roots: Vec<String>,
+    /// Block at the height, if included
+    pub block: Option<RootsBlock>,
+}
+
+/// Version of the JSON schema of the sparse roots with the block
+pub const SPARSE_ROOTS_VERSION: u32 = 2;
+
+/// Block of the sparse roots: its hash and header fields
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootsBlock {
+    /// Block hash
+    pub hash: BlockHash,
+    /// Block header
+    pub header: BlockHeader,
+}
+
+impl RootsBlock {
+    pub fn new(header: BlockHeader) -> Self {
+        Self {
+            hash: header.block_hash(),
+            header,
+        }
+    }
+}
+
+/// Compact representation of the MMR peaks: the non-zero peaks only, with the total number of
+/// elements (leaves and inner nodes) of the MMR
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactPeaks {
+    /// Peak hashes, from the highest mountain to the lowest
+    #[serde(deserialize_with = "deserialize_hashes")]
+    pub peaks: Vec<String>,
+    /// Number of elements (leaves and inner nodes) of the MMR
+    pub elements_count: usize,
+}
+
+impl CompactPeaks {
+    /// Number of leaves (blocks) of the MMR
+    pub fn leaf_count(&self) -> Result<usize, anyhow::Error> {
+        Ok(elements_count_to_leaf_count(self.elements_count)?)
+    }
 }
 
 impl SparseRoots {
+    /// Create from the compact peaks of an MMR with `elements_count` elements
     pub fn try_from_peaks(
         peaks: Vec<String>,
-        mut elements_count: usize,
+        elements_count: usize,
     ) -> Result<Self, anyhow::Error> {
         let leaf_count = elements_count_to_leaf_count(elements_count)?;
-        let null_root = format!("0x{:064x}", 0);
-
-        let mut max_height = elements_count.ilog2() + 1;
-        let mut root_idx = 0;
-        let mut result = vec![];
-
-        while elements_count != 0 || max_height != 0 {
-            // Number of elements of the perfect binary tree of the current max height
-            let elements_per_height = (1 << max_height) - 1;
-            if elements_count >= elements_per_height {
-                result.insert(0, peaks[root_idx].clone());
-                root_idx += 1;
-                elements_count -= elements_per_height;
-            } else {
-                result.insert(0, null_root.clone());
-            }
-            if max_height != 0 {
-                max_height -= 1;
-            }
+        let roots = primitives::sparse_roots(&hashes_from_hex(&peaks)?, leaf_count)?;
+        Ok(Self {
+            roots: roots.iter().map(hash_to_hex).collect(),
+            // Last block height is the number of leaves - 1
+            block_height: leaf_count as u32 - 1,
+            block: None,
+        })
+    }
+
+    /// Include the block at the height, given its header
+    pub fn with_block(mut self, header: BlockHeader) -> Self {
+        self.block = Some(RootsBlock::new(header));
+        self
+    }
+
+    /// Create from the compact representation of the MMR peaks
+    pub fn from_compact(compact: &CompactPeaks) -> Result<Self, anyhow::Error> {
+        Self::try_from_peaks(compact.peaks.clone(), compact.elements_count)
+    }
+
+    /// Compact representation of the MMR peaks, see [primitives::compact_peaks]
+    pub fn to_compact(&self) -> Result<CompactPeaks, anyhow::Error> {
+        let (peaks, leaf_count) = primitives::compact_peaks(&hashes_from_hex(&self.roots)?)?;
+        Ok(CompactPeaks {
+            peaks: peaks.iter().map(hash_to_hex).collect(),
+            elements_count: primitives::leaf_count_to_mmr_size(leaf_count),
+        })
+    }
+}
+
+impl Serialize for SparseRoots {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct Roots<'a> {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            version: Option<u32>,
+            #[serde(serialize_with = "serialize_u256_array")]
+            roots: &'a Vec<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            block: Option<&'a RootsBlock>,
         }
 
-        if result.last().unwrap() != &null_root {
-            result.push(null_root);
+        Roots {
+            version: self.block.as_ref().map(|_| SPARSE_ROOTS_VERSION),
+            roots: &self.roots,
+            block: self.block.as_ref(),
         }
+        .serialize(serializer)
+    }
+}
 
+impl<'de> Deserialize<'de> for SparseRoots {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Roots {
+            version: Option<u32>,
+            #[serde(deserialize_with = "deserialize_u256_array")]
+            roots: Vec<String>,
+            block: Option<RootsBlock>,
+        }
+
+        let Roots {
+            version,
+            roots,
+            block,
+        } = Roots::deserialize(deserializer)?;
+        if let Some(version) = version.filter(|version| *version > SPARSE_ROOTS_VERSION) {
+            return Err(de::Error::custom(format!(
+                "Unsupported sparse roots version {}",
+                version
+            )));
+        }
+        if let Some(block) = &block {
+            if block.header.block_hash() != block.hash {
+                return Err(de::Error::custom(format!(
+                    "Block hash {} doesn't match the header",
+                    block.hash
+                )));
+            }
+        }
+        let (_, leaf_count) = hashes_from_hex(&roots)
+            .and_then(|roots| primitives::compact_peaks(&roots))
+            .map_err(de::Error::custom)?;
+        // Last block height is the number of leaves - 1
+        let block_height = u32::try_from(leaf_count - 1)
+            .map_err(|_| de::Error::custom("Sparse roots of an MMR of more than 2^32 blocks"))?;
         Ok(Self {
-            roots: result,
-            // Last block height is the number of leaves - 1
-            block_height: leaf_count as u32 - 1,
+            block_height,
+            roots,
+            block,
         })
     }
 }
 
+/// Deserialize a list of hashes into their canonical representation (see
+/// [primitives::normalize_hash]), rejecting malformed ones
+pub fn deserialize_hashes<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|hash| normalize_hash(hash).map_err(de::Error::custom))
+        .collect()
+}
+
+/// Deserialize lists of hashes into their canonical representation, see [deserialize_hashes]
+pub fn deserialize_hash_lists<'de, D>(deserializer: D) -> Result<Vec<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<Vec<String>>::deserialize(deserializer)?
+        .iter()
+        .map(|hashes| {
+            hashes
+                .iter()
+                .map(|hash| normalize_hash(hash).map_err(de::Error::custom))
+                .collect()
+        })
+        .collect()
+}
+
+/// Custom deserialization of an array of u256 (see [serialize_u256_array]) or hex strings
+/// into canonical hashes
+pub fn deserialize_u256_array<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct U256ArrayVisitor;
+
+    impl<'de> Visitor<'de> for U256ArrayVisitor {
+        type Value = Vec<String>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an array of u256 or hex strings")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut roots = Vec::new();
+            while let Some(U256Hash(root)) = seq.next_element()? {
+                roots.push(root);
+            }
+            Ok(roots)
+        }
+    }
+
+    deserializer.deserialize_seq(U256ArrayVisitor)
+}
+
+/// Hash deserialized from a u256 (`{"hi": ..., "lo": ...}`) or a hex string
+struct U256Hash(String);
+
+impl<'de> Deserialize<'de> for U256Hash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct U256HashVisitor;
+
+        impl<'de> Visitor<'de> for U256HashVisitor {
+            type Value = U256Hash;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a u256 or a hex string of 32 bytes")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                normalize_hash(value).map(U256Hash).map_err(E::custom)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let (mut hi, mut lo) = (None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    let limb = match key.as_str() {
+                        "hi" => &mut hi,
+                        "lo" => &mut lo,
+                        _ => return Err(de::Error::unknown_field(&key, &["hi", "lo"])),
+                    };
+                    if limb.is_some() {
+                        return Err(de::Error::custom(format!("duplicate field `{}`", key)));
+                    }
+                    *limb = Some(map.next_value::<u128>()?);
+                }
+                let hi = hi.ok_or_else(|| de::Error::missing_field("hi"))?;
+                let lo = lo.ok_or_else(|| de::Error::missing_field("lo"))?;
+                Ok(U256Hash(format!("0x{:032x}{:032x}", hi, lo)))
+            }
+        }
+
+        deserializer.deserialize_any(U256HashVisitor)
+    }
+}
+
 /// Custom serialization for Vec<String> to serialize as array of u256 (in Cairo)
 pub fn serialize_u256_array<S>(items: &Vec<String>, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -100,3 +328,78 @@ where
         .map_err(|e| serde::ser::Error::custom(format!("Failed to serialize BigInt: {}", e)))?;
     Ok(json_number)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_peaks_roundtrip() {
+        let leaf = "0xc713e33d89122b85e2f646cc518c2e6ef88b06d3b016104faa95f84f878dab66";
+        let parent = "0x693aa1ab81c6362fe339fc4c7f6d8ddb1e515701e58c5bb2fb54a193c8287fdc";
+        let compact = CompactPeaks {
+            peaks: vec![parent.to_string(), leaf.to_string()],
+            elements_count: 4,
+        };
+        assert_eq!(compact.leaf_count().unwrap(), 3);
+
+        let sparse_roots = SparseRoots::from_compact(&compact).unwrap();
+        assert_eq!(sparse_roots.block_height, 2);
+        assert_eq!(
+            sparse_roots.roots,
+            vec![leaf, parent, &hash_to_hex(&[0; 32])]
+        );
+        assert_eq!(sparse_roots.to_compact().unwrap(), compact);
+
+        // Through the serialized forms
+        let json = serde_json::to_string(&sparse_roots).unwrap();
+        let sparse_roots: SparseRoots = serde_json::from_str(&json).unwrap();
+        assert_eq!(sparse_roots.block_height, 2);
+        let json = serde_json::to_string(&sparse_roots.to_compact().unwrap()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CompactPeaks>(&json).unwrap(),
+            compact
+        );
+
+        // A missing null root on top is not a valid layout
+        let truncated = SparseRoots {
+            block_height: 2,
+            roots: vec![leaf.to_string(), parent.to_string()],
+            block: None,
+        };
+        assert!(truncated.to_compact().is_err());
+    }
+
+    #[test]
+    fn test_sparse_roots_with_block() {
+        let compact = CompactPeaks {
+            peaks: vec![
+                "0xc713e33d89122b85e2f646cc518c2e6ef88b06d3b016104faa95f84f878dab66".into(),
+            ],
+            elements_count: 1,
+        };
+        let sparse_roots = SparseRoots::from_compact(&compact).unwrap();
+        let json = serde_json::to_value(&sparse_roots).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 1);
+
+        let genesis = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin).header;
+        let sparse_roots = sparse_roots.with_block(genesis);
+        let json = serde_json::to_value(&sparse_roots).unwrap();
+        assert_eq!(json["version"], SPARSE_ROOTS_VERSION);
+        assert_eq!(
+            json["block"]["hash"],
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(json["block"]["header"]["nonce"], 2083236893);
+        let parsed: SparseRoots = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(parsed.block, sparse_roots.block);
+        assert_eq!(parsed.roots, sparse_roots.roots);
+
+        let mut wrong_hash = json.clone();
+        wrong_hash["block"]["header"]["nonce"] = 0.into();
+        assert!(serde_json::from_value::<SparseRoots>(wrong_hash).is_err());
+        let mut future_version = json;
+        future_version["version"] = (SPARSE_ROOTS_VERSION + 1).into();
+        assert!(serde_json::from_value::<SparseRoots>(future_version).is_err());
+    }
+}
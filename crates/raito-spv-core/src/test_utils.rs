@@ -0,0 +1,652 @@
+//! In-process mock servers of the Bitcoin RPC and the Raito bridge RPC, serving canned block
+//! headers, transaction Merkle proofs, block MMR proofs and chain state proofs.
+//!
+//! They let applications built on Raito write integration tests of their proof fetching and
+//! verification flows without a regtest bitcoind or network access:
+//!
+//! ```ignore
+//! let blocks = regtest_chain(vec![vec![], vec![transaction]]);
+//! let headers: Vec<_> = blocks.iter().map(|block| block.header).collect();
+//! let bitcoin_rpc = MockBitcoinRpc::new(blocks).start().await?;
+//! let raito_rpc = MockRaitoRpc::new(&headers).await?.start().await?;
+//! let client = BitcoinClient::new(bitcoin_rpc.url().to_string(), None)?;
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{CONTENT_RANGE, ETAG, IF_RANGE, RANGE},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use bitcoin::absolute::LockTime;
+use bitcoin::block::{Header as BlockHeader, Version};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::script::PushBytesBuf;
+use bitcoin::{
+    transaction, Amount, Block, BlockHash, CompactTarget, MerkleBlock, Network, OutPoint,
+    ScriptBuf, Sequence, Transaction, TxIn, TxMerkleNode, TxOut, Txid, Witness,
+};
+use bitcoincore_rpc_json::GetBlockHeaderResult;
+use num_bigint::BigUint;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::block_mmr::{BlockConsistencyProof, BlockInclusionProof, BlockMMR};
+use crate::sparse_roots::SparseRoots;
+
+/// Mock server running in the background until dropped
+pub struct MockServer {
+    url: String,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Base URL of the server, e.g. `http://127.0.0.1:38615`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Serve `router` on a free local port
+    async fn start(router: Router) -> Result<Self, std::io::Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let handle = tokio::spawn(async move {
+            axum::serve(listener, router).await.ok();
+        });
+        Ok(Self { url, handle })
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Build a regtest chain on top of the genesis block, with one block per entry of
+/// `transactions` containing a coinbase transaction followed by the given transactions.
+///
+/// The coinbase transaction of every block pays 50 BTC to an `OP_TRUE` output, which the
+/// transactions of the following blocks can spend.
+pub fn regtest_chain(transactions: Vec<Vec<Transaction>>) -> Vec<Block> {
+    let mut blocks = vec![genesis_block(Network::Regtest)];
+    for (height, transactions) in (1..).zip(transactions) {
+        let prev_blockhash = blocks.last().expect("genesis block").block_hash();
+        let mut txdata = vec![coinbase_transaction(height)];
+        txdata.extend(transactions);
+        let mut block = Block {
+            header: BlockHeader {
+                version: Version::TWO,
+                prev_blockhash,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 1_296_688_602 + 600 * height,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce: 0,
+            },
+            txdata,
+        };
+        block.header.merkle_root = block.compute_merkle_root().expect("block has transactions");
+        while block.header.validate_pow(block.header.target()).is_err() {
+            block.header.nonce += 1;
+        }
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Coinbase transaction of a regtest block, unique thanks to the height in its script (BIP34)
+fn coinbase_transaction(height: u32) -> Transaction {
+    let height = PushBytesBuf::try_from(height.to_le_bytes().to_vec()).expect("4 bytes");
+    Transaction {
+        version: transaction::Version::ONE,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::builder().push_slice(height).into_script(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_int_btc(50),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+        }],
+    }
+}
+
+/// Mock Bitcoin node JSON-RPC serving a fixed chain.
+///
+/// Supports the methods used by [crate::bitcoin::BitcoinClient]: `getblockcount`,
+/// `getblockhash`, `getblockheader`, `getblock` (verbosity 0 only), `getrawtransaction` (any
+/// verbosity other than `false` or 0 returns the inputs with their previous outputs),
+/// `gettxoutproof` and `getblockchaininfo` (pruning fields only).
+pub struct MockBitcoinRpc {
+    blocks: Vec<Block>,
+    block_heights: HashMap<BlockHash, usize>,
+    /// Height of the block and index of the transaction in the block
+    transactions: HashMap<Txid, (usize, usize)>,
+    /// Height of the first block whose data is served, if pruned
+    prune_height: Option<usize>,
+}
+
+impl MockBitcoinRpc {
+    /// Create a mock node with the given chain, starting at the genesis block
+    pub fn new(blocks: Vec<Block>) -> Self {
+        let mut block_heights = HashMap::new();
+        let mut transactions = HashMap::new();
+        for (height, block) in blocks.iter().enumerate() {
+            block_heights.insert(block.block_hash(), height);
+            for (index, tx) in block.txdata.iter().enumerate() {
+                transactions.insert(tx.compute_txid(), (height, index));
+            }
+        }
+        Self {
+            blocks,
+            block_heights,
+            transactions,
+            prune_height: None,
+        }
+    }
+
+    /// Prune the data of the blocks below `prune_height`, like a pruned node: their headers
+    /// are still served, but not their transactions
+    pub fn with_prune_height(mut self, prune_height: usize) -> Self {
+        self.prune_height = Some(prune_height);
+        self
+    }
+
+    /// Fail like a pruned node if the data of the block at `height` was pruned
+    fn check_not_pruned(&self, height: usize, error: (i32, &str)) -> Result<(), (i32, String)> {
+        match self.prune_height {
+            Some(prune_height) if height < prune_height => Err((error.0, error.1.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start serving the JSON-RPC on a free local port
+    pub async fn start(self) -> Result<MockServer, std::io::Error> {
+        let router = Router::new()
+            .route("/", post(handle_bitcoin_request))
+            .with_state(Arc::new(self));
+        MockServer::start(router).await
+    }
+
+    /// Result of a JSON-RPC call, or the error code and message
+    fn call(&self, method: &str, params: &[Value]) -> Result<Value, (i32, String)> {
+        match method {
+            "getblockcount" => Ok(json!(self.blocks.len() - 1)),
+            "getblockchaininfo" => Ok(match self.prune_height {
+                Some(prune_height) => json!({ "pruned": true, "pruneheight": prune_height }),
+                None => json!({ "pruned": false }),
+            }),
+            "getblockhash" => {
+                let height = params
+                    .first()
+                    .and_then(Value::as_u64)
+                    .ok_or_else(invalid_params)?;
+                let block = self
+                    .blocks
+                    .get(height as usize)
+                    .ok_or((-8, "Block height out of range".to_string()))?;
+                Ok(json!(block.block_hash()))
+            }
+            "getblockheader" => {
+                let height = self.block_height(params.first())?;
+                if params.get(1).and_then(Value::as_bool) == Some(false) {
+                    Ok(json!(serialize_hex(&self.blocks[height].header)))
+                } else {
+                    Ok(json!(self.block_header_result(height)))
+                }
+            }
+            "getblock" => {
+                let height = self.block_height(params.first())?;
+                self.check_not_pruned(height, (-1, "Block not available (pruned data)"))?;
+                match params.get(1).and_then(Value::as_u64) {
+                    Some(0) => Ok(json!(serialize_hex(&self.blocks[height]))),
+                    _ => Err(invalid_params()),
+                }
+            }
+            "getrawtransaction" => {
+                let (height, index) = self.transaction_position(parse(params.first())?)?;
+                self.check_not_pruned(height, (-1, "Block not available"))?;
+                let tx = &self.blocks[height].txdata[index];
+                match params.get(1) {
+                    None | Some(Value::Bool(false)) => Ok(json!(serialize_hex(tx))),
+                    Some(verbosity) if verbosity.as_u64() == Some(0) => {
+                        Ok(json!(serialize_hex(tx)))
+                    }
+                    Some(_) => Ok(self.transaction_result(tx)),
+                }
+            }
+            "gettxoutproof" => {
+                let txid = parse(params.first().and_then(|txids| txids.get(0)))?;
+                let (height, _) = self.transaction_position(txid)?;
+                // Without a transaction index, the block of a transaction is only found while
+                // its block data is available
+                self.check_not_pruned(height, (-5, "Transaction not yet in block"))?;
+                let proof =
+                    MerkleBlock::from_block_with_predicate(&self.blocks[height], |id| *id == txid);
+                Ok(json!(serialize_hex(&proof)))
+            }
+            _ => Err((-32601, "Method not found".to_string())),
+        }
+    }
+
+    fn block_height(&self, hash: Option<&Value>) -> Result<usize, (i32, String)> {
+        let hash = parse::<BlockHash>(hash)?;
+        self.block_heights
+            .get(&hash)
+            .copied()
+            .ok_or((-5, "Block not found".to_string()))
+    }
+
+    fn transaction_position(&self, txid: Txid) -> Result<(usize, usize), (i32, String)> {
+        self.transactions
+            .get(&txid)
+            .copied()
+            .ok_or((-5, "No such mempool or blockchain transaction".to_string()))
+    }
+
+    /// Verbose `getblockheader` result
+    fn block_header_result(&self, height: usize) -> GetBlockHeaderResult {
+        let header = &self.blocks[height].header;
+        let chainwork = self.blocks[..=height]
+            .iter()
+            .map(|block| block.header.work())
+            .reduce(|total, work| total + work)
+            .expect("block is in the chain");
+        GetBlockHeaderResult {
+            hash: header.block_hash(),
+            confirmations: (self.blocks.len() - height) as i32,
+            height,
+            version: header.version,
+            version_hex: Some(header.version.to_consensus().to_be_bytes().to_vec()),
+            merkle_root: header.merkle_root,
+            time: header.time as usize,
+            median_time: None,
+            nonce: header.nonce,
+            bits: format!("{:08x}", header.bits.to_consensus()),
+            difficulty: header.difficulty_float(),
+            chainwork: chainwork.to_be_bytes().to_vec(),
+            n_tx: self.blocks[height].txdata.len(),
+            previous_block_hash: height.checked_sub(1).map(|_| header.prev_blockhash),
+            next_block_hash: self.blocks.get(height + 1).map(Block::block_hash),
+        }
+    }
+
+    /// Verbose `getrawtransaction` result, with the previous outputs of the inputs when they
+    /// are in the chain
+    fn transaction_result(&self, tx: &Transaction) -> Value {
+        let vin: Vec<Value> = tx
+            .input
+            .iter()
+            .map(|input| {
+                if input.previous_output.is_null() {
+                    return json!({ "coinbase": input.script_sig.to_hex_string() });
+                }
+                let OutPoint { txid, vout } = input.previous_output;
+                let prevout = self.transactions.get(&txid).and_then(|(height, index)| {
+                    self.blocks[*height].txdata[*index]
+                        .output
+                        .get(vout as usize)
+                });
+                match prevout {
+                    Some(prevout) => json!({
+                        "txid": txid,
+                        "vout": vout,
+                        "prevout": {
+                            "value": prevout.value.to_btc(),
+                            "scriptPubKey": { "hex": prevout.script_pubkey.to_hex_string() },
+                        },
+                    }),
+                    None => json!({ "txid": txid, "vout": vout }),
+                }
+            })
+            .collect();
+        json!({ "txid": tx.compute_txid(), "hex": serialize_hex(tx), "vin": vin })
+    }
+}
+
+fn invalid_params() -> (i32, String) {
+    (-32602, "Invalid params".to_string())
+}
+
+/// Parse a JSON-RPC string parameter
+fn parse<T: std::str::FromStr>(value: Option<&Value>) -> Result<T, (i32, String)> {
+    value
+        .and_then(Value::as_str)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid_params)
+}
+
+async fn handle_bitcoin_request(
+    State(rpc): State<Arc<MockBitcoinRpc>>,
+    Json(request): Json<Value>,
+) -> Json<Value> {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request.get("method").and_then(Value::as_str).unwrap_or("");
+    let params = match request.get("params") {
+        Some(Value::Array(params)) => params.clone(),
+        _ => Vec::new(),
+    };
+    Json(match rpc.call(method, &params) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "error": { "code": code, "message": message },
+            "id": id,
+        }),
+    })
+}
+
+/// Mock Raito bridge RPC serving the block MMR of a fixed chain, and optionally a canned
+/// chain state proof.
+///
+/// Serves the same endpoints as the bridge node: `/head`, `/roots`,
+/// `/block-inclusion-proof/:block_height`, `/block-consistency-proof/:prev_chain_height`,
+/// `/chainstate-proof/recent_proof`, and optionally `/block-headers/:start_height`,
+/// `/transaction-proof/:txid` and `/chainstate/:height`.
+pub struct MockRaitoRpc {
+    mmr: BlockMMR,
+    chain_state_proof: Option<Vec<u8>>,
+    blocks: Vec<Block>,
+}
+
+/// Query parameters of the MMR endpoints
+#[derive(Deserialize)]
+struct ChainHeightQuery {
+    chain_height: Option<u32>,
+}
+
+/// Query parameters of the block headers endpoint
+#[derive(Deserialize)]
+struct HeadersQuery {
+    count: Option<u32>,
+}
+
+impl MockRaitoRpc {
+    /// Create a mock bridge with a block MMR of the given headers, starting at the genesis block
+    pub async fn new(headers: &[BlockHeader]) -> Result<Self, anyhow::Error> {
+        let mut mmr = BlockMMR::default();
+        for header in headers {
+            mmr.add_block_header(header).await?;
+        }
+        Ok(Self {
+            mmr,
+            chain_state_proof: None,
+            blocks: Vec::new(),
+        })
+    }
+
+    /// Serve the headers, transaction proofs and chain states of `blocks` at
+    /// `/block-headers/:start_height`, `/transaction-proof/:txid` and `/chainstate/:height`,
+    /// like a bridge node with a Bitcoin RPC; they respond with 503 otherwise. Headers and
+    /// chain states are only served up to the MMR size, like indexed headers.
+    pub fn with_blocks(mut self, blocks: Vec<Block>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// Serve `proof` (chain state and recursive proof, as returned by the bridge) at
+    /// `/chainstate-proof/recent_proof`, which responds with 404 otherwise
+    ///
+    /// Like the Raito RPC, the proof is served with an entity tag and its SHA-256 digest in the
+    /// `x-goog-meta-sha256` header, and `bytes=<start>-` range requests are honored.
+    pub fn with_chain_state_proof(mut self, proof: Value) -> Self {
+        self.chain_state_proof = Some(proof.to_string().into_bytes());
+        self
+    }
+
+    /// Start serving the RPC on a free local port
+    pub async fn start(self) -> Result<MockServer, std::io::Error> {
+        let router = Router::new()
+            .route(
+                "/block-inclusion-proof/:block_height",
+                get(block_inclusion_proof),
+            )
+            .route(
+                "/block-consistency-proof/:prev_chain_height",
+                get(block_consistency_proof),
+            )
+            .route("/head", get(head))
+            .route("/roots", get(roots))
+            .route("/chainstate-proof/recent_proof", get(chain_state_proof))
+            .route("/block-headers/:start_height", get(block_headers))
+            .route("/transaction-proof/:txid", get(transaction_proof))
+            .route("/chainstate/:height", get(chain_state))
+            .with_state(Arc::new(self));
+        MockServer::start(router).await
+    }
+}
+
+async fn block_inclusion_proof(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    Path(block_height): Path<u32>,
+    Query(query): Query<ChainHeightQuery>,
+) -> Result<Json<BlockInclusionProof>, StatusCode> {
+    rpc.mmr
+        .generate_proof(block_height, query.chain_height)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn block_consistency_proof(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    Path(prev_chain_height): Path<u32>,
+    Query(query): Query<ChainHeightQuery>,
+) -> Result<Json<BlockConsistencyProof>, StatusCode> {
+    rpc.mmr
+        .generate_consistency_proof(prev_chain_height, query.chain_height)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn head(State(rpc): State<Arc<MockRaitoRpc>>) -> Result<Json<u32>, StatusCode> {
+    match rpc.mmr.get_block_count().await {
+        Ok(block_count) if block_count > 0 => Ok(Json(block_count - 1)),
+        _ => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+async fn roots(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    Query(query): Query<ChainHeightQuery>,
+) -> Result<Json<SparseRoots>, StatusCode> {
+    rpc.mmr
+        .get_sparse_roots(query.chain_height)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn block_headers(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    Path(start_height): Path<u32>,
+    Query(query): Query<HeadersQuery>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    if rpc.blocks.is_empty() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let block_count = rpc
+        .mmr
+        .get_block_count()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if start_height >= block_count {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let end_height = block_count.min(start_height + query.count.unwrap_or(1));
+    let headers = rpc.blocks[start_height as usize..end_height as usize]
+        .iter()
+        .map(|block| serialize_hex(&block.header))
+        .collect();
+    Ok(Json(headers))
+}
+
+async fn transaction_proof(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    Path(txid): Path<Txid>,
+) -> Result<Json<Value>, StatusCode> {
+    if rpc.blocks.is_empty() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let (height, block, transaction) = rpc
+        .blocks
+        .iter()
+        .enumerate()
+        .find_map(|(height, block)| {
+            let transaction = block.txdata.iter().find(|tx| tx.compute_txid() == txid)?;
+            Some((height, block, transaction))
+        })
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let proof = MerkleBlock::from_block_with_predicate(block, |id| *id == txid);
+    Ok(Json(json!({
+        "transaction": serialize_hex(transaction),
+        "transaction_proof": serialize_hex(&proof.txn),
+        "block_header": serialize_hex(&block.header),
+        "block_height": height,
+    })))
+}
+
+async fn chain_state(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    Path(height): Path<u32>,
+) -> Result<Json<Value>, StatusCode> {
+    if rpc.blocks.is_empty() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let block_count = rpc
+        .mmr
+        .get_block_count()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if height >= block_count {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let headers: Vec<BlockHeader> = rpc.blocks[..=height as usize]
+        .iter()
+        .map(|block| block.header)
+        .collect();
+    let total_work = headers
+        .iter()
+        .map(|header| BigUint::from_bytes_be(&header.work().to_be_bytes()))
+        .sum::<BigUint>();
+    let header = headers[height as usize];
+    // Difficulty epochs of 2016 blocks, 11 timestamps for the median time past
+    let epoch_start = headers[(height - height % 2016) as usize];
+    let prev_timestamps: Vec<u32> = headers[headers.len().saturating_sub(11)..]
+        .iter()
+        .map(|header| header.time)
+        .collect();
+    Ok(Json(json!({
+        "block_height": height,
+        "total_work": total_work.to_string(),
+        "best_block_hash": header.block_hash(),
+        "current_target": BigUint::from_bytes_be(&header.target().to_be_bytes()).to_string(),
+        "epoch_start_time": epoch_start.time,
+        "prev_timestamps": prev_timestamps,
+    })))
+}
+
+async fn chain_state_proof(
+    State(rpc): State<Arc<MockRaitoRpc>>,
+    request_headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let body = rpc
+        .chain_state_proof
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let digest = sha256::Hash::hash(body).to_string();
+    let etag = HeaderValue::try_from(format!("\"{}\"", digest))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("x-goog-meta-sha256"),
+        HeaderValue::try_from(digest).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    );
+    headers.insert(ETAG, etag.clone());
+
+    // The range is ignored if the content changed since the entity tag of `If-Range`
+    let start = request_headers
+        .get(RANGE)
+        .filter(|_| request_headers.get(IF_RANGE).is_none_or(|tag| *tag == etag))
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| {
+            range
+                .strip_prefix("bytes=")?
+                .strip_suffix('-')?
+                .parse()
+                .ok()
+        });
+    match start {
+        None => Ok((headers, body.clone()).into_response()),
+        Some(start) if start >= body.len() => Err(StatusCode::RANGE_NOT_SATISFIABLE),
+        Some(start) => {
+            let content_range = format!("bytes {}-{}/{}", start, body.len() - 1, body.len());
+            headers.insert(
+                CONTENT_RANGE,
+                HeaderValue::try_from(content_range)
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+            );
+            Ok((StatusCode::PARTIAL_CONTENT, headers, body[start..].to_vec()).into_response())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::BitcoinClient;
+
+    /// Transaction spending the coinbase output of `block`
+    fn spend_coinbase(block: &Block) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(block.txdata[0].compute_txid(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_int_btc(49),
+                script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_bitcoin_rpc() {
+        let chain = regtest_chain(vec![vec![]]);
+        let tx = spend_coinbase(&chain[1]);
+        let txid = tx.compute_txid();
+        let blocks = regtest_chain(vec![vec![], vec![tx.clone()], vec![]]);
+        assert_eq!(blocks[1].block_hash(), chain[1].block_hash());
+
+        let bitcoin_rpc = MockBitcoinRpc::new(blocks.clone()).start().await.unwrap();
+        let client = BitcoinClient::new(bitcoin_rpc.url().to_string(), None).unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 3);
+        let (header, hash) = client.get_block_header_by_height(2).await.unwrap();
+        assert_eq!(header, blocks[2].header);
+        assert_eq!(client.get_block_header_ex(&hash).await.unwrap().height, 2);
+        assert_eq!(client.get_transaction(&txid, &hash).await.unwrap(), tx);
+        let prevouts = client.get_transaction_prevouts(&txid, &hash).await.unwrap();
+        assert_eq!(prevouts, chain[1].txdata[0].output);
+        let merkle_block = client.get_transaction_inclusion_proof(&txid).await.unwrap();
+        assert_eq!(merkle_block.header, blocks[2].header);
+        assert!(client.get_block_hash(4).await.is_err());
+    }
+}
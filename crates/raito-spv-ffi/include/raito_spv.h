@@ -0,0 +1,59 @@
+/*
+ * C interface of the Raito SPV proof verifier.
+ *
+ * Link against libraito_spv_ffi (cdylib or staticlib) built with
+ * `cargo build --release --package raito-spv-ffi`.
+ */
+
+#ifndef RAITO_SPV_H
+#define RAITO_SPV_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The proof is valid */
+#define RAITO_OK 0
+/* A pointer argument is null, or the configuration is not valid JSON or has invalid values */
+#define RAITO_ERR_INVALID_ARGUMENT 1
+/* The proof could not be decoded */
+#define RAITO_ERR_DESERIALIZATION 2
+/* The transaction is not included in the block */
+#define RAITO_ERR_TRANSACTION_INCLUSION 3
+/* The block is not included in the block MMR, or the MMR doesn't match the chain state */
+#define RAITO_ERR_MMR 4
+/* The chain state proof is invalid or was generated with an unexpected program */
+#define RAITO_ERR_CHAIN_STATE 5
+/* A verification policy is not met, e.g. insufficient work or a stale chain state */
+#define RAITO_ERR_POLICY 6
+/* The transaction doesn't match the expected outputs */
+#define RAITO_ERR_ASSERTION 7
+/* Unexpected failure of the verifier */
+#define RAITO_ERR_INTERNAL 8
+
+/*
+ * Verify a compressed SPV proof given as the bytes of a proof file (bzip2 compressed bincode
+ * or JSON).
+ *
+ * - bytes, len: contents of the proof file
+ * - config_json: NULL for the default configuration, or a JSON object with any of the keys
+ *   "network", "min_work", "min_confirmations", "max_proof_age" and "max_block_age"
+ * - out_report: NULL, or set to a JSON document describing the result, also on failure
+ *   ("valid" is false and "error" holds the reason); release it with raito_free_string
+ *
+ * Returns RAITO_OK if the proof is valid, or one of the RAITO_ERR_* codes.
+ */
+int32_t raito_verify_proof(const uint8_t *bytes, size_t len, const char *config_json,
+                           char **out_report);
+
+/* Release a string returned by raito_verify_proof */
+void raito_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RAITO_SPV_H */
@@ -0,0 +1,211 @@
+//! C bindings of the Raito SPV proof verifier, so that applications written in other languages
+//! can link the verifier directly instead of running the `raito-spv-client` CLI.
+//!
+//! The C declarations are in `include/raito_spv.h`. Every function is safe to call from
+//! multiple threads, and panics are caught at the boundary and reported as
+//! [RAITO_ERR_INTERNAL].
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use raito_spv_verifier::{
+    decode_proof, verify_proof_blocking, FailureClass, VerificationReport, VerifierConfig,
+    VerifyError, VerifyOptions,
+};
+use serde::Serialize;
+
+/// The proof is valid
+pub const RAITO_OK: i32 = 0;
+/// A pointer argument is null, or the configuration is not valid JSON or has invalid values
+pub const RAITO_ERR_INVALID_ARGUMENT: i32 = 1;
+/// The proof could not be decoded
+pub const RAITO_ERR_DESERIALIZATION: i32 = 2;
+/// The transaction is not included in the block
+pub const RAITO_ERR_TRANSACTION_INCLUSION: i32 = 3;
+/// The block is not included in the block MMR, or the MMR doesn't match the chain state
+pub const RAITO_ERR_MMR: i32 = 4;
+/// The chain state proof is invalid or was generated with an unexpected program
+pub const RAITO_ERR_CHAIN_STATE: i32 = 5;
+/// A verification policy is not met, e.g. insufficient work or a stale chain state
+pub const RAITO_ERR_POLICY: i32 = 6;
+/// The transaction doesn't match the expected outputs
+pub const RAITO_ERR_ASSERTION: i32 = 7;
+/// Unexpected failure of the verifier
+pub const RAITO_ERR_INTERNAL: i32 = 8;
+
+/// JSON document written to `out_report`, same shape as `raito-spv-client verify --output json`
+#[derive(Debug, Serialize)]
+struct FfiResult<'a> {
+    valid: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    report: Option<&'a VerificationReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<FailureClass>,
+}
+
+/// Error code of a verification failure
+fn error_code(class: FailureClass) -> i32 {
+    match class {
+        FailureClass::Deserialization => RAITO_ERR_DESERIALIZATION,
+        FailureClass::TransactionInclusion => RAITO_ERR_TRANSACTION_INCLUSION,
+        FailureClass::Mmr => RAITO_ERR_MMR,
+        FailureClass::ChainState => RAITO_ERR_CHAIN_STATE,
+        FailureClass::Policy => RAITO_ERR_POLICY,
+        FailureClass::Assertion => RAITO_ERR_ASSERTION,
+    }
+}
+
+/// Verify a compressed SPV proof given as the bytes of a proof file (bzip2 compressed bincode
+/// or JSON).
+///
+/// Returns [RAITO_OK] if the proof is valid, or one of the `RAITO_ERR_*` codes. Unless
+/// `out_report` is null, it is set to a JSON document describing the result, also on failure
+/// (with `valid` set to false and the reason in `error`), to be released with
+/// [raito_free_string].
+///
+/// # Safety
+///
+/// - `bytes` must point to `len` readable bytes
+/// - `config_json` must be null (default configuration) or a NUL-terminated string
+/// - `out_report` must be null or point to writable memory for a pointer
+#[no_mangle]
+pub unsafe extern "C" fn raito_verify_proof(
+    bytes: *const u8,
+    len: usize,
+    config_json: *const c_char,
+    out_report: *mut *mut c_char,
+) -> i32 {
+    if !out_report.is_null() {
+        *out_report = ptr::null_mut();
+    }
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        if bytes.is_null() {
+            return (
+                RAITO_ERR_INVALID_ARGUMENT,
+                failure("Proof bytes are null", None),
+            );
+        }
+        let bytes = std::slice::from_raw_parts(bytes, len);
+        let config = match parse_config(config_json) {
+            Ok(config) => config,
+            Err(err) => {
+                return (
+                    RAITO_ERR_INVALID_ARGUMENT,
+                    failure(&format!("Invalid configuration: {:#}", err), None),
+                )
+            }
+        };
+        match verify(bytes, &config) {
+            Ok(report) => (
+                RAITO_OK,
+                to_json(&FfiResult {
+                    valid: true,
+                    report: Some(&report),
+                    error: None,
+                    failure: None,
+                }),
+            ),
+            Err(err) => (
+                error_code(err.class()),
+                failure(&err.to_string(), Some(err.class())),
+            ),
+        }
+    }));
+    let (code, report) =
+        res.unwrap_or_else(|_| (RAITO_ERR_INTERNAL, failure("Verifier panicked", None)));
+
+    if !out_report.is_null() {
+        // Serialized JSON never contains NUL bytes
+        *out_report = CString::new(report)
+            .expect("JSON has no NUL bytes")
+            .into_raw();
+    }
+    code
+}
+
+/// Release a string returned by the verifier.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that was not released yet
+#[no_mangle]
+pub unsafe extern "C" fn raito_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Parse the JSON configuration, the default configuration if null
+unsafe fn parse_config(config_json: *const c_char) -> Result<VerifierConfig, anyhow::Error> {
+    if config_json.is_null() {
+        return Ok(VerifierConfig::default());
+    }
+    let options: VerifyOptions = serde_json::from_str(CStr::from_ptr(config_json).to_str()?)?;
+    options.into_config()
+}
+
+/// Decode and verify a proof
+fn verify(bytes: &[u8], config: &VerifierConfig) -> Result<VerificationReport, VerifyError> {
+    verify_proof_blocking(decode_proof(bytes)?, config)
+}
+
+/// JSON document of a failure
+fn failure(error: &str, class: Option<FailureClass>) -> String {
+    to_json(&FfiResult {
+        valid: false,
+        report: None,
+        error: Some(error.to_string()),
+        failure: class,
+    })
+}
+
+fn to_json(result: &FfiResult) -> String {
+    serde_json::to_string(result).expect("Verification result is serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verify through the C interface, returning the code and the JSON report
+    fn verify_c(bytes: &[u8], config_json: Option<&str>) -> (i32, serde_json::Value) {
+        let config_json = config_json.map(|s| CString::new(s).unwrap());
+        let mut report = ptr::null_mut();
+        let code = unsafe {
+            raito_verify_proof(
+                bytes.as_ptr(),
+                bytes.len(),
+                config_json.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                &mut report,
+            )
+        };
+        assert!(!report.is_null());
+        let json = serde_json::from_str(unsafe { CStr::from_ptr(report) }.to_str().unwrap());
+        unsafe { raito_free_string(report) };
+        (code, json.unwrap())
+    }
+
+    #[test]
+    fn test_verify_proof_errors() {
+        let (code, report) = verify_c(b"BZh9 not a proof", None);
+        assert_eq!(code, RAITO_ERR_DESERIALIZATION);
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["failure"], "deserialization");
+
+        let (code, report) = verify_c(b"{}", Some(r#"{"min_work": "lots"}"#));
+        assert_eq!(code, RAITO_ERR_INVALID_ARGUMENT);
+        assert!(report["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid configuration"));
+
+        let (code, _) = verify_c(b"{}", Some(r#"{"network": "signet", "unknown": 1}"#));
+        assert_eq!(code, RAITO_ERR_INVALID_ARGUMENT);
+
+        let code = unsafe { raito_verify_proof(ptr::null(), 0, ptr::null(), ptr::null_mut()) };
+        assert_eq!(code, RAITO_ERR_INVALID_ARGUMENT);
+    }
+}
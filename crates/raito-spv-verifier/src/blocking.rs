@@ -0,0 +1,57 @@
+//! Synchronous variants of the verification and fetching functions, for applications and FFI
+//! layers without an async runtime.
+//!
+//! Every call runs the async function to completion on a single-threaded tokio runtime
+//! created for the call. They panic if called from within an async runtime: use the async
+//! functions there.
+
+use std::future::Future;
+
+#[cfg(feature = "fetch")]
+use bitcoin::Txid;
+
+use crate::error::VerifyError;
+use crate::proof::{CompressedSpvProof, ProofBundle};
+use crate::verify::{VerificationReport, VerifierConfig};
+
+/// Run a future to completion on a runtime local to the call
+fn block_on<F: Future>(future: F) -> Result<F::Output, std::io::Error> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future))
+}
+
+/// Blocking variant of [crate::verify::verify_proof]
+pub fn verify_proof_blocking(
+    proof: CompressedSpvProof,
+    config: &VerifierConfig,
+) -> Result<VerificationReport, VerifyError> {
+    block_on(crate::verify::verify_proof(proof, config))?
+}
+
+/// Blocking variant of [crate::verify::verify_bundle]
+pub fn verify_bundle_blocking(
+    bundle: ProofBundle,
+    config: &VerifierConfig,
+) -> Result<Vec<Result<VerificationReport, VerifyError>>, VerifyError> {
+    block_on(crate::verify::verify_bundle(bundle, config))?
+}
+
+/// Blocking variant of [crate::fetch::fetch_compressed_proof]
+#[cfg(feature = "fetch")]
+pub fn fetch_compressed_proof_blocking(
+    txid: Txid,
+    bitcoin_rpc_url: String,
+    bitcoin_rpc_userpwd: Option<String>,
+    raito_rpc: &crate::fetch::ApiEndpoint,
+    dev: bool,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    block_on(crate::fetch::fetch_compressed_proof(
+        txid,
+        bitcoin_rpc_url,
+        bitcoin_rpc_userpwd,
+        raito_rpc,
+        dev,
+    ))?
+}
@@ -0,0 +1,191 @@
+//! Coinbase verification mode, so that miners can prove payouts: the proven transaction must
+//! be the coinbase of its block, committed to the block height and matured under proven work.
+
+use bitcoin::blockdata::constants::{COINBASE_MATURITY, SUBSIDY_HALVING_INTERVAL};
+use bitcoin::consensus::params::Params;
+use bitcoin::script::{read_scriptint, Instruction};
+use bitcoin::{Amount, Transaction};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::error::VerifyError;
+use crate::proof::ChainState;
+use crate::verify::VerifierConfig;
+use crate::work::verify_subchain_work;
+
+/// Payout of a verified coinbase transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct CoinbaseSummary {
+    /// Block subsidy at the height of the block, per the halving schedule
+    pub subsidy: Amount,
+    /// Total value of the coinbase outputs, i.e. the subsidy plus the claimed fees
+    pub total_output: Amount,
+    /// Claimed transaction fees, i.e. the coinbase output value in excess of the subsidy
+    pub fees: Amount,
+}
+
+/// Block subsidy at the given height, halved every 210,000 blocks
+pub fn block_subsidy(block_height: u32) -> Amount {
+    let halvings = block_height / SUBSIDY_HALVING_INTERVAL;
+    if halvings >= 64 {
+        return Amount::ZERO;
+    }
+    Amount::from_sat(Amount::from_int_btc(50).to_sat() >> halvings)
+}
+
+/// Verify that `transaction` is the coinbase of the block at `block_height` and that it is
+/// mature, i.e. buried under at least 100 blocks of proven work.
+///
+/// Returns the subsidy and the fees claimed by the coinbase on success.
+pub fn verify_coinbase(
+    transaction: &Transaction,
+    block_height: u32,
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<CoinbaseSummary, VerifyError> {
+    if !transaction.is_coinbase() {
+        return Err(VerifyError::NotCoinbase(transaction.compute_txid()));
+    }
+
+    // Since BIP34 the coinbase commits to the height of its block, which binds the subsidy
+    // to the halving schedule
+    if block_height >= Params::new(config.network).bip34_height {
+        let coinbase_height = coinbase_height(transaction);
+        if coinbase_height != Some(block_height) {
+            return Err(VerifyError::CoinbaseHeightMismatch {
+                coinbase_height,
+                block_height,
+            });
+        }
+    }
+
+    let blocks_on_top = chain_state.block_height.saturating_sub(block_height);
+    if blocks_on_top < COINBASE_MATURITY {
+        return Err(VerifyError::ImmatureCoinbase {
+            blocks_on_top,
+            maturity: COINBASE_MATURITY,
+        });
+    }
+    if config.skip_work {
+        warn!("Skipping coinbase maturity work check");
+    } else {
+        // Require the work of the maturity depth at the current target, on top of the
+        // configured work policy
+        let maturity_config = VerifierConfig {
+            min_confirmations: Some(COINBASE_MATURITY),
+            ..config.clone()
+        };
+        verify_subchain_work(block_height, chain_state, &maturity_config)?;
+    }
+
+    let subsidy = block_subsidy(block_height);
+    let total_output = transaction
+        .output
+        .iter()
+        .map(|output| output.value)
+        .sum::<Amount>();
+    let fees = total_output.checked_sub(subsidy).unwrap_or(Amount::ZERO);
+    info!(
+        "Coinbase is mature: subsidy {}, claimed fees {}",
+        subsidy.display_dynamic(),
+        fees.display_dynamic()
+    );
+    Ok(CoinbaseSummary {
+        subsidy,
+        total_output,
+        fees,
+    })
+}
+
+/// Block height committed by a coinbase as the first push of its script (BIP34)
+fn coinbase_height(transaction: &Transaction) -> Option<u32> {
+    let script_sig = &transaction.input.first()?.script_sig;
+    match script_sig.instructions_minimal().next()?.ok()? {
+        Instruction::PushBytes(bytes) => u32::try_from(read_scriptint(bytes.as_bytes()).ok()?).ok(),
+        // Heights past BIP34 activation don't fit in a small integer opcode
+        Instruction::Op(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::script::Builder;
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, TxIn, TxOut, Witness};
+
+    fn coinbase(height: i64, value: Amount) -> Transaction {
+        Transaction {
+            version: Version::ONE,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(height).into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    fn chain_state(block_height: u32) -> ChainState {
+        ChainState {
+            block_height,
+            total_work: "0".to_string(),
+            best_block_hash: bitcoin::BlockHash::all_zeros(),
+            current_target: "0".to_string(),
+            epoch_start_time: 0,
+            prev_timestamps: vec![],
+        }
+    }
+
+    #[test]
+    fn test_block_subsidy() {
+        assert_eq!(block_subsidy(0), Amount::from_int_btc(50));
+        assert_eq!(block_subsidy(209_999), Amount::from_int_btc(50));
+        assert_eq!(block_subsidy(210_000), Amount::from_int_btc(25));
+        assert_eq!(block_subsidy(840_000), Amount::from_sat(312_500_000));
+        assert_eq!(block_subsidy(64 * 210_000), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_verify_coinbase() {
+        let config = VerifierConfig {
+            skip_work: true,
+            ..Default::default()
+        };
+        let height = 840_000;
+        let transaction = coinbase(height as i64, Amount::from_sat(312_500_000 + 1_000));
+
+        let summary =
+            verify_coinbase(&transaction, height, &chain_state(height + 100), &config).unwrap();
+        assert_eq!(summary.subsidy, Amount::from_sat(312_500_000));
+        assert_eq!(summary.fees, Amount::from_sat(1_000));
+
+        assert!(matches!(
+            verify_coinbase(&transaction, height, &chain_state(height + 99), &config),
+            Err(VerifyError::ImmatureCoinbase { .. })
+        ));
+        assert!(matches!(
+            verify_coinbase(
+                &transaction,
+                height + 1,
+                &chain_state(height + 101),
+                &config
+            ),
+            Err(VerifyError::CoinbaseHeightMismatch { .. })
+        ));
+
+        let mut spend = transaction.clone();
+        spend.input[0].previous_output = OutPoint::new(bitcoin::Txid::all_zeros(), 0);
+        assert!(matches!(
+            verify_coinbase(&spend, height, &chain_state(height + 100), &config),
+            Err(VerifyError::NotCoinbase(_))
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+//! Typed errors of the proof verification path, so that callers can tell a malformed proof
+//! from an invalid one or from an unmet verification policy.
+
+use bitcoin::Txid;
+use num_bigint::BigUint;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::verify::format_duration;
+
+/// Error types for compressed SPV proof verification
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    /// The proof could not be read
+    #[error("Failed to read proof: {0}")]
+    Io(#[from] std::io::Error),
+    /// The proof could not be decoded
+    #[error("Failed to decode proof: {0}")]
+    Decode(Box<dyn std::error::Error + Send + Sync>),
+    /// The Merkle proof doesn't prove the transaction is included in the block
+    #[error("Invalid transaction inclusion proof: {0}")]
+    TransactionInclusion(String),
+    /// The MMR proof doesn't prove the block is included in the block MMR
+    #[error("Invalid block inclusion proof: {0}")]
+    BlockInclusion(String),
+    /// The block header hash doesn't satisfy the target encoded in its own `bits` field
+    #[error("Invalid proof of work for block {block_hash}: {reason}")]
+    InvalidProofOfWork {
+        /// Hash of the block header
+        block_hash: String,
+        /// Why the proof of work is invalid
+        reason: String,
+    },
+    /// The block MMR doesn't extend the root pinned by the user
+    #[error("Block MMR doesn't extend the trusted root: {0}")]
+    UntrustedMmr(String),
+    /// The embedded subchain headers don't link the block to the proven best block
+    #[error("Invalid subchain headers: {0}")]
+    InvalidSubchainHeaders(String),
+    /// The embedded previous outputs don't match the transaction inputs
+    #[error("Invalid previous outputs: {0}")]
+    InvalidPrevouts(String),
+    /// The embedded witness proof doesn't prove the transaction witnesses are committed to by
+    /// the block
+    #[error("Invalid witness proof: {0}")]
+    InvalidWitnessProof(String),
+    /// The policy requires a witness proof but the proof has none
+    #[error("Proof has no witness proof, required to verify the transaction witnesses")]
+    MissingWitnessProof,
+    /// The declared block height doesn't match the position of the block in the MMR
+    #[error("Block height {block_height} doesn't match the MMR leaf index {leaf_index}")]
+    BlockHeightMismatch {
+        /// Height declared by the proof
+        block_height: u32,
+        /// Leaf index of the block in the MMR inclusion proof
+        leaf_index: usize,
+    },
+    /// The size of the block MMR doesn't match the proven chain height
+    #[error("Mismatched chain height and MMR size: {leaf_count} blocks in the MMR, chain height {chain_height}")]
+    MmrSizeMismatch {
+        /// Number of blocks in the block MMR
+        leaf_count: usize,
+        /// Height of the proven chain state
+        chain_height: u32,
+    },
+    /// The block MMR root doesn't match the one committed by the chain state proof
+    #[error("Mismatched block MMR roots: {block_mmr_root} != {proven_mmr_root}")]
+    MmrRootMismatch {
+        /// Root of the block MMR the block is included in
+        block_mmr_root: String,
+        /// Root committed by the chain state proof
+        proven_mmr_root: String,
+    },
+    /// The chain state doesn't match its canonical form
+    #[error("Invalid chain state: {0}")]
+    InvalidChainState(String),
+    /// The public output of the chain state proof is malformed
+    #[error("Bootloader output: {0}")]
+    InvalidBootloaderOutput(String),
+    /// A hash claimed by the chain state proof doesn't match the expected one
+    #[error("{what} doesn't match the expected hash: {actual} != {expected}")]
+    HashMismatch {
+        /// Name of the mismatched hash
+        what: &'static str,
+        /// Hash claimed by the proof
+        actual: String,
+        /// Expected hash
+        expected: String,
+    },
+    /// The chain state proof was generated with a program that is not accepted
+    #[error("{0}")]
+    UnknownProgram(String),
+    /// The chain state proof was generated with another program than the one pinned by the
+    /// previously verified proofs
+    #[error(
+        "Chain state proof was generated by another program than the pinned one: bootloader \
+         {bootloader_hash} and task program {task_program_hash}, pinned bootloader \
+         {pinned_bootloader_hash} and task program {pinned_task_program_hash}"
+    )]
+    UnpinnedProgram {
+        /// Bootloader hash of the proof
+        bootloader_hash: String,
+        /// Task program hash of the proof
+        task_program_hash: String,
+        /// Pinned bootloader hash
+        pinned_bootloader_hash: String,
+        /// Pinned task program hash
+        pinned_task_program_hash: String,
+    },
+    /// The chain state commits to another block MMR root than the one verified before at the
+    /// same chain height
+    #[error(
+        "Block MMR root {root} at chain height {chain_height} conflicts with the root \
+         {pinned_root} verified before"
+    )]
+    PinnedRootConflict {
+        /// Height of the proven chain state
+        chain_height: u32,
+        /// Block MMR root committed by the proof
+        root: String,
+        /// Block MMR root verified before
+        pinned_root: String,
+    },
+    /// The STARK proof of the chain state is invalid
+    #[error("Invalid chain state proof: {0}")]
+    InvalidStarkProof(String),
+    /// The chain state proof is a development stub, which is only accepted in development mode
+    #[error("Chain state proof is a development stub, not accepted outside development mode")]
+    DevProof,
+    /// The verification policy is malformed, e.g. a minimum work that is not a number
+    #[error("Invalid verification policy: {0}")]
+    InvalidPolicy(String),
+    /// Not enough work on top of the block containing the transaction
+    #[error("Subchain work is less than the minimum work: {work} < {min_work}")]
+    InsufficientWork {
+        /// Work on top of the block
+        work: BigUint,
+        /// Minimum work required by the policy
+        min_work: BigUint,
+    },
+    /// The block containing the transaction is above the proven chain height
+    #[error("Block {block_height} is not proven yet, proven height is {chain_height}")]
+    BlockNotProven {
+        /// Height of the block containing the transaction
+        block_height: u32,
+        /// Height of the proven chain state
+        chain_height: u32,
+    },
+    /// The chain state or the block is older than allowed by the policy
+    #[error("{what} is too old: {} > {}", format_duration(*age), format_duration(*max_age as i64))]
+    Stale {
+        /// Name of the stale item
+        what: &'static str,
+        /// Age in seconds
+        age: i64,
+        /// Maximum age in seconds
+        max_age: u64,
+    },
+    /// The transaction doesn't match the expected outputs
+    #[error("{0:#}")]
+    Assertion(anyhow::Error),
+    /// The transaction was expected to be a coinbase
+    #[error("Transaction {0} is not a coinbase")]
+    NotCoinbase(Txid),
+    /// The height committed by the coinbase doesn't match the height of its block
+    #[error("Coinbase commits to height {}, expected {block_height}", coinbase_height.map_or("none".to_string(), |height| height.to_string()))]
+    CoinbaseHeightMismatch {
+        /// Height committed by the coinbase script, if any
+        coinbase_height: Option<u32>,
+        /// Height of the block containing the coinbase
+        block_height: u32,
+    },
+    /// Not enough blocks on top of the coinbase for its outputs to be spendable
+    #[error("Coinbase is immature: {blocks_on_top} blocks on top, {maturity} required")]
+    ImmatureCoinbase {
+        /// Number of proven blocks on top of the block containing the coinbase
+        blocks_on_top: u32,
+        /// Number of blocks required for coinbase maturity
+        maturity: u32,
+    },
+    /// The proven chain state doesn't match the live best chain
+    #[error("{0:#}")]
+    CrossCheck(anyhow::Error),
+    /// Some proofs of a bundle or a directory are invalid
+    #[error("{failures} of {total} proof(s) are invalid")]
+    InvalidBundle {
+        /// Number of invalid proofs
+        failures: usize,
+        /// Number of verified proofs
+        total: usize,
+        /// Class of the first failure
+        class: FailureClass,
+    },
+}
+
+/// Class of a verification failure, determining the exit code of the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The proof could not be read or decoded
+    Deserialization,
+    /// The transaction is not included in the block
+    TransactionInclusion,
+    /// The block is not included in the block MMR, or the MMR doesn't match the chain state
+    Mmr,
+    /// The chain state proof is invalid or was generated with an unexpected program
+    ChainState,
+    /// A verification policy is not met, e.g. insufficient work or a stale chain state
+    Policy,
+    /// The transaction doesn't match the expected outputs
+    Assertion,
+}
+
+impl VerifyError {
+    /// Class of the failure
+    pub fn class(&self) -> FailureClass {
+        match self {
+            Self::Io(_) | Self::Decode(_) | Self::InvalidPrevouts(_) => {
+                FailureClass::Deserialization
+            }
+            Self::TransactionInclusion(_) | Self::InvalidWitnessProof(_) => {
+                FailureClass::TransactionInclusion
+            }
+            Self::BlockInclusion(_)
+            | Self::InvalidProofOfWork { .. }
+            | Self::UntrustedMmr(_)
+            | Self::InvalidSubchainHeaders(_)
+            | Self::BlockHeightMismatch { .. }
+            | Self::MmrSizeMismatch { .. }
+            | Self::MmrRootMismatch { .. } => FailureClass::Mmr,
+            Self::InvalidChainState(_)
+            | Self::InvalidBootloaderOutput(_)
+            | Self::HashMismatch { .. }
+            | Self::UnknownProgram(_)
+            | Self::UnpinnedProgram { .. }
+            | Self::PinnedRootConflict { .. }
+            | Self::InvalidStarkProof(_)
+            | Self::DevProof => FailureClass::ChainState,
+            Self::InvalidPolicy(_)
+            | Self::InsufficientWork { .. }
+            | Self::BlockNotProven { .. }
+            | Self::Stale { .. }
+            | Self::ImmatureCoinbase { .. }
+            | Self::MissingWitnessProof
+            | Self::CrossCheck(_) => FailureClass::Policy,
+            Self::Assertion(_) | Self::NotCoinbase(_) | Self::CoinbaseHeightMismatch { .. } => {
+                FailureClass::Assertion
+            }
+            Self::InvalidBundle { class, .. } => *class,
+        }
+    }
+
+    /// Wrap any decoding error
+    pub fn decode(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self::Decode(err.into())
+    }
+}
+
+impl FailureClass {
+    /// Process exit code reported for this class of failure
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Deserialization => 2,
+            Self::TransactionInclusion => 3,
+            Self::Mmr => 4,
+            Self::ChainState => 5,
+            Self::Policy => 6,
+            Self::Assertion => 7,
+        }
+    }
+}
+
+/// Class of a verification failure, `None` for errors unrelated to verification
+pub fn failure_class(err: &anyhow::Error) -> Option<FailureClass> {
+    err.downcast_ref::<VerifyError>().map(VerifyError::class)
+}
@@ -0,0 +1,1640 @@
+//! Functions to fetch all components required to construct a compressed SPV proof
+//! from the Raito bridge RPC and a Bitcoin node.
+
+use std::future::Future;
+use std::io::Read;
+use std::time::Duration;
+
+use backoff::backoff::Backoff;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{
+    block::Header as BlockHeader, consensus, BlockHash, MerkleBlock, Transaction, TxOut, Txid,
+};
+use cairo_air::CairoProof;
+use futures_util::future::try_join_all;
+use raito_spv_core::{
+    bitcoin::{BitcoinClient, BitcoinClientError},
+    block_mmr::{BlockInclusionProof, BlockPeaks},
+    sparse_roots::SparseRoots,
+};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_RANGE, ETAG, IF_RANGE, RANGE,
+};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use stwo_prover::core::vcs::blake2_merkle::Blake2sMerkleHasher;
+use tracing::{info, warn};
+use web_time::Instant;
+
+use crate::proof::{ChainState, CompressedSpvProof, DevChainStateProof, WitnessProof};
+use crate::witness::build_witness_proof;
+
+/// Chain state and its recursive proof produced by the Raito node
+#[derive(Serialize, Deserialize)]
+pub struct ChainStateProof {
+    /// Canonical chain state snapshot
+    #[serde(rename = "chainstate")]
+    pub chain_state: ChainState,
+    /// Recursive STARK proof attesting `chain_state` and block MMR root validity,
+    /// the bridge RPC serves Blake2s proofs
+    #[serde(rename = "proof")]
+    pub chain_state_proof: CairoProof<Blake2sMerkleHasher>,
+}
+
+/// Bitcoin transaction inclusion data in a specific block
+#[derive(Serialize, Deserialize)]
+pub struct TransactionInclusionProof {
+    /// The full Bitcoin transaction being proven
+    pub transaction: Transaction,
+    /// Encoded PartialMerkleTree containing the Merkle path for the transaction
+    pub transaction_proof: Vec<u8>,
+    /// Header of the block that includes the transaction
+    pub block_header: BlockHeader,
+    /// Height of the block that includes the transaction
+    pub block_height: u32,
+}
+
+/// Header carrying the hex SHA-256 digest of the chain state proof as served, i.e. of the
+/// compressed bytes, set as object metadata by `scripts/data/build_recent_proof.py`
+pub const CONTENT_SHA256_HEADER: &str = "x-goog-meta-sha256";
+
+/// Maximum number of block headers served by a single request of the Raito bridge RPC
+const MAX_BRIDGE_HEADERS_PER_REQUEST: u32 = 144;
+
+/// Maximum number of concurrent header requests to a Bitcoin node or an Esplora API, below
+/// the default RPC work queue depth of Bitcoin Core (16)
+const MAX_CONCURRENT_HEADER_REQUESTS: u32 = 8;
+
+/// Timeouts and retries of the requests to the Raito bridge RPC or an Esplora API
+///
+/// Server errors (5xx), timeouts and network errors are retried with an exponential,
+/// jittered backoff; client errors (4xx) fail immediately.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Timeout of a single request, including the download of the response
+    pub timeout: Duration,
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after every retry
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            // The chain state proof can take a while to download over slow links
+            timeout: Duration::from_secs(120),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Send a request built by `operation` with a client honoring the timeout, retrying
+    /// transient failures
+    ///
+    /// - `operation`: Sends the request and reads the response
+    async fn send<T, F, Fut>(&self, operation: F) -> Result<T, anyhow::Error>
+    where
+        F: Fn(reqwest::Client) -> Fut,
+        Fut: Future<Output = Result<T, reqwest::Error>>,
+    {
+        let client = reqwest::Client::builder().timeout(self.timeout).build()?;
+        self.send_with_client(client, operation).await
+    }
+
+    /// Same as [RetryPolicy::send] with a preconfigured client, which must honor the timeout
+    async fn send_with_client<T, F, Fut>(
+        &self,
+        client: reqwest::Client,
+        operation: F,
+    ) -> Result<T, anyhow::Error>
+    where
+        F: Fn(reqwest::Client) -> Fut,
+        Fut: Future<Output = Result<T, reqwest::Error>>,
+    {
+        // Jittered by the default randomization factor of 0.5
+        let mut backoff = backoff::ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_backoff)
+            .with_max_interval(self.max_backoff)
+            .with_max_elapsed_time(None)
+            .build();
+        let mut retries = 0;
+        loop {
+            match operation(client.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(err) if retries < self.max_retries && is_retryable_error(&err) => {
+                    let delay = backoff.next_backoff().unwrap_or(self.max_backoff);
+                    retries += 1;
+                    warn!(
+                        "Raito RPC request failed, retrying in {:?} ({}/{}): {}",
+                        delay, retries, self.max_retries, err
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Determines if a failed request should be retried: server errors, timeouts and network
+/// errors are transient, client errors are not
+fn is_retryable_error(err: &reqwest::Error) -> bool {
+    match err.status() {
+        Some(status) => status.is_server_error(),
+        // Errors building or sending the request itself (e.g. an invalid URL) are not
+        // transient
+        None => err.is_timeout() || err.is_connect() || err.is_body(),
+    }
+}
+
+/// HTTP API the proof components are fetched from, the Raito bridge RPC or an Esplora API,
+/// with the timeouts and retries of its requests
+#[derive(Debug, Clone)]
+pub struct ApiEndpoint {
+    /// Base URL of the API
+    pub url: String,
+    /// Timeouts and retries of the requests
+    pub retry_policy: RetryPolicy,
+}
+
+impl ApiEndpoint {
+    /// Endpoint at `url` with the default retry policy
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Same endpoint with another retry policy
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// URL of a path of the API, `path` starting with a slash
+    fn join(&self, path: &str) -> String {
+        format!("{}{}", self.url.trim_end_matches('/'), path)
+    }
+
+    /// Get a JSON document from the API
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, anyhow::Error> {
+        let url = &self.join(path);
+        self.retry_policy
+            .send(|client| async move {
+                client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+            })
+            .await
+    }
+
+    /// Get a text document from the API
+    async fn get_text(&self, path: &str) -> Result<String, anyhow::Error> {
+        let url = &self.join(path);
+        self.retry_policy
+            .send(|client| async move {
+                client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            })
+            .await
+    }
+}
+
+/// Transaction to prove
+#[derive(Debug, Clone)]
+pub enum TransactionSource {
+    /// Transaction ID, the transaction is fetched from the Bitcoin node
+    Txid(Txid),
+    /// Raw transaction already held by the caller (e.g. from a wallet), embedded as is
+    Raw(Transaction),
+}
+
+impl TransactionSource {
+    /// ID of the transaction
+    pub fn txid(&self) -> Txid {
+        match self {
+            Self::Txid(txid) => *txid,
+            Self::Raw(transaction) => transaction.compute_txid(),
+        }
+    }
+}
+
+impl From<Txid> for TransactionSource {
+    fn from(txid: Txid) -> Self {
+        Self::Txid(txid)
+    }
+}
+
+impl From<Transaction> for TransactionSource {
+    fn from(transaction: Transaction) -> Self {
+        Self::Raw(transaction)
+    }
+}
+
+/// Where the Bitcoin data of a proof, i.e. the transaction inclusion data and the block
+/// headers, is fetched from
+#[derive(Debug, Clone)]
+pub enum BitcoinDataSource {
+    /// Bitcoin node RPC
+    Node {
+        /// URL of the Bitcoin node RPC
+        url: String,
+        /// Optional `user:password` for basic auth
+        userpwd: Option<String>,
+        /// Source of the transaction inclusion data of the blocks pruned by the node, whose
+        /// blocks are checked against the headers of the node
+        pruned_fallback: Option<Box<BitcoinDataSource>>,
+    },
+    /// Raito bridge RPC, serving the data of the Bitcoin node it indexes
+    Bridge {
+        /// Raito bridge RPC endpoint
+        endpoint: ApiEndpoint,
+    },
+    /// Esplora API, e.g. `https://blockstream.info/api`
+    Esplora {
+        /// Esplora API endpoint
+        endpoint: ApiEndpoint,
+    },
+}
+
+impl BitcoinDataSource {
+    /// Fetch the transaction inclusion data, see [fetch_transaction_proof],
+    /// [fetch_transaction_proof_from_bridge] and [fetch_transaction_proof_from_esplora]
+    ///
+    /// If a pruned node can't serve the transaction, the data is fetched from its fallback
+    /// source, if any.
+    pub async fn fetch_transaction_proof(
+        &self,
+        transaction: impl Into<TransactionSource>,
+    ) -> Result<TransactionInclusionProof, anyhow::Error> {
+        match self {
+            Self::Node {
+                url,
+                userpwd,
+                pruned_fallback,
+            } => {
+                let source = transaction.into();
+                match fetch_transaction_proof(source.clone(), url.clone(), userpwd.clone()).await {
+                    Ok(proof) => Ok(proof),
+                    Err(err) => {
+                        fetch_pruned_transaction_proof(
+                            source,
+                            url,
+                            userpwd,
+                            pruned_fallback.as_deref(),
+                            err,
+                        )
+                        .await
+                    }
+                }
+            }
+            Self::Bridge { endpoint } => {
+                fetch_transaction_proof_from_bridge(transaction, endpoint).await
+            }
+            Self::Esplora { endpoint } => {
+                fetch_transaction_proof_from_esplora(transaction, endpoint).await
+            }
+        }
+    }
+
+    /// Fetch the headers of the blocks on top of the target block, see
+    /// [fetch_subchain_headers], [fetch_subchain_headers_from_bridge] and
+    /// [fetch_subchain_headers_from_esplora]
+    pub async fn fetch_subchain_headers(
+        &self,
+        block_height: u32,
+        chain_height: u32,
+    ) -> Result<Vec<BlockHeader>, anyhow::Error> {
+        match self {
+            // Pruned nodes keep all the headers
+            Self::Node { url, userpwd, .. } => {
+                fetch_subchain_headers(block_height, chain_height, url.clone(), userpwd.clone())
+                    .await
+            }
+            Self::Bridge { endpoint } => {
+                fetch_subchain_headers_from_bridge(block_height, chain_height, endpoint).await
+            }
+            Self::Esplora { endpoint } => {
+                fetch_subchain_headers_from_esplora(block_height, chain_height, endpoint).await
+            }
+        }
+    }
+
+    /// Fetch the header of the block at a height of the best chain, to check the block of a
+    /// transaction proof against another source
+    pub async fn fetch_block_header(
+        &self,
+        block_height: u32,
+    ) -> Result<BlockHeader, anyhow::Error> {
+        match self {
+            Self::Node { url, userpwd, .. } => {
+                let bitcoin_client = BitcoinClient::new(url.clone(), userpwd.clone())?;
+                let (header, _) = bitcoin_client
+                    .get_block_header_by_height(block_height)
+                    .await?;
+                Ok(header)
+            }
+            Self::Bridge { endpoint } => {
+                fetch_block_header_from_bridge(block_height, endpoint).await
+            }
+            Self::Esplora { endpoint } => {
+                fetch_block_header_from_esplora(block_height, endpoint).await
+            }
+        }
+    }
+
+    /// Name of the source, for the messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Node { .. } => "Bitcoin node",
+            Self::Bridge { .. } => "Raito bridge",
+            Self::Esplora { .. } => "Esplora",
+        }
+    }
+}
+
+/// Check that the block of a transaction proof is the block at its height in the best chain
+/// of an independent source, e.g. against a remote Bitcoin node that is only semi-trusted
+///
+/// - `block_header`: Header of the block fetched from the primary source
+/// - `block_height`: Height of the block
+/// - `source`: Independent source of the block headers
+pub async fn check_block_header(
+    block_header: &BlockHeader,
+    block_height: u32,
+    source: &BitcoinDataSource,
+) -> Result<(), anyhow::Error> {
+    info!(
+        "Checking block {} against the {} ...",
+        block_height,
+        source.name()
+    );
+    let header = source.fetch_block_header(block_height).await?;
+    if header != *block_header {
+        anyhow::bail!(
+            "Sources disagree on the block at height {}: {} according to the transaction proof, \
+             {} according to the {}",
+            block_height,
+            block_header.block_hash(),
+            header.block_hash(),
+            source.name()
+        );
+    }
+    Ok(())
+}
+
+/// Fetch the transaction inclusion data the Bitcoin node failed to serve with `err` from its
+/// fallback source, if the node is pruned
+///
+/// The block of the fetched data must be in the chain of the node, which keeps the headers
+/// of the pruned blocks, so that the proof is the same as if the node had served it.
+async fn fetch_pruned_transaction_proof(
+    source: TransactionSource,
+    bitcoin_rpc_url: &str,
+    bitcoin_rpc_userpwd: &Option<String>,
+    pruned_fallback: Option<&BitcoinDataSource>,
+    err: anyhow::Error,
+) -> Result<TransactionInclusionProof, anyhow::Error> {
+    // Only an unknown transaction may be in a pruned block, other failures are returned as is
+    if !err
+        .downcast_ref::<BitcoinClientError>()
+        .is_some_and(BitcoinClientError::is_rejected)
+    {
+        return Err(err);
+    }
+    let bitcoin_client =
+        BitcoinClient::new(bitcoin_rpc_url.to_string(), bitcoin_rpc_userpwd.clone())?;
+    let Some(prune_height) = bitcoin_client.get_prune_height().await? else {
+        return Err(err);
+    };
+    let txid = source.txid();
+    let Some(fallback) = pruned_fallback else {
+        anyhow::bail!(
+            "Transaction {} not found by the Bitcoin node, which pruned the blocks below height \
+             {}: the transaction may be in a pruned block, use a fallback source such as Esplora \
+             or the Raito bridge ({})",
+            txid,
+            prune_height,
+            err
+        );
+    };
+
+    warn!(
+        "Transaction {} not found by the Bitcoin node, which pruned the blocks below height {}, \
+         trying the fallback source",
+        txid, prune_height
+    );
+    let proof = Box::pin(fallback.fetch_transaction_proof(source)).await?;
+    let block_hash = proof.block_header.block_hash();
+    if bitcoin_client.get_block_hash(proof.block_height).await? != block_hash {
+        anyhow::bail!(
+            "Fallback source served block {} at height {}, which is not in the chain of the \
+             Bitcoin node",
+            block_hash,
+            proof.block_height
+        );
+    }
+    Ok(proof)
+}
+
+/// Transaction inclusion data as served by the Raito bridge RPC, consensus-encoded in hex
+#[derive(Deserialize)]
+struct BridgeTransactionProof {
+    transaction: String,
+    transaction_proof: String,
+    block_header: String,
+    block_height: u32,
+}
+
+/// Fetch all components required to construct a `CompressedSpvProof`
+///
+/// - `txid`: Transaction id to prove
+/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
+/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn fetch_compressed_proof(
+    txid: Txid,
+    bitcoin_rpc_url: String,
+    bitcoin_rpc_userpwd: Option<String>,
+    raito_rpc: &ApiEndpoint,
+    dev: bool,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    // The chain state proof download dominates wall time, so fetch the transaction proof
+    // concurrently; the block proof depends on both and is fetched as soon as they are ready
+    let (chain_state_proof, transaction_proof) = tokio::try_join!(
+        async {
+            fetch_chain_state_proof(raito_rpc)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch chain state proof: {:?}", e))
+        },
+        async {
+            fetch_transaction_proof(txid, bitcoin_rpc_url, bitcoin_rpc_userpwd)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch transaction proof: {:?}", e))
+        },
+    )?;
+
+    build_compressed_proof(chain_state_proof, transaction_proof, raito_rpc, dev).await
+}
+
+/// Construct a `CompressedSpvProof` from an already fetched chain state proof and
+/// transaction inclusion proof, fetching the missing block MMR inclusion proof
+///
+/// - `chain_state_proof`: Chain state and its recursive proof
+/// - `transaction_proof`: Transaction inclusion data
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn build_compressed_proof(
+    chain_state_proof: ChainStateProof,
+    transaction_proof: TransactionInclusionProof,
+    raito_rpc: &ApiEndpoint,
+    dev: bool,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    let ChainStateProof {
+        chain_state,
+        chain_state_proof,
+    } = chain_state_proof;
+
+    let TransactionInclusionProof {
+        transaction,
+        transaction_proof,
+        block_header,
+        block_height,
+    } = transaction_proof;
+
+    let block_header_proof =
+        fetch_block_proof(block_height, chain_state.block_height, raito_rpc, dev)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch block proof: {:?}", e))?;
+
+    Ok(CompressedSpvProof {
+        chain_state,
+        chain_state_proof: chain_state_proof.into(),
+        block_header,
+        block_height,
+        block_header_proof,
+        transaction,
+        transaction_proof,
+        subchain_headers: None,
+        prevouts: None,
+        witness_proof: None,
+    })
+}
+
+/// Assemble a `CompressedSpvProof` from a development bridge node alone, with a development
+/// stub in place of the chain state proof: the chain state reconstructed by the bridge and the
+/// root of its block MMR are claimed, not proven, so the proof only passes verifiers in
+/// development mode
+///
+/// - `transaction`: Transaction id to prove, or raw transaction to embed
+/// - `raito_rpc`: Development Raito bridge RPC endpoint
+/// - `chain_height`: Height of the chain state, the latest indexed block if not provided
+/// - `bootloader_hash`, `task_program_hash`: Program hashes claimed by the stub
+pub async fn fetch_dev_compressed_proof(
+    transaction: impl Into<TransactionSource>,
+    raito_rpc: &ApiEndpoint,
+    chain_height: Option<u32>,
+    bootloader_hash: &str,
+    task_program_hash: &str,
+) -> Result<CompressedSpvProof, anyhow::Error> {
+    let chain_height = match chain_height {
+        Some(chain_height) => chain_height,
+        None => get_mmr_height(raito_rpc).await?,
+    };
+    let source = BitcoinDataSource::Bridge {
+        endpoint: raito_rpc.clone(),
+    };
+    let (chain_state, transaction_proof) = tokio::try_join!(
+        async {
+            fetch_chain_state(raito_rpc, chain_height)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch chain state: {:?}", e))
+        },
+        async {
+            source
+                .fetch_transaction_proof(transaction)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch transaction proof: {:?}", e))
+        },
+    )?;
+    let TransactionInclusionProof {
+        transaction,
+        transaction_proof,
+        block_header,
+        block_height,
+    } = transaction_proof;
+
+    let block_header_proof = fetch_block_proof(block_height, chain_height, raito_rpc, false)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch block proof: {:?}", e))?;
+    let block_mmr_root = BlockPeaks::from_proof(&block_header_proof)?.root_hash()?;
+    warn!(
+        "DEV PROOF: claiming chain state {} and block MMR root {} without a chain state proof",
+        chain_height, block_mmr_root
+    );
+    let chain_state_proof = DevChainStateProof::new(
+        &chain_state,
+        &block_mmr_root,
+        bootloader_hash,
+        task_program_hash,
+    )?;
+
+    Ok(CompressedSpvProof {
+        chain_state,
+        chain_state_proof: chain_state_proof.into(),
+        block_header,
+        block_height,
+        block_header_proof,
+        transaction,
+        transaction_proof,
+        subchain_headers: None,
+        prevouts: None,
+        witness_proof: None,
+    })
+}
+
+/// Fetch the chain state after the block at `height`, reconstructed by the Raito bridge from
+/// the headers of its Bitcoin node
+pub async fn fetch_chain_state(
+    raito_rpc: &ApiEndpoint,
+    height: u32,
+) -> Result<ChainState, anyhow::Error> {
+    raito_rpc.get_json(&format!("/chainstate/{}", height)).await
+}
+
+/// Fetch the sparse roots of the block MMR with the blocks up to `chain_height` from the
+/// Raito bridge RPC
+pub async fn fetch_sparse_roots(
+    raito_rpc: &ApiEndpoint,
+    chain_height: u32,
+) -> Result<SparseRoots, anyhow::Error> {
+    raito_rpc
+        .get_json(&format!("/roots?chain_height={}", chain_height))
+        .await
+}
+
+/// Fetch the latest chain state proof from the Raito bridge RPC
+///
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn fetch_chain_state_proof(
+    raito_rpc: &ApiEndpoint,
+) -> Result<ChainStateProof, anyhow::Error> {
+    fetch_chain_state_proof_with_progress(raito_rpc, |_, _| {}).await
+}
+
+/// Fetch the latest chain state proof from the Raito bridge RPC, reporting the download
+/// progress
+///
+/// The compressed proof is downloaded as is: a retried download resumes where the previous
+/// attempt stopped, and the content is checked against the digest of the
+/// [CONTENT_SHA256_HEADER] header, if served, before being decompressed.
+///
+/// - `raito_rpc`: Raito bridge RPC endpoint
+/// - `on_progress`: Called with the number of bytes downloaded so far and the total size
+///   (if known) after every received chunk
+pub async fn fetch_chain_state_proof_with_progress(
+    raito_rpc: &ApiEndpoint,
+    on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<ChainStateProof, anyhow::Error> {
+    info!("Fetching latest chain state proof ...");
+    let url = raito_rpc.join("/chainstate-proof/recent_proof");
+    let bytes = download_resumable(&url, &raito_rpc.retry_policy, None, on_progress).await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Download the content served at `url`, reporting the download progress
+///
+/// A retried download resumes where the previous attempt stopped if the content is unchanged
+/// (same entity tag), and the content is checked against the digest of the
+/// [CONTENT_SHA256_HEADER] header, if served, before being decompressed.
+///
+/// - `url`: URL of the content
+/// - `policy`: Timeout and retries of the requests
+/// - `max_len`: Optional maximum size of the content in bytes, both as served and decompressed
+/// - `on_progress`: Called with the number of bytes downloaded so far and the total size
+///   (if known) after every received chunk
+pub async fn download_resumable(
+    url: &str,
+    policy: &RetryPolicy,
+    max_len: Option<u64>,
+    on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<Vec<u8>, anyhow::Error> {
+    // Decompressed by hand, as a resumed download only holds the end of the compressed stream
+    let client = reqwest::Client::builder()
+        .timeout(policy.timeout)
+        .no_gzip()
+        .no_brotli()
+        .no_zstd()
+        .build()?;
+    let download = tokio::sync::Mutex::new(ResumableDownload {
+        max_len,
+        ..Default::default()
+    });
+    let (state, on_progress) = (&download, &std::sync::Mutex::new(on_progress));
+    let start = Instant::now();
+    policy
+        .send_with_client(client, |client| async move {
+            let mut download = state.lock().await;
+            download.receive(&client, url, on_progress).await
+        })
+        .await?;
+    let download = download.into_inner();
+    let downloaded = download.bytes.len();
+    let bytes = download.finish()?;
+
+    let elapsed = start.elapsed().as_secs_f64();
+    info!(
+        "Downloaded {} bytes from {} in {:.1}s ({:.0} KiB/s)",
+        downloaded,
+        url,
+        elapsed,
+        downloaded as f64 / 1024.0 / elapsed.max(f64::EPSILON)
+    );
+    Ok(bytes)
+}
+
+/// Download kept across attempts to resume it with a range request
+#[derive(Default)]
+struct ResumableDownload {
+    /// Content received so far, as served
+    bytes: Vec<u8>,
+    /// Total size of the content, if known
+    total_len: Option<u64>,
+    /// Entity tag of the content, resuming requires the content to be unchanged
+    etag: Option<HeaderValue>,
+    /// Expected hex SHA-256 digest of the content, if served
+    sha256: Option<String>,
+    /// Whether the content is gzip-compressed
+    gzip: bool,
+    /// Maximum size of the content, the rest is not downloaded once exceeded
+    max_len: Option<u64>,
+}
+
+impl ResumableDownload {
+    /// Request the rest of the content, or all of it if the download can't be resumed
+    async fn request(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = client.get(url).header(ACCEPT_ENCODING, "gzip");
+        if let (false, Some(etag)) = (self.bytes.is_empty(), &self.etag) {
+            request = request
+                .header(RANGE, format!("bytes={}-", self.bytes.len()))
+                .header(IF_RANGE, etag.clone());
+        }
+        request.send().await
+    }
+
+    /// Receive the rest of the content, appending it to the bytes received so far
+    ///
+    /// - `on_progress`: Called with the number of bytes received and the total size
+    async fn receive(
+        &mut self,
+        client: &reqwest::Client,
+        url: &str,
+        on_progress: &std::sync::Mutex<impl FnMut(u64, Option<u64>)>,
+    ) -> Result<(), reqwest::Error> {
+        let mut response = self.request(client, url).await?;
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT
+            && content_range_start(response.headers()) == Some(self.bytes.len() as u64);
+        if resumed {
+            info!("Resuming download of {} at byte {}", url, self.bytes.len());
+        } else {
+            // Either the first attempt, or the content changed since the previous one
+            if response.status() == StatusCode::PARTIAL_CONTENT
+                || response.status() == StatusCode::RANGE_NOT_SATISFIABLE
+            {
+                self.etag = None;
+                response = self.request(client, url).await?;
+            }
+            response = response.error_for_status()?;
+            let headers = response.headers();
+            *self = Self {
+                bytes: Vec::new(),
+                total_len: response.content_length(),
+                etag: headers.get(ETAG).cloned(),
+                sha256: headers
+                    .get(CONTENT_SHA256_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_lowercase),
+                gzip: headers
+                    .get(CONTENT_ENCODING)
+                    .is_some_and(|value| value == "gzip"),
+                max_len: self.max_len,
+            };
+        }
+
+        // Rejected by `finish`, not retried
+        while !self.exceeds_max_len() {
+            let Some(chunk) = response.chunk().await? else {
+                break;
+            };
+            self.bytes.extend_from_slice(&chunk);
+            (on_progress.lock().unwrap())(self.bytes.len() as u64, self.total_len);
+        }
+        Ok(())
+    }
+
+    /// Whether the content, as announced or as received so far, is larger than the maximum
+    fn exceeds_max_len(&self) -> bool {
+        let len = self
+            .total_len
+            .unwrap_or_default()
+            .max(self.bytes.len() as u64);
+        self.max_len.is_some_and(|max_len| len > max_len)
+    }
+
+    /// Check the received content against the served digest, and decompress it
+    fn finish(self) -> Result<Vec<u8>, anyhow::Error> {
+        if self.exceeds_max_len() {
+            anyhow::bail!(
+                "Download exceeds the limit of {} bytes",
+                self.max_len.unwrap_or_default()
+            );
+        }
+        match self.sha256 {
+            Some(expected) => {
+                let digest = sha256::Hash::hash(&self.bytes).to_string();
+                if digest != expected {
+                    anyhow::bail!(
+                        "Download checksum mismatch: expected {}, got {}",
+                        expected,
+                        digest
+                    );
+                }
+            }
+            None => info!("No checksum served with the download, skipping the check"),
+        }
+        if !self.gzip {
+            return Ok(self.bytes);
+        }
+        let mut bytes = Vec::new();
+        flate2::read::GzDecoder::new(self.bytes.as_slice())
+            .take(self.max_len.map_or(u64::MAX, |max_len| max_len + 1))
+            .read_to_end(&mut bytes)?;
+        if let Some(max_len) = self.max_len.filter(|&max_len| bytes.len() as u64 > max_len) {
+            anyhow::bail!(
+                "Decompressed download exceeds the limit of {} bytes",
+                max_len
+            );
+        }
+        Ok(bytes)
+    }
+}
+
+/// First byte of a partial response, from its `Content-Range` header
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = range.strip_prefix("bytes ")?.split_once('-')?;
+    start.parse().ok()
+}
+
+/// Fetch the transaction inclusion data from a Bitcoin RPC
+///
+/// - `transaction`: Transaction id to fetch, or raw transaction for which only the Merkle
+///   path and the block header are fetched
+/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
+/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
+pub async fn fetch_transaction_proof(
+    transaction: impl Into<TransactionSource>,
+    bitcoin_rpc_url: String,
+    bitcoin_rpc_userpwd: Option<String>,
+) -> Result<TransactionInclusionProof, anyhow::Error> {
+    let source = transaction.into();
+    let txid = source.txid();
+    info!("Fetching transaction proof for {} ...", txid);
+    let bitcoin_client = BitcoinClient::new(bitcoin_rpc_url, bitcoin_rpc_userpwd)?;
+    let MerkleBlock { header, txn } = bitcoin_client
+        .get_transaction_inclusion_proof(&txid)
+        .await?;
+
+    let block_hash = header.block_hash();
+    let transaction = match source {
+        TransactionSource::Txid(_) => bitcoin_client.get_transaction(&txid, &block_hash).await?,
+        TransactionSource::Raw(transaction) => transaction,
+    };
+
+    let block_header_ex = bitcoin_client.get_block_header_ex(&block_hash).await?;
+    let block_height = block_header_ex.height;
+
+    Ok(TransactionInclusionProof {
+        transaction,
+        transaction_proof: consensus::encode::serialize(&txn),
+        block_header: header,
+        block_height: block_height as u32,
+    })
+}
+
+/// Fetch the transaction inclusion data from the Raito bridge RPC, which looks it up on the
+/// Bitcoin node it indexes
+///
+/// - `transaction`: Transaction id to fetch, or raw transaction for which only the Merkle
+///   path and the block header are fetched
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn fetch_transaction_proof_from_bridge(
+    transaction: impl Into<TransactionSource>,
+    raito_rpc: &ApiEndpoint,
+) -> Result<TransactionInclusionProof, anyhow::Error> {
+    let source = transaction.into();
+    let txid = source.txid();
+    info!(
+        "Fetching transaction proof for {} from the bridge ...",
+        txid
+    );
+    let proof: BridgeTransactionProof = raito_rpc
+        .get_json(&format!("/transaction-proof/{}", txid))
+        .await?;
+
+    let fetched: Transaction = consensus::encode::deserialize_hex(&proof.transaction)?;
+    if fetched.compute_txid() != txid {
+        anyhow::bail!(
+            "Bridge served transaction {} instead of {}",
+            fetched.compute_txid(),
+            txid
+        );
+    }
+    let transaction = match source {
+        TransactionSource::Txid(_) => fetched,
+        TransactionSource::Raw(transaction) => transaction,
+    };
+    Ok(TransactionInclusionProof {
+        transaction,
+        transaction_proof: hex::decode(&proof.transaction_proof)?,
+        block_header: consensus::encode::deserialize_hex(&proof.block_header)?,
+        block_height: proof.block_height,
+    })
+}
+
+/// Confirmation status of a transaction as served by the Esplora `/tx/:txid/status` endpoint
+#[derive(Deserialize)]
+struct EsploraTxStatus {
+    confirmed: bool,
+    block_height: Option<u32>,
+    block_hash: Option<BlockHash>,
+}
+
+/// Fetch the transaction inclusion data from an Esplora API
+///
+/// - `transaction`: Transaction id to fetch, or raw transaction for which only the Merkle
+///   path and the block header are fetched
+/// - `esplora`: Esplora API endpoint, e.g. `https://blockstream.info/api`
+pub async fn fetch_transaction_proof_from_esplora(
+    transaction: impl Into<TransactionSource>,
+    esplora: &ApiEndpoint,
+) -> Result<TransactionInclusionProof, anyhow::Error> {
+    let source = transaction.into();
+    let txid = source.txid();
+    info!("Fetching transaction proof for {} from Esplora ...", txid);
+    let status: EsploraTxStatus = esplora.get_json(&format!("/tx/{}/status", txid)).await?;
+    let (true, Some(block_height), Some(block_hash)) =
+        (status.confirmed, status.block_height, status.block_hash)
+    else {
+        anyhow::bail!("Transaction {} is not confirmed", txid);
+    };
+
+    let merkle_block = esplora
+        .get_text(&format!("/tx/{}/merkleblock-proof", txid))
+        .await?;
+    let MerkleBlock { header, txn } = consensus::encode::deserialize_hex(merkle_block.trim())?;
+    if header.block_hash() != block_hash {
+        anyhow::bail!(
+            "Esplora served the Merkle path of block {} instead of {}",
+            header.block_hash(),
+            block_hash
+        );
+    }
+
+    let transaction = match source {
+        TransactionSource::Txid(_) => {
+            let hex = esplora.get_text(&format!("/tx/{}/hex", txid)).await?;
+            let fetched: Transaction = consensus::encode::deserialize_hex(hex.trim())?;
+            if fetched.compute_txid() != txid {
+                anyhow::bail!(
+                    "Esplora served transaction {} instead of {}",
+                    fetched.compute_txid(),
+                    txid
+                );
+            }
+            fetched
+        }
+        TransactionSource::Raw(transaction) => transaction,
+    };
+    Ok(TransactionInclusionProof {
+        transaction,
+        transaction_proof: consensus::encode::serialize(&txn),
+        block_header: header,
+        block_height,
+    })
+}
+
+/// Fetch the headers of the blocks on top of the target block, up to the chain height, from
+/// an Esplora API
+///
+/// - `block_height`: Height of the target block
+/// - `chain_height`: Height of the best block of the proven chain state
+/// - `esplora`: Esplora API endpoint
+pub async fn fetch_subchain_headers_from_esplora(
+    block_height: u32,
+    chain_height: u32,
+    esplora: &ApiEndpoint,
+) -> Result<Vec<BlockHeader>, anyhow::Error> {
+    info!(
+        "Fetching {} subchain headers from Esplora ...",
+        chain_height.saturating_sub(block_height)
+    );
+    let headers = fetch_headers_concurrently(block_height + 1, chain_height, |height| {
+        fetch_block_header_from_esplora(height, esplora)
+    })
+    .await?;
+    if let Some(offset) = headers
+        .windows(2)
+        .position(|pair| pair[1].prev_blockhash != pair[0].block_hash())
+    {
+        anyhow::bail!(
+            "Esplora served a header at height {} off the chain",
+            block_height + 2 + offset as u32
+        );
+    }
+    Ok(headers)
+}
+
+/// Fetch the headers of the blocks from `start` to `end` (inclusive), in order, with at most
+/// [MAX_CONCURRENT_HEADER_REQUESTS] requests in flight
+///
+/// - `fetch_header`: Fetches the header of the block at a height
+async fn fetch_headers_concurrently<F, Fut>(
+    start: u32,
+    end: u32,
+    fetch_header: F,
+) -> Result<Vec<BlockHeader>, anyhow::Error>
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = Result<BlockHeader, anyhow::Error>>,
+{
+    let mut headers = Vec::new();
+    let mut height = start;
+    while height <= end {
+        let last = end.min(height.saturating_add(MAX_CONCURRENT_HEADER_REQUESTS - 1));
+        headers.extend(try_join_all((height..=last).map(&fetch_header)).await?);
+        height = last + 1;
+    }
+    Ok(headers)
+}
+
+/// Fetch the header of the block at a height of the best chain from an Esplora API
+///
+/// - `block_height`: Height of the block
+/// - `esplora`: Esplora API endpoint
+pub async fn fetch_block_header_from_esplora(
+    block_height: u32,
+    esplora: &ApiEndpoint,
+) -> Result<BlockHeader, anyhow::Error> {
+    let block_hash = esplora
+        .get_text(&format!("/block-height/{}", block_height))
+        .await?;
+    let block_hash: BlockHash = block_hash.trim().parse()?;
+    let header = esplora
+        .get_text(&format!("/block/{}/header", block_hash))
+        .await?;
+    let header: BlockHeader = consensus::encode::deserialize_hex(header.trim())?;
+    if header.block_hash() != block_hash {
+        anyhow::bail!(
+            "Esplora served header {} for block {}",
+            header.block_hash(),
+            block_hash
+        );
+    }
+    Ok(header)
+}
+
+/// Fetch the header of the block at a height of the chain indexed by the Raito bridge RPC
+///
+/// - `block_height`: Height of the block
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn fetch_block_header_from_bridge(
+    block_height: u32,
+    raito_rpc: &ApiEndpoint,
+) -> Result<BlockHeader, anyhow::Error> {
+    let headers: Vec<String> = raito_rpc
+        .get_json(&format!("/block-headers/{}?count=1", block_height))
+        .await?;
+    let header = headers
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Bridge has not indexed block {} yet", block_height))?;
+    Ok(consensus::encode::deserialize_hex(header)?)
+}
+
+/// Fetch the headers of the blocks on top of the target block, up to the chain height, from
+/// the Raito bridge RPC
+///
+/// - `block_height`: Height of the target block
+/// - `chain_height`: Height of the best block of the proven chain state
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn fetch_subchain_headers_from_bridge(
+    block_height: u32,
+    chain_height: u32,
+    raito_rpc: &ApiEndpoint,
+) -> Result<Vec<BlockHeader>, anyhow::Error> {
+    info!(
+        "Fetching {} subchain headers from the bridge ...",
+        chain_height.saturating_sub(block_height)
+    );
+    let mut headers = Vec::new();
+    let mut height = block_height + 1;
+    while height <= chain_height {
+        let count = (chain_height - height + 1).min(MAX_BRIDGE_HEADERS_PER_REQUEST);
+        let chunk: Vec<String> = raito_rpc
+            .get_json(&format!("/block-headers/{}?count={}", height, count))
+            .await?;
+        if chunk.is_empty() {
+            anyhow::bail!("Bridge has not indexed block {} yet", height);
+        }
+        for header in &chunk {
+            headers.push(consensus::encode::deserialize_hex(header)?);
+        }
+        height += chunk.len() as u32;
+    }
+    Ok(headers)
+}
+
+/// Fetch the headers of the blocks on top of the target block, up to the chain height
+///
+/// - `block_height`: Height of the target block
+/// - `chain_height`: Height of the best block of the proven chain state
+/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
+/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
+pub async fn fetch_subchain_headers(
+    block_height: u32,
+    chain_height: u32,
+    bitcoin_rpc_url: String,
+    bitcoin_rpc_userpwd: Option<String>,
+) -> Result<Vec<BlockHeader>, anyhow::Error> {
+    info!(
+        "Fetching {} subchain headers ...",
+        chain_height.saturating_sub(block_height)
+    );
+    let bitcoin_client = &BitcoinClient::new(bitcoin_rpc_url, bitcoin_rpc_userpwd)?;
+    fetch_headers_concurrently(block_height + 1, chain_height, |height| async move {
+        let (header, _) = bitcoin_client.get_block_header_by_height(height).await?;
+        Ok(header)
+    })
+    .await
+}
+
+/// Fetch the outputs spent by the inputs of a transaction, in input order
+///
+/// - `txid`: Transaction id
+/// - `block_hash`: Hash of the block containing the transaction
+/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
+/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
+pub async fn fetch_prevouts(
+    txid: Txid,
+    block_hash: BlockHash,
+    bitcoin_rpc_url: String,
+    bitcoin_rpc_userpwd: Option<String>,
+) -> Result<Vec<TxOut>, anyhow::Error> {
+    info!("Fetching previous outputs of {} ...", txid);
+    let bitcoin_client = BitcoinClient::new(bitcoin_rpc_url, bitcoin_rpc_userpwd)?;
+    Ok(bitcoin_client
+        .get_transaction_prevouts(&txid, &block_hash)
+        .await?)
+}
+
+/// Fetch the coinbase of the block containing a transaction and the Merkle branches to the
+/// witness commitment, downloading the full block
+///
+/// - `txid`: Transaction id
+/// - `block_hash`: Hash of the block containing the transaction
+/// - `bitcoin_rpc_url`: URL of the Bitcoin node RPC
+/// - `bitcoin_rpc_userpwd`: Optional `user:password` for basic auth
+pub async fn fetch_witness_proof(
+    txid: Txid,
+    block_hash: BlockHash,
+    bitcoin_rpc_url: String,
+    bitcoin_rpc_userpwd: Option<String>,
+) -> Result<WitnessProof, anyhow::Error> {
+    info!("Fetching block {} for the witness proof ...", block_hash);
+    let bitcoin_client = BitcoinClient::new(bitcoin_rpc_url, bitcoin_rpc_userpwd)?;
+    let block = bitcoin_client.get_block(&block_hash).await?;
+    build_witness_proof(&block, &txid)
+        .ok_or_else(|| anyhow::anyhow!("Transaction {} is not in block {}", txid, block_hash))
+}
+
+/// Fetch the block MMR inclusion proof from the Raito bridge RPC
+///
+/// The proof is requested at the MMR state committed to by the chain state proof, so that
+/// both proofs agree on the MMR size.
+///
+/// - `block_height`: Height of the block to prove
+/// - `chain_height`: Proven best height (chain head)
+/// - `raito_rpc`: Raito bridge RPC endpoint
+pub async fn fetch_block_proof(
+    block_height: u32,
+    chain_height: u32,
+    raito_rpc: &ApiEndpoint,
+    dev: bool,
+) -> Result<BlockInclusionProof, anyhow::Error> {
+    let dev_rpc;
+    let raito_rpc = if dev {
+        info!("DEV MODE: using local bridge node");
+        dev_rpc = ApiEndpoint {
+            url: "http://127.0.0.1:5000".to_string(),
+            ..raito_rpc.clone()
+        };
+        &dev_rpc
+    } else {
+        raito_rpc
+    };
+    let mmr_height = get_mmr_height(raito_rpc).await?;
+    if mmr_height < chain_height {
+        return Err(anyhow::anyhow!(
+            "MMR height {} is less than chain height {}",
+            mmr_height,
+            chain_height
+        ));
+    }
+    // `chain_height` is kept for the bridges that predate the `block_count` parameter
+    let path = format!(
+        "/block-inclusion-proof/{}?chain_height={}&block_count={}",
+        block_height,
+        chain_height,
+        chain_height + 1
+    );
+
+    if block_height > chain_height {
+        return Err(anyhow::anyhow!(
+            "Block height {} cannot be greater than chain height {}",
+            block_height,
+            chain_height
+        ));
+    }
+
+    info!("Fetching block proof for block height {} ...", block_height);
+    raito_rpc.get_json(&path).await
+}
+
+/// Get the current MMR height from the Raito bridge RPC
+pub async fn get_mmr_height(raito_rpc: &ApiEndpoint) -> Result<u32, anyhow::Error> {
+    raito_rpc.get_json("/head").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VerifyError;
+    use crate::proof::DEV_CHANNEL;
+    use crate::verify::{verify_proof, CheckStatus, VerifierConfig};
+    use raito_spv_core::block_mmr::verify_block_inclusion;
+    use raito_spv_core::test_utils::{regtest_chain, MockBitcoinRpc, MockRaitoRpc};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_fetch_from_mock_servers() {
+        let blocks = regtest_chain(vec![vec![]; 3]);
+        let headers: Vec<_> = blocks.iter().map(|block| block.header).collect();
+        let txid = blocks[2].txdata[0].compute_txid();
+        let bitcoin_rpc = MockBitcoinRpc::new(blocks).start().await.unwrap();
+        let raito_rpc = MockRaitoRpc::new(&headers)
+            .await
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+        let bitcoin_rpc_url = bitcoin_rpc.url().to_string();
+        let raito_endpoint = ApiEndpoint::new(raito_rpc.url());
+
+        let transaction_proof = fetch_transaction_proof(txid, bitcoin_rpc_url.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(transaction_proof.block_height, 2);
+        assert_eq!(transaction_proof.transaction.compute_txid(), txid);
+        let raw_transaction_proof = fetch_transaction_proof(
+            transaction_proof.transaction.clone(),
+            bitcoin_rpc_url.clone(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            raw_transaction_proof.transaction_proof,
+            transaction_proof.transaction_proof
+        );
+        let subchain_headers = fetch_subchain_headers(2, 3, bitcoin_rpc_url.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(subchain_headers, headers[3..]);
+        let block_hash = headers[2].block_hash();
+        let prevouts = fetch_prevouts(txid, block_hash, bitcoin_rpc_url.clone(), None)
+            .await
+            .unwrap();
+        assert!(prevouts.is_empty());
+        let witness_proof = fetch_witness_proof(txid, block_hash, bitcoin_rpc_url.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(witness_proof.coinbase, transaction_proof.transaction);
+        assert!(
+            fetch_witness_proof(txid, headers[1].block_hash(), bitcoin_rpc_url, None)
+                .await
+                .is_err()
+        );
+
+        assert_eq!(get_mmr_height(&raito_endpoint).await.unwrap(), 3);
+        let roots = fetch_sparse_roots(&raito_endpoint, 2).await.unwrap();
+        assert_eq!(roots.block_height, 2);
+        let block_proof = fetch_block_proof(2, 3, &raito_endpoint, false)
+            .await
+            .unwrap();
+        assert!(verify_block_inclusion(&transaction_proof.block_header, &block_proof).unwrap());
+        assert!(fetch_block_proof(2, 4, &raito_endpoint, false)
+            .await
+            .is_err());
+        // No chain state proof is served unless provided
+        assert!(fetch_chain_state_proof(&raito_endpoint).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_bridge() {
+        let blocks = regtest_chain(vec![vec![]; 3]);
+        let headers: Vec<_> = blocks.iter().map(|block| block.header).collect();
+        let txid = blocks[2].txdata[0].compute_txid();
+        let bitcoin_rpc = MockBitcoinRpc::new(blocks.clone()).start().await.unwrap();
+        let raito_rpc = MockRaitoRpc::new(&headers)
+            .await
+            .unwrap()
+            .with_blocks(blocks)
+            .start()
+            .await
+            .unwrap();
+        let node = BitcoinDataSource::Node {
+            url: bitcoin_rpc.url().to_string(),
+            userpwd: None,
+            pruned_fallback: None,
+        };
+        let bridge = BitcoinDataSource::Bridge {
+            endpoint: ApiEndpoint::new(raito_rpc.url()),
+        };
+
+        let expected = node.fetch_transaction_proof(txid).await.unwrap();
+        let transaction_proof = bridge.fetch_transaction_proof(txid).await.unwrap();
+        assert_eq!(transaction_proof.transaction, expected.transaction);
+        assert_eq!(
+            transaction_proof.transaction_proof,
+            expected.transaction_proof
+        );
+        assert_eq!(transaction_proof.block_header, expected.block_header);
+        assert_eq!(transaction_proof.block_height, 2);
+        let raw_transaction_proof = bridge
+            .fetch_transaction_proof(expected.transaction.clone())
+            .await
+            .unwrap();
+        assert_eq!(raw_transaction_proof.transaction, expected.transaction);
+
+        assert_eq!(
+            bridge.fetch_subchain_headers(0, 3).await.unwrap(),
+            node.fetch_subchain_headers(0, 3).await.unwrap()
+        );
+        assert!(bridge
+            .fetch_subchain_headers(2, 2)
+            .await
+            .unwrap()
+            .is_empty());
+        // Headers past the MMR are not indexed yet
+        assert!(bridge.fetch_subchain_headers(2, 4).await.is_err());
+
+        assert_eq!(bridge.fetch_block_header(0).await.unwrap(), headers[0]);
+        assert!(bridge.fetch_block_header(4).await.is_err());
+        check_block_header(&expected.block_header, 2, &bridge)
+            .await
+            .unwrap();
+        let err = check_block_header(&expected.block_header, 1, &bridge)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Sources disagree"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_dev_proof() {
+        let blocks = regtest_chain(vec![vec![]; 3]);
+        let headers: Vec<_> = blocks.iter().map(|block| block.header).collect();
+        let txid = blocks[2].txdata[0].compute_txid();
+        let raito_rpc = MockRaitoRpc::new(&headers)
+            .await
+            .unwrap()
+            .with_blocks(blocks)
+            .start()
+            .await
+            .unwrap();
+        let defaults = VerifierConfig::default();
+
+        let proof = fetch_dev_compressed_proof(
+            txid,
+            &ApiEndpoint::new(raito_rpc.url()),
+            None,
+            &defaults.bootloader_hash,
+            &defaults.task_program_hash,
+        )
+        .await
+        .unwrap();
+        assert!(proof.chain_state_proof.is_dev());
+        assert_eq!(proof.chain_state.block_height, 3);
+        assert_eq!(proof.chain_state.best_block_hash, headers[3].block_hash());
+        assert_eq!(proof.block_height, 2);
+
+        // Rejected unless development stubs are accepted
+        let err = verify_proof(proof, &defaults).await.unwrap_err();
+        assert!(matches!(err, VerifyError::DevProof));
+
+        let proof = fetch_dev_compressed_proof(
+            txid,
+            &ApiEndpoint::new(raito_rpc.url()),
+            Some(2),
+            &defaults.bootloader_hash,
+            &defaults.task_program_hash,
+        )
+        .await
+        .unwrap();
+        let config = VerifierConfig {
+            allow_dev_proofs: true,
+            skip_work: true,
+            ..Default::default()
+        };
+        let report = verify_proof(proof, &config).await.unwrap();
+        assert_eq!(report.channel, DEV_CHANNEL);
+        assert_eq!(report.chain_height, 2);
+        let stark = report.checks.iter().find(|check| check.name == "stark");
+        assert_eq!(stark.unwrap().status, CheckStatus::Skipped);
+        assert!(!report.stark_verified());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_pruned_node() {
+        let blocks = regtest_chain(vec![vec![]; 3]);
+        let headers: Vec<_> = blocks.iter().map(|block| block.header).collect();
+        let pruned_txid = blocks[1].txdata[0].compute_txid();
+        let txid = blocks[3].txdata[0].compute_txid();
+        let bitcoin_rpc = MockBitcoinRpc::new(blocks.clone())
+            .with_prune_height(2)
+            .start()
+            .await
+            .unwrap();
+        let raito_rpc = MockRaitoRpc::new(&headers)
+            .await
+            .unwrap()
+            .with_blocks(blocks)
+            .start()
+            .await
+            .unwrap();
+        let node = |pruned_fallback| BitcoinDataSource::Node {
+            url: bitcoin_rpc.url().to_string(),
+            userpwd: None,
+            pruned_fallback,
+        };
+
+        let Err(err) = node(None).fetch_transaction_proof(pruned_txid).await else {
+            panic!("pruned transaction served without a fallback");
+        };
+        let err = err.to_string();
+        assert!(err.contains("pruned the blocks below height 2"), "{}", err);
+
+        let node = node(Some(Box::new(BitcoinDataSource::Bridge {
+            endpoint: ApiEndpoint::new(raito_rpc.url()),
+        })));
+        let transaction_proof = node.fetch_transaction_proof(pruned_txid).await.unwrap();
+        assert_eq!(transaction_proof.block_height, 1);
+        assert_eq!(transaction_proof.block_header, headers[1]);
+        assert_eq!(transaction_proof.transaction.compute_txid(), pruned_txid);
+        // Blocks above the prune height are served by the node
+        assert_eq!(
+            node.fetch_transaction_proof(txid)
+                .await
+                .unwrap()
+                .block_height,
+            3
+        );
+        assert_eq!(
+            node.fetch_subchain_headers(0, 3).await.unwrap(),
+            headers[1..]
+        );
+    }
+
+    /// Send a GET request with the policy, and return whether it succeeded along with the
+    /// number of attempts
+    async fn get_with_attempts(policy: &RetryPolicy, url: &str) -> (bool, u32) {
+        let attempts = AtomicU32::new(0);
+        let res = policy
+            .send(|client| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async move { client.get(url).send().await?.error_for_status() }
+            })
+            .await;
+        (res.is_ok(), attempts.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let headers: Vec<_> = regtest_chain(vec![vec![]; 2])
+            .iter()
+            .map(|block| block.header)
+            .collect();
+        let raito_rpc = MockRaitoRpc::new(&headers)
+            .await
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+        let policy = RetryPolicy {
+            timeout: Duration::from_secs(5),
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+        let url = |path: &str| format!("{}{}", raito_rpc.url(), path);
+
+        assert_eq!(get_with_attempts(&policy, &url("/head")).await, (true, 1));
+        // Client error: not retried
+        assert_eq!(
+            get_with_attempts(&policy, &url("/chainstate-proof/recent_proof")).await,
+            (false, 1)
+        );
+        // Server error (block above the MMR): retried
+        assert_eq!(
+            get_with_attempts(&policy, &url("/block-inclusion-proof/10")).await,
+            (false, 3)
+        );
+        // Network error: retried
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        assert_eq!(get_with_attempts(&policy, &closed_url).await, (false, 3));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_headers_concurrently() {
+        let headers: Vec<BlockHeader> = regtest_chain(vec![vec![]; 20])
+            .into_iter()
+            .map(|block| block.header)
+            .collect();
+        let fetch_header = |height: u32| {
+            let header = headers[height as usize];
+            async move { Ok(header) }
+        };
+        // Several chunks, the last one partial
+        let fetched = fetch_headers_concurrently(1, 20, fetch_header)
+            .await
+            .unwrap();
+        assert_eq!(fetched, headers[1..]);
+        assert!(fetch_headers_concurrently(2, 1, fetch_header)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let headers = &headers;
+        let failing = |height: u32| async move {
+            match height {
+                10 => Err(anyhow::anyhow!("unavailable")),
+                _ => Ok(headers[height as usize]),
+            }
+        };
+        assert!(fetch_headers_concurrently(1, 20, failing).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resumable_download() {
+        let proof = serde_json::json!({"chainstate": {"block_height": 1}, "proof": [1, 2, 3]});
+        let content = proof.to_string().into_bytes();
+        let raito_rpc = MockRaitoRpc::new(&[])
+            .await
+            .unwrap()
+            .with_chain_state_proof(proof)
+            .start()
+            .await
+            .unwrap();
+        let url = format!("{}/chainstate-proof/recent_proof", raito_rpc.url());
+        let client = reqwest::Client::new();
+        let on_progress = std::sync::Mutex::new(|_, _| {});
+
+        let mut download = ResumableDownload::default();
+        download.receive(&client, &url, &on_progress).await.unwrap();
+        assert_eq!(download.bytes, content);
+        assert_eq!(download.total_len, Some(content.len() as u64));
+
+        // Interrupted halfway: only the rest is requested
+        download.bytes.truncate(content.len() / 2);
+        download.receive(&client, &url, &on_progress).await.unwrap();
+        assert_eq!(download.bytes, content);
+
+        // Changed content: downloaded again from the start
+        download.bytes.truncate(content.len() / 2);
+        download.etag = Some(HeaderValue::from_static("\"stale\""));
+        download.receive(&client, &url, &on_progress).await.unwrap();
+        assert_eq!(download.bytes, content);
+        assert_eq!(download.finish().unwrap(), content);
+
+        // Larger than the limit: not downloaded
+        let max_len = Some(content.len() as u64 - 1);
+        let mut download = ResumableDownload {
+            max_len,
+            ..Default::default()
+        };
+        download.receive(&client, &url, &on_progress).await.unwrap();
+        assert!(download.bytes.is_empty());
+        assert!(download
+            .finish()
+            .unwrap_err()
+            .to_string()
+            .contains("exceeds the limit"));
+        let policy = RetryPolicy::default();
+        assert!(download_resumable(&url, &policy, max_len, |_, _| {})
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_finish_download() {
+        use std::io::Write;
+
+        let content = b"{\"chainstate\": {}}".to_vec();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&content).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let download = ResumableDownload {
+            sha256: Some(sha256::Hash::hash(&compressed).to_string()),
+            bytes: compressed.clone(),
+            gzip: true,
+            ..Default::default()
+        };
+        assert_eq!(download.finish().unwrap(), content);
+
+        let download = ResumableDownload {
+            sha256: Some(sha256::Hash::hash(&content).to_string()),
+            bytes: compressed,
+            gzip: true,
+            ..Default::default()
+        };
+        assert!(download
+            .finish()
+            .unwrap_err()
+            .to_string()
+            .contains("checksum mismatch"));
+    }
+}
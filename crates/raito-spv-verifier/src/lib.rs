@@ -0,0 +1,47 @@
+//! Verification of Raito compressed SPV proofs as a library
+//!
+//! This crate exposes the proof types, the verification policies and the end-to-end
+//! verification of a proof, so that applications can verify proofs without going through the
+//! `raito-spv-client` CLI. Progress is reported with `tracing`; nothing is printed.
+//!
+//! The `parallel` feature (enabled by default) verifies STARK proofs with multiple threads and
+//! bundled proofs concurrently on the tokio runtime. Without it the crate compiles to
+//! `wasm32-unknown-unknown`, see `raito-spv-wasm`. The `blocking` feature (enabled by default)
+//! adds synchronous variants of the async functions for applications without an async
+//! runtime, and the `fetch` feature adds fetching of proofs from the Raito bridge RPC and a
+//! Bitcoin node.
+//!
+//! ```no_run
+//! use raito_spv_verifier::{load_compressed_proof_from_bzip2, verify_proof, VerifierConfig};
+//!
+//! # async fn example() -> Result<(), raito_spv_verifier::VerifyError> {
+//! let proof = load_compressed_proof_from_bzip2("tx_proof.bin.bz2".as_ref())?;
+//! let report = verify_proof(proof, &VerifierConfig::default()).await?;
+//! println!("{} has {} confirmations", report.txid, report.confirmations);
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod coinbase;
+pub mod error;
+#[cfg(feature = "fetch")]
+pub mod fetch;
+pub mod proof;
+pub mod registry;
+pub mod trusted;
+pub mod verify;
+pub mod witness;
+pub mod work;
+
+#[cfg(all(feature = "blocking", feature = "fetch"))]
+pub use blocking::fetch_compressed_proof_blocking;
+#[cfg(feature = "blocking")]
+pub use blocking::{verify_bundle_blocking, verify_proof_blocking};
+pub use error::{FailureClass, VerifyError};
+pub use proof::{CompressedSpvProof, ProofBundle};
+pub use verify::{
+    decode_proof, load_compressed_proof_from_bzip2, verify_bundle, verify_proof,
+    VerificationReport, VerifierConfig, VerifyOptions,
+};
@@ -0,0 +1,721 @@
+//! Types representing the compressed SPV proof and helpers to decode Cairo outputs
+//! and compute chain state digests used during verification.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{block::Header as BlockHeader, BlockHash, Transaction, TxOut};
+use cairo_air::utils::{get_verification_output, VerificationOutput};
+use cairo_air::CairoProof;
+use num_bigint::BigUint;
+use raito_spv_core::block_mmr::BlockInclusionProof;
+use serde::{Deserialize, Serialize};
+use starknet_ff::FieldElement;
+use stwo_prover::core::vcs::blake2_hash::Blake2sHasher;
+use stwo_prover::core::vcs::blake2_merkle::Blake2sMerkleHasher;
+use stwo_prover::core::vcs::poseidon252_merkle::Poseidon252MerkleHasher;
+
+use crate::error::VerifyError;
+
+/// A compact, self-contained proof that a Bitcoin transaction is included
+/// in a specific block and that the block is part of a valid chain state.
+#[derive(Serialize, Deserialize)]
+pub struct CompressedSpvProof {
+    /// The current state of the chain
+    pub chain_state: ChainState,
+    /// Recursive STARK proof of the chain state and block MMR root validity
+    pub chain_state_proof: ChainStateCairoProof,
+    /// The header of the block containing the transaction
+    pub block_header: BlockHeader,
+    /// Height of the block containing the transaction, must match its MMR leaf index
+    pub block_height: u32,
+    /// MMR inclusion proof for the block header
+    pub block_header_proof: BlockInclusionProof,
+    /// The transaction to be proven
+    pub transaction: Transaction,
+    /// Encoded [PartialMerkleTree] structure, contains Merkle branch for the transaction
+    pub transaction_proof: Vec<u8>,
+    /// Optional extension: headers of the blocks on top of the target block, up to the best
+    /// block of the chain state, to verify the actual subchain work instead of a lower bound
+    pub subchain_headers: Option<Vec<BlockHeader>>,
+    /// Optional extension: outputs spent by the transaction inputs, in input order, to display
+    /// input addresses and the fee. They are reported by the Bitcoin node, not proven
+    pub prevouts: Option<Vec<TxOut>>,
+    /// Optional extension: coinbase of the block and Merkle branches to its witness
+    /// commitment, to prove the witnesses of the transaction along with its ID
+    pub witness_proof: Option<WitnessProof>,
+}
+
+/// Proof that a transaction, witnesses included, is committed to by the witness commitment in
+/// the coinbase of its block (BIP141)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessProof {
+    /// The coinbase of the block, holding the witness commitment and its reserved value
+    pub coinbase: Transaction,
+    /// Encoded [PartialMerkleTree] structure, contains Merkle branch for the coinbase in the
+    /// transaction tree
+    pub coinbase_proof: Vec<u8>,
+    /// Encoded [PartialMerkleTree] structure, contains Merkle branch for the wtxid of the
+    /// transaction in the witness tree, whose first leaf is zero in place of the coinbase
+    pub witness_tree_proof: Vec<u8>,
+}
+
+/// Compressed SPV proofs of several transactions sharing a single chain state proof
+#[derive(Serialize, Deserialize)]
+pub struct ProofBundle {
+    /// The current state of the chain
+    pub chain_state: ChainState,
+    /// Recursive STARK proof of the chain state and block MMR root validity
+    pub chain_state_proof: ChainStateCairoProof,
+    /// Inclusion data of each transaction
+    pub transactions: Vec<BundledTransaction>,
+}
+
+/// Recursive STARK proof of the chain state, tagged with the Merkle channel it was generated
+/// with so that the verifier can dispatch on it
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainStateCairoProof {
+    /// Proof committed with Blake2s Merkle trees
+    Blake2s(CairoProof<Blake2sMerkleHasher>),
+    /// Proof committed with Poseidon252 Merkle trees
+    Poseidon252(CairoProof<Poseidon252MerkleHasher>),
+    /// Development stub without a STARK proof, only accepted by verifiers in development mode
+    Dev(DevChainStateProof),
+}
+
+/// Channel name of the development stubs of chain state proofs
+pub const DEV_CHANNEL: &str = "dev";
+
+impl ChainStateCairoProof {
+    /// Name of the Merkle channel the proof was generated with
+    pub fn channel(&self) -> &'static str {
+        match self {
+            Self::Blake2s(_) => "blake2s",
+            Self::Poseidon252(_) => "poseidon252",
+            Self::Dev(_) => DEV_CHANNEL,
+        }
+    }
+
+    /// Whether the proof is a development stub, proving nothing
+    pub fn is_dev(&self) -> bool {
+        matches!(self, Self::Dev(_))
+    }
+
+    /// Bootloader program hash and public output claimed by the proof
+    pub fn verification_output(&self) -> VerificationOutput {
+        match self {
+            Self::Blake2s(proof) => get_verification_output(&proof.claim.public_data.public_memory),
+            Self::Poseidon252(proof) => {
+                get_verification_output(&proof.claim.public_data.public_memory)
+            }
+            Self::Dev(proof) => VerificationOutput {
+                program_hash: proof.bootloader_hash,
+                output: proof.output.clone(),
+            },
+        }
+    }
+}
+
+/// Stand-in for the recursive chain state proof, generated locally to test verification
+/// pipelines before a prover is available: it claims the bootloader output committing to a
+/// chain state and a block MMR root, without proving it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DevChainStateProof {
+    /// Claimed bootloader program hash
+    pub bootloader_hash: FieldElement,
+    /// Claimed bootloader output, encoded like the output of the real programs
+    pub output: Vec<FieldElement>,
+}
+
+impl DevChainStateProof {
+    /// Claim the output of the bootloader and task programs with the given hashes for
+    /// `chain_state` and `block_mmr_root`
+    pub fn new(
+        chain_state: &ChainState,
+        block_mmr_root: &str,
+        bootloader_hash: &str,
+        task_program_hash: &str,
+    ) -> Result<Self, VerifyError> {
+        let bootloader_hash = encode_truncated_hash(bootloader_hash)?;
+        let task_program_hash = encode_truncated_hash(task_program_hash)?;
+        let mut task_output = vec![task_program_hash];
+        task_output.extend(encode_hash(&chain_state.blake2s_digest()?)?);
+        task_output.extend(encode_hash(block_mmr_root)?);
+        task_output.extend([bootloader_hash, task_program_hash]);
+
+        // Number of tasks, then the task output prefixed with its size (the size included)
+        let mut output = vec![FieldElement::ONE, (task_output.len() + 1).into()];
+        output.extend(task_output);
+        Ok(Self {
+            bootloader_hash,
+            output,
+        })
+    }
+}
+
+impl From<DevChainStateProof> for ChainStateCairoProof {
+    fn from(proof: DevChainStateProof) -> Self {
+        Self::Dev(proof)
+    }
+}
+
+impl From<CairoProof<Blake2sMerkleHasher>> for ChainStateCairoProof {
+    fn from(proof: CairoProof<Blake2sMerkleHasher>) -> Self {
+        Self::Blake2s(proof)
+    }
+}
+
+/// Inclusion data of a single transaction in a [ProofBundle]
+#[derive(Serialize, Deserialize)]
+pub struct BundledTransaction {
+    /// The header of the block containing the transaction
+    pub block_header: BlockHeader,
+    /// Height of the block containing the transaction, must match its MMR leaf index
+    pub block_height: u32,
+    /// MMR inclusion proof for the block header
+    pub block_header_proof: BlockInclusionProof,
+    /// The transaction to be proven
+    pub transaction: Transaction,
+    /// Encoded [PartialMerkleTree] structure, contains Merkle branch for the transaction
+    pub transaction_proof: Vec<u8>,
+}
+
+/// Magic bytes at the start of the binary encoding of proofs and bundles, followed by the
+/// format version (little-endian u16)
+pub const PROOF_MAGIC: &[u8; 4] = b"RSPV";
+
+/// Version of the binary encoding of proofs and bundles written by this crate
+pub const PROOF_FORMAT_VERSION: u16 = 1;
+
+/// Compressed proof as encoded before the binary format was versioned: Blake2s chain state
+/// proof only, block height implied by the MMR leaf index, and no extensions
+#[derive(Serialize, Deserialize)]
+struct LegacyCompressedSpvProof<P = CairoProof<Blake2sMerkleHasher>> {
+    chain_state: ChainState,
+    chain_state_proof: P,
+    block_header: BlockHeader,
+    block_header_proof: BlockInclusionProof,
+    transaction: Transaction,
+    transaction_proof: Vec<u8>,
+}
+
+impl<P: Into<ChainStateCairoProof>> From<LegacyCompressedSpvProof<P>> for CompressedSpvProof {
+    fn from(proof: LegacyCompressedSpvProof<P>) -> Self {
+        Self {
+            chain_state: proof.chain_state,
+            chain_state_proof: proof.chain_state_proof.into(),
+            block_header: proof.block_header,
+            block_height: proof.block_header_proof.leaf_index as u32,
+            block_header_proof: proof.block_header_proof,
+            transaction: proof.transaction,
+            transaction_proof: proof.transaction_proof,
+            subchain_headers: None,
+            prevouts: None,
+            witness_proof: None,
+        }
+    }
+}
+
+impl CompressedSpvProof {
+    /// Encode the proof with bincode, prefixed with [PROOF_MAGIC] and [PROOF_FORMAT_VERSION]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, VerifyError> {
+        encode_versioned(self)
+    }
+
+    /// Decode a proof encoded with [CompressedSpvProof::to_bytes], or written before the binary
+    /// format was versioned
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VerifyError> {
+        decode_proof_or_legacy::<CairoProof<Blake2sMerkleHasher>>(bytes)
+    }
+}
+
+impl ProofBundle {
+    /// Encode the bundle with bincode, prefixed with [PROOF_MAGIC] and [PROOF_FORMAT_VERSION]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, VerifyError> {
+        encode_versioned(self)
+    }
+
+    /// Decode a bundle encoded with [ProofBundle::to_bytes]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VerifyError> {
+        match split_version(bytes) {
+            Some((1, payload)) => bincode::deserialize(payload).map_err(VerifyError::decode),
+            Some((version, _)) => Err(unsupported_version(version)),
+            None => Err(VerifyError::decode("missing proof bundle format header")),
+        }
+    }
+}
+
+/// Decode a versioned proof, or a proof in the legacy layout with chain state proofs of type `P`
+fn decode_proof_or_legacy<P>(bytes: &[u8]) -> Result<CompressedSpvProof, VerifyError>
+where
+    P: Into<ChainStateCairoProof> + serde::de::DeserializeOwned,
+{
+    match split_version(bytes) {
+        Some((1, payload)) => bincode::deserialize(payload).map_err(VerifyError::decode),
+        Some((version, _)) => Err(unsupported_version(version)),
+        None => bincode::deserialize::<LegacyCompressedSpvProof<P>>(bytes)
+            .map(Into::into)
+            .map_err(VerifyError::decode),
+    }
+}
+
+fn encode_versioned<T: Serialize>(value: &T) -> Result<Vec<u8>, VerifyError> {
+    let mut bytes = PROOF_MAGIC.to_vec();
+    bytes.extend(PROOF_FORMAT_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, value).map_err(VerifyError::decode)?;
+    Ok(bytes)
+}
+
+/// Format version and payload of versioned bytes, `None` if they don't start with the magic
+fn split_version(bytes: &[u8]) -> Option<(u16, &[u8])> {
+    let rest = bytes.strip_prefix(PROOF_MAGIC)?;
+    let (version, payload) = rest.split_first_chunk::<2>()?;
+    Some((u16::from_le_bytes(*version), payload))
+}
+
+fn unsupported_version(version: u16) -> VerifyError {
+    VerifyError::decode(format!(
+        "unsupported proof format version {version}, this verifier reads up to version {PROOF_FORMAT_VERSION}"
+    ))
+}
+
+/// Snapshot of the consensus chain state used to validate block inclusion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainState {
+    /// The height of the best block in the chain
+    pub block_height: u32,
+    /// The total accumulated work of the chain as a decimal string
+    pub total_work: String,
+    /// The hash of the best block in the chain
+    pub best_block_hash: BlockHash,
+    /// The current target difficulty as a compact decimal string
+    pub current_target: String,
+    /// The start time (UNIX seconds) of the current difficulty epoch
+    pub epoch_start_time: u32,
+    /// The timestamps (UNIX seconds) of the previous 11 blocks
+    pub prev_timestamps: Vec<u32>,
+}
+
+/// Output of the bootloader program
+#[derive(Debug, Clone)]
+pub struct BootloaderOutput {
+    /// Number of tasks (must be always 1)
+    pub n_tasks: u32,
+    /// Size of the task output in felts (including the size field)
+    pub task_output_size: u32,
+    /// Hash of the payload program.
+    pub task_program_hash: String,
+    /// Output of the payload program.
+    pub task_result: TaskResult,
+}
+
+/// Output of the payload program
+#[derive(Debug, Clone)]
+pub struct TaskResult {
+    /// Hash of the chain state after the blocks have been applied.
+    pub chain_state_hash: String,
+    /// Hash of the roots of the Merkle Mountain Range of the block hashes.
+    pub block_mmr_hash: String,
+    /// Hash of the previous bootloader program that was recursively verified.
+    /// We do not hardcode the bootloader hash in the assumevalid program,
+    /// letting the final verifier to check that it is as expected.
+    pub bootloader_hash: String,
+    /// Hash of the assumevalid program that was recursively verified.
+    /// We cannot know the hash of the program from within the program, so we have to carry it over.
+    /// This also allows composing multiple programs (e.g. if we'd need to upgrade at a certain
+    /// block height).
+    pub program_hash: String,
+}
+
+impl BootloaderOutput {
+    /// Extract the bootloader program hash and the decoded bootloader output from the public
+    /// memory of a recursive chain state proof.
+    ///
+    /// This does not verify the proof, it only decodes the claimed public data.
+    pub fn from_proof(
+        chain_state_proof: &ChainStateCairoProof,
+    ) -> Result<(String, Self), VerifyError> {
+        let VerificationOutput {
+            program_hash,
+            output,
+        } = chain_state_proof.verification_output();
+        let bootloader_hash = format!("0x{}", hex::encode(program_hash.to_bytes_be()));
+        Ok((bootloader_hash, Self::decode(output)?))
+    }
+
+    /// Decode `BootloaderOutput` from the Cairo public output felts emitted by the bootloader.
+    pub fn decode(output: Vec<FieldElement>) -> Result<Self, VerifyError> {
+        let mut output = output.into_iter();
+        let n_tasks = next_felt(&mut output)?.try_into().map_err(|_| {
+            VerifyError::InvalidBootloaderOutput("expected number of tasks to be a u32".into())
+        })?;
+        let task_output_size = next_felt(&mut output)?.try_into().map_err(|_| {
+            VerifyError::InvalidBootloaderOutput("expected task output size to be a u32".into())
+        })?;
+        let task_program_hash = decode_truncated_hash(&mut output)?;
+        let task_result = TaskResult::decode(output.collect())?;
+        Ok(Self {
+            n_tasks,
+            task_output_size,
+            task_program_hash,
+            task_result,
+        })
+    }
+}
+
+impl TaskResult {
+    /// Decode `TaskResult` from the remainder of the Cairo public output felts.
+    pub fn decode(output: Vec<FieldElement>) -> Result<Self, VerifyError> {
+        let mut output = output.into_iter();
+        let chain_state_hash = decode_hash(&mut output)?;
+        let block_mmr_hash = decode_hash(&mut output)?;
+        let prev_bootloader_hash = decode_truncated_hash(&mut output)?;
+        let prev_program_hash = decode_truncated_hash(&mut output)?;
+        if output.len() > 0 {
+            return Err(VerifyError::InvalidBootloaderOutput(format!(
+                "{} unexpected trailing felt(s)",
+                output.len()
+            )));
+        }
+        Ok(Self {
+            chain_state_hash,
+            block_mmr_hash,
+            bootloader_hash: prev_bootloader_hash,
+            program_hash: prev_program_hash,
+        })
+    }
+}
+
+/// Next felt of the public output, which is too short if there is none
+fn next_felt(output: &mut impl Iterator<Item = FieldElement>) -> Result<FieldElement, VerifyError> {
+    output
+        .next()
+        .ok_or_else(|| VerifyError::InvalidBootloaderOutput("output is too short".into()))
+}
+
+fn decode_hash(output: &mut impl Iterator<Item = FieldElement>) -> Result<String, VerifyError> {
+    // In Cairo serde u256 low goes first, high goes second
+    let mut next_u128 = || {
+        next_felt(output)?.try_into().map_err(|_| {
+            VerifyError::InvalidBootloaderOutput("expected u256 limb to be a u128".into())
+        })
+    };
+    let lo: u128 = next_u128()?;
+    let hi: u128 = next_u128()?;
+    let bytes = [hi.to_be_bytes(), lo.to_be_bytes()].concat();
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+fn decode_truncated_hash(
+    output: &mut impl Iterator<Item = FieldElement>,
+) -> Result<String, VerifyError> {
+    let bytes = next_felt(output)?.to_bytes_be();
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Encode a hash as Cairo serde u256, the inverse of [decode_hash]
+fn encode_hash(hash: &str) -> Result<[FieldElement; 2], VerifyError> {
+    let bytes = hex::decode(hash.strip_prefix("0x").unwrap_or(hash))
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| VerifyError::InvalidBootloaderOutput(format!("invalid hash {}", hash)))?;
+    let hi = u128::from_be_bytes(bytes[..16].try_into().unwrap());
+    let lo = u128::from_be_bytes(bytes[16..].try_into().unwrap());
+    Ok([lo.into(), hi.into()])
+}
+
+/// Encode a hash as a single felt, the inverse of [decode_truncated_hash]
+fn encode_truncated_hash(hash: &str) -> Result<FieldElement, VerifyError> {
+    FieldElement::from_hex_be(hash)
+        .map_err(|_| VerifyError::InvalidBootloaderOutput(format!("invalid hash {}", hash)))
+}
+
+impl ChainState {
+    /// Timestamp of the best block, i.e. the most recent one of the previous timestamps
+    pub fn best_block_time(&self) -> u32 {
+        self.prev_timestamps
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or(self.epoch_start_time)
+    }
+
+    /// Total accumulated work of the chain
+    pub fn total_work(&self) -> Result<BigUint, VerifyError> {
+        BigUint::from_str(&self.total_work).map_err(|_| {
+            VerifyError::InvalidChainState(format!("invalid total work {}", self.total_work))
+        })
+    }
+
+    /// Current target of the chain
+    pub fn current_target(&self) -> Result<BigUint, VerifyError> {
+        BigUint::from_str(&self.current_target).map_err(|_| {
+            VerifyError::InvalidChainState(format!(
+                "invalid current target {}",
+                self.current_target
+            ))
+        })
+    }
+
+    /// Compute the Blake2s digest of the canonical serialization of the chain state.
+    ///
+    /// The serialization mirrors the Cairo-side little-endian encoding.
+    pub fn blake2s_digest(&self) -> Result<String, VerifyError> {
+        let best_block_hash_words = self
+            .best_block_hash
+            .as_byte_array()
+            .chunks_exact(4)
+            .map(|chunk| u32::from_be_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        // Construct the payload for the hash function, all integers are little-endian
+        let mut words = Vec::new();
+        words.push(self.block_height);
+        words.extend_from_slice(&big_uint_to_u256_words(&self.total_work)?);
+        words.extend_from_slice(&best_block_hash_words);
+        words.extend_from_slice(&big_uint_to_u256_words(&self.current_target)?);
+        words.push(self.epoch_start_time);
+        words.extend_from_slice(&self.prev_timestamps);
+
+        // Serialize to bytes, using little-endian encoding
+        let bytes = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        // Compute the hash
+        let mut hasher = Blake2sHasher::new();
+        hasher.update(&bytes);
+        let mut digest_bytes = hasher.finalize().0.to_vec();
+
+        // Reverse bytes in each 4-byte chunk, to comply with Cairo's little-endian encoding
+        digest_bytes.chunks_exact_mut(4).for_each(|chunk| {
+            chunk.reverse();
+        });
+        let res = format!("0x{}", hex::encode(digest_bytes));
+        Ok(res)
+    }
+}
+
+fn big_uint_to_u256_words(value: &str) -> Result<Vec<u32>, VerifyError> {
+    let number = BigUint::from_str(value)
+        .map_err(|_| VerifyError::InvalidChainState(format!("invalid number {}", value)))?;
+    let mut digits = number.to_u32_digits();
+    digits.extend(vec![0; 8 - digits.len()]);
+    digits.reverse();
+    Ok(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn test_chain_state() -> ChainState {
+        ChainState {
+            block_height: 0,
+            total_work: "4295032833".to_string(),
+            best_block_hash: BlockHash::from_str(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            )
+            .unwrap(),
+            current_target: "26959535291011309493156476344723991336010898738574164086137773096960"
+                .to_string(),
+            epoch_start_time: 1231006505,
+            prev_timestamps: vec![1231006505],
+        }
+    }
+
+    #[test]
+    fn test_chain_state_hash() {
+        let chain_state = test_chain_state();
+        let res = chain_state.blake2s_digest().unwrap();
+        let expected = "0x6002eaa4410bd0b15e778656f84fc895fd091827e27ce697ba4231076c70c43b";
+        assert_eq!(res, expected);
+    }
+
+    fn test_proof(chain_state: ChainState, leaf_index: usize) -> CompressedSpvProof {
+        let genesis = bitcoin::constants::genesis_block(bitcoin::Network::Bitcoin);
+        let chain_state_proof = DevChainStateProof::new(
+            &chain_state,
+            "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            "0x0001837d8b77b6368e0129ce3f65b5d63863cfab93c47865ee5cbe62922ab8f3",
+            "0x00f0876bb47895e8c4a6e7043829d7886e3b135e3ef30544fb688ef4e25663ca",
+        )
+        .unwrap();
+        CompressedSpvProof {
+            chain_state,
+            chain_state_proof: chain_state_proof.into(),
+            block_header: genesis.header,
+            block_height: leaf_index as u32,
+            block_header_proof: BlockInclusionProof {
+                peaks_hashes: vec![],
+                siblings_hashes: vec![],
+                leaf_index,
+                leaf_count: leaf_index + 1,
+            },
+            transaction: genesis.txdata[0].clone(),
+            transaction_proof: vec![1, 2, 3],
+            subchain_headers: None,
+            prevouts: None,
+            witness_proof: None,
+        }
+    }
+
+    #[test]
+    fn test_proof_format_round_trip() {
+        let mut proof = test_proof(test_chain_state(), 0);
+        proof.subchain_headers = Some(vec![proof.block_header]);
+        let bytes = proof.to_bytes().unwrap();
+        assert!(bytes.starts_with(PROOF_MAGIC));
+        assert_eq!(bytes[4..6], PROOF_FORMAT_VERSION.to_le_bytes());
+        let decoded = CompressedSpvProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        assert_eq!(decoded.subchain_headers, proof.subchain_headers);
+
+        let proof = test_proof(test_chain_state(), 0);
+        let bundle = ProofBundle {
+            chain_state: proof.chain_state,
+            chain_state_proof: proof.chain_state_proof,
+            transactions: vec![BundledTransaction {
+                block_header: proof.block_header,
+                block_height: proof.block_height,
+                block_header_proof: proof.block_header_proof,
+                transaction: proof.transaction,
+                transaction_proof: proof.transaction_proof,
+            }],
+        };
+        let bytes = bundle.to_bytes().unwrap();
+        let decoded = ProofBundle::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        // Bundles were versioned from the start
+        assert!(ProofBundle::from_bytes(&bytes[6..]).is_err());
+    }
+
+    #[test]
+    fn test_proof_format_unknown_version() {
+        let mut bytes = test_proof(test_chain_state(), 0).to_bytes().unwrap();
+        bytes[4..6].copy_from_slice(&(PROOF_FORMAT_VERSION + 1).to_le_bytes());
+        let err = CompressedSpvProof::from_bytes(&bytes).err().unwrap();
+        assert!(err.to_string().contains("unsupported proof format version"));
+    }
+
+    #[test]
+    fn test_decode_legacy_proof() {
+        // Fixture in the layout written before the format was versioned, with a development
+        // stub in place of the Blake2s STARK proof which cannot be built here
+        let proof = test_proof(test_chain_state(), 5);
+        let ChainStateCairoProof::Dev(chain_state_proof) = proof.chain_state_proof else {
+            unreachable!()
+        };
+        let fixture = bincode::serialize(&LegacyCompressedSpvProof {
+            chain_state: proof.chain_state,
+            chain_state_proof: chain_state_proof.clone(),
+            block_header: proof.block_header,
+            block_header_proof: proof.block_header_proof,
+            transaction: proof.transaction,
+            transaction_proof: proof.transaction_proof,
+        })
+        .unwrap();
+
+        let decoded = decode_proof_or_legacy::<DevChainStateProof>(&fixture).unwrap();
+        assert_eq!(decoded.block_height, 5);
+        assert_eq!(decoded.block_header_proof.leaf_count, 6);
+        assert!(matches!(
+            decoded.chain_state_proof,
+            ChainStateCairoProof::Dev(ref p) if *p == chain_state_proof
+        ));
+        assert_eq!(decoded.transaction_proof, vec![1, 2, 3]);
+        assert!(decoded.subchain_headers.is_none() && decoded.witness_proof.is_none());
+        // Legacy proofs are re-encoded in the current format
+        let bytes = decoded.to_bytes().unwrap();
+        assert_eq!(
+            CompressedSpvProof::from_bytes(&bytes).unwrap().block_height,
+            5
+        );
+    }
+
+    #[test]
+    fn test_decode_hash() {
+        let felts = vec![
+            FieldElement::from_dec_str("336341903543133962954146260045611975739").unwrap(),
+            FieldElement::from_dec_str("127621031286465709630765493168293005461").unwrap(),
+        ];
+        let res = decode_hash(&mut felts.clone().into_iter()).unwrap();
+        let expected = "0x6002eaa4410bd0b15e778656f84fc895fd091827e27ce697ba4231076c70c43b";
+        assert_eq!(res, expected);
+        assert_eq!(encode_hash(expected).unwrap().to_vec(), felts);
+    }
+
+    #[test]
+    fn test_decode_malformed_output() {
+        let output: Vec<FieldElement> = (1..=10_u64).map(FieldElement::from).collect();
+        assert!(BootloaderOutput::decode(output[..9].to_vec()).is_ok());
+        for len in 0..9 {
+            assert!(matches!(
+                BootloaderOutput::decode(output[..len].to_vec()),
+                Err(VerifyError::InvalidBootloaderOutput(_))
+            ));
+        }
+        assert!(matches!(
+            BootloaderOutput::decode(output),
+            Err(VerifyError::InvalidBootloaderOutput(_))
+        ));
+
+        // u256 limbs don't fit in a felt above 2^128
+        let felts = vec![FieldElement::MAX, FieldElement::ONE];
+        assert!(matches!(
+            decode_hash(&mut felts.into_iter()),
+            Err(VerifyError::InvalidBootloaderOutput(_))
+        ));
+    }
+
+    #[test]
+    fn test_dev_chain_state_proof() {
+        let chain_state = ChainState {
+            block_height: 0,
+            total_work: "4295032833".to_string(),
+            best_block_hash: BlockHash::from_str(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            )
+            .unwrap(),
+            current_target: "26959535291011309493156476344723991336010898738574164086137773096960"
+                .to_string(),
+            epoch_start_time: 1231006505,
+            prev_timestamps: vec![1231006505],
+        };
+        let block_mmr_root = "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let bootloader_hash = "0x0001837d8b77b6368e0129ce3f65b5d63863cfab93c47865ee5cbe62922ab8f3";
+        let task_program_hash =
+            "0x00f0876bb47895e8c4a6e7043829d7886e3b135e3ef30544fb688ef4e25663ca";
+        let proof: ChainStateCairoProof = DevChainStateProof::new(
+            &chain_state,
+            block_mmr_root,
+            bootloader_hash,
+            task_program_hash,
+        )
+        .unwrap()
+        .into();
+        assert_eq!(proof.channel(), DEV_CHANNEL);
+
+        let (decoded_bootloader_hash, output) = BootloaderOutput::from_proof(&proof).unwrap();
+        assert_eq!(decoded_bootloader_hash, bootloader_hash);
+        assert_eq!(output.n_tasks, 1);
+        assert_eq!(output.task_output_size, 8);
+        assert_eq!(output.task_program_hash, task_program_hash);
+        assert_eq!(
+            output.task_result.chain_state_hash,
+            chain_state.blake2s_digest().unwrap()
+        );
+        assert_eq!(output.task_result.block_mmr_hash, block_mmr_root);
+        assert_eq!(output.task_result.bootloader_hash, bootloader_hash);
+        assert_eq!(output.task_result.program_hash, task_program_hash);
+
+        assert!(
+            DevChainStateProof::new(&chain_state, "0x01", bootloader_hash, task_program_hash)
+                .is_err()
+        );
+    }
+}
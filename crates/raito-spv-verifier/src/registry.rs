@@ -0,0 +1,203 @@
+//! Registry of officially released Raito programs, mapping each release to the bootloader and
+//! task program hashes it was proven with and the chain height it is accepted from.
+//!
+//! The registry is embedded into the verifier and can be overridden with a file on disk.
+
+use std::path::Path;
+
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Registry shipped with the verifier
+const EMBEDDED_REGISTRY: &str = include_str!("../programs.toml");
+
+/// Set of program releases accepted by the verifier
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramRegistry {
+    /// Released programs
+    #[serde(rename = "release")]
+    pub releases: Vec<ProgramRelease>,
+}
+
+/// A released Raito program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramRelease {
+    /// Name of the release
+    pub name: String,
+    /// Bootloader program hash (hex string)
+    pub bootloader_hash: String,
+    /// Assumevalid program hash verified by the bootloader (hex string)
+    pub task_program_hash: String,
+    /// Size of the assumevalid program output in felts
+    pub task_output_size: u32,
+    /// Minimum chain height of chain states proven with this release
+    pub activation_height: u32,
+    /// Network the release is built for, any network if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<Network>,
+}
+
+impl ProgramRegistry {
+    /// Registry embedded into the verifier
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED_REGISTRY).expect("Embedded program registry is valid")
+    }
+
+    /// Load a registry from a TOML file
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        info!("Loading program registry from {}", path.display());
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid program registry {}: {}", path.display(), e))
+    }
+
+    /// Parse a registry from its TOML representation
+    pub fn parse(contents: &str) -> Result<Self, anyhow::Error> {
+        let registry: Self = toml::from_str(contents)?;
+        if registry.releases.is_empty() {
+            anyhow::bail!("Program registry has no releases");
+        }
+        Ok(registry)
+    }
+
+    /// Releases built for the given network
+    pub fn for_network(&self, network: Network) -> Result<Self, anyhow::Error> {
+        let releases: Vec<ProgramRelease> = self
+            .releases
+            .iter()
+            .filter(|release| release.network.is_none_or(|n| n == network))
+            .cloned()
+            .collect();
+        if releases.is_empty() {
+            anyhow::bail!("Program registry has no releases for network {}", network);
+        }
+        Ok(Self { releases })
+    }
+
+    /// Find the release a chain state proof was generated with
+    ///
+    /// - `bootloader_hash`: Bootloader program hash claimed by the proof
+    /// - `task_program_hash`: Task program hash claimed by the proof
+    /// - `task_output_size`: Size of the task output claimed by the proof
+    /// - `chain_height`: Height of the proven chain state
+    pub fn find(
+        &self,
+        bootloader_hash: &str,
+        task_program_hash: &str,
+        task_output_size: u32,
+        chain_height: u32,
+    ) -> Result<&ProgramRelease, anyhow::Error> {
+        let matching: Vec<&ProgramRelease> = self
+            .releases
+            .iter()
+            .filter(|release| {
+                same_hash(&release.bootloader_hash, bootloader_hash)
+                    && same_hash(&release.task_program_hash, task_program_hash)
+                    && release.task_output_size == task_output_size
+            })
+            .collect();
+
+        if matching.is_empty() {
+            anyhow::bail!(
+                "Program doesn't match any released program: bootloader {}, task program {}, task output size {}",
+                bootloader_hash,
+                task_program_hash,
+                task_output_size
+            );
+        }
+
+        matching
+            .iter()
+            .copied()
+            .filter(|release| release.activation_height <= chain_height)
+            .max_by_key(|release| release.activation_height)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Program release {} is not active at chain height {} (activation height {})",
+                    matching[0].name,
+                    chain_height,
+                    matching[0].activation_height
+                )
+            })
+    }
+}
+
+/// Compare two hex hashes ignoring case, `0x` prefix, and leading zeros
+pub fn same_hash(a: &str, b: &str) -> bool {
+    fn normalize(hash: &str) -> String {
+        let digits = hash.strip_prefix("0x").unwrap_or(hash);
+        digits.trim_start_matches('0').to_ascii_lowercase()
+    }
+    normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTRY: &str = r#"
+        [[release]]
+        name = "v1"
+        bootloader_hash = "0x01"
+        task_program_hash = "0x0a"
+        task_output_size = 8
+        activation_height = 0
+
+        [[release]]
+        name = "v2"
+        bootloader_hash = "0x02"
+        task_program_hash = "0x0b"
+        task_output_size = 8
+        activation_height = 1000
+        network = "bitcoin"
+    "#;
+
+    #[test]
+    fn test_embedded_registry() {
+        let registry = ProgramRegistry::embedded();
+        assert!(!registry.releases.is_empty());
+    }
+
+    #[test]
+    fn test_find_release() {
+        let registry = ProgramRegistry::parse(REGISTRY).unwrap();
+
+        let release = registry.find("0x0000000000000001", "0x0A", 8, 10).unwrap();
+        assert_eq!(release.name, "v1");
+
+        let release = registry.find("0x02", "0x0b", 8, 1000).unwrap();
+        assert_eq!(release.name, "v2");
+
+        // Not yet activated
+        assert!(registry.find("0x02", "0x0b", 8, 999).is_err());
+        // Unknown program
+        assert!(registry.find("0x01", "0x0b", 8, 1000).is_err());
+        // Bootloader of another release
+        assert!(registry.find("0x02", "0x0a", 8, 1000).is_err());
+        // Unexpected output size
+        assert!(registry.find("0x01", "0x0a", 9, 1000).is_err());
+    }
+
+    #[test]
+    fn test_registry_for_network() {
+        let registry = ProgramRegistry::parse(REGISTRY).unwrap();
+        assert_eq!(
+            registry
+                .for_network(Network::Bitcoin)
+                .unwrap()
+                .releases
+                .len(),
+            2
+        );
+
+        let registry = registry.for_network(Network::Signet).unwrap();
+        assert_eq!(registry.releases.len(), 1);
+        assert_eq!(registry.releases[0].name, "v1");
+
+        let embedded = ProgramRegistry::embedded();
+        assert!(embedded.for_network(Network::Testnet4).is_err());
+    }
+}
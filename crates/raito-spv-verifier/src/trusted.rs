@@ -0,0 +1,76 @@
+//! Verification of the block MMR against a root pinned by the user, so that trusting a proof
+//! doesn't rely solely on the bundled chain state proof.
+
+use raito_spv_core::block_mmr::{
+    root_hash_from_peaks, verify_consistency, BlockConsistencyProof, BlockInclusionProof,
+};
+use tracing::info;
+
+use crate::error::VerifyError;
+
+/// Trusted block MMR root with the consistency proof linking it to the proven MMR
+#[derive(Debug, Clone)]
+pub struct TrustedRoot {
+    /// Trusted block MMR root
+    pub root: String,
+    /// Chain height the root was taken at
+    pub height: u32,
+    /// Consistency proof between the trusted MMR state and the proven one
+    pub consistency_proof: BlockConsistencyProof,
+}
+
+impl TrustedRoot {
+    /// Check that the block is covered by the trusted root and that the MMR it is included in
+    /// extends the trusted one
+    ///
+    /// - `block_header_proof`: Verified inclusion proof of the block in the proven MMR
+    pub fn check(&self, block_header_proof: &BlockInclusionProof) -> Result<(), VerifyError> {
+        let proof = &self.consistency_proof;
+        let untrusted = |msg: String| Err(VerifyError::UntrustedMmr(msg));
+
+        if proof.prev_leaf_count != self.height as usize + 1 {
+            return untrusted(format!(
+                "consistency proof starts at height {}, trusted height is {}",
+                proof.prev_leaf_count as i64 - 1,
+                self.height
+            ));
+        }
+        if block_header_proof.leaf_index > self.height as usize {
+            return untrusted(format!(
+                "block {} is above the trusted height {}",
+                block_header_proof.leaf_index, self.height
+            ));
+        }
+        if proof.leaf_count != block_header_proof.leaf_count
+            || proof.peaks_hashes != block_header_proof.peaks_hashes
+        {
+            return untrusted(
+                "consistency proof doesn't end at the MMR the block is included in".to_string(),
+            );
+        }
+
+        let prev_root =
+            root_hash_from_peaks(proof.prev_peaks_hashes.clone(), proof.prev_leaf_count)
+                .map_err(|e| VerifyError::UntrustedMmr(e.to_string()))?;
+        if normalize_hash(&prev_root) != normalize_hash(&self.root) {
+            return untrusted(format!(
+                "MMR root at height {} is {}, trusted root is {}",
+                self.height, prev_root, self.root
+            ));
+        }
+        if !verify_consistency(proof).map_err(|e| VerifyError::UntrustedMmr(e.to_string()))? {
+            return untrusted("invalid consistency proof".to_string());
+        }
+
+        info!(
+            "Block MMR extends the trusted root at height {}",
+            self.height
+        );
+        Ok(())
+    }
+}
+
+/// Lowercase hex string without prefix
+fn normalize_hash(hash: &str) -> String {
+    hash.trim_start_matches("0x").to_lowercase()
+}
@@ -0,0 +1,1245 @@
+//! Verification routines for compressed SPV proofs, including transaction, block MMR,
+//! Cairo recursive proof, and subchain work checks.
+
+use bitcoin::consensus::params::Params;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::Network;
+use bitcoin::{block::Header as BlockHeader, consensus, MerkleBlock, Transaction};
+use bitcoin::{BlockHash, TxOut, Txid, Wtxid};
+use bzip2::read::BzDecoder;
+use cairo_air::PreProcessedTraceVariant;
+use chrono::Utc;
+use num_bigint::BigUint;
+use raito_spv_core::block_mmr::{BlockInclusionPath, BlockInclusionProof, BlockPeaks};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use stwo_prover::core::vcs::blake2_merkle::Blake2sMerkleChannel;
+use stwo_prover::core::vcs::poseidon252_merkle::Poseidon252MerkleChannel;
+use tracing::{info, warn};
+use web_time::Instant;
+
+use crate::coinbase::{verify_coinbase, CoinbaseSummary};
+use crate::error::VerifyError;
+use crate::proof::{
+    BootloaderOutput, BundledTransaction, ChainState, ChainStateCairoProof, CompressedSpvProof,
+    ProofBundle, TaskResult, WitnessProof, DEV_CHANNEL,
+};
+use crate::registry::{same_hash, ProgramRegistry};
+use crate::trusted::TrustedRoot;
+use crate::witness::verify_witness;
+use crate::work::{
+    has_unreliable_difficulty, verify_subchain_headers, verify_subchain_work, WorkSummary,
+};
+
+/// Magic bytes at the beginning of every bzip2 stream
+pub const BZIP2_MAGIC: &[u8; 3] = b"BZh";
+
+/// Configuration parameters controlling verification policies
+#[derive(Debug, Clone)]
+pub struct VerifierConfig {
+    /// Minimum cumulative work required on top of the target block (decimal string)
+    pub min_work: String,
+    /// Minimum work required on top of the target block in blocks at the current target;
+    /// if set, takes precedence over `min_work` so that the policy follows difficulty changes
+    pub min_confirmations: Option<u32>,
+    /// Expected bootloader program hash used to generate the recursive proof (hex string)
+    pub bootloader_hash: String,
+    /// Expected payload program hash verified by the bootloader (hex string)
+    pub task_program_hash: String,
+    /// Expected size of the payload program output in felts
+    pub task_output_size: u32,
+    /// Released programs accepted by the verifier; if set, the program hashes and the
+    /// output size are taken from the matching release instead of the fields above
+    pub programs: Option<ProgramRegistry>,
+    /// Maximum age in seconds of the proven chain state, judged by its most recent timestamp
+    pub max_proof_age: Option<u64>,
+    /// Maximum age in seconds of the block containing the transaction
+    pub max_block_age: Option<u64>,
+    /// Network the proofs are generated for, determines the difficulty rules used to
+    /// estimate the subchain work
+    pub network: Network,
+    /// Skip the subchain work check
+    pub skip_work: bool,
+    /// Skip the consistency checks between the block MMR proof and the chain state
+    pub skip_mmr_consistency: bool,
+    /// Skip the STARK verification of the chain state proof
+    pub skip_stark: bool,
+    /// Accept the development stubs of chain state proofs, which prove nothing
+    pub allow_dev_proofs: bool,
+    /// Require a witness proof, so that the witnesses of the transaction are proven along
+    /// with its ID
+    pub require_witness_proof: bool,
+    /// Require the transaction to be a mature coinbase committing to its block height
+    pub require_coinbase: bool,
+    /// Block MMR root pinned by the user, which the MMR of the proof must extend
+    pub trusted_root: Option<TrustedRoot>,
+}
+
+impl Default for VerifierConfig {
+    fn default() -> Self {
+        Self {
+            min_work: "1813388729421943762059264".to_string(), // 6 * 2^78, i.e. six block confirmations given the latest difficulty
+            min_confirmations: None,
+            bootloader_hash: "0x0001837d8b77b6368e0129ce3f65b5d63863cfab93c47865ee5cbe62922ab8f3"
+                .to_string(),
+            task_program_hash: "0x00f0876bb47895e8c4a6e7043829d7886e3b135e3ef30544fb688ef4e25663ca"
+                .to_string(),
+            task_output_size: 8,
+            programs: Some(ProgramRegistry::embedded()),
+            max_proof_age: None,
+            max_block_age: None,
+            network: Network::Bitcoin,
+            skip_work: false,
+            skip_mmr_consistency: false,
+            skip_stark: false,
+            allow_dev_proofs: false,
+            require_witness_proof: false,
+            require_coinbase: false,
+            trusted_root: None,
+        }
+    }
+}
+
+impl VerifierConfig {
+    /// Relax the checks that cannot pass against a local development bridge node, whose
+    /// block MMR is not in sync with the proven chain state, and accept the development stubs
+    /// of chain state proofs
+    pub fn enable_dev_mode(&mut self) {
+        self.skip_mmr_consistency = true;
+        self.allow_dev_proofs = true;
+    }
+
+    /// Check that the configured values are well-formed
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if BigUint::from_str(&self.min_work).is_err() {
+            anyhow::bail!("Minimum work must be a decimal number: {}", self.min_work);
+        }
+        for (name, hash) in [
+            ("bootloader hash", &self.bootloader_hash),
+            ("task program hash", &self.task_program_hash),
+        ] {
+            let digits = hash.strip_prefix("0x").unwrap_or(hash);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                anyhow::bail!("Expected {} must be a hex string: {}", name, hash);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verification options exposed by the language bindings, all optional; unset options keep
+/// their [VerifierConfig::default] value
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerifyOptions {
+    /// Network the proof is generated for (`bitcoin`, `testnet4`, `signet` or `regtest`)
+    pub network: Option<Network>,
+    /// Minimum cumulative work required on top of the target block (decimal string)
+    pub min_work: Option<String>,
+    /// Minimum work required on top of the target block in blocks at the current target
+    pub min_confirmations: Option<u32>,
+    /// Maximum age in seconds of the proven chain state
+    pub max_proof_age: Option<u64>,
+    /// Maximum age in seconds of the block containing the transaction
+    pub max_block_age: Option<u64>,
+}
+
+impl VerifyOptions {
+    /// Verifier configuration with these options applied on top of the defaults
+    pub fn into_config(self) -> Result<VerifierConfig, anyhow::Error> {
+        let mut config = VerifierConfig::default();
+        if let Some(network) = self.network {
+            config.network = network;
+        }
+        if let Some(min_work) = self.min_work {
+            config.min_work = min_work;
+        }
+        config.min_confirmations = self.min_confirmations;
+        config.max_proof_age = self.max_proof_age;
+        config.max_block_age = self.max_block_age;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Outcome of a successful chain state proof verification
+#[derive(Debug, Clone)]
+pub struct VerifiedChainState {
+    /// Block MMR root committed by the chain state proof
+    pub block_mmr_root: String,
+    /// Name of the program release the proof was generated with, if a registry is used
+    pub program_release: Option<String>,
+    /// Bootloader program hash the proof was generated with
+    pub bootloader_hash: String,
+    /// Payload program hash verified by the bootloader
+    pub task_program_hash: String,
+    /// Merkle channel the proof was generated with
+    pub channel: &'static str,
+}
+
+/// Summary of a successfully verified compressed SPV proof
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationReport {
+    /// ID of the proven transaction
+    pub txid: Txid,
+    /// The proven transaction
+    pub transaction: Transaction,
+    /// Hash of the block containing the transaction
+    pub block_hash: BlockHash,
+    /// Header of the block containing the transaction
+    pub block_header: BlockHeader,
+    /// Height of the block containing the transaction
+    pub block_height: u32,
+    /// Height of the best block in the proven chain state
+    pub chain_height: u32,
+    /// Hash of the best block in the proven chain state
+    pub best_block_hash: BlockHash,
+    /// Total accumulated work of the proven chain as a decimal string
+    pub total_work: String,
+    /// Total accumulated work of the proven chain in human-meaningful units
+    pub cumulative_work: WorkSummary,
+    /// Number of blocks in the proven chain on top of the transaction block, including itself
+    pub confirmations: u32,
+    /// Work on top of the transaction block (a lower bound unless computed from embedded
+    /// headers), unless the work check is skipped
+    pub subchain_work: Option<WorkSummary>,
+    /// Block MMR root committed by the chain state proof
+    pub block_mmr_root: String,
+    /// Name of the program release the chain state proof was generated with
+    pub program_release: Option<String>,
+    /// Bootloader program hash the chain state proof was generated with
+    pub bootloader_hash: String,
+    /// Payload program hash verified by the bootloader
+    pub task_program_hash: String,
+    /// Merkle channel the chain state proof was generated with
+    pub channel: &'static str,
+    /// Network the proof was verified for, used to render addresses
+    pub network: Network,
+    /// Outputs spent by the transaction inputs, if embedded in the proof (not proven)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prevouts: Option<Vec<TxOut>>,
+    /// Witness ID of the proven transaction, if its witnesses are proven by a witness proof
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wtxid: Option<Wtxid>,
+    /// Path of the block in the block MMR, if requested for display
+    #[serde(skip)]
+    pub inclusion_path: Option<BlockInclusionPath>,
+    /// Subsidy and fees of the coinbase, if verified in coinbase mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<CoinbaseSummary>,
+    /// Verification checks and policies applied to the proof
+    pub checks: Vec<CheckResult>,
+    /// Time spent in each verification stage
+    pub timings: VerificationTimings,
+}
+
+/// Outcome of a single verification check or policy
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    /// Name of the check
+    pub name: &'static str,
+    /// Whether the check passed or was skipped
+    pub status: CheckStatus,
+}
+
+/// Status of a verification check that did not fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// The check was run and passed
+    Passed,
+    /// The check was disabled by the configuration
+    Skipped,
+}
+
+impl CheckResult {
+    /// Result of a check that passed, unless it was skipped
+    pub fn new(name: &'static str, skipped: bool) -> Self {
+        let status = if skipped {
+            CheckStatus::Skipped
+        } else {
+            CheckStatus::Passed
+        };
+        Self { name, status }
+    }
+}
+
+impl VerificationReport {
+    /// Whether the STARK proof of the chain state was verified, i.e. the check was neither
+    /// skipped nor run on a development stub
+    pub fn stark_verified(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.name == "stark" && check.status == CheckStatus::Passed)
+    }
+}
+
+/// Time spent in each verification stage, in milliseconds
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerificationTimings {
+    /// Reading, downloading and decoding the proof, shared by all transactions of a bundle
+    pub load_ms: u64,
+    /// Transaction inclusion proof
+    pub transaction_ms: u64,
+    /// Block MMR inclusion proof
+    pub block_header_ms: u64,
+    /// Chain state proof, shared by all transactions of a bundle
+    pub chain_state_ms: u64,
+    /// Subchain work check
+    pub work_ms: u64,
+    /// Whole verification, excluding loading the proof
+    pub total_ms: u64,
+}
+
+/// Checks and policies applied by a successful verification under `config`, of a chain state
+/// proof generated with `channel`
+fn policy_checks(config: &VerifierConfig, channel: &str) -> Vec<CheckResult> {
+    let mut checks = vec![
+        CheckResult::new("transaction_inclusion", false),
+        CheckResult::new("block_inclusion", false),
+        CheckResult::new("mmr_consistency", config.skip_mmr_consistency),
+        CheckResult::new("program_hashes", false),
+        CheckResult::new("stark", config.skip_stark || channel == DEV_CHANNEL),
+        CheckResult::new("work", config.skip_work),
+    ];
+    if config.max_proof_age.is_some() {
+        checks.push(CheckResult::new("max_proof_age", false));
+    }
+    if config.max_block_age.is_some() {
+        checks.push(CheckResult::new("max_block_age", false));
+    }
+    checks
+}
+
+/// Milliseconds elapsed since `start`
+pub fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// Load a compressed proof from disk that was saved using bincode binary codec with bzip2 compression
+///
+/// - `proof_path`: Path to the bzip2 compressed proof file
+///
+/// This function first decompresses the bzip2 file, then deserializes the bytes
+/// using bincode binary codec (see [CompressedSpvProof::from_bytes]).
+pub fn load_compressed_proof_from_bzip2(
+    proof_path: &Path,
+) -> Result<CompressedSpvProof, VerifyError> {
+    info!(
+        "Loading and decompressing proof from {}",
+        proof_path.display()
+    );
+
+    let file = std::fs::File::open(proof_path)?;
+    decode_compressed_proof_from_bzip2(file)
+}
+
+/// Decompress and deserialize a compressed proof from a bzip2 stream of bincode bytes
+pub fn decode_compressed_proof_from_bzip2<R: Read>(
+    reader: R,
+) -> Result<CompressedSpvProof, VerifyError> {
+    // Step 1: Decompress the stream
+    let mut bz_decoder = BzDecoder::new(reader);
+    let mut decompressed_bytes = Vec::new();
+    bz_decoder.read_to_end(&mut decompressed_bytes)?;
+
+    info!(
+        "Decompressed {} bytes, now deserializing...",
+        decompressed_bytes.len()
+    );
+
+    // Step 2: Deserialize the decompressed bytes, checking the format version
+    let proof = CompressedSpvProof::from_bytes(&decompressed_bytes)?;
+
+    info!("Successfully loaded compressed proof");
+    Ok(proof)
+}
+
+/// Load a proof bundle from disk that was saved using bincode binary codec with bzip2 compression
+pub fn load_proof_bundle_from_bzip2(bundle_path: &Path) -> Result<ProofBundle, VerifyError> {
+    info!(
+        "Loading and decompressing proof bundle from {}",
+        bundle_path.display()
+    );
+
+    let file = std::fs::File::open(bundle_path)?;
+    let mut bz_decoder = BzDecoder::new(file);
+    let mut decompressed_bytes = Vec::new();
+    bz_decoder.read_to_end(&mut decompressed_bytes)?;
+
+    let bundle = ProofBundle::from_bytes(&decompressed_bytes)?;
+    info!(
+        "Successfully loaded bundle of {} proof(s)",
+        bundle.transactions.len()
+    );
+    Ok(bundle)
+}
+
+/// Decode a proof from in-memory bytes, detecting the format by looking at the leading bytes:
+/// bzip2 compressed bincode, or JSON
+pub fn decode_proof(bytes: &[u8]) -> Result<CompressedSpvProof, VerifyError> {
+    if bytes.starts_with(BZIP2_MAGIC) {
+        decode_compressed_proof_from_bzip2(bytes)
+    } else {
+        serde_json::from_slice(bytes).map_err(VerifyError::decode)
+    }
+}
+
+/// Verify a compressed SPV proof end-to-end.
+///
+/// This checks transaction inclusion, block header inclusion in the block MMR,
+/// Cairo recursive proof validity, and sufficient subchain work.
+/// Returns a summary of the verified proof on success.
+pub async fn verify_proof(
+    proof: CompressedSpvProof,
+    config: &VerifierConfig,
+) -> Result<VerificationReport, VerifyError> {
+    verify_proof_with_cache(proof, config, None).await
+}
+
+/// Verify a compressed SPV proof end-to-end, skipping the chain state proof verification if
+/// the same chain state proof was already verified with `cache`.
+pub async fn verify_proof_with_cache(
+    proof: CompressedSpvProof,
+    config: &VerifierConfig,
+    cache: Option<&ChainStateVerificationCache>,
+) -> Result<VerificationReport, VerifyError> {
+    let CompressedSpvProof {
+        chain_state,
+        chain_state_proof,
+        block_header,
+        block_height,
+        block_header_proof,
+        transaction,
+        transaction_proof,
+        subchain_headers,
+        prevouts,
+        witness_proof,
+    } = proof;
+    let transaction_proof = TransactionProof {
+        block_header,
+        block_height,
+        block_header_proof,
+        transaction,
+        transaction_proof,
+        subchain_headers,
+        prevouts,
+        witness_proof,
+    };
+    verify_transaction_proof(&chain_state, transaction_proof, config, || match cache {
+        Some(cache) => cache.verify(&chain_state, chain_state_proof, config),
+        None => verify_chain_state(&chain_state, chain_state_proof, config),
+    })
+}
+
+/// Per-transaction part of a proof, shared by standalone proofs and bundled transactions so
+/// that both are verified by [verify_transaction_proof]
+struct TransactionProof {
+    block_header: BlockHeader,
+    block_height: u32,
+    block_header_proof: BlockInclusionProof,
+    transaction: Transaction,
+    transaction_proof: Vec<u8>,
+    subchain_headers: Option<Vec<BlockHeader>>,
+    prevouts: Option<Vec<TxOut>>,
+    witness_proof: Option<WitnessProof>,
+}
+
+impl From<BundledTransaction> for TransactionProof {
+    fn from(bundled: BundledTransaction) -> Self {
+        // Bundled transactions don't carry proof extensions
+        Self {
+            block_header: bundled.block_header,
+            block_height: bundled.block_height,
+            block_header_proof: bundled.block_header_proof,
+            transaction: bundled.transaction,
+            transaction_proof: bundled.transaction_proof,
+            subchain_headers: None,
+            prevouts: None,
+            witness_proof: None,
+        }
+    }
+}
+
+/// Verify the inclusion of a transaction in the proven chain.
+///
+/// `verify_chain_state` is called once the cheaper per-transaction checks have passed, to
+/// verify the chain state proof or to reuse the verification shared by a bundle.
+fn verify_transaction_proof(
+    chain_state: &ChainState,
+    proof: TransactionProof,
+    config: &VerifierConfig,
+    verify_chain_state: impl FnOnce() -> Result<VerifiedChainState, VerifyError>,
+) -> Result<VerificationReport, VerifyError> {
+    let TransactionProof {
+        block_header,
+        block_height,
+        block_header_proof,
+        transaction,
+        transaction_proof,
+        subchain_headers,
+        prevouts,
+        witness_proof,
+    } = proof;
+    let start = Instant::now();
+    let mut timings = VerificationTimings::default();
+    let mut subchain_work = None;
+
+    // Sanity checks
+    verify_block_height(block_height, &block_header_proof, chain_state, config)?;
+
+    verify_freshness(chain_state, &block_header, config)?;
+
+    info!("Verifying transaction inclusion proof ...");
+    let stage = Instant::now();
+    verify_transaction(&transaction, &block_header, transaction_proof)?;
+    timings.transaction_ms = elapsed_ms(stage);
+
+    if let Some(prevouts) = &prevouts {
+        verify_prevouts(&transaction, prevouts)?;
+    }
+
+    let wtxid = match &witness_proof {
+        Some(witness_proof) => {
+            info!("Verifying witness proof ...");
+            Some(verify_witness(&transaction, &block_header, witness_proof)?)
+        }
+        None if config.require_witness_proof => return Err(VerifyError::MissingWitnessProof),
+        None => None,
+    };
+
+    info!("Verifying block inclusion proof ...");
+    let stage = Instant::now();
+    let block_mmr_root_0 = verify_block_header(&block_header, &block_header_proof)?;
+    timings.block_header_ms = elapsed_ms(stage);
+
+    if let Some(trusted_root) = &config.trusted_root {
+        trusted_root.check(&block_header_proof)?;
+    }
+
+    info!("Verifying chain state proof ...");
+    let stage = Instant::now();
+    let VerifiedChainState {
+        block_mmr_root: block_mmr_hash_1,
+        program_release,
+        bootloader_hash,
+        task_program_hash,
+        channel,
+    } = verify_chain_state()?;
+    timings.chain_state_ms = elapsed_ms(stage);
+
+    if config.skip_mmr_consistency {
+        warn!("INSECURE: skipping block MMR root consistency check");
+    } else if block_mmr_root_0 != block_mmr_hash_1 {
+        return Err(VerifyError::MmrRootMismatch {
+            block_mmr_root: block_mmr_root_0,
+            proven_mmr_root: block_mmr_hash_1,
+        });
+    }
+
+    if config.skip_work {
+        warn!("INSECURE: skipping subchain work check");
+    } else {
+        if has_unreliable_difficulty(&Params::new(config.network)) {
+            warn!(
+                "Blocks on {} are not secured by their difficulty, the estimated subchain work only counts blocks at the minimum difficulty",
+                config.network
+            );
+        }
+        info!("Verifying subchain work ...");
+        let stage = Instant::now();
+        let work = match &subchain_headers {
+            Some(headers) => {
+                verify_subchain_headers(&block_header, block_height, headers, chain_state, config)?
+            }
+            None => verify_subchain_work(block_height, chain_state, config)?,
+        };
+        subchain_work = Some(WorkSummary::new(&work, chain_state));
+        timings.work_ms = elapsed_ms(stage);
+    }
+
+    let coinbase = if config.require_coinbase {
+        Some(verify_coinbase(
+            &transaction,
+            block_height,
+            chain_state,
+            config,
+        )?)
+    } else {
+        None
+    };
+
+    info!("Verification successful!");
+    timings.total_ms = elapsed_ms(start);
+
+    let mut checks = policy_checks(config, channel);
+    if subchain_headers.is_some() {
+        checks.push(CheckResult::new("subchain_headers", config.skip_work));
+    }
+    if wtxid.is_some() {
+        checks.push(CheckResult::new("witness_commitment", false));
+    }
+    if config.trusted_root.is_some() {
+        checks.push(CheckResult::new("trusted_root", false));
+    }
+    if coinbase.is_some() {
+        checks.push(CheckResult::new("coinbase", false));
+    }
+
+    Ok(VerificationReport {
+        txid: transaction.compute_txid(),
+        transaction,
+        block_hash: block_header.block_hash(),
+        block_header,
+        block_height,
+        chain_height: chain_state.block_height,
+        best_block_hash: chain_state.best_block_hash,
+        total_work: chain_state.total_work.clone(),
+        cumulative_work: WorkSummary::new(&chain_state.total_work()?, chain_state),
+        confirmations: (chain_state.block_height + 1).saturating_sub(block_height),
+        subchain_work,
+        block_mmr_root: block_mmr_hash_1,
+        program_release,
+        bootloader_hash,
+        task_program_hash,
+        channel,
+        network: config.network,
+        prevouts,
+        wtxid,
+        inclusion_path: None,
+        coinbase,
+        checks,
+        timings,
+    })
+}
+
+/// Check that the embedded previous outputs match the transaction inputs one to one.
+///
+/// The outputs themselves cannot be checked without the previous transactions, they are
+/// only used for display.
+fn verify_prevouts(transaction: &Transaction, prevouts: &[TxOut]) -> Result<(), VerifyError> {
+    let num_spent = if transaction.is_coinbase() {
+        0
+    } else {
+        transaction.input.len()
+    };
+    if prevouts.len() != num_spent {
+        return Err(VerifyError::InvalidPrevouts(format!(
+            "{} previous output(s) for {} spent input(s)",
+            prevouts.len(),
+            num_spent
+        )));
+    }
+    warn!("Previous outputs are reported by the Bitcoin node and not proven");
+    Ok(())
+}
+
+/// Verify all proofs of a bundle, checking the shared chain state proof only once.
+///
+/// Fails if the chain state proof is invalid, otherwise returns the verification result
+/// of each bundled transaction.
+pub async fn verify_bundle(
+    bundle: ProofBundle,
+    config: &VerifierConfig,
+) -> Result<Vec<Result<VerificationReport, VerifyError>>, VerifyError> {
+    let ProofBundle {
+        chain_state,
+        chain_state_proof,
+        transactions,
+    } = bundle;
+
+    info!("Verifying chain state proof ...");
+    let stage = Instant::now();
+    let verified = verify_chain_state(&chain_state, chain_state_proof, config)?;
+    let chain_state_ms = elapsed_ms(stage);
+
+    let results = verify_bundled_transactions(chain_state, verified, transactions, config).await;
+    Ok(results
+        .into_iter()
+        .map(|res| {
+            res.map(|mut report| {
+                report.timings.chain_state_ms = chain_state_ms;
+                report
+            })
+        })
+        .collect())
+}
+
+/// Verify the transactions of a bundle concurrently: the per-transaction checks are
+/// independent from each other.
+#[cfg(feature = "parallel")]
+async fn verify_bundled_transactions(
+    chain_state: ChainState,
+    verified: VerifiedChainState,
+    transactions: Vec<BundledTransaction>,
+    config: &VerifierConfig,
+) -> Vec<Result<VerificationReport, VerifyError>> {
+    let chain_state = Arc::new(chain_state);
+    let verified = Arc::new(verified);
+    let config = Arc::new(config.clone());
+    let tasks: Vec<_> = transactions
+        .into_iter()
+        .map(|transaction| {
+            let chain_state = chain_state.clone();
+            let verified = verified.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                verify_bundled_transaction(&chain_state, &verified, transaction, &config).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(
+            task.await
+                .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic())),
+        );
+    }
+    results
+}
+
+/// Verify the transactions of a bundle one after the other.
+#[cfg(not(feature = "parallel"))]
+async fn verify_bundled_transactions(
+    chain_state: ChainState,
+    verified: VerifiedChainState,
+    transactions: Vec<BundledTransaction>,
+    config: &VerifierConfig,
+) -> Vec<Result<VerificationReport, VerifyError>> {
+    let mut results = Vec::with_capacity(transactions.len());
+    for transaction in transactions {
+        results
+            .push(verify_bundled_transaction(&chain_state, &verified, transaction, config).await);
+    }
+    results
+}
+
+/// Verify a single transaction of a bundle against an already verified chain state.
+async fn verify_bundled_transaction(
+    chain_state: &ChainState,
+    verified: &VerifiedChainState,
+    bundled: BundledTransaction,
+    config: &VerifierConfig,
+) -> Result<VerificationReport, VerifyError> {
+    verify_transaction_proof(chain_state, bundled.into(), config, || Ok(verified.clone()))
+}
+
+/// In-memory cache of verified chain state proofs, keyed by the hash of the chain state and its
+/// proof, so that proofs sharing a chain state proof only run the STARK verification once
+#[derive(Debug, Default)]
+pub struct ChainStateVerificationCache {
+    entries: Mutex<HashMap<sha256::Hash, Arc<Mutex<Option<VerifiedChainState>>>>>,
+}
+
+impl ChainStateVerificationCache {
+    /// Verify the chain state proof, unless the same proof was already successfully verified
+    pub fn verify(
+        &self,
+        chain_state: &ChainState,
+        chain_state_proof: ChainStateCairoProof,
+        config: &VerifierConfig,
+    ) -> Result<VerifiedChainState, VerifyError> {
+        let key = chain_state_proof_key(chain_state, &chain_state_proof)?;
+        let entry = self
+            .entries
+            .lock()
+            .expect("Chain state cache lock poisoned")
+            .entry(key)
+            .or_default()
+            .clone();
+
+        // Concurrent verifications of the same proof wait for the first one to complete
+        let mut entry = entry.lock().expect("Chain state cache entry lock poisoned");
+        if let Some(verified) = entry.as_ref() {
+            info!("Chain state proof {} was already verified", key);
+            return Ok(verified.clone());
+        }
+        let verified = verify_chain_state(chain_state, chain_state_proof, config)?;
+        *entry = Some(verified.clone());
+        Ok(verified)
+    }
+}
+
+/// SHA-256 of the bincode serialization of the chain state and its proof
+fn chain_state_proof_key(
+    chain_state: &ChainState,
+    chain_state_proof: &ChainStateCairoProof,
+) -> Result<sha256::Hash, VerifyError> {
+    let mut bytes = bincode::serialize(chain_state).map_err(VerifyError::decode)?;
+    bytes.extend(bincode::serialize(chain_state_proof).map_err(VerifyError::decode)?);
+    Ok(sha256::Hash::hash(&bytes))
+}
+
+/// Verify that the proven chain state, and optionally the block containing the transaction,
+/// are recent enough according to the configured staleness windows.
+pub fn verify_freshness(
+    chain_state: &ChainState,
+    block_header: &BlockHeader,
+    config: &VerifierConfig,
+) -> Result<(), VerifyError> {
+    verify_chain_state_age(chain_state, config)?;
+
+    if let Some(max_block_age) = config.max_block_age {
+        let age = Utc::now().timestamp() - block_header.time as i64;
+        if age > max_block_age as i64 {
+            return Err(VerifyError::Stale {
+                what: "Transaction block",
+                age,
+                max_age: max_block_age,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify that the proven chain state is within the configured staleness window.
+pub fn verify_chain_state_age(
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<(), VerifyError> {
+    if let Some(max_proof_age) = config.max_proof_age {
+        let age = Utc::now().timestamp() - chain_state.best_block_time() as i64;
+        if age > max_proof_age as i64 {
+            return Err(VerifyError::Stale {
+                what: "Proven chain state",
+                age,
+                max_age: max_proof_age,
+            });
+        }
+        info!("Proven chain state is {} old", format_duration(age));
+    }
+    Ok(())
+}
+
+/// Verify that `transaction` is included in `block_header` using the provided Merkle proof.
+pub fn verify_transaction(
+    transaction: &Transaction,
+    block_header: &BlockHeader,
+    transaction_proof: Vec<u8>,
+) -> Result<(), VerifyError> {
+    let merkle_block = MerkleBlock {
+        header: block_header.clone(),
+        txn: consensus::deserialize(&transaction_proof)
+            .map_err(|e| VerifyError::TransactionInclusion(e.to_string()))?,
+    };
+
+    let mut matches = Vec::new();
+    let mut indexes = Vec::new();
+    merkle_block
+        .extract_matches(&mut matches, &mut indexes)
+        .map_err(|e| VerifyError::TransactionInclusion(e.to_string()))?;
+
+    if matches.len() != 1 {
+        return Err(VerifyError::TransactionInclusion(
+            "Expected 1 transaction match".to_string(),
+        ));
+    }
+
+    let txid = transaction.compute_txid();
+    if txid != matches[0] {
+        return Err(VerifyError::TransactionInclusion(
+            "Transaction ID mismatch".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Verify that the declared block height is covered by the proven chain state and matches the
+/// position of the block in the MMR, and that the MMR is the one of the proven chain state
+/// (unless the MMR consistency checks are skipped), so that the work and depth checks are
+/// computed for the block that is actually proven.
+pub fn verify_block_height(
+    block_height: u32,
+    block_header_proof: &BlockInclusionProof,
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<(), VerifyError> {
+    // Checked even when the MMR consistency checks are skipped, the work on top of the block
+    // can't be computed for a block above the chain tip
+    if block_height > chain_state.block_height {
+        return Err(VerifyError::BlockNotProven {
+            block_height,
+            chain_height: chain_state.block_height,
+        });
+    }
+    if !config.skip_mmr_consistency
+        && block_header_proof.leaf_count != chain_state.block_height as usize + 1
+    {
+        return Err(VerifyError::MmrSizeMismatch {
+            leaf_count: block_header_proof.leaf_count,
+            chain_height: chain_state.block_height,
+        });
+    }
+    if block_header_proof.leaf_index != block_height as usize {
+        return Err(VerifyError::BlockHeightMismatch {
+            block_height,
+            leaf_index: block_header_proof.leaf_index,
+        });
+    }
+    Ok(())
+}
+
+/// Verify that `block_header` satisfies its own proof of work target and is included in the
+/// block MMR using the supplied inclusion proof.
+///
+/// Returns the computed block MMR root on success.
+pub fn verify_block_header(
+    block_header: &BlockHeader,
+    block_header_proof: &BlockInclusionProof,
+) -> Result<String, VerifyError> {
+    // Redundant with the MMR inclusion, but catches mangled headers with a clearer error
+    block_header
+        .validate_pow(block_header.target())
+        .map_err(|e| VerifyError::InvalidProofOfWork {
+            block_hash: block_header.block_hash().to_string(),
+            reason: e.to_string(),
+        })?;
+    // Decode the peaks once for both the inclusion check and the root
+    let peaks = BlockPeaks::from_proof(block_header_proof)
+        .map_err(|e| VerifyError::BlockInclusion(e.to_string()))?;
+    let included = peaks
+        .verify_block_inclusion(block_header, block_header_proof)
+        .map_err(|e| VerifyError::BlockInclusion(e.to_string()))?;
+    if !included {
+        return Err(VerifyError::BlockInclusion(
+            "Block header is not included in the MMR".to_string(),
+        ));
+    }
+    peaks
+        .root_hash()
+        .map_err(|e| VerifyError::BlockInclusion(e.to_string()))
+}
+
+/// Verify the Cairo recursive proof and consistency of the bootloader output with `chain_state`.
+///
+/// Returns the block MMR root extracted from the proof and the matching program release
+/// on success.
+pub fn verify_chain_state(
+    chain_state: &ChainState,
+    chain_state_proof: ChainStateCairoProof,
+    config: &VerifierConfig,
+) -> Result<VerifiedChainState, VerifyError> {
+    if chain_state_proof.is_dev() {
+        if !config.allow_dev_proofs {
+            return Err(VerifyError::DevProof);
+        }
+        warn!(
+            "DEV PROOF: the chain state proof is a development stub, the chain state is not proven"
+        );
+    }
+
+    info!("Extracting verification output...");
+
+    // Extract the bootloader hash and decode the bootloader output from the public memory
+    let (
+        bootloader_hash,
+        BootloaderOutput {
+            n_tasks,
+            task_output_size,
+            task_program_hash,
+            task_result,
+        },
+    ) = BootloaderOutput::from_proof(&chain_state_proof)?;
+
+    if n_tasks != 1 {
+        return Err(VerifyError::InvalidBootloaderOutput(format!(
+            "number of tasks must be 1, got {}",
+            n_tasks
+        )));
+    }
+
+    // Resolve the expected program: either the matching registry release or the pinned hashes
+    let (
+        expected_bootloader_hash,
+        expected_task_program_hash,
+        expected_task_output_size,
+        program_release,
+    ) = match &config.programs {
+        Some(registry) => {
+            let release = registry
+                .find(
+                    &bootloader_hash,
+                    &task_program_hash,
+                    task_output_size,
+                    chain_state.block_height,
+                )
+                .map_err(|e| VerifyError::UnknownProgram(e.to_string()))?;
+            info!(
+                "Chain state proof was generated with program release {}",
+                release.name
+            );
+            (
+                release.bootloader_hash.clone(),
+                release.task_program_hash.clone(),
+                release.task_output_size,
+                Some(release.name.clone()),
+            )
+        }
+        None => (
+            config.bootloader_hash.clone(),
+            config.task_program_hash.clone(),
+            config.task_output_size,
+            None,
+        ),
+    };
+
+    if task_output_size != expected_task_output_size {
+        return Err(VerifyError::InvalidBootloaderOutput(format!(
+            "task output size must be {}, got {}",
+            expected_task_output_size, task_output_size
+        )));
+    }
+
+    let TaskResult {
+        chain_state_hash,
+        block_mmr_hash,
+        program_hash: prev_program_hash,
+        bootloader_hash: prev_bootloader_hash,
+    } = task_result.clone();
+
+    // Check that chain state hashes match
+    let expected_chain_state_hash = chain_state.blake2s_digest()?;
+    if chain_state_hash != expected_chain_state_hash {
+        return Err(VerifyError::HashMismatch {
+            what: "Chain state hash",
+            actual: chain_state_hash,
+            expected: expected_chain_state_hash,
+        });
+    }
+
+    // Check that the program hash is the same as in the bootloader output and as expected
+    if !same_hash(&task_program_hash, &expected_task_program_hash) {
+        return Err(VerifyError::HashMismatch {
+            what: "Task program hash",
+            actual: task_program_hash,
+            expected: expected_task_program_hash,
+        });
+    }
+    if task_program_hash != prev_program_hash {
+        return Err(VerifyError::HashMismatch {
+            what: "Previous program hash",
+            actual: prev_program_hash,
+            expected: task_program_hash,
+        });
+    }
+
+    // Check that the previous bootloader hash is the same as in the Cairo claim and as expected
+    if !same_hash(&bootloader_hash, &expected_bootloader_hash) {
+        return Err(VerifyError::HashMismatch {
+            what: "Bootloader hash",
+            actual: bootloader_hash,
+            expected: expected_bootloader_hash,
+        });
+    }
+    if bootloader_hash != prev_bootloader_hash {
+        return Err(VerifyError::HashMismatch {
+            what: "Previous bootloader hash",
+            actual: prev_bootloader_hash,
+            expected: bootloader_hash,
+        });
+    }
+
+    let channel = chain_state_proof.channel();
+    if config.skip_stark {
+        warn!("INSECURE: skipping Cairo proof verification");
+    } else if chain_state_proof.is_dev() {
+        warn!("Skipping Cairo proof verification of the development stub");
+    } else {
+        info!("Verifying Cairo proof ({} channel)...", channel);
+        let preprocessed_trace = PreProcessedTraceVariant::CanonicalWithoutPedersenAndPoseidon;
+        match chain_state_proof {
+            ChainStateCairoProof::Blake2s(proof) => {
+                cairo_air::verifier::verify_cairo::<Blake2sMerkleChannel>(proof, preprocessed_trace)
+            }
+            ChainStateCairoProof::Poseidon252(proof) => cairo_air::verifier::verify_cairo::<
+                Poseidon252MerkleChannel,
+            >(proof, preprocessed_trace),
+            ChainStateCairoProof::Dev(_) => unreachable!("development stubs are not verified"),
+        }
+        .map_err(|e| VerifyError::InvalidStarkProof(e.to_string()))?;
+    }
+
+    Ok(VerifiedChainState {
+        block_mmr_root: block_mmr_hash,
+        program_release,
+        bootloader_hash,
+        task_program_hash,
+        channel,
+    })
+}
+
+/// Format a duration in seconds as a compact human-readable string, e.g. `2h 13m`
+pub fn format_duration(seconds: i64) -> String {
+    if seconds < 0 {
+        return format!("-{}", format_duration(-seconds));
+    }
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::proof::DevChainStateProof;
+
+    const BOOTLOADER_HASH: &str =
+        "0x0001837d8b77b6368e0129ce3f65b5d63863cfab93c47865ee5cbe62922ab8f3";
+    const TASK_PROGRAM_HASH: &str =
+        "0x00f0876bb47895e8c4a6e7043829d7886e3b135e3ef30544fb688ef4e25663ca";
+
+    fn chain_state() -> ChainState {
+        ChainState {
+            block_height: 0,
+            total_work: "4295032833".to_string(),
+            best_block_hash: BlockHash::from_str(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            )
+            .unwrap(),
+            current_target: "26959535291011309493156476344723991336010898738574164086137773096960"
+                .to_string(),
+            epoch_start_time: 1231006505,
+            prev_timestamps: vec![1231006505],
+        }
+    }
+
+    fn dev_proof(chain_state: &ChainState) -> ChainStateCairoProof {
+        DevChainStateProof::new(
+            chain_state,
+            "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            BOOTLOADER_HASH,
+            TASK_PROGRAM_HASH,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn registry(bootloader_hash: &str) -> ProgramRegistry {
+        ProgramRegistry::parse(&format!(
+            r#"
+            [[release]]
+            name = "v1"
+            bootloader_hash = "{}"
+            task_program_hash = "{}"
+            task_output_size = 8
+            activation_height = 0
+            "#,
+            bootloader_hash, TASK_PROGRAM_HASH
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_options() {
+        let options: VerifyOptions =
+            serde_json::from_str(r#"{"network": "signet", "max_block_age": 600}"#).unwrap();
+        let config = options.into_config().unwrap();
+        assert_eq!(config.network, Network::Signet);
+        assert_eq!(config.max_block_age, Some(600));
+        assert_eq!(config.min_work, VerifierConfig::default().min_work);
+
+        assert!(serde_json::from_str::<VerifyOptions>(r#"{"skip_stark": true}"#).is_err());
+        let invalid = VerifyOptions {
+            min_work: Some("lots".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.into_config().is_err());
+    }
+
+    #[test]
+    fn test_verify_block_height() {
+        let chain_state = chain_state();
+        let config = VerifierConfig::default();
+        let proof = |leaf_index, leaf_count| BlockInclusionProof {
+            peaks_hashes: vec![],
+            siblings_hashes: vec![],
+            leaf_index,
+            leaf_count,
+        };
+        assert!(verify_block_height(0, &proof(0, 1), &chain_state, &config).is_ok());
+        assert!(matches!(
+            verify_block_height(0, &proof(1, 1), &chain_state, &config),
+            Err(VerifyError::BlockHeightMismatch { .. })
+        ));
+        // Proof against the MMR of another chain height
+        assert!(matches!(
+            verify_block_height(0, &proof(0, 2), &chain_state, &config),
+            Err(VerifyError::MmrSizeMismatch { .. })
+        ));
+
+        let config = VerifierConfig {
+            skip_mmr_consistency: true,
+            ..config
+        };
+        assert!(verify_block_height(0, &proof(0, 2), &chain_state, &config).is_ok());
+        // A block above the proven chain height, in an MMR that has it
+        assert!(matches!(
+            verify_block_height(1, &proof(1, 2), &chain_state, &config),
+            Err(VerifyError::BlockNotProven {
+                block_height: 1,
+                chain_height: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_verify_chain_state_registry() {
+        let chain_state = chain_state();
+        let config = VerifierConfig {
+            programs: Some(registry(BOOTLOADER_HASH)),
+            allow_dev_proofs: true,
+            ..Default::default()
+        };
+        let verified = verify_chain_state(&chain_state, dev_proof(&chain_state), &config).unwrap();
+        assert_eq!(verified.program_release.as_deref(), Some("v1"));
+
+        // A proof generated with another bootloader doesn't match the release
+        let config = VerifierConfig {
+            programs: Some(registry("0x01")),
+            ..config
+        };
+        assert!(matches!(
+            verify_chain_state(&chain_state, dev_proof(&chain_state), &config),
+            Err(VerifyError::UnknownProgram(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_chain_state_pinned_hashes() {
+        let chain_state = chain_state();
+        let config = VerifierConfig {
+            programs: None,
+            allow_dev_proofs: true,
+            ..Default::default()
+        };
+        assert!(verify_chain_state(&chain_state, dev_proof(&chain_state), &config).is_ok());
+
+        let config = VerifierConfig {
+            bootloader_hash: "0x01".to_string(),
+            ..config
+        };
+        assert!(matches!(
+            verify_chain_state(&chain_state, dev_proof(&chain_state), &config),
+            Err(VerifyError::HashMismatch {
+                what: "Bootloader hash",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+//! Witness commitment verification mode, so that a witness-malleated copy of the proven
+//! transaction, which has the same txid, cannot be passed off as the transaction in the block.
+
+use bitcoin::consensus;
+use bitcoin::hashes::Hash;
+use bitcoin::merkle_tree::PartialMerkleTree;
+use bitcoin::{block::Header as BlockHeader, Block, Transaction, TxMerkleNode, Txid};
+use bitcoin::{WitnessMerkleNode, Wtxid};
+use tracing::info;
+
+use crate::error::VerifyError;
+use crate::proof::WitnessProof;
+
+/// Prefix of the coinbase output script holding the witness commitment: `OP_RETURN`, a 36 byte
+/// push, and the commitment header
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Verify that `transaction`, witnesses included, is committed to by the witness commitment of
+/// the block, found in the coinbase proven to be included in `block_header`.
+///
+/// Returns the wtxid of the transaction on success.
+pub fn verify_witness(
+    transaction: &Transaction,
+    block_header: &BlockHeader,
+    witness_proof: &WitnessProof,
+) -> Result<Wtxid, VerifyError> {
+    let WitnessProof {
+        coinbase,
+        coinbase_proof,
+        witness_tree_proof,
+    } = witness_proof;
+    if !coinbase.is_coinbase() {
+        return Err(invalid("Embedded coinbase is not a coinbase transaction"));
+    }
+    let (coinbase_root, coinbase_matches) = extract_matches(coinbase_proof)?;
+    if coinbase_root.to_byte_array() != block_header.merkle_root.to_byte_array() {
+        return Err(invalid(
+            "Coinbase Merkle root doesn't match the block header",
+        ));
+    }
+    if coinbase_matches != [(coinbase.compute_txid(), 0)] {
+        return Err(invalid(
+            "Expected the coinbase as the only match at index 0",
+        ));
+    }
+
+    let commitment = coinbase
+        .output
+        .iter()
+        .rev()
+        .map(|output| output.script_pubkey.as_bytes())
+        .find(|script| script.len() >= 38 && script.starts_with(&WITNESS_COMMITMENT_HEADER))
+        .map(|script| &script[6..38])
+        .ok_or_else(|| invalid("Coinbase has no witness commitment"))?;
+    let reserved_value = match coinbase.input[0].witness.iter().collect::<Vec<_>>()[..] {
+        [reserved_value] if reserved_value.len() == 32 => reserved_value,
+        _ => return Err(invalid("Coinbase witness is not a 32 byte reserved value")),
+    };
+
+    // The witness tree commits to the coinbase through the reserved value, with a zero leaf in
+    // its place, so the coinbase itself is proven by the transaction tree alone
+    let (expected_leaf, expected_index) = if transaction.is_coinbase() {
+        if transaction != coinbase {
+            return Err(invalid("Transaction doesn't match the embedded coinbase"));
+        }
+        (Txid::all_zeros(), Some(0))
+    } else {
+        let wtxid = transaction.compute_wtxid();
+        (Txid::from_raw_hash(wtxid.to_raw_hash()), None)
+    };
+    let (witness_root, witness_matches) = extract_matches(witness_tree_proof)?;
+    match witness_matches[..] {
+        [(leaf, index)] if leaf == expected_leaf && expected_index.is_none_or(|i| i == index) => {}
+        _ => {
+            return Err(invalid(
+                "Expected the wtxid of the transaction as the only match of the witness tree",
+            ))
+        }
+    }
+
+    let witness_root = WitnessMerkleNode::from_byte_array(witness_root.to_byte_array());
+    if Block::compute_witness_commitment(&witness_root, reserved_value).as_byte_array()
+        != commitment
+    {
+        return Err(invalid("Witness root doesn't match the witness commitment"));
+    }
+    let wtxid = transaction.compute_wtxid();
+    info!(
+        "Transaction witnesses match the witness commitment (wtxid {})",
+        wtxid
+    );
+    Ok(wtxid)
+}
+
+/// Build the witness proof of the transaction `txid` from the full block containing it.
+///
+/// Returns `None` if the transaction is not in the block.
+pub fn build_witness_proof(block: &Block, txid: &Txid) -> Option<WitnessProof> {
+    let position = block
+        .txdata
+        .iter()
+        .position(|transaction| transaction.compute_txid() == *txid)?;
+    let coinbase = block.txdata.first()?.clone();
+
+    let txids = block
+        .txdata
+        .iter()
+        .map(Transaction::compute_txid)
+        .collect::<Vec<_>>();
+    let mut matches = vec![false; txids.len()];
+    matches[0] = true;
+    let coinbase_proof = PartialMerkleTree::from_txids(&txids, &matches);
+
+    // Leaves of the witness tree, typed as txids to reuse the partial Merkle tree encoding
+    let wtxids = block
+        .txdata
+        .iter()
+        .enumerate()
+        .map(|(index, transaction)| match index {
+            0 => Txid::all_zeros(),
+            _ => Txid::from_raw_hash(transaction.compute_wtxid().to_raw_hash()),
+        })
+        .collect::<Vec<_>>();
+    let mut matches = vec![false; wtxids.len()];
+    matches[position] = true;
+    let witness_tree_proof = PartialMerkleTree::from_txids(&wtxids, &matches);
+
+    Some(WitnessProof {
+        coinbase,
+        coinbase_proof: consensus::serialize(&coinbase_proof),
+        witness_tree_proof: consensus::serialize(&witness_tree_proof),
+    })
+}
+
+/// Decode a partial Merkle tree and extract its root and its matches with their index
+fn extract_matches(encoded: &[u8]) -> Result<(TxMerkleNode, Vec<(Txid, u32)>), VerifyError> {
+    let tree: PartialMerkleTree =
+        consensus::deserialize(encoded).map_err(|e| invalid(&e.to_string()))?;
+    let mut matches = Vec::new();
+    let mut indexes = Vec::new();
+    let root = tree
+        .extract_matches(&mut matches, &mut indexes)
+        .map_err(|e| invalid(&e.to_string()))?;
+    Ok((root, matches.into_iter().zip(indexes).collect()))
+}
+
+fn invalid(reason: &str) -> VerifyError {
+    VerifyError::InvalidWitnessProof(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, Network, OutPoint, ScriptBuf, Sequence, TxIn, TxOut, Witness};
+
+    fn transaction(previous_output: OutPoint, witness: &[u8]) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[witness]),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_int_btc(1),
+                script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+            }],
+        }
+    }
+
+    /// Block with a coinbase, two segwit spends and a valid witness commitment
+    fn segwit_block() -> Block {
+        let coinbase = transaction(OutPoint::null(), &[0; 32]);
+        let spend = |vout, witness: &[u8]| {
+            let previous_output = OutPoint::new(coinbase.compute_txid(), vout);
+            transaction(previous_output, witness)
+        };
+        let mut block = Block {
+            header: genesis_block(Network::Regtest).header,
+            txdata: vec![coinbase.clone(), spend(0, &[1; 64]), spend(1, &[2; 64])],
+        };
+        let witness_root = block.witness_root().unwrap();
+        let commitment = Block::compute_witness_commitment(&witness_root, &[0; 32]);
+        let mut script = WITNESS_COMMITMENT_HEADER.to_vec();
+        script.extend_from_slice(commitment.as_byte_array());
+        block.txdata[0].output.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::from_bytes(script),
+        });
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        assert!(block.check_witness_commitment());
+        block
+    }
+
+    #[test]
+    fn test_verify_witness() {
+        let block = segwit_block();
+        for transaction in &block.txdata {
+            let witness_proof = build_witness_proof(&block, &transaction.compute_txid()).unwrap();
+            assert_eq!(
+                verify_witness(transaction, &block.header, &witness_proof).unwrap(),
+                transaction.compute_wtxid()
+            );
+        }
+
+        // Same txid, different witness
+        let spend = &block.txdata[1];
+        let witness_proof = build_witness_proof(&block, &spend.compute_txid()).unwrap();
+        let mut malleated = spend.clone();
+        malleated.input[0].witness = Witness::from_slice(&[[3u8; 64]]);
+        assert_eq!(malleated.compute_txid(), spend.compute_txid());
+        assert!(matches!(
+            verify_witness(&malleated, &block.header, &witness_proof),
+            Err(VerifyError::InvalidWitnessProof(_))
+        ));
+
+        // Proof of another transaction of the block
+        assert!(verify_witness(&block.txdata[2], &block.header, &witness_proof).is_err());
+
+        // Proof against another block
+        let other_header = genesis_block(Network::Regtest).header;
+        assert!(verify_witness(spend, &other_header, &witness_proof).is_err());
+
+        // Coinbase without a witness commitment
+        let mut block = segwit_block();
+        block.txdata[0].output.pop();
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        let witness_proof = build_witness_proof(&block, &spend.compute_txid()).unwrap();
+        assert!(verify_witness(spend, &block.header, &witness_proof).is_err());
+    }
+}
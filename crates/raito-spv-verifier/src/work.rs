@@ -0,0 +1,404 @@
+//! Work verification utilities for ensuring sufficient confirmations on top of a target block.
+
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::consensus::params::Params;
+use bitcoin::Network;
+use num_bigint::BigUint;
+use raito_spv_core::primitives::{work_from_target, U256};
+use serde::Serialize;
+use std::cmp::{max, min};
+use std::fmt;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::{error::VerifyError, proof::ChainState, verify::VerifierConfig};
+
+/// Amount of work in human-meaningful units
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkSummary {
+    /// Work as a decimal string
+    pub work: String,
+    /// Equivalent number of blocks at the current target of the proven chain state
+    pub blocks_at_current_target: f64,
+    /// Expected number of hashes to produce the work
+    pub expected_hashes: f64,
+}
+
+impl WorkSummary {
+    /// Express `work` relative to the current target of `chain_state`
+    pub fn new(work: &BigUint, chain_state: &ChainState) -> Self {
+        let target = BigUint::from_str(&chain_state.current_target).unwrap_or_default();
+        let block_work = compute_work_from_target(target);
+        // Keep two decimals of the block count without converting the big integers to floats
+        let centi_blocks = work * BigUint::from(100_u32) / max(block_work, BigUint::from(1_u32));
+        Self {
+            work: work.to_string(),
+            blocks_at_current_target: centi_blocks.to_string().parse::<f64>().unwrap_or(f64::MAX)
+                / 100.0,
+            expected_hashes: work.to_string().parse().unwrap_or(f64::MAX),
+        }
+    }
+}
+
+impl fmt::Display for WorkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "≈ {:.1} blocks at current difficulty, ~{:.1e} hashes",
+            self.blocks_at_current_target, self.expected_hashes
+        )
+    }
+}
+
+/// Verify that there is enough work added on top of the target block.
+///
+/// On networks where the target of a block says little about the work behind it (see
+/// [has_unreliable_difficulty]), every block is only counted at the minimum difficulty.
+/// Returns the (lower bound of the) work on top of the target block on success.
+pub fn verify_subchain_work(
+    block_height: u32,
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<BigUint, VerifyError> {
+    if block_height > chain_state.block_height {
+        return Err(VerifyError::BlockNotProven {
+            block_height,
+            chain_height: chain_state.block_height,
+        });
+    }
+    let params = Params::new(config.network);
+    let max_target = max_target(&params);
+    let subchain_work = if has_unreliable_difficulty(&params) {
+        let block_span = BigUint::from(chain_state.block_height.saturating_sub(block_height));
+        compute_work_from_target(max_target) * block_span
+    } else {
+        // Difficulty target is readjusted every 2016 blocks
+        // The maximum difficulty re-adjustment step is 4x.
+        // We are rewinding the chain state down to the target block height, assuming worst case scenario
+        // where the difficulty is reducing (target is increasing) by 4x every 2016 blocks,
+        // up to the maximum target of the network
+        let start_epoch = chain_state.block_height / 2016;
+        let end_epoch = block_height / 2016;
+        let mut subchain_work = BigUint::ZERO;
+        let mut target = chain_state.current_target()?;
+
+        for epoch in (end_epoch..=start_epoch).rev() {
+            let start_block = min(2016 * (epoch + 1), chain_state.block_height);
+            let end_block = max(2016 * epoch, block_height);
+            let block_span = BigUint::from(start_block - end_block);
+            let block_work = compute_work_from_target(target.clone());
+            subchain_work += block_work * block_span;
+            target = min(target * BigUint::from(4_u32), max_target.clone());
+        }
+        subchain_work
+    };
+
+    let min_work = required_work(chain_state, config)?;
+    if subchain_work < min_work {
+        return Err(VerifyError::InsufficientWork {
+            work: subchain_work,
+            min_work,
+        });
+    }
+
+    info!(
+        "Subchain work is sufficient: {} >= {}",
+        WorkSummary::new(&subchain_work, chain_state),
+        WorkSummary::new(&min_work, chain_state)
+    );
+    Ok(subchain_work)
+}
+
+/// Verify that there is enough work added on top of the target block, summing the actual
+/// work of the embedded headers between the target block and the proven best block.
+///
+/// The headers must link the target block to the best block of the chain state, so that
+/// their work is anchored to the chain proven by the recursive proof.
+/// Returns the work on top of the target block on success.
+pub fn verify_subchain_headers(
+    block_header: &BlockHeader,
+    block_height: u32,
+    headers: &[BlockHeader],
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<BigUint, VerifyError> {
+    let expected = chain_state.block_height.saturating_sub(block_height) as usize;
+    if headers.len() != expected {
+        return Err(VerifyError::InvalidSubchainHeaders(format!(
+            "expected {} headers between heights {} and {}, got {}",
+            expected,
+            block_height,
+            chain_state.block_height,
+            headers.len()
+        )));
+    }
+
+    let mut subchain_work = BigUint::ZERO;
+    let mut prev_block_hash = block_header.block_hash();
+    for (height, header) in (block_height + 1..).zip(headers) {
+        if header.prev_blockhash != prev_block_hash {
+            return Err(VerifyError::InvalidSubchainHeaders(format!(
+                "header at height {} doesn't extend block {}",
+                height, prev_block_hash
+            )));
+        }
+        header.validate_pow(header.target()).map_err(|e| {
+            VerifyError::InvalidSubchainHeaders(format!(
+                "invalid proof of work at height {}: {}",
+                height, e
+            ))
+        })?;
+        subchain_work += BigUint::from_bytes_be(&header.work().to_be_bytes());
+        prev_block_hash = header.block_hash();
+    }
+    if prev_block_hash != chain_state.best_block_hash {
+        return Err(VerifyError::InvalidSubchainHeaders(format!(
+            "headers end at block {}, proven best block is {}",
+            prev_block_hash, chain_state.best_block_hash
+        )));
+    }
+
+    let min_work = required_work(chain_state, config)?;
+    if subchain_work < min_work {
+        return Err(VerifyError::InsufficientWork {
+            work: subchain_work,
+            min_work,
+        });
+    }
+
+    info!(
+        "Subchain work of {} headers is sufficient: {} >= {}",
+        headers.len(),
+        WorkSummary::new(&subchain_work, chain_state),
+        WorkSummary::new(&min_work, chain_state)
+    );
+    Ok(subchain_work)
+}
+
+/// Check that a block is covered by the proven chain state and buried under enough work to
+/// pass verification (unless the work check is disabled).
+pub fn check_block_depth(
+    block_height: u32,
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<(), VerifyError> {
+    if block_height > chain_state.block_height {
+        return Err(VerifyError::BlockNotProven {
+            block_height,
+            chain_height: chain_state.block_height,
+        });
+    }
+    if config.skip_work {
+        return Ok(());
+    }
+    verify_subchain_work(block_height, chain_state, config).map(|_| ())
+}
+
+/// Minimum work required on top of the target block: `min_confirmations` blocks at the
+/// current target (at the minimum difficulty if the difficulty is unreliable) if set,
+/// otherwise the absolute `min_work`.
+fn required_work(
+    chain_state: &ChainState,
+    config: &VerifierConfig,
+) -> Result<BigUint, VerifyError> {
+    match config.min_confirmations {
+        Some(min_confirmations) => {
+            let params = Params::new(config.network);
+            let target = if has_unreliable_difficulty(&params) {
+                max_target(&params)
+            } else {
+                chain_state.current_target()?
+            };
+            Ok(compute_work_from_target(target) * BigUint::from(min_confirmations))
+        }
+        None => BigUint::from_str(&config.min_work).map_err(|_| {
+            VerifyError::InvalidPolicy(format!(
+                "minimum work must be a decimal number: {}",
+                config.min_work
+            ))
+        }),
+    }
+}
+
+/// Whether the target of the chain state is no indication of the work of past blocks:
+/// testnets and regtest allow blocks at the minimum difficulty, and signet blocks are
+/// authorized by the signet challenge rather than by work.
+pub fn has_unreliable_difficulty(params: &Params) -> bool {
+    params.allow_min_difficulty_blocks
+        || params.no_pow_retargeting
+        || params.network == Network::Signet
+}
+
+/// Easiest target allowed by the network
+fn max_target(params: &Params) -> BigUint {
+    BigUint::from_bytes_be(&params.max_attainable_target.to_be_bytes())
+}
+
+/// Compute the expected work for a single block given the target difficulty.
+fn compute_work_from_target(target: BigUint) -> BigUint {
+    let bytes = target.to_bytes_be();
+    let Some(offset) = 32_usize.checked_sub(bytes.len()) else {
+        // Targets above 2^256 are easier than any hash
+        return BigUint::ZERO;
+    };
+    let mut target = [0; 32];
+    target[offset..].copy_from_slice(&bytes);
+    BigUint::from_bytes_be(&work_from_target(U256::from_be_bytes(target)).to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::block::Version;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+
+    /// Mine a header on top of `prev_blockhash` with the regtest (minimum) difficulty
+    fn mine(prev_blockhash: BlockHash) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: Version::TWO,
+            prev_blockhash,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn test_verify_subchain_headers() {
+        let block_header = mine(BlockHash::all_zeros());
+        let mut headers = vec![mine(block_header.block_hash())];
+        for _ in 0..4 {
+            headers.push(mine(headers.last().unwrap().block_hash()));
+        }
+        let chain_state = ChainState {
+            block_height: 105,
+            total_work: "0".to_string(),
+            best_block_hash: headers.last().unwrap().block_hash(),
+            current_target: "0".to_string(),
+            epoch_start_time: 0,
+            prev_timestamps: vec![],
+        };
+        // Each regtest block carries 2 units of work
+        let config = VerifierConfig {
+            min_work: "10".to_string(),
+            ..Default::default()
+        };
+        assert!(
+            verify_subchain_headers(&block_header, 100, &headers, &chain_state, &config).is_ok()
+        );
+
+        let config = VerifierConfig {
+            min_work: "11".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            verify_subchain_headers(&block_header, 100, &headers, &chain_state, &config),
+            Err(VerifyError::InsufficientWork { .. })
+        ));
+
+        let config = VerifierConfig::default();
+        assert!(matches!(
+            verify_subchain_headers(&block_header, 100, &headers[..4], &chain_state, &config),
+            Err(VerifyError::InvalidSubchainHeaders(_))
+        ));
+        let mut unlinked = headers.clone();
+        unlinked.swap(1, 2);
+        assert!(matches!(
+            verify_subchain_headers(&block_header, 100, &unlinked, &chain_state, &config),
+            Err(VerifyError::InvalidSubchainHeaders(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_subchain_work_network() {
+        // Mainnet-like chain state, 6 blocks on top of the target block
+        let chain_state = ChainState {
+            block_height: 900_006,
+            total_work: "0".to_string(),
+            best_block_hash: BlockHash::all_zeros(),
+            current_target: "1000000000000000000000000000000000000000000000000000".to_string(),
+            epoch_start_time: 0,
+            prev_timestamps: vec![],
+        };
+        let config = VerifierConfig::default();
+        assert!(verify_subchain_work(900_000, &chain_state, &config).is_ok());
+
+        // On testnet any of these blocks may have been mined at the minimum difficulty
+        let config = VerifierConfig {
+            network: Network::Testnet,
+            ..Default::default()
+        };
+        assert!(matches!(
+            verify_subchain_work(900_000, &chain_state, &config),
+            Err(VerifyError::InsufficientWork { .. })
+        ));
+        let config = VerifierConfig {
+            network: Network::Signet,
+            min_confirmations: Some(6),
+            ..Default::default()
+        };
+        assert!(verify_subchain_work(900_000, &chain_state, &config).is_ok());
+        assert!(verify_subchain_work(899_999, &chain_state, &config).is_ok());
+        assert!(matches!(
+            verify_subchain_work(900_001, &chain_state, &config),
+            Err(VerifyError::InsufficientWork { .. })
+        ));
+        assert!(matches!(
+            verify_subchain_work(900_007, &chain_state, &VerifierConfig::default()),
+            Err(VerifyError::BlockNotProven { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_subchain_work_malformed() {
+        let mut chain_state = ChainState {
+            block_height: 900_006,
+            total_work: "0".to_string(),
+            best_block_hash: BlockHash::all_zeros(),
+            current_target: "0x1d00ffff".to_string(),
+            epoch_start_time: 0,
+            prev_timestamps: vec![],
+        };
+        assert!(matches!(
+            verify_subchain_work(900_000, &chain_state, &VerifierConfig::default()),
+            Err(VerifyError::InvalidChainState(_))
+        ));
+
+        chain_state.current_target = "1000000000000000000000000000000000000000000000000000".into();
+        let config = VerifierConfig {
+            min_work: "six blocks".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            verify_subchain_work(900_000, &chain_state, &config),
+            Err(VerifyError::InvalidPolicy(_))
+        ));
+    }
+
+    #[test]
+    fn test_work_summary() {
+        let chain_state = ChainState {
+            block_height: 0,
+            total_work: "0".to_string(),
+            best_block_hash: BlockHash::all_zeros(),
+            current_target: "1000000000000000000000000000000000000000000000000000".to_string(),
+            epoch_start_time: 0,
+            prev_timestamps: vec![],
+        };
+        let block_work =
+            compute_work_from_target(BigUint::from_str(&chain_state.current_target).unwrap());
+        let work = block_work * BigUint::from(62_u32) / BigUint::from(10_u32);
+        let summary = WorkSummary::new(&work, &chain_state);
+        assert_eq!(summary.blocks_at_current_target, 6.2);
+        assert_eq!(
+            summary.to_string(),
+            "≈ 6.2 blocks at current difficulty, ~7.2e26 hashes"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! WebAssembly bindings of the Raito SPV proof verifier, so that web wallets can verify
+//! compressed SPV proofs client-side without shipping a native binary.
+//!
+//! The verifier is built without threads: the STARK verification runs on the calling thread,
+//! so it should be called from a web worker to keep the page responsive.
+//!
+//! ```js
+//! import init, { verifyProof } from "raito-spv-wasm";
+//!
+//! await init();
+//! const proof = new Uint8Array(await (await fetch("tx_proof.bin.bz2")).arrayBuffer());
+//! const report = await verifyProof(proof, { minConfirmations: 6 });
+//! console.log(`${report.txid} has ${report.confirmations} confirmations`);
+//! ```
+
+use bitcoin::Network;
+use raito_spv_verifier::decode_proof;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Verification options accepted from JavaScript, all optional: the camelCase form of
+/// [raito_spv_verifier::VerifyOptions]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct VerifyOptions {
+    /// Network the proof is generated for (`bitcoin`, `testnet4`, `signet` or `regtest`)
+    pub network: Option<Network>,
+    /// Minimum cumulative work required on top of the target block (decimal string)
+    pub min_work: Option<String>,
+    /// Minimum work required on top of the target block in blocks at the current target
+    pub min_confirmations: Option<u32>,
+    /// Maximum age in seconds of the proven chain state
+    pub max_proof_age: Option<u64>,
+    /// Maximum age in seconds of the block containing the transaction
+    pub max_block_age: Option<u64>,
+}
+
+impl From<VerifyOptions> for raito_spv_verifier::VerifyOptions {
+    fn from(options: VerifyOptions) -> Self {
+        Self {
+            network: options.network,
+            min_work: options.min_work,
+            min_confirmations: options.min_confirmations,
+            max_proof_age: options.max_proof_age,
+            max_block_age: options.max_block_age,
+        }
+    }
+}
+
+/// Verify a compressed SPV proof, given as the bytes of a proof file (bzip2 compressed bincode
+/// or JSON).
+///
+/// Resolves to the verification report, or rejects with the reason the proof is invalid.
+#[wasm_bindgen(js_name = verifyProof)]
+pub async fn verify_proof(proof: Vec<u8>, options: JsValue) -> Result<JsValue, JsError> {
+    let options: VerifyOptions = if options.is_undefined() || options.is_null() {
+        VerifyOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options)?
+    };
+    let config = raito_spv_verifier::VerifyOptions::from(options)
+        .into_config()
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    let proof = decode_proof(&proof)?;
+    let report = raito_spv_verifier::verify_proof(proof, &config).await?;
+    Ok(report.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use raito_spv_verifier::VerifierConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_verify_options() {
+        let options: VerifyOptions = serde_json::from_str(
+            r#"{"network": "signet", "minConfirmations": 6, "maxProofAge": 3600}"#,
+        )
+        .unwrap();
+        let config = raito_spv_verifier::VerifyOptions::from(options)
+            .into_config()
+            .unwrap();
+        assert_eq!(config.network, Network::Signet);
+        assert_eq!(config.min_confirmations, Some(6));
+        assert_eq!(config.max_proof_age, Some(3600));
+        assert_eq!(config.min_work, VerifierConfig::default().min_work);
+
+        assert!(serde_json::from_str::<VerifyOptions>(r#"{"min_confirmations": 6}"#).is_err());
+        let invalid: VerifyOptions = serde_json::from_str(r#"{"minWork": "lots"}"#).unwrap();
+        assert!(raito_spv_verifier::VerifyOptions::from(invalid)
+            .into_config()
+            .is_err());
+    }
+}
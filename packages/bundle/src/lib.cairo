@@ -0,0 +1,276 @@
+//! Recursive compression of a proof bundle: verifies the proof of a chain state and the
+//! inclusion of a batch of transactions in the blocks of its block MMR, so that a single proof
+//! attests that all the transactions are in the chain with that chain state.
+
+use consensus::types::block::{BlockHash, Header};
+use consensus::types::chain_state::{ChainState, ChainStateHashTrait};
+use core::box::BoxImpl;
+use stwo_cairo_air::{CairoProof, VerificationOutput, get_verification_output, verify_cairo};
+use utils::bit_shifts::pow2;
+use utils::blake2s_hasher::{
+    Blake2sDigest, Blake2sDigestFromU256, Blake2sDigestIntoU256, Blake2sDigestPartialEq,
+    blake2s_hash_pair,
+};
+use utils::double_sha256::double_sha256_parent;
+use utils::hash::Digest;
+use utils::mmr::{MMR, MMRTrait};
+
+#[derive(Drop, Serde)]
+struct Args {
+    /// Chain state the transactions are verified against.
+    chain_state: ChainState,
+    /// Merkle Mountain Range of the block hashes up to the chain state.
+    block_mmr: MMR,
+    /// Transactions to verify.
+    transactions: Array<TransactionInclusion>,
+    /// Proof of the chain state, generated by the assumevalid program.
+    chain_state_proof: CairoProof,
+}
+
+/// Inclusion of a transaction in a block of the block MMR.
+#[derive(Drop, Serde)]
+struct TransactionInclusion {
+    /// Transaction ID, in internal byte order.
+    txid: Digest,
+    /// Siblings of the transaction from its leaf up to the Merkle root of the block.
+    merkle_branch: Span<Digest>,
+    /// Position of the transaction in the block.
+    tx_index: u32,
+    /// Header of the block containing the transaction.
+    header: Header,
+    /// Hash of the previous block.
+    prev_block_hash: Digest,
+    /// Merkle root of the transactions of the block.
+    merkle_root: Digest,
+    /// Height of the block, which is its leaf index in the block MMR.
+    block_height: u32,
+    /// Siblings of the block header digest from its leaf up to its peak in the block MMR.
+    mmr_siblings: Span<u256>,
+}
+
+#[derive(Drop, Serde)]
+struct Result {
+    /// Hash of the chain state the transactions were verified against.
+    chain_state_hash: u256,
+    /// Hash of the roots of the Merkle Mountain Range of the block hashes.
+    block_mmr_hash: u256,
+    /// Hash of the bootloader program the chain state proof was generated with.
+    bootloader_hash: felt252,
+    /// Hash of the program the chain state proof was generated with.
+    program_hash: felt252,
+    /// Number of transactions verified.
+    transaction_count: u32,
+    /// Commitment to the verified transaction IDs, in order: starting from zero, the
+    /// commitment is hashed with each transaction ID as a pair of Blake2s digests.
+    txids_hash: u256,
+}
+
+/// Output of the assumevalid program, as in the assumevalid package.
+#[derive(Drop, Serde)]
+struct ChainStateResult {
+    chain_state_hash: u256,
+    block_mmr_hash: u256,
+    bootloader_hash: felt252,
+    program_hash: felt252,
+}
+
+#[derive(Drop, Serde)]
+struct BootloaderOutput {
+    /// Number of tasks (must be always 1)
+    n_tasks: usize,
+    /// Size of the task output in felts (including the size field)
+    task_output_size: usize,
+    /// Hash of the payload program.
+    task_program_hash: felt252,
+    /// Output of the payload program.
+    task_result: ChainStateResult,
+}
+
+#[executable]
+fn main(args: Args) -> Result {
+    let Args { chain_state, block_mmr, transactions, chain_state_proof } = args;
+    assert(transactions.len() > 0, 'No transactions');
+
+    let chain_state_result = get_chain_state_result(chain_state_proof);
+    // Check that the provided chain state and block MMR are the proven ones
+    assert(
+        chain_state_result.chain_state_hash == chain_state.blake2s_digest().into(),
+        'Invalid chain state',
+    );
+    assert(
+        chain_state_result.block_mmr_hash == block_mmr.blake2s_digest().into(),
+        'Invalid block MMR hash',
+    );
+
+    // The block MMR has a leaf per block up to the chain state
+    let leaf_count = chain_state.block_height + 1;
+    let mut txids_hash: Blake2sDigest = BoxImpl::new([0; 8]);
+    let mut transaction_count: u32 = 0;
+    for transaction in transactions {
+        let TransactionInclusion {
+            txid,
+            merkle_branch,
+            tx_index,
+            header,
+            prev_block_hash,
+            merkle_root,
+            block_height,
+            mmr_siblings,
+        } = transaction;
+
+        assert(
+            compute_merkle_root(txid, merkle_branch, tx_index) == merkle_root,
+            'Transaction not in block',
+        );
+        assert(block_height < leaf_count, 'Block above chain state');
+        let leaf = header.blake2s_digest(prev_block_hash, merkle_root);
+        verify_block_inclusion(@block_mmr, leaf, block_height, leaf_count, mmr_siblings);
+
+        txids_hash = commit_txid(txids_hash, txid);
+        transaction_count += 1;
+    }
+
+    Result {
+        chain_state_hash: chain_state_result.chain_state_hash,
+        block_mmr_hash: chain_state_result.block_mmr_hash,
+        bootloader_hash: chain_state_result.bootloader_hash,
+        program_hash: chain_state_result.program_hash,
+        transaction_count,
+        txids_hash: txids_hash.into(),
+    }
+}
+
+/// Verify the chain state proof, extract and validate its task output.
+fn get_chain_state_result(proof: CairoProof) -> ChainStateResult {
+    let VerificationOutput { program_hash, output } = get_verification_output(proof: @proof);
+
+    // Verify the proof
+    match verify_cairo(proof) {
+        Ok(_) => {},
+        Err(e) => panic!("Invalid proof: {:?}", e),
+    }
+
+    // Deserialize the bootloader output
+    let mut serialized_bootloader_output = output.span();
+    let BootloaderOutput {
+        n_tasks, task_output_size, task_program_hash, task_result,
+    }: BootloaderOutput =
+        Serde::deserialize(ref serialized_bootloader_output).expect('Invalid bootloader output');
+
+    // Check that the bootloader output contains exactly one task
+    assert(serialized_bootloader_output.is_empty(), 'Output too long');
+    assert(n_tasks == 1, 'Unexpected number of tasks');
+    assert(task_output_size == 8, 'Unexpected task output size');
+
+    // Check that the chain state proof was generated by the same programs as the proofs it
+    // recursively verified, unless it is the genesis one
+    if task_result.bootloader_hash != 0 {
+        assert(task_result.bootloader_hash == program_hash, 'Bootloader hash mismatch')
+    }
+    if task_result.program_hash != 0 {
+        assert(task_result.program_hash == task_program_hash, 'Program hash mismatch');
+    }
+
+    ChainStateResult {
+        chain_state_hash: task_result.chain_state_hash,
+        block_mmr_hash: task_result.block_mmr_hash,
+        bootloader_hash: program_hash,
+        program_hash: task_program_hash,
+    }
+}
+
+/// Hash a transaction ID into the commitment to the verified transaction IDs, its words as a
+/// Blake2s digest.
+fn commit_txid(txids_hash: Blake2sDigest, txid: Digest) -> Blake2sDigest {
+    blake2s_hash_pair(txids_hash, BoxImpl::new(txid.value))
+}
+
+/// Compute the Merkle root of a block from one of its transactions and its Merkle branch.
+fn compute_merkle_root(txid: Digest, merkle_branch: Span<Digest>, tx_index: u32) -> Digest {
+    let mut node = txid;
+    let mut index = tx_index;
+    for sibling in merkle_branch {
+        node =
+            if index % 2 == 0 {
+                double_sha256_parent(@node, sibling)
+            } else {
+                double_sha256_parent(sibling, @node)
+            };
+        index /= 2;
+    }
+    assert(index == 0, 'Invalid transaction index');
+    node
+}
+
+/// Check that a block header digest is the leaf `leaf_index` of the block MMR of `leaf_count`
+/// leaves, by hashing it up to its peak with its siblings.
+fn verify_block_inclusion(
+    block_mmr: @MMR, leaf: Blake2sDigest, leaf_index: u32, leaf_count: u32, siblings: Span<u256>,
+) {
+    // The leaf is in the mountain as high as its number of siblings, after the leaves of the
+    // higher mountains
+    let height = siblings.len();
+    let roots = *block_mmr.roots;
+    assert(height < roots.len(), 'Invalid MMR proof length');
+    let size = pow2(height);
+    let (leaf_count, leaf_index): (u64, u64) = (leaf_count.into(), leaf_index.into());
+    assert((leaf_count / size) % 2 == 1, 'Invalid MMR proof length');
+    let offset = leaf_count - leaf_count % (2 * size);
+    assert(leaf_index >= offset && leaf_index < offset + size, 'Invalid MMR proof length');
+
+    let mut node = leaf;
+    let mut index = leaf_index - offset;
+    for sibling in siblings {
+        let sibling: Blake2sDigest = (*sibling).into();
+        node =
+            if index % 2 == 0 {
+                blake2s_hash_pair(node, sibling)
+            } else {
+                blake2s_hash_pair(sibling, node)
+            };
+        index /= 2;
+    }
+    match *roots.at(height) {
+        Some(peak) => assert(peak == node, 'Block not in MMR'),
+        None => panic!("Missing MMR peak"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_block_inclusion() {
+        let a: Blake2sDigest = 0x01_u256.into();
+        let b: Blake2sDigest = 0x02_u256.into();
+        let c: Blake2sDigest = 0x03_u256.into();
+        let mmr: MMR = Default::default();
+        let mmr = mmr.add(a).add(b).add(c);
+
+        verify_block_inclusion(@mmr, a, 0, 3, array![b.into()].span());
+        verify_block_inclusion(@mmr, b, 1, 3, array![a.into()].span());
+        verify_block_inclusion(@mmr, c, 2, 3, array![].span());
+    }
+
+    #[test]
+    fn test_commit_txid() {
+        // Same commitment as in the `compress` command of raito-spv-client
+        let a = Digest { value: [1, 2, 3, 4, 5, 6, 7, 8] };
+        let b = Digest { value: [9, 10, 11, 12, 13, 14, 15, 16] };
+        let txids_hash = commit_txid(commit_txid(BoxImpl::new([0; 8]), a), b);
+        let res: u256 = txids_hash.into();
+        assert_eq!(res, 0x8e6aa2a22f27840dbe007a623961db5089f7b1bfa0c5086f91011868f3869717);
+    }
+
+    #[test]
+    #[should_panic(expected: 'Block not in MMR')]
+    fn test_verify_block_inclusion_wrong_side() {
+        let a: Blake2sDigest = 0x01_u256.into();
+        let b: Blake2sDigest = 0x02_u256.into();
+        let mmr: MMR = Default::default();
+        let mmr = mmr.add(a).add(b);
+
+        verify_block_inclusion(@mmr, b, 0, 2, array![a.into()].span());
+    }
+}
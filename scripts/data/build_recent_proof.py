@@ -7,6 +7,7 @@ import logging
 import time
 import datetime
 import gzip
+import hashlib
 from pathlib import Path
 from typing import Optional, Dict, Any
 import traceback
@@ -139,6 +140,10 @@ def upload_to_gcs(proof_file: Path, chainstate_data: Dict[str, Any]) -> bool:
         # Upload directly as recent_proof
         recent_proof_blob = bucket.blob("recent_proof")
         recent_proof_blob.content_encoding = "gzip"
+        # Served as the x-goog-meta-sha256 header, clients check the compressed bytes against it
+        recent_proof_blob.metadata = {
+            "sha256": hashlib.sha256(compressed_data).hexdigest()
+        }
         recent_proof_blob.upload_from_string(
             compressed_data, content_type="application/json"
         )